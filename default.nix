@@ -39,7 +39,6 @@ let
     cargo-nightly
     xargo
     wasmtime
-    rust-bindgen
     rustfmt
   ];
 
@@ -90,6 +89,9 @@ let commonBuildInputs = pkgs:
     pkgs.ocamlPackages.ppx_tools_versioned
     pkgs.ocamlPackages.obelisk
     pkgs.ocamlPackages.uucp
+    pkgs.ocamlPackages.uunf
+    pkgs.ocamlPackages.uuseg
+    pkgs.ocamlPackages.digestif
     pkgs.perl
     pkgs.removeReferencesTo
   ]; in
@@ -192,7 +194,6 @@ rec {
         __END__
 
         ${llvmEnv}
-        export TOMMATHSRC=${nixpkgs.sources.libtommath}
         export MUSLSRC=${nixpkgs.sources.musl-wasi}/libc-top-half/musl
         export MUSL_WASI_SYSROOT=${musl-wasi-sysroot}
 
@@ -498,6 +499,40 @@ rec {
     installPhase = "touch $out";
   };
 
+  # Builds the benchmarks and the fuzz targets of the RTS, without running
+  # them. These crates have their own lock files, so their dependencies are
+  # vendored separately from the ones of `rts`.
+  check-rts-bench-fuzz =
+    let
+      build = crate:
+        let rustDeps = nixpkgs.rustPlatform-nightly.importCargoLock {
+          lockFile = ./rts + "/${crate}/Cargo.lock";
+        }; in ''
+          cat > $CARGO_HOME/config <<__END__
+            [source."crates-io"]
+            "replace-with" = "vendored-sources"
+
+            [source."vendored-sources"]
+            "directory" = "${rustDeps}"
+          __END__
+          (cd ${crate} && cargo build --frozen --all-targets)
+        '';
+    in
+    stdenv.mkDerivation {
+      name = "check-rts-bench-fuzz";
+      src = subpath ./rts;
+      buildInputs = rtsBuildInputs;
+      doCheck = true;
+      phases = "unpackPhase checkPhase installPhase";
+      checkPhase = ''
+        export CARGO_HOME=$PWD/cargo-home
+        mkdir -p $CARGO_HOME
+        ${build "motoko-rts-bench"}
+        ${build "motoko-rts-fuzz"}
+      '';
+      installPhase = "touch $out";
+    };
+
   base-src = stdenv.mkDerivation {
     name = "base-src";
     phases = "unpackPhase installPhase";
@@ -617,6 +652,7 @@ rec {
       shell
       check-formatting
       check-rts-formatting
+      check-rts-bench-fuzz
       check-generated
       check-grammar
       check-error-codes
@@ -658,7 +694,6 @@ rec {
       unset XDG_DATA_DIRS
     '';
     ESM=nixpkgs.sources.esm;
-    TOMMATHSRC = nixpkgs.sources.libtommath;
     MUSLSRC = "${nixpkgs.sources.musl-wasi}/libc-top-half/musl";
     MUSL_WASI_SYSROOT = musl-wasi-sysroot;
     LOCALE_ARCHIVE = nixpkgs.lib.optionalString stdenv.isLinux "${nixpkgs.glibcLocales}/lib/locale/locale-archive";
@@ -0,0 +1,83 @@
+#!/usr/bin/env python3
+"""
+Generates motoko-rts/src/char_tables.rs, the Unicode properties of characters
+(motoko-rts/src/char.rs), from the Unicode data of the `regex` Python package
+(`pip install regex`).
+
+Run `make unicode-tables` in rts/ after updating the package (and so Unicode) version, and
+`UNICODE_VERSION` in the Makefile.
+"""
+
+import sys
+
+import regex
+
+OUT = "motoko-rts/src/char_tables.rs"
+
+# Unicode version of the `regex` package
+VERSION = "17.0.0"
+
+# General categories, in the order of their numbers in char.rs and prim.mo
+CATEGORIES = [
+    "Lu", "Ll", "Lt", "Lm", "Lo", "Mn", "Mc", "Me", "Nd", "Nl", "No", "Pc", "Pd", "Ps", "Pe",
+    "Pi", "Pf", "Po", "Sm", "Sc", "Sk", "So", "Zs", "Zl", "Zp", "Cc", "Cf", "Cs", "Co", "Cn",
+]
+
+# Flags, as in char.rs
+WHITE_SPACE = 0x20
+ALPHABETIC = 0x40
+UPPERCASE = 0x80
+LOWERCASE = 0x100
+CASE_IGNORABLE = 0x200
+
+# Bits of the properties in the ranges
+PROPERTY_BITS = 10
+
+PER_LINE = 8
+
+
+def matching(pattern, chars):
+    """The characters matching the pattern"""
+    result = set()
+    for m in regex.finditer(pattern + "+", chars):
+        result.update(range(ord(m.group()[0]), ord(m.group()[-1]) + 1))
+    return result
+
+
+def properties():
+    """The properties of every character, category and flags"""
+    chars = "".join(chr(c) for c in range(0x110000))
+    props = [CATEGORIES.index("Cn")] * 0x110000
+    for (i, cat) in enumerate(CATEGORIES[:-1]):
+        for c in matching(r"\p{gc=%s}" % cat, chars):
+            props[c] = i
+    for (flag, prop) in [(WHITE_SPACE, "White_Space"), (ALPHABETIC, "Alphabetic"),
+                         (UPPERCASE, "Uppercase"), (LOWERCASE, "Lowercase"),
+                         (CASE_IGNORABLE, "Case_Ignorable")]:
+        for c in matching(r"\p{%s}" % prop, chars):
+            props[c] |= flag
+    return props
+
+
+def main():
+    props = properties()
+    starts = [c for c in range(0x110000) if c == 0 or props[c] != props[c - 1]]
+    with open(OUT, "w") as out:
+        out.write("//! Unicode %s tables of the properties of characters (see `char.rs`).\n"
+                  % VERSION)
+        out.write("//!\n")
+        out.write("//! Generated by `rts/gen-char-tables.py`, do not edit.\n")
+        out.write("\n")
+        out.write("/// Ranges of the characters with the same properties, up to the start of the next range,\n")
+        out.write("/// as `start << %d | properties`. The properties are encoded as in `char.rs`.\n"
+                  % PROPERTY_BITS)
+        out.write("#[rustfmt::skip]\n")
+        out.write("pub(crate) static CHAR_PROPERTIES: [u32; %d] = [\n" % len(starts))
+        for i in range(0, len(starts), PER_LINE):
+            chunk = starts[i:i + PER_LINE]
+            out.write("    " + " ".join("0x%08X," % (c << PROPERTY_BITS | props[c]) for c in chunk) + "\n")
+        out.write("];\n")
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,63 @@
+#!/usr/bin/env python3
+"""
+Generates motoko-rts/src/float_tables.rs, the powers of five used by the parsing of floats
+(motoko-rts/src/float_parse.rs), as in the `fast_float` library of Daniel Lemire.
+
+Run `make float-tables` in rts/.
+"""
+
+import sys
+
+OUT = "motoko-rts/src/float_tables.rs"
+
+SMALLEST_POWER = -342
+LARGEST_POWER = 308
+
+PER_LINE = 2
+
+
+def power_of_five(q):
+    """The 128 most significant bits of 5^q, truncated for q >= 0 and rounded up for q < 0"""
+    if q >= 0:
+        power5 = 5 ** q
+        while power5 < 1 << 127:
+            power5 *= 2
+        while power5 >= 1 << 128:
+            power5 //= 2
+        return power5
+    power5 = 5 ** -q
+    z = power5.bit_length()
+    b = 2 * z + 2 * 64 if q < -27 else z + 127
+    c = 2 ** b // power5 + 1
+    while c >= 1 << 128:
+        c //= 2
+    return c
+
+
+def main():
+    rows = [power_of_five(q) for q in range(SMALLEST_POWER, LARGEST_POWER + 1)]
+    with open(OUT, "w") as out:
+        out.write("//! Powers of five for the parsing of floats (see `float_parse.rs`).\n")
+        out.write("//!\n")
+        out.write("//! Generated by `rts/gen-float-tables.py`, do not edit.\n")
+        out.write("\n")
+        out.write("/// Smallest power of ten (and five) in `POWERS_OF_FIVE`\n")
+        out.write("pub(crate) const SMALLEST_POWER: i32 = %d;\n" % SMALLEST_POWER)
+        out.write("\n")
+        out.write("/// Largest power of ten (and five) in `POWERS_OF_FIVE`\n")
+        out.write("pub(crate) const LARGEST_POWER: i32 = %d;\n" % LARGEST_POWER)
+        out.write("\n")
+        out.write("/// The 128 most significant bits `(high, low)` of the powers of five from\n")
+        out.write("/// `SMALLEST_POWER` to `LARGEST_POWER`, truncated for non-negative powers, rounded up\n")
+        out.write("/// for negative powers\n")
+        out.write("#[rustfmt::skip]\n")
+        out.write("pub(crate) static POWERS_OF_FIVE: [(u64, u64); %d] = [\n" % len(rows))
+        for i in range(0, len(rows), PER_LINE):
+            chunk = rows[i:i + PER_LINE]
+            out.write("    " + " ".join("(0x%016x, 0x%016x)," % (r >> 64, r & (2 ** 64 - 1))
+                                        for r in chunk) + "\n")
+        out.write("];\n")
+
+
+if __name__ == "__main__":
+    sys.exit(main())
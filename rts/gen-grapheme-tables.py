@@ -0,0 +1,101 @@
+#!/usr/bin/env python3
+"""
+Generates motoko-rts/src/grapheme_tables.rs, the Unicode tables used by the segmentation of
+texts into grapheme clusters (motoko-rts/src/grapheme.rs), from the tables of the `wcwidth`
+Python package (`pip install wcwidth`), which are generated from the Unicode Character Database.
+
+Run `make unicode-tables` in rts/ after updating the package (and so Unicode) version.
+"""
+
+import re
+import sys
+
+from wcwidth import table_grapheme as t
+
+OUT = "motoko-rts/src/grapheme_tables.rs"
+
+# Hangul syllables are LV or LVT, which is computed
+HANGUL = range(0xAC00, 0xAC00 + 11172)
+
+PER_LINE = 4
+
+# Grapheme_Cluster_Break values, as in grapheme.rs
+GCB = [
+    ("CR", t.GRAPHEME_CR),
+    ("LF", t.GRAPHEME_LF),
+    ("CONTROL", t.GRAPHEME_CONTROL),
+    ("EXTEND", t.GRAPHEME_EXTEND),
+    ("ZWJ", t.GRAPHEME_ZWJ),
+    ("REGIONAL_INDICATOR", t.GRAPHEME_REGIONAL_INDICATOR),
+    ("PREPEND", t.GRAPHEME_PREPEND),
+    ("SPACING_MARK", t.GRAPHEME_SPACINGMARK),
+    ("L", t.GRAPHEME_L),
+    ("V", t.GRAPHEME_V),
+    ("T", t.GRAPHEME_T),
+]
+
+# Flags, as in grapheme.rs
+EXTENDED_PICTOGRAPHIC = 0x10
+INCB_CONSONANT = 0x20
+INCB_EXTEND = 0x40
+INCB_LINKER = 0x60
+
+
+def properties():
+    """Properties of the characters, with the Grapheme_Cluster_Break value (1-based index in
+    `GCB`) in the low four bits and the flags above"""
+    props = [0] * 0x110000
+
+    def add(ranges, value):
+        for (first, last) in ranges:
+            for c in range(first, last + 1):
+                props[c] |= value
+
+    for (i, (_, ranges)) in enumerate(GCB):
+        add(ranges, i + 1)
+    add(t.EXTENDED_PICTOGRAPHIC, EXTENDED_PICTOGRAPHIC)
+    add(t.INCB_CONSONANT, INCB_CONSONANT)
+    add(t.INCB_EXTEND, INCB_EXTEND)
+    add(t.INCB_LINKER, INCB_LINKER)
+
+    for c in HANGUL:
+        props[c] = 0
+
+    return props
+
+
+def ranges(props):
+    """Ranges (first, last, properties) of the characters with properties"""
+    result = []
+    for (c, p) in enumerate(props):
+        if p == 0:
+            continue
+        if result and result[-1][1] == c - 1 and result[-1][2] == p:
+            result[-1] = (result[-1][0], c, p)
+        else:
+            result.append((c, c, p))
+    return result
+
+
+def main():
+    version = re.search(r"version (\d+\.\d+\.\d+)", t.__doc__).group(1)
+    rows = ranges(properties())
+    with open(OUT, "w") as out:
+        out.write("//! Unicode %s tables for the segmentation of texts into grapheme clusters (see\n"
+                  % version)
+        out.write("//! `grapheme.rs`).\n")
+        out.write("//!\n")
+        out.write("//! Generated by `rts/gen-grapheme-tables.py`, do not edit.\n")
+        out.write("\n")
+        out.write("/// Ranges `(first, last, properties)` of the characters with a Grapheme_Cluster_Break value\n")
+        out.write("/// or flags, other than Hangul syllables. The properties are encoded as in `grapheme.rs`.\n")
+        out.write("#[rustfmt::skip]\n")
+        out.write("pub(crate) static GRAPHEME_PROPERTIES: [(u32, u32, u8); %d] = [\n" % len(rows))
+        for i in range(0, len(rows), PER_LINE):
+            chunk = rows[i:i + PER_LINE]
+            out.write("    " + " ".join("(0x%X, 0x%X, 0x%02X)," % row for row in chunk) + "\n")
+        out.write("];\n")
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,206 @@
+#!/usr/bin/env python3
+"""
+Generates motoko-rts/src/unicode_tables.rs, the Unicode tables used by the
+normalization of texts (motoko-rts/src/normalize.rs) and the case mappings of
+characters and texts (motoko-rts/src/char.rs, motoko-rts/src/text.rs), from the
+files of the Unicode Character Database in the directory given as argument:
+
+    UnicodeData.txt
+    DerivedNormalizationProps.txt
+    CaseFolding.txt
+    SpecialCasing.txt
+
+Run `make unicode-tables` in rts/, which downloads the files of `UNICODE_VERSION` in the
+Makefile, the version of the other Unicode tables.
+"""
+
+import os
+import re
+import sys
+
+OUT = "motoko-rts/src/unicode_tables.rs"
+
+# Hangul syllables are decomposed and composed algorithmically
+HANGUL = range(0xAC00, 0xAC00 + 11172)
+
+PER_LINE = 4
+
+
+def lines(ucd, name):
+    """The fields of the data lines of a UCD file, without comments"""
+    with open(os.path.join(ucd, name), encoding="utf-8") as f:
+        for line in f:
+            line = line.split("#", 1)[0].strip()
+            if line:
+                yield [field.strip() for field in line.split(";")]
+
+
+def version(ucd):
+    """The Unicode version of the files, from the headers of the files that have one"""
+    versions = set()
+    for name in ["DerivedNormalizationProps.txt", "CaseFolding.txt", "SpecialCasing.txt"]:
+        with open(os.path.join(ucd, name), encoding="utf-8") as f:
+            m = re.match(r"# %s-(\d+\.\d+\.\d+)\.txt" % name[:-4], f.readline())
+            assert m, name
+            versions.add(m.group(1))
+    assert len(versions) == 1, versions
+    return versions.pop()
+
+
+def code_points(field):
+    return [int(c, 16) for c in field.split()]
+
+
+def unicode_data(ucd):
+    """The fields of UnicodeData.txt by character. Ranges (`<..., First>` and `<..., Last>`) have
+    no decompositions or case mappings, they are left out."""
+    return {int(fields[0], 16): fields for fields in lines(ucd, "UnicodeData.txt")}
+
+
+def combining_classes(data):
+    """Ranges (first, last, class) of the characters with a non-zero class"""
+    ranges = []
+    for c in sorted(data):
+        cc = int(data[c][3])
+        if cc == 0:
+            continue
+        if ranges and ranges[-1][1] == c - 1 and ranges[-1][2] == cc:
+            ranges[-1] = (ranges[-1][0], c, cc)
+        else:
+            ranges.append((c, c, cc))
+    return ranges
+
+
+def decompositions(data):
+    """Canonical decompositions (char, first, second), second is 0 for singletons"""
+    result = []
+    for c in sorted(data):
+        d = data[c][5]
+        if c in HANGUL or d == "" or d.startswith("<"):
+            continue
+        parts = code_points(d)
+        assert 1 <= len(parts) <= 2, hex(c)
+        result.append((c, parts[0], parts[1] if len(parts) == 2 else 0))
+    return result
+
+
+def composition_exclusions(ucd):
+    """The characters with the property Full_Composition_Exclusion: singletons, decompositions
+    starting with a non-starter, and the composition exclusions"""
+    result = set()
+    for fields in lines(ucd, "DerivedNormalizationProps.txt"):
+        if fields[1] != "Full_Composition_Exclusion":
+            continue
+        first, _, last = fields[0].partition("..")
+        result.update(range(int(first, 16), int(last or first, 16) + 1))
+    return result
+
+
+def compositions(decomps, exclusions):
+    """Primary composites (first, second, composite), sorted by the pairs"""
+    return sorted((first, second, c) for (c, first, second) in decomps
+                  if second != 0 and c not in exclusions)
+
+
+def simple_mapping(data, field):
+    """The simple case mapping of UnicodeData.txt in the field (12 uppercase, 13 lowercase)"""
+    return {c: int(fields[field], 16) for (c, fields) in data.items() if fields[field]}
+
+
+def simple_folding(ucd):
+    """The simple case folding, the statuses C and S of CaseFolding.txt"""
+    return {int(fields[0], 16): int(fields[2], 16)
+            for fields in lines(ucd, "CaseFolding.txt") if fields[1] in ("C", "S")}
+
+
+def special_casing(ucd, field, simple):
+    """The unconditional full case mappings of SpecialCasing.txt (field 1 lowercase, 3 uppercase)
+    that differ from the simple mapping, as (char, first, second, third), 0 for missing chars.
+    The conditional mappings (Final_Sigma, and the ones of languages) are left out."""
+    result = []
+    for fields in lines(ucd, "SpecialCasing.txt"):
+        if len(fields) > 4 and fields[4] != "":
+            continue
+        c = int(fields[0], 16)
+        mapped = code_points(fields[field])
+        if mapped == [simple.get(c, c)]:
+            continue
+        assert len(mapped) <= 3, hex(c)
+        result.append(tuple([c] + mapped + [0] * (3 - len(mapped))))
+    return sorted(result)
+
+
+def case_runs(mapping):
+    """Runs (first, last, delta, stride) of the characters mapped to another one, which is `delta`
+    away. The characters of a run are every `stride`th character from `first` to `last`, the ones
+    in between are mapped to themselves."""
+    runs = []
+    for c in sorted(mapping):
+        delta = mapping[c] - c
+        if delta == 0:
+            continue
+        if runs:
+            (first, last, d, stride) = runs[-1]
+            if d == delta and (c - last == stride or (first == last and c - last == 2)):
+                runs[-1] = (first, c, d, c - last)
+                continue
+        runs.append((c, c, delta, 1))
+    return runs
+
+
+def table(out, doc, name, ty, rows):
+    out.write("\n")
+    for line in doc:
+        out.write("/// %s\n" % line)
+    out.write("#[rustfmt::skip]\n")
+    out.write("pub(crate) static %s: [%s; %d] = [\n" % (name, ty, len(rows)))
+    for i in range(0, len(rows), PER_LINE):
+        chunk = rows[i:i + PER_LINE]
+        out.write("    " + " ".join(
+            "(%s)," % ", ".join("0x%X" % x if j < 2 or ty.endswith("u32)") else str(x)
+                                for (j, x) in enumerate(row))
+            for row in chunk) + "\n")
+    out.write("];\n")
+
+
+def main():
+    ucd = sys.argv[1]
+    data = unicode_data(ucd)
+    decomps = decompositions(data)
+    upper = simple_mapping(data, 12)
+    lower = simple_mapping(data, 13)
+    with open(OUT, "w") as out:
+        out.write("//! Unicode %s tables for the normalization of texts (see `normalize.rs`) and the\n"
+                  % version(ucd))
+        out.write("//! case mappings of characters and texts (see `char.rs` and `text.rs`).\n")
+        out.write("//!\n")
+        out.write("//! Generated by `rts/gen-unicode-tables.py`, do not edit.\n")
+        table(out,
+              ["Canonical combining classes: ranges `(first, last, class)` of the characters with a",
+               "non-zero class"],
+              "COMBINING_CLASSES", "(u32, u32, u8)", combining_classes(data))
+        table(out,
+              ["Canonical decompositions `(char, first, second)`, other than Hangul syllables. `second`",
+               "is 0 for singletons."],
+              "DECOMPOSITIONS", "(u32, u32, u32)", decomps)
+        table(out,
+              ["Canonical compositions `(first, second, composite)` of the primary composites other",
+               "than Hangul syllables, sorted by the pairs"],
+              "COMPOSITIONS", "(u32, u32, u32)", compositions(decomps, composition_exclusions(ucd)))
+        for (name, doc, mapping) in [("UPPERCASE", "uppercase mappings", upper),
+                                     ("LOWERCASE", "lowercase mappings", lower),
+                                     ("CASE_FOLDING", "case folding", simple_folding(ucd))]:
+            table(out,
+                  ["Simple %s: runs `(first, last, delta, stride)` of every `stride`th character" % doc,
+                   "from `first` to `last` mapped to the character `delta` away"],
+                  name, "(u32, u32, i32, u8)", case_runs(mapping))
+        for (name, doc, field, simple) in [("FULL_UPPERCASE", "uppercase", 3, upper),
+                                           ("FULL_LOWERCASE", "lowercase", 1, lower)]:
+            table(out,
+                  ["Full %s mappings `(char, first, second, third)` that differ from the simple ones," % doc,
+                   "the unconditional ones of `SpecialCasing.txt`. Missing characters are 0."],
+                  name, "(u32, u32, u32, u32)", special_casing(ucd, field, simple))
+
+
+if __name__ == "__main__":
+    sys.exit(main())
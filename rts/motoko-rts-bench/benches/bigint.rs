@@ -0,0 +1,97 @@
+//! BigInt arithmetic, and formatting and parsing of decimal text
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use motoko_rts::bigint::{
+    bigint_add, bigint_div, bigint_lsh, bigint_mul, bigint_of_int64, bigint_of_text_radix,
+    bigint_of_word32, bigint_pow, bigint_rem, bigint_sub, bigint_to_text,
+};
+use motoko_rts::types::SkewedPtr;
+use motoko_rts_bench::{init_rts, iter, root, Numbers};
+
+/// A number of `digits` 32-bit digits
+unsafe fn number(numbers: &mut Numbers, digits: usize) -> SkewedPtr {
+    (0..digits).fold(bigint_of_word32(0), |n, _| {
+        bigint_add(bigint_lsh(n, 32), bigint_of_word32(numbers.next_u32()))
+    })
+}
+
+fn arithmetic(c: &mut Criterion) {
+    init_rts();
+    let mut group = c.benchmark_group("bigint");
+
+    // Two numbers each of 64 bits (the slow path of `Int` arithmetic in the generated code), of
+    // 1024 bits, and of 32768 bits (above the Karatsuba cutoff)
+    for &(name, digits) in &[("64 bits", 2), ("1024 bits", 32), ("32768 bits", 1024)] {
+        unsafe {
+            let mut numbers = Numbers::new();
+            (*root(0)).field = number(&mut numbers, digits);
+            (*root(1)).field = number(&mut numbers, digits);
+            (*root(2)).field = number(&mut numbers, digits / 2);
+        }
+
+        group.bench_function(format!("add {}", name), |b| {
+            iter(b, || unsafe {
+                bigint_add((*root(0)).field, (*root(1)).field)
+            })
+        });
+        group.bench_function(format!("sub {}", name), |b| {
+            iter(b, || unsafe {
+                bigint_sub((*root(0)).field, (*root(1)).field)
+            })
+        });
+        group.bench_function(format!("mul {}", name), |b| {
+            iter(b, || unsafe {
+                bigint_mul((*root(0)).field, (*root(1)).field)
+            })
+        });
+        group.bench_function(format!("div {} by half", name), |b| {
+            iter(b, || unsafe {
+                bigint_div((*root(0)).field, (*root(2)).field)
+            })
+        });
+        group.bench_function(format!("rem {} by half", name), |b| {
+            iter(b, || unsafe {
+                bigint_rem((*root(0)).field, (*root(2)).field)
+            })
+        });
+    }
+
+    group.bench_function("pow 3^10000", |b| {
+        iter(b, || unsafe {
+            bigint_pow(bigint_of_word32(3), bigint_of_word32(10000))
+        })
+    });
+
+    group.finish();
+}
+
+fn decimal(c: &mut Criterion) {
+    init_rts();
+    let mut group = c.benchmark_group("bigint decimal");
+
+    for &(name, digits) in &[("64 bits", 2), ("1024 bits", 32), ("32768 bits", 1024)] {
+        unsafe {
+            (*root(0)).field = number(&mut Numbers::new(), digits);
+            (*root(1)).field = bigint_to_text((*root(0)).field, false);
+        }
+
+        group.bench_function(format!("to_text {}", name), |b| {
+            iter(b, || unsafe { bigint_to_text((*root(0)).field, false) })
+        });
+        group.bench_function(format!("of_text {}", name), |b| {
+            iter(b, || unsafe { bigint_of_text_radix((*root(1)).field, 10) })
+        });
+    }
+
+    group.bench_function("to_text with separators -1234567890", |b| {
+        iter(b, || unsafe {
+            bigint_to_text(bigint_of_int64(-1_234_567_890), true)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, arithmetic, decimal);
+criterion_main!(benches);
@@ -0,0 +1,166 @@
+//! (S)LEB128 encoding and decoding of Candid numbers, and the base32 of principals
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use motoko_rts::bigint::{
+    bigint_add, bigint_leb128_decode, bigint_leb128_encode, bigint_leb128_size, bigint_lsh,
+    bigint_of_word32,
+};
+use motoko_rts::buf::Buf;
+use motoko_rts::leb128::{
+    leb128_decode, leb128_decode_nat, leb128_encode, leb128_encode_u128, sleb128_decode,
+    sleb128_decode_int, sleb128_encode, sleb128_encode_i128,
+};
+use motoko_rts::principal_id::{
+    base32_of_checksummed_blob, base32_to_blob, principal_of_blob, principal_of_text_checked,
+};
+use motoko_rts::text::{blob_of_text, text_of_ptr_size};
+use motoko_rts::types::Bytes;
+use motoko_rts_bench::{init_rts, iter, root, Numbers};
+
+/// Numbers per buffer
+const N: usize = 1000;
+
+/// Buffer with the encodings of `N` numbers
+unsafe fn encodings(encode: impl Fn(&mut Numbers, *mut u8) -> usize) -> Vec<u8> {
+    let mut numbers = Numbers::new();
+    let mut buf = vec![0u8; N * 20];
+    let mut len = 0;
+    for _ in 0..N {
+        len += encode(&mut numbers, buf.as_mut_ptr().add(len));
+    }
+    buf.truncate(len);
+    buf
+}
+
+/// Decodes `N` numbers of the buffer
+unsafe fn decode_all<R>(bytes: &mut [u8], decode: impl Fn(*mut Buf) -> R) {
+    let mut buf = Buf {
+        ptr: bytes.as_mut_ptr(),
+        end: bytes.as_mut_ptr().add(bytes.len()),
+    };
+    for _ in 0..N {
+        black_box(decode(&mut buf));
+    }
+}
+
+/// Numbers of up to 32 bits, shorter ones more often, as in Candid messages
+fn small(numbers: &mut Numbers) -> u32 {
+    numbers.next_u32() >> (numbers.next_u32() % 32)
+}
+
+fn leb128(c: &mut Criterion) {
+    init_rts();
+    let mut group = c.benchmark_group("leb128");
+
+    let mut buf = [0u8; 20];
+    group.bench_function("encode 1000 u32", |b| {
+        b.iter(|| unsafe {
+            let mut numbers = Numbers::new();
+            for _ in 0..N {
+                leb128_encode(small(&mut numbers), buf.as_mut_ptr());
+            }
+        })
+    });
+    group.bench_function("encode 1000 i32", |b| {
+        b.iter(|| unsafe {
+            let mut numbers = Numbers::new();
+            for _ in 0..N {
+                sleb128_encode(small(&mut numbers) as i32, buf.as_mut_ptr());
+            }
+        })
+    });
+
+    unsafe {
+        let mut unsigned = encodings(|numbers, buf| {
+            let n = small(numbers);
+            leb128_encode_u128(u128::from(n), buf)
+        });
+        let mut signed = encodings(|numbers, buf| {
+            let n = small(numbers) as i32;
+            sleb128_encode_i128(i128::from(n), buf)
+        });
+        group.bench_function("decode 1000 u32", |b| {
+            b.iter(|| decode_all(&mut unsigned, |buf| leb128_decode(buf)))
+        });
+        group.bench_function("decode 1000 i32", |b| {
+            b.iter(|| decode_all(&mut signed, |buf| sleb128_decode(buf)))
+        });
+        group.bench_function("decode 1000 Nat", |b| {
+            iter(b, || {
+                decode_all(&mut unsigned, |buf| leb128_decode_nat(buf))
+            })
+        });
+        group.bench_function("decode 1000 Int", |b| {
+            iter(b, || decode_all(&mut signed, |buf| sleb128_decode_int(buf)))
+        });
+
+        // 128-bit numbers, the fast path of the `Nat` decoder, and larger ones, decoded to BigInts
+        let mut nat128 = encodings(|numbers, buf| {
+            let n = (0..4).fold(0u128, |n, _| n << 32 | u128::from(numbers.next_u32()));
+            leb128_encode_u128(n, buf)
+        });
+        group.bench_function("decode 1000 Nat of 128 bits", |b| {
+            iter(b, || decode_all(&mut nat128, |buf| leb128_decode_nat(buf)))
+        });
+
+        let mut numbers = Numbers::new();
+        let large = (0..32).fold(bigint_of_word32(0), |n, _| {
+            bigint_add(bigint_lsh(n, 32), bigint_of_word32(numbers.next_u32()))
+        });
+        let mut large_bytes = vec![0u8; bigint_leb128_size(large) as usize];
+        bigint_leb128_encode(large, large_bytes.as_mut_ptr());
+        (*root(0)).field = large;
+
+        group.bench_function("encode Nat of 1024 bits", |b| {
+            iter(b, || {
+                bigint_leb128_encode((*root(0)).field, large_bytes.as_mut_ptr());
+            })
+        });
+        group.bench_function("decode Nat of 1024 bits", |b| {
+            iter(b, || {
+                let mut buf = Buf {
+                    ptr: large_bytes.as_mut_ptr(),
+                    end: large_bytes.as_mut_ptr().add(large_bytes.len()),
+                };
+                bigint_leb128_decode(&mut buf)
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn base32(c: &mut Criterion) {
+    init_rts();
+    let mut group = c.benchmark_group("base32");
+
+    unsafe {
+        // A principal of 29 bytes, the longest, as text and as base32
+        let mut numbers = Numbers::new();
+        let bytes: Vec<u8> = (0..29).map(|_| numbers.next_u32() as u8).collect();
+        (*root(0)).field = blob_of_text(text_of_ptr_size(bytes.as_ptr(), Bytes(bytes.len())));
+        (*root(1)).field = principal_of_blob((*root(0)).field);
+        (*root(2)).field = base32_of_checksummed_blob((*root(0)).field);
+    }
+
+    group.bench_function("principal_of_blob", |b| {
+        iter(b, || unsafe { principal_of_blob((*root(0)).field) })
+    });
+    group.bench_function("principal_of_text_checked", |b| {
+        iter(b, || unsafe { principal_of_text_checked((*root(1)).field) })
+    });
+    group.bench_function("base32_of_checksummed_blob", |b| {
+        iter(b, || unsafe {
+            base32_of_checksummed_blob((*root(0)).field)
+        })
+    });
+    group.bench_function("base32_to_blob", |b| {
+        iter(b, || unsafe { base32_to_blob((*root(2)).field) })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, leb128, base32);
+criterion_main!(benches);
@@ -0,0 +1,80 @@
+//! Collections of synthetic heaps, with the copying and the mark-sweep collector
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use motoko_rts::types::{Bytes, SkewedPtr};
+use motoko_rts_bench::{init_rts, root, Numbers};
+
+extern "C" {
+    fn collect();
+    fn mark_sweep_gc();
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+}
+
+/// Live arrays of the heaps
+const LIVE: usize = 100_000;
+
+/// Fields of the arrays
+const FIELDS: usize = 4;
+
+/// Builds a heap of `LIVE` arrays, reachable from an array in root 0. The fields of the arrays are
+/// arrays built before, small blobs, and scalars, a quarter each.
+unsafe fn build_heap() {
+    let mut numbers = Numbers::new();
+    let all = alloc_array(LIVE);
+    for i in 0..LIVE {
+        all.as_array().set(i, SkewedPtr(0));
+    }
+    (*root(0)).field = all;
+
+    for i in 0..LIVE {
+        let array = alloc_array(FIELDS);
+        for field in 0..FIELDS {
+            let value = match numbers.next_u32() % 4 {
+                0 if i > 0 => all.as_array().get(numbers.next_u32() as usize % i),
+                1 => alloc_blob(Bytes(numbers.next_u32() as usize % 64)),
+                _ => SkewedPtr((numbers.next_u32() as usize) << 1),
+            };
+            array.as_array().set(field, value);
+        }
+        all.as_array().set(i, array);
+    }
+}
+
+/// Allocates `LIVE` blobs of garbage
+unsafe fn garbage() {
+    for i in 0..LIVE {
+        alloc_blob(Bytes(i % 64));
+    }
+}
+
+unsafe fn bench_collector(c: &mut Criterion, name: &str, gc: unsafe extern "C" fn()) {
+    build_heap();
+    gc();
+
+    let mut group = c.benchmark_group(name);
+
+    group.bench_function("100000 live", |b| b.iter(|| gc()));
+
+    group.bench_function("100000 live 100000 garbage", |b| {
+        b.iter_batched(|| garbage(), |()| gc(), BatchSize::PerIteration)
+    });
+
+    group.finish();
+
+    (*root(0)).field = SkewedPtr(0);
+    gc();
+}
+
+fn collectors(c: &mut Criterion) {
+    init_rts();
+    // The mark-sweep GC is last, as the other collectors don't know about its free lists
+    unsafe {
+        bench_collector(c, "copying gc", collect);
+        bench_collector(c, "mark-sweep gc", mark_sweep_gc);
+    }
+}
+
+criterion_group!(benches, collectors);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+//! Texts and blobs: concatenation, iteration, comparison, and the blob operations
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use motoko_rts::text::{
+    blob_compare_const_time, blob_concat_many, blob_copy_range, blob_fill, blob_of_text,
+    text_compare, text_concat, text_len, text_of_str,
+};
+use motoko_rts::text_iter::{text_iter, text_iter_done, text_iter_next};
+use motoko_rts::types::{Bytes, SkewedPtr};
+use motoko_rts_bench::{init_rts, iter, root, Numbers};
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+}
+
+/// Pieces of the ropes
+const PIECES: usize = 1000;
+
+/// `PIECES` pieces of ASCII and non-ASCII characters
+fn pieces() -> Vec<String> {
+    let mut numbers = Numbers::new();
+    (0..PIECES)
+        .map(|_| format!("{}-µ{}", numbers.next_u32(), numbers.next_u32() % 100))
+        .collect()
+}
+
+/// A rope of the pieces, concatenated left to right
+unsafe fn rope<S: AsRef<str>>(pieces: &[S]) -> SkewedPtr {
+    let mut text = text_of_str("");
+    for piece in pieces {
+        text = text_concat(text, text_of_str(piece.as_ref()));
+    }
+    text
+}
+
+fn concat(c: &mut Criterion) {
+    init_rts();
+    let mut group = c.benchmark_group("text");
+
+    let pieces = pieces();
+    group.bench_function("concat 1000", |b| iter(b, || unsafe { rope(&pieces) }));
+
+    unsafe {
+        (*root(0)).field = rope(&pieces);
+        // The same contents as a blob, and as a rope of other pieces
+        (*root(1)).field = blob_of_text((*root(0)).field);
+        let chars: Vec<char> = pieces.concat().chars().collect();
+        let chunks: Vec<String> = chars.chunks(7).map(|c| c.iter().collect()).collect();
+        (*root(2)).field = rope(&chunks);
+    }
+
+    group.bench_function("iter rope", |b| {
+        iter(b, || unsafe {
+            let iter = text_iter((*root(0)).field);
+            let mut sum = 0u32;
+            while text_iter_done(iter) == 0 {
+                sum = sum.wrapping_add(text_iter_next(iter));
+            }
+            sum
+        })
+    });
+
+    group.bench_function("len rope", |b| {
+        iter(b, || unsafe { text_len((*root(0)).field) })
+    });
+
+    group.bench_function("compare rope blob", |b| {
+        iter(b, || unsafe {
+            text_compare((*root(0)).field, (*root(1)).field)
+        })
+    });
+
+    group.bench_function("compare ropes", |b| {
+        iter(b, || unsafe {
+            text_compare((*root(0)).field, (*root(2)).field)
+        })
+    });
+
+    group.bench_function("blob_of_text rope", |b| {
+        iter(b, || unsafe { blob_of_text((*root(0)).field) })
+    });
+
+    group.finish();
+
+    unsafe {
+        for i in 0..3 {
+            (*root(i)).field = SkewedPtr(0);
+        }
+    }
+}
+
+fn blob(c: &mut Criterion) {
+    init_rts();
+    let mut group = c.benchmark_group("blob");
+
+    const SIZE: usize = 64 << 10;
+
+    unsafe {
+        (*root(0)).field = blob_fill(0xab, Bytes(SIZE));
+        (*root(1)).field = blob_fill(0xab, Bytes(SIZE));
+        let blobs = alloc_array(PIECES);
+        for i in 0..PIECES {
+            blobs.as_array().set(i, blob_fill(i as u32, Bytes(i % 100)));
+        }
+        (*root(2)).field = blobs;
+    }
+
+    group.bench_function("fill 64KiB", |b| {
+        iter(b, || unsafe { blob_fill(0xab, Bytes(SIZE)) })
+    });
+
+    group.bench_function("compare_const_time 64KiB", |b| {
+        iter(b, || unsafe {
+            blob_compare_const_time((*root(0)).field, (*root(1)).field)
+        })
+    });
+
+    group.bench_function("copy_range 64KiB", |b| {
+        iter(b, || unsafe {
+            blob_copy_range((*root(1)).field, 0, (*root(0)).field, 0, SIZE)
+        })
+    });
+
+    group.bench_function("concat_many 1000", |b| {
+        iter(b, || unsafe { blob_concat_many((*root(2)).field) })
+    });
+
+    group.finish();
+
+    unsafe {
+        for i in 0..3 {
+            (*root(i)).field = SkewedPtr(0);
+        }
+    }
+}
+
+criterion_group!(benches, concat, blob);
+criterion_main!(benches);
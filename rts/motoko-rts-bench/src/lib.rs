@@ -0,0 +1,169 @@
+//! The RTS environment of the benchmarks in `benches`.
+//!
+//! The RTS runs on a heap in the memory of the process, as in `motoko-rts-tests`, with the
+//! functions normally generated by the compiler defined here. The inputs of the benchmarks are
+//! fixed (no random seeds), so that the results of different commits can be compared (see
+//! "Benchmarks" in `rts/README.md`).
+//!
+//! The generated code collects at the end of every message. The benchmarks collect between batches
+//! of iterations instead (`iter`), outside of the measured time, so that the heap doesn't grow
+//! without bounds. Only the benchmarks of the collectors measure collections.
+
+use criterion::{black_box, Bencher};
+
+use motoko_rts::types::*;
+
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+extern "C" {
+    fn init();
+    fn collect();
+    fn get_heap_size() -> Bytes<usize>;
+}
+
+/// Size of the dynamic heap
+const HEAP_SIZE: usize = 1 << 30;
+
+/// Bytes allocated by the iterations of a batch, see `iter`
+const BATCH_ALLOCATION: usize = 16 << 20;
+
+/// Number of static roots, see `root`
+pub const N_ROOTS: usize = 4;
+
+static mut HEAP_BASE: usize = 0;
+
+/// Static memory with the static roots array: an array header, pointers to the mutable boxes, and
+/// the mutable boxes
+static mut STATIC_ROOTS: [usize; 2 + 3 * N_ROOTS] = [0; 2 + 3 * N_ROOTS];
+
+static INIT: Once = Once::new();
+
+/// Sets up the heap and the RTS, once per benchmark binary. Called by the benchmarks before they
+/// allocate.
+pub fn init_rts() {
+    INIT.call_once(|| unsafe {
+        let heap = Box::leak(vec![0usize; HEAP_SIZE / WORD_SIZE].into_boxed_slice());
+        HEAP_BASE = heap.as_ptr() as usize;
+
+        let array = STATIC_ROOTS.as_mut_ptr() as *mut Array;
+        (*array).header.tag = TAG_ARRAY;
+        (*array).len = N_ROOTS;
+        for i in 0..N_ROOTS {
+            let mutbox = root(i);
+            (*mutbox).header.tag = TAG_MUTBOX;
+            (*mutbox).field = SkewedPtr(0);
+            array.set(i, skew(mutbox as usize));
+        }
+
+        init();
+    });
+}
+
+/// Returns the mutable box of static root `i`. Objects that the benchmarks use across batches
+/// (e.g. the inputs) must be reachable from a root, as `iter` collects between batches.
+pub unsafe fn root(i: usize) -> *mut MutBox {
+    STATIC_ROOTS.as_mut_ptr().add(2 + N_ROOTS + 2 * i) as *mut MutBox
+}
+
+/// Like `Bencher::iter`, but collects (with the copying collector) after every batch of iterations
+/// that allocated `BATCH_ALLOCATION` bytes, outside of the measured time. Results of `routine` are
+/// garbage after the iteration.
+pub fn iter<R, F: FnMut() -> R>(b: &mut Bencher, mut routine: F) {
+    b.iter_custom(|iters| unsafe {
+        let mut time = Duration::from_secs(0);
+        let mut done = 0;
+        while done < iters {
+            let limit = get_heap_size().0 + BATCH_ALLOCATION;
+            let start = Instant::now();
+            while done < iters && get_heap_size().0 < limit {
+                black_box(routine());
+                done += 1;
+            }
+            time += start.elapsed();
+            collect();
+        }
+        time
+    })
+}
+
+/// Deterministic pseudo-random numbers (xorshift32) for the inputs
+pub struct Numbers(u32);
+
+impl Numbers {
+    pub fn new() -> Numbers {
+        Numbers(0x9e37_79b9)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+impl Default for Numbers {
+    fn default() -> Numbers {
+        Numbers::new()
+    }
+}
+
+// Called by the RTS to get the beginning of the dynamic heap. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn get_heap_base() -> usize {
+    unsafe { HEAP_BASE }
+}
+
+// Called by the RTS to get the static roots. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn get_static_roots() -> SkewedPtr {
+    unsafe { skew(STATIC_ROOTS.as_ptr() as usize) }
+}
+
+// Called by the RTS to trap. The benchmarks don't trap.
+#[no_mangle]
+unsafe extern "C" fn rts_trap(msg: *const u8, len: Bytes<usize>) -> ! {
+    let msg = core::slice::from_raw_parts(msg, len.0);
+    panic!("rts_trap_with called: {}", String::from_utf8_lossy(msg));
+}
+
+// Called by RTS BigInt functions to trap. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn bigint_trap() -> ! {
+    panic!("bigint_trap called");
+}
+
+// Called by the RTS for debug prints
+#[no_mangle]
+unsafe extern "C" fn print_ptr(ptr: usize, len: usize) {
+    let str: &[u8] = core::slice::from_raw_parts(ptr as *const u8, len);
+    println!("[RTS] {}", String::from_utf8_lossy(str));
+}
+
+// Called by the RTS for stable memory, not used in the benchmarks. Normally generated by the
+// compiler (`ic0.stable_*`)
+#[no_mangle]
+extern "C" fn stable_mem_size() -> u32 {
+    0
+}
+
+#[no_mangle]
+extern "C" fn stable_mem_grow(_pages: u32) -> i32 {
+    -1
+}
+
+#[no_mangle]
+extern "C" fn stable_mem_read(_dst: *mut u8, _offset: u32, _len: u32) {
+    unreachable!("stable_mem_read called")
+}
+
+#[no_mangle]
+extern "C" fn stable_mem_write(_offset: u32, _src: *const u8, _len: u32) {
+    unreachable!("stable_mem_write called")
+}
+
+// Called by the RTS to set the global timer, not used in the benchmarks. Normally generated by the
+// compiler (`ic0.global_timer_set`)
+#[no_mangle]
+extern "C" fn timer_global_set(_timestamp: u64) {}
@@ -0,0 +1,9 @@
+//! Decodes arbitrary bytes as Candid arguments
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = motoko_rts_fuzz::decode(data);
+});
@@ -0,0 +1,120 @@
+//! Decodes arbitrary values of arbitrary, well-formed type tables. Arbitrary bytes rarely get past
+//! the type table, the mutations of the `Input` reach the decoding of the values.
+
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+/// Opcodes of the primitive types, `null` to `empty`, and `principal`
+const PRIMITIVES: &[i64] = &[
+    -1, -2, -3, -4, -5, -6, -7, -8, -9, -10, -11, -12, -13, -14, -15, -16, -17, -24,
+];
+
+#[derive(Arbitrary, Debug)]
+enum Type {
+    /// Index into `PRIMITIVES`
+    Prim(u8),
+    /// Index into the type table
+    Ref(u8),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Con {
+    Opt(Type),
+    Vec(Type),
+    Record(Vec<(u32, Type)>),
+    Variant(Vec<(u32, Type)>),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    table: Vec<Con>,
+    args: Vec<Type>,
+    /// The encoding of the values
+    values: Vec<u8>,
+}
+
+fn leb128(buf: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn sleb128(buf: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn encode_type(buf: &mut Vec<u8>, t: &Type, n_types: usize) {
+    match *t {
+        Type::Ref(idx) if n_types != 0 => sleb128(buf, (idx as usize % n_types) as i64),
+        Type::Ref(idx) | Type::Prim(idx) => {
+            sleb128(buf, PRIMITIVES[idx as usize % PRIMITIVES.len()])
+        }
+    }
+}
+
+/// Fields sorted by id, without duplicates, as Candid requires
+fn encode_fields(buf: &mut Vec<u8>, fields: &[(u32, Type)], n_types: usize) {
+    let mut fields: Vec<&(u32, Type)> = fields.iter().collect();
+    fields.sort_by_key(|(id, _)| *id);
+    fields.dedup_by_key(|(id, _)| *id);
+    leb128(buf, fields.len() as u64);
+    for (id, t) in fields {
+        leb128(buf, *id as u64);
+        encode_type(buf, t, n_types);
+    }
+}
+
+fn encode(input: &Input) -> Vec<u8> {
+    let mut buf = b"DIDL".to_vec();
+    let n_types = input.table.len();
+
+    leb128(&mut buf, n_types as u64);
+    for con in &input.table {
+        match con {
+            Con::Opt(t) => {
+                sleb128(&mut buf, -18);
+                encode_type(&mut buf, t, n_types);
+            }
+            Con::Vec(t) => {
+                sleb128(&mut buf, -19);
+                encode_type(&mut buf, t, n_types);
+            }
+            Con::Record(fields) => {
+                sleb128(&mut buf, -20);
+                encode_fields(&mut buf, fields, n_types);
+            }
+            Con::Variant(fields) => {
+                sleb128(&mut buf, -21);
+                encode_fields(&mut buf, fields, n_types);
+            }
+        }
+    }
+
+    leb128(&mut buf, input.args.len() as u64);
+    for t in &input.args {
+        encode_type(&mut buf, t, n_types);
+    }
+
+    buf.extend_from_slice(&input.values);
+    buf
+}
+
+fuzz_target!(|input: Input| {
+    let _ = motoko_rts_fuzz::decode(&encode(&input));
+});
@@ -0,0 +1,204 @@
+//! Decoding of Candid with the RTS (`idl.rs`), for the fuzz targets in `fuzz_targets`.
+//!
+//! The RTS runs on a heap in the memory of the process, as in `motoko-rts-tests`, with the
+//! functions normally generated by the compiler defined here. `decode` does what the generated
+//! deserialization code does with the RTS, except for building the values: it parses the header and
+//! skips the arguments.
+//!
+//! A trap of the RTS is an expected outcome for malformed input. It unwinds to `decode`, which
+//! returns the message, instead of ending the process as a crash. Every other panic (e.g. an
+//! assertion of the RTS or an out-of-bounds access of the mock memory) is a crash, as are inputs
+//! whose decoding allocates more than `MAX_ALLOCATION_PER_BYTE` bytes per input byte.
+
+use motoko_rts::buf::Buf;
+use motoko_rts::leb128::{leb128_decode, sleb128_decode};
+use motoko_rts::types::*;
+
+use std::panic;
+use std::sync::Once;
+
+extern "C" {
+    fn init();
+    fn collect();
+    fn get_total_allocations() -> Bytes<u64>;
+    fn idl_set_limits(max_depth: u32, max_values: u32, max_zero_sized: u32);
+    fn parse_idl_header(
+        extended: bool,
+        buf: *mut Buf,
+        typtbl_out: *mut *mut *mut u8,
+        typtbl_size_out: *mut u32,
+        main_types_out: *mut *mut u8,
+    );
+    fn skip_any(buf: *mut Buf, typtbl: *mut *mut u8, t: i32, depth: i32);
+}
+
+/// Size of the dynamic heap. It is collected after every input.
+const HEAP_SIZE: usize = 64 << 20;
+
+/// Bytes allocated per byte of input above which decoding is considered a blowup. The type table
+/// takes a word per type, every value is read once.
+pub const MAX_ALLOCATION_PER_BYTE: u64 = 16;
+
+/// Allocation allowed for any input, e.g. for the tables of the RTS
+const BASE_ALLOCATION: u64 = 64 << 10;
+
+/// The limits of the decoder in the fuzz targets, see `idl_set_limits`. Without a limit on the
+/// values, vectors of zero-sized values take time but no input.
+const MAX_DEPTH: u32 = 100;
+const MAX_VALUES: u32 = 1 << 20;
+const MAX_ZERO_SIZED: u32 = 1 << 16;
+
+static mut HEAP_BASE: usize = 0;
+
+/// The static roots array, empty
+static mut STATIC_ROOTS: [usize; 2] = [0; 2];
+
+static INIT: Once = Once::new();
+
+/// Payload of the panic of a trap of the RTS
+struct Trap(String);
+
+/// Decodes `data` as the Candid arguments of a message. Returns the message of the trap when the
+/// input is rejected.
+pub fn decode(data: &[u8]) -> Result<(), String> {
+    INIT.call_once(|| unsafe { init_rts() });
+
+    // The RTS may write to the input (e.g. for the memo fields of the extended format)
+    let mut input = data.to_vec();
+
+    let allocated_before = unsafe { get_total_allocations().0 };
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| unsafe {
+        decode_args(&mut input)
+    }));
+
+    let allocated = unsafe { get_total_allocations().0 } - allocated_before;
+    assert!(
+        allocated <= BASE_ALLOCATION + MAX_ALLOCATION_PER_BYTE * data.len() as u64,
+        "decoding {} bytes allocated {} bytes",
+        data.len(),
+        allocated
+    );
+
+    // Nothing is reachable, this frees all that was allocated
+    unsafe { collect() };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(payload) => match payload.downcast::<Trap>() {
+            Ok(trap) => Err(trap.0),
+            Err(payload) => panic::resume_unwind(payload),
+        },
+    }
+}
+
+unsafe fn init_rts() {
+    let heap = Box::leak(vec![0usize; HEAP_SIZE / WORD_SIZE].into_boxed_slice());
+    HEAP_BASE = heap.as_ptr() as usize;
+
+    let array = STATIC_ROOTS.as_mut_ptr() as *mut Array;
+    (*array).header.tag = TAG_ARRAY;
+    (*array).len = 0;
+
+    init();
+
+    idl_set_limits(MAX_DEPTH, MAX_VALUES, MAX_ZERO_SIZED);
+}
+
+/// The RTS calls of `deserialize_from_blob` in `compile.ml`
+unsafe fn decode_args(input: &mut [u8]) {
+    let mut data_buf = Buf {
+        ptr: input.as_mut_ptr(),
+        end: input.as_mut_ptr().add(input.len()),
+    };
+
+    let mut typtbl: *mut *mut u8 = core::ptr::null_mut();
+    let mut typtbl_size: u32 = 0;
+    let mut main_types: *mut u8 = core::ptr::null_mut();
+
+    parse_idl_header(
+        false,
+        &mut data_buf,
+        &mut typtbl,
+        &mut typtbl_size,
+        &mut main_types,
+    );
+
+    let mut main_types_buf = Buf {
+        ptr: main_types,
+        end: data_buf.end,
+    };
+    let n_args = leb128_decode(&mut main_types_buf);
+    for _ in 0..n_args {
+        let t = sleb128_decode(&mut main_types_buf);
+        skip_any(&mut data_buf, typtbl, t, 0);
+    }
+
+    if data_buf.ptr != data_buf.end {
+        trap("left-over bytes".to_string());
+    }
+}
+
+/// Unwinds to `decode`, without the panic hook (the hook of libfuzzer aborts)
+fn trap(msg: String) -> ! {
+    panic::resume_unwind(Box::new(Trap(msg)))
+}
+
+// Called by the RTS to get the beginning of the dynamic heap. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn get_heap_base() -> usize {
+    unsafe { HEAP_BASE }
+}
+
+// Called by the RTS to get the static roots. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn get_static_roots() -> SkewedPtr {
+    unsafe { skew(STATIC_ROOTS.as_ptr() as usize) }
+}
+
+// Called by the RTS to trap, unwinds to `decode`
+#[no_mangle]
+unsafe extern "C" fn rts_trap(msg: *const u8, len: Bytes<usize>) -> ! {
+    let msg = core::slice::from_raw_parts(msg, len.0);
+    trap(String::from_utf8_lossy(msg).into_owned())
+}
+
+// Called by RTS BigInt functions to trap. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn bigint_trap() -> ! {
+    trap("bigint_trap called".to_string())
+}
+
+// Called by the RTS for debug prints
+#[no_mangle]
+unsafe extern "C" fn print_ptr(ptr: usize, len: usize) {
+    let str: &[u8] = core::slice::from_raw_parts(ptr as *const u8, len);
+    println!("[RTS] {}", String::from_utf8_lossy(str));
+}
+
+// Called by the RTS for stable memory, not used in decoding. Normally generated by the compiler
+// (`ic0.stable_*`)
+#[no_mangle]
+extern "C" fn stable_mem_size() -> u32 {
+    0
+}
+
+#[no_mangle]
+extern "C" fn stable_mem_grow(_pages: u32) -> i32 {
+    -1
+}
+
+#[no_mangle]
+extern "C" fn stable_mem_read(_dst: *mut u8, _offset: u32, _len: u32) {
+    unreachable!("stable_mem_read called")
+}
+
+#[no_mangle]
+extern "C" fn stable_mem_write(_offset: u32, _src: *const u8, _len: u32) {
+    unreachable!("stable_mem_write called")
+}
+
+// Called by the RTS to set the global timer, not used in decoding. Normally generated by the
+// compiler (`ic0.global_timer_set`)
+#[no_mangle]
+extern "C" fn timer_global_set(_timestamp: u64) {}
@@ -0,0 +1,36 @@
+use motoko_rts::types::{Bytes, SkewedPtr};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+    fn get_heap_size() -> Bytes<usize>;
+    fn get_total_allocations() -> Bytes<u64>;
+
+    /// Locations of the heap pointer, the limit, and the allocation counters (bytes and objects) of
+    /// the fast path
+    fn get_alloc_fast_path() -> *const [*mut usize; 4];
+}
+
+pub unsafe fn test() {
+    println!("Testing allocation fast path ...");
+
+    let [hp, limit, allocated, _] = *get_alloc_fast_path();
+
+    let blob = alloc_blob(Bytes(100));
+    assert_eq!(*hp, crate::HEAP_BASE + get_heap_size().0);
+    assert!(blob.unskew() < *hp);
+
+    // The tests allocate with redzones, which the fast path can't
+    assert_eq!(*limit, 0);
+
+    // Allocations of the fast path are counted, and added to the total on collection
+    let total = get_total_allocations();
+    *allocated += 16;
+    assert_eq!(get_total_allocations(), total + Bytes(16));
+    collect();
+    assert_eq!(*allocated, 0);
+    assert_eq!(get_total_allocations(), total + Bytes(16));
+    assert_eq!(*limit, 0);
+
+    println!("OK");
+}
@@ -0,0 +1,54 @@
+use motoko_rts::alloc_profile::{
+    alloc_profile, alloc_profile_enable, set_alloc_site, update_alloc_profile,
+};
+use motoko_rts::text::text_of_str;
+use motoko_rts::types::*;
+
+pub unsafe fn test() {
+    println!("Testing allocation profiler ...");
+
+    alloc_profile_enable();
+
+    // More than the initial table size, to exercise growing the table
+    set_alloc_site(1);
+    for _ in 0..100 {
+        text_of_str("site 1");
+    }
+
+    set_alloc_site(3);
+    text_of_str("site 3");
+
+    set_alloc_site(0);
+    text_of_str("no site");
+
+    let names = [
+        TAG_ARRAY,
+        3,
+        text_of_str("one").0,
+        text_of_str("two").0,
+        text_of_str("three").0,
+    ];
+    let names = skew(names.as_ptr() as usize);
+
+    // Counts are updated after a GC
+    assert_eq!(alloc_profile(names).as_array().len(), 0);
+    update_alloc_profile();
+
+    let profile = alloc_profile(names).as_array();
+    assert_eq!(profile.len(), 2);
+
+    // Small text header + 6 bytes, rounded up
+    let text_size = (size_of::<SmallText>() + Bytes(6).to_words()).to_bytes().0;
+
+    let site_1 = profile.get(0).as_array();
+    assert_eq!(site_1.get(0).0, names.as_array().get(0).0);
+    assert_eq!(site_1.get(1).0, 100 << 1);
+    assert_eq!(site_1.get(2).0, (100 * text_size) << 1);
+
+    let site_3 = profile.get(1).as_array();
+    assert_eq!(site_3.get(0).0, names.as_array().get(2).0);
+    assert_eq!(site_3.get(1).0, 1 << 1);
+    assert_eq!(site_3.get(2).0, text_size << 1);
+
+    println!("OK");
+}
@@ -0,0 +1,67 @@
+use motoko_rts::array::array_blit;
+use motoko_rts::types::SkewedPtr;
+
+use quickcheck::{quickcheck, TestResult};
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+}
+
+pub unsafe fn test() {
+    println!("Testing array blit ...");
+
+    // Overlapping ranges in the same array, in both directions
+    let array = array_of(&[0, 1, 2, 3, 4, 5]);
+    array_blit(array, 2, array, 0, 3);
+    assert_eq!(elements(array), [0, 1, 0, 1, 2, 5]);
+    array_blit(array, 0, array, 3, 3);
+    assert_eq!(elements(array), [1, 2, 5, 1, 2, 5]);
+
+    // Empty ranges, also at the end
+    let array = array_of(&[7, 8]);
+    array_blit(array, 2, array_of(&[]), 0, 0);
+    assert_eq!(elements(array), [7, 8]);
+
+    quickcheck(blit_prop as fn(Vec<u16>, Vec<u16>, u8, u8, u8) -> TestResult);
+
+    println!("OK");
+}
+
+fn blit_prop(dst: Vec<u16>, src: Vec<u16>, dst_off: u8, src_off: u8, len: u8) -> TestResult {
+    // Offsets and length in bounds
+    let dst_off = usize::from(dst_off) % (dst.len() + 1);
+    let src_off = usize::from(src_off) % (src.len() + 1);
+    let len = usize::from(len) % (std::cmp::min(dst.len() - dst_off, src.len() - src_off) + 1);
+
+    unsafe {
+        let array = array_of(&dst);
+        array_blit(array, dst_off, array_of(&src), src_off, len);
+
+        let mut expected = dst.clone();
+        expected[dst_off..dst_off + len].copy_from_slice(&src[src_off..src_off + len]);
+        if elements(array) != expected {
+            return TestResult::error(format!(
+                "array_blit({:?}, {}, {:?}, {}, {})",
+                dst, dst_off, src, src_off, len
+            ));
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// An array of the (scalar) values
+unsafe fn array_of(values: &[u16]) -> SkewedPtr {
+    let array = alloc_array(values.len());
+    for (i, value) in values.iter().enumerate() {
+        array.as_array().set(i, SkewedPtr(usize::from(*value) << 1));
+    }
+    array
+}
+
+unsafe fn elements(array: SkewedPtr) -> Vec<u16> {
+    let array = array.as_array();
+    (0..array.len())
+        .map(|i| (array.get(i).0 >> 1) as u16)
+        .collect()
+}
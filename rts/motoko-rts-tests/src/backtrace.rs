@@ -0,0 +1,49 @@
+use motoko_rts::backtrace::{backtrace_depth, backtrace_pop, backtrace_push, write_backtrace};
+use motoko_rts::text::{blob_of_text, text_of_str};
+
+pub unsafe fn test() {
+    println!("Testing backtraces ...");
+
+    assert_eq!(backtrace(), "");
+
+    push("main (a.mo:7.1-9.2)");
+    push("copy (a.mo:3.1-5.2)");
+    assert_eq!(
+        backtrace(),
+        "; backtrace: copy (a.mo:3.1-5.2) <- main (a.mo:7.1-9.2)"
+    );
+    backtrace_pop();
+    assert_eq!(backtrace(), "; backtrace: main (a.mo:7.1-9.2)");
+
+    // Deep recursion, the innermost frames are counted
+    for _ in 0..70 {
+        push("f");
+    }
+    let bt = backtrace();
+    assert!(bt.starts_with("; backtrace: (7 more) <- f <- f"));
+    assert!(bt.ends_with("f <- main (a.mo:7.1-9.2)"));
+    for _ in 0..70 {
+        backtrace_pop();
+    }
+    assert_eq!(backtrace_depth(), 1);
+
+    // Cut off at the end of the buffer
+    let mut buf = [0u8; 20];
+    assert_eq!(write_backtrace(&mut buf), 20);
+    assert_eq!(&buf, b"; backtrace: main (a");
+
+    // Unbalanced pops don't underflow
+    backtrace_pop();
+    backtrace_pop();
+    assert_eq!(backtrace_depth(), 0);
+}
+
+unsafe fn push(name: &str) {
+    backtrace_push(blob_of_text(text_of_str(name)));
+}
+
+unsafe fn backtrace() -> String {
+    let mut buf = [0u8; 512];
+    let len = write_backtrace(&mut buf);
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
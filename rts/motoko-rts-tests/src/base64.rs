@@ -0,0 +1,98 @@
+use crate::utils::{blob_of_bytes, bytes_of_blob, string_of_text};
+
+use motoko_rts::base64::{base64_decode, base64_encode, BASE64_PADDING, BASE64_URL_SAFE};
+use motoko_rts::text::{text_concat, text_of_str};
+
+use quickcheck::{quickcheck, TestResult};
+
+pub unsafe fn test() {
+    println!("Testing base64 ...");
+
+    // Test vectors from RFC 4648
+    let cases: &[(&str, &str)] = &[
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    for (input, encoded) in cases {
+        let blob = blob_of_bytes(input.as_bytes());
+        assert_eq!(
+            string_of_text(base64_encode(blob, BASE64_PADDING)),
+            *encoded
+        );
+        assert_eq!(
+            string_of_text(base64_encode(blob, 0)),
+            encoded.trim_end_matches('=')
+        );
+        assert_eq!(decode(encoded, 0), Some(input.as_bytes().to_vec()));
+        assert_eq!(
+            decode(encoded.trim_end_matches('='), 0),
+            Some(input.as_bytes().to_vec())
+        );
+    }
+
+    // The alphabets differ in the last two characters
+    let blob = blob_of_bytes(&[0xfb, 0xff, 0xbf]);
+    assert_eq!(string_of_text(base64_encode(blob, 0)), "+/+/");
+    assert_eq!(string_of_text(base64_encode(blob, BASE64_URL_SAFE)), "-_-_");
+    assert_eq!(decode("+/+/", 0), Some(vec![0xfb, 0xff, 0xbf]));
+    assert_eq!(
+        decode("-_-_", BASE64_URL_SAFE),
+        Some(vec![0xfb, 0xff, 0xbf])
+    );
+    assert_eq!(decode("-_-_", 0), None);
+    assert_eq!(decode("+/+/", BASE64_URL_SAFE), None);
+
+    // Invalid encodings
+    for invalid in &[
+        "Z", "Zg=", "Zg===", "Z===", "====", "Zm9v\n", " Zm9v", "Zm=v", "Zh==", "Zm9=",
+    ] {
+        assert_eq!(decode(invalid, 0), None, "{:?}", invalid);
+    }
+
+    // Decoding a concatenation
+    let t = text_concat(text_of_str("Zm9vYm"), text_of_str("Fy"));
+    assert_eq!(bytes_of_blob(base64_decode(t, 0)), b"foobar");
+
+    quickcheck(base64_prop as fn(Vec<u8>, bool, bool) -> TestResult);
+
+    println!("OK");
+}
+
+fn base64_prop(input: Vec<u8>, url_safe: bool, padding: bool) -> TestResult {
+    unsafe {
+        let mut flags = 0;
+        if url_safe {
+            flags |= BASE64_URL_SAFE;
+        }
+        if padding {
+            flags |= BASE64_PADDING;
+        }
+
+        let encoded = string_of_text(base64_encode(blob_of_bytes(&input), flags));
+
+        if padding && encoded.len() % 4 != 0 {
+            return TestResult::error(format!("padding of {:?}", encoded));
+        }
+
+        if decode(&encoded, flags) != Some(input.clone()) {
+            return TestResult::error(format!("base64 round trip of {:?}", input));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn decode(encoded: &str, flags: u32) -> Option<Vec<u8>> {
+    let blob = base64_decode(text_of_str(encoded), flags);
+    if blob.0 == 0 {
+        None
+    } else {
+        Some(bytes_of_blob(blob))
+    }
+}
@@ -1,10 +1,18 @@
+use crate::gc_stress::{collect_with, with_alloc_collections};
+use crate::utils::{bytes_of_blob, string_of_text};
+
 use motoko_rts::bigint::*;
 use motoko_rts::buf::Buf;
+use motoko_rts::text::text_of_str;
 use motoko_rts::types::SkewedPtr;
 
+use quickcheck::{quickcheck, TestResult};
+
 pub unsafe fn test() {
     println!("Testing BigInt ...");
 
+    let ten = bigint_of_word32(10);
+
     assert!(bigint_eq(
         bigint_pow(bigint_of_word32(70), bigint_of_word32(32)),
         bigint_mul(
@@ -13,6 +21,20 @@ pub unsafe fn test() {
         )
     ));
 
+    //
+    // Small numbers are not allocated
+    //
+
+    for j in -200..1100 {
+        let a = bigint_of_int64(j);
+        let b = bigint_of_int32(j as i32);
+        assert_eq!(a.0 == b.0, (-128..=1024).contains(&j));
+        assert!(bigint_eq(a, b));
+        assert_eq!(bigint_to_word64_wrap(a), j as u64);
+    }
+    assert_eq!(bigint_of_word32(0).0, bigint_of_word64(0).0);
+    assert!(!bigint_isneg(bigint_of_int32(0)));
+
     //
     // (s)leb128 encoding
     //
@@ -35,6 +57,669 @@ pub unsafe fn test() {
         test_bigint_sleb128(bigint_neg(two_pow_i));
         test_bigint_sleb128(bigint_neg(plus_one));
     }
+
+    //
+    // Arithmetic
+    //
+
+    quickcheck(arith_prop as fn(i64, i64) -> TestResult);
+    quickcheck(wrap_prop as fn(i64, u8) -> TestResult);
+    quickcheck(large_prop as fn(Vec<u32>, Vec<u32>, Vec<u32>) -> TestResult);
+    quickcheck(bitwise_prop as fn(i64, i64, u8, u8) -> TestResult);
+
+    // Operands above the Karatsuba cutoff, balanced and unbalanced
+    for &(a_len, b_len) in &[(32u32, 32u32), (40, 70), (100, 33), (200, 1000)] {
+        let a: Vec<u32> = (0..a_len)
+            .map(|i| 0xdead_beef ^ i.wrapping_mul(0x9e37_79b9))
+            .collect();
+        let b: Vec<u32> = (0..b_len)
+            .map(|i| 0x0123_4567 ^ i.wrapping_mul(0x85eb_ca6b))
+            .collect();
+        let c = vec![u32::MAX; 3];
+        assert!(!large_prop(a, b, c).is_failure());
+    }
+
+    // Powers
+    assert!(bigint_eq(bigint_pow(ten, bigint_of_word32(0)), one));
+    assert!(bigint_eq(
+        bigint_pow(ten, bigint_of_word32(19)),
+        bigint_of_word64(10_000_000_000_000_000_000)
+    ));
+    assert!(bigint_eq(
+        bigint_pow(bigint_of_int32(-2), bigint_of_word32(63)),
+        bigint_of_int64(i64::MIN)
+    ));
+    assert!(bigint_eq(
+        bigint_pow(two, bigint_of_word32(200)),
+        bigint_lsh(one, 200)
+    ));
+
+    //
+    // Little-endian blobs
+    //
+
+    quickcheck(le_blob_prop as fn(i64, u8) -> TestResult);
+
+    let n = bigint_of_word64(0x0102_0304_0506);
+    assert_eq!(bytes_of_blob(bigint_to_le_blob(n)), [6, 5, 4, 3, 2, 1]);
+    assert!(bytes_of_blob(bigint_to_le_blob(bigint_of_word32(0))).is_empty());
+    assert_eq!(
+        bytes_of_blob(bigint_to_signed_le_blob(bigint_of_int32(-2), 3)),
+        [0xfe, 0xff, 0xff]
+    );
+    assert_eq!(
+        bytes_of_blob(bigint_to_signed_le_blob(bigint_of_int32(128), 2)),
+        [0x80, 0]
+    );
+    assert!(bytes_of_blob(bigint_to_signed_le_blob(bigint_of_word32(0), 0)).is_empty());
+
+    //
+    // Decimal text
+    //
+
+    quickcheck(text_prop as fn(Vec<u32>, bool) -> TestResult);
+
+    assert_eq!(text_string(bigint_of_word32(0), true), "0");
+    assert_eq!(text_string(bigint_of_int32(-1234567), true), "-1_234_567");
+    assert_eq!(text_string(bigint_of_int32(-1234567), false), "-1234567");
+    assert_eq!(text_string(bigint_of_word32(123456), true), "123_456");
+
+    // Powers of ten, large enough for the reciprocals with Newton's method
+    for &k in &[18, 19, 36, 1000, 5000] {
+        let power = bigint_pow(ten, bigint_of_word32(k));
+        let text = text_string(bigint_sub(power, one), false);
+        assert!(text.len() == k as usize && text.bytes().all(|c| c == b'9'));
+        let text = text_string(power, false);
+        assert!(text.starts_with('1') && text[1..].bytes().all(|c| c == b'0'));
+        assert_eq!(text.len(), k as usize + 1);
+
+        let parsed = bigint_of_text_radix(text_of_str(&text), 10);
+        assert!(bigint_eq(parsed, power));
+    }
+
+    //
+    // Floats
+    //
+
+    quickcheck(to_float_prop as fn(i64, u8) -> TestResult);
+    quickcheck(large_to_float_prop as fn(Vec<u32>) -> TestResult);
+    quickcheck(of_float_prop as fn(u64) -> TestResult);
+
+    // Ties round to even: 2^53 + 1 is between 2^53 and 2^53 + 2
+    let two_53 = bigint_pow(two, bigint_of_word32(53));
+    assert_eq!(
+        bigint_to_float64(bigint_add(two_53, one)),
+        9007199254740992.0
+    );
+    assert_eq!(
+        bigint_to_float64(bigint_add(two_53, bigint_of_word32(3))),
+        9007199254740996.0
+    );
+    // Beyond the range of `f64`, after rounding
+    let two_1024 = bigint_pow(two, bigint_of_word32(1024));
+    assert_eq!(bigint_to_float64(bigint_sub(two_1024, one)), f64::INFINITY);
+    assert_eq!(bigint_to_float64(bigint_neg(two_1024)), f64::NEG_INFINITY);
+    assert!(bigint_eq(
+        bigint_of_float64(f64::MAX),
+        bigint_sub(two_1024, bigint_pow(two, bigint_of_word32(971)))
+    ));
+    assert!(bigint_eq(bigint_of_float64(-0.9), bigint_of_word32(0)));
+    assert!(bigint_eq(bigint_of_float64(-2.5), bigint_of_int32(-2)));
+
+    //
+    // Parsing text
+    //
+
+    quickcheck(parse_prop as fn(Vec<u32>, bool) -> TestResult);
+    quickcheck(parse_radix_prop as fn(i64) -> TestResult);
+
+    assert!(parses_to("0", 0, 0));
+    assert!(parses_to("-0", 0, 0));
+    assert!(parses_to("+1_000", 10, 1000));
+    assert!(parses_to("0xfF_ff", 0, 0xffff));
+    assert!(parses_to("-0b1010", 0, -10));
+    assert!(parses_to("0o17", 8, 15));
+    assert!(parses_to("0b1", 16, 0xb1));
+    assert!(parses_to("zz", 36, 36 * 36 - 1));
+    for &(text, radix) in &[
+        ("", 10),
+        ("-", 10),
+        ("0x", 0),
+        ("_1", 10),
+        ("1_", 10),
+        ("1__0", 10),
+        ("0x_1", 0),
+        ("0x1", 10),
+        ("12", 2),
+        ("1 ", 10),
+        ("--1", 10),
+    ] {
+        assert!(
+            parse(text, radix).is_none(),
+            "{:?} in radix {}",
+            text,
+            radix
+        );
+    }
+
+    //
+    // GCD and LCM
+    //
+
+    quickcheck(gcd_prop as fn(i64, i64) -> TestResult);
+    quickcheck(large_gcd_prop as fn(Vec<u32>, Vec<u32>, Vec<u32>, u8) -> TestResult);
+
+    let zero = bigint_of_word32(0);
+    assert!(bigint_eq(bigint_gcd(zero, zero), zero));
+    assert!(bigint_eq(
+        bigint_gcd(zero, bigint_of_int32(-6)),
+        bigint_of_word32(6)
+    ));
+    assert!(bigint_eq(bigint_lcm(zero, bigint_of_int32(-6)), zero));
+    assert!(bigint_eq(
+        bigint_lcm(bigint_of_int32(-4), bigint_of_word32(6)),
+        bigint_of_word32(12)
+    ));
+
+    //
+    // Square roots
+    //
+
+    quickcheck(isqrt_prop as fn(Vec<u32>) -> TestResult);
+    // Around squares, the largest is (2^32 - 1)^2
+    let around_squares = [
+        0,
+        1,
+        2,
+        3,
+        4,
+        15,
+        16,
+        17,
+        0xffff_fffe_0000_0000,
+        0xffff_fffe_0000_0001,
+        u64::MAX,
+    ];
+    for &n in around_squares.iter() {
+        assert!(!isqrt_prop(vec![n as u32, (n >> 32) as u32]).is_failure());
+    }
+
+    //
+    // Modular exponentiation
+    //
+
+    quickcheck(pow_mod_prop as fn(i64, u64, u64) -> TestResult);
+
+    // Fermat's little theorem, for the Mersenne primes 2^127 - 1 and 2^521 - 1
+    for &bits in &[127, 521] {
+        let p = bigint_sub(bigint_lsh(one, bits), one);
+        let p_minus_one = bigint_sub(p, one);
+        for &a in &[2, 3, -5] {
+            let a = bigint_of_int32(a);
+            assert!(bigint_eq(bigint_pow_mod(a, p_minus_one, p), one));
+            assert!(bigint_eq(
+                bigint_pow_mod(a, p, p),
+                bigint_rem(bigint_add(a, p), p)
+            ));
+        }
+    }
+
+    //
+    // GC stress
+    //
+
+    with_alloc_collections(|| stress());
+}
+
+/// Arithmetic with a collection after every call, see `gc_stress.rs`
+unsafe fn stress() {
+    // Fibonacci numbers, checked with `i128` while they fit, and factorials, checked by division
+    let mut values = [
+        bigint_of_word32(0),
+        bigint_of_word32(1),
+        bigint_of_word32(1),
+        bigint_of_word32(1),
+    ];
+    let (mut a, mut b) = (0i128, 1i128);
+    for i in 1..200 {
+        let next = bigint_add(values[0], values[1]);
+        values[0] = values[1];
+        values[1] = next;
+        collect_with(&mut values);
+        if let Some(sum) = a.checked_add(b) {
+            a = b;
+            b = sum;
+            assert!(bigint_eq(values[1], bigint_of_i128(b)));
+        }
+
+        values[3] = values[2];
+        values[2] = bigint_mul(values[2], bigint_of_word32(i));
+        collect_with(&mut values);
+        assert!(bigint_eq(
+            bigint_div(values[2], bigint_of_word32(i)),
+            values[3]
+        ));
+        collect_with(&mut values);
+    }
+
+    // The 199th Fibonacci number and 199!, the last values
+    assert_eq!(
+        text_string(values[0], false),
+        "173402521172797813159685037284371942044301"
+    );
+    assert_eq!(text_string(values[3], false).len(), 371);
+
+    // Decimal text and parsing, with 199! only reachable from the text
+    let mut values = [bigint_to_text(values[2], false), values[3]];
+    collect_with(&mut values);
+    values[0] = bigint_of_text_radix(values[0], 10);
+    collect_with(&mut values);
+    assert!(bigint_eq(
+        bigint_div(values[0], bigint_of_word32(199)),
+        values[1]
+    ));
+}
+
+/// Round trips through little-endian blobs, on numbers larger than 64 bits
+fn le_blob_prop(a: i64, shift: u8) -> TestResult {
+    unsafe {
+        let shift = u32::from(shift);
+        let n = bigint_shl(bigint_of_int64(a), shift);
+        let magnitude = bigint_abs(n);
+
+        if !bigint_eq(bigint_of_le_blob(bigint_to_le_blob(magnitude)), magnitude) {
+            return TestResult::error(format!("{} << {}", a, shift));
+        }
+
+        // The smallest width, and a wider one
+        let bits = bigint_2complement_bits(n);
+        for &width in &[(bits + 7) / 8, (bits + 7) / 8 + 5] {
+            let blob = bigint_to_signed_le_blob(n, width);
+            if bytes_of_blob(blob).len() != width as usize
+                || !bigint_eq(bigint_of_signed_le_blob(blob), n)
+            {
+                return TestResult::error(format!("{} << {} in {} bytes", a, shift, width));
+            }
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// The text agrees with the digits from repeated division by 10
+fn text_prop(digits: Vec<u32>, negative: bool) -> TestResult {
+    unsafe {
+        let mut n = bigint_of_digits(&digits);
+        if negative {
+            n = bigint_neg(n);
+        }
+
+        let mut expected = vec![];
+        let ten = bigint_of_word32(10);
+        let mut m = bigint_abs(n);
+        loop {
+            expected.push(b'0' + bigint_to_word32_wrap(bigint_rem(m, ten)) as u8);
+            m = bigint_div(m, ten);
+            if bigint_eq(m, bigint_of_word32(0)) {
+                break;
+            }
+        }
+        if bigint_isneg(n) {
+            expected.push(b'-');
+        }
+        expected.reverse();
+
+        if text_string(n, false).as_bytes() != &expected[..] {
+            return TestResult::error(format!("text of {:?}", digits));
+        }
+        TestResult::passed()
+    }
+}
+
+/// Conversion to `f64` agrees with Rust's, which rounds to nearest, ties to even
+fn to_float_prop(a: i64, shift: u8) -> TestResult {
+    let n = i128::from(a) << (shift % 64);
+    let f = unsafe { bigint_to_float64(bigint_of_i128(n)) };
+    if f.to_bits() != (n as f64).to_bits() {
+        return TestResult::error(format!("{} to float: {} instead of {}", n, f, n as f64));
+    }
+    TestResult::passed()
+}
+
+/// Conversion to `f64` agrees with Rust's parsing of the decimal text, which is correctly rounded
+fn large_to_float_prop(digits: Vec<u32>) -> TestResult {
+    unsafe {
+        let n = bigint_of_digits(&digits);
+        let expected: f64 = text_string(n, false).parse().unwrap();
+        if bigint_to_float64(n) != expected {
+            return TestResult::error(format!("{:?} to float", digits));
+        }
+        TestResult::passed()
+    }
+}
+
+/// Conversion from `f64` truncates, and is exact for integral floats
+fn of_float_prop(bits: u64) -> TestResult {
+    let f = f64::from_bits(bits);
+    if !f.is_finite() {
+        return TestResult::discard();
+    }
+    unsafe {
+        let n = bigint_of_float64(f);
+        if bigint_to_float64(n) != f.trunc() {
+            return TestResult::error(format!("{} of float", f));
+        }
+        if f.abs() < 1e30 && !bigint_eq(n, bigint_of_i128(f as i128)) {
+            return TestResult::error(format!("{} of float", f));
+        }
+        TestResult::passed()
+    }
+}
+
+/// Parsing the decimal text gives the number
+fn parse_prop(digits: Vec<u32>, separators: bool) -> TestResult {
+    unsafe {
+        let n = bigint_of_digits(&digits);
+        let text = bigint_to_text(n, separators);
+        for &radix in &[0, 10] {
+            let parsed = bigint_of_text_radix(text, radix);
+            if parsed.0 == 0 || !bigint_eq(parsed, n) {
+                return TestResult::error(format!("parsing {:?} in radix {}", digits, radix));
+            }
+        }
+        TestResult::passed()
+    }
+}
+
+/// Parsing agrees with Rust's formatting, in the radixes with prefixes
+fn parse_radix_prop(a: i64) -> TestResult {
+    let sign = if a < 0 { "-" } else { "" };
+    let m = i128::from(a).abs();
+    // Texts with a prefix also parse with radix 0
+    for &(ref text, radix, prefixed) in &[
+        (format!("{}{:#x}", sign, m), 16, true),
+        (format!("{}{:X}", sign, m), 16, false),
+        (format!("{}{:#o}", sign, m), 8, true),
+        (format!("{}{:#b}", sign, m), 2, true),
+        (format!("{}{:b}", sign, m), 2, false),
+    ] {
+        let radixes = if prefixed {
+            vec![0, radix]
+        } else {
+            vec![radix]
+        };
+        for radix in radixes {
+            if unsafe { !parses_to(text, radix, i128::from(a)) } {
+                return TestResult::error(format!("parsing {:?} in radix {}", text, radix));
+            }
+        }
+    }
+    TestResult::passed()
+}
+
+/// The parsed number, `None` for malformed text
+unsafe fn parse(text: &str, radix: u32) -> Option<SkewedPtr> {
+    let n = bigint_of_text_radix(text_of_str(text), radix);
+    if n.0 == 0 {
+        None
+    } else {
+        Some(n)
+    }
+}
+
+unsafe fn parses_to(text: &str, radix: u32, expected: i128) -> bool {
+    match parse(text, radix) {
+        Some(n) => bigint_eq(n, bigint_of_i128(expected)),
+        None => false,
+    }
+}
+
+unsafe fn text_string(n: SkewedPtr, separators: bool) -> String {
+    string_of_text(bigint_to_text(n, separators))
+}
+
+/// GCD and LCM agree with Euclid's algorithm on `i128`
+fn gcd_prop(a: i64, b: i64) -> TestResult {
+    let (mut x, mut y) = (i128::from(a).abs(), i128::from(b).abs());
+    while y != 0 {
+        let r = x % y;
+        x = y;
+        y = r;
+    }
+    let lcm = if x == 0 {
+        0
+    } else {
+        i128::from(a).abs() / x * i128::from(b).abs()
+    };
+
+    unsafe {
+        let (a, b) = (bigint_of_int64(a), bigint_of_int64(b));
+        if !bigint_eq(bigint_gcd(a, b), bigint_of_i128(x)) {
+            return TestResult::error("gcd");
+        }
+        if !bigint_eq(bigint_lcm(a, b), bigint_of_i128(lcm)) {
+            return TestResult::error("lcm");
+        }
+    }
+    TestResult::passed()
+}
+
+/// GCD agrees with Euclid's algorithm on large numbers with a common factor (times a power of
+/// two), and `gcd(a, b) * lcm(a, b) = a * b`. The numbers have at most 8 digits, as each step of
+/// Euclid's algorithm allocates.
+fn large_gcd_prop(a: Vec<u32>, b: Vec<u32>, c: Vec<u32>, twos: u8) -> TestResult {
+    let digits = |ds: &[u32]| unsafe { bigint_of_digits(&ds[..ds.len().min(8)]) };
+    unsafe {
+        let c = bigint_shl(digits(&c), u32::from(twos));
+        let a = bigint_mul(digits(&a), c);
+        let b = bigint_mul(digits(&b), c);
+
+        let zero = bigint_of_word32(0);
+        let (mut x, mut y) = (a, b);
+        while !bigint_eq(y, zero) {
+            let r = bigint_rem(x, y);
+            x = y;
+            y = r;
+        }
+
+        let gcd = bigint_gcd(a, b);
+        if !bigint_eq(gcd, x) {
+            return TestResult::error("gcd");
+        }
+        if !bigint_eq(bigint_mul(gcd, bigint_lcm(a, b)), bigint_mul(a, b)) {
+            return TestResult::error("lcm");
+        }
+        TestResult::passed()
+    }
+}
+
+/// The square root `r` of `n` satisfies `r^2 <= n < (r+1)^2`
+fn isqrt_prop(n: Vec<u32>) -> TestResult {
+    unsafe {
+        let n = bigint_of_digits(&n);
+        let r = bigint_isqrt(n);
+        let r1 = bigint_add(r, bigint_of_word32(1));
+        if !bigint_le(bigint_mul(r, r), n) || !bigint_lt(n, bigint_mul(r1, r1)) {
+            return TestResult::error("isqrt");
+        }
+        TestResult::passed()
+    }
+}
+
+/// Modular exponentiation agrees with square-and-multiply on `u128`
+fn pow_mod_prop(base: i64, exp: u64, modulus: u64) -> TestResult {
+    if modulus == 0 {
+        return TestResult::discard();
+    }
+
+    let m = u128::from(modulus);
+    let mut b = (i128::from(base).rem_euclid(m as i128)) as u128;
+    let mut e = exp;
+    let mut expected = 1 % m;
+    while e != 0 {
+        if e & 1 != 0 {
+            expected = expected * b % m;
+        }
+        b = b * b % m;
+        e >>= 1;
+    }
+
+    unsafe {
+        let r = bigint_pow_mod(
+            bigint_of_int64(base),
+            bigint_of_word64(exp),
+            bigint_of_word64(modulus),
+        );
+        if !bigint_eq(r, bigint_of_word64(expected as u64)) {
+            return TestResult::error(format!("{} ^ {} mod {}", base, exp, modulus));
+        }
+    }
+
+    TestResult::passed()
+}
+
+/// Operations on small numbers agree with `i128`
+fn arith_prop(a: i64, b: i64) -> TestResult {
+    unsafe {
+        let (x, y) = (bigint_of_int64(a), bigint_of_int64(b));
+        let (a, b) = (i128::from(a), i128::from(b));
+
+        let mut results = vec![
+            ("add", bigint_add(x, y), a + b),
+            ("sub", bigint_sub(x, y), a - b),
+            ("mul", bigint_mul(x, y), a * b),
+            ("neg", bigint_neg(x), -a),
+            ("abs", bigint_abs(x), a.abs()),
+        ];
+        if b != 0 {
+            // Rounded towards zero, the remainder has the sign of the dividend, like `i128`
+            results.push(("div", bigint_div(x, y), a / b));
+            results.push(("rem", bigint_rem(x, y), a % b));
+        }
+
+        for (name, result, expected) in results {
+            if !bigint_eq(result, bigint_of_i128(expected)) {
+                return TestResult::error(format!("{}({}, {})", name, a, b));
+            }
+        }
+
+        let comparisons = [
+            (bigint_eq(x, y), a == b),
+            (bigint_lt(x, y), a < b),
+            (bigint_le(x, y), a <= b),
+            (bigint_gt(x, y), a > b),
+            (bigint_ge(x, y), a >= b),
+            (bigint_isneg(x), a < 0),
+        ];
+        if comparisons.iter().any(|(r, e)| r != e) {
+            return TestResult::error(format!("comparison of {} and {}", a, b));
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// Conversions to words wrap like two's complement, counting bits agrees with `i128`
+fn wrap_prop(a: i64, shift: u8) -> TestResult {
+    unsafe {
+        let shift = u32::from(shift % 64);
+        let x = bigint_lsh(bigint_of_int64(a), shift as i32);
+        let a = i128::from(a) << shift;
+
+        if bigint_to_word64_wrap(x) != a as u64 || bigint_to_word32_wrap(x) != a as u32 {
+            return TestResult::error(format!("wrapping {}", a));
+        }
+
+        if bigint_count_bits(x) as u32 != 128 - a.abs().leading_zeros() {
+            return TestResult::error(format!("bits of {}", a));
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// Bitwise operations agree with `i128`, on numbers larger than 64 bits
+fn bitwise_prop(a: i64, b: i64, shift: u8, index: u8) -> TestResult {
+    unsafe {
+        let (a_shift, b_shift) = (u32::from(shift % 64), u32::from(shift / 4));
+        let x = bigint_shl(bigint_of_int64(a), a_shift);
+        let y = bigint_shl(bigint_of_int64(b), b_shift);
+        let (a, b) = (i128::from(a) << a_shift, i128::from(b) << b_shift);
+        let index = u32::from(index % 128);
+
+        let results = [
+            ("and", bigint_and(x, y), a & b),
+            ("or", bigint_or(x, y), a | b),
+            ("xor", bigint_xor(x, y), a ^ b),
+            ("shr", bigint_shr(x, index), a >> index),
+        ];
+        for (name, result, expected) in results.iter() {
+            if !bigint_eq(*result, bigint_of_i128(*expected)) {
+                return TestResult::error(format!("{}({}, {})", name, a, b));
+            }
+        }
+
+        if bigint_bit_test(x, index) != (a >> index & 1 != 0) {
+            return TestResult::error(format!("bit {} of {}", index, a));
+        }
+
+        // Shifting out all bits leaves the sign
+        let expected = if a < 0 { -1 } else { 0 };
+        if !bigint_eq(bigint_shr(x, 200), bigint_of_i128(expected))
+            || bigint_bit_test(x, 200) != (a < 0)
+        {
+            return TestResult::error(format!("large shift of {}", a));
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// Identities of multiplication and division on large numbers
+fn large_prop(a: Vec<u32>, b: Vec<u32>, c: Vec<u32>) -> TestResult {
+    unsafe {
+        let (a, b, c) = (
+            bigint_of_digits(&a),
+            bigint_of_digits(&b),
+            bigint_of_digits(&c),
+        );
+
+        // Distributivity
+        if !bigint_eq(
+            bigint_mul(a, bigint_add(b, c)),
+            bigint_add(bigint_mul(a, b), bigint_mul(a, c)),
+        ) {
+            return TestResult::error("a * (b + c) != a * b + a * c");
+        }
+
+        // a = q * b + r with 0 <= r < b
+        let b = bigint_add(b, bigint_of_word32(1));
+        let (q, r) = (bigint_div(a, b), bigint_rem(a, b));
+        if !bigint_eq(a, bigint_add(bigint_mul(q, b), r)) || bigint_isneg(r) || !bigint_lt(r, b) {
+            return TestResult::error("a != (a / b) * b + a % b");
+        }
+
+        // Dividing a product
+        let ab = bigint_add(bigint_mul(a, b), c);
+        if !bigint_eq(bigint_div(ab, b), bigint_add(a, bigint_div(c, b))) {
+            return TestResult::error("(a * b + c) / b != a + c / b");
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// The number with the digits (base 2^32), least significant first
+unsafe fn bigint_of_digits(digits: &[u32]) -> SkewedPtr {
+    digits.iter().rev().fold(bigint_of_word32(0), |n, &d| {
+        bigint_add(bigint_lsh(n, 32), bigint_of_word32(d))
+    })
+}
+
+unsafe fn bigint_of_i128(n: i128) -> SkewedPtr {
+    let low = bigint_of_word64(n as u64);
+    let high = bigint_lsh(bigint_of_int64((n >> 64) as i64), 64);
+    bigint_add(high, low)
 }
 
 // Check leb128 encode/decode roundtrip
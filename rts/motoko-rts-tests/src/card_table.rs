@@ -0,0 +1,64 @@
+use crate::static_root;
+
+use motoko_rts::types::{Bytes, SkewedPtr};
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn gc_force();
+    fn generational_gc();
+    fn post_write_barrier(array: SkewedPtr, idx: usize);
+}
+
+pub unsafe fn test() {
+    println!("Testing card tables ...");
+
+    // A major collection, for the generations to start after the heap of the other collectors
+    gc_force();
+    generational_gc();
+
+    // Large enough for a card table (see `MIN_LEN`), with a partial last card
+    test_array(3000);
+
+    // Without a card table the barrier records the location
+    test_array(100);
+
+    println!("OK");
+}
+
+/// Young objects referenced only by an array in the old generation survive a minor collection
+unsafe fn test_array(len: usize) {
+    let array = alloc_array(len);
+    for i in 0..len {
+        array.as_array().set(i, SkewedPtr(i << 1));
+    }
+    (*static_root(0)).field = array;
+
+    // Promoted
+    generational_gc();
+    let array = (*static_root(0)).field;
+
+    let indices = [0, 1, len / 2, len - 1];
+    for (n, idx) in indices.iter().enumerate() {
+        let blob = alloc_blob(Bytes(1));
+        blob.as_blob().set(0, n as u8);
+        array.as_array().set(*idx, blob);
+        post_write_barrier(array, *idx);
+    }
+
+    // Minor collection
+    generational_gc();
+
+    let array = (*static_root(0)).field;
+    for (n, idx) in indices.iter().enumerate() {
+        assert_eq!(array.as_array().get(*idx).as_blob().get(0), n as u8);
+    }
+    for i in 0..len {
+        if !indices.contains(&i) {
+            assert_eq!(array.as_array().get(i).0, i << 1);
+        }
+    }
+
+    (*static_root(0)).field = SkewedPtr(0);
+    generational_gc();
+}
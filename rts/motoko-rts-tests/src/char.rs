@@ -0,0 +1,155 @@
+use motoko_rts::char::{
+    char_fold_case, char_general_category, char_is_alphabetic, char_is_lowercase, char_is_numeric,
+    char_is_uppercase, char_is_whitespace, char_to_lower, char_to_upper,
+};
+
+// General categories, as in `char.rs`
+const LU: u32 = 0;
+const LL: u32 = 1;
+const LO: u32 = 4;
+const MN: u32 = 5;
+const ND: u32 = 8;
+const NL: u32 = 9;
+const NO: u32 = 10;
+const PO: u32 = 17;
+const SO: u32 = 21;
+const ZS: u32 = 22;
+const CC: u32 = 25;
+const CS: u32 = 27;
+const CO: u32 = 28;
+const CN: u32 = 29;
+
+pub unsafe fn test() {
+    println!("Testing character properties ...");
+
+    // (character, category, alphabetic, numeric, white space)
+    for &(c, category, alphabetic, numeric, white_space) in &[
+        ('A' as u32, LU, true, false, false),
+        ('ß' as u32, LL, true, false, false),
+        ('5' as u32, ND, false, true, false),
+        ('!' as u32, PO, false, false, false),
+        (' ' as u32, ZS, false, false, true),
+        ('\t' as u32, CC, false, false, true),
+        (0x3000, ZS, false, false, true),
+        (0x216B, NL, true, true, false),  // Roman numeral twelve
+        (0xBD, NO, false, true, false),   // Vulgar fraction one half
+        (0x345, MN, true, false, false),  // Combining Greek ypogegrammeni
+        (0x24B6, SO, true, false, false), // Circled Latin capital letter A
+        (0x4E00, LO, true, false, false),
+        (0x11BC0, LO, true, false, false), // Sunuwar letter devi, of Unicode 16
+        (0xD800, CS, false, false, false),
+        (0xE000, CO, false, false, false),
+        (0x10FFFD, CO, false, false, false),
+        (0x10FFFF, CN, false, false, false),
+        (0x378, CN, false, false, false),
+    ] {
+        assert_eq!(char_general_category(c), category, "{:#x}", c);
+        assert_eq!(char_is_alphabetic(c) != 0, alphabetic, "{:#x}", c);
+        assert_eq!(char_is_numeric(c) != 0, numeric, "{:#x}", c);
+        assert_eq!(char_is_whitespace(c) != 0, white_space, "{:#x}", c);
+    }
+
+    // (character, uppercase, lowercase)
+    for &(c, uppercase, lowercase) in &[
+        ('A' as u32, true, false),
+        ('ß' as u32, false, true),
+        (0x1C5, false, false), // Latin capital letter D with small z with caron, titlecase
+        (0x2160, true, false), // Roman numeral one
+        (0x345, false, true),  // Combining Greek ypogegrammeni
+        (0xA7DC, true, false), // Latin capital letter lambda with stroke, of Unicode 16
+        ('京' as u32, false, false),
+        ('5' as u32, false, false),
+    ] {
+        assert_eq!(char_is_uppercase(c) != 0, uppercase, "{:#x}", c);
+        assert_eq!(char_is_lowercase(c) != 0, lowercase, "{:#x}", c);
+    }
+
+    // The properties of `core`, of an older Unicode version, but no character lost them since, but
+    // 'ʕ', which is no longer lowercase since Unicode 16
+    for c in (0..=0x10FFFF).filter_map(core::char::from_u32) {
+        let code = c as u32;
+        assert!(
+            !c.is_alphabetic() || char_is_alphabetic(code) != 0,
+            "{:#x}",
+            code
+        );
+        assert!(
+            !c.is_uppercase() || char_is_uppercase(code) != 0,
+            "{:#x}",
+            code
+        );
+        assert!(
+            !c.is_lowercase() || char_is_lowercase(code) != 0 || code == 0x295,
+            "{:#x}",
+            code
+        );
+        assert!(!c.is_numeric() || char_is_numeric(code) != 0, "{:#x}", code);
+        assert_eq!(
+            c.is_whitespace(),
+            char_is_whitespace(code) != 0,
+            "{:#x}",
+            code
+        );
+    }
+
+    // (character, uppercase, lowercase, folded)
+    for &(c, upper, lower, folded) in &[
+        ('a' as u32, 'A' as u32, 'a' as u32, 'a' as u32),
+        ('Ö' as u32, 'Ö' as u32, 'ö' as u32, 'ö' as u32),
+        ('ÿ' as u32, 'Ÿ' as u32, 'ÿ' as u32, 'ÿ' as u32),
+        ('ß' as u32, 'ß' as u32, 'ß' as u32, 'ß' as u32),
+        (0x1E9E, 0x1E9E, 'ß' as u32, 'ß' as u32), // Capital sharp s
+        ('ſ' as u32, 'S' as u32, 'ſ' as u32, 's' as u32),
+        ('ς' as u32, 'Σ' as u32, 'ς' as u32, 'σ' as u32),
+        ('Σ' as u32, 'Σ' as u32, 'σ' as u32, 'σ' as u32),
+        ('µ' as u32, 'Μ' as u32, 'µ' as u32, 'μ' as u32),
+        ('İ' as u32, 'İ' as u32, 'i' as u32, 'İ' as u32),
+        (0x212A, 0x212A, 'k' as u32, 'k' as u32), // Kelvin sign
+        (0x1C5, 0x1C4, 0x1C6, 0x1C6),             // Latin capital letter D with small z with caron
+        (0x1F80, 0x1F88, 0x1F80, 0x1F80), // Greek small letter alpha with psili and ypogegrammeni
+        (0x149, 0x149, 0x149, 0x149),     // Latin small letter n preceded by apostrophe
+        (0xAB70, 0x13A0, 0xAB70, 0x13A0), // Cherokee small letter a
+        (0x13A0, 0x13A0, 0xAB70, 0x13A0),
+        (0x10400, 0x10400, 0x10428, 0x10428), // Deseret capital letter long i
+        (0x1E921, 0x1E921, 0x1E943, 0x1E943), // Adlam capital letter sha
+        (0xA7DC, 0xA7DC, 0x19B, 0x19B),       // Latin capital letter lambda with stroke
+        ('京' as u32, '京' as u32, '京' as u32, '京' as u32),
+        (0x10FFFF, 0x10FFFF, 0x10FFFF, 0x10FFFF),
+    ] {
+        assert_eq!(char_to_upper(c), upper, "{:#x}", c);
+        assert_eq!(char_to_lower(c), lower, "{:#x}", c);
+        assert_eq!(char_fold_case(c), folded, "{:#x}", c);
+    }
+
+    // Mapped as by `core` where both map to single characters (`core` may be of an older Unicode
+    // version, with fewer mappings), and all cases fold to the same character, but the Turkic
+    // dotted capital I and dotless small i, which fold to themselves
+    for c in (0..=0x10FFFF).filter_map(core::char::from_u32) {
+        let code = c as u32;
+        let mut upper = c.to_uppercase();
+        if upper.len() == 1 && upper.clone().next() != Some(c) && char_to_upper(code) != code {
+            assert_eq!(
+                char_to_upper(code),
+                upper.next().unwrap() as u32,
+                "{:#x}",
+                code
+            );
+        }
+        let mut lower = c.to_lowercase();
+        if lower.len() == 1 && lower.clone().next() != Some(c) && char_to_lower(code) != code {
+            assert_eq!(
+                char_to_lower(code),
+                lower.next().unwrap() as u32,
+                "{:#x}",
+                code
+            );
+        }
+        if code == 0x130 || code == 0x131 {
+            continue;
+        }
+        let folded = char_fold_case(code);
+        assert_eq!(char_fold_case(folded), folded, "{:#x}", code);
+        assert_eq!(char_fold_case(char_to_upper(code)), folded, "{:#x}", code);
+        assert_eq!(char_fold_case(char_to_lower(code)), folded, "{:#x}", code);
+    }
+}
@@ -1,5 +1,34 @@
-use motoko_rts::closure_table::{closure_count, recall_closure, remember_closure};
-use motoko_rts::types::SkewedPtr;
+use motoko_rts::closure_table::{
+    closure_age, closure_count, closure_handle, closure_table_max_age, closure_table_size,
+    recall_closure, remember_closure, INDEX_BITS,
+};
+use motoko_rts::types::{size_of, Array, Blob, Bytes, SkewedPtr, Words};
+
+use crate::gc_stress::{collect_with, with_alloc_collections};
+use crate::static_root;
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+    fn generational_gc();
+    fn incremental_gc();
+    fn mark_sweep_gc();
+    fn gc_force();
+    fn get_gc_stats() -> *const RtsGcStats;
+}
+
+/// See `gc.rs`
+#[repr(C)]
+pub(crate) struct RtsGcStats {
+    pub(crate) collections: u64,
+    pub(crate) reclaimed: Bytes<u64>,
+    pub(crate) gc_instructions: u64,
+    pub(crate) max_live_size: Bytes<usize>,
+    pub(crate) heap_size: Bytes<usize>,
+    pub(crate) fragmentation: Bytes<usize>,
+    pub(crate) callback_table_retained: Bytes<usize>,
+}
 
 pub unsafe fn test() {
     println!("Testing closure table ...");
@@ -8,27 +37,166 @@ pub unsafe fn test() {
 
     const N: usize = 2000; // >256, to exercise `double_closure_table`
 
-    let mut references: [u32; N] = [0; N];
+    let mut references: [usize; N] = [0; N];
     for i in 0..N {
         references[i] = remember_closure(SkewedPtr((i << 2).wrapping_sub(1)));
-        assert_eq!(closure_count(), (i + 1) as u32);
+        assert_eq!(closure_count(), i + 1);
     }
 
     for i in 0..N / 2 {
         let c = recall_closure(references[i]);
         assert_eq!(c.0, (i << 2).wrapping_sub(1));
-        assert_eq!(closure_count(), (N - i - 1) as u32);
+        assert_eq!(closure_count(), N - i - 1);
     }
 
     for i in 0..N / 2 {
         references[i] = remember_closure(SkewedPtr((i << 2).wrapping_sub(1)));
-        assert_eq!(closure_count(), (N / 2 + i + 1) as u32);
+        assert_eq!(closure_count(), N / 2 + i + 1);
     }
 
     for i in (0..N).rev() {
         assert_eq!(recall_closure(references[i]).0, (i << 2).wrapping_sub(1));
-        assert_eq!(closure_count(), i as u32);
+        assert_eq!(closure_count(), i);
+    }
+    assert_eq!(closure_table_size(), 256);
+
+    // Handles of reused slots have new generations
+    let handle = remember_closure(SkewedPtr(0xFF));
+    assert_eq!(closure_handle(index(handle)), Some(handle));
+    recall_closure(handle);
+    let new_handle = remember_closure(SkewedPtr(0xFB));
+    assert_eq!(index(new_handle), index(handle));
+    assert_ne!(new_handle, handle);
+    assert_eq!(closure_age(handle), None);
+    assert_eq!(closure_age(new_handle), Some(0));
+    assert_eq!(recall_closure(new_handle).0, 0xFB);
+    assert_eq!(closure_handle(index(handle)), None);
+
+    // Shrinks when the closures left are in the first slots, their indices stay valid
+    for i in 0..N {
+        references[i] = remember_closure(SkewedPtr((i << 2).wrapping_sub(1)));
+        assert_eq!(index(references[i]), i);
+    }
+    assert_eq!(closure_table_size(), 2048);
+
+    // At a quarter of the size, with closures in the upper half, the free list is rebuilt and new
+    // closures take the lowest slots
+    for i in 10..N - 512 + 10 {
+        recall_closure(references[i]);
+    }
+    assert_eq!(closure_count(), 512);
+    let handle = remember_closure(SkewedPtr(0xFF));
+    assert_eq!(index(handle), 10);
+    assert_eq!(recall_closure(handle).0, 0xFF);
+
+    for i in N - 512 + 10..N - 1 {
+        recall_closure(references[i]);
+    }
+    assert_eq!(closure_count(), 11);
+    assert_eq!(closure_table_size(), 2048);
+
+    assert_eq!(closure_age(references[N - 1]), Some(1));
+    assert_eq!(closure_age(references[0]), Some(N));
+    assert_eq!(closure_age(references[10]), None);
+    assert_eq!(closure_table_max_age(), N);
+
+    // Shrinks at the next check, when the closures halved again
+    assert_eq!(recall_closure(references[N - 1]).0, ((N - 1) << 2) - 1);
+    assert_eq!(recall_closure(references[9]).0, (9 << 2) - 1);
+    assert_eq!(closure_table_size(), 2048);
+    assert_eq!(recall_closure(references[8]).0, (8 << 2) - 1);
+    assert_eq!(closure_table_size(), 256);
+    for i in 0..8 {
+        assert_eq!(recall_closure(references[i]).0, (i << 2).wrapping_sub(1));
     }
+    assert_eq!(closure_count(), 0);
+
+    assert_eq!(closure_table_max_age(), 0);
+
+    test_retained();
+
+    with_alloc_collections(|| stress());
 
     println!("OK");
 }
+
+/// Heap memory only retained by the closure table, noted by the collectors in the GC statistics
+unsafe fn test_retained() {
+    let collectors: [(&str, unsafe extern "C" fn()); 4] = [
+        ("copying", collect),
+        ("generational", generational_gc),
+        ("incremental", incremental_gc),
+        ("mark-sweep", mark_sweep_gc),
+    ];
+
+    let closure_size =
+        (size_of::<Array>() + Words(1) + size_of::<Blob>() + Bytes(30usize).to_words()).to_bytes();
+    let shared_size =
+        (size_of::<Array>() + Words(1) + size_of::<Blob>() + Bytes(40usize).to_words()).to_bytes();
+
+    for &(name, collector) in collectors.iter() {
+        // A whole cycle of the incremental GC, a major collection of the generational GC
+        let retained = || {
+            gc_force();
+            collector();
+            (*get_gc_stats()).callback_table_retained
+        };
+
+        assert_eq!(retained(), Bytes(0), "{}", name);
+
+        // A closure only in the closure table, capturing a blob
+        let closure = alloc_array(1);
+        closure.as_array().set(0, alloc_blob(Bytes(30)));
+        let handle = remember_closure(closure);
+
+        // A closure also reachable from a static root
+        let shared = alloc_array(1);
+        shared.as_array().set(0, alloc_blob(Bytes(40)));
+        let shared_handle = remember_closure(shared);
+        (*static_root(0)).field = shared;
+
+        assert_eq!(retained(), closure_size, "{}", name);
+
+        // Only in the closure table when the static root is cleared
+        (*static_root(0)).field = SkewedPtr(0);
+        assert_eq!(retained(), closure_size + shared_size, "{}", name);
+
+        recall_closure(handle);
+        recall_closure(shared_handle);
+        assert_eq!(retained(), Bytes(0), "{}", name);
+    }
+}
+
+/// Closures only reachable from the table, with a collection after every call, see
+/// `gc_stress.rs`
+unsafe fn stress() {
+    const N: usize = 300; // >256, the table grows and shrinks
+
+    let mut handles = vec![];
+    for i in 0..N {
+        let blob = alloc_blob(Bytes(i % 50));
+        let payload = blob.as_blob().payload_addr();
+        std::ptr::write_bytes(payload, i as u8, i % 50);
+        handles.push(remember_closure(blob));
+        collect_with(&mut []);
+    }
+
+    // Every other closure, then the others
+    for &first in &[0, 1] {
+        for i in (first..N).step_by(2) {
+            let blob = recall_closure(handles[i]).as_blob();
+            assert_eq!(blob.len(), Bytes(i % 50));
+            for j in 0..i % 50 {
+                assert_eq!(blob.get(j), i as u8);
+            }
+            collect_with(&mut []);
+        }
+    }
+
+    assert_eq!(closure_count(), 0);
+    assert_eq!(closure_table_size(), 256);
+}
+
+fn index(handle: usize) -> usize {
+    handle & ((1 << INDEX_BITS) - 1)
+}
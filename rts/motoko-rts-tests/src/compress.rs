@@ -0,0 +1,94 @@
+use crate::idl_encode::{array, scalar, MUT_ARRAY, NAT};
+use crate::utils::{read_stable_vars, read_stable_vars_range};
+use crate::STABLE_MEM;
+
+use motoko_rts::buf::StableBuf;
+use motoko_rts::compress::{compress, decompress, max_compressed_len, BLOCK_SIZE};
+use motoko_rts::idl_encode::idl_serialize_stable;
+use motoko_rts::region::{
+    region_size, stable_vars_check, stable_vars_compress, stable_vars_size, stable_vars_stream,
+    FLAG_CHECKSUM, FLAG_COMPRESSED, STABLE_VARS,
+};
+use motoko_rts::stream::Stream;
+
+pub unsafe fn test() {
+    println!("Testing compression ...");
+
+    // Blocks
+    let text = b"stable var log : [Text] = []; stable var log : [Text] = [\"log\", \"log\"];";
+    let repeated: Vec<u8> = text.iter().cycle().take(BLOCK_SIZE).cloned().collect();
+    let compressed = check_block(&repeated);
+    assert!(compressed < repeated.len() / 10);
+
+    let noise: Vec<u8> = (0..BLOCK_SIZE as u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+    check_block(&noise);
+    check_block(b"");
+    check_block(b"short");
+    check_block(&[0; 300]);
+
+    let mut dst = vec![0; BLOCK_SIZE];
+    assert_eq!(decompress(b"", &mut dst), None);
+    // A match before the beginning
+    assert_eq!(decompress(b"\x14a\x05\x00", &mut dst), None);
+    // Literals beyond the block
+    assert_eq!(decompress(b"\x50ab", &mut dst), None);
+
+    // Stable variables, compressed in blocks
+    let data: Vec<u8> = repeated
+        .iter()
+        .chain(noise.iter())
+        .chain(repeated[..100].iter())
+        .cloned()
+        .collect();
+    stable_vars_compress();
+    let mut stream = stable_vars_stream(data.len() as u32, false);
+    stream.write(&data[..10]);
+    stream.write(&data[10..]);
+    stream.flush();
+    assert_eq!(stream.written(), data.len());
+    assert_eq!(STABLE_MEM[20], (FLAG_COMPRESSED | FLAG_CHECKSUM) as u8);
+    assert!(stable_vars_check().is_ok());
+    assert_eq!(stable_vars_size() as usize, data.len());
+    assert_eq!(read_stable_vars(), data);
+    // Reads across blocks, and reads before the last block read
+    assert_eq!(
+        read_stable_vars_range(BLOCK_SIZE - 3, 10),
+        &data[BLOCK_SIZE - 3..BLOCK_SIZE + 7]
+    );
+    assert_eq!(read_stable_vars_range(2, 3), &data[2..5]);
+
+    let mut buf = StableBuf::new(STABLE_VARS, 0, data.len() as u64);
+    let mut read = vec![0u8; data.len()];
+    buf.read(read.as_mut_ptr(), 100);
+    for byte in read[100..].iter_mut() {
+        *byte = buf.read_byte();
+    }
+    assert_eq!(read, data);
+
+    // Only the stable variables written next are compressed
+    let desc: Vec<u8> = [MUT_ARRAY, 2, NAT]
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    let tydesc = b"DIDL";
+    let pages = region_size(STABLE_VARS);
+    idl_serialize_stable(desc.as_ptr(), 3, array(&[scalar(5)]), tydesc.as_ptr(), 4);
+    assert_eq!(STABLE_MEM[20], FLAG_CHECKSUM as u8);
+    assert_eq!(
+        read_stable_vars(),
+        b"DIDL\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x05"
+    );
+    assert_eq!(region_size(STABLE_VARS), pages);
+}
+
+/// Checks that the block decompresses to itself, returns its compressed length
+fn check_block(block: &[u8]) -> usize {
+    let mut compressed = vec![0; max_compressed_len(block.len())];
+    let len = compress(block, &mut compressed);
+    let mut dst = vec![0; BLOCK_SIZE];
+    assert_eq!(decompress(&compressed[..len], &mut dst), Some(block.len()));
+    assert_eq!(&dst[..block.len()], block);
+    len
+}
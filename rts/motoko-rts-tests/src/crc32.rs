@@ -1,17 +1,60 @@
-use motoko_rts::principal_id::{base32_of_checksummed_blob, base32_to_blob};
-use motoko_rts::text::{text_compare, text_of_ptr_size};
+use crate::utils::{blob_of_bytes, bytes_of_blob};
+
+use motoko_rts::crc::{blob_crc16, blob_crc32c, blob_crc8, compute_crc32};
+use motoko_rts::principal_id::{
+    base32_of_checksummed_blob, base32_of_checksummed_blob_to_stream, base32_of_checksummed_len,
+    base32_to_blob,
+};
+use motoko_rts::stream::Stream;
+use motoko_rts::text::{blob_of_text, text_compare, text_concat, text_of_ptr_size, text_of_str};
 use motoko_rts::types::Bytes;
 
+use quickcheck::{quickcheck, TestResult};
+
+/// Collects the chunks written to it
+struct Chunks(Vec<Vec<u8>>);
+
+impl Stream for Chunks {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.push(bytes.to_vec());
+    }
+
+    fn written(&self) -> usize {
+        self.0.iter().map(|chunk| chunk.len()).sum()
+    }
+}
+
 pub unsafe fn test() {
     println!("Testing crc32 ...");
 
+    //
+    // Check values of the catalogue of parametrised CRC algorithms
+    //
+
+    let check = blob_of_text(text_of_str("123456789"));
+    assert_eq!(compute_crc32(check), 0xcbf4_3926);
+    assert_eq!(blob_crc32c(check), 0xe306_9283);
+    assert_eq!(blob_crc16(check), 0x29b1);
+    assert_eq!(blob_crc8(check), 0xf4);
+
+    let empty = blob_of_text(text_of_str(""));
+    assert_eq!(compute_crc32(empty), 0);
+    assert_eq!(blob_crc32c(empty), 0);
+    assert_eq!(blob_crc16(empty), 0xffff);
+    assert_eq!(blob_crc8(empty), 0);
+
+    quickcheck(crc_text_prop as fn(Vec<String>) -> TestResult);
+
     //
     // Encoding
     //
 
     assert_eq!(
         text_compare(
-            base32_of_checksummed_blob(text_of_ptr_size(b"abcdefghijklmnop".as_ptr(), Bytes(16))),
+            base32_of_checksummed_blob(blob_of_text(text_of_ptr_size(
+                b"abcdefghijklmnop".as_ptr(),
+                Bytes(16)
+            ))),
             text_of_ptr_size(b"SQ5MBE3BMJRWIZLGM5UGS2TLNRWW433Q".as_ptr(), Bytes(32))
         ),
         0
@@ -19,19 +62,28 @@ pub unsafe fn test() {
 
     assert_eq!(
         text_compare(
-            base32_of_checksummed_blob(text_of_ptr_size(b"abcdefghijklmnop".as_ptr(), Bytes(16))),
+            base32_of_checksummed_blob(blob_of_text(text_of_ptr_size(
+                b"abcdefghijklmnop".as_ptr(),
+                Bytes(16)
+            ))),
             text_of_ptr_size(b"SQ5MBE3BMJRWIZLGM5UGS2TLNRWW433Q".as_ptr(), Bytes(32))
         ),
         0
     );
 
+    //
+    // Streaming encoding
+    //
+
+    quickcheck(stream_prop as fn(Vec<u8>) -> TestResult);
+
     //
     // Decoding
     //
 
     assert_eq!(
         text_compare(
-            base32_to_blob(text_of_ptr_size(b"".as_ptr(), Bytes(0))),
+            base32_to_blob(blob_of_text(text_of_ptr_size(b"".as_ptr(), Bytes(0)))),
             text_of_ptr_size(b"".as_ptr(), Bytes(0))
         ),
         0
@@ -39,7 +91,10 @@ pub unsafe fn test() {
 
     assert_eq!(
         text_compare(
-            base32_to_blob(text_of_ptr_size(b"GEZDGNBVGY3TQOI".as_ptr(), Bytes(15))),
+            base32_to_blob(blob_of_text(text_of_ptr_size(
+                b"GEZDGNBVGY3TQOI".as_ptr(),
+                Bytes(15)
+            ))),
             text_of_ptr_size(b"123456789".as_ptr(), Bytes(9))
         ),
         0
@@ -47,10 +102,10 @@ pub unsafe fn test() {
 
     assert_eq!(
         text_compare(
-            base32_to_blob(text_of_ptr_size(
+            base32_to_blob(blob_of_text(text_of_ptr_size(
                 b"MFRGGZDFMZTWQ2LKNNWG23TPOA".as_ptr(),
                 Bytes(26)
-            )),
+            ))),
             text_of_ptr_size(b"abcdefghijklmnop".as_ptr(), Bytes(16))
         ),
         0
@@ -58,9 +113,74 @@ pub unsafe fn test() {
 
     assert_eq!(
         text_compare(
-            base32_to_blob(text_of_ptr_size(b"em77e-bvlzu-aq".as_ptr(), Bytes(14))),
+            base32_to_blob(blob_of_text(text_of_ptr_size(
+                b"em77e-bvlzu-aq".as_ptr(),
+                Bytes(14)
+            ))),
             text_of_ptr_size(b"\x23\x3f\xf2\x06\xab\xcd\x01".as_ptr(), Bytes(7))
         ),
         0
     );
 }
+
+/// Streaming encoding writes the characters of `base32_of_checksummed_blob` in chunks of at most 64
+fn stream_prop(bytes: Vec<u8>) -> TestResult {
+    unsafe {
+        let blob = blob_of_bytes(&bytes);
+        let mut chunks = Chunks(vec![]);
+        base32_of_checksummed_blob_to_stream(blob, &mut chunks);
+
+        if chunks.0.iter().any(|chunk| chunk.len() > 64) {
+            return TestResult::error(format!("chunk too large for {:?}", bytes));
+        }
+
+        let streamed = chunks.0.concat();
+        if streamed.len() != base32_of_checksummed_len(Bytes(bytes.len())) {
+            return TestResult::error(format!("wrong length for {:?}", bytes));
+        }
+
+        let expected = base32_of_checksummed_blob(blob);
+        if bytes_of_blob(expected) != streamed {
+            return TestResult::error(format!("streamed differently for {:?}", bytes));
+        }
+    }
+
+    TestResult::passed()
+}
+
+/// The CRCs of a concatenation are those of the flat text
+fn crc_text_prop(strs: Vec<String>) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for s in &strs {
+            text = text_concat(text, text_of_str(s));
+        }
+        let flat = blob_of_text(text_of_str(&strs.concat()));
+
+        if blob_crc32c(text) != crc32c_bitwise(&strs.concat())
+            || blob_crc32c(text) != blob_crc32c(flat)
+            || blob_crc16(text) != blob_crc16(flat)
+            || blob_crc8(text) != blob_crc8(flat)
+        {
+            return TestResult::error(format!("CRCs of {:?}", strs));
+        }
+    }
+
+    TestResult::passed()
+}
+
+/// CRC-32C, one bit at a time
+fn crc32c_bitwise(s: &str) -> u32 {
+    let mut crc = !0u32;
+    for &byte in s.as_bytes() {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,9 @@
+use motoko_rts::error_code::ErrorCode;
+
+pub unsafe fn test() {
+    println!("Testing error codes ...");
+
+    assert_eq!(&ErrorCode::Internal.tag(), b"[E0001]");
+    assert_eq!(&ErrorCode::IdlDecode.tag(), b"[E0002]");
+    assert_eq!(&ErrorCode::StableMemory.tag(), b"[E0008]");
+}
@@ -0,0 +1,44 @@
+use motoko_rts::features::*;
+
+use std::slice::from_raw_parts;
+
+pub unsafe fn test() {
+    println!("Testing features ...");
+
+    assert_eq!(gc_algorithm(), GcAlgorithm::Unknown);
+
+    let features = &*get_features();
+    assert_eq!(string(features.version, features.version_len.0), VERSION);
+    assert_eq!(string(features.gc, features.gc_len.0), "unknown");
+    assert_eq!(features.word_size as usize, std::mem::size_of::<usize>());
+
+    // The tests are built with these features, see `Cargo.toml`
+    let rts_features = FEATURE_HEAP_SANITY | FEATURE_REDZONES | FEATURE_FREE_LIST;
+    assert_eq!(features.flags, rts_features);
+
+    // Bits of the RTS features are not taken from the compiler
+    set_compiler_features(
+        GcAlgorithm::Incremental as u32,
+        FEATURE_GLOBAL_TIMER | FEATURE_TRAP_BACKTRACE | FEATURE_HEAP_SANITY,
+    );
+    let features = &*get_features();
+    assert_eq!(gc_algorithm(), GcAlgorithm::Incremental);
+    assert_eq!(string(features.gc, features.gc_len.0), "incremental");
+    assert_eq!(
+        features.flags,
+        rts_features | FEATURE_GLOBAL_TIMER | FEATURE_TRAP_BACKTRACE
+    );
+
+    set_compiler_features(GcAlgorithm::MarkSweep as u32, 0);
+    let features = &*get_features();
+    assert_eq!(string(features.gc, features.gc_len.0), "mark-sweep");
+    assert_eq!(features.flags, rts_features);
+
+    // Unknown collectors
+    set_compiler_features(100, 0);
+    assert_eq!(gc_algorithm(), GcAlgorithm::Unknown);
+}
+
+unsafe fn string(ptr: *const u8, len: usize) -> &'static str {
+    std::str::from_utf8(from_raw_parts(ptr, len)).unwrap()
+}
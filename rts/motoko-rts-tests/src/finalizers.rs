@@ -0,0 +1,16 @@
+use motoko_rts::finalizers::{pending_finalizers, register_finalizer};
+use motoko_rts::text::text_of_str;
+
+pub unsafe fn test() {
+    println!("Testing finalizers ...");
+
+    // More than the initial table size, to exercise growing the table
+    for _ in 0..100 {
+        register_finalizer(text_of_str("object"), text_of_str("closure"));
+    }
+
+    // Without a GC nothing is finalized
+    assert_eq!(pending_finalizers(), 0);
+
+    println!("OK");
+}
@@ -0,0 +1,209 @@
+use motoko_rts::float::{float_fmt, float_to_text};
+use motoko_rts::text::text_size;
+use motoko_rts::text::text_to_buf;
+use motoko_rts::types::SkewedPtr;
+
+use quickcheck::{quickcheck, TestResult};
+
+extern "C" {
+    // Of the libc of the host, to compare with
+    fn snprintf(buf: *mut u8, len: usize, fmt: *const u8, ...) -> i32;
+}
+
+pub unsafe fn test() {
+    println!("Testing float formatting ...");
+
+    // Shortest
+    for (f, text) in &[
+        (0.0, "0"),
+        (-0.0, "-0"),
+        (1.0, "1"),
+        (-42.0, "-42"),
+        (0.1, "0.1"),
+        (2.1, "2.1"),
+        (1000000.12345, "1000000.12345"),
+        (1e20, "100000000000000000000"),
+        (1e21, "1e+21"),
+        (0.000001, "0.000001"),
+        (1.5e-7, "1.5e-7"),
+        (f64::MAX, "1.7976931348623157e+308"),
+        (5e-324, "5e-324"),
+        (f64::INFINITY, "inf"),
+        (f64::NEG_INFINITY, "-inf"),
+        (f64::NAN, "nan"),
+    ] {
+        assert_eq!(text_of(float_to_text(*f)), *text);
+    }
+
+    // `printf`, as in `test/run/float-fmt.mo`
+    let pi = std::f64::consts::PI;
+    for (mode, texts) in &[
+        (
+            0,
+            [
+                "3",
+                "3.1",
+                "3.1416",
+                "3.141592654",
+                "3.14159265358979311600",
+            ],
+        ),
+        (
+            1,
+            [
+                "3e+00",
+                "3.1e+00",
+                "3.1416e+00",
+                "3.141592654e+00",
+                "3.14159265358979311600e+00",
+            ],
+        ),
+        (2, ["3", "3", "3.142", "3.14159265", "3.141592653589793116"]),
+        (
+            3,
+            [
+                "0x2p+1",
+                "0x1.9p+1",
+                "0x1.9220p+1",
+                "0x1.921fb5444p+1",
+                "0x1.921fb54442d180000000p+1",
+            ],
+        ),
+    ] {
+        for (prec, text) in [0, 1, 4, 9, 20].iter().zip(texts.iter()) {
+            assert_eq!(text_of(fmt(pi, *prec, *mode)), *text);
+        }
+    }
+
+    // Engineering
+    for (f, prec, text) in &[
+        (12345.678, 2, "12.35e+03"),
+        (999.96, 1, "1.0e+03"),
+        (0.00012345, 3, "123.450e-06"),
+        (0.0, 2, "0.00e+00"),
+        (-1.5, 0, "-2e+00"),
+        (1e21, 0, "1e+21"),
+        (1e22, 0, "10e+21"),
+        (1e23, 0, "100e+21"),
+    ] {
+        assert_eq!(text_of(fmt(*f, *prec, 4)), *text);
+    }
+
+    // Hexadecimal with the digits it needs
+    for (f, text) in &[
+        (pi, "0x1.921fb54442d18p+1"),
+        (1.0, "0x1p+0"),
+        (-0.5, "-0x1p-1"),
+        (0.0, "0x0p+0"),
+        (5e-324, "0x1p-1074"),
+    ] {
+        assert_eq!(text_of(fmt(*f, 0, 5)), *text);
+    }
+
+    // Ties are rounded to even
+    assert_eq!(text_of(fmt(0.5, 0, 0)), "0");
+    assert_eq!(text_of(fmt(2.5, 0, 0)), "2");
+    assert_eq!(text_of(fmt(0.125, 2, 0)), "0.12");
+    assert_eq!(text_of(fmt(9.5, 0, 1)), "1e+01");
+
+    quickcheck(shortest_prop as fn(u64) -> TestResult);
+    quickcheck(printf_prop as fn(u64, u8, u8) -> TestResult);
+    quickcheck(printf_small_prop as fn(i32, u8, u8) -> TestResult);
+}
+
+unsafe fn fmt(f: f64, prec: u32, mode: u32) -> SkewedPtr {
+    // Nat8 arguments are in the highest byte
+    float_fmt(f, prec << 24, mode << 24)
+}
+
+unsafe fn text_of(text: SkewedPtr) -> String {
+    let mut buf = vec![0u8; text_size(text).0];
+    text_to_buf(text, buf.as_mut_ptr());
+    String::from_utf8(buf).unwrap()
+}
+
+/// The shortest text reads back as the float, with the significant digits of the shortest
+/// exponential notation of Rust
+fn shortest_prop(bits: u64) -> TestResult {
+    let f = f64::from_bits(bits);
+    if !f.is_finite() {
+        return TestResult::discard();
+    }
+    let text = unsafe { text_of(float_to_text(f)) };
+    if text.parse::<f64>() != Ok(f) {
+        return TestResult::error(format!("{} reads back as {:?}", text, text.parse::<f64>()));
+    }
+    let digits = |s: &str| -> String {
+        let mantissa = s.split('e').next().unwrap();
+        let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+        digits.trim_matches('0').to_string()
+    };
+    if digits(&text) != digits(&format!("{:e}", f)) {
+        return TestResult::error(format!("{} is not the shortest text of {:e}", text, f));
+    }
+    TestResult::passed()
+}
+
+/// The modes of `float_fmt` print as `printf` of the host
+fn printf_prop(bits: u64, prec: u8, mode: u8) -> TestResult {
+    printf_check(f64::from_bits(bits), prec, mode)
+}
+
+/// As `printf_prop`, for floats of magnitudes where fixed and exponential notations mix
+fn printf_small_prop(n: i32, prec: u8, mode: u8) -> TestResult {
+    printf_check(f64::from(n) / 1024.0, prec, mode)
+}
+
+fn printf_check(f: f64, prec: u8, mode: u8) -> TestResult {
+    let prec = prec % 30;
+    let mode: u8 = [0, 1, 2, 3, 5][mode as usize % 5];
+    if mode >= 3 && f != 0.0 && !f.is_normal() {
+        // glibc prints subnormals as `0x0.…p-1022`, musl (as we do) normalizes them
+        return TestResult::discard();
+    }
+    let mut buf = vec![0u8; 1000];
+    let len = unsafe {
+        match mode {
+            0 => snprintf(
+                buf.as_mut_ptr(),
+                buf.len(),
+                b"%.*f\0".as_ptr(),
+                i32::from(prec),
+                f,
+            ),
+            1 => snprintf(
+                buf.as_mut_ptr(),
+                buf.len(),
+                b"%.*e\0".as_ptr(),
+                i32::from(prec),
+                f,
+            ),
+            2 => snprintf(
+                buf.as_mut_ptr(),
+                buf.len(),
+                b"%.*g\0".as_ptr(),
+                i32::from(prec),
+                f,
+            ),
+            3 => snprintf(
+                buf.as_mut_ptr(),
+                buf.len(),
+                b"%.*a\0".as_ptr(),
+                i32::from(prec),
+                f,
+            ),
+            _ => snprintf(buf.as_mut_ptr(), buf.len(), b"%a\0".as_ptr(), f),
+        }
+    };
+    buf.truncate(len as usize);
+    let expected = String::from_utf8(buf).unwrap();
+
+    let text = unsafe { text_of(fmt(f, u32::from(prec), u32::from(mode))) };
+    if text != expected {
+        return TestResult::error(format!(
+            "float_fmt({:?}, {}, {}) = {}, expected {}",
+            f, prec, mode, text, expected
+        ));
+    }
+    TestResult::passed()
+}
@@ -0,0 +1,103 @@
+use motoko_rts::float_parse::{float_of_text, parse_float};
+use motoko_rts::text::text_of_str;
+use motoko_rts::types::{Bits64, TAG_BITS64};
+
+use quickcheck::{quickcheck, TestResult};
+
+pub unsafe fn test() {
+    println!("Testing float parsing ...");
+
+    for (text, f) in &[
+        ("0", 0.0),
+        ("1", 1.0),
+        ("+1.5", 1.5),
+        ("-.5", -0.5),
+        ("5.", 5.0),
+        ("007", 7.0),
+        ("0.1", 0.1),
+        ("1e10", 1e10),
+        ("1E+10", 1e10),
+        ("2.5e-3", 0.0025),
+        ("1e22", 1e22),
+        ("1e23", 1e23),
+        ("9007199254740993", 9007199254740992.0),
+        ("1.7976931348623157e308", f64::MAX),
+        ("1.7976931348623158e308", f64::MAX),
+        ("1.7976931348623159e308", f64::INFINITY),
+        ("2.2250738585072014e-308", f64::MIN_POSITIVE),
+        ("2.2250738585072011e-308", 2.225073858507201e-308),
+        ("4.9e-324", 5e-324),
+        ("2.4703282292062327e-324", 0.0),
+        ("2.4703282292062328e-324", 5e-324),
+        ("1e400", f64::INFINITY),
+        ("1e-400", 0.0),
+        ("0e999999999999", 0.0),
+        ("inf", f64::INFINITY),
+        ("-Infinity", f64::NEG_INFINITY),
+    ] {
+        assert_eq!(parse_float(text.as_bytes()), Some(*f), "{}", text);
+    }
+
+    assert_eq!(
+        parse_float(b"-0").map(f64::to_bits),
+        Some((-0.0f64).to_bits())
+    );
+    assert!(parse_float(b"NaN").unwrap().is_nan());
+
+    // Halfway between two floats, decided by the last of many digits
+    let halfway = format!("9007199254740993.{}", "0".repeat(800));
+    assert_eq!(parse_float(halfway.as_bytes()), Some(9007199254740992.0));
+    let above = format!("{}1", halfway);
+    assert_eq!(parse_float(above.as_bytes()), Some(9007199254740994.0));
+    let below = format!("9007199254740992.{}e0", "9".repeat(800));
+    assert_eq!(parse_float(below.as_bytes()), Some(9007199254740992.0));
+
+    for text in &[
+        "", "-", "+", ".", "e5", ".e5", "1e", "1e+", "1.2.3", " 1", "1 ", "0x10", "1_000", "--1",
+        "infx", "nan1", "1,5",
+    ] {
+        assert_eq!(parse_float(text.as_bytes()), None, "{}", text);
+    }
+
+    // Boxed for the generated code
+    let boxed = float_of_text(text_of_str("-2.5"));
+    assert_eq!(boxed.tag(), TAG_BITS64);
+    let bits = (*(boxed.unskew() as *const Bits64)).bits;
+    assert_eq!(f64::from_bits(bits), -2.5);
+    assert_eq!(float_of_text(text_of_str("2.5 ")).0, 0);
+
+    quickcheck(round_trip_prop as fn(u64) -> TestResult);
+    quickcheck(decimal_prop as fn(u64, i16) -> bool);
+    quickcheck(digits_prop as fn(Vec<u8>, i16) -> bool);
+}
+
+/// Floats read back from their shortest and their exact decimal notations
+fn round_trip_prop(bits: u64) -> TestResult {
+    let f = f64::from_bits(bits);
+    if !f.is_finite() {
+        return TestResult::discard();
+    }
+    for text in &[format!("{:e}", f), format!("{}", f), format!("{:?}", f)] {
+        if parse_float(text.as_bytes()).map(f64::to_bits) != Some(bits) {
+            return TestResult::error(format!("{} does not read back as {:e}", text, f));
+        }
+    }
+    TestResult::passed()
+}
+
+/// Decimal numbers are parsed as by Rust
+fn decimal_prop(mantissa: u64, exponent: i16) -> bool {
+    let text = format!("{}e{}", mantissa, exponent % 400);
+    parse_float(text.as_bytes()) == text.parse::<f64>().ok()
+}
+
+/// Longer decimal numbers are parsed as by Rust
+fn digits_prop(digits: Vec<u8>, exponent: i16) -> bool {
+    let digits: String = digits
+        .iter()
+        .take(300)
+        .map(|d| (b'0' + d % 10) as char)
+        .collect();
+    let text = format!("0.{}e{}", digits, exponent % 400);
+    parse_float(text.as_bytes()) == text.parse::<f64>().ok()
+}
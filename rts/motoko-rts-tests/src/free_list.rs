@@ -0,0 +1,119 @@
+use crate::static_root;
+
+use motoko_rts::types::{Bytes, SkewedPtr, WORD_SIZE};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn free_object(obj: SkewedPtr);
+    fn collect();
+    fn get_heap_size() -> Bytes<usize>;
+    fn get_free_list_stats() -> *const FreeListStats;
+}
+
+/// See `alloc/free_list.rs`
+#[repr(C)]
+struct FreeListStats {
+    free: usize,
+    chunks: usize,
+    reused: u64,
+    freed: u64,
+    merged: u64,
+}
+
+/// Size of the allocation of a blob, with the redzones (the tests enable them)
+fn allocation_size(len: usize) -> usize {
+    (2 + (len + WORD_SIZE - 1) / WORD_SIZE + 4) * WORD_SIZE
+}
+
+/// Beginning of the allocation of an object, before its redzone
+fn allocation(obj: SkewedPtr) -> usize {
+    obj.unskew() - 2 * WORD_SIZE
+}
+
+unsafe fn heap_end() -> usize {
+    crate::HEAP_BASE + get_heap_size().0
+}
+
+pub unsafe fn test() {
+    println!("Testing free lists ...");
+
+    // Collections empty the free lists
+    collect();
+    let stats = &*get_free_list_stats();
+    assert_eq!(stats.chunks, 0);
+    assert_eq!(stats.free, 0);
+
+    let (reused, freed, merged) = (stats.reused, stats.freed, stats.merged);
+    let size = allocation_size(100);
+
+    let a = alloc_blob(Bytes(100));
+    let b = alloc_blob(Bytes(100));
+    let c = alloc_blob(Bytes(100));
+    let d = alloc_blob(Bytes(100));
+
+    free_object(b);
+    assert_eq!(stats.chunks, 1);
+    assert_eq!(stats.free, size);
+    assert_eq!(stats.freed, freed + size as u64);
+
+    // Merged with the chunk after it
+    free_object(a);
+    assert_eq!(stats.chunks, 1);
+    assert_eq!(stats.free, 2 * size);
+    assert_eq!(stats.merged, merged + 1);
+
+    // Allocated at the beginning of the chunk, the rest stays free
+    let e = alloc_blob(Bytes(100));
+    assert_eq!(e.unskew(), a.unskew());
+    assert_eq!(stats.chunks, 1);
+    assert_eq!(stats.free, size);
+    assert_eq!(stats.reused, reused + size as u64);
+
+    // Given back to the bump allocator at the end of the heap
+    free_object(d);
+    assert_eq!(heap_end(), allocation(d));
+    free_object(c);
+    assert_eq!(heap_end(), allocation(c));
+
+    // Not merged with the chunk before it
+    assert_eq!(stats.chunks, 1);
+
+    collect();
+    assert_eq!(stats.chunks, 0);
+
+    // Large objects allocated from the free lists, below other large objects
+    let l1 = alloc_blob(Bytes(70_000));
+    let l2 = alloc_blob(Bytes(70_000));
+    alloc_blob(Bytes(10));
+    free_object(l1);
+    let l3 = alloc_blob(Bytes(70_000));
+    assert_eq!(l3.unskew(), l1.unskew());
+
+    fill(l2, 2);
+    fill(l3, 3);
+    (*static_root(0)).field = l2;
+    (*static_root(1)).field = l3;
+    collect();
+    check((*static_root(0)).field, 2);
+    check((*static_root(1)).field, 3);
+
+    (*static_root(0)).field = SkewedPtr(0);
+    (*static_root(1)).field = SkewedPtr(0);
+    collect();
+
+    println!("OK");
+}
+
+unsafe fn fill(blob: SkewedPtr, byte: u8) {
+    let blob = blob.as_blob();
+    for i in 0..blob.len().0 {
+        blob.set(i, byte);
+    }
+}
+
+unsafe fn check(blob: SkewedPtr, byte: u8) {
+    let blob = blob.as_blob();
+    for i in 0..blob.len().0 {
+        assert_eq!(blob.get(i), byte);
+    }
+}
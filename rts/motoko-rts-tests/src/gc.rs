@@ -0,0 +1,313 @@
+//! GC tests. The RTS is built with the `heap_sanity` feature, so the heap is checked after each
+//! collection. Here we check that the objects reachable from the static roots survive with their
+//! contents.
+
+use crate::closure_table::RtsGcStats;
+use crate::{static_root, N_STATIC_ROOTS};
+
+use motoko_rts::types::*;
+
+use std::collections::HashMap;
+
+extern "C" {
+    fn collect();
+    fn generational_gc();
+    fn incremental_gc();
+    fn set_compaction_threshold(threshold: u32);
+    fn mark_sweep_gc();
+    fn gc_force();
+    fn write_barrier(location: *mut SkewedPtr);
+    fn generational_write_barrier(location: *mut SkewedPtr);
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn get_gc_stats() -> *const RtsGcStats;
+    fn get_heap_size() -> Bytes<usize>;
+    fn get_incremental_gc_work() -> Words<usize>;
+    fn set_gc_schedule(target_utilization: u32, min_interval: u32, max_interval: u32);
+}
+
+/// Work done in an increment of the incremental GC, `INCREMENT` in `gc/incremental.rs`
+const INCREMENT: usize = 1 << 20;
+
+/// Rounds of mutation and collection per collector
+const ROUNDS: u32 = 20;
+
+/// Objects allocated per round, also the number of fields updated
+const ALLOCS_PER_ROUND: u32 = 200;
+
+#[derive(Clone, Copy)]
+enum Strategy {
+    Copying,
+    Generational,
+    Incremental,
+    /// The incremental GC, leaving regions with little garbage in place
+    PartialCompaction,
+    MarkSweep,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Value {
+    Scalar(u32),
+    /// Index of an array in `Heap::arrays`
+    Ref(usize),
+}
+
+/// The expected contents of the heap. All objects are arrays, the objects allocated as garbage are
+/// not recorded.
+struct Heap {
+    strategy: Strategy,
+    arrays: Vec<Vec<Value>>,
+    roots: [Value; N_STATIC_ROOTS],
+    rng: u32,
+}
+
+pub unsafe fn test() {
+    println!("Testing garbage collection ...");
+
+    test_incremental_work();
+
+    // The mark-sweep GC is last, as the other collectors don't know about its free lists
+    for strategy in [
+        Strategy::Copying,
+        Strategy::Incremental,
+        Strategy::PartialCompaction,
+        Strategy::Generational,
+        Strategy::MarkSweep,
+    ]
+    .iter()
+    {
+        let mut heap = Heap {
+            strategy: *strategy,
+            arrays: vec![],
+            roots: [Value::Scalar(0); N_STATIC_ROOTS],
+            rng: 1,
+        };
+
+        for i in 0..N_STATIC_ROOTS {
+            heap.write(&mut (*static_root(i)).field, SkewedPtr(0));
+        }
+
+        if let Strategy::PartialCompaction = strategy {
+            set_compaction_threshold(50);
+        }
+
+        for round in 0..ROUNDS {
+            let addrs = heap.check();
+            heap.mutate(&addrs);
+
+            // Force some of the collections, so that the incremental GC finishes its cycles and the
+            // generational GC does major collections too
+            if round % 2 == 1 {
+                gc_force();
+            }
+
+            heap.collect();
+        }
+
+        heap.check();
+
+        set_compaction_threshold(0);
+    }
+
+    println!("OK");
+}
+
+/// A cycle of the incremental GC on a heap larger than an increment takes several increments, each
+/// doing at most `INCREMENT` words of work and the object it ends with, and moves nothing
+unsafe fn test_incremental_work() {
+    // Start with a compacted heap, and start a cycle at every scheduling point
+    gc_force();
+    incremental_gc();
+    set_gc_schedule(67, 1, 1);
+
+    // Blobs with their index, in arrays of `LEN`, the largest objects, with garbage between them
+    const ARRAYS: usize = 200;
+    const LEN: usize = 1000;
+    let arrays = alloc_array(ARRAYS);
+    for i in 0..ARRAYS {
+        let array = alloc_array(LEN);
+        for j in 0..LEN {
+            alloc_blob(Bytes(WORD_SIZE));
+            array.as_array().set(j, index_blob(i * LEN + j));
+        }
+        arrays.as_array().set(i, array);
+    }
+    (*static_root(0)).field = arrays;
+    let array_addr = arrays.as_array().get(0).unskew();
+    assert!(get_heap_size().to_words().0 > 2 * INCREMENT);
+
+    let collections = (*get_gc_stats()).collections;
+    let mut increments = 0;
+    while (*get_gc_stats()).collections == collections {
+        incremental_gc();
+        increments += 1;
+        assert!(get_incremental_gc_work().0 <= INCREMENT + 2 + LEN);
+        assert_eq!((*static_root(0)).field.0, arrays.0);
+        assert_eq!(arrays.as_array().get(0).unskew(), array_addr);
+
+        // Replace a blob, with the same index, between the increments
+        let (i, j) = (increments % ARRAYS, increments * 7 % LEN);
+        let array = arrays.as_array().get(i).as_array();
+        write_barrier(array.payload_addr().add(j));
+        array.set(j, index_blob(i * LEN + j));
+    }
+    assert!(increments > 2);
+
+    for i in 0..ARRAYS {
+        let array = arrays.as_array().get(i).as_array();
+        for j in 0..LEN {
+            let blob = array.get(j).as_blob();
+            assert_eq!(*(blob.payload_addr() as *const usize), i * LEN + j);
+        }
+    }
+
+    // The garbage is reused
+    let heap_size = get_heap_size();
+    for _ in 0..1000 {
+        alloc_blob(Bytes(WORD_SIZE));
+    }
+    assert_eq!(get_heap_size(), heap_size);
+
+    set_gc_schedule(67, 1, u32::MAX);
+    (*static_root(0)).field = SkewedPtr(0);
+    gc_force();
+    incremental_gc();
+}
+
+/// A blob with `index` as its payload
+unsafe fn index_blob(index: usize) -> SkewedPtr {
+    let blob = alloc_blob(Bytes(WORD_SIZE));
+    *(blob.as_blob().payload_addr() as *mut usize) = index;
+    blob
+}
+
+impl Heap {
+    fn random(&mut self, n: u32) -> u32 {
+        // xorshift
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng % n
+    }
+
+    unsafe fn collect(&self) {
+        match self.strategy {
+            Strategy::Copying => collect(),
+            Strategy::Generational => generational_gc(),
+            Strategy::Incremental | Strategy::PartialCompaction => incremental_gc(),
+            Strategy::MarkSweep => mark_sweep_gc(),
+        }
+    }
+
+    /// Writes to a field of an existing object. Only calls the barrier of the collector being
+    /// tested, so that the collectors tested before don't record anything.
+    unsafe fn write(&self, location: *mut SkewedPtr, value: SkewedPtr) {
+        match self.strategy {
+            Strategy::Copying | Strategy::MarkSweep => {}
+            Strategy::Generational => generational_write_barrier(location),
+            Strategy::Incremental | Strategy::PartialCompaction => write_barrier(location),
+        }
+        *location = value;
+    }
+
+    fn random_value(&mut self, targets: &[usize]) -> Value {
+        if targets.is_empty() || self.random(4) == 0 {
+            Value::Scalar(self.random(1 << 30))
+        } else {
+            Value::Ref(targets[self.random(targets.len() as u32) as usize])
+        }
+    }
+
+    /// Allocates new arrays, pointing to reachable ones, and garbage, and updates fields of
+    /// reachable arrays and the roots
+    unsafe fn mutate(&mut self, addrs: &HashMap<usize, usize>) {
+        let mut addrs = addrs.clone();
+        // Arrays that new arrays can point to: reachable ones and ones allocated in this round
+        let mut targets: Vec<usize> = addrs.keys().copied().collect();
+        targets.sort();
+
+        for _ in 0..ALLOCS_PER_ROUND {
+            // New array
+            let len = self.random(5);
+            let fields: Vec<Value> = (0..len).map(|_| self.random_value(&targets)).collect();
+            let array = alloc_array(len as usize);
+            for (i, field) in fields.iter().enumerate() {
+                array.as_array().set(i, value_ptr(*field, &addrs));
+            }
+            let idx = self.arrays.len();
+            self.arrays.push(fields);
+            addrs.insert(idx, array.unskew());
+
+            // Garbage
+            alloc_blob(Bytes(self.random(100) as usize));
+
+            // Store the new array in a reachable array or a root, or drop it
+            let value = Value::Ref(idx);
+            match self.random(3) {
+                0 if !targets.is_empty() => {
+                    let target = targets[self.random(targets.len() as u32) as usize];
+                    let len = self.arrays[target].len() as u32;
+                    if len != 0 {
+                        let field = self.random(len);
+                        let array = addrs[&target] as *mut Array;
+                        self.write(
+                            array.payload_addr().add(field as usize),
+                            value_ptr(value, &addrs),
+                        );
+                        self.arrays[target][field as usize] = value;
+                    }
+                }
+                1 => {
+                    let root = self.random(N_STATIC_ROOTS as u32) as usize;
+                    self.write(&mut (*static_root(root)).field, value_ptr(value, &addrs));
+                    self.roots[root] = value;
+                }
+                _ => {}
+            }
+
+            targets.push(idx);
+        }
+    }
+
+    /// Checks the heap against the expected contents, starting from the roots. Returns the
+    /// addresses of the reachable arrays.
+    unsafe fn check(&self) -> HashMap<usize, usize> {
+        let mut addrs: HashMap<usize, usize> = HashMap::new();
+        let mut stack: Vec<(Value, SkewedPtr)> = vec![];
+
+        for i in 0..N_STATIC_ROOTS {
+            stack.push((self.roots[i], (*static_root(i)).field));
+        }
+
+        while let Some((expected, actual)) = stack.pop() {
+            match expected {
+                Value::Scalar(n) => assert_eq!(actual.0, (n << 1) as usize),
+                Value::Ref(idx) => {
+                    assert!(!actual.is_tagged_scalar());
+                    let addr = actual.unskew();
+                    if let Some(expected_addr) = addrs.get(&idx) {
+                        assert_eq!(*expected_addr, addr);
+                        continue;
+                    }
+                    addrs.insert(idx, addr);
+
+                    let array = addr as *mut Array;
+                    assert_eq!((*array).header.tag, TAG_ARRAY);
+                    assert_eq!(array.len(), self.arrays[idx].len());
+                    for (i, field) in self.arrays[idx].iter().enumerate() {
+                        stack.push((*field, array.get(i)));
+                    }
+                }
+            }
+        }
+
+        addrs
+    }
+}
+
+fn value_ptr(value: Value, addrs: &HashMap<usize, usize>) -> SkewedPtr {
+    match value {
+        Value::Scalar(n) => SkewedPtr((n << 1) as usize),
+        Value::Ref(idx) => skew(addrs[&idx]),
+    }
+}
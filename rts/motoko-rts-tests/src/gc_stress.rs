@@ -0,0 +1,143 @@
+//! GC stress mode, with the `gc_stress` feature (`make test-gc-stress`): the stress tests of the
+//! text, BigInt, and closure table functions collect after every call of the RTS, with the objects
+//! of the test as the only roots besides those of the RTS (the closure table etc.), and in every
+//! allocation of the RTS, in the middle of the RTS functions.
+//!
+//! Every collection moves every object that is not a large object or pinned: the first root is a
+//! new blob, one word longer or shorter than that of the previous collection, and the copying
+//! collector copies it first. A pointer to the heap that the RTS keeps outside of its roots then
+//! points to a moved or freed object, which the heap sanity checks (`heap_sanity` feature) or the
+//! tests find.
+//!
+//! The RTS has no safe points of its own (objects in the locals of RTS functions are not roots,
+//! the generated code only collects between messages). For the collections in allocations the
+//! locals of the RTS functions, and of the test, are found by scanning the native stack
+//! conservatively: every word on the stack, or in a callee-saved register, that points into an
+//! object (or is a skewed pointer to one) pins the object for the collection, so the object stays
+//! alive and in place. The other objects still move. A Wasm engine doesn't expose the locals, so
+//! on wasm32 the stress tests only collect between calls.
+//!
+//! Without the feature `collect_with` does nothing, and the stress tests just run the functions.
+
+use crate::{static_root, N_STATIC_ROOTS};
+
+use motoko_rts::types::{Bytes, SkewedPtr, WORD_SIZE};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+}
+
+/// Payload of the first root, in words, alternating between 1 and 2
+static mut PAD_WORDS: usize = 1;
+
+/// Collects with `values` as roots, and updates them to the moved objects. Static roots are
+/// cleared after the collection.
+pub unsafe fn collect_with(values: &mut [SkewedPtr]) {
+    if !cfg!(feature = "gc_stress") {
+        return;
+    }
+
+    assert!(values.len() < N_STATIC_ROOTS);
+
+    PAD_WORDS = 3 - PAD_WORDS;
+    (*static_root(0)).field = alloc_blob(Bytes(PAD_WORDS * WORD_SIZE));
+    for (i, value) in values.iter().enumerate() {
+        (*static_root(i + 1)).field = *value;
+    }
+
+    collect();
+
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = (*static_root(i + 1)).field;
+    }
+    for i in 0..=values.len() {
+        (*static_root(i)).field = SkewedPtr(0);
+    }
+}
+
+/// Runs a stress test, collecting in every allocation of the RTS. The objects of the test must be
+/// in the locals of `test` (not in a `Vec` etc.), so that the collections find them on the stack.
+pub unsafe fn with_alloc_collections<F: FnOnce()>(test: F) {
+    #[cfg(all(feature = "gc_stress", not(target_arch = "wasm32")))]
+    {
+        let bottom = 0usize;
+        STACK_BOTTOM = &bottom as *const usize as usize;
+        motoko_rts::gc_stress::ALLOC_HOOK = Some(collect_in_alloc);
+        test();
+        motoko_rts::gc_stress::ALLOC_HOOK = None;
+    }
+
+    #[cfg(not(all(feature = "gc_stress", not(target_arch = "wasm32"))))]
+    test();
+}
+
+/// The stack above the frame of `with_alloc_collections`, which the collections in allocations scan
+#[cfg(all(feature = "gc_stress", not(target_arch = "wasm32")))]
+static mut STACK_BOTTOM: usize = 0;
+
+/// Set while collecting in an allocation, the allocations of the collection don't collect
+#[cfg(all(feature = "gc_stress", not(target_arch = "wasm32")))]
+static mut COLLECTING: bool = false;
+
+#[cfg(all(feature = "gc_stress", not(target_arch = "wasm32")))]
+extern "C" {
+    // Only used to spill the callee-saved registers, it never returns twice as we don't `longjmp`
+    fn _setjmp(env: *mut usize) -> i32;
+}
+
+/// The allocation hook of the RTS, see `motoko_rts::gc_stress`
+#[cfg(all(feature = "gc_stress", not(target_arch = "wasm32")))]
+unsafe fn collect_in_alloc() {
+    use motoko_rts::gc_stress::for_each_object;
+    use motoko_rts::pin::{pin_object, unpin_object};
+    use motoko_rts::types::skew;
+    use std::cmp::Ordering;
+
+    if COLLECTING {
+        return;
+    }
+    COLLECTING = true;
+
+    // Larger than the `jmp_buf` of the supported targets
+    let mut registers = [0usize; 64];
+    _setjmp(registers.as_mut_ptr());
+
+    // The words that may be pointers, the registers are also on the stack
+    let top = registers.as_ptr() as usize;
+    let words = std::slice::from_raw_parts(top as *const usize, (STACK_BOTTOM - top) / WORD_SIZE);
+
+    // The objects, sorted by address, and the ones the words point into
+    let mut objects = vec![];
+    for_each_object(|addr, size| objects.push((addr, addr + size.0)));
+    let mut pinned = vec![];
+    for &word in words {
+        // A skewed pointer points into the previous word
+        for &addr in &[word, word.wrapping_add(1)] {
+            let found = objects.binary_search_by(|&(begin, end)| {
+                if end <= addr {
+                    Ordering::Less
+                } else if begin > addr {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            });
+            if let Ok(i) = found {
+                pinned.push(objects[i].0);
+            }
+        }
+    }
+    pinned.sort_unstable();
+    pinned.dedup();
+
+    for &addr in &pinned {
+        pin_object(skew(addr));
+    }
+    collect_with(&mut []);
+    for &addr in &pinned {
+        unpin_object(skew(addr));
+    }
+
+    COLLECTING = false;
+}
@@ -0,0 +1,103 @@
+use crate::idl_encode::{array, object, scalar, NAT};
+use crate::utils::read_stable_vars;
+use crate::STABLE_MEM;
+
+use motoko_rts::graph_copy::{graph_copy_restore, graph_copy_stabilize};
+use motoko_rts::region::{FLAG_CHECKSUM, FLAG_GRAPH_COPY};
+use motoko_rts::types::*;
+
+const RECORD: u32 = 16;
+const NULL: u32 = 0;
+
+pub unsafe fn test() {
+    println!("Testing graph copy of stable variables ...");
+
+    let hash_lists: &[usize] = &[2, 1, 33, 2, 11, 22];
+    let typ = b"T1";
+    let null = object(&[TAG_NULL]);
+
+    // A record with a mutable field, holding an array with aliases and a cycle
+    let elems = array(&[scalar(1), scalar(2)]);
+    let arr = array(&[elems, elems, scalar(3)]);
+    arr.as_array().set(2, arr);
+    let mut_box = object(&[TAG_MUTBOX, arr.0]);
+    let record = object(&[TAG_OBJECT, 2, object(&[11, 22]).0, mut_box.0, null.0]);
+
+    stabilize(record, typ, hash_lists);
+    assert_eq!(&read_stable_vars()[..4], b"MOGC");
+    assert_eq!(STABLE_MEM[20], (FLAG_GRAPH_COPY | FLAG_CHECKSUM) as u8);
+    assert_eq!(record.tag(), TAG_OBJECT);
+    assert_eq!(arr.tag(), TAG_ARRAY);
+
+    // Restored with the hash lists (in a different order) and the null of the new version
+    let new_hash_lists: &[usize] = &[2, 2, 11, 22, 1, 33];
+    let new_null = object(&[TAG_NULL]);
+    let restored = restore(typ, new_hash_lists, new_null);
+    let obj = restored.as_obj() as *const Object;
+    assert_eq!(restored.tag(), TAG_OBJECT);
+    assert_ne!(restored.0, record.0);
+    assert_eq!(
+        SkewedPtr((*obj).hash_ptr).unskew(),
+        new_hash_lists[2..].as_ptr() as usize
+    );
+    let fields = obj.payload_addr();
+    assert_eq!((*fields.add(1)).0, new_null.0);
+    let mut_box = *fields;
+    assert_eq!(mut_box.tag(), TAG_MUTBOX);
+    let arr = (*(mut_box.as_obj() as *mut MutBox)).field.as_array();
+    assert_eq!(arr.len(), 3);
+    assert_eq!(arr.get(0).0, arr.get(1).0);
+    assert_eq!(arr.get(2).as_array(), arr);
+    let elems = arr.get(0).as_array();
+    assert_eq!((elems.get(0).0, elems.get(1).0), (scalar(1).0, scalar(2).0));
+
+    // Encoded in Candid with the old type after an upgrade to a different type
+    let record = object(&[TAG_OBJECT, 2, object(&[11, 22]).0, scalar(7).0, null.0]);
+    stabilize(record, typ, hash_lists);
+    assert_eq!(restore(b"T2", new_hash_lists, new_null).0, 0);
+    assert_eq!(read_stable_vars(), b"DIDL\x07");
+    assert_eq!(STABLE_MEM[20], FLAG_CHECKSUM as u8);
+
+    // Values that cannot be copied are encoded in Candid
+    let closure = object(&[TAG_CLOSURE, 0, 0]);
+    let record = object(&[TAG_OBJECT, 2, object(&[11, 22]).0, scalar(7).0, closure.0]);
+    stabilize(record, typ, hash_lists);
+    assert_eq!(read_stable_vars(), b"DIDL\x07");
+    assert_eq!(restore(typ, hash_lists, new_null).0, 0);
+    assert_eq!(closure.tag(), TAG_CLOSURE);
+
+    // An object with a private field
+    let record = object(&[
+        TAG_OBJECT,
+        3,
+        object(&[11, 22, 55]).0,
+        scalar(7).0,
+        null.0,
+        scalar(8).0,
+    ]);
+    stabilize(record, typ, hash_lists);
+    assert_eq!(read_stable_vars(), b"DIDL\x07");
+}
+
+/// Stabilizes a record with fields 11 of type `Nat` and 22 of type `Null`
+unsafe fn stabilize(record: SkewedPtr, typ: &[u8], hash_lists: &[usize]) {
+    let desc: Vec<u8> = [RECORD, 2, 11, 6, 22, 7, NAT, NULL]
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    let tydesc = b"DIDL";
+    graph_copy_stabilize(
+        record,
+        desc.as_ptr(),
+        8,
+        tydesc.as_ptr(),
+        4,
+        typ.as_ptr(),
+        typ.len() as u32,
+        hash_lists.as_ptr(),
+    );
+}
+
+unsafe fn restore(typ: &[u8], hash_lists: &[usize], null: SkewedPtr) -> SkewedPtr {
+    graph_copy_restore(typ.as_ptr(), typ.len() as u32, hash_lists.as_ptr(), null)
+}
@@ -0,0 +1,68 @@
+use crate::utils::string_of_text;
+
+use motoko_rts::grapheme::{
+    text_iter_graphemes, text_iter_graphemes_done, text_iter_graphemes_next,
+};
+use motoko_rts::text::{text_concat, text_of_str};
+use motoko_rts::types::SkewedPtr;
+
+pub unsafe fn test() {
+    println!("Testing grapheme clusters ...");
+
+    let cases: &[&[&str]] = &[
+        &[],
+        &["a", "b", "c"],
+        // CR LF is one cluster, other controls are not joined
+        &["a", "\r\n", "b"],
+        &["\n", "\r", "\u{301}"],
+        // Combining marks
+        &["e\u{301}\u{323}", "x"],
+        &["\u{301}", "a"],
+        // Spacing marks and prepended characters
+        &["\u{915}\u{93f}", "\u{915}"],
+        &["\u{600}1", "2"],
+        // Hangul syllables and jamo
+        &["\u{ac00}\u{11a8}", "\u{1100}\u{1161}\u{11a8}", "\u{ac01}"],
+        &["\u{ac01}", "\u{1161}"],
+        // Flags are pairs of regional indicators
+        &["\u{1f1e9}\u{1f1ea}", "\u{1f1eb}\u{1f1f7}", "\u{1f1fa}"],
+        &["a", "\u{1f1e9}\u{1f1ea}", "\u{1f1eb}\u{1f1f7}"],
+        // Emoji with modifiers and ZWJ sequences
+        &["\u{1f44d}\u{1f3fd}", "!"],
+        &["\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}", "\u{1f467}"],
+        &["a\u{200d}", "\u{1f467}"],
+        // Devanagari conjuncts
+        &["\u{915}\u{94d}\u{937}", "\u{924}\u{94d}\u{930}"],
+        &["\u{915}\u{93c}\u{94d}\u{937}\u{93f}"],
+        &["\u{915}\u{93c}", "\u{937}"],
+    ];
+
+    for clusters in cases {
+        let input = clusters.concat();
+        let got = graphemes(text_of_str(&input));
+        assert_eq!(got, *clusters, "input {:?}", input);
+    }
+
+    // Clusters across the leaves of a concatenation
+    let text = text_concat(text_of_str("abcdefghe"), text_of_str("\u{301}\r"));
+    let text = text_concat(text, text_of_str("\n\u{1f1e9}"));
+    let text = text_concat(text, text_of_str("\u{1f1ea}"));
+    let mut expected: Vec<String> = "abcdefgh".chars().map(String::from).collect();
+    expected.extend(
+        ["e\u{301}", "\r\n", "\u{1f1e9}\u{1f1ea}"]
+            .iter()
+            .map(|s| s.to_string()),
+    );
+    assert_eq!(graphemes(text), expected);
+
+    println!("OK");
+}
+
+unsafe fn graphemes(text: SkewedPtr) -> Vec<String> {
+    let iter = text_iter_graphemes(text);
+    let mut clusters = vec![];
+    while text_iter_graphemes_done(iter) == 0 {
+        clusters.push(string_of_text(text_iter_graphemes_next(iter)));
+    }
+    clusters
+}
@@ -0,0 +1,111 @@
+use crate::static_root;
+
+use motoko_rts::text::{text_size, text_to_buf};
+use motoko_rts::types::{size_of, Array, Blob, Bytes, SkewedPtr, Words};
+
+use std::collections::HashMap;
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn heap_checkpoint();
+    fn heap_diff() -> SkewedPtr;
+    fn heap_live_census() -> SkewedPtr;
+}
+
+pub unsafe fn test() {
+    println!("Testing heap census ...");
+
+    heap_checkpoint();
+
+    for _ in 0..3 {
+        alloc_array(10);
+    }
+
+    let array_size = (size_of::<Array>() + Words(10)).to_bytes().0 as isize;
+    assert_eq!(diff(), vec![("Array".to_string(), 3, 3 * array_size)]);
+
+    // The result of the last `heap_diff`: an array with one tuple, with a (small) text
+    let last = diff();
+    assert_eq!(last.len(), 2);
+    assert_eq!((last[0].0.as_str(), last[0].1), ("Array", 3 + 2));
+    assert_eq!((last[1].0.as_str(), last[1].1), ("SmallText", 1));
+
+    // New checkpoint
+    heap_checkpoint();
+    assert!(diff().is_empty());
+
+    test_live_census();
+
+    println!("OK");
+}
+
+unsafe fn test_live_census() {
+    // Objects left reachable by the other tests (e.g. in the closure table)
+    let before = live_census();
+
+    // Reachable from a static root: an array with two blobs
+    let array = alloc_array(2);
+    array.as_array().set(0, alloc_blob(Bytes(10)));
+    array.as_array().set(1, alloc_blob(Bytes(20)));
+    (*static_root(0)).field = array;
+
+    // Garbage
+    alloc_array(10);
+    alloc_blob(Bytes(100));
+
+    let after = live_census();
+
+    let array_size = (size_of::<Array>() + Words(2)).to_bytes().0 as isize;
+    let blob_size = size_of::<Blob>()
+        + Bytes(10usize).to_words()
+        + size_of::<Blob>()
+        + Bytes(20usize).to_words();
+    let blob_size = blob_size.to_bytes().0 as isize;
+    let mut expected = before.clone();
+    let entry = expected.entry("Array".to_string()).or_insert((0, 0));
+    *entry = (entry.0 + 1, entry.1 + array_size);
+    let entry = expected.entry("Blob".to_string()).or_insert((0, 0));
+    *entry = (entry.0 + 2, entry.1 + blob_size);
+    assert_eq!(after, expected);
+
+    // The results of the censuses are garbage
+    (*static_root(0)).field = SkewedPtr(0);
+    assert_eq!(live_census(), before);
+}
+
+/// Decodes the result of `heap_live_census`, by tag name
+unsafe fn live_census() -> HashMap<String, (isize, isize)> {
+    decode(heap_live_census())
+        .into_iter()
+        .map(|(name, objects, bytes)| (name, (objects, bytes)))
+        .collect()
+}
+
+/// Decodes the result of `heap_diff`
+unsafe fn diff() -> Vec<(String, isize, isize)> {
+    decode(heap_diff())
+}
+
+unsafe fn decode(result: SkewedPtr) -> Vec<(String, isize, isize)> {
+    let result = result.as_array();
+    (0..result.len())
+        .map(|i| {
+            let tuple = result.get(i).as_array();
+            // The names are short texts, not blobs
+            let mut name = vec![0u8; text_size(tuple.get(0)).0];
+            text_to_buf(tuple.get(0), name.as_mut_ptr());
+            (
+                String::from_utf8(name).unwrap(),
+                int(tuple.get(1)),
+                int(tuple.get(2)),
+            )
+        })
+        .collect()
+}
+
+/// Small `Int`s are tagged scalars
+fn int(n: SkewedPtr) -> isize {
+    assert!(n.is_tagged_scalar());
+    (n.0 as isize) >> 1
+}
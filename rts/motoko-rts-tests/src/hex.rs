@@ -0,0 +1,74 @@
+use crate::utils::{blob_of_bytes, bytes_of_blob, string_of_text};
+
+use motoko_rts::hex::{blob_of_hex, blob_to_hex};
+use motoko_rts::text::{text_concat, text_of_str};
+
+use quickcheck::{quickcheck, TestResult};
+
+pub unsafe fn test() {
+    println!("Testing hex ...");
+
+    assert_eq!(string_of_text(blob_to_hex(blob_of_bytes(&[]))), "");
+    assert_eq!(
+        string_of_text(blob_to_hex(blob_of_bytes(&[0x00, 0x1f, 0xa0, 0xff]))),
+        "001fa0ff"
+    );
+
+    assert_eq!(decode(""), Some(vec![]));
+    assert_eq!(decode("001fa0ff"), Some(vec![0x00, 0x1f, 0xa0, 0xff]));
+    assert_eq!(decode("001FA0Ff"), Some(vec![0x00, 0x1f, 0xa0, 0xff]));
+    assert_eq!(decode("0x001fa0ff"), Some(vec![0x00, 0x1f, 0xa0, 0xff]));
+    assert_eq!(decode("0XAB"), Some(vec![0xab]));
+    assert_eq!(decode("0x"), Some(vec![]));
+
+    // Invalid encodings
+    for invalid in &[
+        "0",
+        "abc",
+        "0xa",
+        "gg",
+        "0x0x",
+        "00 ",
+        " 00",
+        "x0",
+        "\u{e4}\u{e4}",
+    ] {
+        assert_eq!(decode(invalid), None, "{:?}", invalid);
+    }
+
+    // Decoding a concatenation, with a leaf boundary within a byte
+    let t = text_concat(text_of_str("0xdeadb"), text_of_str("eef"));
+    assert_eq!(bytes_of_blob(blob_of_hex(t)), [0xde, 0xad, 0xbe, 0xef]);
+
+    quickcheck(hex_prop as fn(Vec<u8>) -> TestResult);
+
+    println!("OK");
+}
+
+fn hex_prop(input: Vec<u8>) -> TestResult {
+    unsafe {
+        let encoded = string_of_text(blob_to_hex(blob_of_bytes(&input)));
+
+        let expected: String = input.iter().map(|b| format!("{:02x}", b)).collect();
+        if encoded != expected {
+            return TestResult::error(format!("blob_to_hex({:?}) = {:?}", input, encoded));
+        }
+
+        if decode(&encoded) != Some(input.clone())
+            || decode(&encoded.to_uppercase()) != Some(input.clone())
+        {
+            return TestResult::error(format!("hex round trip of {:?}", input));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let blob = blob_of_hex(text_of_str(encoded));
+    if blob.0 == 0 {
+        None
+    } else {
+        Some(bytes_of_blob(blob))
+    }
+}
@@ -0,0 +1,181 @@
+use motoko_rts::bigint::{bigint_of_int64, bigint_of_word64};
+use motoko_rts::idl_encode::{idl_serialize, idl_serialize_size};
+use motoko_rts::text::{blob_of_text, text_concat, text_of_ptr_size, text_of_str};
+use motoko_rts::types::*;
+
+// Opcodes of the descriptor, see `idl_encode.rs`
+const NULL: u32 = 0;
+const BOOL: u32 = 1;
+pub(crate) const NAT: u32 = 2;
+const INT: u32 = 3;
+const NAT8: u32 = 4;
+const NAT16: u32 = 5;
+const NAT32: u32 = 6;
+const CHAR: u32 = 7;
+const NAT64: u32 = 8;
+const FLOAT: u32 = 9;
+const TEXT: u32 = 10;
+const BLOB: u32 = 11;
+const PRINCIPAL: u32 = 12;
+const OPT: u32 = 13;
+const VEC: u32 = 14;
+const TUPLE: u32 = 15;
+const RECORD: u32 = 16;
+const VARIANT: u32 = 17;
+const FUNC: u32 = 18;
+pub(crate) const MUT: u32 = 20;
+pub(crate) const MUT_ARRAY: u32 = 21;
+
+pub unsafe fn test() {
+    println!("Testing Candid encoding ...");
+
+    //
+    // Scalars
+    //
+
+    let tuple = array(&[scalar(300), scalar(-129), scalar(1)]);
+    check(
+        &[TUPLE, 3, 5, 6, 7, NAT, INT, BOOL],
+        tuple,
+        b"\xAC\x02\xFF\x7E\x01",
+    );
+
+    let big = array(&[bigint_of_word64(1 << 40), bigint_of_int64(-(1 << 40))]);
+    check(
+        &[TUPLE, 2, 4, 5, NAT, INT],
+        big,
+        b"\x80\x80\x80\x80\x80\x20\x80\x80\x80\x80\x80\x60",
+    );
+
+    let words = array(&[
+        SkewedPtr(0xfe << 24),
+        SkewedPtr(0xbeef << 16),
+        scalar(12),
+        bits32(0xdead_beef),
+        SkewedPtr(0x1f600 << 8),
+        scalar(-2),
+        bits64(0x0123_4567_89ab_cdef),
+        bits64(1.5f64.to_bits()),
+    ]);
+    check(
+        &[
+            TUPLE, 8, 10, 11, 12, 12, 13, 14, 14, 15, NAT8, NAT16, NAT32, CHAR, NAT64, FLOAT,
+        ],
+        words,
+        b"\xFE\xEF\xBE\x0C\x00\x00\x00\xEF\xBE\xAD\xDE\x00\xF6\x01\x00\
+          \xFE\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xEF\xCD\xAB\x89\x67\x45\x23\x01\
+          \x00\x00\x00\x00\x00\x00\xF8\x3F",
+    );
+
+    //
+    // Texts and blobs
+    //
+
+    let text = text_concat(text_of_str("Motoko "), text_of_str("Candid"));
+    check(&[TEXT], text, b"\x0DMotoko Candid");
+
+    let blob = blob_of_text(text_of_ptr_size(b"\xC0\xFE".as_ptr(), Bytes(2)));
+    check(&[BLOB], blob, b"\x02\xC0\xFE");
+    check(&[PRINCIPAL], blob, b"\x01\x02\xC0\xFE");
+
+    let func = array(&[blob, text_of_str("go")]);
+    check(&[FUNC], func, b"\x01\x01\x02\xC0\xFE\x02go");
+
+    //
+    // Options and vectors
+    //
+
+    let null = object(&[TAG_NULL]);
+    let some_null = object(&[TAG_SOME, null.0]);
+    let opts = array(&[null, some_null, scalar(5)]);
+    check(
+        &[VEC, 2, OPT, 4, OPT, 6, NAT],
+        opts,
+        b"\x03\x00\x01\x00\x01\x01\x05",
+    );
+
+    check(&[VEC, 2, NULL], array(&[]), b"\x00");
+
+    //
+    // Records and variants, fields are found by their hash in the heap representation
+    //
+
+    let hashes = object(&[11, 22]);
+    let record = object(&[TAG_OBJECT, 2, hashes.0, scalar(7).0, text_of_str("b").0]);
+    // Candid order differs from the heap order
+    check(&[RECORD, 2, 22, 6, 11, 7, TEXT, NAT], record, b"\x01b\x07");
+
+    let variant = object(&[TAG_VARIANT, 22, bits32(0xdead_beef).0]);
+    check(
+        &[VARIANT, 2, 11, 6, 22, 7, NULL, NAT32],
+        variant,
+        b"\x01\xEF\xBE\xAD\xDE",
+    );
+
+    //
+    // Mutable values are aliased, with space for the decoder after the first occurrence
+    //
+
+    let a = array(&[scalar(1)]);
+    let b = array(&[scalar(2)]);
+    check(
+        &[TUPLE, 3, 5, 5, 5, MUT_ARRAY, 7, NAT],
+        array(&[a, a, b]),
+        b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x01\
+          \x01\xF5\xFF\xFF\xFF\
+          \x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x02",
+    );
+
+    let hashes = object(&[11]);
+    let mut_box = object(&[TAG_MUTBOX, scalar(7).0]);
+    let record = object(&[TAG_OBJECT, 1, hashes.0, mut_box.0]);
+    check(
+        &[RECORD, 1, 11, 4, MUT, 6, NAT],
+        record,
+        b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x07",
+    );
+}
+
+/// Checks the size and the encoding of the value
+unsafe fn check(desc: &[u32], value: SkewedPtr, expected: &[u8]) {
+    let desc: Vec<u8> = desc
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    let n_words = (desc.len() / 4) as u32;
+
+    let size = idl_serialize_size(desc.as_ptr(), n_words, value);
+    assert_eq!(size as usize, expected.len());
+
+    let mut buf = vec![0u8; size as usize];
+    idl_serialize(desc.as_ptr(), n_words, value, buf.as_mut_ptr(), size);
+    assert_eq!(buf, expected);
+}
+
+pub(crate) fn scalar(n: isize) -> SkewedPtr {
+    SkewedPtr((n << 1) as usize)
+}
+
+/// A heap object with the given words, which is never freed
+pub(crate) fn object(words: &[usize]) -> SkewedPtr {
+    let words = Box::leak(words.to_vec().into_boxed_slice());
+    skew(words.as_ptr() as usize)
+}
+
+pub(crate) fn array(elems: &[SkewedPtr]) -> SkewedPtr {
+    let mut words = vec![TAG_ARRAY, elems.len()];
+    words.extend(elems.iter().map(|elem| elem.0));
+    object(&words)
+}
+
+unsafe fn bits32(bits: u32) -> SkewedPtr {
+    let obj = object(&[TAG_BITS32, 0]);
+    (*(obj.unskew() as *mut Bits32)).bits = bits;
+    obj
+}
+
+unsafe fn bits64(bits: u64) -> SkewedPtr {
+    let obj = object(&[TAG_BITS64, 0, 0]);
+    (*(obj.unskew() as *mut Bits64)).bits = bits;
+    obj
+}
@@ -1,5 +1,14 @@
+use motoko_rts::bigint::{
+    bigint_add, bigint_eq, bigint_isneg, bigint_leb128_decode, bigint_leb128_encode,
+    bigint_leb128_size, bigint_lsh, bigint_neg, bigint_of_int64, bigint_of_word32,
+    bigint_of_word64, bigint_sleb128_decode, bigint_sleb128_encode, bigint_sleb128_size,
+};
 use motoko_rts::buf::Buf;
-use motoko_rts::leb128::{leb128_decode, leb128_encode, sleb128_decode, sleb128_encode};
+use motoko_rts::leb128::{
+    leb128_decode, leb128_decode_nat, leb128_decode_u128, leb128_encode, leb128_encode_u128,
+    sleb128_decode, sleb128_decode_i128, sleb128_decode_int, sleb128_encode, sleb128_encode_i128,
+};
+use motoko_rts::types::SkewedPtr;
 
 use quickcheck::{quickcheck, TestResult};
 
@@ -7,6 +16,36 @@ pub unsafe fn test() {
     println!("Testing (s)leb128 encode-decode roundtrip ...");
     quickcheck(roundtrip_signed as fn(i32) -> TestResult);
     quickcheck(roundtrip_unsigned as fn(u32) -> TestResult);
+    for i in 0..32 {
+        for &val in &[1 << i, (1i32 << i).wrapping_sub(1), !0 << i, !(1 << i)] {
+            assert!(!roundtrip_signed(val).is_failure());
+            assert!(!roundtrip_unsigned(val as u32).is_failure());
+        }
+    }
+
+    println!("Testing 128-bit (s)leb128 encode-decode roundtrip ...");
+    quickcheck(roundtrip_signed_128 as fn(i128) -> TestResult);
+    quickcheck(roundtrip_unsigned_128 as fn(u128) -> TestResult);
+    for i in 0..128 {
+        for &val in &[1 << i, (1i128 << i).wrapping_sub(1), !0 << i, !(1 << i)] {
+            assert!(!roundtrip_signed_128(val).is_failure());
+            assert!(!roundtrip_unsigned_128(val as u128).is_failure());
+        }
+    }
+
+    println!("Testing nat and int decoding ...");
+    test_decode_nat_int();
+
+    println!("Testing BigInt (s)leb128 encode-decode roundtrip ...");
+    quickcheck(roundtrip_bigint_signed as fn(i64) -> TestResult);
+    quickcheck(roundtrip_bigint_unsigned as fn(u64) -> TestResult);
+    quickcheck(roundtrip_bigint_large as fn(Vec<u32>, bool) -> TestResult);
+    for i in 0..64 {
+        for &val in &[1 << i, (1i64 << i).wrapping_sub(1), !0 << i, !(1 << i)] {
+            assert!(!roundtrip_bigint_signed(val).is_failure());
+            assert!(!roundtrip_bigint_unsigned(val as u64).is_failure());
+        }
+    }
 }
 
 fn roundtrip_signed(val: i32) -> TestResult {
@@ -14,12 +53,7 @@ fn roundtrip_signed(val: i32) -> TestResult {
         let mut buf = [0u8; 100];
         sleb128_encode(val, buf.as_mut_ptr());
 
-        let mut buf_ = Buf {
-            ptr: buf.as_mut_ptr(),
-            end: buf.as_mut_ptr().add(100),
-        };
-
-        TestResult::from_bool(sleb128_decode(&mut buf_) == val)
+        TestResult::from_bool(decode_both(&mut buf, |buf| sleb128_decode(buf) == val))
     }
 }
 
@@ -28,11 +62,174 @@ fn roundtrip_unsigned(val: u32) -> TestResult {
         let mut buf = [0u8; 100];
         leb128_encode(val, buf.as_mut_ptr());
 
+        TestResult::from_bool(decode_both(&mut buf, |buf| leb128_decode(buf) == val))
+    }
+}
+
+/// Decodes the number at the beginning of the buffer with the whole buffer after it (reading it
+/// as words) and with the buffer ending after the number (reading it byte by byte)
+unsafe fn decode_both<F: Fn(*mut Buf) -> bool>(buf: &mut [u8], decode: F) -> bool {
+    let len = buf.iter().position(|byte| byte & 0b1000_0000 == 0).unwrap() + 1;
+    [buf.len(), len].iter().all(|&end| {
         let mut buf_ = Buf {
             ptr: buf.as_mut_ptr(),
-            end: buf.as_mut_ptr().add(100),
+            end: buf.as_mut_ptr().add(end),
         };
+        decode(&mut buf_) && buf_.ptr == buf.as_mut_ptr().add(len)
+    })
+}
+
+fn roundtrip_signed_128(val: i128) -> TestResult {
+    unsafe {
+        let mut buf = [0u8; 100];
+        sleb128_encode_i128(val, buf.as_mut_ptr());
+
+        TestResult::from_bool(decode_both(&mut buf, |buf| {
+            sleb128_decode_i128(buf) == Some(val)
+        }))
+    }
+}
+
+fn roundtrip_unsigned_128(val: u128) -> TestResult {
+    unsafe {
+        let mut buf = [0u8; 100];
+        leb128_encode_u128(val, buf.as_mut_ptr());
+
+        TestResult::from_bool(decode_both(&mut buf, |buf| {
+            leb128_decode_u128(buf) == Some(val)
+        }))
+    }
+}
+
+unsafe fn test_decode_nat_int() {
+    // Tagged scalars
+    assert_eq!(decode(b"\x05", false).0, 5 << 1);
+    assert_eq!(decode(b"\x7b", true).0, (-5isize << 1) as usize);
+    assert_eq!(
+        decode(b"\xff\xff\xff\xff\x03", false).0,
+        ((1 << 30) - 1) << 1
+    );
+
+    // Big integers, of 128 bits and of more, like the encoding
+    let encodings: &[&[u8]] = &[
+        b"\x80\x80\x80\x80\x04",
+        b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x03",
+        b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x04",
+        b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x01",
+        b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x7e",
+        b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x7f",
+    ];
+    for bytes in encodings {
+        for &signed in &[false, true] {
+            let n = decode(bytes, signed);
+            let mut expected_buf = buf_of(bytes);
+            let expected = if signed {
+                bigint_sleb128_decode(&mut expected_buf)
+            } else {
+                bigint_leb128_decode(&mut expected_buf)
+            };
+            assert!(!n.is_tagged_scalar());
+            assert!(bigint_eq(n, expected));
+        }
+    }
+}
+
+/// Decodes the whole encoding as a `Nat` or an `Int`
+unsafe fn decode(bytes: &[u8], signed: bool) -> SkewedPtr {
+    let mut buf = buf_of(bytes);
+    let n = if signed {
+        sleb128_decode_int(&mut buf)
+    } else {
+        leb128_decode_nat(&mut buf)
+    };
+    assert!(buf.ptr == buf.end);
+    n
+}
+
+fn buf_of(bytes: &[u8]) -> Buf {
+    let bytes = Box::leak(bytes.to_vec().into_boxed_slice());
+    Buf {
+        ptr: bytes.as_mut_ptr(),
+        end: unsafe { bytes.as_mut_ptr().add(bytes.len()) },
+    }
+}
+
+/// The BigInt encoding is that of the 128-bit encoder, and decodes to the number
+fn roundtrip_bigint_signed(val: i64) -> TestResult {
+    unsafe {
+        let n = bigint_of_int64(val);
+        let mut buf = vec![0u8; bigint_sleb128_size(n) as usize];
+        bigint_sleb128_encode(n, buf.as_mut_ptr());
+
+        let mut expected = [0u8; 100];
+        sleb128_encode_i128(i128::from(val), expected.as_mut_ptr());
+        if expected[..buf.len()] != buf[..] || expected[buf.len() - 1] & 0b1000_0000 != 0 {
+            return TestResult::error(format!("encoding of {}: {:?}", val, buf));
+        }
+
+        TestResult::from_bool(decode_both(&mut buf, |buf| {
+            bigint_eq(boxed(sleb128_decode_int(buf)), n)
+        }))
+    }
+}
+
+fn roundtrip_bigint_unsigned(val: u64) -> TestResult {
+    unsafe {
+        let n = bigint_of_word64(val);
+        let mut buf = vec![0u8; bigint_leb128_size(n) as usize];
+        bigint_leb128_encode(n, buf.as_mut_ptr());
+
+        let mut expected = [0u8; 100];
+        leb128_encode_u128(u128::from(val), expected.as_mut_ptr());
+        if expected[..buf.len()] != buf[..] || expected[buf.len() - 1] & 0b1000_0000 != 0 {
+            return TestResult::error(format!("encoding of {}: {:?}", val, buf));
+        }
+
+        TestResult::from_bool(decode_both(&mut buf, |buf| {
+            bigint_eq(boxed(leb128_decode_nat(buf)), n)
+        }))
+    }
+}
+
+/// Numbers of any size, from 32-bit digits (least significant first). Decoding with the `Nat` and
+/// `Int` decoders (which only use BigInts above 128 bits) agrees with the BigInt decoders.
+fn roundtrip_bigint_large(digits: Vec<u32>, negative: bool) -> TestResult {
+    unsafe {
+        let mut n = bigint_of_word32(0);
+        for (i, &digit) in digits.iter().enumerate() {
+            n = bigint_add(n, bigint_lsh(bigint_of_word32(digit), 32 * i as i32));
+        }
+        if negative {
+            n = bigint_neg(n);
+        }
+
+        let mut buf = vec![0u8; bigint_sleb128_size(n) as usize];
+        bigint_sleb128_encode(n, buf.as_mut_ptr());
+        if !decode_both(&mut buf, |buf| bigint_eq(boxed(sleb128_decode_int(buf)), n))
+            || !bigint_eq(bigint_sleb128_decode(&mut buf_of(&buf)), n)
+        {
+            return TestResult::error(format!("signed roundtrip of {:?}", digits));
+        }
+
+        if !bigint_isneg(n) {
+            let mut buf = vec![0u8; bigint_leb128_size(n) as usize];
+            bigint_leb128_encode(n, buf.as_mut_ptr());
+            if !decode_both(&mut buf, |buf| bigint_eq(boxed(leb128_decode_nat(buf)), n))
+                || !bigint_eq(bigint_leb128_decode(&mut buf_of(&buf)), n)
+            {
+                return TestResult::error(format!("unsigned roundtrip of {:?}", digits));
+            }
+        }
+
+        TestResult::passed()
+    }
+}
 
-        TestResult::from_bool(leb128_decode(&mut buf_) == val)
+/// A decoded number as a BigInt, for the comparison with `bigint_eq`
+unsafe fn boxed(n: SkewedPtr) -> SkewedPtr {
+    if n.is_tagged_scalar() {
+        bigint_of_int64((n.0 as isize >> 1) as i64)
+    } else {
+        n
     }
 }
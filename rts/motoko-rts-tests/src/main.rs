@@ -1,35 +1,164 @@
 #![feature(ptr_offset_from)]
 
+mod alloc_fast_path;
+mod alloc_profile;
+mod array;
+mod backtrace;
+mod base64;
 mod bigint;
+mod card_table;
+mod char;
 mod closure_table;
+mod compress;
 mod crc32;
+mod error_code;
+mod features;
+mod finalizers;
+mod float;
+mod float_parse;
+mod free_list;
+mod gc;
+mod gc_stress;
+mod graph_copy;
+mod grapheme;
+mod heap_census;
+mod hex;
+mod idl_encode;
 mod leb128;
+mod message_allocation;
+mod nat128;
+mod normalize;
+mod perf;
+mod pin;
 mod principal_id;
+mod random;
+mod redzones;
+mod region;
+mod scratch;
+mod sha256;
 mod text;
+mod text_intern;
+mod timer;
+mod to_space_arena;
 mod utf8;
+mod utils;
+#[cfg(target_arch = "wasm32")]
+mod wasm_memory;
+mod weak_ref;
 
 use motoko_rts::types::*;
 
-fn main() {
-    if std::mem::size_of::<usize>() != 4 {
-        println!("Motoko RTS only works on 32-bit architectures");
-        std::process::exit(1);
-    }
+extern "C" {
+    fn init();
+}
+
+/// Size of the dynamic heap on native targets. The RTS is built with the GC (see `Cargo.toml`), all
+/// tests allocate in this heap. 64 MiB on 32-bit targets, with room for as many objects on 64-bit
+/// targets. On wasm32 the heap grows as needed, see `wasm_memory.rs`.
+const HEAP_SIZE: usize = (16 << 20) * WORD_SIZE;
+
+static mut HEAP_BASE: usize = 0;
+
+/// Number of static roots, see `static_root`
+const N_STATIC_ROOTS: usize = 8;
 
+/// Static memory with the static roots array: an array header, pointers to the mutable boxes, and
+/// the mutable boxes
+static mut STATIC_ROOTS: [usize; 2 + 3 * N_STATIC_ROOTS] = [0; 2 + 3 * N_STATIC_ROOTS];
+
+fn main() {
     unsafe {
+        init_heap();
+
+        alloc_fast_path::test();
+        message_allocation::test();
         closure_table::test();
+        array::test();
+        backtrace::test();
         bigint::test();
+        nat128::test();
+        perf::test();
         utf8::test();
+        char::test();
         crc32::test();
+        error_code::test();
+        features::test();
         principal_id::test();
+        random::test();
+        sha256::test();
+        base64::test();
+        hex::test();
+        float::test();
+        float_parse::test();
         text::test();
+        normalize::test();
+        grapheme::test();
+        text_intern::test();
         leb128::test();
+        idl_encode::test();
+        region::test();
+        graph_copy::test();
+        compress::test();
+        weak_ref::test();
+        finalizers::test();
+        pin::test();
+        timer::test();
+        scratch::test();
+        to_space_arena::test();
+        free_list::test();
+        heap_census::test();
+        redzones::test();
+        gc::test();
+        card_table::test();
+        // Last, as profiling can't be turned off
+        alloc_profile::test();
+    }
+}
+
+unsafe fn init_heap() {
+    #[cfg(target_arch = "wasm32")]
+    {
+        HEAP_BASE = wasm_memory::heap_base();
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let heap = Box::leak(vec![0usize; HEAP_SIZE / WORD_SIZE].into_boxed_slice());
+        HEAP_BASE = heap.as_ptr() as usize;
+    }
+
+    let array = STATIC_ROOTS.as_mut_ptr() as *mut Array;
+    (*array).header.tag = TAG_ARRAY;
+    (*array).len = N_STATIC_ROOTS;
+    for i in 0..N_STATIC_ROOTS {
+        let mutbox = static_root(i);
+        (*mutbox).header.tag = TAG_MUTBOX;
+        (*mutbox).field = SkewedPtr(0);
+        array.set(i, skew(mutbox as usize));
+    }
+
+    init();
+}
+
+/// Returns the mutable box of static root `i`, used by the GC tests to keep objects alive
+unsafe fn static_root(i: usize) -> *mut MutBox {
+    STATIC_ROOTS.as_mut_ptr().add(2 + N_STATIC_ROOTS + 2 * i) as *mut MutBox
+}
+
+// Called by the RTS to get the beginning of the dynamic heap. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn get_heap_base() -> usize {
+    unsafe { HEAP_BASE }
+}
+
+// Called by the RTS to get the static roots. Normally generated by the compiler
+#[no_mangle]
+extern "C" fn get_static_roots() -> SkewedPtr {
+    unsafe { skew(STATIC_ROOTS.as_ptr() as usize) }
 }
 
 // Called by the RTS to panic
 #[no_mangle]
-extern "C" fn rts_trap(_msg: *const u8, _len: Bytes<u32>) -> ! {
+extern "C" fn rts_trap(_msg: *const u8, _len: Bytes<usize>) -> ! {
     panic!("rts_trap_with called");
 }
 
@@ -41,7 +170,61 @@ extern "C" fn bigint_trap() -> ! {
 
 // Called by the RTS for debug prints
 #[no_mangle]
-unsafe extern "C" fn print_ptr(ptr: usize, len: u32) {
-    let str: &[u8] = core::slice::from_raw_parts(ptr as *const u8, len as usize);
+unsafe extern "C" fn print_ptr(ptr: usize, len: usize) {
+    let str: &[u8] = core::slice::from_raw_parts(ptr as *const u8, len);
     println!("[RTS] {}", String::from_utf8_lossy(str));
 }
+
+/// Stable memory of the tests, see `region.rs`
+pub(crate) static mut STABLE_MEM: Vec<u8> = Vec::new();
+
+/// Maximum size of the stable memory of the tests in pages
+pub(crate) const MAX_STABLE_PAGES: usize = 1024;
+
+// Called by the RTS for stable memory. Normally generated by the compiler (`ic0.stable_*`)
+#[no_mangle]
+unsafe extern "C" fn stable_mem_size() -> u32 {
+    (STABLE_MEM.len() / 65536) as u32
+}
+
+#[no_mangle]
+unsafe extern "C" fn stable_mem_grow(pages: u32) -> i32 {
+    let old_pages = STABLE_MEM.len() / 65536;
+    if old_pages + pages as usize > MAX_STABLE_PAGES {
+        return -1;
+    }
+    STABLE_MEM.resize((old_pages + pages as usize) * 65536, 0);
+    old_pages as i32
+}
+
+#[no_mangle]
+unsafe extern "C" fn stable_mem_read(dst: *mut u8, offset: u32, len: u32) {
+    let src = &STABLE_MEM[stable_mem_range(offset, len)];
+    core::ptr::copy_nonoverlapping(src.as_ptr(), dst, len as usize);
+}
+
+#[no_mangle]
+unsafe extern "C" fn stable_mem_write(offset: u32, src: *const u8, len: u32) {
+    let dst = &mut STABLE_MEM[stable_mem_range(offset, len)];
+    core::ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), len as usize);
+}
+
+/// The bytes of an access, trapping (as `ic0.stable_*`) when not in the stable memory. The end is
+/// computed in 64 bits, as it overflows `usize` on wasm32.
+unsafe fn stable_mem_range(offset: u32, len: u32) -> core::ops::Range<usize> {
+    let end = u64::from(offset) + u64::from(len);
+    if end > STABLE_MEM.len() as u64 {
+        panic!("stable memory access out of bounds");
+    }
+    offset as usize..end as usize
+}
+
+/// Value of the global timer of the tests, see `timer.rs`
+pub(crate) static mut GLOBAL_TIMER: u64 = 0;
+
+// Called by the RTS to set the global timer. Normally generated by the compiler
+// (`ic0.global_timer_set`)
+#[no_mangle]
+unsafe extern "C" fn timer_global_set(timestamp: u64) {
+    GLOBAL_TIMER = timestamp;
+}
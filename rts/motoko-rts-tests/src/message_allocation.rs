@@ -0,0 +1,81 @@
+use motoko_rts::types::{size_of, Array, Blob, Bytes, SkewedPtr, Words};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn collect();
+    fn message_alloc_reset();
+    fn get_message_allocation() -> *const MessageAllocation;
+
+    /// Locations of the heap pointer, the limit, the allocation counter, and the object counter of
+    /// the fast path
+    fn get_alloc_fast_path() -> *const [*mut usize; 4];
+}
+
+/// Mirrors `gc::MessageAllocation`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MessageAllocation {
+    bytes: u64,
+    objects: usize,
+}
+
+unsafe fn message_allocation() -> MessageAllocation {
+    *get_message_allocation()
+}
+
+pub unsafe fn test() {
+    println!("Testing per-message allocation counters ...");
+
+    message_alloc_reset();
+    assert_eq!(
+        message_allocation(),
+        MessageAllocation {
+            bytes: 0,
+            objects: 0
+        }
+    );
+
+    alloc_blob(Bytes(10));
+    alloc_array(3);
+    let blob_size = size_of::<Blob>() + Bytes(10usize).to_words();
+    let array_size = size_of::<Array>() + Words(3);
+    let bytes = (blob_size + array_size).to_bytes().0 as u64;
+    assert_eq!(
+        message_allocation(),
+        MessageAllocation { bytes, objects: 2 }
+    );
+
+    // Allocations of the fast path are counted too
+    let [_, _, allocated, objects] = *get_alloc_fast_path();
+    *allocated += 16;
+    *objects += 1;
+    assert_eq!(
+        message_allocation(),
+        MessageAllocation {
+            bytes: bytes + 16,
+            objects: 3
+        }
+    );
+
+    // Collections don't change the counters
+    collect();
+    assert_eq!(
+        message_allocation(),
+        MessageAllocation {
+            bytes: bytes + 16,
+            objects: 3
+        }
+    );
+
+    message_alloc_reset();
+    assert_eq!(
+        message_allocation(),
+        MessageAllocation {
+            bytes: 0,
+            objects: 0
+        }
+    );
+
+    println!("OK");
+}
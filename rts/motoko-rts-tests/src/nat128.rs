@@ -0,0 +1,144 @@
+use crate::utils::string_of_text;
+
+use motoko_rts::bigint::{bigint_eq, bigint_of_int32, bigint_of_text_radix, bigint_to_text};
+use motoko_rts::nat128::*;
+use motoko_rts::text::text_of_str;
+use motoko_rts::types::SkewedPtr;
+
+use quickcheck::{quickcheck, TestResult};
+
+pub unsafe fn test() {
+    println!("Testing Nat128 ...");
+
+    let max = u128::MAX;
+
+    // Checked operations, the result is unchanged on failure
+    assert_eq!(add(1 << 64, max >> 1), Some((1 << 64) + (max >> 1)));
+    assert_eq!(add(max, 1), None);
+    assert_eq!(sub(1 << 64, 1), Some(u128::from(u64::MAX)));
+    assert_eq!(sub(1, 2), None);
+    assert_eq!(mul(1 << 100, 1 << 27), Some(1 << 127));
+    assert_eq!(mul(1 << 100, 1 << 28), None);
+    assert_eq!(div(max, 1 << 64), Some(u128::from(u64::MAX)));
+    assert_eq!(div(5, 0), None);
+    let mut r = Nat128::new(7);
+    assert_eq!(nat128_div(5, 0, 0, 0, &mut r), 0);
+    assert_eq!(r.get(), 7);
+
+    assert_eq!(compare(1 << 64, u128::from(u64::MAX)), 1);
+    assert_eq!(compare(3, 3), 0);
+    assert_eq!(compare(3, max), -1);
+
+    // Big integers
+    for &n in &[0, 1024, 1025, u128::from(u64::MAX) + 1, max] {
+        let big = nat128_to_bigint(n as u64, (n >> 64) as u64);
+        assert!(bigint_eq(
+            big,
+            bigint_of_text_radix(text_of_str(&n.to_string()), 10)
+        ));
+        assert_eq!(of_bigint(big), Some(n));
+    }
+    assert_eq!(of_bigint(bigint_of_int32(-1)), None);
+    let too_big = bigint_of_text_radix(
+        text_of_str("0x1_0000_0000_0000_0000_0000_0000_0000_0000"),
+        0,
+    );
+    assert_eq!(of_bigint(too_big), None);
+
+    // Text
+    assert_eq!(to_text(0, false), "0");
+    assert_eq!(to_text(1_000, true), "1_000");
+    assert_eq!(to_text(100, true), "100");
+    assert_eq!(to_text(max, false), max.to_string());
+    assert_eq!(
+        to_text(max, true),
+        string_of_text(bigint_to_text(nat128_to_bigint(u64::MAX, u64::MAX), true))
+    );
+    assert_eq!(
+        of_text("340282366920938463463374607431768211455"),
+        Some(max)
+    );
+    assert_eq!(of_text("340282366920938463463374607431768211456"), None);
+    assert_eq!(of_text("1_000_000"), Some(1_000_000));
+    assert_eq!(of_text("007"), Some(7));
+    for bad in &["", "_1", "1_", "1__0", "-1", "+1", "1.0", "0x10"] {
+        assert_eq!(of_text(bad), None);
+    }
+
+    quickcheck(arith_prop as fn(u64, u64, u64, u64) -> TestResult);
+}
+
+fn arith_prop(a_lo: u64, a_hi: u64, b_lo: u64, b_hi: u64) -> TestResult {
+    let a = u128::from(a_hi) << 64 | u128::from(a_lo);
+    // Small operands too, for multiplications that don't overflow
+    let b = (u128::from(b_hi) << 64 | u128::from(b_lo)) >> (a_lo % 128);
+    unsafe {
+        assert_eq!(add(a, b), a.checked_add(b));
+        assert_eq!(sub(a, b), a.checked_sub(b));
+        assert_eq!(mul(a >> 64, b), (a >> 64).checked_mul(b));
+        assert_eq!(div(a, b), a.checked_div(b));
+        assert_eq!(compare(a, b), a.cmp(&b) as i32);
+        assert_eq!(of_text(&to_text(a, a_lo & 1 == 0)), Some(a));
+    }
+    TestResult::passed()
+}
+
+type Op = unsafe extern "C" fn(u64, u64, u64, u64, *mut Nat128) -> u32;
+
+unsafe fn apply(op: Op, a: u128, b: u128) -> Option<u128> {
+    let mut r = Nat128::new(0);
+    if op(
+        a as u64,
+        (a >> 64) as u64,
+        b as u64,
+        (b >> 64) as u64,
+        &mut r,
+    ) == 1
+    {
+        Some(r.get())
+    } else {
+        None
+    }
+}
+
+unsafe fn add(a: u128, b: u128) -> Option<u128> {
+    apply(nat128_add, a, b)
+}
+
+unsafe fn sub(a: u128, b: u128) -> Option<u128> {
+    apply(nat128_sub, a, b)
+}
+
+unsafe fn mul(a: u128, b: u128) -> Option<u128> {
+    apply(nat128_mul, a, b)
+}
+
+unsafe fn div(a: u128, b: u128) -> Option<u128> {
+    apply(nat128_div, a, b)
+}
+
+fn compare(a: u128, b: u128) -> i32 {
+    nat128_compare(a as u64, (a >> 64) as u64, b as u64, (b >> 64) as u64)
+}
+
+unsafe fn of_bigint(n: SkewedPtr) -> Option<u128> {
+    let mut r = Nat128::new(0);
+    if nat128_of_bigint(n, &mut r) == 1 {
+        Some(r.get())
+    } else {
+        None
+    }
+}
+
+unsafe fn to_text(n: u128, separators: bool) -> String {
+    string_of_text(nat128_to_text(n as u64, (n >> 64) as u64, separators))
+}
+
+unsafe fn of_text(s: &str) -> Option<u128> {
+    let mut r = Nat128::new(0);
+    if nat128_of_text(text_of_str(s), &mut r) == 1 {
+        Some(r.get())
+    } else {
+        None
+    }
+}
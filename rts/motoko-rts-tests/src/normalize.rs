@@ -0,0 +1,51 @@
+use crate::utils::string_of_text;
+
+use motoko_rts::normalize::text_normalize_nfc;
+use motoko_rts::text::{text_concat, text_of_str};
+
+pub unsafe fn test() {
+    println!("Testing text normalization ...");
+
+    let cases: &[(&str, &str)] = &[
+        ("", ""),
+        ("abc", "abc"),
+        // Composition
+        ("e\u{301}", "\u{e9}"),
+        ("A\u{30a}", "\u{c5}"),
+        // Singleton decomposition
+        ("\u{212b}", "\u{c5}"),
+        // Reordering of the combining marks before composition
+        ("d\u{307}\u{323}", "\u{1e0d}\u{307}"),
+        ("\u{1e0b}\u{323}", "\u{1e0d}\u{307}"),
+        ("a\u{328}\u{301}\u{323}", "\u{105}\u{323}\u{301}"),
+        // Non-starter decomposition
+        ("\u{344}", "\u{308}\u{301}"),
+        // Composition exclusion
+        ("\u{958}", "\u{915}\u{93c}"),
+        // Hangul syllables
+        ("\u{1100}\u{1161}\u{11a8}", "\u{ac01}"),
+        ("\u{ac00}\u{11a8}", "\u{ac01}"),
+        ("\u{ac01}", "\u{ac01}"),
+        // Blocked by a mark of the same class, but not by one of a lower class
+        ("e\u{301}\u{301}", "\u{e9}\u{301}"),
+        ("e\u{31b}\u{301}", "\u{e9}\u{31b}"),
+    ];
+
+    for (input, expected) in cases {
+        let normalized = text_normalize_nfc(text_of_str(input));
+        assert_eq!(string_of_text(normalized), *expected, "input {:?}", input);
+    }
+
+    // Normalized texts are not copied
+    let text = text_of_str("\u{e9}t\u{e9}");
+    assert_eq!(text_normalize_nfc(text).unskew(), text.unskew());
+
+    // A combining mark at the beginning of the second part of a concatenation
+    let text = text_concat(text_of_str("abcdefghe"), text_of_str("\u{301}fghijklmn"));
+    assert_eq!(
+        string_of_text(text_normalize_nfc(text)),
+        "abcdefgh\u{e9}fghijklmn"
+    );
+
+    println!("OK");
+}
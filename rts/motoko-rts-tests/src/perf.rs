@@ -0,0 +1,42 @@
+use motoko_rts::perf::*;
+
+pub unsafe fn test() {
+    println!("Testing performance counters ...");
+
+    // Instructions outside of sections are not counted
+    perf_leave(10);
+    perf_enter(Subsystem::Gc as u32, 100);
+    perf_leave(150);
+    assert_eq!(perf_instructions(Subsystem::Gc), 50);
+
+    // Nested sections, the inner instructions are only counted for the inner subsystem
+    perf_enter(Subsystem::Stabilization as u32, 200);
+    perf_enter(Subsystem::CandidDecode as u32, 210);
+    perf_leave(250);
+    perf_leave(300);
+    assert_eq!(perf_instructions(Subsystem::Stabilization), 60);
+    assert_eq!(perf_instructions(Subsystem::CandidDecode), 40);
+
+    // The counter restarts in the next message
+    perf_enter(Subsystem::CandidEncode as u32, 5);
+    perf_leave(25);
+    assert_eq!(perf_instructions(Subsystem::CandidEncode), 20);
+
+    // Deeper sections than kept count for the innermost kept one
+    perf_enter(Subsystem::Gc as u32, 0);
+    for i in 0..16 {
+        perf_enter(Subsystem::CandidEncode as u32, 10 + i);
+    }
+    for i in 0..16 {
+        perf_leave(100 + i);
+    }
+    perf_leave(200);
+    assert_eq!(perf_instructions(Subsystem::CandidEncode), 20 + 105);
+    assert_eq!(perf_instructions(Subsystem::Gc), 50 + 10 + 85);
+
+    let counters = &*get_perf_counters();
+    assert_eq!(counters.gc, 145);
+    assert_eq!(counters.candid_decode, 40);
+    assert_eq!(counters.candid_encode, 125);
+    assert_eq!(counters.stabilization, 60);
+}
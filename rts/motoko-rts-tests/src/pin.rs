@@ -0,0 +1,170 @@
+use crate::closure_table::RtsGcStats;
+use crate::static_root;
+
+use motoko_rts::pin::{pin_object, pinned_count, unpin_object};
+use motoko_rts::text::text_of_str;
+use motoko_rts::types::{Bytes, SkewedPtr};
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+    fn generational_gc();
+    fn incremental_gc();
+    fn mark_sweep_gc();
+    fn gc_force();
+    fn get_heap_size() -> Bytes<usize>;
+    fn get_gc_stats() -> *const RtsGcStats;
+    fn set_gc_schedule(target_utilization: u32, min_interval: u32, max_interval: u32);
+}
+
+pub unsafe fn test() {
+    println!("Testing pinning ...");
+
+    assert_eq!(pinned_count(), 0);
+
+    // More than the initial table size, to exercise growing the table
+    const N: usize = 40;
+
+    let mut objects = vec![];
+    for i in 0..N {
+        let obj = text_of_str("pinned");
+        pin_object(obj);
+        objects.push(obj);
+        assert_eq!(pinned_count(), i + 1);
+    }
+
+    // Pins nest
+    pin_object(objects[0]);
+    assert_eq!(pinned_count(), N + 1);
+
+    // Unpin in a different order than pinned
+    for (i, obj) in objects.iter().enumerate().rev() {
+        unpin_object(*obj);
+        assert_eq!(pinned_count(), i + 1);
+    }
+
+    unpin_object(objects[0]);
+    assert_eq!(pinned_count(), 0);
+
+    test_collectors();
+    test_incremental_cycle();
+
+    println!("OK");
+}
+
+/// A pinned object after garbage stays where it is, and is kept alive. The garbage after it is
+/// still reclaimed by the collectors that move objects.
+unsafe fn test_collectors() {
+    let collectors: [(&str, unsafe extern "C" fn(), bool); 4] = [
+        ("copying", collect, true),
+        ("generational", generational_gc, true),
+        ("incremental", incremental_gc, true),
+        ("mark-sweep", mark_sweep_gc, false),
+    ];
+
+    for &(name, collector, moves) in collectors.iter() {
+        alloc_blob(Bytes(64));
+        let obj = new_blob(0xA1);
+        pin_object(obj);
+        alloc_blob(Bytes(4 << 20));
+        let heap_size = get_heap_size();
+
+        // A whole cycle of the incremental GC, a major collection of the generational GC
+        gc_force();
+        collector();
+
+        check_blob(obj, 0xA1, name);
+        if moves {
+            assert!(get_heap_size().0 < heap_size.0 - (2 << 20), "{}", name);
+        }
+
+        // Roots are not updated either
+        (*static_root(0)).field = obj;
+        gc_force();
+        collector();
+        assert_eq!((*static_root(0)).field.0, obj.0, "{}", name);
+
+        unpin_object(obj);
+        (*static_root(0)).field = SkewedPtr(0);
+    }
+}
+
+/// Objects pinned during a cycle of the incremental GC are not moved by the cycle: an object
+/// allocated during the cycle, and an old object that compacting the heap would move
+unsafe fn test_incremental_cycle() {
+    // A cycle starts at every scheduling point
+    set_gc_schedule(67, 1, 1);
+
+    for &pin_new in &[true, false] {
+        // Finish the cycle in progress, which starts without pins so that there is no region table
+        gc_force();
+        incremental_gc();
+
+        alloc_blob(Bytes(64));
+        let old = new_blob(0xB2);
+        (*static_root(0)).field = old;
+
+        // More live data than an increment marks or sweeps, so that the cycle takes several
+        alloc_blob(Bytes(64));
+        let live = alloc_array(24);
+        for i in 0..24 {
+            live.as_array().set(i, alloc_blob(Bytes(256 << 10)));
+        }
+        (*static_root(1)).field = live;
+
+        // Mark phase
+        let collections = (*get_gc_stats()).collections;
+        incremental_gc();
+        assert_eq!((*get_gc_stats()).collections, collections);
+
+        let obj = if pin_new { new_blob(0xC3) } else { old };
+        pin_object(obj);
+        (*static_root(2)).field = obj;
+
+        // Done with the cycle and the next one, which starts with the pin
+        for _ in 0..10 {
+            incremental_gc();
+        }
+
+        check_blob(obj, if pin_new { 0xC3 } else { 0xB2 }, "incremental");
+        assert_eq!((*static_root(2)).field.0, obj.0);
+        assert_eq!((*static_root(0)).field.as_blob().get(0), 0xB2);
+        let live = (*static_root(1)).field.as_array();
+        for i in 0..24 {
+            assert_eq!(live.get(i).as_blob().len(), Bytes(256 << 10));
+        }
+
+        unpin_object(obj);
+        (*static_root(0)).field = SkewedPtr(0);
+        (*static_root(1)).field = SkewedPtr(0);
+        (*static_root(2)).field = SkewedPtr(0);
+    }
+
+    set_gc_schedule(67, 1, u32::MAX);
+    gc_force();
+    incremental_gc();
+
+    // A forced cycle is done right away
+    let collections = (*get_gc_stats()).collections;
+    gc_force();
+    incremental_gc();
+    assert_eq!((*get_gc_stats()).collections, collections + 1);
+}
+
+unsafe fn new_blob(byte: u8) -> SkewedPtr {
+    let blob = alloc_blob(Bytes(16));
+    for i in 0..16 {
+        blob.as_blob().set(i, byte);
+    }
+    blob
+}
+
+/// The blob at the (unchanged) address of `obj` has the contents given to `new_blob`
+unsafe fn check_blob(obj: SkewedPtr, byte: u8, name: &str) {
+    let blob = obj.as_blob();
+    assert_eq!(blob.len(), Bytes(16), "{}", name);
+    for i in 0..16 {
+        assert_eq!(blob.get(i), byte, "{}", name);
+    }
+}
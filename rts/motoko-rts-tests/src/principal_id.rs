@@ -1,7 +1,16 @@
-use motoko_rts::principal_id::{blob_of_principal, principal_of_blob};
-use motoko_rts::text::{text_compare, text_of_ptr_size, text_of_str};
+use crate::utils::{blob_of_bytes, bytes_of_blob};
+
+use motoko_rts::crc::compute_crc32;
+use motoko_rts::hex::{blob_of_hex, blob_to_hex};
+use motoko_rts::principal_id::{
+    account_identifier, base32_of_checksummed_blob, base32_to_blob, blob_of_principal,
+    principal_compare, principal_of_blob, principal_of_public_key, principal_of_text_checked,
+};
+use motoko_rts::text::{blob_of_text, text_compare, text_of_ptr_size, text_of_str};
 use motoko_rts::types::Bytes;
 
+use quickcheck::{quickcheck, TestResult};
+
 pub unsafe fn test() {
     println!("Testing principal id encoding ...");
 
@@ -10,13 +19,19 @@ pub unsafe fn test() {
     //
 
     assert_eq!(
-        text_compare(principal_of_blob(text_of_str("")), text_of_str("aaaaa-aa"),),
+        text_compare(
+            principal_of_blob(blob_of_text(text_of_str(""))),
+            text_of_str("aaaaa-aa"),
+        ),
         0,
     );
 
     assert_eq!(
         text_compare(
-            principal_of_blob(text_of_ptr_size(b"\xC0\xFE\xFE\xD0\x0D".as_ptr(), Bytes(5))),
+            principal_of_blob(blob_of_text(text_of_ptr_size(
+                b"\xC0\xFE\xFE\xD0\x0D".as_ptr(),
+                Bytes(5)
+            ))),
             text_of_str("bfozs-kwa73-7nadi"),
         ),
         0
@@ -38,4 +53,170 @@ pub unsafe fn test() {
         ),
         0
     );
+
+    //
+    // Checked decoding
+    //
+
+    assert_eq!(
+        text_compare(
+            principal_of_text_checked(text_of_str("bfozs-kwa73-7nadi")),
+            text_of_ptr_size(b"\xC0\xFE\xFE\xD0\x0D".as_ptr(), Bytes(5))
+        ),
+        0
+    );
+
+    for text in &[
+        "",
+        "aaaaa",
+        "AAAAA-AA",
+        "aaaaaaa",
+        "aaaaa-aa-",
+        "bfozs-kwa73-7nadj",
+        "bfozs-kwa73-7nad1",
+        "bfozs-kwa73-7nadi ",
+        "bfozs-kwa73-7nädi",
+    ] {
+        assert_eq!(
+            principal_of_text_checked(text_of_str(text)).0,
+            0,
+            "{:?}",
+            text
+        );
+    }
+
+    // At most 29 bytes
+    let bytes = [0xabu8; 30];
+    let text = principal_of_blob(blob_of_text(text_of_ptr_size(bytes.as_ptr(), Bytes(29))));
+    assert_ne!(principal_of_text_checked(text).0, 0);
+    let text = principal_of_blob(blob_of_text(text_of_ptr_size(bytes.as_ptr(), Bytes(30))));
+    assert_eq!(principal_of_text_checked(text).0, 0);
+
+    quickcheck(base32_roundtrip_prop as fn(Vec<u8>) -> TestResult);
+    quickcheck(principal_roundtrip_prop as fn(Vec<u8>) -> TestResult);
+
+    //
+    // Ordering
+    //
+
+    let principals: Vec<_> = [
+        "aaaaa-aa",
+        "2vxsx-fae",
+        "5h74t-uflzu",
+        "em77e-bvlzu-aq",
+        "bfozs-kwa73-7nadi",
+    ]
+    .iter()
+    .map(|text| blob_of_principal(text_of_str(text)))
+    .collect();
+    // Sorted: "", "\x04", "\xab\xcd", "\xab\xcd\x01", "\xc0\xfe\xfe\xd0\x0d"
+    for (i, p1) in principals.iter().enumerate() {
+        for (j, p2) in principals.iter().enumerate() {
+            assert_eq!(principal_compare(*p1, *p2), (i as i32 - j as i32).signum());
+        }
+    }
+
+    //
+    // Self-authenticating principals
+    //
+
+    let ed25519_key: Vec<u8> = b"\x30\x2a\x30\x05\x06\x03\x2b\x65\x70\x03\x21\x00"
+        .iter()
+        .copied()
+        .chain(0..32)
+        .collect();
+    for (key, principal) in &[
+        (
+            &[][..],
+            "o2x4y-ywrji-biykr-2fpeu-oyicx-muien-gecwr-lah4c-r2tcv-rnt4q-xqe",
+        ),
+        (
+            &ed25519_key[..],
+            "7gheb-jchfc-rcrvr-e6jtu-yplhf-2463c-shoi4-2zgcr-mxtd4-e4v72-6qe",
+        ),
+    ] {
+        let key = blob_of_text(text_of_ptr_size(key.as_ptr(), Bytes(key.len())));
+        assert_eq!(
+            text_compare(
+                principal_of_blob(principal_of_public_key(key)),
+                text_of_str(principal),
+            ),
+            0,
+        );
+    }
+
+    //
+    // Account identifiers
+    //
+
+    let subaccount_1 = format!("01{}", "00".repeat(31));
+    for (principal, subaccount, account) in &[
+        (
+            "04",
+            "00".repeat(32),
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79",
+        ),
+        (
+            "04",
+            subaccount_1.clone(),
+            "1fba27d8ddf404c7ddc16c083871679913fa835443e15090e17ee382e7d8bbbe",
+        ),
+        (
+            "c0fefed00d",
+            "00".repeat(32),
+            "ce7478994b87f86b3b3dc071f82dd8a15b8bb9b07139c8b62dce69aa13fd7cba",
+        ),
+    ] {
+        let account_id = account_identifier(
+            blob_of_hex(text_of_str(principal)),
+            blob_of_hex(text_of_str(subaccount)),
+        );
+        assert_eq!(
+            text_compare(blob_to_hex(account_id), text_of_str(account)),
+            0
+        );
+    }
+}
+
+/// Decoding the checksummed base32 gives the checksum (big-endian) and the bytes
+fn base32_roundtrip_prop(input: Vec<u8>) -> TestResult {
+    unsafe {
+        let blob = blob_of_bytes(&input);
+        let decoded = bytes_of_blob(base32_to_blob(base32_of_checksummed_blob(blob)));
+
+        if decoded.len() != input.len() + 4 {
+            return TestResult::error(format!("length of {:?}", decoded));
+        }
+        if decoded[..4] != compute_crc32(blob).to_be_bytes() {
+            return TestResult::error("checksum");
+        }
+        if decoded[4..] != input[..] {
+            return TestResult::error(format!("{:?} decoded to {:?}", input, decoded));
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// The textual representation of a principal decodes to its bytes, checked when it has at most
+/// 29 bytes
+fn principal_roundtrip_prop(input: Vec<u8>) -> TestResult {
+    unsafe {
+        let text = principal_of_blob(blob_of_bytes(&input));
+
+        if bytes_of_blob(blob_of_principal(text)) != input {
+            return TestResult::error(format!("blob_of_principal of {:?}", input));
+        }
+
+        let checked = principal_of_text_checked(text);
+        if input.len() <= 29 {
+            if checked.0 == 0 || bytes_of_blob(checked) != input {
+                return TestResult::error(format!("principal_of_text_checked of {:?}", input));
+            }
+        } else if checked.0 != 0 {
+            return TestResult::error(format!("{} bytes accepted", input.len()));
+        }
+
+        TestResult::passed()
+    }
 }
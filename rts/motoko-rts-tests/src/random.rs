@@ -0,0 +1,77 @@
+use crate::utils::blob_of_bytes;
+
+use motoko_rts::random::*;
+use motoko_rts::types::Bytes;
+
+use quickcheck::{quickcheck, TestResult};
+
+pub unsafe fn test() {
+    println!("Testing random numbers ...");
+
+    // Reference output of xoshiro256++ for the state [1, 2, 3, 4]
+    let mut seed = vec![];
+    for word in &[1u64, 2, 3, 4] {
+        seed.extend_from_slice(&word.to_le_bytes());
+    }
+    random_seed_blob(blob_of_bytes(&seed));
+    let expected = [
+        41943041,
+        58720359,
+        3588806011781223,
+        3591011842654386,
+        9228616714210784205,
+        9973669472204895162,
+        14011001112246962877,
+        12406186145184390807,
+        15849039046786891736,
+        10450023813501588000,
+    ];
+    for n in expected.iter() {
+        assert_eq!(random_next(), *n);
+    }
+
+    // The all-zero state is replaced
+    random_seed_blob(blob_of_bytes(&[0; STATE_SIZE]));
+    let first = random_next();
+    random_seed(0);
+    assert_eq!(random_next(), first);
+
+    quickcheck(deterministic as fn(u64) -> bool);
+    quickcheck(blob_continues_sequence as fn(u64, u8) -> TestResult);
+}
+
+fn deterministic(seed: u64) -> bool {
+    unsafe {
+        random_seed(seed);
+        let numbers: Vec<u64> = (0..8).map(|_| random_next()).collect();
+        random_seed(seed);
+        numbers.iter().all(|n| *n == random_next())
+    }
+}
+
+fn blob_continues_sequence(seed: u64, len: u8) -> TestResult {
+    unsafe {
+        let len = len as usize;
+
+        random_seed(seed);
+        let mut expected = vec![];
+        for _ in 0..(len + 7) / 8 {
+            expected.extend_from_slice(&random_next().to_le_bytes());
+        }
+        let next = random_next();
+
+        random_seed(seed);
+        let blob = blob_random(Bytes(len)).as_blob();
+        if blob.len().0 != len {
+            return TestResult::error("wrong blob size");
+        }
+        let bytes: Vec<u8> = (0..len).map(|i| blob.get(i)).collect();
+        if bytes[..] != expected[..len] {
+            return TestResult::error("blob is not the sequence");
+        }
+        if random_next() != next {
+            return TestResult::error("sequence doesn't continue after the blob");
+        }
+        TestResult::passed()
+    }
+}
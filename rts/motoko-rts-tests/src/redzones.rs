@@ -0,0 +1,46 @@
+use motoko_rts::types::{size_of, Blob, Bytes, SkewedPtr, TAG_BLOB, WORD_SIZE};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+}
+
+/// See `alloc/redzones.rs`
+const TAG_REDZONE: usize = 17;
+const CANARY: usize = 0xCAFE_F00D;
+
+pub unsafe fn test() {
+    println!("Testing redzones ...");
+
+    let blob = alloc_blob(Bytes(10)).unskew() as *mut usize;
+    assert_eq!(*blob, TAG_BLOB);
+
+    // Redzone before the blob records the allocation size
+    let n = size_of::<Blob>() + Bytes(10).to_words();
+    assert_eq!(*blob.sub(2), TAG_REDZONE);
+    assert_eq!(*blob.sub(1), n.0);
+
+    // Redzone after the blob
+    assert_eq!(*blob.add(n.0), TAG_REDZONE);
+    assert_eq!(*blob.add(n.0 + 1), CANARY);
+
+    // Writing the whole payload, including the padding, is fine
+    let payload = blob.add(size_of::<Blob>().0) as *mut u8;
+    for i in 0..Bytes(10).to_words().0 * WORD_SIZE {
+        *payload.add(i) = 0xFF;
+    }
+
+    // A shrunk blob is fine, the slop is zeroed as in `principal_id.rs`
+    let blob = alloc_blob(Bytes(100)).unskew() as *mut usize;
+    (*(blob as *mut Blob)).len = Bytes(10);
+    let old_size = size_of::<Blob>() + Bytes(100).to_words();
+    let new_size = size_of::<Blob>() + Bytes(10).to_words();
+    for i in new_size.0..old_size.0 {
+        *blob.add(i) = 0;
+    }
+
+    // The redzones are checked before collecting
+    collect();
+
+    println!("OK");
+}
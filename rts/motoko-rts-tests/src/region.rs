@@ -0,0 +1,154 @@
+use crate::idl_encode::{array, scalar, MUT_ARRAY, NAT};
+use crate::utils::{blob_of_bytes, read_stable_vars};
+use crate::{MAX_STABLE_PAGES, STABLE_MEM};
+
+use motoko_rts::buf::{region_buf_new, region_buf_read, region_buf_remaining, StableBuf};
+use motoko_rts::idl_encode::idl_serialize_stable;
+
+use motoko_rts::crc::compute_crc32;
+use motoko_rts::region::{
+    region_grow, region_load_blob, region_new, region_read, region_size, region_write,
+    stable_memory_header, stable_vars_check, stable_vars_size, stable_vars_stream, BLOCK_PAGES,
+    STABLE_VARS, VERSION,
+};
+use motoko_rts::stream::{
+    region_stream_flush, region_stream_new, region_stream_write, Stream, CHUNK_SIZE,
+};
+use motoko_rts::text::{blob_of_text, text_of_str};
+
+const BLOCK_SIZE: u64 = BLOCK_PAGES * 65536;
+
+pub unsafe fn test() {
+    println!("Testing stable memory regions ...");
+
+    // Stable variables in the layout before regions
+    let legacy = b"\x06\x00\x00\x00DIDL\x00\x00";
+    STABLE_MEM.resize(65536, 0);
+    STABLE_MEM[..legacy.len()].copy_from_slice(legacy);
+    assert_eq!(stable_vars_size(), 6);
+    assert_eq!(read_stable_vars(), b"DIDL\x00\x00");
+    let header = stable_memory_header();
+    assert_eq!(((*header).version, (*header).regions), (0, 0));
+
+    // The first region formats stable memory, region 0 is for the stable variables
+    let r1 = region_new();
+    assert_eq!(r1, 1);
+    assert_eq!(stable_vars_size(), 0);
+    assert_eq!(&STABLE_MEM[..8], b"\x00\x00\x00\x00MOrg");
+    assert_eq!(STABLE_MEM[8..12], VERSION.to_le_bytes());
+    // The header as found before formatting
+    assert_eq!((*header).version, 0);
+
+    assert_eq!(region_grow(r1, 3), 0);
+    assert_eq!(region_size(r1), 3);
+
+    // Blocks of different regions are interleaved
+    let r2 = region_new();
+    assert_eq!(r2, 2);
+    assert_eq!(region_grow(r2, 2 * BLOCK_PAGES), 0);
+    assert_eq!(region_grow(r1, BLOCK_PAGES), 3);
+    assert_eq!(region_size(r1), BLOCK_PAGES + 3);
+    assert_eq!(STABLE_MEM.len() as u64, (1 + 4 * BLOCK_PAGES) * 65536);
+
+    // Accesses across blocks, regions are isolated
+    let offset = BLOCK_SIZE - 5;
+    region_write(r1, offset, b"0123456789".as_ptr(), 10);
+    region_write(r2, offset, b"abcdefghij".as_ptr(), 10);
+    assert_eq!(read_region(r1, offset, 10), b"0123456789");
+    assert_eq!(read_region(r2, offset, 10), b"abcdefghij");
+    assert_eq!(read_region(r2, 0, 3), b"\x00\x00\x00");
+
+    // Stable variables in region 0, streamed in small and large writes
+    let data: Vec<u8> = (0..3 * CHUNK_SIZE + 100).map(|i| i as u8).collect();
+    let mut stream = stable_vars_stream(data.len() as u32, false);
+    stream.write(&data[..10]);
+    stream.write(&data[10..CHUNK_SIZE + 20]);
+    for i in CHUNK_SIZE + 20..data.len() {
+        stream.write(&data[i..i + 1]);
+    }
+    stream.flush();
+    assert_eq!(stream.written(), data.len());
+    assert_eq!(stable_vars_size() as usize, data.len());
+    assert_eq!(read_stable_vars(), data);
+    assert_eq!(region_size(STABLE_VARS), 1);
+    assert_eq!(read_region(r1, offset, 10), b"0123456789");
+
+    // Read back in chunks, in small and large reads
+    let mut buf = StableBuf::new(STABLE_VARS, 0, data.len() as u64);
+    let mut read = vec![0u8; data.len()];
+    read[0] = buf.read_byte();
+    buf.read(read[1..].as_mut_ptr(), 9);
+    buf.read(read[10..].as_mut_ptr(), CHUNK_SIZE + 10);
+    for i in CHUNK_SIZE + 20..data.len() {
+        read[i] = buf.read_byte();
+    }
+    assert_eq!(read, data);
+    assert_eq!(buf.remaining(), 0);
+
+    // Encoding of stable variables, streamed to region 0
+    let desc: Vec<u8> = [MUT_ARRAY, 2, NAT]
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    let tydesc = b"DIDL";
+    idl_serialize_stable(desc.as_ptr(), 3, array(&[scalar(5)]), tydesc.as_ptr(), 4);
+    assert_eq!(
+        read_stable_vars(),
+        b"DIDL\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x05"
+    );
+
+    // Checked against the checksum after them
+    assert!(stable_vars_check().is_ok());
+    region_write(STABLE_VARS, 4, b"DIDM".as_ptr(), 4);
+    match stable_vars_check() {
+        Err(mismatch) => {
+            assert_eq!(mismatch.size, 15);
+            assert_eq!(
+                mismatch.expected,
+                crc32_of(b"DIDL\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x05")
+            );
+            assert_eq!(
+                mismatch.found,
+                crc32_of(b"DIDM\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x05")
+            );
+        }
+        Ok(()) => panic!("corrupted stable variables not found"),
+    }
+
+    // Streams of the generated code, in blobs
+    let stream = region_stream_new(r2, 100);
+    region_stream_write(stream, blob_of_text(text_of_str("log ")));
+    region_stream_write(stream, blob_of_text(text_of_str("entry")));
+    assert_eq!(read_region(r2, 100, 4), b"\x00\x00\x00\x00");
+    let long = "x".repeat(CHUNK_SIZE);
+    region_stream_write(stream, blob_of_text(text_of_str(&long)));
+    assert_eq!(region_stream_flush(stream), 100 + 9 + CHUNK_SIZE as u64);
+    assert_eq!(read_region(r2, 100, 9), b"log entry");
+    let blob = region_load_blob(r2, 109, CHUNK_SIZE as u32).as_blob();
+    assert_eq!(blob.len().0, CHUNK_SIZE);
+    assert_eq!(*blob.payload_addr().add(CHUNK_SIZE - 1), b'x');
+
+    // Buffers of the generated code, across blocks
+    let buf = region_buf_new(r1, offset, 10);
+    let blob = region_buf_read(buf, 4).as_blob();
+    assert_eq!(*blob.payload_addr().add(3), b'3');
+    assert_eq!(region_buf_remaining(buf), 6);
+    let blob = region_buf_read(buf, 6).as_blob();
+    assert_eq!(*blob.payload_addr().add(5), b'9');
+    assert_eq!(region_buf_remaining(buf), 0);
+
+    // Growing fails when stable memory cannot grow
+    assert_eq!(region_grow(r1, MAX_STABLE_PAGES as u64), u64::MAX);
+    assert_eq!(region_size(r1), BLOCK_PAGES + 3);
+    assert_eq!(region_grow(r1, u64::MAX), u64::MAX);
+}
+
+unsafe fn read_region(id: u32, offset: u64, len: u32) -> Vec<u8> {
+    let mut buf = vec![0u8; len as usize];
+    region_read(id, offset, buf.as_mut_ptr(), len);
+    buf
+}
+
+unsafe fn crc32_of(bytes: &[u8]) -> u32 {
+    compute_crc32(blob_of_bytes(bytes))
+}
@@ -0,0 +1,59 @@
+use motoko_rts::scratch::scratch_alloc;
+use motoko_rts::types::{Bytes, WORD_SIZE};
+
+extern "C" {
+    fn collect();
+    fn gc_defer(defer: bool);
+}
+
+pub unsafe fn test() {
+    println!("Testing scratch space ...");
+
+    // Buffers are word-aligned and allocated one after the other
+    let buf1 = scratch_alloc(Bytes(10));
+    let buf2 = scratch_alloc(Bytes(100));
+    assert_eq!(buf1 as usize % WORD_SIZE, 0);
+    assert_eq!(
+        buf2 as usize,
+        buf1 as usize + (10 + WORD_SIZE - 1) / WORD_SIZE * WORD_SIZE
+    );
+    fill(buf1, 10, 1);
+    fill(buf2, 100, 2);
+
+    // More than fits in the first chunk, doesn't overwrite the other buffers
+    let buf3 = scratch_alloc(Bytes(10_000));
+    fill(buf3, 10_000, 3);
+    check(buf1, 10, 1);
+    check(buf2, 100, 2);
+
+    // At the end of a message without a collection the chunk is reused
+    gc_defer(true);
+    collect();
+    gc_defer(false);
+    assert_eq!(scratch_alloc(Bytes(4)), buf3);
+
+    // After a collection there's a new chunk, as big as the scratch space of the previous message
+    collect();
+    let buf4 = scratch_alloc(Bytes(9_000));
+    let buf5 = scratch_alloc(Bytes(1_000));
+    assert_eq!(buf5 as usize, buf4 as usize + 9_000);
+    fill(buf4, 9_000, 4);
+    fill(buf5, 1_000, 5);
+    check(buf4, 9_000, 4);
+
+    collect();
+
+    println!("OK");
+}
+
+unsafe fn fill(buf: *mut u8, len: usize, byte: u8) {
+    for i in 0..len {
+        *buf.add(i) = byte;
+    }
+}
+
+unsafe fn check(buf: *mut u8, len: usize, byte: u8) {
+    for i in 0..len {
+        assert_eq!(*buf.add(i), byte);
+    }
+}
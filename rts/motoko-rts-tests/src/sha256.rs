@@ -0,0 +1,77 @@
+use motoko_rts::sha256::{blob_sha224, blob_sha256};
+use motoko_rts::text::{blob_of_text, text_concat, text_of_str};
+use motoko_rts::types::SkewedPtr;
+
+use quickcheck::{quickcheck, TestResult};
+
+pub unsafe fn test() {
+    println!("Testing SHA-256 ...");
+
+    let million_a = "a".repeat(1_000_000);
+
+    // Test vectors from FIPS 180-4 and NIST
+    let cases: &[(&str, &str, &str)] = &[
+        (
+            "",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f",
+        ),
+        (
+            "abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7",
+        ),
+        (
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            "75388b16512776cc5dba5da1fd890150b0c6455cb4f58b1952522525",
+        ),
+        (
+            &million_a,
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            "20794655980c91d8bbb4c1ea97618a4bf03f42581948b2ee4ee7ad67",
+        ),
+    ];
+
+    for (input, sha256, sha224) in cases {
+        let blob = blob_of_text(text_of_str(input));
+        assert_eq!(hex(blob_sha256(blob)), *sha256);
+        assert_eq!(hex(blob_sha224(blob)), *sha224);
+    }
+
+    // Texts are hashed without flattening, with leaves that don't end at block boundaries
+    let text = text_concat(text_of_str("Hello, "), text_of_str("B\u{e4}r"));
+    let text = text_concat(text, text_of_str("\u{2603}!"));
+    assert_eq!(
+        hex(blob_sha256(text)),
+        "b2ac3c36bfc479a8cb41b38ebfe861e172d23f94c1a7fa779107a4e0a1672fcc"
+    );
+
+    quickcheck(sha256_prop as fn(Vec<String>) -> TestResult);
+
+    println!("OK");
+}
+
+/// A text hashes to the same as its blob
+fn sha256_prop(strs: Vec<String>) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for str in &strs {
+            text = text_concat(text, text_of_str(str));
+        }
+
+        let blob = blob_of_text(text);
+        if hex(blob_sha256(text)) != hex(blob_sha256(blob)) {
+            return TestResult::error(format!("blob_sha256({:?})", strs));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn hex(blob: SkewedPtr) -> String {
+    let blob = blob.as_blob();
+    (0..blob.len().0)
+        .map(|i| format!("{:02x}", blob.get(i)))
+        .collect()
+}
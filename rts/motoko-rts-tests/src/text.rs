@@ -1,11 +1,19 @@
 //! Text and text iterator tests
 
+use crate::gc_stress::{collect_with, with_alloc_collections};
+use crate::static_root;
+use crate::utils::blob_of_bytes;
+
+use motoko_rts::char::{char_to_lower, char_to_upper};
 use motoko_rts::text::{
-    blob_of_text, decode_code_point, text_compare, text_concat, text_len, text_of_str,
-    text_singleton, text_size,
+    blob_compare_const_time, blob_concat_many, blob_copy_range, blob_fill, blob_of_text,
+    decode_code_point, text_compare, text_concat, text_find, text_len, text_of_blob_lossy,
+    text_of_str, text_of_utf16_blob, text_repeat, text_singleton, text_size, text_slice,
+    text_to_buf, text_to_lower, text_to_upper, text_to_utf16_blob,
 };
 use motoko_rts::text_iter::{text_iter, text_iter_done, text_iter_next};
-use motoko_rts::types::{Bytes, SkewedPtr, TAG_BLOB};
+use motoko_rts::text_split::{text_split, text_split_done, text_split_next};
+use motoko_rts::types::{Bytes, SkewedPtr, TAG_BLOB, TAG_CONCAT, TAG_SLICE, TAG_SMALL_TEXT};
 
 use std::convert::TryFrom;
 
@@ -13,6 +21,15 @@ use quickcheck::{quickcheck, TestResult};
 
 static STR: &str = "abcdefgh";
 
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+}
+
+extern "C" {
+    fn collect();
+    fn get_total_allocations() -> Bytes<u64>;
+}
+
 struct TextIter {
     obj: SkewedPtr,
 }
@@ -39,6 +56,32 @@ impl Iterator for TextIter {
     }
 }
 
+struct TextSplit {
+    obj: SkewedPtr,
+}
+
+impl TextSplit {
+    fn new(text: SkewedPtr, delimiter: SkewedPtr) -> Self {
+        TextSplit {
+            obj: unsafe { text_split(text, delimiter) },
+        }
+    }
+}
+
+impl Iterator for TextSplit {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        unsafe {
+            if text_split_done(self.obj) == 1 {
+                None
+            } else {
+                Some(TextIter::from_text(text_split_next(self.obj)).collect())
+            }
+        }
+    }
+}
+
 pub unsafe fn test() {
     println!("Testing text and text iterators ...");
 
@@ -56,18 +99,691 @@ pub unsafe fn test() {
         assert_eq!(TextIter::from_text(text).collect::<String>(), str);
     }
 
-    println!("  Testing text blob iteration");
+    println!("  Testing small text iteration");
     for i in 0..8 {
         let str = &STR[0..i + 1];
         let text = text_of_str(str);
-        assert_eq!(text.tag(), TAG_BLOB);
+        assert_eq!(text.tag(), TAG_SMALL_TEXT);
         let iter = TextIter::from_text(text);
         assert_eq!(iter.collect::<String>(), str);
     }
 
+    println!("  Testing small texts");
+    small1();
+
     println!("  Testing concatenation");
     concat1();
     quickcheck(concat_prop as fn(Vec<String>) -> TestResult);
+
+    println!("  Testing rebalancing");
+    rebalance();
+
+    println!("  Testing comparison");
+    compare1();
+    quickcheck(compare_prop as fn(Vec<String>, Vec<String>) -> TestResult);
+    quickcheck(concat_compare_prop as fn(String, String, String) -> TestResult);
+    const_time1();
+    quickcheck(const_time_prop as fn(Vec<u8>, Vec<u8>) -> TestResult);
+
+    println!("  Testing slicing");
+    slice1();
+    quickcheck(slice_prop as fn(Vec<String>, usize, usize) -> TestResult);
+
+    println!("  Testing substring search");
+    find1();
+    quickcheck(find_prop as fn(Vec<String>, Vec<String>) -> TestResult);
+
+    println!("  Testing case conversion");
+    case1();
+    quickcheck(case_prop as fn(Vec<String>) -> TestResult);
+
+    println!("  Testing UTF-16 conversion");
+    quickcheck(utf16_prop as fn(Vec<String>) -> TestResult);
+
+    println!("  Testing lossy UTF-8 decoding");
+    lossy1();
+    quickcheck(lossy_prop as fn(Vec<u8>) -> TestResult);
+
+    println!("  Testing splitting");
+    split1();
+    quickcheck(split_prop as fn(Vec<String>, Vec<String>) -> TestResult);
+
+    println!("  Testing writing to a buffer");
+    quickcheck(to_buf_prop as fn(Vec<String>) -> TestResult);
+
+    println!("  Testing repeating and filling");
+    repeat1();
+    quickcheck(repeat_prop as fn(Vec<String>, u8) -> TestResult);
+
+    println!("  Testing concatenating blobs");
+    quickcheck(concat_many_prop as fn(Vec<String>) -> TestResult);
+
+    println!("  Testing copying blob ranges");
+    copy_range1();
+
+    println!("  Testing under GC stress");
+    with_alloc_collections(|| stress());
+}
+
+unsafe fn small1() {
+    // Texts shorter than a concatenation are small texts, with the empty text and texts filling
+    // the payload
+    for str in &["", "a", "abcd", "abcdefgh"] {
+        let text = text_of_str(str);
+        assert_eq!(text.tag(), TAG_SMALL_TEXT);
+        assert_eq!(text_size(text), Bytes(str.len()));
+        assert_eq!(text_len(text), str.chars().count());
+        assert_eq!(TextIter::from_text(text).collect::<String>(), *str);
+    }
+    assert_eq!(text_of_str("abcdefghi").tag(), TAG_BLOB);
+
+    // Multi-byte characters
+    let text = text_of_str("\u{3b1}\u{1f600}");
+    assert_eq!(text.tag(), TAG_SMALL_TEXT);
+    assert_eq!(text_len(text), 2);
+    assert_eq!(
+        TextIter::from_text(text).collect::<String>(),
+        "\u{3b1}\u{1f600}"
+    );
+
+    // Short concatenations are small texts, and are flattened to blobs
+    let concat = text_concat(text_of_str("abc"), text_of_str("def"));
+    assert_eq!(concat.tag(), TAG_SMALL_TEXT);
+    assert_eq!(text_compare(concat, text_of_str("abcdef")), 0);
+    let blob = blob_of_text(concat);
+    assert_eq!(blob.tag(), TAG_BLOB);
+    assert_eq!(text_compare(blob, concat), 0);
+
+    // Small texts in concatenations
+    let concat = text_concat(text_of_str("abcdefghijklmnop"), text_of_str("q"));
+    assert_eq!(concat.tag(), TAG_CONCAT);
+    assert_eq!(
+        TextIter::from_text(concat).collect::<String>(),
+        "abcdefghijklmnopq"
+    );
+
+    // The GC copies small texts
+    (*static_root(0)).field = text_of_str("xyz");
+    collect();
+    let text = (*static_root(0)).field;
+    assert_eq!(text.tag(), TAG_SMALL_TEXT);
+    assert_eq!(text_compare(text, text_of_str("xyz")), 0);
+    (*static_root(0)).field = SkewedPtr(0);
+}
+
+unsafe fn split1() {
+    let mut text = text_of_str("");
+    for str in &["abc,defg", "h,,ijklm", "nopq,,rs", "tuvw,xyz"] {
+        text = text_concat(text, text_of_str(str));
+    }
+
+    let parts: Vec<String> = TextSplit::new(text, text_of_str(",")).collect();
+    assert_eq!(
+        parts,
+        vec!["abc", "defgh", "", "ijklmnopq", "", "rstuvw", "xyz"]
+    );
+
+    // Delimiter across leaves
+    let parts: Vec<String> = TextSplit::new(text, text_of_str("q,,r")).collect();
+    assert_eq!(parts, vec!["abc,defgh,,ijklmnop", "stuvw,xyz"]);
+
+    // Delimiters at the ends
+    let parts: Vec<String> = TextSplit::new(text_of_str(",a,"), text_of_str(",")).collect();
+    assert_eq!(parts, vec!["", "a", ""]);
+
+    // No delimiter, empty delimiter, empty text
+    let parts: Vec<String> = TextSplit::new(text, text_of_str(";")).collect();
+    assert_eq!(parts, vec!["abc,defgh,,ijklmnopq,,rstuvw,xyz"]);
+    let parts: Vec<String> = TextSplit::new(text, text_of_str("")).collect();
+    assert_eq!(parts, vec!["abc,defgh,,ijklmnopq,,rstuvw,xyz"]);
+    let parts: Vec<String> = TextSplit::new(text_of_str(""), text_of_str(",")).collect();
+    assert_eq!(parts, vec![""]);
+
+    // Long parts are slices
+    let parts = text_split(text_of_str("abcdefghijklmnop,q"), text_of_str(","));
+    assert_eq!(text_split_next(parts).tag(), TAG_SLICE);
+}
+
+fn split_prop(strs: Vec<String>, delimiter: Vec<String>) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for str in &strs {
+            text = text_concat(text, text_of_str(str));
+        }
+
+        let str = strs.concat();
+        // Often a part of the text
+        let delimiter = if delimiter.len() % 2 == 0 && !strs.is_empty() {
+            strs[delimiter.len() % strs.len()].clone()
+        } else {
+            delimiter.concat()
+        };
+
+        let expected: Vec<String> = if delimiter.is_empty() {
+            vec![str.clone()]
+        } else {
+            str.split(delimiter.as_str()).map(String::from).collect()
+        };
+
+        let parts: Vec<String> = TextSplit::new(text, text_of_str(&delimiter)).collect();
+        if parts != expected {
+            return TestResult::error(format!(
+                "text_split({:?}, {:?}) = {:?}, expected {:?}",
+                str, delimiter, parts, expected
+            ));
+        }
+
+        TestResult::passed()
+    }
+}
+
+fn to_buf_prop(strs: Vec<String>) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for str in &strs {
+            text = text_concat(text, text_of_str(str));
+        }
+
+        // The bytes after the text are not written
+        let size = text_size(text).0;
+        let mut buf = vec![0xFFu8; size + 1];
+        text_to_buf(text, buf.as_mut_ptr());
+
+        if buf[..size] != *strs.concat().as_bytes() || buf[size] != 0xFF {
+            return TestResult::error(format!("text_to_buf({:?})", strs));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn repeat1() {
+    // Repeating once returns the text itself
+    let text = text_concat(text_of_str("abcdefgh"), text_of_str("ijklmnop"));
+    assert_eq!(text_repeat(text, 1).unskew(), text.unskew());
+
+    // The result is a single leaf
+    let repeated = text_repeat(text, 3);
+    assert_eq!(repeated.tag(), TAG_BLOB);
+    assert_eq!(
+        TextIter::from_text(repeated).collect::<String>(),
+        "abcdefghijklmnop".repeat(3)
+    );
+
+    assert_eq!(text_size(text_repeat(text, 0)), Bytes(0));
+    assert_eq!(text_repeat(text_of_str("ab"), 3).tag(), TAG_SMALL_TEXT);
+
+    for &(byte, n) in &[(0u8, 0usize), (0, 1), (0xAB, 7), (0xFF, 1000)] {
+        let blob = blob_fill(byte as u32, Bytes(n)).as_blob();
+        assert_eq!(blob.len(), Bytes(n));
+        let bytes = std::slice::from_raw_parts(blob.payload_addr(), n);
+        assert!(bytes.iter().all(|&b| b == byte));
+    }
+}
+
+fn repeat_prop(strs: Vec<String>, n: u8) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for str in &strs {
+            text = text_concat(text, text_of_str(str));
+        }
+
+        let repeated = text_repeat(text, n as usize);
+        let expected = strs.concat().repeat(n as usize);
+        if TextIter::from_text(repeated).collect::<String>() != expected {
+            return TestResult::error(format!("text_repeat({:?}, {})", strs, n));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn lossy1() {
+    // Valid UTF-8 is returned as it is
+    let blob = blob_of_bytes("abc \u{3b1}\u{1f600}".as_bytes());
+    assert_eq!(text_of_blob_lossy(blob).unskew(), blob.unskew());
+
+    let cases: &[(&[u8], &str)] = &[
+        (b"a\xffb", "a\u{fffd}b"),
+        // A truncated sequence is replaced once, at the end and in the middle
+        (b"a\xe2\x96", "a\u{fffd}"),
+        (b"\xe2\x96a", "\u{fffd}a"),
+        (b"\xf0\x9f\x98", "\u{fffd}"),
+        // Overlong encodings and surrogates are replaced byte by byte
+        (b"\xc0\xaf", "\u{fffd}\u{fffd}"),
+        (b"\xed\xa0\x80", "\u{fffd}\u{fffd}\u{fffd}"),
+        // Continuation bytes without a start
+        (b"\x80\x80x", "\u{fffd}\u{fffd}x"),
+    ];
+
+    for (bytes, expected) in cases {
+        let text = text_of_blob_lossy(blob_of_bytes(bytes));
+        assert_eq!(TextIter::from_text(text).collect::<String>(), *expected);
+        assert_eq!(text_size(text), Bytes(expected.len()));
+    }
+}
+
+fn lossy_prop(bytes: Vec<u8>) -> TestResult {
+    unsafe {
+        let text = text_of_blob_lossy(blob_of_bytes(&bytes));
+        let expected = String::from_utf8_lossy(&bytes);
+        if TextIter::from_text(text).collect::<String>() != expected {
+            return TestResult::error(format!("text_of_blob_lossy({:?})", bytes));
+        }
+
+        TestResult::passed()
+    }
+}
+
+fn utf16_prop(strs: Vec<String>) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for str in &strs {
+            text = text_concat(text, text_of_str(str));
+        }
+
+        let str = strs.concat();
+        let expected: Vec<u8> = str
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes().to_vec())
+            .collect();
+
+        let blob = text_to_utf16_blob(text).as_blob();
+        let bytes = std::slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+        if bytes != expected.as_slice() {
+            return TestResult::error(format!("text_to_utf16_blob({:?})", str));
+        }
+
+        let decoded = text_of_utf16_blob(text_to_utf16_blob(text));
+        if TextIter::from_text(decoded).collect::<String>() != str {
+            return TestResult::error(format!("text_of_utf16_blob({:?})", str));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn case1() {
+    let text = text_of_str("Straße und Ǆ, ΣΑΣ");
+    assert_eq!(
+        TextIter::from_text(text_to_upper(text)).collect::<String>(),
+        "STRASSE UND Ǆ, ΣΑΣ"
+    );
+    assert_eq!(
+        TextIter::from_text(text_to_lower(text)).collect::<String>(),
+        "straße und ǆ, σας"
+    );
+
+    // Final sigmas, after a cased character (ignoring the apostrophe) and not before one
+    let text = text_of_str("ΟΔΥΣΣΕΥΣ Σ ΑΣ' Σ'Α");
+    assert_eq!(
+        TextIter::from_text(text_to_lower(text)).collect::<String>(),
+        "οδυσσευς σ ας' σ'α"
+    );
+
+    // 'İ' lowercases to "i̇"
+    let text = text_of_str("İ");
+    assert_eq!(
+        TextIter::from_text(text_to_lower(text)).collect::<String>(),
+        "i\u{307}"
+    );
+
+    // Unchanged texts are not copied
+    let text = text_of_str("ABC 123");
+    assert_eq!(text_to_upper(text).unskew(), text.unskew());
+    let text = text_concat(text_of_str("abcdefgh"), text_of_str("ijklmnop"));
+    assert_eq!(text_to_lower(text).unskew(), text.unskew());
+}
+
+fn case_prop(strs: Vec<String>) -> TestResult {
+    unsafe {
+        let mut text = text_of_str("");
+        for str in &strs {
+            text = text_concat(text, text_of_str(str));
+        }
+
+        // Mapped as by `std`, but for the characters that only have mappings in the newer Unicode
+        // version of the RTS
+        let str = strs.concat();
+        if str.chars().any(|c| {
+            (c.to_uppercase().eq(Some(c)) && char_to_upper(c as u32) != c as u32)
+                || (c.to_lowercase().eq(Some(c)) && char_to_lower(c as u32) != c as u32)
+        }) {
+            return TestResult::discard();
+        }
+        let upper = str.to_uppercase();
+        let lower = str.to_lowercase();
+
+        if TextIter::from_text(text_to_upper(text)).collect::<String>() != upper {
+            return TestResult::error(format!("text_to_upper({:?})", str));
+        }
+
+        if TextIter::from_text(text_to_lower(text)).collect::<String>() != lower {
+            return TestResult::error(format!("text_to_lower({:?})", str));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn compare1() {
+    // Two ropes with the same contents but different shapes, one a slice of a longer text
+    let mut text1 = text_of_str("");
+    let mut text2 = text_of_str("x");
+    for i in 0..10000 {
+        let str = format!("<{}>", i);
+        text1 = text_concat(text1, text_of_str(&str));
+        text2 = text_concat(text2, text_of_str(&str[..1]));
+        text2 = text_concat(text2, text_of_str(&str[1..]));
+    }
+    let text2 = text_slice(text2, Bytes(1), text_size(text1));
+
+    let allocations = get_total_allocations();
+    assert_eq!(text_compare(text1, text2), 0);
+    assert_eq!(text_compare(text1, text1), 0);
+
+    let longer = text_concat(text2, text_of_str("!"));
+    assert_eq!(text_compare(text1, longer), -1);
+    assert_eq!(text_compare(longer, text1), 1);
+
+    let different = text_concat(text_of_str("<1>"), text1);
+    assert_eq!(text_compare(text1, different), -1);
+    assert_eq!(text_compare(different, text1), 1);
+
+    // Comparison doesn't allocate
+    let before = get_total_allocations();
+    text_compare(text1, text2);
+    text_compare(longer, text1);
+    assert_eq!(get_total_allocations(), before);
+    assert!(before > allocations);
+}
+
+fn compare_prop(strs1: Vec<String>, strs2: Vec<String>) -> TestResult {
+    unsafe {
+        // Often with a common prefix
+        let str1 = strs1.concat();
+        let str2 = if strs2.len() % 2 == 0 {
+            str1.clone() + &strs2.concat()
+        } else {
+            strs2.concat()
+        };
+
+        // Split into leaves at different offsets: one per string, and one per character
+        let mut text1 = text_of_str("");
+        for str in &strs1 {
+            text1 = text_concat(text1, text_of_str(str));
+        }
+        let mut text2 = text_of_str("");
+        for c in str2.chars() {
+            text2 = text_concat(text2, text_singleton(c as u32));
+        }
+
+        let expected = match str1.cmp(&str2) {
+            std::cmp::Ordering::Less => -1,
+            std::cmp::Ordering::Equal => 0,
+            std::cmp::Ordering::Greater => 1,
+        };
+
+        if text_compare(text1, text2) != expected || text_compare(text2, text1) != -expected {
+            return TestResult::error(format!("text_compare({:?}, {:?})", str1, str2));
+        }
+
+        TestResult::passed()
+    }
+}
+
+/// Comparison is consistent with concatenation: it doesn't depend on the shape of the rope, and a
+/// common prefix doesn't change the order
+fn concat_compare_prop(a: String, b: String, c: String) -> TestResult {
+    unsafe {
+        let ta = text_of_str(&a);
+        let tb = text_of_str(&b);
+        let tc = text_of_str(&c);
+
+        let left = text_concat(text_concat(ta, tb), tc);
+        let right = text_concat(ta, text_concat(tb, tc));
+        let flat = text_of_str(&(a.clone() + &b + &c));
+        if text_compare(left, right) != 0
+            || text_compare(left, flat) != 0
+            || text_compare(flat, right) != 0
+        {
+            return TestResult::error(format!("associativity of {:?}, {:?}, {:?}", a, b, c));
+        }
+
+        let expected = match b.cmp(&c) {
+            std::cmp::Ordering::Less => -1,
+            std::cmp::Ordering::Equal => 0,
+            std::cmp::Ordering::Greater => 1,
+        };
+        let ab = text_concat(ta, tb);
+        let ac = text_concat(ta, tc);
+        if text_compare(ab, ac) != expected || text_compare(ac, ab) != -expected {
+            return TestResult::error(format!("common prefix {:?} of {:?}, {:?}", a, b, c));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn const_time1() {
+    let cases: &[(&[u8], &[u8], u32)] = &[
+        (b"", b"", 1),
+        (b"secret", b"secret", 1),
+        (b"secret", b"secreT", 0),
+        (b"secret", b"Secret", 0),
+        (b"secret", b"secrets", 0),
+        (b"", b"\0", 0),
+    ];
+
+    for (bytes1, bytes2, expected) in cases {
+        let result = blob_compare_const_time(blob_of_bytes(bytes1), blob_of_bytes(bytes2));
+        assert_eq!(result, *expected, "{:?} {:?}", bytes1, bytes2);
+    }
+}
+
+fn const_time_prop(bytes1: Vec<u8>, bytes2: Vec<u8>) -> TestResult {
+    unsafe {
+        let blob1 = blob_of_bytes(&bytes1);
+        let blob2 = blob_of_bytes(&bytes2);
+
+        if blob_compare_const_time(blob1, blob2) != (bytes1 == bytes2) as u32 {
+            return TestResult::error(format!("{:?} {:?}", bytes1, bytes2));
+        }
+
+        if blob_compare_const_time(blob1, blob_of_bytes(&bytes1)) != 1 {
+            return TestResult::error(format!("{:?}", bytes1));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn find1() {
+    let mut haystack = text_of_str("");
+    for str in &["abcdefgh", "ijklmnop", "qrstuvwx", "yzabcdef"] {
+        haystack = text_concat(haystack, text_of_str(str));
+    }
+
+    assert_eq!(text_find(haystack, text_of_str("")), 0);
+    assert_eq!(text_find(haystack, text_of_str("abc")), 0);
+    assert_eq!(text_find(haystack, text_of_str("fgh")), 5);
+    // Across leaves
+    assert_eq!(text_find(haystack, text_of_str("ghijklmnopqr")), 6);
+    assert_eq!(text_find(haystack, text_of_str("xyzab")), 23);
+    assert_eq!(text_find(haystack, text_of_str("cdef")), 2);
+    assert_eq!(text_find(haystack, text_of_str("abd")), -1);
+    assert_eq!(text_find(text_of_str("ab"), text_of_str("abc")), -1);
+
+    // Needle a concat
+    let needle = text_concat(text_of_str("mnopq"), text_of_str("rstuv"));
+    assert_eq!(text_find(haystack, needle), 12);
+
+    // Haystack a slice
+    let slice = text_slice(haystack, Bytes(3), Bytes(26));
+    assert_eq!(text_find(slice, text_of_str("yza")), 21);
+    assert_eq!(text_find(slice, text_of_str("abc")), 23);
+}
+
+fn find_prop(haystack: Vec<String>, needle: Vec<String>) -> TestResult {
+    unsafe {
+        // Often a part of the haystack
+        let expected_haystack = haystack.concat();
+        let expected_needle = if needle.len() % 2 == 0 && !haystack.is_empty() {
+            haystack[needle.len() % haystack.len()].clone()
+        } else {
+            needle.concat()
+        };
+
+        let mut haystack_text = text_of_str("");
+        for str in &haystack {
+            haystack_text = text_concat(haystack_text, text_of_str(str));
+        }
+
+        let found = text_find(haystack_text, text_of_str(&expected_needle));
+        let expected = match expected_haystack.find(&expected_needle) {
+            Some(offset) => offset as isize,
+            None => -1,
+        };
+
+        if found != expected {
+            return TestResult::error(format!(
+                "text_find({:?}, {:?}) = {}, expected {}",
+                expected_haystack, expected_needle, found, expected
+            ));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn slice1() {
+    let blob = text_of_str("abcdefghijklmnopqrstuvwxyz");
+
+    // Slices refer to the blob, short ones are copied
+    let slice = text_slice(blob, Bytes(2), Bytes(20));
+    assert_eq!(slice.tag(), TAG_SLICE);
+    assert_eq!(
+        TextIter::from_text(slice).collect::<String>(),
+        "cdefghijklmnopqrstuv"
+    );
+    let short = text_slice(slice, Bytes(1), Bytes(3));
+    assert_eq!(short.tag(), TAG_SMALL_TEXT);
+    assert_eq!(TextIter::from_text(short).collect::<String>(), "def");
+
+    // A slice of a slice refers to the blob
+    let slice2 = text_slice(slice, Bytes(5), Bytes(10));
+    assert_eq!(slice2.tag(), TAG_SLICE);
+    assert_eq!((*slice2.as_slice()).blob.unskew(), blob.unskew());
+    assert_eq!(text_compare(slice2, text_of_str("hijklmnopq")), 0);
+
+    // The whole text is returned as it is
+    assert_eq!(
+        text_slice(slice, Bytes(0), Bytes(20)).unskew(),
+        slice.unskew()
+    );
+
+    // Slices spanning leaves are concatenations of slices
+    let concat = text_concat(blob, text_of_str("0123456789"));
+    let slice = text_slice(concat, Bytes(20), Bytes(12));
+    assert_eq!(slice.tag(), TAG_CONCAT);
+    assert_eq!(text_len(slice), 12);
+    assert_eq!(
+        TextIter::from_text(slice).collect::<String>(),
+        "uvwxyz012345"
+    );
+
+    // The GC follows the blob of a slice
+    (*static_root(0)).field = slice2;
+    collect();
+    let slice2 = (*static_root(0)).field;
+    assert_eq!(slice2.tag(), TAG_SLICE);
+    assert_eq!(text_compare(slice2, text_of_str("hijklmnopq")), 0);
+    (*static_root(0)).field = SkewedPtr(0);
+}
+
+fn slice_prop(strs: Vec<String>, start: usize, len: usize) -> TestResult {
+    unsafe {
+        let mut obj = text_of_str("");
+        for str in &strs {
+            obj = text_concat(obj, text_of_str(str));
+        }
+
+        // Move the range to character boundaries
+        let expected = strs.concat();
+        let mut start = start % (expected.len() + 1);
+        while !expected.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = start + len % (expected.len() - start + 1);
+        while !expected.is_char_boundary(end) {
+            end -= 1;
+        }
+        let expected = &expected[start..end];
+
+        let slice = text_slice(obj, Bytes(start), Bytes(end - start));
+
+        if text_size(slice) != Bytes(expected.len()) {
+            return TestResult::error("text_size");
+        }
+
+        if text_len(slice) != expected.chars().count() {
+            return TestResult::error("text_len");
+        }
+
+        if TextIter::from_text(slice).collect::<String>() != expected {
+            return TestResult::error("iteration");
+        }
+
+        let blob = blob_of_text(slice);
+        if TextIter::from_text(blob).collect::<String>() != expected {
+            return TestResult::error("blob_of_text");
+        }
+
+        if text_compare(slice, blob) != 0 || text_compare(blob, slice) != 0 {
+            return TestResult::error("text_compare");
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn rebalance() {
+    let strs: Vec<String> = (0..10_000).map(|i| format!("{:09}", i)).collect();
+
+    // Appending, prepending, and concatenating both ways
+    let mut appended = text_of_str("");
+    let mut prepended = text_of_str("");
+    for str in &strs {
+        appended = text_concat(appended, text_of_str(str));
+    }
+    for str in strs.iter().rev() {
+        prepended = text_concat(text_of_str(str), prepended);
+    }
+    let both = text_concat(appended, prepended);
+
+    let expected = strs.concat();
+    for text in [appended, prepended].iter() {
+        assert!(depth(*text) <= 48);
+        assert_eq!(TextIter::from_text(*text).collect::<String>(), expected);
+        assert_eq!(text_compare(*text, blob_of_text(*text)), 0);
+    }
+
+    assert!(depth(both) <= 48);
+    assert_eq!(text_size(both), Bytes(2 * expected.len()));
+    assert_eq!(
+        TextIter::from_text(both).collect::<String>(),
+        expected.repeat(2)
+    );
+}
+
+/// Depth of the concat tree of a text
+unsafe fn depth(text: SkewedPtr) -> usize {
+    if text.tag() == TAG_CONCAT {
+        let concat = text.as_concat();
+        1 + std::cmp::max(depth(concat.text1()), depth(concat.text2()))
+    } else {
+        0
+    }
 }
 
 unsafe fn concat1() {
@@ -83,19 +799,19 @@ unsafe fn concat1() {
     let expected = strs.concat();
 
     // Check number of characters
-    assert_eq!(text_len(obj), expected.chars().count() as u32);
+    assert_eq!(text_len(obj), expected.chars().count());
 
     // Check text size in bytes
-    assert_eq!(text_size(obj), Bytes(expected.len() as u32));
+    assert_eq!(text_size(obj), Bytes(expected.len()));
 
     // Generate blob
     let text_blob = blob_of_text(obj);
 
     // Check number of characters in blob
-    assert_eq!(text_len(text_blob), expected.chars().count() as u32);
+    assert_eq!(text_len(text_blob), expected.chars().count());
 
     // Check blob size in bytes
-    assert_eq!(text_size(text_blob), Bytes(expected.len() as u32));
+    assert_eq!(text_size(text_blob), Bytes(expected.len()));
 
     // Check blob iteration
     assert_eq!(
@@ -121,12 +837,12 @@ fn concat_prop(strs: Vec<String>) -> TestResult {
         let expected = strs.concat();
 
         // Check number of characters
-        if text_len(obj) != expected.chars().count() as u32 {
+        if text_len(obj) != expected.chars().count() {
             return TestResult::error("text_len");
         }
 
         // Check text size in bytes
-        if text_size(obj) != Bytes(expected.len() as u32) {
+        if text_size(obj) != Bytes(expected.len()) {
             return TestResult::error("text_size");
         }
 
@@ -134,12 +850,12 @@ fn concat_prop(strs: Vec<String>) -> TestResult {
         let text_blob = blob_of_text(obj);
 
         // Check number of characters in blob
-        if text_len(text_blob) != expected.chars().count() as u32 {
+        if text_len(text_blob) != expected.chars().count() {
             return TestResult::error("blob text_len");
         }
 
         // Check blob size in bytes
-        if text_size(text_blob) != Bytes(expected.len() as u32) {
+        if text_size(text_blob) != Bytes(expected.len()) {
             return TestResult::error("blob text_size");
         }
 
@@ -161,3 +877,81 @@ fn concat_prop(strs: Vec<String>) -> TestResult {
         TestResult::passed()
     }
 }
+
+fn concat_many_prop(strs: Vec<String>) -> TestResult {
+    unsafe {
+        let array = alloc_array(strs.len());
+        for (i, str) in strs.iter().enumerate() {
+            array.as_array().set(i, blob_of_text(text_of_str(str)));
+        }
+
+        let blob = blob_concat_many(array).as_blob();
+        let bytes = std::slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+        if bytes != strs.concat().as_bytes() {
+            return TestResult::error(format!("blob_concat_many({:?})", strs));
+        }
+
+        TestResult::passed()
+    }
+}
+
+unsafe fn copy_range1() {
+    let dst = blob_of_text(text_of_str("0123456789"));
+    let src = blob_of_text(text_of_str("abcdef"));
+
+    let cases: &[(usize, usize, usize, &str)] = &[
+        (0, 0, 0, "0123456789"),
+        (2, 1, 3, "01bcd56789"),
+        (7, 3, 3, "0123456def"),
+        (10, 6, 0, "0123456789"),
+        (0, 0, 6, "abcdef6789"),
+    ];
+
+    for &(dst_off, src_off, len, expected) in cases {
+        let blob = blob_copy_range(dst, dst_off, src, src_off, len).as_blob();
+        let bytes = std::slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+        assert_eq!(bytes, expected.as_bytes());
+    }
+
+    // The original blob is not changed
+    let blob = dst.as_blob();
+    let bytes = std::slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+    assert_eq!(bytes, b"0123456789");
+}
+
+/// Concatenation, slicing, and iteration with a collection after every call, see `gc_stress.rs`
+unsafe fn stress() {
+    // A rope, a slice of it, and a blob of the slice
+    let mut texts = [text_of_str(""); 3];
+    let mut expected = String::new();
+    for i in 0..100 {
+        let str = format!("<{}ü>", i);
+        texts[0] = text_concat(texts[0], text_of_str(&str));
+        collect_with(&mut texts);
+        expected.push_str(&str);
+
+        let start = expected.char_indices().nth(i * 3).unwrap().0;
+        texts[1] = text_slice(texts[0], Bytes(start), Bytes(expected.len() - start));
+        collect_with(&mut texts);
+        texts[2] = blob_of_text(texts[1]);
+        collect_with(&mut texts);
+
+        assert_eq!(TextIter::from_text(texts[0]).collect::<String>(), expected);
+        assert_eq!(
+            TextIter::from_text(texts[1]).collect::<String>(),
+            expected[start..]
+        );
+        assert_eq!(text_compare(texts[1], texts[2]), 0);
+        assert_eq!(text_len(texts[2]), expected[start..].chars().count());
+    }
+
+    // An iterator, with the rope only reachable from it
+    let mut iter = [text_iter(texts[0])];
+    collect_with(&mut iter);
+    for c in expected.chars() {
+        assert_eq!(text_iter_done(iter[0]), 0);
+        assert_eq!(text_iter_next(iter[0]), c as u32);
+        collect_with(&mut iter);
+    }
+    assert_eq!(text_iter_done(iter[0]), 1);
+}
@@ -0,0 +1,81 @@
+use crate::static_root;
+
+use motoko_rts::text::{text_compare, text_concat, text_of_str, text_slice};
+use motoko_rts::text_intern::{text_intern, text_intern_count};
+use motoko_rts::types::{Bytes, SkewedPtr, TAG_BLOB, TAG_SMALL_TEXT};
+
+extern "C" {
+    fn collect();
+}
+
+pub unsafe fn test() {
+    println!("Testing text interning ...");
+
+    // The first text with the contents is interned
+    let text = text_of_str("abcdefghijklmnopqrstuvwxyz");
+    assert_eq!(text_intern(text).unskew(), text.unskew());
+    assert_eq!(text_intern(text).unskew(), text.unskew());
+
+    // Equal texts of other shapes
+    let concat = text_concat(text_of_str("abcdefghijklm"), text_of_str("nopqrstuvwxyz"));
+    assert_eq!(text_intern(concat).unskew(), text.unskew());
+
+    // Concatenations and slices are interned as blobs
+    let slice = text_slice(text, Bytes(1), Bytes(20));
+    let interned = text_intern(slice);
+    assert_eq!(interned.tag(), TAG_BLOB);
+    assert_eq!(text_compare(interned, slice), 0);
+    assert_eq!(
+        text_intern(text_of_str("bcdefghijklmnopqrstu")).unskew(),
+        interned.unskew()
+    );
+
+    let concat = text_concat(text_of_str("0123456789"), text_of_str("0123456789"));
+    let interned = text_intern(concat);
+    assert_eq!(interned.tag(), TAG_BLOB);
+    assert_eq!(text_compare(interned, concat), 0);
+
+    // Small texts, and the empty text
+    let small = text_intern(text_of_str("key"));
+    assert_eq!(small.tag(), TAG_SMALL_TEXT);
+    assert_eq!(text_intern(text_of_str("key")).unskew(), small.unskew());
+    assert_ne!(text_intern(text_of_str("kez")).unskew(), small.unskew());
+    let empty = text_intern(text_of_str(""));
+    assert_eq!(text_intern(text_of_str("")).unskew(), empty.unskew());
+    assert_ne!(empty.unskew(), small.unskew());
+
+    // Many texts, growing the table
+    let first = text_intern(text_of_str("text 0"));
+    for i in 0..10_000 {
+        let str = format!("text {}", i);
+        let interned = text_intern(text_of_str(&str));
+        assert_eq!(text_compare(interned, text_of_str(&str)), 0);
+        assert_eq!(text_intern(text_of_str(&str)).unskew(), interned.unskew());
+    }
+    assert_eq!(text_intern(text_of_str("text 0")).unskew(), first.unskew());
+    assert_eq!(text_intern_count(), 10_000 + 6);
+
+    // The table doesn't keep the texts alive. The stack is not scanned, so only the text in the
+    // static root survives.
+    (*static_root(0)).field = text_intern(text_of_str("text 1234"));
+    collect();
+    assert_eq!(text_intern_count(), 1);
+    let kept = (*static_root(0)).field;
+    assert_eq!(text_compare(kept, text_of_str("text 1234")), 0);
+    assert_eq!(
+        text_intern(text_of_str("text 1234")).unskew(),
+        kept.unskew()
+    );
+
+    // Texts interned after a collection reuse the slots of the reclaimed ones
+    for i in 0..100 {
+        let str = format!("text {}", i);
+        let interned = text_intern(text_of_str(&str));
+        assert_eq!(text_intern(text_of_str(&str)).unskew(), interned.unskew());
+    }
+    assert_eq!(text_intern_count(), 101);
+
+    (*static_root(0)).field = SkewedPtr(0);
+
+    println!("OK");
+}
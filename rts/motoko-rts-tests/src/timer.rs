@@ -0,0 +1,104 @@
+use crate::GLOBAL_TIMER;
+
+use motoko_rts::closure_table::{closure_count, recall_closure};
+use motoko_rts::timer::{
+    timer_cancel, timer_count, timer_deadline, timer_dequeue, timer_expiration, timer_expired,
+    timer_next, timer_rearm, timer_set,
+};
+use motoko_rts::types::SkewedPtr;
+
+pub unsafe fn test() {
+    println!("Testing timers ...");
+
+    let n_closures = closure_count();
+
+    // The global timer is set to the earliest expiration
+    let t30 = timer_set(30, closure(30));
+    assert_eq!(GLOBAL_TIMER, 30);
+    let t10 = timer_set(10, closure(10));
+    assert_eq!(GLOBAL_TIMER, 10);
+    let t20 = timer_set(20, closure(20));
+    assert_eq!(GLOBAL_TIMER, 10);
+    assert_eq!(timer_count(), 3);
+    assert_eq!(closure_count(), n_closures + 3);
+    assert_eq!(timer_expiration(t20), Some(20));
+
+    // Nothing expired, the global timer is set again
+    GLOBAL_TIMER = 0;
+    assert_eq!(fire(5), vec![]);
+    assert_eq!(GLOBAL_TIMER, 10);
+
+    // Expired timers fire in the order of their expiration
+    assert_eq!(fire(20), vec![closure(10).0, closure(20).0]);
+    assert_eq!(GLOBAL_TIMER, 30);
+    assert_eq!(timer_expiration(t10), None);
+
+    // A job that couldn't be sent stays, the global timer is set to its expiration
+    GLOBAL_TIMER = 0;
+    assert_eq!(timer_expired(40), 1);
+    timer_rearm();
+    assert_eq!((timer_count(), GLOBAL_TIMER), (1, 30));
+
+    // Fired timers can't be cancelled, cancelling the last timer deactivates the global timer
+    assert_eq!(timer_cancel(t10), 0);
+    assert_eq!(timer_cancel(t30), 1);
+    assert_eq!(timer_cancel(t30), 0);
+    assert_eq!((timer_count(), GLOBAL_TIMER, timer_deadline()), (0, 0, 0));
+    assert_eq!(closure_count(), n_closures);
+
+    // Timers with the same expiration fire in the order they were set, expiration 0 doesn't
+    // deactivate the global timer
+    let t1 = timer_set(0, closure(1));
+    let t2 = timer_set(0, closure(2));
+    assert!(t1 < t2);
+    assert_eq!(timer_expiration(t1), Some(1));
+    assert_eq!(GLOBAL_TIMER, 1);
+    assert_eq!(fire(1), vec![closure(1).0, closure(2).0]);
+    assert_eq!(GLOBAL_TIMER, 0);
+
+    // Many timers, to grow the table, some cancelled
+    const N: u64 = 1000;
+    let mut ids = vec![];
+    let mut expiration: u64 = 7;
+    for i in 0..N {
+        expiration = expiration.wrapping_mul(6364136223846793005).wrapping_add(1);
+        let exp = expiration >> 54;
+        ids.push((exp.max(1), timer_set(exp, closure(i as usize)), i));
+    }
+    assert_eq!(timer_count(), N as usize);
+    for &(_, id, i) in ids.iter() {
+        if i % 3 == 0 {
+            assert_eq!(timer_cancel(id), 1);
+        }
+    }
+    ids.retain(|&(_, _, i)| i % 3 != 0);
+    ids.sort();
+    assert_eq!(GLOBAL_TIMER, ids[0].0);
+    for &(exp, _, i) in ids.iter() {
+        assert_eq!(timer_expired(exp), 1);
+        assert_eq!(
+            recall_closure(timer_next() as usize).0,
+            closure(i as usize).0
+        );
+        timer_dequeue();
+    }
+    assert_eq!(fire(u64::MAX), vec![]);
+    assert_eq!((timer_count(), GLOBAL_TIMER), (0, 0));
+    assert_eq!(closure_count(), n_closures);
+}
+
+/// Does what `canister_global_timer` does at `now`, returns the closures of the jobs it sends
+unsafe fn fire(now: u64) -> Vec<usize> {
+    let mut jobs = vec![];
+    while timer_expired(now) != 0 {
+        jobs.push(recall_closure(timer_next() as usize).0);
+        timer_dequeue();
+    }
+    timer_rearm();
+    jobs
+}
+
+/// A fake closure, timers don't look at it
+fn closure(i: usize) -> SkewedPtr {
+    SkewedPtr((i << 2).wrapping_sub(1))
+}
@@ -0,0 +1,60 @@
+use motoko_rts::to_space_arena::to_space_alloc;
+use motoko_rts::types::{Bytes, SkewedPtr, WORD_SIZE};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+}
+
+pub unsafe fn test() {
+    println!("Testing to-space arena ...");
+
+    // Garbage, so there's idle memory above the heap after the collection
+    alloc_blob(Bytes(1 << 20));
+    collect();
+
+    // Buffers are word-aligned and allocated downwards
+    let buf1 = to_space_alloc(Bytes(10));
+    let buf2 = to_space_alloc(Bytes(100));
+    assert!(!buf1.is_null());
+    assert_eq!(buf1 as usize % WORD_SIZE, 0);
+    assert_eq!(
+        buf2 as usize,
+        buf1 as usize - (100 + WORD_SIZE - 1) / WORD_SIZE * WORD_SIZE
+    );
+    fill(buf1, 10, 1);
+    fill(buf2, 100, 2);
+
+    // Doesn't fit
+    assert!(to_space_alloc(Bytes(crate::HEAP_SIZE)).is_null());
+
+    // When the heap grows into the arena the buffers are kept, new objects are allocated after it
+    loop {
+        let blob = alloc_blob(Bytes(1000)).unskew();
+        assert!(blob + 1000 < buf2 as usize || blob > buf1 as usize);
+        if blob > buf1 as usize {
+            break;
+        }
+    }
+    check(buf1, 10, 1);
+    check(buf2, 100, 2);
+
+    // The arena is released by the collection
+    collect();
+    assert!(!to_space_alloc(Bytes(10)).is_null());
+    collect();
+
+    println!("OK");
+}
+
+unsafe fn fill(buf: *mut u8, len: usize, byte: u8) {
+    for i in 0..len {
+        *buf.add(i) = byte;
+    }
+}
+
+unsafe fn check(buf: *mut u8, len: usize, byte: u8) {
+    for i in 0..len {
+        assert_eq!(*buf.add(i), byte);
+    }
+}
@@ -1,7 +1,10 @@
 // NOTE (osa): These tests are ported from the C version of the RTS, we could probably remove this
 // now as we use Rust's UTF-8 validation now.
 
-use motoko_rts::utf8::utf8_valid;
+use motoko_rts::text::decode_code_point;
+use motoko_rts::utf8::{utf16_valid, utf8_valid};
+
+use quickcheck::{quickcheck, TestResult};
 
 static TEST_STRS_VALID: [&[u8]; 2] = [
     b"abcd",
@@ -52,20 +55,100 @@ static TEST_STRS_INVALID: [&[u8]; 32] = [
     b"\xed\xaf\xbf\xed\xbf\xbf",
 ];
 
+// Little-endian
+static UTF16_STRS_VALID: [&[u8]; 4] = [
+    b"",
+    b"a\x00b\x00",
+    // U+2588
+    b"\x88\x25",
+    // U+1F600, a surrogate pair
+    b"\x3d\xd8\x00\xde",
+];
+
+static UTF16_STRS_INVALID: [&[u8]; 5] = [
+    // Odd number of bytes
+    b"a",
+    b"a\x00b",
+    // Unpaired high surrogate
+    b"\x3d\xd8a\x00",
+    // Unpaired low surrogate
+    b"\x00\xde",
+    // Surrogates in the wrong order
+    b"\x00\xde\x3d\xd8",
+];
+
 pub unsafe fn test() {
     println!("Testing UTF8 validation ...");
 
     for test_str in TEST_STRS_VALID.iter() {
-        assert!(utf8_valid(
-            test_str.as_ptr() as *const _,
-            test_str.len() as u32
-        ));
+        assert!(utf8_valid(test_str.as_ptr() as *const _, test_str.len()));
     }
 
     for test_str in TEST_STRS_INVALID.iter() {
-        assert!(!utf8_valid(
-            test_str.as_ptr() as *const _,
-            test_str.len() as u32
-        ));
+        assert!(!utf8_valid(test_str.as_ptr() as *const _, test_str.len()));
+    }
+
+    quickcheck(utf8_prop as fn(String, Vec<u8>, usize) -> TestResult);
+    quickcheck(utf8_decode_prop as fn(String, Vec<u8>, usize) -> TestResult);
+
+    println!("Testing UTF16 validation ...");
+
+    for test_str in UTF16_STRS_VALID.iter() {
+        assert!(utf16_valid(test_str.as_ptr(), test_str.len()));
+    }
+
+    for test_str in UTF16_STRS_INVALID.iter() {
+        assert!(!utf16_valid(test_str.as_ptr(), test_str.len()));
     }
 }
+
+/// Validation agrees with `std`, on valid strings with ASCII runs and non-ASCII characters at
+/// various offsets, and on these with other bytes inserted
+fn utf8_prop(str: String, bytes: Vec<u8>, offset: usize) -> TestResult {
+    let mut valid = "0123456789abcdef".repeat(offset % 4);
+    valid.push_str(&str);
+    assert!(unsafe { utf8_valid(valid.as_ptr() as *const _, valid.len()) });
+
+    let mut mixed = valid.into_bytes();
+    let offset = offset % (mixed.len() + 1);
+    mixed.splice(offset..offset, bytes);
+
+    let expected = std::str::from_utf8(&mixed).is_ok();
+    if unsafe { utf8_valid(mixed.as_ptr() as *const _, mixed.len()) } != expected {
+        return TestResult::error(format!("utf8_valid({:?})", mixed));
+    }
+
+    TestResult::passed()
+}
+
+/// Decoding the characters of the bytes that pass validation gives the characters of `std`. The
+/// decoder relies on the validation, it doesn't check the bytes itself.
+fn utf8_decode_prop(str: String, bytes: Vec<u8>, offset: usize) -> TestResult {
+    let mut mixed = str.into_bytes();
+    let offset = offset % (mixed.len() + 1);
+    mixed.splice(offset..offset, bytes);
+
+    if !unsafe { utf8_valid(mixed.as_ptr() as *const _, mixed.len()) } {
+        return TestResult::discard();
+    }
+
+    let expected: Vec<u32> = std::str::from_utf8(&mixed)
+        .unwrap()
+        .chars()
+        .map(u32::from)
+        .collect();
+
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < mixed.len() {
+        let mut size = 0;
+        decoded.push(unsafe { decode_code_point(mixed.as_ptr().add(i), &mut size) });
+        i += size as usize;
+    }
+
+    if i != mixed.len() || decoded != expected {
+        return TestResult::error(format!("decoding {:?}: {:?}", mixed, decoded));
+    }
+
+    TestResult::passed()
+}
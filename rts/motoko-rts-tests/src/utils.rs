@@ -0,0 +1,40 @@
+//! Helpers shared by the tests
+
+use motoko_rts::region::{stable_vars_read, stable_vars_size};
+use motoko_rts::text::blob_of_text;
+use motoko_rts::types::{Bytes, SkewedPtr};
+
+extern "C" {
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+}
+
+/// A new blob with the bytes
+pub(crate) unsafe fn blob_of_bytes(bytes: &[u8]) -> SkewedPtr {
+    let blob = alloc_blob(Bytes(bytes.len()));
+    let payload = blob.as_blob().payload_addr();
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), payload, bytes.len());
+    blob
+}
+
+/// Contents of a blob
+pub(crate) unsafe fn bytes_of_blob(blob: SkewedPtr) -> Vec<u8> {
+    let blob = blob.as_blob();
+    std::slice::from_raw_parts(blob.payload_addr(), blob.len().0).to_vec()
+}
+
+/// Contents of a text
+pub(crate) unsafe fn string_of_text(text: SkewedPtr) -> String {
+    String::from_utf8(bytes_of_blob(blob_of_text(text))).unwrap()
+}
+
+/// The stable variables as written to stable memory
+pub(crate) unsafe fn read_stable_vars() -> Vec<u8> {
+    read_stable_vars_range(0, stable_vars_size() as usize)
+}
+
+/// `len` bytes of the stable variables, from `offset`
+pub(crate) unsafe fn read_stable_vars_range(offset: usize, len: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; len];
+    stable_vars_read(buf.as_mut_ptr(), offset as u32, len as u32);
+    buf
+}
@@ -0,0 +1,87 @@
+//! The memory of the tests on wasm32 (`make test32`), laid out as in a canister: the dynamic heap
+//! starts at the end of the memory and the RTS grows it with `memory.grow` (see `grow_pages` in
+//! `alloc/gc.rs`), so the tests run the allocation code of the canisters, with 32-bit pointers.
+//!
+//! The allocations of Rust (vectors of the tests, quickcheck) would grow the memory too, and end up
+//! in the heap. They are in a region reserved before the heap instead, with a simple allocator:
+//! free lists of blocks of powers of two.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::arch::wasm32;
+
+const WASM_PAGE_SIZE: usize = 65536;
+
+/// Pages reserved for the allocations of Rust, 1 GiB. The heap can grow into the rest of the 4 GiB.
+const RESERVED_PAGES: usize = 16384;
+
+/// Blocks of `1 << class` bytes, the smallest holds the link of the free list
+const MIN_CLASS: u32 = 3;
+const N_CLASSES: usize = 32;
+
+/// Next free address of the reserved region, 0 before the region is reserved
+static mut NEXT: usize = 0;
+
+/// End of the reserved region, the beginning of the heap
+static mut END: usize = 0;
+
+/// Free lists of the classes, linked through the first word of the blocks
+static mut FREE: [usize; N_CLASSES] = [0; N_CLASSES];
+
+struct Allocator;
+
+#[global_allocator]
+static ALLOCATOR: Allocator = Allocator;
+
+/// Reserves the region at the end of the memory, on the first allocation (before `main`)
+unsafe fn reserve() {
+    let pages = wasm32::memory_grow(0, RESERVED_PAGES);
+    if pages == usize::MAX {
+        std::process::abort();
+    }
+    NEXT = pages * WASM_PAGE_SIZE;
+    END = NEXT + RESERVED_PAGES * WASM_PAGE_SIZE;
+}
+
+fn class(layout: Layout) -> usize {
+    let size = std::cmp::max(layout.size(), layout.align());
+    std::cmp::max(size.next_power_of_two().trailing_zeros(), MIN_CLASS) as usize
+}
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if END == 0 {
+            reserve();
+        }
+
+        let class = class(layout);
+        let block = FREE[class];
+        if block != 0 {
+            FREE[class] = *(block as *const usize);
+            return block as *mut u8;
+        }
+
+        // Aligned to the size, as blocks are reused for any alignment up to the size
+        let size = 1usize << class;
+        let align = std::cmp::min(size, WASM_PAGE_SIZE);
+        let block = (NEXT + align - 1) & !(align - 1);
+        if block > END || END - block < size {
+            return std::ptr::null_mut();
+        }
+        NEXT = block + size;
+        block as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = class(layout);
+        *(ptr as *mut usize) = FREE[class];
+        FREE[class] = ptr as usize;
+    }
+}
+
+/// Beginning of the dynamic heap, the end of the memory after the reserved region
+pub unsafe fn heap_base() -> usize {
+    if END == 0 {
+        reserve();
+    }
+    wasm32::memory_size(0) * WASM_PAGE_SIZE
+}
@@ -0,0 +1,83 @@
+use crate::static_root;
+
+use motoko_rts::text::text_of_str;
+use motoko_rts::types::{Bytes, SkewedPtr};
+use motoko_rts::weak_ref::{weak_ref_alive, weak_ref_get, weak_ref_new};
+
+extern "C" {
+    fn alloc_array(len: usize) -> SkewedPtr;
+    fn alloc_blob(size: Bytes<usize>) -> SkewedPtr;
+    fn collect();
+    fn generational_gc();
+    fn incremental_gc();
+    fn mark_sweep_gc();
+    fn gc_force();
+}
+
+pub unsafe fn test() {
+    println!("Testing weak references ...");
+
+    let referent = text_of_str("hello");
+    let weak_ref = weak_ref_new(referent);
+    assert_eq!(weak_ref_alive(weak_ref), 1);
+    assert_eq!(weak_ref_get(weak_ref).0, referent.0);
+
+    // Without a GC the referent is never reclaimed
+    let weak_ref2 = weak_ref_new(referent);
+    assert_eq!(weak_ref_get(weak_ref2).0, referent.0);
+
+    test_collector("copying", collect, true);
+    test_collector("incremental", full_incremental_gc, true);
+    test_collector("mark-sweep", mark_sweep_gc, false);
+    test_collector("generational major", major_generational_gc, true);
+
+    // A minor collection, with the weak references and the referents in the young generation
+    major_generational_gc();
+    test_collector("generational minor", generational_gc, true);
+
+    println!("OK");
+}
+
+/// A whole cycle of the incremental GC
+unsafe extern "C" fn full_incremental_gc() {
+    gc_force();
+    incremental_gc();
+}
+
+unsafe extern "C" fn major_generational_gc() {
+    gc_force();
+    generational_gc();
+}
+
+/// After a collection the weak reference to a dead object is cleared, and the one to a live object
+/// points to it, at its new address when the collector moves objects
+unsafe fn test_collector(name: &str, collector: unsafe extern "C" fn(), moves: bool) {
+    // Allocated first, so that the live objects move when it's reclaimed
+    let dead = alloc_blob(Bytes(32));
+
+    let live = alloc_blob(Bytes(4));
+    live.as_blob().set(0, 0xAB);
+    let old_addr = live.unskew();
+
+    let weak_refs = alloc_array(2);
+    weak_refs.as_array().set(0, weak_ref_new(dead));
+    weak_refs.as_array().set(1, weak_ref_new(live));
+
+    (*static_root(0)).field = weak_refs;
+    (*static_root(1)).field = live;
+
+    collector();
+
+    let weak_refs = (*static_root(0)).field.as_array();
+    let live = (*static_root(1)).field;
+
+    assert_eq!(weak_ref_alive(weak_refs.get(0)), 0, "{}", name);
+
+    assert_eq!(weak_ref_alive(weak_refs.get(1)), 1, "{}", name);
+    assert_eq!(weak_ref_get(weak_refs.get(1)).0, live.0, "{}", name);
+    assert_eq!(live.as_blob().get(0), 0xAB, "{}", name);
+    assert_eq!(live.unskew() != old_addr, moves, "{}", name);
+
+    (*static_root(0)).field = SkewedPtr(0);
+    (*static_root(1)).field = SkewedPtr(0);
+}
@@ -6,20 +6,31 @@ pub(crate) mod alloc_impl;
 #[path = "alloc/nogc.rs"]
 mod alloc_impl;
 
+#[cfg(feature = "gc")]
+#[path = "alloc/free_list.rs"]
+pub(crate) mod free_list;
+
+#[cfg(feature = "redzones")]
+#[path = "alloc/redzones.rs"]
+pub(crate) mod redzones;
+
 pub use alloc_impl::alloc_words;
+pub(crate) use alloc_impl::{weak_ref_read_barrier, write_barrier};
 
 #[cfg(feature = "gc")]
-pub(crate) use alloc_impl::grow_memory;
+pub(crate) use alloc_impl::{
+    flush_fast_allocated, grow_memory, update_alloc_limit, FAST_ALLOCATED,
+};
 
+use crate::error_code::ErrorCode;
 use crate::rts_trap_with;
-use crate::types::{size_of, Array, Blob, Bytes, SkewedPtr, Words, TAG_ARRAY, TAG_BLOB};
+use crate::types::{size_of, Array, Blob, Bytes, SkewedPtr, Words, TAG_ARRAY, TAG_BLOB, WORD_SIZE};
 
 #[no_mangle]
-pub unsafe extern "C" fn alloc_array(len: u32) -> SkewedPtr {
+pub unsafe extern "C" fn alloc_array(len: usize) -> SkewedPtr {
     // Array payload should not be larger than half of the memory
-    if len > 1 << (32 - 2 - 1) {
-        // 2 for word size, 1 to divide by two
-        rts_trap_with("Array allocation too large");
+    if len > usize::MAX / WORD_SIZE / 2 {
+        rts_trap_with(ErrorCode::OutOfMemory, "Array allocation too large");
     }
 
     let skewed_ptr = alloc_words(size_of::<Array>() + Words(len));
@@ -32,7 +43,7 @@ pub unsafe extern "C" fn alloc_array(len: u32) -> SkewedPtr {
 }
 
 #[no_mangle]
-pub(crate) unsafe extern "C" fn alloc_blob(size: Bytes<u32>) -> SkewedPtr {
+pub(crate) unsafe extern "C" fn alloc_blob(size: Bytes<usize>) -> SkewedPtr {
     // NOTE: We round the size up to the next word and allocates words, but we initialize blob
     // length as `size` instead of `round_up_to_word(size)`. This is fine as as GC knows that we
     // can only allocate words and looks for objects in word boundaries.
@@ -0,0 +1,226 @@
+//! Segregated-fit free lists, for reusing memory in the heap below the heap pointer.
+//!
+//! The mark-sweep GC adds the runs of unmarked objects to the free lists when sweeping (see
+//! `gc/mark_sweep.rs`), and so does the incremental GC in the cycles that sweep (see
+//! `gc/incremental.rs`). `free_object` adds objects freed in place by the RTS (with the `free_list`
+//! feature, see `alloc/gc.rs`). `alloc_words` allocates from the free lists before bumping the
+//! heap pointer.
+//!
+//! Free list `i` holds chunks with size in `[2^i, 2^(i+1))` words. An allocation takes the first
+//! large enough chunk in its list, or the first chunk in a larger list, and the rest of the chunk
+//! goes back to the free lists.
+//!
+//! Free chunks are objects with tag `TAG_FREE_CHUNK`, so that the heap can still be walked with
+//! `object_size`, linked in doubly-linked lists. Memory smaller than `MIN_CHUNK_SIZE` is filled
+//! with zeros (see the case for tag 0 in `object_size`) and is not reused until it's merged with
+//! adjacent free memory.
+//!
+//! A freed object is merged with the free chunks and zero words after it, and given back to the
+//! bump allocator when it ends at the heap pointer. Free memory before it is not merged, as the
+//! beginning of the previous object is not known. The mark-sweep GC merges all adjacent free
+//! memory when sweeping.
+//!
+//! The copying and generational GCs move objects or use the memory after the heap pointer, so they
+//! `reset` the free lists at the beginning of each collection: the chunks become blobs, which are
+//! garbage. The incremental GC resets them when it compacts.
+
+use crate::gc::large_objects::fill_gap;
+#[cfg(feature = "free_list")]
+use crate::gc::HP;
+use crate::types::*;
+
+use core::ptr::null_mut;
+
+/// Header, size, and links
+const MIN_CHUNK_SIZE: Words<usize> = Words(4);
+
+/// One free list per power of two chunk size (in words)
+const N_FREE_LISTS: usize = WORD_SIZE * 8;
+
+static mut FREE_LISTS: [*mut FreeChunk; N_FREE_LISTS] = [null_mut(); N_FREE_LISTS];
+
+/// Free list statistics, returned by `get_free_list_stats`
+#[repr(C)]
+pub struct FreeListStats {
+    /// Total size of the chunks in the free lists
+    pub free: Bytes<usize>,
+    /// Number of chunks in the free lists
+    pub chunks: usize,
+    /// Memory allocated from the free lists
+    pub reused: Bytes<u64>,
+    /// Memory of the objects freed in place
+    pub freed: Bytes<u64>,
+    /// Number of free chunks merged with freed objects
+    pub merged: u64,
+}
+
+static mut STATS: FreeListStats = FreeListStats {
+    free: Bytes(0),
+    chunks: 0,
+    reused: Bytes(0),
+    freed: Bytes(0),
+    merged: 0,
+};
+
+#[no_mangle]
+pub unsafe extern "C" fn get_free_list_stats() -> *const FreeListStats {
+    &STATS
+}
+
+fn free_list_idx(size: Words<usize>) -> usize {
+    N_FREE_LISTS - 1 - size.0.leading_zeros() as usize
+}
+
+/// Whether there are no chunks to allocate from
+pub(crate) unsafe fn is_empty() -> bool {
+    STATS.chunks == 0
+}
+
+/// Total size of the chunks in the free lists
+pub(crate) unsafe fn free_size() -> Bytes<usize> {
+    STATS.free
+}
+
+/// Adds `size` words of free memory at `addr` to the free lists
+pub(crate) unsafe fn add_free_chunk(addr: usize, size: Words<usize>) {
+    if size.0 < MIN_CHUNK_SIZE.0 {
+        libc::memset(addr as *mut _, 0, size.to_bytes().0);
+        return;
+    }
+
+    let chunk = addr as *mut FreeChunk;
+    let idx = free_list_idx(size);
+    let next = FREE_LISTS[idx];
+
+    (*chunk).header.tag = TAG_FREE_CHUNK;
+    (*chunk).size = size;
+    (*chunk).next = next;
+    (*chunk).prev = null_mut();
+
+    if !next.is_null() {
+        (*next).prev = chunk;
+    }
+    FREE_LISTS[idx] = chunk;
+
+    STATS.free += size.to_bytes();
+    STATS.chunks += 1;
+}
+
+unsafe fn remove_free_chunk(chunk: *mut FreeChunk) {
+    let next = (*chunk).next;
+    let prev = (*chunk).prev;
+
+    if prev.is_null() {
+        FREE_LISTS[free_list_idx((*chunk).size)] = next;
+    } else {
+        (*prev).next = next;
+    }
+
+    if !next.is_null() {
+        (*next).prev = prev;
+    }
+
+    STATS.free -= (*chunk).size.to_bytes();
+    STATS.chunks -= 1;
+}
+
+/// Takes the chunk at `addr` out of the free lists. Used by the incremental GC when sweeping, which
+/// merges the chunk with the free memory around it.
+pub(crate) unsafe fn take_chunk(addr: usize) {
+    remove_free_chunk(addr as *mut FreeChunk);
+}
+
+/// Allocates `n` words from the free lists. Returns `None` when there isn't a large enough chunk.
+pub(crate) unsafe fn alloc_from_free_list(n: Words<usize>) -> Option<usize> {
+    if STATS.chunks == 0 || n.0 == 0 {
+        return None;
+    }
+
+    let idx = free_list_idx(n);
+
+    // First fit in the list with chunks of similar size
+    let mut chunk = FREE_LISTS[idx];
+    while !chunk.is_null() {
+        if (*chunk).size.0 >= n.0 {
+            return Option::Some(split_chunk(chunk, n));
+        }
+        chunk = (*chunk).next;
+    }
+
+    // Any chunk in larger lists is large enough
+    for &chunk in &FREE_LISTS[idx + 1..] {
+        if !chunk.is_null() {
+            return Option::Some(split_chunk(chunk, n));
+        }
+    }
+
+    None
+}
+
+/// Allocates `n` words at the beginning of a chunk, returns the rest to the free lists
+unsafe fn split_chunk(chunk: *mut FreeChunk, n: Words<usize>) -> usize {
+    remove_free_chunk(chunk);
+    STATS.reused += Bytes(n.to_bytes().0 as u64);
+
+    let rest = (*chunk).size - n;
+    if rest.0 != 0 {
+        add_free_chunk(chunk as usize + n.to_bytes().0, rest);
+    }
+
+    chunk as usize
+}
+
+/// Frees `size` words at `addr` in place. The memory is merged with the free memory after it, and
+/// added to the free lists, or given back to the bump allocator when it ends at the heap pointer.
+#[cfg(feature = "free_list")]
+pub(crate) unsafe fn free(addr: usize, size: Words<usize>) {
+    STATS.freed += Bytes(size.to_bytes().0 as u64);
+
+    let mut end = addr + size.to_bytes().0;
+    while end < HP {
+        let tag = (end as *mut Obj).tag();
+        if tag == TAG_FREE_CHUNK {
+            let chunk = end as *mut FreeChunk;
+            remove_free_chunk(chunk);
+            end += (*chunk).size.to_bytes().0;
+            STATS.merged += 1;
+        } else if tag == 0 {
+            end += WORD_SIZE;
+        } else {
+            break;
+        }
+    }
+
+    if end == HP {
+        HP = addr;
+    } else {
+        add_free_chunk(addr, Bytes(end - addr).to_words());
+    }
+}
+
+/// Empties the free lists. The chunks are overwritten by the caller, the mark-sweep GC when
+/// sweeping.
+pub(crate) unsafe fn clear() {
+    FREE_LISTS = [null_mut(); N_FREE_LISTS];
+    STATS.free = Bytes(0);
+    STATS.chunks = 0;
+}
+
+/// Empties the free lists, the chunks become blobs. Called by the copying and generational GCs
+/// before collecting, and by the incremental GC before compacting.
+pub(crate) unsafe fn reset() {
+    if STATS.chunks == 0 {
+        return;
+    }
+
+    for &head in FREE_LISTS.iter() {
+        let mut chunk = head;
+        while !chunk.is_null() {
+            let next = (*chunk).next;
+            fill_gap(chunk as usize, (*chunk).size.to_bytes());
+            chunk = next;
+        }
+    }
+
+    clear();
+}
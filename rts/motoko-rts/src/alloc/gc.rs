@@ -1,36 +1,230 @@
 //! Implements allocation routines used by the generated code and the GC.
 
-use core::arch::wasm32;
+#[cfg(target_arch = "wasm32")]
+use core::arch::wasm32 as wasm;
+#[cfg(target_arch = "wasm64")]
+use core::arch::wasm64 as wasm;
 
+use super::free_list;
+#[cfg(feature = "redzones")]
+use super::redzones;
+use crate::alloc_profile::record_alloc;
 use crate::gc;
-use crate::rts_trap_with;
+use crate::to_space_arena;
 use crate::types::{skew, Bytes, SkewedPtr, Words};
 
+/// Limit of the allocation fast path in the generated code (`Heap.alloc` in `compile.ml`): an
+/// object smaller than `LARGE_OBJECT_THRESHOLD` is allocated by bumping `HP`, when the new heap
+/// pointer is not above the limit, its size is added to `FAST_ALLOCATED`, and it is counted in
+/// `gc::ALLOCATED_OBJECTS`. Otherwise the generated code calls `alloc_words`, the slow path. 0
+/// disables the fast path.
+///
+/// Below the limit bumping the heap pointer is all `alloc_words` would do, see `update_alloc_limit`.
+static mut ALLOC_LIMIT: usize = 0;
+
+/// Bytes allocated by the fast path since the last collection, not counted in `gc::ALLOCATED` yet
+pub(crate) static mut FAST_ALLOCATED: usize = 0;
+
+/// Locations of the variables used by the allocation fast path. The generated code reads the
+/// fields by offset on start up, see `Heap.init_alloc_fast_path` in `compile.ml`.
+#[repr(C)]
+pub struct AllocFastPath {
+    pub hp: *mut usize,
+    pub limit: *mut usize,
+    pub allocated: *mut usize,
+    pub objects: *mut usize,
+}
+
+static mut ALLOC_FAST_PATH: AllocFastPath = AllocFastPath {
+    hp: core::ptr::null_mut(),
+    limit: core::ptr::null_mut(),
+    allocated: core::ptr::null_mut(),
+    objects: core::ptr::null_mut(),
+};
+
 #[no_mangle]
-pub unsafe extern "C" fn alloc_words(n: Words<u32>) -> SkewedPtr {
+pub unsafe extern "C" fn get_alloc_fast_path() -> *const AllocFastPath {
+    ALLOC_FAST_PATH = AllocFastPath {
+        hp: &mut gc::HP,
+        limit: &mut ALLOC_LIMIT,
+        allocated: &mut FAST_ALLOCATED,
+        objects: &mut gc::ALLOCATED_OBJECTS,
+    };
+    &ALLOC_FAST_PATH
+}
+
+/// Updates `ALLOC_LIMIT`. Called by `alloc_words` and after each collection, and when the limit
+/// may have to be lowered: when the heap limit is set, when the to-space arena grows, and when the
+/// allocation profiler is enabled.
+///
+/// The fast path is disabled when allocations need more than a bump of the heap pointer: with
+/// redzones, for recording allocation sites, and when there are free chunks to reuse.
+/// Otherwise the limit is the lowest of the end of the allocated memory, the heap limit, and the
+/// beginning of the to-space arena.
+pub(crate) unsafe fn update_alloc_limit() {
+    if cfg!(feature = "redzones") || crate::alloc_profile::enabled() || !free_list::is_empty() {
+        ALLOC_LIMIT = 0;
+        return;
+    }
+
+    let limit = core::cmp::min(allocated_memory_end(), gc::heap_limit_end());
+    let limit = core::cmp::min(limit, to_space_arena::heap_end_limit());
+
+    // So that `HP + size` doesn't overflow in the fast path
+    ALLOC_LIMIT = core::cmp::min(
+        limit,
+        usize::MAX - gc::large_objects::LARGE_OBJECT_THRESHOLD.0,
+    );
+}
+
+/// End of the allocated pages
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+unsafe fn allocated_memory_end() -> usize {
+    wasm::memory_size(0).saturating_mul(65536)
+}
+
+/// Native builds (the tests) allocate the heap in advance, but don't know its end
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+unsafe fn allocated_memory_end() -> usize {
+    gc::MEMORY_END
+}
+
+/// Adds the allocations of the fast path to `gc::ALLOCATED`
+pub(crate) unsafe fn flush_fast_allocated() {
+    gc::ALLOCATED += Bytes(FAST_ALLOCATED as u64);
+    FAST_ALLOCATED = 0;
+}
+
+/// The slow path of the allocation fast path in the generated code, and the allocation routine of
+/// the RTS
+#[no_mangle]
+pub unsafe extern "C" fn alloc_words(n: Words<usize>) -> SkewedPtr {
+    // Collects in the GC stress mode of the tests, see `gc_stress.rs`
+    #[cfg(feature = "gc_stress")]
+    if let Some(hook) = crate::gc_stress::ALLOC_HOOK {
+        hook();
+    }
+
     let bytes = n.to_bytes();
     // Update ALLOCATED
     gc::ALLOCATED += Bytes(bytes.0 as u64);
+    gc::ALLOCATED_OBJECTS = gc::ALLOCATED_OBJECTS.wrapping_add(1);
+
+    // Space for the object, and the redzones around it
+    #[cfg(not(feature = "redzones"))]
+    let size = n;
+    #[cfg(feature = "redzones")]
+    let size = n + redzones::REDZONES;
+
+    // Reuse memory freed by the mark-sweep or incremental GC or `free_object`, or bump the heap
+    // pointer
+    let addr = match free_list::alloc_from_free_list(size) {
+        Some(addr) => {
+            gc::incremental::mark_reused(addr, size);
+            addr
+        }
+        None => bump(size),
+    };
+
+    #[cfg(feature = "redzones")]
+    let addr = redzones::guard(addr, n);
+
+    // The RTS may allocate other objects before initializing the fields of this one, the
+    // collections in allocations of the GC stress mode read the fields as scalars
+    #[cfg(feature = "gc_stress")]
+    libc::memset(addr as *mut _, 0, bytes.0);
+
+    if bytes >= gc::large_objects::LARGE_OBJECT_THRESHOLD {
+        gc::large_objects::register(addr);
+    }
+
+    record_alloc(skew(addr));
+    update_alloc_limit();
+
+    skew(addr)
+}
+
+/// Allocates `size` words at the heap pointer
+unsafe fn bump(size: Words<usize>) -> usize {
+    // The heap may grow into the arena above it
+    to_space_arena::make_room(size.to_bytes());
 
     // Update heap pointer
     let old_hp = gc::HP;
-    let new_hp = old_hp + bytes.0;
+    let new_hp = old_hp + size.to_bytes().0;
+    gc::check_heap_limit(new_hp);
     gc::HP = new_hp;
 
     // Grow memory if needed
-    grow_memory(new_hp as usize);
+    grow_memory(new_hp);
+
+    old_hp
+}
+
+/// Frees an object in place, for large temporaries of the RTS. The object must be unreachable, and
+/// allocated since the last collection (since the last call of a collector), so that it's young
+/// (generational GC) or new (incremental GC) memory that is reused.
+///
+/// With the `free_list` feature the memory is added to the free lists, see `free_list.rs`. With the
+/// bump allocator it's given back only when the object is at the end of the heap, otherwise it's
+/// left to the GC.
+#[no_mangle]
+pub unsafe extern "C" fn free_object(obj: SkewedPtr) {
+    let addr = obj.unskew();
+    gc::large_objects::forget(addr);
+
+    #[cfg(not(feature = "redzones"))]
+    let size = crate::types::object_size(addr);
 
-    skew(old_hp as usize)
+    // The redzones are freed with the object
+    #[cfg(feature = "redzones")]
+    let (addr, size) = redzones::allocation(addr);
+
+    #[cfg(feature = "free_list")]
+    free_list::free(addr, size);
+
+    #[cfg(not(feature = "free_list"))]
+    if addr + size.to_bytes().0 == gc::HP {
+        gc::HP = addr;
+    }
+
+    update_alloc_limit();
 }
 
 /// Page allocation. Ensures that the memory up to the given pointer is allocated.
 pub(crate) unsafe fn grow_memory(ptr: usize) {
-    let total_pages_needed = ((ptr / 65536) + 1) as i32;
-    let current_pages = wasm32::memory_size(0) as i32;
-    let new_pages_needed = total_pages_needed - current_pages;
-    if new_pages_needed > 0 {
-        if wasm32::memory_grow(0, new_pages_needed as usize) == core::usize::MAX {
-            rts_trap_with("Cannot grow memory");
+    if ptr > gc::MEMORY_END {
+        gc::MEMORY_END = ptr;
+        grow_pages(ptr);
+    }
+}
+
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+unsafe fn grow_pages(ptr: usize) {
+    let total_pages_needed = (ptr / 65536) + 1;
+    let current_pages = wasm::memory_size(0);
+    if total_pages_needed > current_pages {
+        if wasm::memory_grow(0, total_pages_needed - current_pages) == core::usize::MAX {
+            crate::rts_trap_with(
+                crate::error_code::ErrorCode::OutOfMemory,
+                "Cannot grow memory",
+            );
         }
     }
 }
+
+/// Native builds (the tests) allocate the heap in advance
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+unsafe fn grow_pages(_ptr: usize) {}
+
+/// Write barrier for the RTS. Should be called before updating a field of an existing object.
+/// Calls the barriers of all collectors, barriers of collectors not in use do nothing.
+pub(crate) unsafe fn write_barrier(location: *mut SkewedPtr) {
+    gc::incremental::write_barrier(location);
+    gc::generational::generational_write_barrier(location);
+}
+
+/// Read barrier for weak references, called with the referent when the mutator reads it
+pub(crate) unsafe fn weak_ref_read_barrier(referent: SkewedPtr) {
+    gc::incremental::weak_ref_read_barrier(referent);
+}
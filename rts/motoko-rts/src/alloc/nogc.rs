@@ -1,6 +1,15 @@
+use crate::alloc_profile::record_alloc;
 use crate::types::{skew, SkewedPtr, Words};
 
 #[no_mangle]
-pub unsafe extern "C" fn alloc_words(n: Words<u32>) -> SkewedPtr {
-    skew(libc::malloc(n.to_bytes().0 as usize) as usize)
+pub unsafe extern "C" fn alloc_words(n: Words<usize>) -> SkewedPtr {
+    let ptr = skew(libc::malloc(n.to_bytes().0) as usize);
+    record_alloc(ptr);
+    ptr
 }
+
+/// No GC, nothing to do
+pub(crate) unsafe fn write_barrier(_location: *mut SkewedPtr) {}
+
+/// No GC, nothing to do
+pub(crate) unsafe fn weak_ref_read_barrier(_referent: SkewedPtr) {}
@@ -0,0 +1,87 @@
+//! Redzones around allocations, enabled with the `redzones` feature. For catching buffer overruns
+//! in the RTS, e.g. off-by-one writes when filling a blob or text. Enabled in the tests.
+//!
+//! `alloc_words` allocates two redzones with every object, one before and one after it:
+//!
+//! ```text
+//! | TAG_REDZONE | n | object (n words) | TAG_REDZONE | CANARY |
+//! ```
+//!
+//! Redzones are two-word objects with no pointers, so the heap can still be walked with
+//! `object_size`. The one before the object records the size of the allocation, as the object
+//! may be shrunk after allocation (see `principal_id.rs`). Nothing points to redzones, so they're
+//! garbage and are dropped by the next collection, together with the dead objects. (Objects
+//! allocated during a cycle of the incremental GC are moved with their redzones.)
+//!
+//! The collectors call `check_heap` before collecting, i.e. before anything is freed or moved. It
+//! walks the heap and traps when a redzone was overwritten or when an object is larger than its
+//! allocation.
+
+use crate::error_code::ErrorCode;
+use crate::gc::{get_heap_base, HP};
+use crate::rts_trap_with;
+use crate::types::*;
+
+/// Space taken by the redzones of an allocation
+pub(crate) const REDZONES: Words<usize> = Words(4);
+
+/// Second word of the redzone after an object
+const CANARY: usize = 0xCAFE_F00D;
+
+/// Writes the redzones of an allocation of `n` words at `addr`, which must have space for
+/// `REDZONES` more words. Returns the address of the object.
+pub(crate) unsafe fn guard(addr: usize, n: Words<usize>) -> usize {
+    let before = addr as *mut usize;
+    *before = TAG_REDZONE;
+    *before.add(1) = n.0;
+
+    let obj = addr + Words(2).to_bytes().0;
+
+    let after = (obj + n.to_bytes().0) as *mut usize;
+    *after = TAG_REDZONE;
+    *after.add(1) = CANARY;
+
+    obj
+}
+
+/// Returns the memory allocated for the object at `obj`, with its redzones
+pub(crate) unsafe fn allocation(obj: usize) -> (usize, Words<usize>) {
+    let addr = obj - Words(2).to_bytes().0;
+    let n = Words(*(addr as *mut usize).add(1));
+    (addr, n + REDZONES)
+}
+
+/// Checks the redzones of all objects in the heap
+pub(crate) unsafe fn check_heap() {
+    let heap_end = HP;
+
+    let mut p = get_heap_base();
+    while p < heap_end {
+        if (p as *mut Obj).tag() != TAG_REDZONE {
+            // Not allocated with `alloc_words`, or its redzones were dropped in a collection
+            p += object_size(p).to_bytes().0;
+            continue;
+        }
+
+        let n = Words(*(p as *mut usize).add(1));
+        let obj = p + Words(2).to_bytes().0;
+        let end = obj + n.to_bytes().0;
+
+        if end + Words(2).to_bytes().0 > heap_end || object_size(obj).0 > n.0 {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "redzones: object larger than its allocation",
+            );
+        }
+
+        let after = end as *mut usize;
+        if *after != TAG_REDZONE || *after.add(1) != CANARY {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "redzones: redzone after object overwritten",
+            );
+        }
+
+        p = end + Words(2).to_bytes().0;
+    }
+}
@@ -0,0 +1,246 @@
+//! Allocation-site heap profiler.
+//!
+//! In profiling mode (`moc --alloc-profile`) the generated code numbers the expressions, and
+//! calls `set_alloc_site` with the number of the expression being evaluated. `alloc_words` then
+//! records every allocated object, with the current site, in the profile table. After each
+//! collection `update_alloc_profile` drops the dead objects from the table and counts the live
+//! objects and bytes per site. `alloc_profile` returns these counts.
+//!
+//! Site 0 means no site is known. Objects allocated by the RTS are attributed to the expression
+//! that called the RTS.
+//!
+//! The table is a heap-allocated Motoko array, a GC root like the closure table. Slot 0 holds a
+//! blob with the counts of the last collection, two words (objects, bytes) per site, indexed by
+//! site, or `FREE` before the first collection. The other slots hold two per entry: a weak
+//! reference to the object (see `weak_ref.rs`), so profiling does not keep objects alive, and the
+//! site as a scalar. Objects allocated by the profiler are not recorded.
+
+use crate::alloc::{alloc_array, alloc_blob, write_barrier};
+use crate::bigint::bigint_of_word64;
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::*;
+use crate::weak_ref::{weak_ref_alive, weak_ref_new};
+
+/// Initial number of entries
+const INITIAL_SIZE: usize = 64;
+
+const FREE: SkewedPtr = SkewedPtr(0);
+
+/// Set by `alloc_profile_enable`
+static mut ENABLED: bool = false;
+
+/// Site of the expression being evaluated
+static mut SITE: u32 = 0;
+
+/// Set while the profiler allocates, to not record its own objects
+static mut IN_PROFILER: bool = false;
+
+// Skewed pointer to the `Array` object, a GC root like the closure table
+static mut TABLE: SkewedPtr = SkewedPtr(0);
+
+// Number of used entries. Entries after these are free.
+static mut N_USED: usize = 0;
+
+unsafe fn capacity() -> usize {
+    if TABLE.0 == 0 {
+        0
+    } else {
+        (TABLE.as_array().len() - 1) / 2
+    }
+}
+
+unsafe fn get_slot(idx: usize) -> SkewedPtr {
+    TABLE.as_array().get(idx)
+}
+
+unsafe fn set_slot(idx: usize, value: SkewedPtr) {
+    let array = TABLE.as_array();
+    write_barrier(array.payload_addr().add(idx));
+    array.set(idx, value);
+}
+
+/// Doubles the size of the table
+unsafe fn grow_table() {
+    let old_size = capacity();
+    let new_size = if old_size == 0 {
+        INITIAL_SIZE
+    } else {
+        old_size * 2
+    };
+
+    let new_table = alloc_array(1 + new_size * 2);
+    let new_array = new_table.as_array();
+
+    if old_size == 0 {
+        new_array.set(0, FREE);
+    } else {
+        for i in 0..1 + N_USED * 2 {
+            new_array.set(i, get_slot(i));
+        }
+    }
+
+    for i in 1 + N_USED * 2..1 + new_size * 2 {
+        new_array.set(i, FREE);
+    }
+
+    TABLE = new_table;
+}
+
+/// Turns on profiling. Called by the generated code on start up in profiling mode.
+#[no_mangle]
+pub unsafe extern "C" fn alloc_profile_enable() {
+    ENABLED = true;
+    #[cfg(feature = "gc")]
+    crate::alloc::update_alloc_limit();
+}
+
+/// Whether allocations are recorded, which the allocation fast path of the generated code doesn't
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn enabled() -> bool {
+    ENABLED
+}
+
+/// Sets the site of the objects allocated next
+#[no_mangle]
+pub unsafe extern "C" fn set_alloc_site(site: u32) {
+    SITE = site;
+}
+
+/// Called by `alloc_words` with the new object, records it with the current site
+pub(crate) unsafe fn record_alloc(obj: SkewedPtr) {
+    if !ENABLED || IN_PROFILER {
+        return;
+    }
+
+    IN_PROFILER = true;
+
+    if N_USED == capacity() {
+        grow_table();
+    }
+
+    let weak_ref = weak_ref_new(obj);
+
+    set_slot(1 + N_USED * 2, weak_ref);
+    set_slot(2 + N_USED * 2, SkewedPtr((SITE as usize) << 1));
+    N_USED += 1;
+
+    IN_PROFILER = false;
+}
+
+/// Drops dead objects from the table, and counts the live objects per site. Called by the
+/// collectors after each collection.
+pub unsafe fn update_alloc_profile() {
+    if TABLE.0 == 0 {
+        return;
+    }
+
+    IN_PROFILER = true;
+
+    let mut n_used = 0;
+    let mut max_site = 0;
+    for i in 0..N_USED {
+        let weak_ref = get_slot(1 + i * 2);
+        if weak_ref_alive(weak_ref) == 0 {
+            continue;
+        }
+
+        let site = get_slot(2 + i * 2);
+        if n_used != i {
+            set_slot(1 + n_used * 2, weak_ref);
+            set_slot(2 + n_used * 2, site);
+        }
+        n_used += 1;
+        max_site = ::core::cmp::max(max_site, site.0 >> 1);
+    }
+
+    for i in 1 + n_used * 2..1 + N_USED * 2 {
+        set_slot(i, FREE);
+    }
+
+    N_USED = n_used;
+
+    let counts_blob = alloc_blob(Words((max_site + 1) * 2).to_bytes());
+    let counts = counts_blob.as_blob().payload_addr() as *mut usize;
+    libc::memset(counts as *mut _, 0, Words((max_site + 1) * 2).to_bytes().0);
+
+    for i in 0..N_USED {
+        let obj = (*get_slot(1 + i * 2).as_weak_ref()).field;
+        let site = get_slot(2 + i * 2).0 >> 1;
+        *counts.add(site * 2) += 1;
+        *counts.add(site * 2 + 1) += object_size(obj.unskew()).to_bytes().0;
+    }
+
+    set_slot(0, counts_blob);
+
+    IN_PROFILER = false;
+}
+
+/// A `Nat` in the compact representation of the generated code: tagged scalar when it fits in
+/// 31 bits (signed), boxed otherwise
+unsafe fn nat_of_word(n: usize) -> SkewedPtr {
+    if n < 1 << 30 {
+        SkewedPtr(n << 1)
+    } else {
+        bigint_of_word64(n as u64)
+    }
+}
+
+/// Returns an array of `(name, objects, bytes)` tuples, with the live objects and their total
+/// size per site, as of the last collection. Sites without live objects, and objects allocated
+/// when no site was known, are not included.
+/// `site_names` is an array with the name of site `i` at index `i - 1`, provided by the generated
+/// code.
+#[no_mangle]
+pub unsafe extern "C" fn alloc_profile(site_names: SkewedPtr) -> SkewedPtr {
+    let counts_blob = if TABLE.0 == 0 { FREE } else { get_slot(0) };
+    if counts_blob == FREE {
+        return alloc_array(0);
+    }
+
+    let n_sites = counts_blob.as_blob().len().to_words().0 / 2;
+    let counts = counts_blob.as_blob().payload_addr() as *const usize;
+    let site_names = site_names.as_array();
+
+    let mut n_live_sites = 0;
+    for site in 1..n_sites {
+        if *counts.add(site * 2) != 0 {
+            n_live_sites += 1;
+        }
+    }
+
+    let result = alloc_array(n_live_sites);
+
+    let mut idx = 0;
+    for site in 1..n_sites {
+        let n_objects = *counts.add(site * 2);
+        if n_objects == 0 {
+            continue;
+        }
+
+        if site > site_names.len() {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "alloc_profile: unknown allocation site",
+            );
+        }
+
+        let n_bytes = *counts.add(site * 2 + 1);
+
+        let tuple = alloc_array(3);
+        let tuple_array = tuple.as_array();
+        tuple_array.set(0, site_names.get(site - 1));
+        tuple_array.set(1, nat_of_word(n_objects));
+        tuple_array.set(2, nat_of_word(n_bytes));
+
+        result.as_array().set(idx, tuple);
+        idx += 1;
+    }
+
+    result
+}
+
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn alloc_profile_table_loc() -> *mut SkewedPtr {
+    &mut TABLE
+}
@@ -0,0 +1,37 @@
+//! Copying ranges of mutable arrays.
+
+use crate::alloc::write_barrier;
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::SkewedPtr;
+
+/// Copies the `len` elements of the array `src` from `src_off` to the mutable array `dst` at
+/// `dst_off`. The arrays can be the same, and the ranges can overlap.
+#[no_mangle]
+pub unsafe extern "C" fn array_blit(
+    dst: SkewedPtr,
+    dst_off: usize,
+    src: SkewedPtr,
+    src_off: usize,
+    len: usize,
+) {
+    let dst = dst.as_array();
+    let src = src.as_array();
+
+    let in_bounds = |off: usize, array_len: usize| match off.checked_add(len) {
+        Some(end) => end <= array_len,
+        None => false,
+    };
+    if !in_bounds(dst_off, dst.len()) || !in_bounds(src_off, src.len()) {
+        rts_trap_with(ErrorCode::OutOfBounds, "array_blit: Range out of bounds");
+    }
+
+    let to = dst.payload_addr().add(dst_off);
+
+    // The barriers are called with the old values, before any of them is overwritten
+    for i in 0..len {
+        write_barrier(to.add(i));
+    }
+
+    core::ptr::copy(src.payload_addr().add(src_off), to, len);
+}
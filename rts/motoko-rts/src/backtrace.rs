@@ -0,0 +1,98 @@
+//! Motoko backtraces in trap messages.
+//!
+//! With `--trap-backtrace` the generated code calls `backtrace_push` when a function or message is
+//! entered, with a static blob naming the function and its source region, and `backtrace_pop` when
+//! it returns. The traps of the RTS (`rts_trap_with`) and, with the flag, of the generated code
+//! (`trap_with_backtrace`) append the function names to the message, innermost first:
+//!
+//! ```text
+//! [E0005] RTS error: array_blit: Range out of bounds; backtrace: copy (a.mo:3.1-5.2) <- main (a.mo:7.1-9.2)
+//! ```
+//!
+//! Only the outermost `MAX_FRAMES` frames are kept, the number of frames inside them is given
+//! instead. Without the flag nothing is pushed, and the messages have no backtrace.
+//!
+//! A trap rolls back the message, so the frames are balanced at the end of every message that
+//! doesn't trap.
+
+use crate::types::{Bytes, SkewedPtr};
+
+const MAX_FRAMES: usize = 64;
+
+/// Static blobs with the names of the functions, outermost first
+static mut FRAMES: [SkewedPtr; MAX_FRAMES] = [SkewedPtr(0); MAX_FRAMES];
+
+/// Number of functions entered and not returned from, including those not in `FRAMES`
+static mut DEPTH: usize = 0;
+
+/// Enters the function named by the (static) blob
+#[no_mangle]
+pub unsafe extern "C" fn backtrace_push(name: SkewedPtr) {
+    if DEPTH < MAX_FRAMES {
+        FRAMES[DEPTH] = name;
+    }
+    DEPTH += 1;
+}
+
+/// Returns from the innermost function
+#[no_mangle]
+pub unsafe extern "C" fn backtrace_pop() {
+    DEPTH = DEPTH.saturating_sub(1);
+}
+
+/// Number of functions entered and not returned from
+pub unsafe fn backtrace_depth() -> usize {
+    DEPTH
+}
+
+/// Writes `; backtrace: ` and the frames to `buf`, cut off at its end, returns the number of bytes
+/// written. Writes nothing when there are no frames.
+pub unsafe fn write_backtrace(buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    let mut write = |bytes: &[u8]| {
+        let n = core::cmp::min(bytes.len(), buf.len() - len);
+        buf[len..len + n].copy_from_slice(&bytes[..n]);
+        len += n;
+    };
+
+    if DEPTH == 0 {
+        return 0;
+    }
+
+    write(b"; backtrace: ");
+
+    let n_frames = core::cmp::min(DEPTH, MAX_FRAMES);
+    if DEPTH > n_frames {
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut n = DEPTH - n_frames;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        write(b"(");
+        write(&digits[start..]);
+        write(b" more) <- ");
+    }
+
+    for i in (0..n_frames).rev() {
+        let blob = FRAMES[i].as_blob();
+        let Bytes(name_len) = blob.len();
+        write(core::slice::from_raw_parts(blob.payload_addr(), name_len));
+        if i > 0 {
+            write(b" <- ");
+        }
+    }
+
+    len
+}
+
+/// Traps with the message of the generated code and the backtrace, with `--trap-backtrace`
+#[no_mangle]
+unsafe extern "C" fn trap_with_backtrace(msg: *const u8, len: Bytes<usize>) -> ! {
+    crate::trap_with_parts(&[core::slice::from_raw_parts(msg, len.0)])
+}
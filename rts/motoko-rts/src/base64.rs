@@ -0,0 +1,141 @@
+//! Base64 encoding and decoding of blobs (RFC 4648), with the standard alphabet or the URL-safe
+//! one (`-` and `_` instead of `+` and `/`), with or without padding.
+//!
+//! The flags of `base64_encode` select the alphabet and whether the text is padded with `=` to a
+//! multiple of four characters. `base64_decode` accepts texts with and without padding, but not
+//! the characters of the other alphabet, whitespace, or bits after the last byte that are not
+//! zero, so every blob has only one (padded or unpadded) encoding.
+
+use crate::alloc::alloc_blob;
+use crate::text::{alloc_text, text_str};
+use crate::types::{Bytes, SkewedPtr};
+
+use core::slice;
+
+/// Use the URL-safe alphabet
+pub const BASE64_URL_SAFE: u32 = 1;
+
+/// Pad the encoding with `=` (only for `base64_encode`)
+pub const BASE64_PADDING: u32 = 2;
+
+static STANDARD_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+static URL_SAFE_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes the blob, returns a text
+#[no_mangle]
+pub unsafe extern "C" fn base64_encode(b: SkewedPtr, flags: u32) -> SkewedPtr {
+    let blob = b.as_blob();
+    let bytes = slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+
+    let chars = if flags & BASE64_URL_SAFE != 0 {
+        URL_SAFE_CHARS
+    } else {
+        STANDARD_CHARS
+    };
+    let padding = flags & BASE64_PADDING != 0;
+
+    let size = if padding {
+        (bytes.len() + 2) / 3 * 4
+    } else {
+        (bytes.len() * 4 + 2) / 3
+    };
+
+    let (r, payload) = alloc_text(Bytes(size));
+    let mut dest = payload;
+
+    for group in bytes.chunks(3) {
+        let data = group.iter().enumerate().fold(0u32, |data, (i, byte)| {
+            data | u32::from(*byte) << (16 - 8 * i)
+        });
+
+        // A group of `n` bytes is encoded with `n + 1` characters
+        for i in 0..4 {
+            if i <= group.len() {
+                *dest = chars[(data >> (18 - 6 * i) & 0b11_1111) as usize];
+            } else if padding {
+                *dest = b'=';
+            } else {
+                break;
+            }
+            dest = dest.add(1);
+        }
+    }
+
+    debug_assert_eq!(dest.offset_from(payload) as usize, size);
+
+    r
+}
+
+/// Value of the character in the alphabet
+fn char_value(c: u8, url_safe: bool) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some(u32::from(c - b'A')),
+        b'a'..=b'z' => Some(u32::from(c - b'a') + 26),
+        b'0'..=b'9' => Some(u32::from(c - b'0') + 52),
+        b'+' if !url_safe => Some(62),
+        b'/' if !url_safe => Some(63),
+        b'-' if url_safe => Some(62),
+        b'_' if url_safe => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes the text, returns a blob, or 0 when the text is not a valid encoding (see above)
+#[no_mangle]
+pub unsafe extern "C" fn base64_decode(t: SkewedPtr, flags: u32) -> SkewedPtr {
+    let url_safe = flags & BASE64_URL_SAFE != 0;
+    let mut chars = text_str(t).as_bytes();
+
+    // With padding the size is a multiple of four
+    if chars.len() % 4 == 0 {
+        if chars.ends_with(b"==") {
+            chars = &chars[..chars.len() - 2];
+        } else if chars.ends_with(b"=") {
+            chars = &chars[..chars.len() - 1];
+        }
+    }
+
+    // Only the last group can be partial, and one character is not enough for a byte
+    if chars.len() % 4 == 1 {
+        return SkewedPtr(0);
+    }
+
+    if !chars.iter().all(|&c| char_value(c, url_safe).is_some()) {
+        return SkewedPtr(0);
+    }
+
+    let groups = chars.chunks(4);
+
+    // The bits after the last byte must be zero
+    if let Some(last) = groups.clone().last() {
+        let unused_bits = match last.len() {
+            2 => 0b1111,
+            3 => 0b11,
+            _ => 0,
+        };
+        if char_value(last[last.len() - 1], url_safe).unwrap() & unused_bits != 0 {
+            return SkewedPtr(0);
+        }
+    }
+
+    let size = chars.len() / 4 * 3 + (chars.len() % 4).saturating_sub(1);
+    let r = alloc_blob(Bytes(size));
+    let mut dest = r.as_blob().payload_addr();
+
+    for group in groups {
+        let data = group.iter().enumerate().fold(0u32, |data, (i, c)| {
+            data | char_value(*c, url_safe).unwrap() << (18 - 6 * i)
+        });
+
+        // A group of `n` characters has `n - 1` bytes
+        for i in 0..group.len() - 1 {
+            *dest = (data >> (16 - 8 * i)) as u8;
+            dest = dest.add(1);
+        }
+    }
+
+    r
+}
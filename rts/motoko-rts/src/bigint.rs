@@ -1,403 +1,766 @@
 //! Implements big int support:
 //!
-//! - libtommath memory management
-//! - libtommath wrappers
+//! - allocation of big integers
+//! - arithmetic, in terms of the magnitude arithmetic of `mag.rs`
 //! - (s)leb128 encoding/decoding for bigints
 
 /*
-A libtommath arbitrary precision integer is a struct (`mp_int`) that contains a pointer to a data
-array.
+A big integer is a `TAG_BIGINT` object (see `BigInt` in `types.rs`) with a sign and the digits of
+the magnitude. The compiler emits big integer literals in the same layout.
 
- - The libtommath library never allocates the struct, so we are in full control.
+ - Results are allocated with enough digits for any result of the operation (e.g. the sum of the
+   digits of the operands for a multiplication), written in place, and then `used` is set to the
+   number of digits without leading zeros. So the GC manages the digits like the payload of a
+   blob, there is no separate allocation.
 
-   We allocate that struct on the stack if we need it temporarily, or embed it in a TAG_BIGINT
-   object when we persist the number.
+ - Big integers on the heap are never modified after `finish`.
 
- - The data array is allocated with mp_calloc() and mp_realloc(), and these functions are _only_ used
-   to allocate the data array.
+ - Temporary digits (e.g. of a division) are allocated as blobs, and reclaimed by the GC. The
+   GC runs only between messages, so the digits can be used as slices until the end of the
+   function.
 
-   We provide these calls, allocate TAG_BIGINT objects (leaving space for the mp_int), and pass a
-   pointer _into_ this object back to libtommath, which stores it in the `mp_digit* dp`
-   field of the struct.
+ - Conversions from machine integers return static objects for small numbers (see
+   `SMALL_BIGINTS`), as the compiler boxes tagged scalars for the slow paths of arithmetic.
+*/
 
-When persisting a `mp_int` (presumably stack-allocated), we know that the `mp_digit` pointer
-points to a `TAG_BIGINT` with sufficient space for the `mp_int` data. We copy the `mp_int`
-there, and use the overall `TAG_BIGINT` as the bignum object.
+mod decimal;
+mod float;
+mod mag;
+mod parse;
 
-This scheme makes the following assumptions:
+pub use self::decimal::bigint_to_text;
+pub use self::float::{bigint_of_float64, bigint_to_float64};
+pub use self::parse::bigint_of_text_radix;
 
- - libtommath never modifies the data on the heap.
-   (or put differently, we only pass those to libtommath when they are immutable)
- - libtommath uses mp_calloc() and mp_realloc() _only_ to allocate the `mp_digit *` array.
-*/
+use self::mag::{Digit, DIGIT_BITS};
 
-use crate::alloc::alloc_words;
+use crate::alloc::{alloc_blob, alloc_words};
 use crate::buf::{read_byte, Buf};
-use crate::mem::memcpy_bytes;
-use crate::types::{size_of, skew, BigInt, Bytes, SkewedPtr, TAG_BIGINT};
+use crate::error_code::ErrorCode;
+use crate::stream::Stream;
+use crate::types::{size_of, skew, BigInt, Bytes, SkewedPtr, TAG_BIGINT, WORD_SIZE};
+use crate::{rts_trap, rts_trap_with};
 
-use crate::{rts_trap, tommath_bindings::*};
+use core::cmp::Ordering;
 
-unsafe fn mp_alloc(size: Bytes<u32>) -> *mut u8 {
-    let ptr = alloc_words(size_of::<BigInt>() + size.to_words());
-    let blob = ptr.unskew() as *mut BigInt;
-    (*blob).header.tag = TAG_BIGINT;
-    // libtommath stores the size of the object in alloc
-    // as count of mp_digits (u64)
-    debug_assert_eq!((size.0 as usize % core::mem::size_of::<mp_digit>()), 0);
-    (*blob).mp_int.alloc = (size.0 as usize / core::mem::size_of::<mp_digit>()) as i32;
-    blob.payload_addr() as *mut u8
+// Trap function generated by compiler. Originally added in e2ca6a1. I think this could be
+// simplified now by calling rts_trap and removing generated code from the compiler.
+extern "C" {
+    fn bigint_trap() -> !;
 }
 
-#[no_mangle]
-unsafe extern "C" fn mp_calloc(n_elems: usize, elem_size: Bytes<usize>) -> *mut libc::c_void {
-    debug_assert_eq!(elem_size.0, core::mem::size_of::<mp_digit>());
-    // Overflow check for the following multiplication
-    if n_elems > 1 << 30 {
+/// Allocates a big integer with `n` digits, the digits are not initialized
+unsafe fn alloc_bigint(n: usize) -> *mut BigInt {
+    // Larger than the heap, also avoids overflows in the size computations
+    if n > 1 << 28 {
         bigint_trap();
     }
-    let size = Bytes((n_elems * elem_size.0) as u32);
-    let payload = mp_alloc(size) as *mut u32;
+    let ptr =
+        alloc_words(size_of::<BigInt>() + Bytes(n * core::mem::size_of::<Digit>()).to_words());
+    let bigint = ptr.unskew() as *mut BigInt;
+    (*bigint).header.tag = TAG_BIGINT;
+    (*bigint).used = 0;
+    (*bigint).alloc = n;
+    (*bigint).sign = 0;
+    bigint
+}
 
-    // NB. alloc_bytes rounds up to words so we do the same here to set the whole buffer
-    for i in 0..size.to_words().0 {
-        *payload.add(i as usize) = 0;
-    }
+/// All allocated digits of a big integer that is not finished yet, to write the result to
+unsafe fn digits_mut(bigint: *mut BigInt) -> &'static mut [Digit] {
+    core::slice::from_raw_parts_mut(bigint.payload_addr(), (*bigint).alloc)
+}
 
-    payload as *mut _
+/// Sets the number of used digits and the sign (zero is not negative)
+unsafe fn finish(bigint: *mut BigInt, negative: bool) -> SkewedPtr {
+    let used = mag::normalized_len(digits_mut(bigint));
+    (*bigint).used = used;
+    (*bigint).sign = (negative && used > 0) as usize;
+    skew(bigint as usize)
 }
 
-#[no_mangle]
-unsafe extern "C" fn mp_realloc(
-    ptr: *mut libc::c_void,
-    old_size: Bytes<u32>,
-    new_size: Bytes<u32>,
-) -> *mut libc::c_void {
-    let bigint = BigInt::from_payload(ptr as *mut mp_digit);
+/// The digits of the magnitude, without leading zeros
+unsafe fn digits(p: SkewedPtr) -> &'static [Digit] {
+    let bigint = p.as_bigint();
+    core::slice::from_raw_parts(bigint.payload_addr(), (*bigint).used)
+}
 
-    debug_assert_eq!((*bigint).header.tag, TAG_BIGINT);
-    debug_assert_eq!(bigint.len(), old_size);
+unsafe fn is_neg(p: SkewedPtr) -> bool {
+    (*p.as_bigint()).sign == 1
+}
+
+/// A big integer with the digits of the magnitude
+unsafe fn bigint_of_digits(ds: &[Digit], negative: bool) -> SkewedPtr {
+    let r = alloc_bigint(ds.len());
+    digits_mut(r).copy_from_slice(ds);
+    finish(r, negative)
+}
 
-    if new_size > bigint.len() {
-        let new_ptr = mp_alloc(new_size);
-        memcpy_bytes(new_ptr as usize, ptr as usize, old_size);
-        new_ptr as *mut _
-    } else if new_size == bigint.len() {
-        ptr
+unsafe fn bigint_of_mag64(w: u64, negative: bool) -> SkewedPtr {
+    let small = if negative {
+        w <= SMALL_MIN
     } else {
-        // libtommath only shrinks via mp_shrink and we do not use that function, so this should not
-        // happen.
-        panic!("mp_realloc: trying to shrink");
+        w <= SMALL_MAX
+    };
+    if small {
+        return small_bigint(w as Digit, negative);
     }
+    bigint_of_digits(&[w as Digit, (w >> DIGIT_BITS) as Digit], negative)
 }
 
-#[no_mangle]
-unsafe extern "C" fn mp_free(_ptr: *mut libc::c_void, _size: u32) {}
-
-/*
-Note on libtommath error handling
----------------------------------
-
-Most libtommath operations return an enum to signal error codes. These are (see tommath.h):
+/// A big integer with the magnitude of up to 128 bits
+pub(crate) unsafe fn bigint_of_mag128(w: u128, negative: bool) -> SkewedPtr {
+    if w <= u128::from(u64::MAX) {
+        return bigint_of_mag64(w as u64, negative);
+    }
+    let mut ds = [0; (128 / DIGIT_BITS) as usize];
+    for (i, d) in ds.iter_mut().enumerate() {
+        *d = (w >> (i as u32 * DIGIT_BITS)) as Digit;
+    }
+    bigint_of_digits(&ds, negative)
+}
 
-   MP_OKAY  = 0,   /* no error */
-   MP_ERR   = -1,  /* unknown error */
-   MP_MEM   = -2,  /* out of mem */
-   MP_VAL   = -3,  /* invalid input */
-   MP_ITER  = -4,  /* maximum iterations reached */
-   MP_BUF   = -5   /* buffer overflow, supplied buffer too small */
+/// Magnitudes of the smallest and largest numbers in `SMALL_BIGINTS`, i.e. the numbers from -128
+/// to 1024
+const SMALL_MIN: u64 = 128;
+const SMALL_MAX: u64 = 1024;
 
-We will never hit MP_MEM, because our allocation functions trap if they cannot allocate. But the
-others can happen (e.g. division by 0). In that case, we call a trap function provided by the
-compiler.
-*/
+/// Words of a big integer with one digit, in whole words so the objects are word-aligned
+const SMALL_BIGINT_WORDS: usize = core::mem::size_of::<BigInt>() / WORD_SIZE + 1;
 
-// Trap function generated by compiler. Originally added in e2ca6a1. I think this could be
-// simplified now by calling rts_trap and removing generated code from the compiler.
-extern "C" {
-    fn bigint_trap() -> !;
-}
+/// Big integers of the numbers from `-SMALL_MIN` to `SMALL_MAX`, initialized on first use. They are
+/// outside of the dynamic heap, so the GC does not move or reclaim them (like the static big
+/// integers of literals), and as the table is initially zero it takes no space in the Wasm module.
+static mut SMALL_BIGINTS: [[usize; SMALL_BIGINT_WORDS]; (SMALL_MIN + SMALL_MAX + 1) as usize] =
+    [[0; SMALL_BIGINT_WORDS]; (SMALL_MIN + SMALL_MAX + 1) as usize];
 
-unsafe fn check(err: mp_err) {
-    if err != 0 {
-        bigint_trap();
+unsafe fn small_bigint(magnitude: Digit, negative: bool) -> SkewedPtr {
+    let i = if negative {
+        SMALL_MIN as usize - magnitude as usize
+    } else {
+        SMALL_MIN as usize + magnitude as usize
+    };
+    let bigint = SMALL_BIGINTS[i].as_mut_ptr() as *mut BigInt;
+    if (*bigint).header.tag != TAG_BIGINT {
+        (*bigint).header.tag = TAG_BIGINT;
+        (*bigint).alloc = 1;
+        *bigint.payload_addr() = magnitude;
+        finish(bigint, negative);
     }
+    skew(bigint as usize)
 }
 
-unsafe fn mp_get_u32(p: *const mp_int) -> u32 {
-    mp_get_i32(p) as u32
-}
-
-unsafe fn mp_get_u64(p: *const mp_int) -> u64 {
-    mp_get_i64(p) as u64
-}
-
-unsafe fn mp_isneg(p: *const mp_int) -> bool {
-    (*p).sign == 1
+/// A big integer with the value of the 64-bit signed integer
+unsafe fn bigint_of_i64(j: i64) -> SkewedPtr {
+    if j < 0 {
+        bigint_of_mag64((j as u64).wrapping_neg(), true)
+    } else {
+        bigint_of_mag64(j as u64, false)
+    }
 }
 
-unsafe fn mp_iszero(p: *const mp_int) -> bool {
-    (*p).used == 0
+/// The low 64 bits of the magnitude
+unsafe fn low_u64(p: SkewedPtr) -> u64 {
+    let ds = digits(p);
+    let low = u64::from(ds.first().copied().unwrap_or(0));
+    let high = u64::from(ds.get(1).copied().unwrap_or(0));
+    high << DIGIT_BITS | low
 }
 
-// Allocates a mp_int on the stack
-unsafe fn tmp_bigint() -> mp_int {
-    let mut i: mp_int = core::mem::zeroed();
-    check(mp_init(&mut i));
-    i
+/// The number, when it is not negative and fits in 128 bits
+pub(crate) unsafe fn bigint_to_nat128(p: SkewedPtr) -> Option<u128> {
+    if is_neg(p) || bigint_count_bits(p) > 128 {
+        return None;
+    }
+    let mut w: u128 = 0;
+    for (i, &d) in digits(p).iter().enumerate() {
+        w |= u128::from(d) << (i as u32 * DIGIT_BITS);
+    }
+    Some(w)
 }
 
-// Persists an mp_int from the stack on the heap
-unsafe fn persist_bigint(i: mp_int) -> SkewedPtr {
-    if i.dp == core::ptr::null_mut() {
-        panic!("persist_bigint: dp == NULL?");
-    }
-    let r = BigInt::from_payload(i.dp);
-    if (*r).mp_int.alloc != i.alloc {
-        panic!("persist_bigint: alloc changed?");
+/// The low 64 bits of the two's complement of the number
+unsafe fn wrap_u64(p: SkewedPtr) -> u64 {
+    if is_neg(p) {
+        low_u64(p).wrapping_neg()
+    } else {
+        low_u64(p)
     }
-    (*r).mp_int = i;
-    skew(r as usize)
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_of_word32(w: u32) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    mp_set_u32(&mut i, w);
-    persist_bigint(i)
+    bigint_of_mag64(u64::from(w), false)
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_of_int32(j: i32) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    mp_set_i32(&mut i, j);
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_of_int32(j: i32) -> SkewedPtr {
+    bigint_of_i64(i64::from(j))
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_to_word32_wrap(p: SkewedPtr) -> u32 {
-    mp_get_u32(p.as_bigint().mp_int_ptr())
+pub unsafe extern "C" fn bigint_to_word32_wrap(p: SkewedPtr) -> u32 {
+    wrap_u64(p) as u32
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_to_word32_trap(p: SkewedPtr) -> u32 {
-    let mp_int = p.as_bigint().mp_int_ptr();
-
-    if mp_isneg(mp_int) || mp_count_bits(mp_int) > 32 {
+pub unsafe extern "C" fn bigint_to_word32_trap(p: SkewedPtr) -> u32 {
+    if is_neg(p) || bigint_count_bits(p) > 32 {
         bigint_trap();
     }
 
-    mp_get_u32(mp_int)
+    low_u64(p) as u32
 }
 
 // a : BigInt, msg : Blob
 #[no_mangle]
 unsafe extern "C" fn bigint_to_word32_trap_with(p: SkewedPtr, msg: SkewedPtr) -> u32 {
-    let mp_int = p.as_bigint().mp_int_ptr();
-
-    if mp_isneg(mp_int) || mp_count_bits(mp_int) > 32 {
+    if is_neg(p) || bigint_count_bits(p) > 32 {
         rts_trap(msg.as_blob().payload_addr(), msg.as_blob().len());
     }
 
-    mp_get_u32(mp_int)
+    low_u64(p) as u32
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_to_word64_wrap(p: SkewedPtr) -> u64 {
-    mp_get_u64(p.as_bigint().mp_int_ptr())
+pub unsafe extern "C" fn bigint_to_word64_wrap(p: SkewedPtr) -> u64 {
+    wrap_u64(p)
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_to_word64_trap(p: SkewedPtr) -> u64 {
-    let mp_int = p.as_bigint().mp_int_ptr();
-
-    if mp_isneg(mp_int) || mp_count_bits(mp_int) > 64 {
+pub unsafe extern "C" fn bigint_to_word64_trap(p: SkewedPtr) -> u64 {
+    if is_neg(p) || bigint_count_bits(p) > 64 {
         bigint_trap();
     }
 
-    mp_get_u64(mp_int)
+    low_u64(p)
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_of_word64(w: u64) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    mp_set_u64(&mut i, w);
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_of_word64(w: u64) -> SkewedPtr {
+    bigint_of_mag64(w, false)
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_of_int64(j: i64) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    mp_set_i64(&mut i, j);
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_of_int64(j: i64) -> SkewedPtr {
+    bigint_of_i64(j)
+}
+
+unsafe fn bigint_cmp(a: SkewedPtr, b: SkewedPtr) -> Ordering {
+    match (is_neg(a), is_neg(b)) {
+        (false, false) => mag::cmp(digits(a), digits(b)),
+        (true, true) => mag::cmp(digits(b), digits(a)),
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_eq(a: SkewedPtr, b: SkewedPtr) -> bool {
-    mp_cmp(a.as_bigint().mp_int_ptr(), b.as_bigint().mp_int_ptr()) == 0
+    bigint_cmp(a, b) == Ordering::Equal
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_lt(a: SkewedPtr, b: SkewedPtr) -> bool {
-    mp_cmp(a.as_bigint().mp_int_ptr(), b.as_bigint().mp_int_ptr()) < 0
+pub unsafe extern "C" fn bigint_lt(a: SkewedPtr, b: SkewedPtr) -> bool {
+    bigint_cmp(a, b) == Ordering::Less
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_gt(a: SkewedPtr, b: SkewedPtr) -> bool {
-    mp_cmp(a.as_bigint().mp_int_ptr(), b.as_bigint().mp_int_ptr()) > 0
+pub unsafe extern "C" fn bigint_gt(a: SkewedPtr, b: SkewedPtr) -> bool {
+    bigint_cmp(a, b) == Ordering::Greater
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_le(a: SkewedPtr, b: SkewedPtr) -> bool {
-    mp_cmp(a.as_bigint().mp_int_ptr(), b.as_bigint().mp_int_ptr()) <= 0
+pub unsafe extern "C" fn bigint_le(a: SkewedPtr, b: SkewedPtr) -> bool {
+    bigint_cmp(a, b) != Ordering::Greater
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_ge(a: SkewedPtr, b: SkewedPtr) -> bool {
-    mp_cmp(a.as_bigint().mp_int_ptr(), b.as_bigint().mp_int_ptr()) >= 0
+pub unsafe extern "C" fn bigint_ge(a: SkewedPtr, b: SkewedPtr) -> bool {
+    bigint_cmp(a, b) != Ordering::Less
+}
+
+/// Sum of the numbers with the magnitudes and signs
+unsafe fn add_signed(a: &[Digit], a_neg: bool, b: &[Digit], b_neg: bool) -> SkewedPtr {
+    if a_neg == b_neg {
+        let r = alloc_bigint(core::cmp::max(a.len(), b.len()) + 1);
+        mag::add(a, b, digits_mut(r));
+        return finish(r, a_neg);
+    }
+
+    // Different signs: subtract the smaller magnitude from the larger one
+    let (large, small, negative) = match mag::cmp(a, b) {
+        Ordering::Less => (b, a, b_neg),
+        _ => (a, b, a_neg),
+    };
+    let r = alloc_bigint(large.len());
+    mag::sub(large, small, digits_mut(r));
+    finish(r, negative)
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_add(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_add(
-        a.as_bigint().mp_int_ptr(),
-        b.as_bigint().mp_int_ptr(),
-        &mut i,
-    ));
-    persist_bigint(i)
+    add_signed(digits(a), is_neg(a), digits(b), is_neg(b))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_sub(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_sub(
-        a.as_bigint().mp_int_ptr(),
-        b.as_bigint().mp_int_ptr(),
-        &mut i,
-    ));
-    persist_bigint(i)
+    add_signed(digits(a), is_neg(a), digits(b), !is_neg(b))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_mul(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_mul(
-        a.as_bigint().mp_int_ptr(),
-        b.as_bigint().mp_int_ptr(),
-        &mut i,
-    ));
-    persist_bigint(i)
+    let (a_digits, b_digits) = (digits(a), digits(b));
+    let r = alloc_bigint(a_digits.len() + b_digits.len());
+    mag::mul(a_digits, b_digits, digits_mut(r));
+    finish(r, is_neg(a) != is_neg(b))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_pow(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
-    let exp = bigint_to_word32_trap(b);
-    let mut i = tmp_bigint();
-    check(mp_expt_u32(a.as_bigint().mp_int_ptr(), exp, &mut i));
-    persist_bigint(i)
+    let mut exp = bigint_to_word32_trap(b);
+
+    // Square and multiply, from the least significant bit of the exponent
+    let mut base = a;
+    let mut r = bigint_of_word32(1);
+    while exp != 0 {
+        if exp & 1 != 0 {
+            r = bigint_mul(r, base);
+        }
+        exp >>= 1;
+        if exp != 0 {
+            base = bigint_mul(base, base);
+        }
+    }
+    r
+}
+
+/// `a mod m` of normalized magnitudes, normalized
+unsafe fn mag_mod(a: &'static [Digit], m: &[Digit]) -> &'static [Digit] {
+    if mag::cmp(a, m) == Ordering::Less {
+        return a;
+    }
+    let q = mag::alloc_digits(a.len() - m.len() + 1);
+    let r = mag::alloc_digits(m.len());
+    mag::div_rem(a, m, q, r);
+    &r[..mag::normalized_len(r)]
+}
+
+/// `a * b mod m` of normalized magnitudes, normalized
+unsafe fn mag_mul_mod(a: &[Digit], b: &[Digit], m: &[Digit]) -> &'static [Digit] {
+    let p = mag::alloc_digits(a.len() + b.len());
+    mag::mul(a, b, p);
+    mag_mod(&p[..mag::normalized_len(p)], m)
+}
+
+/// Bits of the exponent per window of `bigint_pow_mod`, divides `DIGIT_BITS`
+const POW_MOD_WINDOW_BITS: u32 = 4;
+
+/// `base ^ exp mod modulus`, in `[0, modulus)` also for negative bases. Traps when the exponent
+/// is negative or the modulus is not positive.
+///
+/// Uses fixed windows of the exponent: each window of `POW_MOD_WINDOW_BITS` bits costs that many
+/// squarings and at most one multiplication with a precomputed power of the base.
+#[no_mangle]
+pub unsafe extern "C" fn bigint_pow_mod(
+    base: SkewedPtr,
+    exp: SkewedPtr,
+    modulus: SkewedPtr,
+) -> SkewedPtr {
+    if is_neg(exp) {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_pow_mod: Negative exponent",
+        );
+    }
+    if is_neg(modulus) || digits(modulus).is_empty() {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_pow_mod: Modulus is not positive",
+        );
+    }
+    let m = digits(modulus);
+    let e = digits(exp);
+
+    // The base as a non-negative number less than the modulus
+    let mut b = mag_mod(digits(base), m);
+    if is_neg(base) && !b.is_empty() {
+        let r = mag::alloc_digits(m.len());
+        mag::sub(m, b, r);
+        b = &r[..mag::normalized_len(r)];
+    }
+
+    // powers[i] = b ^ i mod m
+    let one = mag_mod(&[1], m);
+    let mut powers: [&[Digit]; 1 << POW_MOD_WINDOW_BITS] = [&[]; 1 << POW_MOD_WINDOW_BITS];
+    powers[0] = one;
+    for i in 1..powers.len() {
+        powers[i] = mag_mul_mod(powers[i - 1], b, m);
+    }
+
+    let windows = (mag::count_bits(e) as u32 + POW_MOD_WINDOW_BITS - 1) / POW_MOD_WINDOW_BITS;
+    let mut r = one;
+    for w in (0..windows).rev() {
+        for _ in 0..POW_MOD_WINDOW_BITS {
+            r = mag_mul_mod(r, r, m);
+        }
+        let bit = w * POW_MOD_WINDOW_BITS;
+        let window =
+            e[(bit / DIGIT_BITS) as usize] >> (bit % DIGIT_BITS) & ((1 << POW_MOD_WINDOW_BITS) - 1);
+        if window != 0 {
+            r = mag_mul_mod(r, powers[window as usize], m);
+        }
+    }
+
+    bigint_of_digits(r, false)
+}
+
+/// The integer square root of a 64-bit number, with Newton's method
+fn isqrt_u64(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    // Start above the root, so that the iteration decreases until it reaches the root
+    let mut x = 1u64 << ((64 - n.leading_zeros() + 1) / 2);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// The integer square root (rounded down). Traps on negative numbers.
+#[no_mangle]
+pub unsafe extern "C" fn bigint_isqrt(a: SkewedPtr) -> SkewedPtr {
+    if is_neg(a) {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_isqrt: Negative argument",
+        );
+    }
+
+    let bits = bigint_count_bits(a) as u32;
+    if bits <= 64 {
+        return bigint_of_word64(isqrt_u64(low_u64(a)));
+    }
+
+    // Newton's method, as in `isqrt_u64`
+    let mut x = bigint_shl(bigint_of_word32(1), (bits + 1) / 2);
+    loop {
+        let y = bigint_shr(bigint_add(x, bigint_div(a, x)), 1);
+        if bigint_ge(y, x) {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Binary GCD of non-zero numbers
+fn gcd_u64(mut a: u64, mut b: u64) -> u64 {
+    let twos = (a | b).trailing_zeros();
+    a >>= a.trailing_zeros();
+    loop {
+        b >>= b.trailing_zeros();
+        if a > b {
+            core::mem::swap(&mut a, &mut b);
+        }
+        b -= a;
+        if b == 0 {
+            return a << twos;
+        }
+    }
+}
+
+/// The greatest common divisor of the magnitudes, with binary GCD (`gcd(0, 0) = 0`)
+#[no_mangle]
+pub unsafe extern "C" fn bigint_gcd(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    let a_digits = digits(a);
+    let b_digits = digits(b);
+    if a_digits.is_empty() {
+        return bigint_abs(b);
+    }
+    if b_digits.is_empty() {
+        return bigint_abs(a);
+    }
+    if a_digits.len() <= 2 && b_digits.len() <= 2 {
+        return bigint_of_word64(gcd_u64(low_u64(a), low_u64(b)));
+    }
+
+    let x = mag::alloc_digits(a_digits.len());
+    x.copy_from_slice(a_digits);
+    let y = mag::alloc_digits(b_digits.len());
+    y.copy_from_slice(b_digits);
+    let (odd, twos) = mag::gcd(x, y);
+
+    let r = alloc_bigint(odd.len() + twos / DIGIT_BITS as usize + 1);
+    mag::shl(odd, twos, digits_mut(r));
+    finish(r, false)
 }
 
+/// The least common multiple of the magnitudes (zero when one of them is zero)
 #[no_mangle]
-unsafe extern "C" fn bigint_div(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_div(
-        a.as_bigint().mp_int_ptr(),
-        b.as_bigint().mp_int_ptr(),
-        &mut i,
-        core::ptr::null_mut(),
-    ));
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_lcm(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    if digits(a).is_empty() || digits(b).is_empty() {
+        return bigint_of_word32(0);
+    }
+    bigint_mul(bigint_div(bigint_abs(a), bigint_gcd(a, b)), bigint_abs(b))
+}
+
+/// Quotient (rounded towards zero) and remainder (with the sign of `a`)
+unsafe fn div_rem(a: SkewedPtr, b: SkewedPtr) -> (SkewedPtr, SkewedPtr) {
+    let (a_digits, b_digits) = (digits(a), digits(b));
+    if b_digits.is_empty() {
+        bigint_trap();
+    }
+
+    if mag::cmp(a_digits, b_digits) == Ordering::Less {
+        return (bigint_of_word32(0), a);
+    }
+
+    let q = alloc_bigint(a_digits.len() - b_digits.len() + 1);
+    let r = alloc_bigint(b_digits.len());
+    mag::div_rem(a_digits, b_digits, digits_mut(q), digits_mut(r));
+    (finish(q, is_neg(a) != is_neg(b)), finish(r, is_neg(a)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bigint_div(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    div_rem(a, b).0
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_rem(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_div(
-        a.as_bigint().mp_int_ptr(),
-        b.as_bigint().mp_int_ptr(),
-        core::ptr::null_mut(),
-        &mut i,
-    ));
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_rem(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    div_rem(a, b).1
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_neg(a: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_neg(a.as_bigint().mp_int_ptr(), &mut i));
-    persist_bigint(i)
+    bigint_of_digits(digits(a), !is_neg(a))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bigint_abs(a: SkewedPtr) -> SkewedPtr {
+    if is_neg(a) {
+        bigint_of_digits(digits(a), false)
+    } else {
+        a
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bigint_isneg(a: SkewedPtr) -> bool {
+    is_neg(a)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bigint_lsh(a: SkewedPtr, b: i32) -> SkewedPtr {
+    if b < 0 {
+        bigint_trap();
+    }
+    bigint_shl(a, b as u32)
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_abs(a: SkewedPtr) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_abs(a.as_bigint().mp_int_ptr(), &mut i));
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_count_bits(a: SkewedPtr) -> i32 {
+    mag::count_bits(digits(a)) as i32
+}
+
+//
+// Bitwise operations. These work on the two's complement of the numbers, with infinitely many
+// sign bits, so e.g. `-1` has all bits set.
+//
+
+/// The two's complement of the number in `n` digits, more than the digits of the magnitude so
+/// that the top bit is the sign
+unsafe fn twos_complement(p: SkewedPtr, n: usize) -> &'static mut [Digit] {
+    let ds = digits(p);
+    debug_assert!(n > ds.len());
+    let out = mag::alloc_digits(n);
+    out[..ds.len()].copy_from_slice(ds);
+    for d in out[ds.len()..].iter_mut() {
+        *d = 0;
+    }
+    if is_neg(p) {
+        mag::negate(out);
+    }
+    out
+}
+
+/// Applies `op` to the digits of the two's complements of the numbers
+unsafe fn bitwise(a: SkewedPtr, b: SkewedPtr, op: fn(Digit, Digit) -> Digit) -> SkewedPtr {
+    let n = core::cmp::max(digits(a).len(), digits(b).len()) + 1;
+    let a_digits = twos_complement(a, n);
+    let b_digits = twos_complement(b, n);
+
+    let r = alloc_bigint(n);
+    let out = digits_mut(r);
+    for (i, d) in out.iter_mut().enumerate() {
+        *d = op(a_digits[i], b_digits[i]);
+    }
+
+    let negative = out[n - 1] >> (DIGIT_BITS - 1) != 0;
+    if negative {
+        mag::negate(out);
+    }
+    finish(r, negative)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bigint_and(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    bitwise(a, b, |x, y| x & y)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bigint_or(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    bitwise(a, b, |x, y| x | y)
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_isneg(a: SkewedPtr) -> bool {
-    mp_isneg(a.as_bigint().mp_int_ptr())
+pub unsafe extern "C" fn bigint_xor(a: SkewedPtr, b: SkewedPtr) -> SkewedPtr {
+    bitwise(a, b, |x, y| x ^ y)
 }
 
+/// `a * 2^shift`
 #[no_mangle]
-unsafe extern "C" fn bigint_lsh(a: SkewedPtr, b: i32) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    check(mp_mul_2d(a.as_bigint().mp_int_ptr(), b, &mut i));
-    persist_bigint(i)
+pub unsafe extern "C" fn bigint_shl(a: SkewedPtr, shift: u32) -> SkewedPtr {
+    let a_digits = digits(a);
+    let r = alloc_bigint(a_digits.len() + shift as usize / DIGIT_BITS as usize + 1);
+    mag::shl(a_digits, shift as usize, digits_mut(r));
+    finish(r, is_neg(a))
 }
 
+/// `a / 2^shift`, rounded towards negative infinity (an arithmetic shift of the two's complement)
 #[no_mangle]
-unsafe extern "C" fn bigint_count_bits(a: SkewedPtr) -> i32 {
-    mp_count_bits(a.as_bigint().mp_int_ptr())
+pub unsafe extern "C" fn bigint_shr(a: SkewedPtr, shift: u32) -> SkewedPtr {
+    let a_digits = digits(a);
+    let n = a_digits
+        .len()
+        .saturating_sub(shift as usize / DIGIT_BITS as usize);
+
+    // One more digit for rounding negative numbers
+    let r = alloc_bigint(n + 1);
+    let out = digits_mut(r);
+    let lost = mag::shr(a_digits, shift as usize, &mut out[..n]);
+    out[n] = 0;
+    if is_neg(a) && lost {
+        let carry = mag::add_assign(out, &[1]);
+        debug_assert_eq!(carry, 0);
+    }
+    finish(r, is_neg(a))
+}
+
+/// Whether bit `i` of the two's complement is set
+#[no_mangle]
+pub unsafe extern "C" fn bigint_bit_test(a: SkewedPtr, i: u32) -> bool {
+    let ds = digits(a);
+    let index = i as usize / DIGIT_BITS as usize;
+    let mut digit = ds.get(index).copied().unwrap_or(0);
+
+    if is_neg(a) {
+        // In the two's complement of a negative number the digits below the lowest non-zero digit
+        // of the magnitude are zero, that digit is negated, and the digits above it are
+        // complemented
+        let lowest = ds.iter().position(|&d| d != 0).unwrap();
+        digit = match index.cmp(&lowest) {
+            Ordering::Less => 0,
+            Ordering::Equal => digit.wrapping_neg(),
+            Ordering::Greater => !digit,
+        };
+    }
+
+    digit >> (i % DIGIT_BITS) & 1 != 0
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_leb128_size(a: SkewedPtr) -> u32 {
-    if mp_iszero(a.as_bigint().mp_int_ptr()) {
+    if digits(a).is_empty() {
         1
     } else {
         (bigint_count_bits(a) as u32 + 6) / 7 // divide by 7, round up
     }
 }
 
-// `add_bit` argument is to make this work for both leb and sleb encoding
-unsafe fn bigint_leb128_encode_go(tmp: *mut mp_int, mut buf: *mut u8, add_bit: bool) {
-    if mp_isneg(tmp) {
-        bigint_trap();
-    }
-
-    loop {
-        let byte = mp_get_u32(tmp) as u8;
-        check(mp_div_2d(tmp, 7, tmp, core::ptr::null_mut()));
-        if !mp_iszero(tmp) || (add_bit && byte & (1 << 6) != 0) {
-            *buf = byte | (1 << 7);
-            buf = buf.add(1);
+/// Emits the low `7 * n_bytes` bits of the two's complement of the number in (s)leb128 groups
+unsafe fn bigint_leb128_encode_go<F: FnMut(u8)>(n: SkewedPtr, n_bytes: u32, mut emit: F) {
+    let ds = digits(n);
+    let negative = is_neg(n);
+
+    // The two's complement of a negative number is the complement of the magnitude plus one. The
+    // carry of the plus one propagates through the zero digits.
+    let mut carry = negative;
+    let mut twos_complement = (0..).map(move |i| {
+        let d = ds.get(i).copied().unwrap_or(0);
+        if negative {
+            let (d, c) = (!d).overflowing_add(carry as Digit);
+            carry = c;
+            d
         } else {
-            *buf = byte;
-            break;
+            d
+        }
+    });
+
+    let mut bits: u64 = 0;
+    let mut n_bits = 0;
+    for i in 0..n_bytes {
+        if n_bits < 7 {
+            bits |= u64::from(twos_complement.next().unwrap()) << n_bits;
+            n_bits += DIGIT_BITS;
         }
+        let byte = (bits & 0b0111_1111) as u8;
+        bits >>= 7;
+        n_bits -= 7;
+
+        emit(if i + 1 < n_bytes {
+            byte | (1 << 7)
+        } else {
+            byte
+        });
+    }
+}
+
+/// Writes the bytes to consecutive addresses from `buf`
+unsafe fn emit_to_buf(mut buf: *mut u8) -> impl FnMut(u8) {
+    move |byte| {
+        *buf = byte;
+        buf = buf.add(1);
     }
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_leb128_encode(n: SkewedPtr, buf: *mut u8) {
-    let mut tmp: mp_int = core::mem::zeroed(); // or core::mem::uninitialized?
-    check(mp_init_copy(&mut tmp, n.as_bigint().mp_int_ptr()));
-    bigint_leb128_encode_go(&mut tmp, buf, false)
+    if is_neg(n) {
+        bigint_trap();
+    }
+
+    bigint_leb128_encode_go(n, bigint_leb128_size(n), emit_to_buf(buf))
+}
+
+/// Writes the leb128 encoding of a non-negative number to the stream
+pub(crate) unsafe fn bigint_leb128_write<S: Stream>(n: SkewedPtr, stream: &mut S) {
+    if is_neg(n) {
+        bigint_trap();
+    }
+
+    bigint_leb128_encode_go(n, bigint_leb128_size(n), |byte| stream.write(&[byte]))
 }
 
 #[no_mangle]
-unsafe extern "C" fn bigint_2complement_bits(n: SkewedPtr) -> u32 {
-    let mp_int = n.as_bigint().mp_int_ptr();
-    if mp_isneg(mp_int) {
-        let mut tmp: mp_int = core::mem::zeroed(); // or core::mem::uninitialized?
-        check(mp_init_copy(&mut tmp, mp_int));
-        check(mp_incr(&mut tmp));
-        1 + mp_count_bits(&tmp) as u32
+pub unsafe extern "C" fn bigint_2complement_bits(n: SkewedPtr) -> u32 {
+    let ds = digits(n);
+    if is_neg(n) {
+        // The bits of `-m` are one more than the bits of `m - 1`, which has one bit less than `m`
+        // when `m` is a power of two
+        let (top, low) = ds.split_last().unwrap();
+        let power_of_two = top.is_power_of_two() && low.iter().all(|&d| d == 0);
+        let bits = mag::count_bits(ds) as u32;
+        if power_of_two {
+            bits
+        } else {
+            1 + bits
+        }
     } else {
-        1 + mp_count_bits(mp_int) as u32
+        1 + mag::count_bits(ds) as u32
     }
 }
 
@@ -408,69 +771,169 @@ pub unsafe extern "C" fn bigint_sleb128_size(n: SkewedPtr) -> u32 {
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_sleb128_encode(n: SkewedPtr, buf: *mut u8) {
-    let mut tmp: mp_int = core::mem::zeroed(); // or core::mem::uninitialized?
-    check(mp_init_copy(&mut tmp, n.as_bigint().mp_int_ptr()));
-
-    if mp_isneg(&tmp) {
-        // Turn negatiave numbers into the two's complement of the right size
-        let bytes = bigint_sleb128_size(n);
-        let mut big: mp_int = core::mem::zeroed();
-        check(mp_init(&mut big));
-        check(mp_2expt(&mut big, 7 * bytes as i32));
-        check(mp_add(&mut tmp, &big, &mut tmp));
-        bigint_leb128_encode_go(&mut tmp, buf, false)
-    } else {
-        bigint_leb128_encode_go(&mut tmp, buf, true)
+    bigint_leb128_encode_go(n, bigint_sleb128_size(n), emit_to_buf(buf))
+}
+
+/// Writes the sleb128 encoding of the number to the stream
+pub(crate) unsafe fn bigint_sleb128_write<S: Stream>(n: SkewedPtr, stream: &mut S) {
+    bigint_leb128_encode_go(n, bigint_sleb128_size(n), |byte| stream.write(&[byte]))
+}
+
+/// Reads the (s)leb128 groups to a big integer of the (unsigned) bits, returns it with the number
+/// of bits and whether the last group has the sign bit set
+unsafe fn bigint_leb128_decode_go(buf: *mut Buf) -> (*mut BigInt, usize, bool) {
+    // Find the end first, `read_byte` traps when reading past the end of the buffer
+    let start = (*buf).ptr;
+    while read_byte(buf) & 0b1000_0000 != 0 {}
+    let n_bytes = (*buf).ptr.offset_from(start) as usize;
+
+    let n_bits = 7 * n_bytes;
+    let r = alloc_bigint((n_bits + DIGIT_BITS as usize - 1) / DIGIT_BITS as usize);
+    let ds = digits_mut(r);
+
+    let mut bits: u64 = 0;
+    let mut n_buffered = 0;
+    let mut i = 0;
+    for j in 0..n_bytes {
+        bits |= u64::from(*start.add(j) & 0b0111_1111) << n_buffered;
+        n_buffered += 7;
+        if n_buffered >= DIGIT_BITS {
+            ds[i] = bits as Digit;
+            i += 1;
+            bits >>= DIGIT_BITS;
+            n_buffered -= DIGIT_BITS;
+        }
+    }
+    if n_buffered > 0 {
+        ds[i] = bits as Digit;
     }
+
+    let sign_bit_set = *start.add(n_bytes - 1) & 0b0100_0000 != 0;
+    (r, n_bits, sign_bit_set)
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bigint_leb128_decode(buf: *mut Buf) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    let mut tmp = tmp_bigint();
+    let (r, _, _) = bigint_leb128_decode_go(buf);
+    finish(r, false)
+}
 
-    let mut shift = 0;
-    loop {
-        let byte = read_byte(buf);
-        mp_set_u32(&mut tmp, (byte & 0b0111_1111) as u32);
-        check(mp_mul_2d(&mut tmp, shift, &mut tmp));
-        check(mp_add(&mut i, &tmp, &mut i));
-        shift += 7;
-
-        if byte & 0b1000_0000 == 0 {
-            break;
+#[no_mangle]
+pub unsafe extern "C" fn bigint_sleb128_decode(buf: *mut Buf) -> SkewedPtr {
+    let (r, n_bits, sign_bit_set) = bigint_leb128_decode_go(buf);
+
+    if sign_bit_set {
+        // Negative number, un-2-complement it: the magnitude is `2^n_bits - bits`, the complement
+        // of the bits plus one, in `n_bits` bits
+        let ds = digits_mut(r);
+        let mut carry = true;
+        for d in ds.iter_mut() {
+            let (x, c) = (!*d).overflowing_add(carry as Digit);
+            *d = x;
+            carry = c;
+        }
+        let top_bits = n_bits % DIGIT_BITS as usize;
+        if top_bits != 0 {
+            *ds.last_mut().unwrap() &= (1 << top_bits) - 1;
         }
     }
 
-    persist_bigint(i)
+    finish(r, sign_bit_set)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn bigint_sleb128_decode(buf: *mut Buf) -> SkewedPtr {
-    let mut i = tmp_bigint();
-    let mut tmp = tmp_bigint();
+//
+// Little-endian blobs, for binary formats other than (s)leb128
+//
 
-    let mut shift = 0;
-    let mut last_sign_bit_set;
-    loop {
-        let byte = read_byte(buf);
-        mp_set_u32(&mut tmp, (byte & 0b0111_1111) as u32);
-        check(mp_mul_2d(&mut tmp, shift, &mut tmp));
-        check(mp_add(&mut i, &tmp, &mut i));
-        last_sign_bit_set = byte & 0b0100_0000 != 0;
-        shift += 7;
-
-        if byte & 0b1000_0000 == 0 {
-            break;
+/// Byte `i` of the digits
+fn digits_byte(ds: &[Digit], i: usize) -> u8 {
+    (ds[i / 4] >> (8 * (i % 4))) as u8
+}
+
+/// A big integer with `n` digits of the little-endian bytes, which are extended with `extension`
+/// bytes
+unsafe fn digits_of_bytes(bytes: &[u8], extension: u8, n: usize) -> *mut BigInt {
+    let r = alloc_bigint(n);
+    let ds = digits_mut(r);
+    for (i, d) in ds.iter_mut().enumerate() {
+        let mut digit: Digit = 0;
+        for j in (0..4).rev() {
+            let byte = bytes.get(4 * i + j).copied().unwrap_or(extension);
+            digit = digit << 8 | Digit::from(byte);
         }
+        *d = digit;
+    }
+    r
+}
+
+/// The magnitude of a natural number as little-endian bytes, with no trailing zero bytes (so zero
+/// is the empty blob). Traps on negative numbers.
+#[no_mangle]
+pub unsafe extern "C" fn bigint_to_le_blob(n: SkewedPtr) -> SkewedPtr {
+    if is_neg(n) {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_to_le_blob: Negative number",
+        );
+    }
+    let ds = digits(n);
+    let n_bytes = (mag::count_bits(ds) + 7) / 8;
+
+    let r = alloc_blob(Bytes(n_bytes));
+    let payload = core::slice::from_raw_parts_mut(r.as_blob().payload_addr(), n_bytes);
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte = digits_byte(ds, i);
+    }
+    r
+}
+
+/// The natural number of the little-endian bytes
+#[no_mangle]
+pub unsafe extern "C" fn bigint_of_le_blob(b: SkewedPtr) -> SkewedPtr {
+    let blob = b.as_blob();
+    let bytes = core::slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+    let r = digits_of_bytes(bytes, 0, (bytes.len() + 3) / 4);
+    finish(r, false)
+}
+
+/// The two's complement of the number in `width` little-endian bytes. Traps when the number does
+/// not fit.
+#[no_mangle]
+pub unsafe extern "C" fn bigint_to_signed_le_blob(n: SkewedPtr, width: u32) -> SkewedPtr {
+    let ds = digits(n);
+    let width = width as usize;
+    if !ds.is_empty() && bigint_2complement_bits(n) as usize > 8 * width {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_to_signed_le_blob: Number does not fit",
+        );
     }
 
-    if last_sign_bit_set {
-        // Negative number, un-2-complement it
-        let mut big = tmp_bigint();
-        check(mp_2expt(&mut big, shift));
-        check(mp_sub(&mut i, &big, &mut i));
+    let tc = twos_complement(n, core::cmp::max(ds.len() + 1, (width + 3) / 4));
+    let r = alloc_blob(Bytes(width));
+    let payload = core::slice::from_raw_parts_mut(r.as_blob().payload_addr(), width);
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte = digits_byte(tc, i);
     }
+    r
+}
 
-    persist_bigint(i)
+/// The number of the two's complement in the little-endian bytes (negative when the top bit of
+/// the last byte is set)
+#[no_mangle]
+pub unsafe extern "C" fn bigint_of_signed_le_blob(b: SkewedPtr) -> SkewedPtr {
+    let blob = b.as_blob();
+    let bytes = core::slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+    let negative = matches!(bytes.last(), Some(&byte) if byte & 0b1000_0000 != 0);
+
+    // Sign extended to whole digits, so the negation gives the magnitude
+    let r = digits_of_bytes(
+        bytes,
+        if negative { 0xff } else { 0 },
+        (bytes.len() + 3) / 4,
+    );
+    if negative {
+        mag::negate(digits_mut(r));
+    }
+    finish(r, negative)
 }
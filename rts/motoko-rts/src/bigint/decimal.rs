@@ -0,0 +1,182 @@
+//! Decimal formatting of big integers.
+//!
+//! Repeated division by 10 is quadratic in the number of digits. Instead the number is split into
+//! a high and a low half by a division by `10^(18 * 2^i)`, recursively, down to parts of at most
+//! 18 digits, which are formatted as `u64`s. The divisions multiply with reciprocals of the powers
+//! of ten (Barrett reduction), which are computed once per power with Newton's method, so all the
+//! work is in multiplications, which are subquadratic with Karatsuba's method.
+
+use super::{
+    bigint_abs, bigint_add, bigint_count_bits, bigint_div, bigint_ge, bigint_isneg, bigint_lt,
+    bigint_mul, bigint_of_word32, bigint_of_word64, bigint_shl, bigint_shr, bigint_sub, low_u64,
+};
+
+use crate::alloc::alloc_blob;
+use crate::text::alloc_text;
+use crate::types::{Bytes, SkewedPtr};
+
+/// Decimal digits of the parts that are formatted as `u64`, `10^18 < 2^64`
+const CHUNK_DIGITS: usize = 18;
+
+/// Reciprocals with at most this many bits are computed with long division, which is linear in
+/// the size of the divisor for short quotients
+const RECIPROCAL_CUTOFF_BITS: u32 = 2048;
+
+/// `floor(2^k / d)` for positive `d`
+unsafe fn reciprocal(d: SkewedPtr, k: u32) -> SkewedPtr {
+    let one = bigint_of_word32(1);
+    let d_bits = bigint_count_bits(d) as u32;
+    if k < d_bits {
+        return bigint_of_word32(0);
+    }
+
+    // The result has at most `p` bits
+    let p = k - d_bits + 1;
+    if p <= RECIPROCAL_CUTOFF_BITS {
+        return bigint_div(bigint_shl(one, k), d);
+    }
+
+    // An approximation of the top `h` bits of the result, from the top bits of `d`
+    let h = p / 2 + 2;
+    let s = d_bits.saturating_sub(h + 2);
+    let x = bigint_shl(reciprocal(bigint_shr(d, s), k - (p - h) - s), p - h);
+
+    // A step of Newton's method for `1 / d` doubles the correct bits: `x + x * (2^k - d * x) / 2^k`
+    let power = bigint_shl(one, k);
+    let e = bigint_sub(power, bigint_mul(d, x));
+    let mut x = bigint_add(x, bigint_shr(bigint_mul(x, e), k));
+
+    // The approximation is off by a few units at most, correct it with the remainder
+    let mut r = bigint_sub(power, bigint_mul(d, x));
+    while bigint_isneg(r) {
+        x = bigint_sub(x, one);
+        r = bigint_add(r, d);
+    }
+    while bigint_ge(r, d) {
+        x = bigint_add(x, one);
+        r = bigint_sub(r, d);
+    }
+    x
+}
+
+/// A power of ten with its reciprocal, `reciprocal = floor(2^k / power)` for `k = 2 * bits(power)`
+#[derive(Clone, Copy)]
+struct Power {
+    power: SkewedPtr,
+    reciprocal: SkewedPtr,
+    k: u32,
+}
+
+impl Power {
+    unsafe fn new(power: SkewedPtr) -> Power {
+        let k = 2 * bigint_count_bits(power) as u32;
+        Power {
+            power,
+            reciprocal: reciprocal(power, k),
+            k,
+        }
+    }
+}
+
+/// Writes the digits of `n` to `out`, padded with zeros. `powers[i]` is `10^(CHUNK_DIGITS * 2^i)`,
+/// `out` has `CHUNK_DIGITS * 2^powers.len()` digits, and `n < 10^out.len()`.
+unsafe fn write_digits(n: SkewedPtr, powers: &[Power], out: &mut [u8]) {
+    let (top, rest) = match powers.split_last() {
+        Some(split) => split,
+        None => {
+            let mut x = low_u64(n);
+            for c in out.iter_mut().rev() {
+                *c = b'0' + (x % 10) as u8;
+                x /= 10;
+            }
+            return;
+        }
+    };
+
+    let (high, low) = out.split_at_mut(out.len() / 2);
+
+    if bigint_lt(n, top.power) {
+        for c in high.iter_mut() {
+            *c = b'0';
+        }
+        write_digits(n, rest, low);
+        return;
+    }
+
+    // The estimate of the quotient is at most two too small, as `n < 2^k`
+    let one = bigint_of_word32(1);
+    let mut q = bigint_shr(bigint_mul(n, top.reciprocal), top.k);
+    let mut r = bigint_sub(n, bigint_mul(q, top.power));
+    while bigint_ge(r, top.power) {
+        q = bigint_add(q, one);
+        r = bigint_sub(r, top.power);
+    }
+
+    write_digits(q, rest, high);
+    write_digits(r, rest, low);
+}
+
+/// The decimal digits of the magnitude of `n`, without leading zeros
+unsafe fn magnitude_digits(n: SkewedPtr, buf: &mut [u8; CHUNK_DIGITS]) -> &[u8] {
+    let n = bigint_abs(n);
+
+    // Powers while `n >= 10^(CHUNK_DIGITS * 2^powers.len())`, i.e. the square of the last power
+    let mut powers = [Power {
+        power: SkewedPtr(0),
+        reciprocal: SkewedPtr(0),
+        k: 0,
+    }; 32];
+    let mut n_powers = 0;
+    let mut next = bigint_of_word64(1_000_000_000_000_000_000);
+    while !bigint_lt(n, next) {
+        powers[n_powers] = Power::new(next);
+        n_powers += 1;
+        next = bigint_mul(next, next);
+    }
+
+    let len = CHUNK_DIGITS << n_powers;
+    let out: &mut [u8] = if n_powers == 0 {
+        buf
+    } else {
+        let blob = alloc_blob(Bytes(len)).as_blob();
+        core::slice::from_raw_parts_mut(blob.payload_addr(), len)
+    };
+    write_digits(n, &powers[..n_powers], out);
+
+    let start = out.iter().position(|&c| c != b'0').unwrap_or(len - 1);
+    &out[start..]
+}
+
+/// The decimal text of the number, with a `-` for negative numbers, and, with `separators`, `_`
+/// between groups of three digits (as in `debug_show`)
+#[no_mangle]
+pub unsafe extern "C" fn bigint_to_text(n: SkewedPtr, separators: bool) -> SkewedPtr {
+    let mut buf = [0u8; CHUNK_DIGITS];
+    let digits = magnitude_digits(n, &mut buf);
+    let negative = bigint_isneg(n);
+
+    let n_separators = if separators {
+        (digits.len() - 1) / 3
+    } else {
+        0
+    };
+    let len = negative as usize + digits.len() + n_separators;
+    let (r, payload) = alloc_text(Bytes(len));
+    let out = core::slice::from_raw_parts_mut(payload, len);
+
+    let mut i = 0;
+    if negative {
+        out[0] = b'-';
+        i = 1;
+    }
+    for (j, &c) in digits.iter().enumerate() {
+        if j > 0 && n_separators > 0 && (digits.len() - j) % 3 == 0 {
+            out[i] = b'_';
+            i += 1;
+        }
+        out[i] = c;
+        i += 1;
+    }
+
+    r
+}
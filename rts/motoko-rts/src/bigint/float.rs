@@ -0,0 +1,89 @@
+//! Conversions between big integers and `f64`
+
+use super::mag::{self, DIGIT_BITS};
+use super::{bigint_neg, bigint_of_word64, bigint_shl, digits, is_neg, low_u64};
+
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::SkewedPtr;
+
+/// Bits of the significand of an `f64`, with the implicit leading bit
+const SIGNIFICAND_BITS: u32 = 53;
+
+/// The exponent of an `f64` is stored with this bias
+const EXPONENT_BIAS: i32 = 1023;
+
+/// The number rounded to the nearest `f64`, ties to even, infinite when the rounded number is
+/// beyond the range of `f64`
+#[no_mangle]
+pub unsafe extern "C" fn bigint_to_float64(n: SkewedPtr) -> f64 {
+    let ds = digits(n);
+    let n_bits = mag::count_bits(ds);
+
+    let magnitude = if n_bits <= 64 {
+        // Conversion from `u64` rounds to nearest, ties to even
+        low_u64(n) as f64
+    } else {
+        // The top 64 bits, with the lowest bit set when any of the dropped bits is set. That bit
+        // is far below the rounding position, so the rounding of the top bits is the rounding of
+        // the number.
+        let shift = n_bits - 64;
+        let top = mag::alloc_digits(ds.len() - shift / DIGIT_BITS as usize);
+        let inexact = mag::shr(ds, shift, top);
+        let top = (u64::from(top[1]) << DIGIT_BITS | u64::from(top[0])) | inexact as u64;
+
+        // In `[2^63, 2^64]`, exact after scaling unless it is out of range
+        let rounded = top as f64;
+        let exponent = (rounded.to_bits() >> (SIGNIFICAND_BITS - 1)) as usize + shift;
+        if exponent >= 0x7ff {
+            f64::INFINITY
+        } else {
+            f64::from_bits(
+                (exponent as u64) << (SIGNIFICAND_BITS - 1)
+                    | rounded.to_bits() & ((1 << (SIGNIFICAND_BITS - 1)) - 1),
+            )
+        }
+    };
+
+    if is_neg(n) {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// The number, truncated towards zero. Traps on NaN and infinities.
+#[no_mangle]
+pub unsafe extern "C" fn bigint_of_float64(f: f64) -> SkewedPtr {
+    if !f.is_finite() {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_of_float64: Not a finite number",
+        );
+    }
+
+    let bits = f.to_bits();
+    let negative = bits >> 63 != 0;
+    let biased_exponent = ((bits >> (SIGNIFICAND_BITS - 1)) & 0x7ff) as i32;
+
+    // Below one, including zeros and subnormal numbers
+    if biased_exponent < EXPONENT_BIAS {
+        return bigint_of_word64(0);
+    }
+
+    // `|f| = significand * 2^exponent`
+    let significand = bits & ((1 << (SIGNIFICAND_BITS - 1)) - 1) | 1 << (SIGNIFICAND_BITS - 1);
+    let exponent = biased_exponent - EXPONENT_BIAS - (SIGNIFICAND_BITS as i32 - 1);
+
+    let r = if exponent < 0 {
+        bigint_of_word64(significand >> -exponent)
+    } else {
+        bigint_shl(bigint_of_word64(significand), exponent as u32)
+    };
+
+    if negative {
+        bigint_neg(r)
+    } else {
+        r
+    }
+}
@@ -0,0 +1,372 @@
+//! Arithmetic on magnitudes (natural numbers): slices of 32-bit digits, least significant first.
+//!
+//! Slices can have leading zero digits unless noted. Results are written to slices provided by the
+//! caller, of the size given in the function's documentation, and callers drop the leading zeros
+//! of the result with `normalized_len`. Temporary digits are allocated as blobs on the heap, which
+//! the GC reclaims (see `alloc_digits`).
+
+use crate::alloc::alloc_blob;
+use crate::types::Bytes;
+
+use core::cmp::{max, min, Ordering};
+
+pub(super) type Digit = u32;
+type DoubleDigit = u64;
+
+pub(super) const DIGIT_BITS: u32 = 32;
+
+/// Operands with at least this many digits are multiplied with Karatsuba's method
+const KARATSUBA_CUTOFF: usize = 32;
+
+/// Allocates `n` digits, not initialized
+pub(super) unsafe fn alloc_digits(n: usize) -> &'static mut [Digit] {
+    let blob = alloc_blob(Bytes(n * core::mem::size_of::<Digit>())).as_blob();
+    core::slice::from_raw_parts_mut(blob.payload_addr() as *mut Digit, n)
+}
+
+/// Number of digits without the leading zeros
+pub(super) fn normalized_len(a: &[Digit]) -> usize {
+    a.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1)
+}
+
+/// Compares normalized magnitudes
+pub(super) fn cmp(a: &[Digit], b: &[Digit]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+/// Number of bits, without the leading zeros
+pub(super) fn count_bits(a: &[Digit]) -> usize {
+    match normalized_len(a) {
+        0 => 0,
+        n => (n - 1) * DIGIT_BITS as usize + (DIGIT_BITS - a[n - 1].leading_zeros()) as usize,
+    }
+}
+
+/// Adds `b` to `acc` (with at least as many digits as `b`), returns the carry
+pub(super) fn add_assign(acc: &mut [Digit], b: &[Digit]) -> Digit {
+    let mut carry = false;
+    for (i, x) in acc.iter_mut().enumerate() {
+        if i >= b.len() && !carry {
+            break;
+        }
+        let y = b.get(i).copied().unwrap_or(0);
+        let (s1, c1) = x.overflowing_add(y);
+        let (s2, c2) = s1.overflowing_add(carry as Digit);
+        *x = s2;
+        carry = c1 || c2;
+    }
+    carry as Digit
+}
+
+/// `acc = acc * m + a`, returns the carry digit
+pub(super) fn mul_add_digit(acc: &mut [Digit], m: Digit, a: Digit) -> Digit {
+    let m = DoubleDigit::from(m);
+    let mut carry = DoubleDigit::from(a);
+    for x in acc.iter_mut() {
+        let t = DoubleDigit::from(*x) * m + carry;
+        *x = t as Digit;
+        carry = t >> DIGIT_BITS;
+    }
+    carry as Digit
+}
+
+/// Subtracts `b` from `acc` (with at least as many digits as `b`), returns the borrow
+fn sub_assign(acc: &mut [Digit], b: &[Digit]) -> Digit {
+    let mut borrow = false;
+    for (i, x) in acc.iter_mut().enumerate() {
+        if i >= b.len() && !borrow {
+            break;
+        }
+        let y = b.get(i).copied().unwrap_or(0);
+        let (d1, b1) = x.overflowing_sub(y);
+        let (d2, b2) = d1.overflowing_sub(borrow as Digit);
+        *x = d2;
+        borrow = b1 || b2;
+    }
+    borrow as Digit
+}
+
+/// `out = a + b`, `out` has `max(a.len(), b.len()) + 1` digits
+pub(super) fn add(a: &[Digit], b: &[Digit], out: &mut [Digit]) {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    debug_assert_eq!(out.len(), long.len() + 1);
+    out[..long.len()].copy_from_slice(long);
+    out[long.len()] = add_assign(&mut out[..long.len()], short);
+}
+
+/// `out = a - b` for `a >= b`, `out` has `a.len()` digits
+pub(super) fn sub(a: &[Digit], b: &[Digit], out: &mut [Digit]) {
+    debug_assert_eq!(out.len(), a.len());
+    out.copy_from_slice(a);
+    let borrow = sub_assign(out, b);
+    debug_assert_eq!(borrow, 0);
+}
+
+/// `out = a * b`, `out` has `a.len() + b.len()` digits
+pub(super) unsafe fn mul(a: &[Digit], b: &[Digit], out: &mut [Digit]) {
+    debug_assert_eq!(out.len(), a.len() + b.len());
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+
+    if short.len() < KARATSUBA_CUTOFF {
+        mul_schoolbook(short, long, out);
+    } else if long.len() >= 2 * short.len() {
+        // Multiply pieces of the long operand of the size of the short one, so that Karatsuba's
+        // method splits balanced operands
+        for d in out.iter_mut() {
+            *d = 0;
+        }
+        let product = alloc_digits(2 * short.len());
+        for (i, piece) in long.chunks(short.len()).enumerate() {
+            let product = &mut product[..piece.len() + short.len()];
+            mul(piece, short, product);
+            let carry = add_assign(&mut out[i * short.len()..], product);
+            debug_assert_eq!(carry, 0);
+        }
+    } else {
+        mul_karatsuba(short, long, out);
+    }
+}
+
+fn mul_schoolbook(a: &[Digit], b: &[Digit], out: &mut [Digit]) {
+    for d in out.iter_mut() {
+        *d = 0;
+    }
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry: DoubleDigit = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let t =
+                DoubleDigit::from(x) * DoubleDigit::from(y) + DoubleDigit::from(out[i + j]) + carry;
+            out[i + j] = t as Digit;
+            carry = t >> DIGIT_BITS;
+        }
+        out[i + b.len()] = carry as Digit;
+    }
+}
+
+/// Karatsuba's method, for `a.len() <= b.len() < 2 * a.len()`: with `x = x1 * B^m + x0`,
+/// `a * b = z2 * B^2m + z1 * B^m + z0` where `z2 = a1 * b1`, `z0 = a0 * b0`, and
+/// `z1 = (a1 + a0) * (b1 + b0) - z2 - z0`
+unsafe fn mul_karatsuba(a: &[Digit], b: &[Digit], out: &mut [Digit]) {
+    let m = b.len() / 2;
+    let (a0, a1) = a.split_at(m);
+    let (b0, b1) = b.split_at(m);
+
+    // z0 and z2 go to the low and high digits of the result
+    {
+        let (low, high) = out.split_at_mut(2 * m);
+        mul(a0, b0, low);
+        mul(a1, b1, high);
+    }
+
+    let sum_a = alloc_digits(max(a0.len(), a1.len()) + 1);
+    add(a1, a0, sum_a);
+    let sum_b = alloc_digits(max(b0.len(), b1.len()) + 1);
+    add(b1, b0, sum_b);
+
+    let z1 = alloc_digits(sum_a.len() + sum_b.len());
+    mul(sum_a, sum_b, z1);
+    let (z0, z2) = out.split_at(2 * m);
+    let borrow =
+        sub_assign(z1, &z0[..normalized_len(z0)]) + sub_assign(z1, &z2[..normalized_len(z2)]);
+    debug_assert_eq!(borrow, 0);
+
+    let z1_len = normalized_len(z1);
+    let carry = add_assign(&mut out[m..], &z1[..z1_len]);
+    debug_assert_eq!(carry, 0);
+}
+
+/// `q = a / b` and `r = a % b` for normalized `a` and `b` with `a.len() >= b.len() > 0`, `q` has
+/// `a.len() - b.len() + 1` digits and `r` has `b.len()` digits
+///
+/// Algorithm D of Knuth, The Art of Computer Programming, Vol. 2, 4.3.1
+pub(super) unsafe fn div_rem(a: &[Digit], b: &[Digit], q: &mut [Digit], r: &mut [Digit]) {
+    let n = b.len();
+    debug_assert!(n > 0 && b[n - 1] != 0 && a.len() >= n);
+    debug_assert_eq!(q.len(), a.len() - n + 1);
+    debug_assert_eq!(r.len(), n);
+
+    if n == 1 {
+        r[0] = div_rem_digit(a, b[0], q);
+        return;
+    }
+
+    // Normalize, so that the top digit of the divisor has its top bit set
+    let shift = b[n - 1].leading_zeros();
+    let bn = alloc_digits(n);
+    shl_bits(b, shift, bn);
+    let an = alloc_digits(a.len() + 1);
+    an[a.len()] = shl_bits(a, shift, &mut an[..a.len()]);
+
+    let base: DoubleDigit = 1 << DIGIT_BITS;
+    let top = DoubleDigit::from(bn[n - 1]);
+    let second = DoubleDigit::from(bn[n - 2]);
+
+    for j in (0..q.len()).rev() {
+        // Estimate the quotient digit from the top digits, it's at most two too large
+        let num = DoubleDigit::from(an[j + n]) << DIGIT_BITS | DoubleDigit::from(an[j + n - 1]);
+        let mut qhat = num / top;
+        let mut rhat = num % top;
+        while qhat >= base
+            || qhat * second > (rhat << DIGIT_BITS | DoubleDigit::from(an[j + n - 2]))
+        {
+            qhat -= 1;
+            rhat += top;
+            if rhat >= base {
+                break;
+            }
+        }
+
+        // Subtract qhat * bn from the current digits
+        let mut carry: DoubleDigit = 0;
+        let mut borrow = false;
+        for i in 0..=n {
+            let p = qhat * DoubleDigit::from(bn.get(i).copied().unwrap_or(0)) + carry;
+            carry = p >> DIGIT_BITS;
+            let (d1, b1) = an[j + i].overflowing_sub(p as Digit);
+            let (d2, b2) = d1.overflowing_sub(borrow as Digit);
+            an[j + i] = d2;
+            borrow = b1 || b2;
+        }
+
+        // The estimate was one too large, add back
+        if borrow {
+            qhat -= 1;
+            let carry = add_assign(&mut an[j..j + n], bn);
+            an[j + n] = an[j + n].wrapping_add(carry);
+        }
+
+        q[j] = qhat as Digit;
+    }
+
+    // The remainder is in the low digits, denormalize
+    for i in 0..n {
+        r[i] = if shift == 0 {
+            an[i]
+        } else {
+            an[i] >> shift | an[i + 1] << (DIGIT_BITS - shift)
+        };
+    }
+}
+
+/// `q = a / d`, returns `a % d`, `q` has `a.len()` digits
+fn div_rem_digit(a: &[Digit], d: Digit, q: &mut [Digit]) -> Digit {
+    let d = DoubleDigit::from(d);
+    let mut rem: DoubleDigit = 0;
+    for i in (0..a.len()).rev() {
+        let num = rem << DIGIT_BITS | DoubleDigit::from(a[i]);
+        q[i] = (num / d) as Digit;
+        rem = num % d;
+    }
+    rem as Digit
+}
+
+/// `out = a << shift` for `shift < DIGIT_BITS`, `out` has `a.len()` digits, returns the bits
+/// shifted out
+fn shl_bits(a: &[Digit], shift: u32, out: &mut [Digit]) -> Digit {
+    if shift == 0 {
+        out.copy_from_slice(a);
+        return 0;
+    }
+    let mut carry = 0;
+    for (x, y) in a.iter().zip(out.iter_mut()) {
+        *y = x << shift | carry;
+        carry = x >> (DIGIT_BITS - shift);
+    }
+    carry
+}
+
+/// `out = a << shift`, `out` has `a.len() + shift / DIGIT_BITS + 1` digits
+pub(super) fn shl(a: &[Digit], shift: usize, out: &mut [Digit]) {
+    let digits = shift / DIGIT_BITS as usize;
+    debug_assert_eq!(out.len(), a.len() + digits + 1);
+    let (low, high) = out.split_at_mut(digits);
+    for d in low.iter_mut() {
+        *d = 0;
+    }
+    high[a.len()] = shl_bits(
+        a,
+        (shift % DIGIT_BITS as usize) as u32,
+        &mut high[..a.len()],
+    );
+}
+
+/// `out = a >> shift`, `out` has `a.len() - shift / DIGIT_BITS` digits (none when the shift is
+/// larger than `a`), returns whether any non-zero bits were shifted out
+pub(super) fn shr(a: &[Digit], shift: usize, out: &mut [Digit]) -> bool {
+    let digits = min(shift / DIGIT_BITS as usize, a.len());
+    let bits = (shift % DIGIT_BITS as usize) as u32;
+    debug_assert_eq!(out.len(), a.len() - digits);
+    let (low, high) = a.split_at(digits);
+    let mut lost = low.iter().any(|&d| d != 0);
+
+    if bits == 0 {
+        out.copy_from_slice(high);
+        return lost;
+    }
+
+    if let Some(&d) = high.first() {
+        lost |= d << (DIGIT_BITS - bits) != 0;
+    }
+    for (i, y) in out.iter_mut().enumerate() {
+        let next = high.get(i + 1).copied().unwrap_or(0);
+        *y = high[i] >> bits | next << (DIGIT_BITS - bits);
+    }
+    lost
+}
+
+/// `a = -a` modulo `B^a.len()`, i.e. converts between a magnitude and its two's complement
+pub(super) fn negate(a: &mut [Digit]) {
+    let mut carry = true;
+    for d in a.iter_mut() {
+        let (s, c) = (!*d).overflowing_add(carry as Digit);
+        *d = s;
+        carry = c;
+    }
+}
+
+/// Number of trailing zero bits of a non-zero magnitude
+fn trailing_zeros(a: &[Digit]) -> usize {
+    let i = a.iter().position(|&d| d != 0).unwrap();
+    i * DIGIT_BITS as usize + a[i].trailing_zeros() as usize
+}
+
+/// Shifts the non-zero `a` right in place until it is odd, returns the digits of the result
+fn make_odd(a: &mut [Digit]) -> &mut [Digit] {
+    let shift = trailing_zeros(a);
+    let digits = shift / DIGIT_BITS as usize;
+    let bits = (shift % DIGIT_BITS as usize) as u32;
+    a.copy_within(digits.., 0);
+    let len = a.len() - digits;
+    if bits != 0 {
+        for i in 0..len {
+            let next = if i + 1 < len { a[i + 1] } else { 0 };
+            a[i] = a[i] >> bits | next << (DIGIT_BITS - bits);
+        }
+    }
+    let len = normalized_len(&a[..len]);
+    &mut a[..len]
+}
+
+/// The greatest common divisor of the non-zero `a` and `b` with binary GCD, as its odd part and
+/// the exponent of its factor two. Computed in place, overwriting `a` and `b`, the odd part is
+/// in one of them.
+pub(super) fn gcd<'a>(a: &'a mut [Digit], b: &'a mut [Digit]) -> (&'a [Digit], usize) {
+    let twos = min(trailing_zeros(a), trailing_zeros(b));
+    let mut a = make_odd(a);
+    let mut b = make_odd(b);
+
+    // Both are odd, so the difference is even and the odd part of the difference has the same
+    // common divisors
+    loop {
+        if cmp(a, b) == Ordering::Less {
+            core::mem::swap(&mut a, &mut b);
+        }
+        sub_assign(a, b);
+        if normalized_len(a) == 0 {
+            return (b, twos);
+        }
+        a = make_odd(core::mem::take(&mut a));
+    }
+}
@@ -0,0 +1,110 @@
+//! Parsing of big integers from text, in the syntax of literals
+
+use super::mag::{self, Digit};
+use super::{alloc_bigint, digits_mut, finish};
+
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::text::text_str;
+use crate::types::SkewedPtr;
+
+/// The value of the character as a digit, when it is less than the radix
+fn digit_value(c: u8, radix: u32) -> Option<u32> {
+    let value = match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'z' => c - b'a' + 10,
+        b'A'..=b'Z' => c - b'A' + 10,
+        _ => return None,
+    };
+    Some(u32::from(value)).filter(|&value| value < radix)
+}
+
+/// The radix of the prefix (`0x`, `0o` or `0b`, in either case)
+fn prefix_radix(s: &[u8]) -> Option<u32> {
+    match s {
+        [b'0', b'x', ..] | [b'0', b'X', ..] => Some(16),
+        [b'0', b'o', ..] | [b'0', b'O', ..] => Some(8),
+        [b'0', b'b', ..] | [b'0', b'B', ..] => Some(2),
+        _ => None,
+    }
+}
+
+/// Parses the text as an integer in the radix, as in literals: an optional sign, an optional
+/// prefix (`0x`, `0o` or `0b`, only when it matches the radix), and digits with single `_` between
+/// them. Radix 0 is decimal unless there is a prefix. Letters are digits in either case. Returns
+/// the (boxed) number, or 0 when the text is not of this form. Traps when the radix is not 0 or in
+/// `2..=36`.
+#[no_mangle]
+pub unsafe extern "C" fn bigint_of_text_radix(text: SkewedPtr, radix: u32) -> SkewedPtr {
+    if radix == 1 || radix > 36 {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "bigint_of_text_radix: Invalid radix",
+        );
+    }
+
+    let mut s = text_str(text).as_bytes();
+
+    let negative = s.first() == Some(&b'-');
+    if matches!(s.first(), Some(b'-') | Some(b'+')) {
+        s = &s[1..];
+    }
+
+    let radix = match prefix_radix(s) {
+        Some(r) if radix == 0 || radix == r => {
+            s = &s[2..];
+            r
+        }
+        _ if radix == 0 => 10,
+        _ => radix,
+    };
+
+    // Digits and separators, not starting or ending with a separator, no two separators in a row
+    if s.is_empty()
+        || s[0] == b'_'
+        || s[s.len() - 1] == b'_'
+        || s.windows(2).any(|w| w == b"__")
+        || !s
+            .iter()
+            .all(|&c| c == b'_' || digit_value(c, radix).is_some())
+    {
+        return SkewedPtr(0);
+    }
+
+    // Enough digits for the bits of all characters
+    let bits_per_char = 32 - (radix - 1).leading_zeros() as usize;
+    let r = alloc_bigint((s.len() * bits_per_char + 31) / 32 + 1);
+    let ds = digits_mut(r);
+
+    // Digits are added in chunks, `chunk` is the value of the last digits, `scale` is `radix` to the
+    // power of their number
+    let mut len = 0;
+    let mut chunk: Digit = 0;
+    let mut scale: Digit = 1;
+    for &c in s {
+        if let Some(value) = digit_value(c, radix) {
+            if scale.checked_mul(radix).is_none() {
+                len = mul_add(ds, len, scale, chunk);
+                chunk = 0;
+                scale = 1;
+            }
+            chunk = chunk * radix + value;
+            scale *= radix;
+        }
+    }
+    len = mul_add(ds, len, scale, chunk);
+    ds[len..].iter_mut().for_each(|d| *d = 0);
+
+    finish(r, negative)
+}
+
+/// `ds[..len] * m + a`, in place, returns the new length
+fn mul_add(ds: &mut [Digit], len: usize, m: Digit, a: Digit) -> usize {
+    let carry = mag::mul_add_digit(&mut ds[..len], m, a);
+    if carry == 0 {
+        len
+    } else {
+        ds[len] = carry;
+        len + 1
+    }
+}
@@ -1,8 +1,8 @@
-use crate::alloc::alloc_words;
+use crate::alloc::{alloc_words, write_barrier};
 use crate::types::{size_of, Array, Bytes, SkewedPtr, Words, TAG_ARRAY};
 
-const ITER_BLOB_IDX: u32 = 0;
-const ITER_POS_IDX: u32 = 1;
+const ITER_BLOB_IDX: usize = 0;
+const ITER_POS_IDX: usize = 1;
 
 /// Returns iterator for the given blob
 #[no_mangle]
@@ -25,7 +25,7 @@ unsafe extern "C" fn blob_iter_done(iter: SkewedPtr) -> u32 {
     let iter_array = iter.as_array();
 
     let blob = iter_array.get(ITER_BLOB_IDX);
-    let pos = Bytes((iter_array.get(ITER_POS_IDX).0 >> 2) as u32);
+    let pos = Bytes(iter_array.get(ITER_POS_IDX).0 >> 2);
 
     (pos >= blob.as_blob().len()).into()
 }
@@ -36,9 +36,10 @@ unsafe extern "C" fn blob_iter_next(iter: SkewedPtr) -> u32 {
     let iter_array = iter.as_array();
 
     let blob = iter_array.get(ITER_BLOB_IDX);
-    let pos = (iter_array.get(ITER_POS_IDX).0 >> 2) as u32;
+    let pos = iter_array.get(ITER_POS_IDX).0 >> 2;
 
-    iter_array.set(ITER_POS_IDX, SkewedPtr(((pos + 1) << 2) as usize));
+    write_barrier(iter_array.payload_addr().add(ITER_POS_IDX));
+    iter_array.set(ITER_POS_IDX, SkewedPtr((pos + 1) << 2));
 
     blob.as_blob().get(pos).into()
 }
@@ -1,6 +1,15 @@
-//! This module implements a simple buffer to be used by the compiler (in generated code)
+//! This module implements a simple buffer to be used by the compiler (in generated code), and a
+//! buffer reading from stable memory in chunks
 
+use crate::alloc::alloc_blob;
+use crate::error_code::ErrorCode;
 use crate::idl::idl_trap_with;
+use crate::region::{region_read, stable_vars_read, STABLE_VARS};
+use crate::rts_trap_with;
+use crate::stream::CHUNK_SIZE;
+use crate::types::{Bytes, SkewedPtr, WORD_SIZE};
+
+use core::mem::size_of;
 
 #[repr(packed)]
 pub struct Buf {
@@ -43,7 +52,9 @@ pub(crate) unsafe fn read_word(buf: *mut Buf) -> u32 {
 }
 
 unsafe fn advance(buf: *mut Buf, n: u32) {
-    if (*buf).ptr.add(n as usize) > (*buf).end {
+    // Compared with the remaining length, as a large `n` (like the length of a future value) could
+    // wrap around the address space
+    if n as usize > (*buf).end as usize - (*buf).ptr as usize {
         idl_trap_with("advance out of buffer");
     }
 
@@ -60,3 +71,120 @@ pub(crate) unsafe extern "C" fn skip_leb128(buf: *mut Buf) {
         }
     }
 }
+
+/// A buffer reading `len` bytes from an offset of a region of stable memory (see `region.rs`) in
+/// chunks of `CHUNK_SIZE`, as every read from stable memory is a system call, so a large encoding
+/// can be decoded without copying it to the heap first. Region `STABLE_VARS` is the encoding of
+/// the stable variables, after its size word, in either layout of stable memory (see
+/// `stable_vars_read`).
+///
+/// Packed, as it's also a heap object, for the generated code (see `region_buf_new`).
+#[repr(packed)]
+pub struct StableBuf {
+    region: u32,
+    /// Offset in the region of the next byte after the buffered bytes
+    offset: u64,
+    /// Offset in the region of the end of the bytes to read
+    end: u64,
+    buf: [u8; CHUNK_SIZE],
+    /// Position of the next byte in `buf`
+    pos: usize,
+    /// Number of bytes in `buf`
+    len: usize,
+}
+
+impl StableBuf {
+    pub fn new(region: u32, offset: u64, len: u64) -> Self {
+        StableBuf {
+            region,
+            offset,
+            end: offset + len,
+            buf: [0; CHUNK_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Number of bytes left to read
+    pub fn remaining(&self) -> u64 {
+        self.end - self.offset + (self.len - self.pos) as u64
+    }
+
+    /// Copies the next `len` bytes to `dst`
+    pub unsafe fn read(&mut self, dst: *mut u8, len: usize) {
+        let buffered = core::cmp::min(len, self.len - self.pos);
+        core::ptr::copy_nonoverlapping(self.buf.as_ptr().add(self.pos), dst, buffered);
+        self.pos += buffered;
+        let rest = len - buffered;
+        if rest == 0 {
+            return;
+        }
+
+        if rest as u64 > self.end - self.offset {
+            rts_trap_with(ErrorCode::OutOfBounds, "read out of stable memory buffer");
+        }
+        if rest >= CHUNK_SIZE {
+            // Large reads (of objects and blobs) are not copied to the buffer
+            self.read_through(dst.add(buffered), rest);
+        } else {
+            self.len = core::cmp::min(CHUNK_SIZE as u64, self.end - self.offset) as usize;
+            let buf = self.buf.as_mut_ptr();
+            self.read_through(buf, self.len);
+            core::ptr::copy_nonoverlapping(self.buf.as_ptr(), dst.add(buffered), rest);
+            self.pos = rest;
+        }
+    }
+
+    pub unsafe fn read_byte(&mut self) -> u8 {
+        let mut byte = 0u8;
+        self.read(&mut byte, 1);
+        byte
+    }
+
+    /// Reads a little-endian machine word
+    pub unsafe fn read_word(&mut self) -> usize {
+        let mut word = 0usize;
+        self.read(&mut word as *mut usize as *mut u8, WORD_SIZE);
+        word
+    }
+
+    unsafe fn read_through(&mut self, dst: *mut u8, len: usize) {
+        if self.region == STABLE_VARS {
+            stable_vars_read(dst, self.offset as u32, len as u32);
+        } else {
+            region_read(self.region, self.offset, dst, len as u32);
+        }
+        self.offset += len as u64;
+    }
+}
+
+// Buffers for the generated code, for decoding data the program wrote to a region (see
+// `regionBuffer` in `prim.mo`). Like the streams of `stream.rs`, the buffer is the payload of a
+// blob, which the GC can move.
+
+unsafe fn buf_of_blob(blob: SkewedPtr) -> *mut StableBuf {
+    blob.as_blob().payload_addr() as *mut StableBuf
+}
+
+/// A buffer reading `len` bytes from `offset` of the region, in a blob
+#[no_mangle]
+pub unsafe extern "C" fn region_buf_new(region: u32, offset: u64, len: u64) -> SkewedPtr {
+    let blob = alloc_blob(Bytes(size_of::<StableBuf>()));
+    core::ptr::write(buf_of_blob(blob), StableBuf::new(region, offset, len));
+    blob
+}
+
+/// A blob with the next `len` bytes of the buffer
+#[no_mangle]
+pub unsafe extern "C" fn region_buf_read(buf: SkewedPtr, len: u32) -> SkewedPtr {
+    // Allocated first, as allocation can move the buffer
+    let blob = alloc_blob(Bytes(len as usize));
+    (*buf_of_blob(buf)).read(blob.as_blob().payload_addr(), len as usize);
+    blob
+}
+
+/// Number of bytes left to read in the buffer
+#[no_mangle]
+pub unsafe extern "C" fn region_buf_remaining(buf: SkewedPtr) -> u64 {
+    (*buf_of_blob(buf)).remaining()
+}
@@ -1,39 +1,195 @@
-#[no_mangle]
-unsafe extern "C" fn char_to_upper(c: u32) -> u32 {
-    let mut upper_chars = core::char::from_u32_unchecked(c).to_uppercase();
-    if upper_chars.len() == 1 {
-        upper_chars.next().unwrap() as u32
-    } else {
-        c
+//! Properties of characters. The general category, the alphabetic, case, numeric and white space
+//! properties are looked up in the generated `char_tables.rs`, of the same Unicode version as the
+//! grapheme clusters and the case mappings.
+//!
+//! Case conversions of characters map a character to a single character, with the simple case
+//! mappings and simple case folding of Unicode (in the generated `unicode_tables.rs`, with the
+//! normalization tables). Where the full mapping has several characters, the simple one is
+//! usually the character itself (e.g. 'ß' stays 'ß' in uppercase, as "SS" is two characters), see
+//! `text_to_upper` for the full mappings. Folding maps the characters that only differ in case to
+//! the same character, e.g. 'ſ', 's' and 'S' to 's', and 'ẞ' and 'ß' to 'ß'.
+//!
+//! The properties of a range of characters are the number of its general category in the low 5
+//! bits, and the flags `WHITE_SPACE`, `ALPHABETIC`, `UPPERCASE_FLAG`, `LOWERCASE_FLAG` and
+//! `CASE_IGNORABLE`. The general categories are numbered in the order of the Unicode Standard
+//! (as in `prim.mo`):
+//!
+//! ```text
+//! 0  Lu   1  Ll   2  Lt   3  Lm   4  Lo   5  Mn   6  Mc   7  Me   8  Nd   9  Nl
+//! 10 No   11 Pc   12 Pd   13 Ps   14 Pe   15 Pi   16 Pf   17 Po   18 Sm   19 Sc
+//! 20 Sk   21 So   22 Zs   23 Zl   24 Zp   25 Cc   26 Cf   27 Cs   28 Co   29 Cn
+//! ```
+
+use crate::char_tables::CHAR_PROPERTIES;
+use crate::unicode_tables::{CASE_FOLDING, FULL_LOWERCASE, FULL_UPPERCASE, LOWERCASE, UPPERCASE};
+
+const CATEGORY_MASK: u32 = 0x1F;
+const WHITE_SPACE: u32 = 0x20;
+const ALPHABETIC: u32 = 0x40;
+const UPPERCASE_FLAG: u32 = 0x80;
+const LOWERCASE_FLAG: u32 = 0x100;
+const CASE_IGNORABLE: u32 = 0x200;
+
+/// Bits of the properties in the ranges of `CHAR_PROPERTIES`, below the start of the range
+const PROPERTY_BITS: u32 = 10;
+
+/// General categories of the numeric characters
+const ND: u32 = 8;
+const NL: u32 = 9;
+const NO: u32 = 10;
+
+/// General category of titlecase letters, which are cased but neither uppercase nor lowercase
+const LT: u32 = 2;
+
+/// General category of unassigned code points
+const CN: u32 = 29;
+
+/// The properties of the character, of the range in `CHAR_PROPERTIES` that it is in
+fn properties(c: u32) -> u32 {
+    if c > 0x10FFFF {
+        return CN;
     }
+    let i = match CHAR_PROPERTIES.binary_search_by_key(&c, |range| range >> PROPERTY_BITS) {
+        Ok(i) => i,
+        Err(i) => i - 1,
+    };
+    CHAR_PROPERTIES[i] & ((1 << PROPERTY_BITS) - 1)
 }
 
-#[no_mangle]
-unsafe extern "C" fn char_to_lower(c: u32) -> u32 {
-    let mut lower_chars = core::char::from_u32_unchecked(c).to_lowercase();
-    if lower_chars.len() == 1 {
-        lower_chars.next().unwrap() as u32
+/// The character that `c` maps to, in a table of runs `(first, last, delta, stride)`
+fn case_map(table: &[(u32, u32, i32, u8)], c: u32) -> u32 {
+    let i = match table.binary_search_by_key(&c, |run| run.0) {
+        Ok(i) => i,
+        Err(0) => return c,
+        Err(i) => i - 1,
+    };
+    let (first, last, delta, stride) = table[i];
+    if c <= last && (c - first) % u32::from(stride) == 0 {
+        (c as i32 + delta) as u32
     } else {
         c
     }
 }
 
+/// The characters of a full case mapping, at most three
+#[derive(Clone)]
+pub(crate) struct CaseMapping {
+    chars: [u32; 3],
+    len: usize,
+    next: usize,
+}
+
+impl CaseMapping {
+    pub(crate) fn single(c: u32) -> CaseMapping {
+        CaseMapping {
+            chars: [c, 0, 0],
+            len: 1,
+            next: 0,
+        }
+    }
+}
+
+impl Iterator for CaseMapping {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.next == self.len {
+            return None;
+        }
+        self.next += 1;
+        Some(unsafe { core::char::from_u32_unchecked(self.chars[self.next - 1]) })
+    }
+}
+
+/// The full case mapping of `c`, its entry in the table of full mappings `(char, first, second,
+/// third)` or else its simple mapping
+fn full_case_map(
+    full: &[(u32, u32, u32, u32)],
+    simple: &[(u32, u32, i32, u8)],
+    c: u32,
+) -> CaseMapping {
+    match full.binary_search_by_key(&c, |mapping| mapping.0) {
+        Ok(i) => {
+            let (_, first, second, third) = full[i];
+            let chars = [first, second, third];
+            CaseMapping {
+                chars,
+                len: chars.iter().take_while(|&&c| c != 0).count(),
+                next: 0,
+            }
+        }
+        Err(_) => CaseMapping::single(case_map(simple, c)),
+    }
+}
+
+/// The full uppercase mapping, without the conditional mappings of `SpecialCasing.txt`
+pub(crate) fn to_upper_full(c: char) -> CaseMapping {
+    full_case_map(&FULL_UPPERCASE, &UPPERCASE, c as u32)
+}
+
+/// The full lowercase mapping, without the conditional mappings of `SpecialCasing.txt` (see
+/// `text_to_lower` for the final sigma)
+pub(crate) fn to_lower_full(c: char) -> CaseMapping {
+    full_case_map(&FULL_LOWERCASE, &LOWERCASE, c as u32)
+}
+
+#[no_mangle]
+pub extern "C" fn char_to_upper(c: u32) -> u32 {
+    case_map(&UPPERCASE, c)
+}
+
+#[no_mangle]
+pub extern "C" fn char_to_lower(c: u32) -> u32 {
+    case_map(&LOWERCASE, c)
+}
+
+/// The simple case folding, for comparisons that ignore case
 #[no_mangle]
-unsafe extern "C" fn char_is_whitespace(c: u32) -> u32 {
-    core::char::from_u32_unchecked(c).is_whitespace().into()
+pub extern "C" fn char_fold_case(c: u32) -> u32 {
+    case_map(&CASE_FOLDING, c)
+}
+
+#[no_mangle]
+pub extern "C" fn char_is_whitespace(c: u32) -> u32 {
+    (properties(c) & WHITE_SPACE != 0).into()
+}
+
+#[no_mangle]
+pub extern "C" fn char_is_uppercase(c: u32) -> u32 {
+    (properties(c) & UPPERCASE_FLAG != 0).into()
+}
+
+#[no_mangle]
+pub extern "C" fn char_is_lowercase(c: u32) -> u32 {
+    (properties(c) & LOWERCASE_FLAG != 0).into()
+}
+
+/// Whether the character is cased: uppercase, lowercase or a titlecase letter
+pub(crate) fn char_is_cased(c: char) -> bool {
+    let props = properties(c as u32);
+    props & (UPPERCASE_FLAG | LOWERCASE_FLAG) != 0 || props & CATEGORY_MASK == LT
+}
+
+/// Whether the character is ignored when looking for the cased characters around a character,
+/// e.g. apostrophes and combining marks
+pub(crate) fn char_is_case_ignorable(c: char) -> bool {
+    properties(c as u32) & CASE_IGNORABLE != 0
 }
 
 #[no_mangle]
-unsafe extern "C" fn char_is_uppercase(c: u32) -> u32 {
-    core::char::from_u32_unchecked(c).is_uppercase().into()
+pub extern "C" fn char_is_alphabetic(c: u32) -> u32 {
+    (properties(c) & ALPHABETIC != 0).into()
 }
 
+/// Whether the character is a number (of the categories Nd, Nl or No), as `char::is_numeric`
 #[no_mangle]
-unsafe extern "C" fn char_is_lowercase(c: u32) -> u32 {
-    core::char::from_u32_unchecked(c).is_lowercase().into()
+pub extern "C" fn char_is_numeric(c: u32) -> u32 {
+    let category = properties(c) & CATEGORY_MASK;
+    (category == ND || category == NL || category == NO).into()
 }
 
+/// The number of the general category of the character, see the module documentation
 #[no_mangle]
-unsafe extern "C" fn char_is_alphabetic(c: u32) -> u32 {
-    core::char::from_u32_unchecked(c).is_alphabetic().into()
+pub extern "C" fn char_general_category(c: u32) -> u32 {
+    properties(c) & CATEGORY_MASK
 }
@@ -0,0 +1,540 @@
+//! Unicode 17.0.0 tables of the properties of characters (see `char.rs`).
+//!
+//! Generated by `rts/gen-char-tables.py`, do not edit.
+
+/// Ranges of the characters with the same properties, up to the start of the next range,
+/// as `start << 10 | properties`. The properties are encoded as in `char.rs`.
+#[rustfmt::skip]
+pub(crate) static CHAR_PROPERTIES: [u32; 4245] = [
+    0x00000019, 0x00002439, 0x00003819, 0x00008036, 0x00008411, 0x00009013, 0x00009411, 0x00009E11,
+    0x0000A00D, 0x0000A40E, 0x0000A811, 0x0000AC12, 0x0000B011, 0x0000B40C, 0x0000BA11, 0x0000BC11,
+    0x0000C008, 0x0000EA11, 0x0000EC11, 0x0000F012, 0x0000FC11, 0x000104C0, 0x00016C0D, 0x00017011,
+    0x0001740E, 0x00017A14, 0x00017C0B, 0x00018214, 0x00018541, 0x0001EC0D, 0x0001F012, 0x0001F40E,
+    0x0001F812, 0x0001FC19, 0x00021439, 0x00021819, 0x00028036, 0x00028411, 0x00028813, 0x00029815,
+    0x00029C11, 0x0002A214, 0x0002A415, 0x0002A944, 0x0002AC0F, 0x0002B012, 0x0002B61A, 0x0002B815,
+    0x0002BE14, 0x0002C015, 0x0002C412, 0x0002C80A, 0x0002D214, 0x0002D541, 0x0002D811, 0x0002DE11,
+    0x0002E214, 0x0002E40A, 0x0002E944, 0x0002EC10, 0x0002F00A, 0x0002FC11, 0x000300C0, 0x00035C12,
+    0x000360C0, 0x00037D41, 0x0003DC12, 0x0003E141, 0x000400C0, 0x00040541, 0x000408C0, 0x00040D41,
+    0x000410C0, 0x00041541, 0x000418C0, 0x00041D41, 0x000420C0, 0x00042541, 0x000428C0, 0x00042D41,
+    0x000430C0, 0x00043541, 0x000438C0, 0x00043D41, 0x000440C0, 0x00044541, 0x000448C0, 0x00044D41,
+    0x000450C0, 0x00045541, 0x000458C0, 0x00045D41, 0x000460C0, 0x00046541, 0x000468C0, 0x00046D41,
+    0x000470C0, 0x00047541, 0x000478C0, 0x00047D41, 0x000480C0, 0x00048541, 0x000488C0, 0x00048D41,
+    0x000490C0, 0x00049541, 0x000498C0, 0x00049D41, 0x0004A0C0, 0x0004A541, 0x0004A8C0, 0x0004AD41,
+    0x0004B0C0, 0x0004B541, 0x0004B8C0, 0x0004BD41, 0x0004C0C0, 0x0004C541, 0x0004C8C0, 0x0004CD41,
+    0x0004D0C0, 0x0004D541, 0x0004D8C0, 0x0004DD41, 0x0004E4C0, 0x0004E941, 0x0004ECC0, 0x0004F141,
+    0x0004F4C0, 0x0004F941, 0x0004FCC0, 0x00050141, 0x000504C0, 0x00050941, 0x00050CC0, 0x00051141,
+    0x000514C0, 0x00051941, 0x00051CC0, 0x00052141, 0x000528C0, 0x00052D41, 0x000530C0, 0x00053541,
+    0x000538C0, 0x00053D41, 0x000540C0, 0x00054541, 0x000548C0, 0x00054D41, 0x000550C0, 0x00055541,
+    0x000558C0, 0x00055D41, 0x000560C0, 0x00056541, 0x000568C0, 0x00056D41, 0x000570C0, 0x00057541,
+    0x000578C0, 0x00057D41, 0x000580C0, 0x00058541, 0x000588C0, 0x00058D41, 0x000590C0, 0x00059541,
+    0x000598C0, 0x00059D41, 0x0005A0C0, 0x0005A541, 0x0005A8C0, 0x0005AD41, 0x0005B0C0, 0x0005B541,
+    0x0005B8C0, 0x0005BD41, 0x0005C0C0, 0x0005C541, 0x0005C8C0, 0x0005CD41, 0x0005D0C0, 0x0005D541,
+    0x0005D8C0, 0x0005DD41, 0x0005E0C0, 0x0005E941, 0x0005ECC0, 0x0005F141, 0x0005F4C0, 0x0005F941,
+    0x000604C0, 0x00060D41, 0x000610C0, 0x00061541, 0x000618C0, 0x00062141, 0x000624C0, 0x00063141,
+    0x000638C0, 0x00064941, 0x00064CC0, 0x00065541, 0x000658C0, 0x00066541, 0x000670C0, 0x00067941,
+    0x00067CC0, 0x00068541, 0x000688C0, 0x00068D41, 0x000690C0, 0x00069541, 0x000698C0, 0x0006A141,
+    0x0006A4C0, 0x0006A941, 0x0006B0C0, 0x0006B541, 0x0006B8C0, 0x0006C141, 0x0006C4C0, 0x0006D141,
+    0x0006D4C0, 0x0006D941, 0x0006DCC0, 0x0006E541, 0x0006EC44, 0x0006F0C0, 0x0006F541, 0x00070044,
+    0x000710C0, 0x00071442, 0x00071941, 0x00071CC0, 0x00072042, 0x00072541, 0x000728C0, 0x00072C42,
+    0x00073141, 0x000734C0, 0x00073941, 0x00073CC0, 0x00074141, 0x000744C0, 0x00074941, 0x00074CC0,
+    0x00075141, 0x000754C0, 0x00075941, 0x00075CC0, 0x00076141, 0x000764C0, 0x00076941, 0x00076CC0,
+    0x00077141, 0x000778C0, 0x00077D41, 0x000780C0, 0x00078541, 0x000788C0, 0x00078D41, 0x000790C0,
+    0x00079541, 0x000798C0, 0x00079D41, 0x0007A0C0, 0x0007A541, 0x0007A8C0, 0x0007AD41, 0x0007B0C0,
+    0x0007B541, 0x0007B8C0, 0x0007BD41, 0x0007C4C0, 0x0007C842, 0x0007CD41, 0x0007D0C0, 0x0007D541,
+    0x0007D8C0, 0x0007E541, 0x0007E8C0, 0x0007ED41, 0x0007F0C0, 0x0007F541, 0x0007F8C0, 0x0007FD41,
+    0x000800C0, 0x00080541, 0x000808C0, 0x00080D41, 0x000810C0, 0x00081541, 0x000818C0, 0x00081D41,
+    0x000820C0, 0x00082541, 0x000828C0, 0x00082D41, 0x000830C0, 0x00083541, 0x000838C0, 0x00083D41,
+    0x000840C0, 0x00084541, 0x000848C0, 0x00084D41, 0x000850C0, 0x00085541, 0x000858C0, 0x00085D41,
+    0x000860C0, 0x00086541, 0x000868C0, 0x00086D41, 0x000870C0, 0x00087541, 0x000878C0, 0x00087D41,
+    0x000880C0, 0x00088541, 0x000888C0, 0x00088D41, 0x000890C0, 0x00089541, 0x000898C0, 0x00089D41,
+    0x0008A0C0, 0x0008A541, 0x0008A8C0, 0x0008AD41, 0x0008B0C0, 0x0008B541, 0x0008B8C0, 0x0008BD41,
+    0x0008C0C0, 0x0008C541, 0x0008C8C0, 0x0008CD41, 0x0008E8C0, 0x0008F141, 0x0008F4C0, 0x0008FD41,
+    0x000904C0, 0x00090941, 0x00090CC0, 0x00091D41, 0x000920C0, 0x00092541, 0x000928C0, 0x00092D41,
+    0x000930C0, 0x00093541, 0x000938C0, 0x00093D41, 0x000A5044, 0x000A5941, 0x000AC343, 0x000AE643,
+    0x000B0343, 0x000B0A14, 0x000B1A43, 0x000B4A14, 0x000B8343, 0x000B9614, 0x000BB243, 0x000BB614,
+    0x000BBA43, 0x000BBE14, 0x000C0205, 0x000D1745, 0x000D1A05, 0x000D8E45, 0x000DC0C0, 0x000DC541,
+    0x000DC8C0, 0x000DCD41, 0x000DD243, 0x000DD614, 0x000DD8C0, 0x000DDD41, 0x000DE01D, 0x000DEB43,
+    0x000DED41, 0x000DF811, 0x000DFCC0, 0x000E001D, 0x000E1214, 0x000E18C0, 0x000E1E11, 0x000E20C0,
+    0x000E2C1D, 0x000E30C0, 0x000E341D, 0x000E38C0, 0x000E4141, 0x000E44C0, 0x000E881D, 0x000E8CC0,
+    0x000EB141, 0x000F3CC0, 0x000F4141, 0x000F48C0, 0x000F5541, 0x000F60C0, 0x000F6541, 0x000F68C0,
+    0x000F6D41, 0x000F70C0, 0x000F7541, 0x000F78C0, 0x000F7D41, 0x000F80C0, 0x000F8541, 0x000F88C0,
+    0x000F8D41, 0x000F90C0, 0x000F9541, 0x000F98C0, 0x000F9D41, 0x000FA0C0, 0x000FA541, 0x000FA8C0,
+    0x000FAD41, 0x000FB0C0, 0x000FB541, 0x000FB8C0, 0x000FBD41, 0x000FD0C0, 0x000FD541, 0x000FD812,
+    0x000FDCC0, 0x000FE141, 0x000FE4C0, 0x000FED41, 0x000FF4C0, 0x0010C141, 0x001180C0, 0x00118541,
+    0x001188C0, 0x00118D41, 0x001190C0, 0x00119541, 0x001198C0, 0x00119D41, 0x0011A0C0, 0x0011A541,
+    0x0011A8C0, 0x0011AD41, 0x0011B0C0, 0x0011B541, 0x0011B8C0, 0x0011BD41, 0x0011C0C0, 0x0011C541,
+    0x0011C8C0, 0x0011CD41, 0x0011D0C0, 0x0011D541, 0x0011D8C0, 0x0011DD41, 0x0011E0C0, 0x0011E541,
+    0x0011E8C0, 0x0011ED41, 0x0011F0C0, 0x0011F541, 0x0011F8C0, 0x0011FD41, 0x001200C0, 0x00120541,
+    0x00120815, 0x00120E05, 0x00122207, 0x001228C0, 0x00122D41, 0x001230C0, 0x00123541, 0x001238C0,
+    0x00123D41, 0x001240C0, 0x00124541, 0x001248C0, 0x00124D41, 0x001250C0, 0x00125541, 0x001258C0,
+    0x00125D41, 0x001260C0, 0x00126541, 0x001268C0, 0x00126D41, 0x001270C0, 0x00127541, 0x001278C0,
+    0x00127D41, 0x001280C0, 0x00128541, 0x001288C0, 0x00128D41, 0x001290C0, 0x00129541, 0x001298C0,
+    0x00129D41, 0x0012A0C0, 0x0012A541, 0x0012A8C0, 0x0012AD41, 0x0012B0C0, 0x0012B541, 0x0012B8C0,
+    0x0012BD41, 0x0012C0C0, 0x0012C541, 0x0012C8C0, 0x0012CD41, 0x0012D0C0, 0x0012D541, 0x0012D8C0,
+    0x0012DD41, 0x0012E0C0, 0x0012E541, 0x0012E8C0, 0x0012ED41, 0x0012F0C0, 0x0012F541, 0x0012F8C0,
+    0x0012FD41, 0x001300C0, 0x00130941, 0x00130CC0, 0x00131141, 0x001314C0, 0x00131941, 0x00131CC0,
+    0x00132141, 0x001324C0, 0x00132941, 0x00132CC0, 0x00133141, 0x001334C0, 0x00133941, 0x001340C0,
+    0x00134541, 0x001348C0, 0x00134D41, 0x001350C0, 0x00135541, 0x001358C0, 0x00135D41, 0x001360C0,
+    0x00136541, 0x001368C0, 0x00136D41, 0x001370C0, 0x00137541, 0x001378C0, 0x00137D41, 0x001380C0,
+    0x00138541, 0x001388C0, 0x00138D41, 0x001390C0, 0x00139541, 0x001398C0, 0x00139D41, 0x0013A0C0,
+    0x0013A541, 0x0013A8C0, 0x0013AD41, 0x0013B0C0, 0x0013B541, 0x0013B8C0, 0x0013BD41, 0x0013C0C0,
+    0x0013C541, 0x0013C8C0, 0x0013CD41, 0x0013D0C0, 0x0013D541, 0x0013D8C0, 0x0013DD41, 0x0013E0C0,
+    0x0013E541, 0x0013E8C0, 0x0013ED41, 0x0013F0C0, 0x0013F541, 0x0013F8C0, 0x0013FD41, 0x001400C0,
+    0x00140541, 0x001408C0, 0x00140D41, 0x001410C0, 0x00141541, 0x001418C0, 0x00141D41, 0x001420C0,
+    0x00142541, 0x001428C0, 0x00142D41, 0x001430C0, 0x00143541, 0x001438C0, 0x00143D41, 0x001440C0,
+    0x00144541, 0x001448C0, 0x00144D41, 0x001450C0, 0x00145541, 0x001458C0, 0x00145D41, 0x001460C0,
+    0x00146541, 0x001468C0, 0x00146D41, 0x001470C0, 0x00147541, 0x001478C0, 0x00147D41, 0x001480C0,
+    0x00148541, 0x001488C0, 0x00148D41, 0x001490C0, 0x00149541, 0x001498C0, 0x00149D41, 0x0014A0C0,
+    0x0014A541, 0x0014A8C0, 0x0014AD41, 0x0014B0C0, 0x0014B541, 0x0014B8C0, 0x0014BD41, 0x0014C01D,
+    0x0014C4C0, 0x00155C1D, 0x00156643, 0x00156811, 0x00157E11, 0x00158141, 0x00162411, 0x0016280C,
+    0x00162C1D, 0x00163415, 0x00163C13, 0x0016401D, 0x00164605, 0x0016C245, 0x0016F80C, 0x0016FE45,
+    0x00170011, 0x00170645, 0x00170C11, 0x00171245, 0x00171811, 0x00171E45, 0x0017201D, 0x00174044,
+    0x0017AC1D, 0x0017BC44, 0x0017CC11, 0x0017D211, 0x0017D41D, 0x0018021A, 0x00181812, 0x00182411,
+    0x00182C13, 0x00183011, 0x00183815, 0x00184245, 0x00186C11, 0x0018721A, 0x00187411, 0x00188044,
+    0x00190243, 0x00190444, 0x00192E45, 0x00196205, 0x00196645, 0x00198008, 0x0019A811, 0x0019B844,
+    0x0019C245, 0x0019C444, 0x001B5011, 0x001B5444, 0x001B5A45, 0x001B761A, 0x001B7815, 0x001B7E05,
+    0x001B8645, 0x001B9643, 0x001B9E45, 0x001BA415, 0x001BAA05, 0x001BB645, 0x001BB844, 0x001BC008,
+    0x001BE844, 0x001BF415, 0x001BFC44, 0x001C0011, 0x001C381D, 0x001C3E1A, 0x001C4044, 0x001C4645,
+    0x001C4844, 0x001CC245, 0x001D0205, 0x001D2C1D, 0x001D3444, 0x001E9A45, 0x001EC444, 0x001EC81D,
+    0x001F0008, 0x001F2844, 0x001FAE05, 0x001FD243, 0x001FD815, 0x001FDC11, 0x001FEA43, 0x001FEC1D,
+    0x001FF605, 0x001FF813, 0x00200044, 0x00205A45, 0x00206205, 0x00206A43, 0x00206E45, 0x00209243,
+    0x00209645, 0x0020A243, 0x0020A645, 0x0020B605, 0x0020B81D, 0x0020C011, 0x0020FC1D, 0x00210044,
+    0x00216605, 0x0021701D, 0x00217811, 0x00217C1D, 0x00218044, 0x0021AC1D, 0x0021C044, 0x00222214,
+    0x00222444, 0x0022421A, 0x0022481D, 0x00225E45, 0x00226205, 0x00228044, 0x00232643, 0x00232A05,
+    0x00235245, 0x00238205, 0x00238A1A, 0x00238E45, 0x0023AA05, 0x0023C245, 0x00240C46, 0x00241044,
+    0x0024EA45, 0x0024EC46, 0x0024F205, 0x0024F444, 0x0024F846, 0x00250645, 0x00252446, 0x00253605,
+    0x00253846, 0x00254044, 0x00254605, 0x00255645, 0x00256044, 0x00258A45, 0x00259011, 0x00259808,
+    0x0025C011, 0x0025C643, 0x0025C844, 0x00260645, 0x00260846, 0x0026101D, 0x00261444, 0x0026341D,
+    0x00263C44, 0x0026441D, 0x00264C44, 0x0026A41D, 0x0026A844, 0x0026C41D, 0x0026C844, 0x0026CC1D,
+    0x0026D844, 0x0026E81D, 0x0026F205, 0x0026F444, 0x0026F846, 0x00270645, 0x0027141D, 0x00271C46,
+    0x0027241D, 0x00272C46, 0x00273605, 0x00273844, 0x00273C1D, 0x00275C46, 0x0027601D, 0x00277044,
+    0x0027781D, 0x00277C44, 0x00278A45, 0x0027901D, 0x00279808, 0x0027C044, 0x0027C813, 0x0027D00A,
+    0x0027E815, 0x0027EC13, 0x0027F044, 0x0027F411, 0x0027FA05, 0x0027FC1D, 0x00280645, 0x00280C46,
+    0x0028101D, 0x00281444, 0x00282C1D, 0x00283C44, 0x0028441D, 0x00284C44, 0x0028A41D, 0x0028A844,
+    0x0028C41D, 0x0028C844, 0x0028D01D, 0x0028D444, 0x0028DC1D, 0x0028E044, 0x0028E81D, 0x0028F205,
+    0x0028F41D, 0x0028F846, 0x00290645, 0x00290C1D, 0x00291E45, 0x0029241D, 0x00292E45, 0x00293605,
+    0x0029381D, 0x00294645, 0x0029481D, 0x00296444, 0x0029741D, 0x00297844, 0x00297C1D, 0x00299808,
+    0x0029C245, 0x0029C844, 0x0029D645, 0x0029D811, 0x0029DC1D, 0x002A0645, 0x002A0C46, 0x002A101D,
+    0x002A1444, 0x002A381D, 0x002A3C44, 0x002A481D, 0x002A4C44, 0x002AA41D, 0x002AA844, 0x002AC41D,
+    0x002AC844, 0x002AD01D, 0x002AD444, 0x002AE81D, 0x002AF205, 0x002AF444, 0x002AF846, 0x002B0645,
+    0x002B181D, 0x002B1E45, 0x002B2446, 0x002B281D, 0x002B2C46, 0x002B3605, 0x002B381D, 0x002B4044,
+    0x002B441D, 0x002B8044, 0x002B8A45, 0x002B901D, 0x002B9808, 0x002BC011, 0x002BC413, 0x002BC81D,
+    0x002BE444, 0x002BEA45, 0x002BF605, 0x002C001D, 0x002C0645, 0x002C0846, 0x002C101D, 0x002C1444,
+    0x002C341D, 0x002C3C44, 0x002C441D, 0x002C4C44, 0x002CA41D, 0x002CA844, 0x002CC41D, 0x002CC844,
+    0x002CD01D, 0x002CD444, 0x002CE81D, 0x002CF205, 0x002CF444, 0x002CF846, 0x002CFE45, 0x002D0046,
+    0x002D0645, 0x002D141D, 0x002D1C46, 0x002D241D, 0x002D2C46, 0x002D3605, 0x002D381D, 0x002D5605,
+    0x002D5A45, 0x002D5C46, 0x002D601D, 0x002D7044, 0x002D781D, 0x002D7C44, 0x002D8A45, 0x002D901D,
+    0x002D9808, 0x002DC015, 0x002DC444, 0x002DC80A, 0x002DE01D, 0x002E0A45, 0x002E0C44, 0x002E101D,
+    0x002E1444, 0x002E2C1D, 0x002E3844, 0x002E441D, 0x002E4844, 0x002E581D, 0x002E6444, 0x002E6C1D,
+    0x002E7044, 0x002E741D, 0x002E7844, 0x002E801D, 0x002E8C44, 0x002E941D, 0x002EA044, 0x002EAC1D,
+    0x002EB844, 0x002EE81D, 0x002EF846, 0x002F0245, 0x002F0446, 0x002F0C1D, 0x002F1846, 0x002F241D,
+    0x002F2846, 0x002F3605, 0x002F381D, 0x002F4044, 0x002F441D, 0x002F5C46, 0x002F601D, 0x002F9808,
+    0x002FC00A, 0x002FCC15, 0x002FE413, 0x002FE815, 0x002FEC1D, 0x00300245, 0x00300446, 0x00301245,
+    0x00301444, 0x0030341D, 0x00303844, 0x0030441D, 0x00304844, 0x0030A41D, 0x0030A844, 0x0030E81D,
+    0x0030F205, 0x0030F444, 0x0030FA45, 0x00310446, 0x0031141D, 0x00311A45, 0x0031241D, 0x00312A45,
+    0x00313605, 0x0031381D, 0x00315645, 0x00315C1D, 0x00316044, 0x00316C1D, 0x00317044, 0x0031781D,
+    0x00318044, 0x00318A45, 0x0031901D, 0x00319808, 0x0031C01D, 0x0031DC11, 0x0031E00A, 0x0031FC15,
+    0x00320044, 0x00320645, 0x00320846, 0x00321011, 0x00321444, 0x0032341D, 0x00323844, 0x0032441D,
+    0x00324844, 0x0032A41D, 0x0032A844, 0x0032D01D, 0x0032D444, 0x0032E81D, 0x0032F205, 0x0032F444,
+    0x0032F846, 0x0032FE45, 0x00330046, 0x0033141D, 0x00331A45, 0x00331C46, 0x0033241D, 0x00332846,
+    0x00333245, 0x00333605, 0x0033381D, 0x00335446, 0x00335C1D, 0x00337044, 0x00337C1D, 0x00338044,
+    0x00338A45, 0x0033901D, 0x00339808, 0x0033C01D, 0x0033C444, 0x0033CC46, 0x0033D01D, 0x00340245,
+    0x00340846, 0x00341044, 0x0034341D, 0x00343844, 0x0034441D, 0x00344844, 0x0034EE05, 0x0034F444,
+    0x0034F846, 0x00350645, 0x0035141D, 0x00351846, 0x0035241D, 0x00352846, 0x00353605, 0x00353844,
+    0x00353C15, 0x0035401D, 0x00355044, 0x00355C46, 0x0035600A, 0x00357C44, 0x00358A45, 0x0035901D,
+    0x00359808, 0x0035C00A, 0x0035E415, 0x0035E844, 0x0036001D, 0x00360645, 0x00360846, 0x0036101D,
+    0x00361444, 0x00365C1D, 0x00366844, 0x0036C81D, 0x0036CC44, 0x0036F01D, 0x0036F444, 0x0036F81D,
+    0x00370044, 0x00371C1D, 0x00372A05, 0x00372C1D, 0x00373C46, 0x00374A45, 0x0037541D, 0x00375A45,
+    0x00375C1D, 0x00376046, 0x0037801D, 0x00379808, 0x0037C01D, 0x0037C846, 0x0037D011, 0x0037D41D,
+    0x00380444, 0x0038C645, 0x0038C844, 0x0038D245, 0x0038EC1D, 0x0038FC13, 0x00390044, 0x00391A43,
+    0x00391E05, 0x00393645, 0x00393A05, 0x00393C11, 0x00394008, 0x00396811, 0x0039701D, 0x003A0444,
+    0x003A0C1D, 0x003A1044, 0x003A141D, 0x003A1844, 0x003A2C1D, 0x003A3044, 0x003A901D, 0x003A9444,
+    0x003A981D, 0x003A9C44, 0x003AC645, 0x003AC844, 0x003AD245, 0x003AEA05, 0x003AEE45, 0x003AF444,
+    0x003AF81D, 0x003B0044, 0x003B141D, 0x003B1A43, 0x003B1C1D, 0x003B2205, 0x003B3645, 0x003B3A05,
+    0x003B3C1D, 0x003B4008, 0x003B681D, 0x003B7044, 0x003B801D, 0x003C0044, 0x003C0415, 0x003C1011,
+    0x003C4C15, 0x003C5011, 0x003C5415, 0x003C6205, 0x003C6815, 0x003C8008, 0x003CA80A, 0x003CD015,
+    0x003CD605, 0x003CD815, 0x003CDE05, 0x003CE015, 0x003CE605, 0x003CE80D, 0x003CEC0E, 0x003CF00D,
+    0x003CF40E, 0x003CF806, 0x003D0044, 0x003D201D, 0x003D2444, 0x003DB41D, 0x003DC645, 0x003DFC46,
+    0x003E0245, 0x003E1205, 0x003E1411, 0x003E1A05, 0x003E2044, 0x003E3645, 0x003E601D, 0x003E6645,
+    0x003EF41D, 0x003EF815, 0x003F1A05, 0x003F1C15, 0x003F341D, 0x003F3815, 0x003F4011, 0x003F5415,
+    0x003F6411, 0x003F6C1D, 0x00400044, 0x0040AC46, 0x0040B645, 0x0040C446, 0x0040CA45, 0x0040DE05,
+    0x0040E046, 0x0040E605, 0x0040EC46, 0x0040F645, 0x0040FC44, 0x00410008, 0x00412811, 0x00414044,
+    0x00415846, 0x00416245, 0x00416844, 0x00417A45, 0x00418444, 0x00418846, 0x00419444, 0x00419C46,
+    0x0041B844, 0x0041C645, 0x0041D444, 0x00420A45, 0x00420C46, 0x00421645, 0x00421C46, 0x00423645,
+    0x00423844, 0x00423C46, 0x00424008, 0x00426846, 0x00427645, 0x00427815, 0x004280C0, 0x0043181D,
+    0x00431CC0, 0x0043201D, 0x004334C0, 0x0043381D, 0x00434141, 0x0043EC11, 0x0043F343, 0x0043F541,
+    0x00440044, 0x0049241D, 0x00492844, 0x0049381D, 0x00494044, 0x00495C1D, 0x00496044, 0x0049641D,
+    0x00496844, 0x0049781D, 0x00498044, 0x004A241D, 0x004A2844, 0x004A381D, 0x004A4044, 0x004AC41D,
+    0x004AC844, 0x004AD81D, 0x004AE044, 0x004AFC1D, 0x004B0044, 0x004B041D, 0x004B0844, 0x004B181D,
+    0x004B2044, 0x004B5C1D, 0x004B6044, 0x004C441D, 0x004C4844, 0x004C581D, 0x004C6044, 0x004D6C1D,
+    0x004D7605, 0x004D8011, 0x004DA40A, 0x004DF41D, 0x004E0044, 0x004E4015, 0x004E681D, 0x004E80C0,
+    0x004FD81D, 0x004FE141, 0x004FF81D, 0x0050000C, 0x00500444, 0x0059B415, 0x0059B811, 0x0059BC44,
+    0x005A0036, 0x005A0444, 0x005A6C0D, 0x005A700E, 0x005A741D, 0x005A8044, 0x005BAC11, 0x005BB849,
+    0x005BC444, 0x005BE41D, 0x005C0044, 0x005C4A45, 0x005C5205, 0x005C5406, 0x005C581D, 0x005C7C44,
+    0x005CCA45, 0x005CD006, 0x005CD411, 0x005CDC1D, 0x005D0044, 0x005D4A45, 0x005D501D, 0x005D8044,
+    0x005DB41D, 0x005DB844, 0x005DC41D, 0x005DCA45, 0x005DD01D, 0x005E0044, 0x005ED205, 0x005ED846,
+    0x005EDE45, 0x005EF846, 0x005F1A45, 0x005F1C46, 0x005F2605, 0x005F5011, 0x005F5E43, 0x005F6011,
+    0x005F6C13, 0x005F7044, 0x005F7605, 0x005F781D, 0x005F8008, 0x005FA81D, 0x005FC00A, 0x005FE81D,
+    0x00600011, 0x0060180C, 0x00601C11, 0x00602E05, 0x00603A1A, 0x00603E05, 0x00604008, 0x0060681D,
+    0x00608044, 0x00610E43, 0x00611044, 0x0061E41D, 0x00620044, 0x00621645, 0x00621C44, 0x0062A645,
+    0x0062A844, 0x0062AC1D, 0x0062C044, 0x0063D81D, 0x00640044, 0x00647C1D, 0x00648245, 0x00648C46,
+    0x00649E45, 0x0064A446, 0x0064B01D, 0x0064C046, 0x0064CA45, 0x0064CC46, 0x0064E605, 0x0064F01D,
+    0x00650015, 0x0065041D, 0x00651011, 0x00651808, 0x00654044, 0x0065B81D, 0x0065C044, 0x0065D41D,
+    0x00660044, 0x0066B01D, 0x0066C044, 0x0067281D, 0x00674008, 0x0067680A, 0x00676C1D, 0x00677815,
+    0x00680044, 0x00685E45, 0x00686446, 0x00686E45, 0x0068701D, 0x00687811, 0x00688044, 0x00695446,
+    0x00695A45, 0x00695C46, 0x00696245, 0x00697C1D, 0x00698205, 0x00698446, 0x00698A45, 0x00698C46,
+    0x00699645, 0x0069B446, 0x0069CE45, 0x0069D605, 0x0069F41D, 0x0069FE05, 0x006A0008, 0x006A281D,
+    0x006A4008, 0x006A681D, 0x006A8011, 0x006A9E43, 0x006AA011, 0x006AB81D, 0x006AC205, 0x006AFA07,
+    0x006AFE45, 0x006B0605, 0x006B3245, 0x006B3E05, 0x006B781D, 0x006B8205, 0x006BB01D, 0x006C0245,
+    0x006C1046, 0x006C1444, 0x006CD205, 0x006CD446, 0x006CDA45, 0x006CEC46, 0x006CF245, 0x006CF446,
+    0x006D0A45, 0x006D0C46, 0x006D1006, 0x006D1444, 0x006D341D, 0x006D3811, 0x006D4008, 0x006D6811,
+    0x006D8415, 0x006DAE05, 0x006DD015, 0x006DF411, 0x006E0245, 0x006E0846, 0x006E0C44, 0x006E8446,
+    0x006E8A45, 0x006E9846, 0x006EA245, 0x006EA806, 0x006EAE05, 0x006EB245, 0x006EB844, 0x006EC008,
+    0x006EE844, 0x006F9A05, 0x006F9C46, 0x006FA245, 0x006FA846, 0x006FB645, 0x006FB846, 0x006FBE45,
+    0x006FC806, 0x006FD01D, 0x006FF011, 0x00700044, 0x00709046, 0x0070B245, 0x0070D046, 0x0070DA45,
+    0x0070DE05, 0x0070E01D, 0x0070EC11, 0x00710008, 0x0071281D, 0x00713444, 0x00714008, 0x00716844,
+    0x0071E243, 0x0071F811, 0x00720141, 0x007224C0, 0x00722941, 0x00722C1D, 0x007240C0, 0x0072EC1D,
+    0x0072F4C0, 0x00730011, 0x0073201D, 0x00734205, 0x00734C11, 0x00735205, 0x00738406, 0x00738A05,
+    0x0073A444, 0x0073B605, 0x0073B844, 0x0073D205, 0x0073D444, 0x0073DC06, 0x0073E205, 0x0073E844,
+    0x0073EC1D, 0x00740141, 0x0074B343, 0x0075AD41, 0x0075E343, 0x0075E541, 0x00766F43, 0x00770205,
+    0x00774E45, 0x0077D605, 0x007800C0, 0x00780541, 0x007808C0, 0x00780D41, 0x007810C0, 0x00781541,
+    0x007818C0, 0x00781D41, 0x007820C0, 0x00782541, 0x007828C0, 0x00782D41, 0x007830C0, 0x00783541,
+    0x007838C0, 0x00783D41, 0x007840C0, 0x00784541, 0x007848C0, 0x00784D41, 0x007850C0, 0x00785541,
+    0x007858C0, 0x00785D41, 0x007860C0, 0x00786541, 0x007868C0, 0x00786D41, 0x007870C0, 0x00787541,
+    0x007878C0, 0x00787D41, 0x007880C0, 0x00788541, 0x007888C0, 0x00788D41, 0x007890C0, 0x00789541,
+    0x007898C0, 0x00789D41, 0x0078A0C0, 0x0078A541, 0x0078A8C0, 0x0078AD41, 0x0078B0C0, 0x0078B541,
+    0x0078B8C0, 0x0078BD41, 0x0078C0C0, 0x0078C541, 0x0078C8C0, 0x0078CD41, 0x0078D0C0, 0x0078D541,
+    0x0078D8C0, 0x0078DD41, 0x0078E0C0, 0x0078E541, 0x0078E8C0, 0x0078ED41, 0x0078F0C0, 0x0078F541,
+    0x0078F8C0, 0x0078FD41, 0x007900C0, 0x00790541, 0x007908C0, 0x00790D41, 0x007910C0, 0x00791541,
+    0x007918C0, 0x00791D41, 0x007920C0, 0x00792541, 0x007928C0, 0x00792D41, 0x007930C0, 0x00793541,
+    0x007938C0, 0x00793D41, 0x007940C0, 0x00794541, 0x007948C0, 0x00794D41, 0x007950C0, 0x00795541,
+    0x007958C0, 0x00795D41, 0x007960C0, 0x00796541, 0x007968C0, 0x00796D41, 0x007970C0, 0x00797541,
+    0x007978C0, 0x00797D41, 0x007980C0, 0x00798541, 0x007988C0, 0x00798D41, 0x007990C0, 0x00799541,
+    0x007998C0, 0x00799D41, 0x0079A0C0, 0x0079A541, 0x0079A8C0, 0x0079AD41, 0x0079B0C0, 0x0079B541,
+    0x0079B8C0, 0x0079BD41, 0x0079C0C0, 0x0079C541, 0x0079C8C0, 0x0079CD41, 0x0079D0C0, 0x0079D541,
+    0x0079D8C0, 0x0079DD41, 0x0079E0C0, 0x0079E541, 0x0079E8C0, 0x0079ED41, 0x0079F0C0, 0x0079F541,
+    0x0079F8C0, 0x0079FD41, 0x007A00C0, 0x007A0541, 0x007A08C0, 0x007A0D41, 0x007A10C0, 0x007A1541,
+    0x007A18C0, 0x007A1D41, 0x007A20C0, 0x007A2541, 0x007A28C0, 0x007A2D41, 0x007A30C0, 0x007A3541,
+    0x007A38C0, 0x007A3D41, 0x007A40C0, 0x007A4541, 0x007A48C0, 0x007A4D41, 0x007A50C0, 0x007A5541,
+    0x007A78C0, 0x007A7D41, 0x007A80C0, 0x007A8541, 0x007A88C0, 0x007A8D41, 0x007A90C0, 0x007A9541,
+    0x007A98C0, 0x007A9D41, 0x007AA0C0, 0x007AA541, 0x007AA8C0, 0x007AAD41, 0x007AB0C0, 0x007AB541,
+    0x007AB8C0, 0x007ABD41, 0x007AC0C0, 0x007AC541, 0x007AC8C0, 0x007ACD41, 0x007AD0C0, 0x007AD541,
+    0x007AD8C0, 0x007ADD41, 0x007AE0C0, 0x007AE541, 0x007AE8C0, 0x007AED41, 0x007AF0C0, 0x007AF541,
+    0x007AF8C0, 0x007AFD41, 0x007B00C0, 0x007B0541, 0x007B08C0, 0x007B0D41, 0x007B10C0, 0x007B1541,
+    0x007B18C0, 0x007B1D41, 0x007B20C0, 0x007B2541, 0x007B28C0, 0x007B2D41, 0x007B30C0, 0x007B3541,
+    0x007B38C0, 0x007B3D41, 0x007B40C0, 0x007B4541, 0x007B48C0, 0x007B4D41, 0x007B50C0, 0x007B5541,
+    0x007B58C0, 0x007B5D41, 0x007B60C0, 0x007B6541, 0x007B68C0, 0x007B6D41, 0x007B70C0, 0x007B7541,
+    0x007B78C0, 0x007B7D41, 0x007B80C0, 0x007B8541, 0x007B88C0, 0x007B8D41, 0x007B90C0, 0x007B9541,
+    0x007B98C0, 0x007B9D41, 0x007BA0C0, 0x007BA541, 0x007BA8C0, 0x007BAD41, 0x007BB0C0, 0x007BB541,
+    0x007BB8C0, 0x007BBD41, 0x007BC0C0, 0x007BC541, 0x007BC8C0, 0x007BCD41, 0x007BD0C0, 0x007BD541,
+    0x007BD8C0, 0x007BDD41, 0x007BE0C0, 0x007BE541, 0x007BE8C0, 0x007BED41, 0x007BF0C0, 0x007BF541,
+    0x007BF8C0, 0x007BFD41, 0x007C20C0, 0x007C4141, 0x007C581D, 0x007C60C0, 0x007C781D, 0x007C8141,
+    0x007CA0C0, 0x007CC141, 0x007CE0C0, 0x007D0141, 0x007D181D, 0x007D20C0, 0x007D381D, 0x007D4141,
+    0x007D601D, 0x007D64C0, 0x007D681D, 0x007D6CC0, 0x007D701D, 0x007D74C0, 0x007D781D, 0x007D7CC0,
+    0x007D8141, 0x007DA0C0, 0x007DC141, 0x007DF81D, 0x007E0141, 0x007E2042, 0x007E4141, 0x007E6042,
+    0x007E8141, 0x007EA042, 0x007EC141, 0x007ED41D, 0x007ED941, 0x007EE0C0, 0x007EF042, 0x007EF614,
+    0x007EF941, 0x007EFE14, 0x007F0941, 0x007F141D, 0x007F1941, 0x007F20C0, 0x007F3042, 0x007F3614,
+    0x007F4141, 0x007F501D, 0x007F5941, 0x007F60C0, 0x007F701D, 0x007F7614, 0x007F8141, 0x007FA0C0,
+    0x007FB614, 0x007FC01D, 0x007FC941, 0x007FD41D, 0x007FD941, 0x007FE0C0, 0x007FF042, 0x007FF614,
+    0x007FFC1D, 0x00800036, 0x00802E1A, 0x0080400C, 0x00805811, 0x0080620F, 0x00806610, 0x0080680D,
+    0x00806C0F, 0x00807410, 0x0080780D, 0x00807C0F, 0x00808011, 0x00809211, 0x00809411, 0x00809E11,
+    0x0080A037, 0x0080A438, 0x0080AA1A, 0x0080BC36, 0x0080C011, 0x0080E40F, 0x0080E810, 0x0080EC11,
+    0x0080FC0B, 0x00810411, 0x00811012, 0x0081140D, 0x0081180E, 0x00811C11, 0x00814812, 0x00814C11,
+    0x0081500B, 0x00815411, 0x00817C36, 0x0081821A, 0x0081941D, 0x00819A1A, 0x0081C00A, 0x0081C743,
+    0x0081C81D, 0x0081D00A, 0x0081E812, 0x0081F40D, 0x0081F80E, 0x0081FF43, 0x0082000A, 0x00822812,
+    0x0082340D, 0x0082380E, 0x00823C1D, 0x00824343, 0x0082741D, 0x00828013, 0x0083081D, 0x00834205,
+    0x00837607, 0x00838605, 0x00838A07, 0x00839605, 0x0083C41D, 0x00840015, 0x008408C0, 0x00840C15,
+    0x00841CC0, 0x00842015, 0x00842941, 0x00842CC0, 0x00843941, 0x008440C0, 0x00844D41, 0x00845015,
+    0x008454C0, 0x00845815, 0x00846012, 0x008464C0, 0x00847815, 0x008490C0, 0x00849415, 0x008498C0,
+    0x00849C15, 0x0084A0C0, 0x0084A415, 0x0084A8C0, 0x0084B815, 0x0084BD41, 0x0084C0C0, 0x0084D141,
+    0x0084D444, 0x0084E541, 0x0084E815, 0x0084F141, 0x0084F8C0, 0x00850012, 0x008514C0, 0x00851941,
+    0x00852815, 0x00852C12, 0x00853015, 0x00853941, 0x00853C15, 0x0085400A, 0x008580C9, 0x0085C149,
+    0x00860049, 0x00860CC0, 0x00861141, 0x00861449, 0x0086240A, 0x00862815, 0x0086301D, 0x00864012,
+    0x00865415, 0x00866812, 0x00867015, 0x00868012, 0x00868415, 0x00868C12, 0x00869015, 0x00869812,
+    0x00869C15, 0x0086B812, 0x0086BC15, 0x00873812, 0x00874015, 0x00874812, 0x00874C15, 0x00875012,
+    0x00875415, 0x0087D012, 0x008C0015, 0x008C200D, 0x008C240E, 0x008C280D, 0x008C2C0E, 0x008C3015,
+    0x008C8012, 0x008C8815, 0x008CA40D, 0x008CA80E, 0x008CAC15, 0x008DF012, 0x008DF415, 0x008E6C12,
+    0x008ED015, 0x008F7012, 0x008F8815, 0x0090A81D, 0x00910015, 0x00912C1D, 0x0091800A, 0x00927015,
+    0x0092D8D5, 0x00934155, 0x0093A80A, 0x00940015, 0x0096DC12, 0x0096E015, 0x00970412, 0x00970815,
+    0x0097E012, 0x00980015, 0x0099BC12, 0x0099C015, 0x009DA00D, 0x009DA40E, 0x009DA80D, 0x009DAC0E,
+    0x009DB00D, 0x009DB40E, 0x009DB80D, 0x009DBC0E, 0x009DC00D, 0x009DC40E, 0x009DC80D, 0x009DCC0E,
+    0x009DD00D, 0x009DD40E, 0x009DD80A, 0x009E5015, 0x009F0012, 0x009F140D, 0x009F180E, 0x009F1C12,
+    0x009F980D, 0x009F9C0E, 0x009FA00D, 0x009FA40E, 0x009FA80D, 0x009FAC0E, 0x009FB00D, 0x009FB40E,
+    0x009FB80D, 0x009FBC0E, 0x009FC012, 0x00A00015, 0x00A40012, 0x00A60C0D, 0x00A6100E, 0x00A6140D,
+    0x00A6180E, 0x00A61C0D, 0x00A6200E, 0x00A6240D, 0x00A6280E, 0x00A62C0D, 0x00A6300E, 0x00A6340D,
+    0x00A6380E, 0x00A63C0D, 0x00A6400E, 0x00A6440D, 0x00A6480E, 0x00A64C0D, 0x00A6500E, 0x00A6540D,
+    0x00A6580E, 0x00A65C0D, 0x00A6600E, 0x00A66412, 0x00A7600D, 0x00A7640E, 0x00A7680D, 0x00A76C0E,
+    0x00A77012, 0x00A7F00D, 0x00A7F40E, 0x00A7F812, 0x00AC0015, 0x00ACC012, 0x00AD1415, 0x00AD1C12,
+    0x00AD3415, 0x00ADD01D, 0x00ADD815, 0x00B000C0, 0x00B0C141, 0x00B180C0, 0x00B18541, 0x00B188C0,
+    0x00B19541, 0x00B19CC0, 0x00B1A141, 0x00B1A4C0, 0x00B1A941, 0x00B1ACC0, 0x00B1B141, 0x00B1B4C0,
+    0x00B1C541, 0x00B1C8C0, 0x00B1CD41, 0x00B1D4C0, 0x00B1D941, 0x00B1F343, 0x00B1F8C0, 0x00B20541,
+    0x00B208C0, 0x00B20D41, 0x00B210C0, 0x00B21541, 0x00B218C0, 0x00B21D41, 0x00B220C0, 0x00B22541,
+    0x00B228C0, 0x00B22D41, 0x00B230C0, 0x00B23541, 0x00B238C0, 0x00B23D41, 0x00B240C0, 0x00B24541,
+    0x00B248C0, 0x00B24D41, 0x00B250C0, 0x00B25541, 0x00B258C0, 0x00B25D41, 0x00B260C0, 0x00B26541,
+    0x00B268C0, 0x00B26D41, 0x00B270C0, 0x00B27541, 0x00B278C0, 0x00B27D41, 0x00B280C0, 0x00B28541,
+    0x00B288C0, 0x00B28D41, 0x00B290C0, 0x00B29541, 0x00B298C0, 0x00B29D41, 0x00B2A0C0, 0x00B2A541,
+    0x00B2A8C0, 0x00B2AD41, 0x00B2B0C0, 0x00B2B541, 0x00B2B8C0, 0x00B2BD41, 0x00B2C0C0, 0x00B2C541,
+    0x00B2C8C0, 0x00B2CD41, 0x00B2D0C0, 0x00B2D541, 0x00B2D8C0, 0x00B2DD41, 0x00B2E0C0, 0x00B2E541,
+    0x00B2E8C0, 0x00B2ED41, 0x00B2F0C0, 0x00B2F541, 0x00B2F8C0, 0x00B2FD41, 0x00B300C0, 0x00B30541,
+    0x00B308C0, 0x00B30D41, 0x00B310C0, 0x00B31541, 0x00B318C0, 0x00B31D41, 0x00B320C0, 0x00B32541,
+    0x00B328C0, 0x00B32D41, 0x00B330C0, 0x00B33541, 0x00B338C0, 0x00B33D41, 0x00B340C0, 0x00B34541,
+    0x00B348C0, 0x00B34D41, 0x00B350C0, 0x00B35541, 0x00B358C0, 0x00B35D41, 0x00B360C0, 0x00B36541,
+    0x00B368C0, 0x00B36D41, 0x00B370C0, 0x00B37541, 0x00B378C0, 0x00B37D41, 0x00B380C0, 0x00B38541,
+    0x00B388C0, 0x00B38D41, 0x00B39415, 0x00B3ACC0, 0x00B3B141, 0x00B3B4C0, 0x00B3B941, 0x00B3BE05,
+    0x00B3C8C0, 0x00B3CD41, 0x00B3D01D, 0x00B3E411, 0x00B3F40A, 0x00B3F811, 0x00B40141, 0x00B4981D,
+    0x00B49D41, 0x00B4A01D, 0x00B4B541, 0x00B4B81D, 0x00B4C044, 0x00B5A01D, 0x00B5BE43, 0x00B5C011,
+    0x00B5C41D, 0x00B5FE05, 0x00B60044, 0x00B65C1D, 0x00B68044, 0x00B69C1D, 0x00B6A044, 0x00B6BC1D,
+    0x00B6C044, 0x00B6DC1D, 0x00B6E044, 0x00B6FC1D, 0x00B70044, 0x00B71C1D, 0x00B72044, 0x00B73C1D,
+    0x00B74044, 0x00B75C1D, 0x00B76044, 0x00B77C1D, 0x00B78245, 0x00B80011, 0x00B8080F, 0x00B80C10,
+    0x00B8100F, 0x00B81410, 0x00B81811, 0x00B8240F, 0x00B82810, 0x00B82C11, 0x00B8300F, 0x00B83410,
+    0x00B83811, 0x00B85C0C, 0x00B86011, 0x00B8680C, 0x00B86C11, 0x00B8700F, 0x00B87410, 0x00B87811,
+    0x00B8800F, 0x00B88410, 0x00B8880D, 0x00B88C0E, 0x00B8900D, 0x00B8940E, 0x00B8980D, 0x00B89C0E,
+    0x00B8A00D, 0x00B8A40E, 0x00B8A811, 0x00B8BE43, 0x00B8C011, 0x00B8E80C, 0x00B8F011, 0x00B9000C,
+    0x00B90411, 0x00B9080D, 0x00B90C11, 0x00B94015, 0x00B94811, 0x00B9540D, 0x00B9580E, 0x00B95C0D,
+    0x00B9600E, 0x00B9640D, 0x00B9680E, 0x00B96C0D, 0x00B9700E, 0x00B9740C, 0x00B9781D, 0x00BA0015,
+    0x00BA681D, 0x00BA6C15, 0x00BBD01D, 0x00BC0015, 0x00BF581D, 0x00BFC015, 0x00C00036, 0x00C00411,
+    0x00C01015, 0x00C01643, 0x00C01844, 0x00C01C49, 0x00C0200D, 0x00C0240E, 0x00C0280D, 0x00C02C0E,
+    0x00C0300D, 0x00C0340E, 0x00C0380D, 0x00C03C0E, 0x00C0400D, 0x00C0440E, 0x00C04815, 0x00C0500D,
+    0x00C0540E, 0x00C0580D, 0x00C05C0E, 0x00C0600D, 0x00C0640E, 0x00C0680D, 0x00C06C0E, 0x00C0700C,
+    0x00C0740D, 0x00C0780E, 0x00C08015, 0x00C08449, 0x00C0AA05, 0x00C0B806, 0x00C0C00C, 0x00C0C643,
+    0x00C0D815, 0x00C0E049, 0x00C0EE43, 0x00C0F044, 0x00C0F411, 0x00C0F815, 0x00C1001D, 0x00C10444,
+    0x00C25C1D, 0x00C26605, 0x00C26E14, 0x00C27643, 0x00C27C44, 0x00C2800C, 0x00C28444, 0x00C3EC11,
+    0x00C3F243, 0x00C3FC44, 0x00C4001D, 0x00C41444, 0x00C4C01D, 0x00C4C444, 0x00C63C1D, 0x00C64015,
+    0x00C6480A, 0x00C65815, 0x00C68044, 0x00C70015, 0x00C7981D, 0x00C7BC15, 0x00C7C044, 0x00C80015,
+    0x00C87C1D, 0x00C8800A, 0x00C8A815, 0x00C9200A, 0x00C94015, 0x00C9440A, 0x00C98015, 0x00CA000A,
+    0x00CA2815, 0x00CAC40A, 0x00CB0015, 0x00D00044, 0x01370015, 0x01380044, 0x02805643, 0x02805844,
+    0x0292341D, 0x02924015, 0x02931C1D, 0x02934044, 0x0293E243, 0x0293F811, 0x02940044, 0x02983243,
+    0x02983411, 0x02984044, 0x02988008, 0x0298A844, 0x0298B01D, 0x029900C0, 0x02990541, 0x029908C0,
+    0x02990D41, 0x029910C0, 0x02991541, 0x029918C0, 0x02991D41, 0x029920C0, 0x02992541, 0x029928C0,
+    0x02992D41, 0x029930C0, 0x02993541, 0x029938C0, 0x02993D41, 0x029940C0, 0x02994541, 0x029948C0,
+    0x02994D41, 0x029950C0, 0x02995541, 0x029958C0, 0x02995D41, 0x029960C0, 0x02996541, 0x029968C0,
+    0x02996D41, 0x029970C0, 0x02997541, 0x029978C0, 0x02997D41, 0x029980C0, 0x02998541, 0x029988C0,
+    0x02998D41, 0x029990C0, 0x02999541, 0x029998C0, 0x02999D41, 0x0299A0C0, 0x0299A541, 0x0299A8C0,
+    0x0299AD41, 0x0299B0C0, 0x0299B541, 0x0299B844, 0x0299BE05, 0x0299C207, 0x0299CC11, 0x0299D245,
+    0x0299F205, 0x0299F811, 0x0299FE43, 0x029A00C0, 0x029A0541, 0x029A08C0, 0x029A0D41, 0x029A10C0,
+    0x029A1541, 0x029A18C0, 0x029A1D41, 0x029A20C0, 0x029A2541, 0x029A28C0, 0x029A2D41, 0x029A30C0,
+    0x029A3541, 0x029A38C0, 0x029A3D41, 0x029A40C0, 0x029A4541, 0x029A48C0, 0x029A4D41, 0x029A50C0,
+    0x029A5541, 0x029A58C0, 0x029A5D41, 0x029A60C0, 0x029A6541, 0x029A68C0, 0x029A6D41, 0x029A7343,
+    0x029A7A45, 0x029A8044, 0x029B9849, 0x029BC205, 0x029BC811, 0x029BE01D, 0x029C0214, 0x029C5E43,
+    0x029C8214, 0x029C88C0, 0x029C8D41, 0x029C90C0, 0x029C9541, 0x029C98C0, 0x029C9D41, 0x029CA0C0,
+    0x029CA541, 0x029CA8C0, 0x029CAD41, 0x029CB0C0, 0x029CB541, 0x029CB8C0, 0x029CBD41, 0x029CC8C0,
+    0x029CCD41, 0x029CD0C0, 0x029CD541, 0x029CD8C0, 0x029CDD41, 0x029CE0C0, 0x029CE541, 0x029CE8C0,
+    0x029CED41, 0x029CF0C0, 0x029CF541, 0x029CF8C0, 0x029CFD41, 0x029D00C0, 0x029D0541, 0x029D08C0,
+    0x029D0D41, 0x029D10C0, 0x029D1541, 0x029D18C0, 0x029D1D41, 0x029D20C0, 0x029D2541, 0x029D28C0,
+    0x029D2D41, 0x029D30C0, 0x029D3541, 0x029D38C0, 0x029D3D41, 0x029D40C0, 0x029D4541, 0x029D48C0,
+    0x029D4D41, 0x029D50C0, 0x029D5541, 0x029D58C0, 0x029D5D41, 0x029D60C0, 0x029D6541, 0x029D68C0,
+    0x029D6D41, 0x029D70C0, 0x029D7541, 0x029D78C0, 0x029D7D41, 0x029D80C0, 0x029D8541, 0x029D88C0,
+    0x029D8D41, 0x029D90C0, 0x029D9541, 0x029D98C0, 0x029D9D41, 0x029DA0C0, 0x029DA541, 0x029DA8C0,
+    0x029DAD41, 0x029DB0C0, 0x029DB541, 0x029DB8C0, 0x029DBD41, 0x029DC343, 0x029DC541, 0x029DE4C0,
+    0x029DE941, 0x029DECC0, 0x029DF141, 0x029DF4C0, 0x029DFD41, 0x029E00C0, 0x029E0541, 0x029E08C0,
+    0x029E0D41, 0x029E10C0, 0x029E1541, 0x029E18C0, 0x029E1D41, 0x029E2243, 0x029E2614, 0x029E2CC0,
+    0x029E3141, 0x029E34C0, 0x029E3941, 0x029E3C44, 0x029E40C0, 0x029E4541, 0x029E48C0, 0x029E4D41,
+    0x029E58C0, 0x029E5D41, 0x029E60C0, 0x029E6541, 0x029E68C0, 0x029E6D41, 0x029E70C0, 0x029E7541,
+    0x029E78C0, 0x029E7D41, 0x029E80C0, 0x029E8541, 0x029E88C0, 0x029E8D41, 0x029E90C0, 0x029E9541,
+    0x029E98C0, 0x029E9D41, 0x029EA0C0, 0x029EA541, 0x029EA8C0, 0x029EBD41, 0x029EC0C0, 0x029ED541,
+    0x029ED8C0, 0x029EDD41, 0x029EE0C0, 0x029EE541, 0x029EE8C0, 0x029EED41, 0x029EF0C0, 0x029EF541,
+    0x029EF8C0, 0x029EFD41, 0x029F00C0, 0x029F0541, 0x029F08C0, 0x029F0D41, 0x029F10C0, 0x029F2141,
+    0x029F24C0, 0x029F2941, 0x029F2CC0, 0x029F3541, 0x029F38C0, 0x029F3D41, 0x029F40C0, 0x029F4541,
+    0x029F48C0, 0x029F4D41, 0x029F50C0, 0x029F5541, 0x029F58C0, 0x029F5D41, 0x029F60C0, 0x029F6541,
+    0x029F68C0, 0x029F6D41, 0x029F70C0, 0x029F741D, 0x029FC743, 0x029FD4C0, 0x029FD941, 0x029FDC44,
+    0x029FE343, 0x029FE941, 0x029FEC44, 0x02A00A45, 0x02A00C44, 0x02A01A05, 0x02A01C44, 0x02A02E45,
+    0x02A03044, 0x02A08C46, 0x02A09645, 0x02A09C46, 0x02A0A015, 0x02A0B205, 0x02A0B41D, 0x02A0C00A,
+    0x02A0D815, 0x02A0E013, 0x02A0E415, 0x02A0E81D, 0x02A10044, 0x02A1D011, 0x02A1E01D, 0x02A20046,
+    0x02A20844, 0x02A2D046, 0x02A31205, 0x02A31645, 0x02A3181D, 0x02A33811, 0x02A34008, 0x02A3681D,
+    0x02A38205, 0x02A3C844, 0x02A3E011, 0x02A3EC44, 0x02A3F011, 0x02A3F444, 0x02A3FE45, 0x02A40008,
+    0x02A42844, 0x02A49A45, 0x02A4AE05, 0x02A4B811, 0x02A4C044, 0x02A51E45, 0x02A54846, 0x02A54C06,
+    0x02A5501D, 0x02A57C11, 0x02A58044, 0x02A5F41D, 0x02A60245, 0x02A60C46, 0x02A61044, 0x02A6CE05,
+    0x02A6D046, 0x02A6DA45, 0x02A6E846, 0x02A6F245, 0x02A6F846, 0x02A70006, 0x02A70411, 0x02A7381D,
+    0x02A73E43, 0x02A74008, 0x02A7681D, 0x02A77811, 0x02A78044, 0x02A79645, 0x02A79A43, 0x02A79C44,
+    0x02A7C008, 0x02A7E844, 0x02A7FC1D, 0x02A80044, 0x02A8A645, 0x02A8BC46, 0x02A8C645, 0x02A8CC46,
+    0x02A8D645, 0x02A8DC1D, 0x02A90044, 0x02A90E45, 0x02A91044, 0x02A93245, 0x02A93446, 0x02A9381D,
+    0x02A94008, 0x02A9681D, 0x02A97011, 0x02A98044, 0x02A9C243, 0x02A9C444, 0x02A9DC15, 0x02A9E844,
+    0x02A9EC46, 0x02A9F245, 0x02A9F446, 0x02A9F844, 0x02AAC245, 0x02AAC444, 0x02AACA45, 0x02AAD444,
+    0x02AADE45, 0x02AAE444, 0x02AAFA45, 0x02AAFE05, 0x02AB0044, 0x02AB0605, 0x02AB0844, 0x02AB0C1D,
+    0x02AB6C44, 0x02AB7643, 0x02AB7811, 0x02AB8044, 0x02ABAC46, 0x02ABB245, 0x02ABB846, 0x02ABC011,
+    0x02ABC844, 0x02ABCE43, 0x02ABD446, 0x02ABDA05, 0x02ABDC1D, 0x02AC0444, 0x02AC1C1D, 0x02AC2444,
+    0x02AC3C1D, 0x02AC4444, 0x02AC5C1D, 0x02AC8044, 0x02AC9C1D, 0x02ACA044, 0x02ACBC1D, 0x02ACC141,
+    0x02AD6E14, 0x02AD7343, 0x02AD8141, 0x02ADA743, 0x02ADAA14, 0x02ADB01D, 0x02ADC141, 0x02AF0044,
+    0x02AF8C46, 0x02AF9645, 0x02AF9846, 0x02AFA245, 0x02AFA446, 0x02AFAC11, 0x02AFB006, 0x02AFB605,
+    0x02AFB81D, 0x02AFC008, 0x02AFE81D, 0x02B00044, 0x035E901D, 0x035EC044, 0x035F1C1D, 0x035F2C44,
+    0x035FF01D, 0x0360001B, 0x0380001C, 0x03E40044, 0x03E9B81D, 0x03E9C044, 0x03EB681D, 0x03EC0141,
+    0x03EC1C1D, 0x03EC4D41, 0x03EC601D, 0x03EC7444, 0x03EC7A45, 0x03EC7C44, 0x03ECA412, 0x03ECA844,
+    0x03ECDC1D, 0x03ECE044, 0x03ECF41D, 0x03ECF844, 0x03ECFC1D, 0x03ED0044, 0x03ED081D, 0x03ED0C44,
+    0x03ED141D, 0x03ED1844, 0x03EECA14, 0x03EF0C15, 0x03EF4C44, 0x03F4F80E, 0x03F4FC0D, 0x03F50015,
+    0x03F54044, 0x03F64015, 0x03F64844, 0x03F72015, 0x03F7401D, 0x03F7C044, 0x03F7F013, 0x03F7F415,
+    0x03F80205, 0x03F84011, 0x03F84E11, 0x03F85011, 0x03F85C0D, 0x03F8600E, 0x03F86411, 0x03F8681D,
+    0x03F88205, 0x03F8C011, 0x03F8C40C, 0x03F8CC0B, 0x03F8D40D, 0x03F8D80E, 0x03F8DC0D, 0x03F8E00E,
+    0x03F8E40D, 0x03F8E80E, 0x03F8EC0D, 0x03F8F00E, 0x03F8F40D, 0x03F8F80E, 0x03F8FC0D, 0x03F9000E,
+    0x03F9040D, 0x03F9080E, 0x03F90C0D, 0x03F9100E, 0x03F91411, 0x03F91C0D, 0x03F9200E, 0x03F92411,
+    0x03F9340B, 0x03F94011, 0x03F94A11, 0x03F94C1D, 0x03F95011, 0x03F95611, 0x03F95811, 0x03F9600C,
+    0x03F9640D, 0x03F9680E, 0x03F96C0D, 0x03F9700E, 0x03F9740D, 0x03F9780E, 0x03F97C11, 0x03F98812,
+    0x03F98C0C, 0x03F99012, 0x03F99C1D, 0x03F9A011, 0x03F9A413, 0x03F9A811, 0x03F9B01D, 0x03F9C044,
+    0x03F9D41D, 0x03F9D844, 0x03FBF41D, 0x03FBFE1A, 0x03FC001D, 0x03FC0411, 0x03FC1013, 0x03FC1411,
+    0x03FC1E11, 0x03FC200D, 0x03FC240E, 0x03FC2811, 0x03FC2C12, 0x03FC3011, 0x03FC340C, 0x03FC3A11,
+    0x03FC3C11, 0x03FC4008, 0x03FC6A11, 0x03FC6C11, 0x03FC7012, 0x03FC7C11, 0x03FC84C0, 0x03FCEC0D,
+    0x03FCF011, 0x03FCF40E, 0x03FCFA14, 0x03FCFC0B, 0x03FD0214, 0x03FD0541, 0x03FD6C0D, 0x03FD7012,
+    0x03FD740E, 0x03FD7812, 0x03FD7C0D, 0x03FD800E, 0x03FD8411, 0x03FD880D, 0x03FD8C0E, 0x03FD9011,
+    0x03FD9844, 0x03FDC243, 0x03FDC444, 0x03FE7A43, 0x03FE8044, 0x03FEFC1D, 0x03FF0844, 0x03FF201D,
+    0x03FF2844, 0x03FF401D, 0x03FF4844, 0x03FF601D, 0x03FF6844, 0x03FF741D, 0x03FF8013, 0x03FF8812,
+    0x03FF8E14, 0x03FF9015, 0x03FF9413, 0x03FF9C1D, 0x03FFA015, 0x03FFA412, 0x03FFB415, 0x03FFBC1D,
+    0x03FFE61A, 0x03FFF015, 0x03FFF81D, 0x04000044, 0x0400301D, 0x04003444, 0x04009C1D, 0x0400A044,
+    0x0400EC1D, 0x0400F044, 0x0400F81D, 0x0400FC44, 0x0401381D, 0x04014044, 0x0401781D, 0x04020044,
+    0x0403EC1D, 0x04040011, 0x04040C1D, 0x04041C0A, 0x0404D01D, 0x0404DC15, 0x04050049, 0x0405D40A,
+    0x0405E415, 0x0406280A, 0x04063015, 0x04063C1D, 0x04064015, 0x0406741D, 0x04068015, 0x0406841D,
+    0x04074015, 0x0407F605, 0x0407F81D, 0x040A0044, 0x040A741D, 0x040A8044, 0x040B441D, 0x040B8205,
+    0x040B840A, 0x040BF01D, 0x040C0044, 0x040C800A, 0x040C901D, 0x040CB444, 0x040D0449, 0x040D0844,
+    0x040D2849, 0x040D2C1D, 0x040D4044, 0x040DDA45, 0x040DEC1D, 0x040E0044, 0x040E781D, 0x040E7C11,
+    0x040E8044, 0x040F101D, 0x040F2044, 0x040F4011, 0x040F4449, 0x040F581D, 0x041000C0, 0x0410A141,
+    0x04114044, 0x0412781D, 0x04128008, 0x0412A81D, 0x0412C0C0, 0x0413501D, 0x04136141, 0x0413F01D,
+    0x04140044, 0x0414A01D, 0x0414C044, 0x0415901D, 0x0415BC11, 0x0415C0C0, 0x0415EC1D, 0x0415F0C0,
+    0x04162C1D, 0x041630C0, 0x04164C1D, 0x041650C0, 0x0416581D, 0x04165D41, 0x0416881D, 0x04168D41,
+    0x0416C81D, 0x0416CD41, 0x0416E81D, 0x0416ED41, 0x0416F41D, 0x04170044, 0x0417D01D, 0x04180044,
+    0x041CDC1D, 0x041D0044, 0x041D581D, 0x041D8044, 0x041DA01D, 0x041E0343, 0x041E0643, 0x041E0F43,
+    0x041E181D, 0x041E1F43, 0x041EC41D, 0x041ECB43, 0x041EEC1D, 0x04200044, 0x0420181D, 0x04202044,
+    0x0420241D, 0x04202844, 0x0420D81D, 0x0420DC44, 0x0420E41D, 0x0420F044, 0x0420F41D, 0x0420FC44,
+    0x0421581D, 0x04215C11, 0x0421600A, 0x04218044, 0x0421DC15, 0x0421E40A, 0x04220044, 0x04227C1D,
+    0x04229C0A, 0x0422C01D, 0x04238044, 0x0423CC1D, 0x0423D044, 0x0423D81D, 0x0423EC0A, 0x04240044,
+    0x0424580A, 0x0424701D, 0x04247C11, 0x04248044, 0x0424E81D, 0x0424FC11, 0x04250044, 0x0425681D,
+    0x04260044, 0x0426E01D, 0x0426F00A, 0x0426F844, 0x0427000A, 0x0427401D, 0x0427480A, 0x04280044,
+    0x04280645, 0x0428101D, 0x04281645, 0x04281C1D, 0x04283245, 0x04284044, 0x0428501D, 0x04285444,
+    0x0428601D, 0x04286444, 0x0428D81D, 0x0428E205, 0x0428EC1D, 0x0428FE05, 0x0429000A, 0x0429241D,
+    0x04294011, 0x0429641D, 0x04298044, 0x0429F40A, 0x0429FC11, 0x042A0044, 0x042A740A, 0x042A801D,
+    0x042B0044, 0x042B2015, 0x042B2444, 0x042B9605, 0x042B9C1D, 0x042BAC0A, 0x042BC011, 0x042BDC1D,
+    0x042C0044, 0x042CD81D, 0x042CE411, 0x042D0044, 0x042D581D, 0x042D600A, 0x042D8044, 0x042DCC1D,
+    0x042DE00A, 0x042E0044, 0x042E481D, 0x042E6411, 0x042E741D, 0x042EA40A, 0x042EC01D, 0x04300044,
+    0x0431241D, 0x043200C0, 0x0432CC1D, 0x04330141, 0x0433CC1D, 0x0433E80A, 0x04340044, 0x04349245,
+    0x0434A01D, 0x0434C008, 0x0434E81D, 0x04350008, 0x04352844, 0x04353A43, 0x04353C44, 0x043540C0,
+    0x0435981D, 0x0435A645, 0x0435AA05, 0x0435B80C, 0x0435BE43, 0x0435C141, 0x0436181D, 0x04363812,
+    0x0436401D, 0x0439800A, 0x0439FC1D, 0x043A0044, 0x043AA81D, 0x043AAE45, 0x043AB40C, 0x043AB81D,
+    0x043AC044, 0x043AC81D, 0x043B0844, 0x043B1643, 0x043B1844, 0x043B201D, 0x043B4011, 0x043B4415,
+    0x043B641D, 0x043BEA45, 0x043BF605, 0x043C0044, 0x043C740A, 0x043C9C44, 0x043CA01D, 0x043CC044,
+    0x043D1A05, 0x043D440A, 0x043D5411, 0x043D681D, 0x043DC044, 0x043E0A05, 0x043E1811, 0x043E281D,
+    0x043EC044, 0x043F140A, 0x043F301D, 0x043F8044, 0x043FDC1D, 0x04400046, 0x04400645, 0x04400846,
+    0x04400C44, 0x0440E245, 0x04411A05, 0x04411C11, 0x0441381D, 0x0441480A, 0x04419808, 0x0441C205,
+    0x0441C444, 0x0441CE45, 0x0441D444, 0x0441D81D, 0x0441FE05, 0x04420245, 0x04420846, 0x04420C44,
+    0x0442C046, 0x0442CE45, 0x0442DC46, 0x0442E605, 0x0442EC11, 0x0442F61A, 0x0442F811, 0x04430A45,
+    0x04430C1D, 0x0443361A, 0x0443381D, 0x04434044, 0x0443A41D, 0x0443C008, 0x0443E81D, 0x04440245,
+    0x04440C44, 0x04449E45, 0x0444B046, 0x0444B645, 0x0444CE05, 0x0444D41D, 0x0444D808, 0x04450011,
+    0x04451044, 0x04451446, 0x04451C44, 0x0445201D, 0x04454044, 0x0445CE05, 0x0445D011, 0x0445D844,
+    0x0445DC1D, 0x04460245, 0x04460846, 0x04460C44, 0x0446CC46, 0x0446DA45, 0x0446FC46, 0x04470006,
+    0x04470444, 0x04471411, 0x04472605, 0x04473411, 0x04473846, 0x04473E45, 0x04474008, 0x04476844,
+    0x04476C11, 0x04477044, 0x04477411, 0x0447801D, 0x0447840A, 0x0447D41D, 0x04480044, 0x0448481D,
+    0x04484C44, 0x0448B046, 0x0448BE45, 0x0448C846, 0x0448D245, 0x0448D406, 0x0448DA05, 0x0448DE45,
+    0x0448E011, 0x0448FA45, 0x0448FC44, 0x04490645, 0x0449081D, 0x044A0044, 0x044A1C1D, 0x044A2044,
+    0x044A241D, 0x044A2844, 0x044A381D, 0x044A3C44, 0x044A781D, 0x044A7C44, 0x044AA411, 0x044AA81D,
+    0x044AC044, 0x044B7E45, 0x044B8046, 0x044B8E45, 0x044BA605, 0x044BAC1D, 0x044BC008, 0x044BE81D,
+    0x044C0245, 0x044C0846, 0x044C101D, 0x044C1444, 0x044C341D, 0x044C3C44, 0x044C441D, 0x044C4C44,
+    0x044CA41D, 0x044CA844, 0x044CC41D, 0x044CC844, 0x044CD01D, 0x044CD444, 0x044CE81D, 0x044CEE05,
+    0x044CF444, 0x044CF846, 0x044D0245, 0x044D0446, 0x044D141D, 0x044D1C46, 0x044D241D, 0x044D2C46,
+    0x044D3406, 0x044D381D, 0x044D4044, 0x044D441D, 0x044D5C46, 0x044D601D, 0x044D7444, 0x044D8846,
+    0x044D901D, 0x044D9A05, 0x044DB41D, 0x044DC205, 0x044DD41D, 0x044E0044, 0x044E281D, 0x044E2C44,
+    0x044E301D, 0x044E3844, 0x044E3C1D, 0x044E4044, 0x044ED81D, 0x044EDC44, 0x044EE046, 0x044EEE45,
+    0x044F041D, 0x044F0846, 0x044F0C1D, 0x044F1446, 0x044F181D, 0x044F1C46, 0x044F2C1D, 0x044F3046,
+    0x044F3A05, 0x044F3C06, 0x044F4205, 0x044F4444, 0x044F4A05, 0x044F4C44, 0x044F5011, 0x044F581D,
+    0x044F5C11, 0x044F641D, 0x044F8605, 0x044F8C1D, 0x04500044, 0x0450D446, 0x0450E245, 0x04510046,
+    0x04510A05, 0x04510E45, 0x04511446, 0x04511A05, 0x04511C44, 0x04512C11, 0x04514008, 0x04516811,
+    0x0451701D, 0x04517411, 0x04517A05, 0x04517C44, 0x0451881D, 0x04520044, 0x0452C046, 0x0452CE45,
+    0x0452E446, 0x0452EA45, 0x0452EC46, 0x0452FE45, 0x04530446, 0x04530A05, 0x04531044, 0x04531811,
+    0x04531C44, 0x0453201D, 0x04534008, 0x0453681D, 0x04560044, 0x0456BC46, 0x0456CA45, 0x0456D81D,
+    0x0456E046, 0x0456F245, 0x0456F846, 0x0456FE05, 0x04570411, 0x04576044, 0x04577245, 0x0457781D,
+    0x04580044, 0x0458C046, 0x0458CE45, 0x0458EC46, 0x0458F645, 0x0458F846, 0x0458FE05, 0x04590245,
+    0x04590411, 0x04591044, 0x0459141D, 0x04594008, 0x0459681D, 0x04598011, 0x0459B41D, 0x045A0044,
+    0x045AAE45, 0x045AB046, 0x045AB645, 0x045AB846, 0x045AC245, 0x045AD806, 0x045ADE05, 0x045AE044,
+    0x045AE411, 0x045AE81D, 0x045B0008, 0x045B281D, 0x045B4008, 0x045B901D, 0x045C0044, 0x045C6C1D,
+    0x045C7645, 0x045C7846, 0x045C7E45, 0x045C8046, 0x045C8A45, 0x045C9846, 0x045C9E45, 0x045CAE05,
+    0x045CB01D, 0x045CC008, 0x045CE80A, 0x045CF011, 0x045CFC15, 0x045D0044, 0x045D1C1D, 0x04600044,
+    0x0460B046, 0x0460BE45, 0x0460E046, 0x0460E605, 0x0460EC11, 0x0460F01D, 0x046280C0, 0x04630141,
+    0x04638008, 0x0463A80A, 0x0463CC1D, 0x0463FC44, 0x04641C1D, 0x04642444, 0x0464281D, 0x04643044,
+    0x0464501D, 0x04645444, 0x04645C1D, 0x04646044, 0x0464C046, 0x0464D81D, 0x0464DC46, 0x0464E41D,
+    0x0464EE45, 0x0464F406, 0x0464FA05, 0x0464FC44, 0x04650046, 0x04650444, 0x04650846, 0x04650E05,
+    0x04651011, 0x04651C1D, 0x04654008, 0x0465681D, 0x04668044, 0x0466A01D, 0x0466A844, 0x04674446,
+    0x04675245, 0x0467601D, 0x04676A45, 0x04677046, 0x04678205, 0x04678444, 0x04678811, 0x04678C44,
+    0x04679046, 0x0467941D, 0x04680044, 0x04680645, 0x04682C44, 0x0468CE05, 0x0468D645, 0x0468E446,
+    0x0468E844, 0x0468EE45, 0x0468FC11, 0x04691E05, 0x0469201D, 0x04694044, 0x04694645, 0x04695C46,
+    0x04696645, 0x04697044, 0x046A2A45, 0x046A5C46, 0x046A6205, 0x046A6811, 0x046A7444, 0x046A7811,
+    0x046A8C1D, 0x046AC044, 0x046BE41D, 0x046C0011, 0x046C281D, 0x046D8245, 0x046D8446, 0x046D8A45,
+    0x046D9446, 0x046D9A45, 0x046D9C46, 0x046DA01D, 0x046F0044, 0x046F8411, 0x046F881D, 0x046FC008,
+    0x046FE81D, 0x04700044, 0x0470241D, 0x04702844, 0x0470BC46, 0x0470C245, 0x0470DC1D, 0x0470E245,
+    0x0470F846, 0x0470FE05, 0x04710044, 0x04710411, 0x0471181D, 0x04714008, 0x0471680A, 0x0471B41D,
+    0x0471C011, 0x0471C844, 0x0472401D, 0x04724A45, 0x0472A01D, 0x0472A446, 0x0472AA45, 0x0472C446,
+    0x0472CA45, 0x0472D046, 0x0472D645, 0x0472DC1D, 0x04740044, 0x04741C1D, 0x04742044, 0x0474281D,
+    0x04742C44, 0x0474C645, 0x0474DC1D, 0x0474EA45, 0x0474EC1D, 0x0474F245, 0x0474F81D, 0x0474FE45,
+    0x04750A05, 0x04750E45, 0x04751205, 0x04751844, 0x04751E45, 0x0475201D, 0x04754008, 0x0475681D,
+    0x04758044, 0x0475981D, 0x04759C44, 0x0475A41D, 0x0475A844, 0x04762846, 0x04763C1D, 0x04764245,
+    0x0476481D, 0x04764C46, 0x04765645, 0x04765846, 0x04765E05, 0x04766044, 0x0476641D, 0x04768008,
+    0x0476A81D, 0x0476C044, 0x04776643, 0x04776844, 0x0477701D, 0x04778008, 0x0477A81D, 0x047B8044,
+    0x047BCE45, 0x047BD446, 0x047BDC11, 0x047BE41D, 0x047C0245, 0x047C0844, 0x047C0C46, 0x047C1044,
+    0x047C441D, 0x047C4844, 0x047CD046, 0x047CDA45, 0x047CEC1D, 0x047CF846, 0x047D0245, 0x047D0406,
+    0x047D0A05, 0x047D0C11, 0x047D4008, 0x047D6A05, 0x047D6C1D, 0x047EC044, 0x047EC41D, 0x047F000A,
+    0x047F5415, 0x047F7413, 0x047F8415, 0x047FC81D, 0x047FFC11, 0x04800044, 0x048E681D, 0x04900049,
+    0x0491BC1D, 0x0491C011, 0x0491D41D, 0x04920044, 0x0495101D, 0x04BE4044, 0x04BFC411, 0x04BFCC1D,
+    0x04C00044, 0x04D0C21A, 0x04D10205, 0x04D10444, 0x04D11E05, 0x04D1581D, 0x04D18044, 0x050FEC1D,
+    0x05100044, 0x05191C1D, 0x05840044, 0x05847A45, 0x0584A846, 0x0584B645, 0x0584BE05, 0x0584C008,
+    0x0584E81D, 0x05A00044, 0x05A8E41D, 0x05A90044, 0x05A97C1D, 0x05A98008, 0x05A9A81D, 0x05A9B811,
+    0x05A9C044, 0x05AAFC1D, 0x05AB0008, 0x05AB281D, 0x05AB4044, 0x05ABB81D, 0x05ABC205, 0x05ABD411,
+    0x05ABD81D, 0x05AC0044, 0x05ACC205, 0x05ACDC11, 0x05ACF015, 0x05AD0243, 0x05AD1011, 0x05AD1415,
+    0x05AD181D, 0x05AD4008, 0x05AD681D, 0x05AD6C0A, 0x05AD881D, 0x05AD8C44, 0x05ADE01D, 0x05ADF444,
+    0x05AE401D, 0x05B50243, 0x05B50C44, 0x05B5AE43, 0x05B5B411, 0x05B5C008, 0x05B5E81D, 0x05B900C0,
+    0x05B98141, 0x05BA000A, 0x05BA5C11, 0x05BA6C1D, 0x05BA80C0, 0x05BAE41D, 0x05BAED41, 0x05BB501D,
+    0x05BC0044, 0x05BD2C1D, 0x05BD3E45, 0x05BD4044, 0x05BD4446, 0x05BE201D, 0x05BE3E45, 0x05BE4E43,
+    0x05BE801D, 0x05BF8243, 0x05BF8811, 0x05BF8E43, 0x05BF9205, 0x05BF941D, 0x05BFC046, 0x05BFCA43,
+    0x05BFD049, 0x05BFDC1D, 0x05C00044, 0x0633581D, 0x0633FC44, 0x06347C1D, 0x06360044, 0x0637CC1D,
+    0x06BFC243, 0x06BFD01D, 0x06BFD643, 0x06BFF01D, 0x06BFF643, 0x06BFFC1D, 0x06C00044, 0x06C48C1D,
+    0x06C4C844, 0x06C4CC1D, 0x06C54044, 0x06C54C1D, 0x06C55444, 0x06C5581D, 0x06C59044, 0x06C5A01D,
+    0x06C5C044, 0x06CBF01D, 0x06F00044, 0x06F1AC1D, 0x06F1C044, 0x06F1F41D, 0x06F20044, 0x06F2241D,
+    0x06F24044, 0x06F2681D, 0x06F27015, 0x06F27605, 0x06F27A45, 0x06F27C11, 0x06F2821A, 0x06F2901D,
+    0x07300015, 0x0733C008, 0x0733E815, 0x0733F41D, 0x07340015, 0x073AD01D, 0x073AE815, 0x073B441D,
+    0x073B8015, 0x073BC012, 0x073BC41D, 0x073C0205, 0x073CB81D, 0x073CC205, 0x073D1C1D, 0x073D4015,
+    0x073F101D, 0x07400015, 0x0743D81D, 0x07440015, 0x07449C1D, 0x0744A415, 0x07459406, 0x07459E05,
+    0x0745A815, 0x0745B406, 0x0745CE1A, 0x0745EE05, 0x07460C15, 0x07461605, 0x07463015, 0x0746AA05,
+    0x0746B815, 0x0747AC1D, 0x07480015, 0x07490A05, 0x07491415, 0x0749181D, 0x074B000A, 0x074B501D,
+    0x074B800A, 0x074BD01D, 0x074C0015, 0x074D5C1D, 0x074D800A, 0x074DE41D, 0x075000C0, 0x07506941,
+    0x0750D0C0, 0x07513941, 0x0751541D, 0x07515941, 0x0751A0C0, 0x07520941, 0x075270C0, 0x0752741D,
+    0x075278C0, 0x0752801D, 0x075288C0, 0x07528C1D, 0x075294C0, 0x07529C1D, 0x0752A4C0, 0x0752B41D,
+    0x0752B8C0, 0x0752D941, 0x0752E81D, 0x0752ED41, 0x0752F01D, 0x0752F541, 0x0753101D, 0x07531541,
+    0x075340C0, 0x0753A941, 0x075410C0, 0x0754181D, 0x07541CC0, 0x07542C1D, 0x075434C0, 0x0754541D,
+    0x075458C0, 0x0754741D, 0x07547941, 0x0754E0C0, 0x0754E81D, 0x0754ECC0, 0x0754FC1D, 0x075500C0,
+    0x0755141D, 0x075518C0, 0x07551C1D, 0x075528C0, 0x0755441D, 0x07554941, 0x0755B0C0, 0x07561941,
+    0x075680C0, 0x0756E941, 0x075750C0, 0x0757B941, 0x075820C0, 0x07588941, 0x0758F0C0, 0x07595941,
+    0x0759C0C0, 0x075A2941, 0x075A981D, 0x075AA0C0, 0x075B0412, 0x075B0941, 0x075B6C12, 0x075B7141,
+    0x075B88C0, 0x075BEC12, 0x075BF141, 0x075C5412, 0x075C5941, 0x075C70C0, 0x075CD412, 0x075CD941,
+    0x075D3C12, 0x075D4141, 0x075D58C0, 0x075DBC12, 0x075DC141, 0x075E2412, 0x075E2941, 0x075E40C0,
+    0x075EA412, 0x075EA941, 0x075F0C12, 0x075F1141, 0x075F28C0, 0x075F2D41, 0x075F301D, 0x075F3808,
+    0x07600015, 0x07680205, 0x0768DC15, 0x0768EE05, 0x0769B415, 0x0769D605, 0x0769D815, 0x076A1205,
+    0x076A1415, 0x076A1C11, 0x076A301D, 0x076A6E05, 0x076A801D, 0x076A8605, 0x076AC01D, 0x077C0141,
+    0x077C2844, 0x077C2D41, 0x077C7C1D, 0x077C9541, 0x077CAC1D, 0x07800245, 0x07801C1D, 0x07802245,
+    0x0780641D, 0x07806E45, 0x0780881D, 0x07808E45, 0x0780941D, 0x07809A45, 0x0780AC1D, 0x0780C343,
+    0x0781B81D, 0x07823E45, 0x0782401D, 0x07840044, 0x0784B41D, 0x0784C205, 0x0784DE43, 0x0784F81D,
+    0x07850008, 0x0785281D, 0x07853844, 0x07853C15, 0x0785401D, 0x078A4044, 0x078ABA05, 0x078ABC1D,
+    0x078B0044, 0x078BB205, 0x078BC008, 0x078BE81D, 0x078BFC13, 0x078C001D, 0x07934044, 0x0793AE43,
+    0x0793B205, 0x0793C008, 0x0793E81D, 0x07974044, 0x0797BA05, 0x0797C044, 0x0797C408, 0x0797EC1D,
+    0x0797FC11, 0x0798001D, 0x079B0044, 0x079B7C1D, 0x079B8044, 0x079B8E45, 0x079B9044, 0x079B9A45,
+    0x079B9C44, 0x079BBA45, 0x079BC044, 0x079BD645, 0x079BD81D, 0x079BF844, 0x079BFE43, 0x079C001D,
+    0x079F8044, 0x079F9C1D, 0x079FA044, 0x079FB01D, 0x079FB444, 0x079FBC1D, 0x079FC044, 0x079FFC1D,
+    0x07A00044, 0x07A3141D, 0x07A31C0A, 0x07A34205, 0x07A35C1D, 0x07A400C0, 0x07A48941, 0x07A51205,
+    0x07A51E45, 0x07A52205, 0x07A52E43, 0x07A5301D, 0x07A54008, 0x07A5681D, 0x07A57811, 0x07A5801D,
+    0x07B1C40A, 0x07B2B015, 0x07B2B40A, 0x07B2C013, 0x07B2C40A, 0x07B2D41D, 0x07B4040A, 0x07B4B815,
+    0x07B4BC0A, 0x07B4F81D, 0x07B80044, 0x07B8101D, 0x07B81444, 0x07B8801D, 0x07B88444, 0x07B88C1D,
+    0x07B89044, 0x07B8941D, 0x07B89C44, 0x07B8A01D, 0x07B8A444, 0x07B8CC1D, 0x07B8D044, 0x07B8E01D,
+    0x07B8E444, 0x07B8E81D, 0x07B8EC44, 0x07B8F01D, 0x07B90844, 0x07B90C1D, 0x07B91C44, 0x07B9201D,
+    0x07B92444, 0x07B9281D, 0x07B92C44, 0x07B9301D, 0x07B93444, 0x07B9401D, 0x07B94444, 0x07B94C1D,
+    0x07B95044, 0x07B9541D, 0x07B95C44, 0x07B9601D, 0x07B96444, 0x07B9681D, 0x07B96C44, 0x07B9701D,
+    0x07B97444, 0x07B9781D, 0x07B97C44, 0x07B9801D, 0x07B98444, 0x07B98C1D, 0x07B99044, 0x07B9941D,
+    0x07B99C44, 0x07B9AC1D, 0x07B9B044, 0x07B9CC1D, 0x07B9D044, 0x07B9E01D, 0x07B9E444, 0x07B9F41D,
+    0x07B9F844, 0x07B9FC1D, 0x07BA0044, 0x07BA281D, 0x07BA2C44, 0x07BA701D, 0x07BA8444, 0x07BA901D,
+    0x07BA9444, 0x07BAA81D, 0x07BAAC44, 0x07BAF01D, 0x07BBC012, 0x07BBC81D, 0x07C00015, 0x07C0B01D,
+    0x07C0C015, 0x07C2501D, 0x07C28015, 0x07C2BC1D, 0x07C2C415, 0x07C3001D, 0x07C30415, 0x07C3401D,
+    0x07C34415, 0x07C3D81D, 0x07C4000A, 0x07C43415, 0x07C4C0D5, 0x07C52815, 0x07C540D5, 0x07C5A815,
+    0x07C5C0D5, 0x07C62815, 0x07C6B81D, 0x07C79815, 0x07C80C1D, 0x07C84015, 0x07C8F01D, 0x07C90015,
+    0x07C9241D, 0x07C94015, 0x07C9481D, 0x07C98015, 0x07C9981D, 0x07CC0015, 0x07CFEE14, 0x07D00015,
+    0x07DB641D, 0x07DB7015, 0x07DBB41D, 0x07DBC015, 0x07DBF41D, 0x07DC0015, 0x07DF681D, 0x07DF8015,
+    0x07DFB01D, 0x07DFC015, 0x07DFC41D, 0x07E00015, 0x07E0301D, 0x07E04015, 0x07E1201D, 0x07E14015,
+    0x07E1681D, 0x07E18015, 0x07E2201D, 0x07E24015, 0x07E2B81D, 0x07E2C015, 0x07E2F01D, 0x07E30015,
+    0x07E3081D, 0x07E34012, 0x07E3641D, 0x07E40015, 0x07E9601D, 0x07E98015, 0x07E9B81D, 0x07E9C015,
+    0x07E9F41D, 0x07EA0015, 0x07EA2C1D, 0x07EA3815, 0x07EB1C1D, 0x07EB2015, 0x07EB241D, 0x07EB3415,
+    0x07EB741D, 0x07EB7C15, 0x07EBAC1D, 0x07EBBC15, 0x07EBE41D, 0x07EC0015, 0x07EE4C1D, 0x07EE5015,
+    0x07EFC008, 0x07EFE815, 0x07EFEC1D, 0x08000044, 0x0A9B801D, 0x0A9C0044, 0x0AE0781D, 0x0AE08044,
+    0x0B3AB81D, 0x0B3AC044, 0x0BAF841D, 0x0BAFC044, 0x0BB9781D, 0x0BE00044, 0x0BE8781D, 0x0C000044,
+    0x0C4D2C1D, 0x0C4D4044, 0x0CD1E81D, 0x3800061A, 0x3800081D, 0x3800821A, 0x3802001D, 0x38040205,
+    0x3807C01D, 0x3C00001C, 0x3FFFF81D, 0x4000001C, 0x43FFF81D,
+];
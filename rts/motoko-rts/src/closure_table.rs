@@ -1,8 +1,8 @@
 //! This file implements the data structure the Motoko runtime uses to keep track of outstanding
 //! closures. It needs to support the following operations
 //!
-//!  1. Adding a closure (any heap pointer) and getting an index (i32)
-//!  2. Looking up a closure by index, which also frees it
+//!  1. Adding a closure (any heap pointer) and getting a handle (i32)
+//!  2. Looking up a closure by handle, which also frees it
 //!  3. GC must be able to traverse and move closures in the table
 //!
 //! Current implementation stores the closures in heap-allocated Motoko array.
@@ -12,124 +12,313 @@
 //! next free item, shifted 2 bits to the left (to make the index a scalar and traverse them in
 //! GC).
 //!
-//! The last item will have value `TABLE.len() << 2`, so after adding a closure to the last free
+//! The last item will have value `table_size << 2`, so after adding a closure to the last free
 //! slot `FREE_SLOT` will be `table_size`, which is when we see that the array is full.
 //!
 //! When the table is full, we double the size, copy the existing table, and add the second half to
-//! the free list. Since all indices are relative to the payload begin, they stay valid. We never
-//! shrink the table.
+//! the free list. Since all indices are relative to the payload begin, they stay valid. The table
+//! has at most `MAX_SIZE` slots.
+//!
+//! When the number of closures drops to a quarter of the size, we shrink the table: we halve the
+//! size (not below `INITIAL_SIZE`) as long as the table stays at most half full and no closure is
+//! in the dropped half, copy the rest, and rebuild the free list in ascending order. When a closure
+//! is in the dropped half, only the free list is rebuilt, so that the next closures take the lowest
+//! free slots and the high slots free up. We try again when the number of closures halved again,
+//! so that the scans for shrinking take amortized constant time.
+//!
+//! Every slot also has a stamp, the number of closures remembered up to its closure, in the second
+//! half of the array. The low `GENERATION_BITS` of the stamp are the generation of the closure, in
+//! the handle above the index:
+//!
+//! ```text
+//! handle = generation << INDEX_BITS | index
+//! ```
+//!
+//! Handles have 30 bits, so that they can be stored as scalars (e.g. by `finalizers.rs`).
+//!
+//! As slots are reused, a stale handle (e.g. of a duplicated reply) has a different generation
+//! than the closure in its slot, and recalling it traps rather than calling the wrong closure,
+//! unless a multiple of `1 << GENERATION_BITS` closures were remembered in between. The age of a
+//! closure is the number of closures remembered after it (see `closure_age`), closures that get
+//! old are callbacks that are probably never called.
 
-use crate::alloc::alloc_array;
+use crate::alloc::{alloc_array, write_barrier};
+use crate::error_code::ErrorCode;
+use crate::print::WriteBuf;
 use crate::rts_trap_with;
 use crate::types::SkewedPtr;
 
-const INITIAL_SIZE: u32 = 256;
+use core::fmt::Write;
+
+const INITIAL_SIZE: usize = 256;
+
+/// Bits of the index in a handle
+pub const INDEX_BITS: u32 = 20;
+
+/// Bits of the generation in a handle
+pub const GENERATION_BITS: u32 = 30 - INDEX_BITS;
+
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+
+const GENERATION_MASK: usize = (1 << GENERATION_BITS) - 1;
+
+/// Most slots of the table, all indices fit in a handle
+const MAX_SIZE: usize = 1 << INDEX_BITS;
+
+/// Words of the array per slot: the closure (or the next free slot), and the stamp
+const SLOT_WORDS: usize = 2;
 
 // Skewed pointer to the `Array` object. This needs to be a skewed pointer to be able to pass its
 // location to the GC.
 static mut TABLE: SkewedPtr = SkewedPtr(0);
 
 // Number of currently live closures
-static mut N_CLOSURES: u32 = 0;
+static mut N_CLOSURES: usize = 0;
 
 // Next free slot
-static mut FREE_SLOT: u32 = 0;
+static mut FREE_SLOT: usize = 0;
+
+// Shrink the table when the number of closures drops to this
+static mut SHRINK_AT: usize = 0;
+
+// Number of closures remembered so far, for the stamps
+static mut CLOCK: usize = 0;
+
+/// Number of slots of the table
+unsafe fn table_size() -> usize {
+    TABLE.as_array().len() / SLOT_WORDS
+}
+
+/// Stamp of the slot `idx`, in the bits of a scalar
+unsafe fn stamp(idx: usize) -> usize {
+    TABLE.as_array().get(table_size() + idx).0 >> 2
+}
+
+/// Allocates a table of `size` slots, all free
+unsafe fn alloc_table(size: usize) -> SkewedPtr {
+    let table = alloc_array(size * SLOT_WORDS);
+    let array = table.as_array();
+    for i in 0..size {
+        array.set(i, SkewedPtr((i + 1) << 2));
+    }
+    for i in size..size * SLOT_WORDS {
+        array.set(i, SkewedPtr(0));
+    }
+    table
+}
+
+/// Copies the first `size` slots of the table to `new_table`
+unsafe fn copy_slots(new_table: SkewedPtr, size: usize) {
+    let old_array = TABLE.as_array();
+    let new_array = new_table.as_array();
+    let old_size = table_size();
+    let new_size = new_array.len() / SLOT_WORDS;
+
+    for i in 0..size {
+        new_array.set(i, old_array.get(i));
+        new_array.set(new_size + i, old_array.get(old_size + i));
+    }
+}
 
 unsafe fn crate_closure_table() {
-    TABLE = alloc_array(INITIAL_SIZE);
+    TABLE = alloc_table(INITIAL_SIZE);
     FREE_SLOT = 0;
     N_CLOSURES = 0;
-
-    let table = TABLE.as_array();
-    for i in 0..INITIAL_SIZE {
-        table.set(i, SkewedPtr((i as usize + 1) << 2));
-    }
+    SHRINK_AT = 0;
 }
 
 unsafe fn double_closure_table() {
-    let old_array = TABLE.as_array();
-    let old_size = old_array.len();
+    let old_size = table_size();
 
     assert_eq!(FREE_SLOT, old_size);
 
+    if old_size == MAX_SIZE {
+        rts_trap_with(
+            ErrorCode::OutOfMemory,
+            "remember_closure: Too many closures",
+        );
+    }
+
     let new_size = old_size * 2;
 
-    TABLE = alloc_array(new_size);
-    let new_array = TABLE.as_array();
+    let new_table = alloc_table(new_size);
+    copy_slots(new_table, old_size);
+    TABLE = new_table;
 
-    for i in 0..old_size {
-        new_array.set(i, old_array.get(i));
+    SHRINK_AT = new_size / 4;
+}
+
+unsafe fn shrink_closure_table() {
+    let array = TABLE.as_array();
+    let size = table_size();
+
+    // Slots from `used_end` on are free
+    let mut used_end = size;
+    while used_end > 0 && array.get(used_end - 1).is_tagged_scalar() {
+        used_end -= 1;
+    }
+
+    let mut new_size = size;
+    while new_size / 2 >= INITIAL_SIZE && new_size / 2 >= used_end && N_CLOSURES <= new_size / 4 {
+        new_size /= 2;
+    }
+
+    if new_size < size {
+        let new_table = alloc_table(new_size);
+        copy_slots(new_table, new_size);
+        TABLE = new_table;
     }
 
-    for i in old_size..new_size {
-        new_array.set(i, SkewedPtr((i as usize + 1) << 2));
+    // Free slots only hold scalars, so no write barrier is needed
+    let array = TABLE.as_array();
+    FREE_SLOT = new_size;
+    for i in (0..new_size).rev() {
+        if array.get(i).is_tagged_scalar() {
+            array.set(i, SkewedPtr(FREE_SLOT << 2));
+            FREE_SLOT = i;
+        }
     }
+
+    SHRINK_AT = N_CLOSURES / 2;
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn remember_closure(ptr: SkewedPtr) -> u32 {
+pub unsafe extern "C" fn remember_closure(ptr: SkewedPtr) -> usize {
     if TABLE.0 == 0 {
         crate_closure_table();
     }
 
-    if FREE_SLOT == TABLE.as_array().len() {
+    if FREE_SLOT == table_size() {
         double_closure_table();
     }
 
     // Just as a sanity check make sure the ptr is really skewed
     if ptr.is_tagged_scalar() {
-        rts_trap_with("remember_closure: Argument is not a skewed pointer");
+        rts_trap_with(
+            ErrorCode::Internal,
+            "remember_closure: Argument is not a skewed pointer",
+        );
     }
 
     let idx = FREE_SLOT;
 
-    FREE_SLOT = (TABLE.as_array().get(idx).0 >> 2) as u32;
+    FREE_SLOT = TABLE.as_array().get(idx).0 >> 2;
+    write_barrier(TABLE.as_array().payload_addr().add(idx));
     TABLE.as_array().set(idx, ptr);
     N_CLOSURES += 1;
 
-    idx
+    CLOCK = CLOCK.wrapping_add(1);
+    TABLE
+        .as_array()
+        .set(table_size() + idx, SkewedPtr(CLOCK << 2));
+
+    (CLOCK & GENERATION_MASK) << INDEX_BITS | idx
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn recall_closure(idx: u32) -> SkewedPtr {
+pub unsafe extern "C" fn recall_closure(handle: usize) -> SkewedPtr {
     if TABLE.0 == 0 {
-        rts_trap_with("recall_closure: Closure table not allocated");
+        rts_trap_with(
+            ErrorCode::Internal,
+            "recall_closure: Closure table not allocated",
+        );
     }
 
-    if idx >= TABLE.as_array().len() {
-        rts_trap_with("recall_closure: Closure index out of range");
+    let idx = handle & INDEX_MASK;
+    let generation = handle >> INDEX_BITS;
+
+    if idx >= table_size() {
+        rts_trap_with(
+            ErrorCode::Internal,
+            "recall_closure: Closure index out of range",
+        );
     }
 
     let ptr = TABLE.as_array().get(idx);
+    let slot_generation = stamp(idx) & GENERATION_MASK;
 
-    TABLE
-        .as_array()
-        .set(idx, SkewedPtr((FREE_SLOT << 2) as usize));
+    if ptr.0 & 0b1 != 1 {
+        if generation == slot_generation {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "recall_closure: Closure already recalled",
+            );
+        } else {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "recall_closure: Closure index not in table",
+            );
+        }
+    }
+
+    if generation != slot_generation {
+        let mut buf = [0u8; 120];
+        let mut msg = WriteBuf::new(&mut buf);
+        let _ = write!(
+            &mut msg,
+            "recall_closure: Stale closure handle: generation {} of slot {}, expected {}",
+            generation, idx, slot_generation
+        );
+        rts_trap_with(ErrorCode::Internal, msg.as_str());
+    }
+
+    write_barrier(TABLE.as_array().payload_addr().add(idx));
+    TABLE.as_array().set(idx, SkewedPtr(FREE_SLOT << 2));
     FREE_SLOT = idx;
 
     N_CLOSURES -= 1;
 
-    if ptr.0 & 0b1 != 1 {
-        rts_trap_with("recall_closure: Closure index not in table");
+    if N_CLOSURES <= SHRINK_AT && table_size() > INITIAL_SIZE {
+        shrink_closure_table();
     }
 
     ptr
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn closure_count() -> u32 {
+pub unsafe extern "C" fn closure_count() -> usize {
     N_CLOSURES
 }
 
+/// Handle of the closure at slot `idx`, or `None` when there is no closure at `idx`
+pub unsafe fn closure_handle(idx: usize) -> Option<usize> {
+    if TABLE.0 == 0 || idx >= table_size() || TABLE.as_array().get(idx).is_tagged_scalar() {
+        return None;
+    }
+    Some((stamp(idx) & GENERATION_MASK) << INDEX_BITS | idx)
+}
+
+/// Age of the closure of `handle`, the number of closures remembered after it, or `None` when
+/// the handle is not of a closure in the table
+pub unsafe fn closure_age(handle: usize) -> Option<usize> {
+    if closure_handle(handle & INDEX_MASK) != Some(handle) {
+        return None;
+    }
+    // The stamps wrap around with the clock, in the bits of a scalar
+    Some(CLOCK.wrapping_sub(stamp(handle & INDEX_MASK)) & (usize::MAX >> 2))
+}
+
+/// Age of the oldest closure (see `closure_age`), 0 when there are no closures
+#[no_mangle]
+pub unsafe extern "C" fn closure_table_max_age() -> usize {
+    if TABLE.0 == 0 {
+        return 0;
+    }
+    (0..table_size())
+        .filter_map(|idx| closure_age(closure_handle(idx)?))
+        .max()
+        .unwrap_or(0)
+}
+
 pub(crate) unsafe fn closure_table_loc() -> *mut SkewedPtr {
     &mut TABLE
 }
 
 #[no_mangle]
-unsafe extern "C" fn closure_table_size() -> u32 {
+pub unsafe extern "C" fn closure_table_size() -> usize {
     if TABLE.0 == 0 {
         0
     } else {
-        TABLE.as_array().len()
+        table_size()
     }
 }
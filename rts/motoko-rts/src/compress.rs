@@ -0,0 +1,361 @@
+//! Compression of the stable variables (with `--compress-stabilization`), in the LZ4 block
+//! format.
+//!
+//! The Candid encoding (or the graph copy) of the stable variables is split into blocks of
+//! `BLOCK_SIZE` bytes, compressed one by one, so neither the compressor nor the decompressor need
+//! more than a block in memory. A block that does not get smaller is stored as it is. The
+//! compressed stable variables are in region 0, after the size word (see `region.rs`):
+//!
+//! ```text
+//! length         the uncompressed length, 32-bit
+//! blocks         the compressed length of each block (with `STORED` set when it's stored as it
+//!                is), 32-bit, followed by its bytes
+//! ```
+//!
+//! A compressed block is a sequence of literals and matches, each sequence is:
+//!
+//! ```text
+//! token          the literal length in the high 4 bits, the match length minus 4 in the low 4
+//!                bits, 15 when the length continues in the following bytes
+//! length         of the literals, continued in bytes of 255 and a last byte below 255
+//! literals
+//! offset         of the match, back from the current position, 16-bit little-endian
+//! length         of the match, continued as for the literals
+//! ```
+//!
+//! The last sequence has no match. As in LZ4, the last `LAST_LITERALS` bytes of a block are
+//! literals, and no match starts in the last `MATCH_LIMIT` bytes. The compressor finds matches of
+//! 4 bytes with a hash table of the last position of every hash, and extends them greedily.
+
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::stream::Stream;
+
+/// Bytes of a block, uncompressed
+pub const BLOCK_SIZE: usize = 8192;
+
+/// Most bytes of a compressed block
+pub const MAX_BLOCK_LEN: usize = max_compressed_len(BLOCK_SIZE);
+
+/// Set in the length of a block stored as it is
+pub const STORED: u32 = 1 << 31;
+
+const MIN_MATCH: usize = 4;
+const LAST_LITERALS: usize = 5;
+const MATCH_LIMIT: usize = 12;
+const HASH_BITS: u32 = 12;
+
+/// Most bytes of the compression of `len` bytes
+pub const fn max_compressed_len(len: usize) -> usize {
+    len + len / 255 + 16
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+fn hash(seq: u32) -> usize {
+    (seq.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn write_length(dst: &mut [u8], mut out: usize, mut len: usize) -> usize {
+    while len >= 255 {
+        dst[out] = 255;
+        out += 1;
+        len -= 255;
+    }
+    dst[out] = len as u8;
+    out + 1
+}
+
+/// Writes a sequence of `literals`, and a match when `match_len` is not 0
+fn write_sequence(
+    dst: &mut [u8],
+    mut out: usize,
+    literals: &[u8],
+    offset: usize,
+    match_len: usize,
+) -> usize {
+    let lit_len = literals.len();
+    let match_code = if match_len == 0 {
+        0
+    } else {
+        match_len - MIN_MATCH
+    };
+    let token = out;
+    dst[token] = (core::cmp::min(lit_len, 15) << 4 | core::cmp::min(match_code, 15)) as u8;
+    out += 1;
+    if lit_len >= 15 {
+        out = write_length(dst, out, lit_len - 15);
+    }
+    dst[out..out + lit_len].copy_from_slice(literals);
+    out += lit_len;
+
+    if match_len != 0 {
+        dst[out..out + 2].copy_from_slice(&(offset as u16).to_le_bytes());
+        out += 2;
+        if match_code >= 15 {
+            out = write_length(dst, out, match_code - 15);
+        }
+    }
+    out
+}
+
+/// Compresses the block `src` (of at most 64 KiB) to `dst`, which must have room for
+/// `max_compressed_len(src.len())` bytes. Returns the compressed length.
+pub fn compress(src: &[u8], dst: &mut [u8]) -> usize {
+    // Last position of every hash, plus 1, as 0 is no position
+    let mut table = [0u16; 1 << HASH_BITS];
+    let mut out = 0;
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    while pos + MATCH_LIMIT < src.len() {
+        let seq = read_u32(src, pos);
+        let entry = &mut table[hash(seq)];
+        let candidate = *entry as usize;
+        *entry = pos as u16 + 1;
+
+        if candidate == 0 || read_u32(src, candidate - 1) != seq {
+            pos += 1;
+            continue;
+        }
+
+        let start = candidate - 1;
+        let mut len = MIN_MATCH;
+        while pos + len < src.len() - LAST_LITERALS && src[start + len] == src[pos + len] {
+            len += 1;
+        }
+        out = write_sequence(dst, out, &src[anchor..pos], pos - start, len);
+        pos += len;
+        anchor = pos;
+    }
+
+    write_sequence(dst, out, &src[anchor..], 0, 0)
+}
+
+fn read_length(src: &[u8], pos: &mut usize, mut len: usize) -> Option<usize> {
+    if len != 15 {
+        return Some(len);
+    }
+    loop {
+        let byte = *src.get(*pos)?;
+        *pos += 1;
+        len += byte as usize;
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
+
+/// Decompresses the block `src` to `dst`. Returns the decompressed length, or `None` when `src`
+/// is not a compressed block or does not fit in `dst`.
+pub fn decompress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut pos = 0;
+    let mut out = 0;
+    loop {
+        let token = *src.get(pos)? as usize;
+        pos += 1;
+
+        let lit_len = read_length(src, &mut pos, token >> 4)?;
+        if lit_len > src.len() - pos || lit_len > dst.len() - out {
+            return None;
+        }
+        dst[out..out + lit_len].copy_from_slice(&src[pos..pos + lit_len]);
+        pos += lit_len;
+        out += lit_len;
+        if pos == src.len() {
+            return Some(out);
+        }
+
+        if pos + 2 > src.len() {
+            return None;
+        }
+        let offset = u16::from_le_bytes([src[pos], src[pos + 1]]) as usize;
+        pos += 2;
+        let match_len = read_length(src, &mut pos, token & 15)? + MIN_MATCH;
+        if offset == 0 || offset > out || match_len > dst.len() - out {
+            return None;
+        }
+        // Byte by byte, as the match may overlap the bytes it writes
+        for i in out..out + match_len {
+            dst[i] = dst[i - offset];
+        }
+        out += match_len;
+    }
+}
+
+/// A stream compressing the bytes written to it into another stream, in the format of the
+/// module documentation, without the uncompressed length. `finish` writes the last block.
+pub struct CompressStream<S: Stream> {
+    inner: S,
+    buf: [u8; BLOCK_SIZE],
+    /// Number of buffered bytes
+    len: usize,
+    /// Number of bytes written so far, uncompressed
+    written: usize,
+}
+
+impl<S: Stream> CompressStream<S> {
+    pub fn new(inner: S) -> Self {
+        CompressStream {
+            inner,
+            buf: [0; BLOCK_SIZE],
+            len: 0,
+            written: 0,
+        }
+    }
+
+    /// Writes the last block, returns the stream of the compressed bytes
+    pub fn finish(&mut self) -> &mut S {
+        if self.len != 0 {
+            self.write_block();
+        }
+        &mut self.inner
+    }
+
+    fn write_block(&mut self) {
+        let mut out = [0u8; MAX_BLOCK_LEN];
+        let len = compress(&self.buf[..self.len], &mut out);
+        if len < self.len {
+            self.inner.write(&(len as u32).to_le_bytes());
+            self.inner.write(&out[..len]);
+        } else {
+            self.inner.write(&(self.len as u32 | STORED).to_le_bytes());
+            self.inner.write(&self.buf[..self.len]);
+        }
+        self.len = 0;
+    }
+}
+
+impl<S: Stream> Stream for CompressStream<S> {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.written += bytes.len();
+        while !bytes.is_empty() {
+            let n = core::cmp::min(bytes.len(), BLOCK_SIZE - self.len);
+            self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+            self.len += n;
+            bytes = &bytes[n..];
+            if self.len == BLOCK_SIZE {
+                self.write_block();
+            }
+        }
+    }
+
+    fn written(&self) -> usize {
+        self.written
+    }
+}
+
+/// Reads the uncompressed bytes of compressed blocks, in the format of the module documentation
+/// without the uncompressed length, one block after the other. Reads before the last block read
+/// start over from the first block.
+pub struct Decompressor {
+    /// Offset of the next block in the compressed bytes
+    offset: u64,
+    /// Uncompressed offset and length of the block in `buf`
+    block_start: usize,
+    block_len: usize,
+    buf: [u8; BLOCK_SIZE],
+}
+
+impl Decompressor {
+    pub const fn new() -> Self {
+        Decompressor {
+            offset: 0,
+            block_start: 0,
+            block_len: 0,
+            buf: [0; BLOCK_SIZE],
+        }
+    }
+
+    /// Starts over from the first block, when the compressed bytes change
+    pub fn reset(&mut self) {
+        self.offset = 0;
+        self.block_start = 0;
+        self.block_len = 0;
+    }
+
+    /// Copies `len` uncompressed bytes at `offset` to `dst`, of the `total` uncompressed bytes.
+    /// `read_compressed(dst, offset, len)` copies compressed bytes.
+    pub unsafe fn read<F: FnMut(*mut u8, u64, u32)>(
+        &mut self,
+        mut read_compressed: F,
+        total: usize,
+        mut dst: *mut u8,
+        mut offset: usize,
+        mut len: usize,
+    ) {
+        if offset > total || len > total - offset {
+            rts_trap_with(
+                ErrorCode::StableMemory,
+                "read out of compressed stable variables",
+            );
+        }
+        if offset < self.block_start {
+            self.reset();
+        }
+
+        while len != 0 {
+            if offset < self.block_start + self.block_len {
+                let pos = offset - self.block_start;
+                let n = core::cmp::min(len, self.block_len - pos);
+                core::ptr::copy_nonoverlapping(self.buf.as_ptr().add(pos), dst, n);
+                dst = dst.add(n);
+                offset += n;
+                len -= n;
+            } else {
+                self.block_start += self.block_len;
+                self.next_block(&mut read_compressed, total - self.block_start);
+            }
+        }
+    }
+
+    /// Decompresses the next block, of `remaining` uncompressed bytes to go
+    unsafe fn next_block<F: FnMut(*mut u8, u64, u32)>(
+        &mut self,
+        read_compressed: &mut F,
+        remaining: usize,
+    ) {
+        let expected = core::cmp::min(remaining, BLOCK_SIZE);
+        let mut header = 0u32;
+        read_compressed(&mut header as *mut u32 as *mut u8, self.offset, 4);
+        let len = (header & !STORED) as usize;
+        if len > MAX_BLOCK_LEN {
+            rts_trap_with(
+                ErrorCode::StableMemory,
+                "corrupt compressed stable variables",
+            );
+        }
+
+        let mut src = [0u8; MAX_BLOCK_LEN];
+        read_compressed(src.as_mut_ptr(), self.offset + 4, len as u32);
+        self.offset += 4 + len as u64;
+
+        let block_len = if header & STORED != 0 {
+            if len > BLOCK_SIZE {
+                rts_trap_with(
+                    ErrorCode::StableMemory,
+                    "corrupt compressed stable variables",
+                );
+            }
+            self.buf[..len].copy_from_slice(&src[..len]);
+            Some(len)
+        } else {
+            decompress(&src[..len], &mut self.buf)
+        };
+        if block_len != Some(expected) {
+            rts_trap_with(
+                ErrorCode::StableMemory,
+                "corrupt compressed stable variables",
+            );
+        }
+        self.block_len = expected;
+    }
+}
+
+impl Default for Decompressor {
+    fn default() -> Self {
+        Decompressor::new()
+    }
+}
@@ -0,0 +1,175 @@
+//! Cyclic redundancy checks of blobs and texts: CRC-32 (as in zlib, used in textual principals),
+//! CRC-32C (Castagnoli), CRC-16/CCITT-FALSE and CRC-8 (as in SMBus).
+//!
+//! All are table-driven, with the tables computed at compile time. The leaves of a text are
+//! checked one after the other (see `Leaves` in `text.rs`), so texts are not copied to a blob
+//! first.
+
+use crate::text::Leaves;
+use crate::types::{SkewedPtr, TAG_BLOB};
+
+/// Parameters of a CRC (in the usual model of Rocksoft), up to 32 bits wide
+pub(crate) struct Params {
+    width: u32,
+    /// Whether the bytes and the result are bit-reversed, then `table` is of the reversed
+    /// polynomial
+    reflected: bool,
+    init: u32,
+    xor_out: u32,
+    table: [u32; 256],
+}
+
+/// The remainder of every byte, shifted to the top of the CRC (or reflected)
+const fn table(width: u32, poly: u32, reflected: bool) -> [u32; 256] {
+    let mask = if width == 32 { !0 } else { (1 << width) - 1 };
+    let top = 1 << (width - 1);
+
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = if reflected {
+            i as u32
+        } else {
+            (i as u32) << (width - 8)
+        };
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if reflected {
+                if crc & 1 != 0 {
+                    crc >> 1 ^ poly
+                } else {
+                    crc >> 1
+                }
+            } else if crc & top != 0 {
+                (crc << 1 ^ poly) & mask
+            } else {
+                (crc << 1) & mask
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub(crate) static CRC32: Params = Params {
+    width: 32,
+    reflected: true,
+    init: !0,
+    xor_out: !0,
+    table: table(32, 0xedb8_8320, true),
+};
+
+pub(crate) static CRC32C: Params = Params {
+    width: 32,
+    reflected: true,
+    init: !0,
+    xor_out: !0,
+    table: table(32, 0x82f6_3b78, true),
+};
+
+pub(crate) static CRC16_CCITT: Params = Params {
+    width: 16,
+    reflected: false,
+    init: 0xffff,
+    xor_out: 0,
+    table: table(16, 0x1021, false),
+};
+
+pub(crate) static CRC8: Params = Params {
+    width: 8,
+    reflected: false,
+    init: 0,
+    xor_out: 0,
+    table: table(8, 0x07, false),
+};
+
+/// Incremental computation of a CRC
+#[derive(Clone, Copy)]
+pub(crate) struct Crc {
+    params: &'static Params,
+    crc: u32,
+}
+
+impl Crc {
+    pub(crate) fn new(params: &'static Params) -> Self {
+        Crc {
+            params,
+            crc: params.init,
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        let params = self.params;
+        let mut crc = self.crc;
+        if params.reflected {
+            for &byte in bytes {
+                crc = params.table[usize::from(crc as u8 ^ byte)] ^ crc >> 8;
+            }
+        } else {
+            let shift = params.width - 8;
+            for &byte in bytes {
+                // The bits shifted above the width do not matter, they are cleared at the end
+                crc = params.table[usize::from((crc >> shift) as u8 ^ byte)] ^ crc << 8;
+            }
+            if params.width < 32 {
+                crc &= (1 << params.width) - 1;
+            }
+        }
+        self.crc = crc;
+    }
+
+    pub(crate) fn finish(self) -> u32 {
+        self.crc ^ self.params.xor_out
+    }
+}
+
+/// The CRC-32 of the bytes
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new(&CRC32);
+    crc.update(bytes);
+    crc.finish()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn compute_crc32(blob: SkewedPtr) -> u32 {
+    if blob.tag() != TAG_BLOB {
+        panic!("compute_crc32: Blob expected");
+    }
+
+    let blob = blob.as_blob();
+    crc32(core::slice::from_raw_parts(
+        blob.payload_addr(),
+        blob.len().0,
+    ))
+}
+
+/// The CRC of a blob or a text
+unsafe fn crc_of(params: &'static Params, s: SkewedPtr) -> u32 {
+    let mut crc = Crc::new(params);
+    let mut leaves = Leaves::new(s);
+    while let Some(bytes) = leaves.next() {
+        crc.update(bytes);
+    }
+    crc.finish()
+}
+
+/// CRC-32C (Castagnoli, as in iSCSI and ext4) of a blob or a text
+#[no_mangle]
+pub unsafe extern "C" fn blob_crc32c(s: SkewedPtr) -> u32 {
+    crc_of(&CRC32C, s)
+}
+
+/// CRC-16/CCITT-FALSE (polynomial 0x1021, initially 0xffff, as in XMODEM but for the initial
+/// value) of a blob or a text
+#[no_mangle]
+pub unsafe extern "C" fn blob_crc16(s: SkewedPtr) -> u32 {
+    crc_of(&CRC16_CCITT, s)
+}
+
+/// CRC-8 (polynomial 0x07, initially 0, as in SMBus) of a blob or a text
+#[no_mangle]
+pub unsafe extern "C" fn blob_crc8(s: SkewedPtr) -> u32 {
+    crc_of(&CRC8, s)
+}
@@ -40,22 +40,34 @@ pub(crate) unsafe fn print_closure_table() {
     }
 
     let arr = (*closure_tbl).unskew() as *mut Array;
-    let len = (*arr).len;
+    let len = closure_table::closure_table_size();
 
     if len == 0 {
         println!(50, "Closure table empty");
         return;
     }
 
-    println!(50, "Closure table: {}", len);
+    println!(
+        50,
+        "Closure table: {} ({} closures)",
+        len,
+        closure_table::closure_count()
+    );
 
     let mut buf = [0u8; 1000];
     let mut write_buf = WriteBuf::new(&mut buf);
 
     for i in 0..len {
         let elem = arr.get(i);
-        if !elem.is_tagged_scalar() {
-            let _ = write!(&mut write_buf, "{}: {:#x} --> ", i, elem.unskew());
+        if let Some(handle) = closure_table::closure_handle(i) {
+            let _ = write!(
+                &mut write_buf,
+                "{} (handle {:#x}, age {}): {:#x} --> ",
+                i,
+                handle,
+                closure_table::closure_age(handle).unwrap(),
+                elem.unskew()
+            );
             print_boxed_object(&mut write_buf, elem.unskew());
             print(&write_buf);
             write_buf.reset();
@@ -109,11 +121,11 @@ unsafe fn print_heap() {
     let mut p = heap_begin;
     while p < heap_end {
         let _ = write!(&mut write_buf, "{:#x}: ", p);
-        print_boxed_object(&mut write_buf, p as usize);
+        print_boxed_object(&mut write_buf, p);
         print(&write_buf);
         write_buf.reset();
 
-        p += object_size(p as usize).to_bytes().0;
+        p += object_size(p).to_bytes().0;
     }
 }
 
@@ -202,6 +214,10 @@ unsafe fn print_boxed_object(buf: &mut WriteBuf, p: usize) {
             let blob = obj as *const Blob;
             let _ = write!(buf, "<Blob len={:#x}>", (*blob).len.0);
         }
+        TAG_SMALL_TEXT => {
+            let small_text = obj as *mut SmallText;
+            let _ = write!(buf, "<SmallText len={:#x}>", small_text.len().0);
+        }
         TAG_FWD_PTR => {
             let ind = obj as *const FwdPtr;
             let _ = write!(buf, "<Forwarding to {:#x}>", (*ind).fwd.0);
@@ -215,19 +231,30 @@ unsafe fn print_boxed_object(buf: &mut WriteBuf, p: usize) {
             let _ = write!(
                 buf,
                 "<BigInt used={:#x} alloc={:#x} sign={}>",
-                (*bigint).mp_int.used,
-                (*bigint).mp_int.alloc,
-                (*bigint).mp_int.sign,
+                (*bigint).used,
+                (*bigint).alloc,
+                (*bigint).sign,
             );
         }
         TAG_CONCAT => {
             let concat = obj as *const Concat;
             let _ = write!(
                 buf,
-                "<Concat n_bytes={:#x} obj1={:#x} obj2={:#x}>",
+                "<Concat n_bytes={:#x} obj1={:#x} obj2={:#x} depth={}>",
                 (*concat).n_bytes.0,
                 (*concat).text1.0,
-                (*concat).text2.0
+                (*concat).text2.0,
+                (*concat).depth
+            );
+        }
+        TAG_SLICE => {
+            let slice = obj as *const Slice;
+            let _ = write!(
+                buf,
+                "<Slice n_bytes={:#x} blob={:#x} offset={:#x}>",
+                (*slice).n_bytes.0,
+                (*slice).blob.0,
+                (*slice).offset.0
             );
         }
         other => {
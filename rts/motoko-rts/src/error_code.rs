@@ -0,0 +1,52 @@
+//! Error codes of the traps of the RTS.
+//!
+//! The message of a trap starts with the code in brackets, e.g.
+//!
+//! ```text
+//! [E0002] IDL error: missing magic bytes
+//! ```
+//!
+//! so tools and tests can tell the kind of error from the code, `\[E([0-9]{4})\]`, instead of
+//! matching the message, which may change. The codes are stable: a code is never reused for a
+//! different kind of error. The traps of the generated code for malformed Candid have the code of
+//! `IdlDecode` too (see `idl_error` in `compile.ml`).
+
+/// Kinds of errors
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    /// A bug of the RTS or the compiler, e.g. a corrupt heap
+    Internal = 1,
+    /// Malformed Candid input
+    IdlDecode = 2,
+    /// Candid input exceeding a decoding limit (see `idl_set_limits`)
+    IdlLimit = 3,
+    /// Input that is not UTF-8 (or UTF-16) when it is decoded as text
+    Utf8 = 4,
+    /// An index or range out of the bounds of an array, blob, text, region, or buffer
+    OutOfBounds = 5,
+    /// Allocation failure: the memory, the heap limit, or a table is exhausted, or an object would
+    /// be too large
+    OutOfMemory = 6,
+    /// An invalid argument of a primitive, e.g. a negative exponent or an exhausted iterator
+    InvalidArgument = 7,
+    /// Stable memory that is corrupt or of an unsupported version
+    StableMemory = 8,
+}
+
+impl ErrorCode {
+    /// The code as it starts a trap message, `[E` and four digits `]`
+    pub fn tag(self) -> [u8; 7] {
+        let n = self as u32;
+        let digit = |place: u32| b'0' + (n / place % 10) as u8;
+        [
+            b'[',
+            b'E',
+            digit(1000),
+            digit(100),
+            digit(10),
+            digit(1),
+            b']',
+        ]
+    }
+}
@@ -0,0 +1,152 @@
+//! Features of the RTS and of the canister, for canisters, tests, and tooling to adapt to at run
+//! time (`Prim.rts_features`, see `Features.get` in `compile.ml`), rather than guessing from the
+//! compiler version.
+//!
+//! Some features are fixed when the RTS is built: the version, the git revision, the word size, and
+//! the Cargo features. The others are chosen by the compiler flags, as all collectors are compiled
+//! in: the generated code passes them to `set_compiler_features` on start up, after `init`.
+
+use crate::types::Bytes;
+
+/// Version of the RTS, as `version`
+pub const VERSION: &str = "0.1";
+
+/// Git revision the RTS was built from, set by `rts/Makefile`, empty when unknown
+const GIT_REVISION: &str = match option_env!("MOTOKO_RTS_GIT_REVISION") {
+    Some(revision) => revision,
+    None => "",
+};
+
+/// Collectors of the compiler flags (`--incremental-gc` etc.)
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GcAlgorithm {
+    /// Before `set_compiler_features` was called, e.g. in the RTS tests
+    Unknown = 0,
+    Copying = 1,
+    Incremental = 2,
+    Generational = 3,
+    MarkSweep = 4,
+}
+
+impl GcAlgorithm {
+    fn of_u32(n: u32) -> GcAlgorithm {
+        match n {
+            1 => GcAlgorithm::Copying,
+            2 => GcAlgorithm::Incremental,
+            3 => GcAlgorithm::Generational,
+            4 => GcAlgorithm::MarkSweep,
+            _ => GcAlgorithm::Unknown,
+        }
+    }
+
+    /// Name of the collector, as in the compiler flag
+    pub fn name(self) -> &'static str {
+        match self {
+            GcAlgorithm::Unknown => "unknown",
+            GcAlgorithm::Copying => "copying",
+            GcAlgorithm::Incremental => "incremental",
+            GcAlgorithm::Generational => "generational",
+            GcAlgorithm::MarkSweep => "mark-sweep",
+        }
+    }
+}
+
+// Bits of `RtsFeatures::flags`. The low byte has the Cargo features of the RTS, the second byte
+// the compiler flags.
+
+/// Cargo feature `heap_sanity`
+pub const FEATURE_HEAP_SANITY: u32 = 1 << 0;
+/// Cargo feature `redzones`
+pub const FEATURE_REDZONES: u32 = 1 << 1;
+/// Cargo feature `free_list`
+pub const FEATURE_FREE_LIST: u32 = 1 << 2;
+
+/// `--graph-copy-stabilization`
+pub const FEATURE_GRAPH_COPY_STABILIZATION: u32 = 1 << 8;
+/// `--compress-stabilization`
+pub const FEATURE_COMPRESS_STABILIZATION: u32 = 1 << 9;
+/// `--global-timer`
+pub const FEATURE_GLOBAL_TIMER: u32 = 1 << 10;
+/// `--trap-backtrace`
+pub const FEATURE_TRAP_BACKTRACE: u32 = 1 << 11;
+/// `--perf-counters`
+pub const FEATURE_PERF_COUNTERS: u32 = 1 << 12;
+
+const COMPILER_FEATURES: u32 = 0xff << 8;
+
+/// Features, returned by `get_features`. The generated code reads the fields by offset, the
+/// strings are static and not NUL-terminated.
+#[repr(C)]
+pub struct RtsFeatures {
+    pub version: *const u8,
+    pub version_len: Bytes<usize>,
+    pub git_revision: *const u8,
+    pub git_revision_len: Bytes<usize>,
+    pub gc: *const u8,
+    pub gc_len: Bytes<usize>,
+    /// Bytes of a word, and of a pointer
+    pub word_size: u32,
+    /// `FEATURE_*` bits
+    pub flags: u32,
+}
+
+static mut GC: GcAlgorithm = GcAlgorithm::Unknown;
+
+static mut COMPILER_FLAGS: u32 = 0;
+
+static mut FEATURES: RtsFeatures = RtsFeatures {
+    version: core::ptr::null(),
+    version_len: Bytes(0),
+    git_revision: core::ptr::null(),
+    git_revision_len: Bytes(0),
+    gc: core::ptr::null(),
+    gc_len: Bytes(0),
+    word_size: 0,
+    flags: 0,
+};
+
+/// Called by the generated code on start up with the collector (a `GcAlgorithm`) and the
+/// `FEATURE_*` bits of the compiler flags. Other bits are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn set_compiler_features(gc: u32, flags: u32) {
+    GC = GcAlgorithm::of_u32(gc);
+    COMPILER_FLAGS = flags & COMPILER_FEATURES;
+}
+
+/// Collector passed to `set_compiler_features`
+pub unsafe fn gc_algorithm() -> GcAlgorithm {
+    GC
+}
+
+/// `FEATURE_*` bits of the RTS build and of the compiler flags
+pub unsafe fn feature_flags() -> u32 {
+    let mut flags = COMPILER_FLAGS;
+    if cfg!(feature = "heap_sanity") {
+        flags |= FEATURE_HEAP_SANITY;
+    }
+    if cfg!(feature = "redzones") {
+        flags |= FEATURE_REDZONES;
+    }
+    if cfg!(feature = "free_list") {
+        flags |= FEATURE_FREE_LIST;
+    }
+    flags
+}
+
+/// Returns a pointer to the features, updated on every call
+#[no_mangle]
+pub unsafe extern "C" fn get_features() -> *const RtsFeatures {
+    let gc = GC.name();
+    FEATURES = RtsFeatures {
+        version: VERSION.as_ptr(),
+        version_len: Bytes(VERSION.len()),
+        git_revision: GIT_REVISION.as_ptr(),
+        git_revision_len: Bytes(GIT_REVISION.len()),
+        gc: gc.as_ptr(),
+        gc_len: Bytes(gc.len()),
+        word_size: core::mem::size_of::<usize>() as u32,
+        flags: feature_flags(),
+    };
+    &FEATURES
+}
@@ -0,0 +1,188 @@
+//! Finalizers: closures that are run when an object becomes unreachable.
+//!
+//! `register_finalizer` adds the object and the closure to the finalizer table. The table refers
+//! to the object with a weak reference (see `weak_ref.rs`), so registering a finalizer does not
+//! keep the object alive. When the GC finds that the object is dead it clears the weak reference,
+//! and after the collection `queue_finalizers` adds the closure to the closure table and queues
+//! its index. At the end of update messages the generated code sends the queued closures, by
+//! index, to the canister itself (see `FuncDec.dispatch_finalizers` in `compile.ml`), where each
+//! finalizer runs in its own message, and a trapping finalizer does not affect the others. A
+//! finalizer is removed from the queue with `dequeue_finalizer` once its message is sent. When the
+//! message can't be sent it stays queued until the end of the next update message.
+//!
+//! The closure should not refer to the object, otherwise the object stays reachable and the
+//! finalizer never runs.
+//!
+//! The table is a heap-allocated Motoko array with two slots per entry. The first slot is the weak
+//! reference, or `FREE` or `QUEUED`. The second slot is the closure, or for queued entries the
+//! closure table index, shifted 2 bits to the left to make it a scalar. New entries are added
+//! after the last used entry. When the table is full we drop the free entries, and double the size
+//! if it's still more than half full.
+
+use crate::alloc::{alloc_array, write_barrier};
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::SkewedPtr;
+use crate::weak_ref::weak_ref_new;
+
+#[cfg(feature = "gc")]
+use crate::closure_table::remember_closure;
+#[cfg(feature = "gc")]
+use crate::weak_ref::weak_ref_alive;
+
+/// Initial number of entries
+const INITIAL_SIZE: usize = 16;
+
+const FREE: SkewedPtr = SkewedPtr(0);
+const QUEUED: SkewedPtr = SkewedPtr(1 << 2);
+
+// Skewed pointer to the `Array` object, a GC root like the closure table
+static mut TABLE: SkewedPtr = SkewedPtr(0);
+
+// Number of used entries. Entries after these are free.
+static mut N_USED: usize = 0;
+
+// Number of queued finalizers
+static mut N_QUEUED: usize = 0;
+
+unsafe fn capacity() -> usize {
+    if TABLE.0 == 0 {
+        0
+    } else {
+        TABLE.as_array().len() / 2
+    }
+}
+
+unsafe fn get_slot(idx: usize) -> SkewedPtr {
+    TABLE.as_array().get(idx)
+}
+
+unsafe fn set_slot(idx: usize, value: SkewedPtr) {
+    let array = TABLE.as_array();
+    write_barrier(array.payload_addr().add(idx));
+    array.set(idx, value);
+}
+
+/// Drops free entries, grows the table if it's still more than half full
+unsafe fn make_room() {
+    let mut n_used = 0;
+    for i in 0..N_USED {
+        let weak_ref = get_slot(i * 2);
+        if weak_ref != FREE {
+            if n_used != i {
+                let closure = get_slot(i * 2 + 1);
+                set_slot(n_used * 2, weak_ref);
+                set_slot(n_used * 2 + 1, closure);
+            }
+            n_used += 1;
+        }
+    }
+
+    for i in n_used * 2..N_USED * 2 {
+        set_slot(i, FREE);
+    }
+
+    N_USED = n_used;
+
+    let old_size = capacity();
+    if N_USED * 2 <= old_size && old_size != 0 {
+        return;
+    }
+
+    let new_size = if old_size == 0 {
+        INITIAL_SIZE
+    } else {
+        old_size * 2
+    };
+
+    let new_table = alloc_array(new_size * 2);
+    let new_array = new_table.as_array();
+
+    for i in 0..N_USED * 2 {
+        new_array.set(i, get_slot(i));
+    }
+
+    for i in N_USED * 2..new_size * 2 {
+        new_array.set(i, FREE);
+    }
+
+    TABLE = new_table;
+}
+
+/// Registers `closure` to be run after `obj` becomes unreachable
+#[no_mangle]
+pub unsafe extern "C" fn register_finalizer(obj: SkewedPtr, closure: SkewedPtr) {
+    if closure.is_tagged_scalar() {
+        rts_trap_with(
+            ErrorCode::Internal,
+            "register_finalizer: Closure is not a skewed pointer",
+        );
+    }
+
+    if N_USED == capacity() {
+        make_room();
+    }
+
+    let weak_ref = weak_ref_new(obj);
+
+    set_slot(N_USED * 2, weak_ref);
+    set_slot(N_USED * 2 + 1, closure);
+    N_USED += 1;
+}
+
+/// Number of finalizers waiting to be run
+#[no_mangle]
+pub unsafe extern "C" fn pending_finalizers() -> usize {
+    N_QUEUED
+}
+
+/// Closure table index of the next queued finalizer, which stays queued
+#[no_mangle]
+pub unsafe extern "C" fn next_finalizer() -> usize {
+    match next_queued() {
+        Some(i) => get_slot(i * 2 + 1).0 >> 2,
+        None => rts_trap_with(ErrorCode::Internal, "next_finalizer: No queued finalizers"),
+    }
+}
+
+/// Removes the next queued finalizer from the queue
+#[no_mangle]
+pub unsafe extern "C" fn dequeue_finalizer() {
+    match next_queued() {
+        Some(i) => {
+            set_slot(i * 2, FREE);
+            set_slot(i * 2 + 1, FREE);
+            N_QUEUED -= 1;
+        }
+        None => rts_trap_with(
+            ErrorCode::Internal,
+            "dequeue_finalizer: No queued finalizers",
+        ),
+    }
+}
+
+/// Entry of the next queued finalizer
+unsafe fn next_queued() -> Option<usize> {
+    (0..N_USED).find(|&i| get_slot(i * 2) == QUEUED)
+}
+
+/// Queues the finalizers of dead objects. Called by the collectors after each collection.
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn queue_finalizers() {
+    for i in 0..N_USED {
+        let weak_ref = get_slot(i * 2);
+        if weak_ref.is_tagged_scalar() || weak_ref_alive(weak_ref) != 0 {
+            continue;
+        }
+
+        let idx = remember_closure(get_slot(i * 2 + 1));
+        set_slot(i * 2, QUEUED);
+        set_slot(i * 2 + 1, SkewedPtr(idx << 2));
+        N_QUEUED += 1;
+    }
+}
+
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn finalizer_table_loc() -> *mut SkewedPtr {
+    &mut TABLE
+}
@@ -1,34 +1,488 @@
+//! Formatting of floats as text, without libc: the shortest text that reads back as the same
+//! float for `Float.toText` (and `debug_show`), and the modes of `Float.format`:
+//!
+//! ```text
+//! 0   fixed, as `%.*f`
+//! 1   exponential, as `%.*e`
+//! 2   generic, as `%.*g`
+//! 3   hexadecimal, as `%.*a`
+//! 4   engineering, as `%.*e` with an exponent that is a multiple of 3 (and 1 to 3 digits before
+//!     the point)
+//! 5   hexadecimal with the digits the float needs, as `%a` (the precision is ignored)
+//! ```
+//!
+//! The precision is the number of digits after the point (significant digits for `%g`), of at
+//! most 100. As there is no locale, the point is always `.`, digits are not grouped, and the
+//! infinities and NaN are `inf` and `nan`, with a `-` for a negative sign.
+//!
+//! The shortest digits are found by the formatter of `core` (Grisu, with Dragon4 when Grisu
+//! cannot decide), and laid out as JavaScript lays out numbers: without an exponent for
+//! magnitudes from 1e-6 to below 1e21, with `e+n` or `e-n` otherwise.
+//!
+//! The modes with a precision use the exact decimal expansion of the float (a float is a binary
+//! fraction, so its decimal expansion is finite, up to 767 significant digits), rounded half to
+//! even, as `printf` does in the default rounding mode.
+
+use crate::error_code::ErrorCode;
+use crate::print::WriteBuf;
 use crate::text::text_of_ptr_size;
 use crate::types::{Bytes, SkewedPtr};
 
-// The meaning of the `mode` parameter is documented in motoko-base, function Float.format()
-#[no_mangle]
-unsafe extern "C" fn float_fmt(a: f64, prec: u32, mode: u32) -> SkewedPtr {
-    // prec and mode are tagged (TODO (osa): what tag???)
-    let mode = mode >> 24;
-    let prec = core::cmp::min(prec >> 24, 100) as usize;
-
-    // 110 bytes needed for max precision (TODO (osa): why? how?)
-    let buf = [0u8; 120];
-
-    // NB. Using snprintf because I think only 0 and 3 are supposed by Rust's built-in formatter
-    let fmt = match mode {
-        0 => "%.*f\0",
-        1 => "%.*e\0",
-        2 => "%.*g\0",
-        3 => "%.*a\0",
-        _ => panic!("float_fmt: unrecognized mode"),
+use core::fmt::Write;
+
+/// Most significant digits of the exact decimal expansion of a float
+const MAX_DIGITS: usize = 800;
+
+/// Most bytes of a formatted float: 309 integer digits, the point and 100 digits of precision
+const BUF_SIZE: usize = 420;
+
+/// A decimal number `0.d1 d2 ... dn * 10^exp`, without trailing zeros. 0 has no digits.
+struct Decimal {
+    digits: [u8; MAX_DIGITS],
+    len: usize,
+    exp: i32,
+}
+
+/// Words of `Big`, enough for 2^1024 and for fractions of 1074 bits times 10
+const BIG_WORDS: usize = 36;
+
+/// A natural number of `BIG_WORDS` 32-bit words, least significant first
+struct Big([u32; BIG_WORDS]);
+
+impl Big {
+    fn of_u64(n: u64) -> Self {
+        let mut big = Big([0; BIG_WORDS]);
+        big.0[0] = n as u32;
+        big.0[1] = (n >> 32) as u32;
+        big
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    fn shl(&mut self, bits: usize) {
+        let words = bits / 32;
+        let bits = bits % 32;
+        for i in (0..BIG_WORDS).rev() {
+            let hi = if i >= words { self.0[i - words] } else { 0 };
+            let lo = if i > words { self.0[i - words - 1] } else { 0 };
+            self.0[i] = if bits == 0 {
+                hi
+            } else {
+                hi << bits | lo >> (32 - bits)
+            };
+        }
+    }
+
+    fn mul_small(&mut self, n: u32) {
+        let mut carry = 0u64;
+        for word in self.0.iter_mut() {
+            let x = u64::from(*word) * u64::from(n) + carry;
+            *word = x as u32;
+            carry = x >> 32;
+        }
+    }
+
+    /// Divides by `n`, returns the remainder
+    fn div_small(&mut self, n: u32) -> u32 {
+        let mut rem = 0u64;
+        for word in self.0.iter_mut().rev() {
+            let x = rem << 32 | u64::from(*word);
+            *word = (x / u64::from(n)) as u32;
+            rem = x % u64::from(n);
+        }
+        rem as u32
+    }
+
+    /// Removes the bits from `bit` up, returns them (they must fit in a `u32`)
+    fn split_at(&mut self, bit: usize) -> u32 {
+        let word = bit / 32;
+        let shift = bit % 32;
+        let mut high = u64::from(self.0[word]) >> shift;
+        self.0[word] &= ((1u64 << shift) - 1) as u32;
+        if word + 1 < BIG_WORDS {
+            high |= u64::from(self.0[word + 1]) << (32 - shift);
+            self.0[word + 1] = 0;
+        }
+        high as u32
+    }
+}
+
+impl Decimal {
+    /// The exact decimal expansion of the magnitude of `a`, which is finite
+    fn exact(a: f64) -> Self {
+        let mut dec = Decimal {
+            digits: [0; MAX_DIGITS],
+            len: 0,
+            exp: 0,
+        };
+
+        let bits = a.to_bits();
+        let biased_exp = ((bits >> 52) & 0x7ff) as i32;
+        let frac = bits & ((1 << 52) - 1);
+        let (mant, exp2) = if biased_exp == 0 {
+            (frac, -1074)
+        } else {
+            (frac | 1 << 52, biased_exp - 1075)
+        };
+        if mant == 0 {
+            return dec;
+        }
+
+        if exp2 >= 0 {
+            // An integer, converted in groups of 9 digits
+            let mut n = Big::of_u64(mant);
+            n.shl(exp2 as usize);
+            let mut groups = [0u32; 40];
+            let mut n_groups = 0;
+            while !n.is_zero() {
+                groups[n_groups] = n.div_small(1_000_000_000);
+                n_groups += 1;
+            }
+            dec.push_int(u64::from(groups[n_groups - 1]));
+            for group in groups[..n_groups - 1].iter().rev() {
+                for i in (0..9).rev() {
+                    dec.push((group / 10u32.pow(i) % 10) as u8);
+                }
+            }
+            dec.exp = dec.len as i32;
+        } else {
+            // The integer part, then the digits of the fraction of `frac_bits` bits, one by one
+            let frac_bits = (-exp2) as usize;
+            let int = if frac_bits < 64 { mant >> frac_bits } else { 0 };
+            let mut frac = Big::of_u64(if frac_bits < 64 {
+                mant & ((1 << frac_bits) - 1)
+            } else {
+                mant
+            });
+            if int != 0 {
+                dec.push_int(int);
+            }
+            dec.exp = dec.len as i32;
+            while !frac.is_zero() {
+                frac.mul_small(10);
+                let digit = frac.split_at(frac_bits) as u8;
+                if dec.len == 0 && digit == 0 {
+                    // Leading zeros
+                    dec.exp -= 1;
+                } else {
+                    dec.push(digit);
+                }
+            }
+        }
+
+        dec.trim();
+        dec
+    }
+
+    fn push(&mut self, digit: u8) {
+        self.digits[self.len] = digit;
+        self.len += 1;
+    }
+
+    fn push_int(&mut self, n: u64) {
+        let mut digits = [0u8; 20];
+        let mut n_digits = 0;
+        let mut n = n;
+        while n != 0 {
+            digits[n_digits] = (n % 10) as u8;
+            n /= 10;
+            n_digits += 1;
+        }
+        for digit in digits[..n_digits].iter().rev() {
+            self.push(*digit);
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.len > 0 && self.digits[self.len - 1] == 0 {
+            self.len -= 1;
+        }
+    }
+
+    /// Rounds to the first `keep` digits, half to even
+    fn round(&mut self, keep: i32) {
+        if keep >= self.len as i32 {
+            return;
+        }
+        if keep < 0 {
+            self.len = 0;
+            return;
+        }
+
+        let keep = keep as usize;
+        let first = self.digits[keep];
+        let rest = self.digits[keep + 1..self.len]
+            .iter()
+            .any(|&digit| digit != 0);
+        let odd = keep > 0 && self.digits[keep - 1] % 2 == 1;
+        self.len = keep;
+        if first > 5 || (first == 5 && (rest || odd)) {
+            loop {
+                if self.len == 0 {
+                    // All nines, or nothing kept
+                    self.digits[0] = 1;
+                    self.len = 1;
+                    self.exp += 1;
+                    break;
+                }
+                if self.digits[self.len - 1] == 9 {
+                    self.len -= 1;
+                } else {
+                    self.digits[self.len - 1] += 1;
+                    break;
+                }
+            }
+        }
+        self.trim();
+    }
+
+    /// The digit at `pos`, counted from the first digit, 0 out of the digits
+    fn digit(&self, pos: i32) -> char {
+        let digit = if pos < 0 || pos >= self.len as i32 {
+            0
+        } else {
+            self.digits[pos as usize]
+        };
+        (b'0' + digit) as char
+    }
+
+    /// Writes the number without an exponent, with `frac_digits` digits after the point (none
+    /// without the point when 0), or only the digits it has when `None`
+    fn write_fixed(&self, buf: &mut WriteBuf, frac_digits: Option<i32>) {
+        if self.exp <= 0 {
+            let _ = buf.write_char('0');
+        }
+        for pos in 0..self.exp {
+            let _ = buf.write_char(self.digit(pos));
+        }
+        let frac_digits = frac_digits.unwrap_or(self.len as i32 - self.exp);
+        if frac_digits > 0 {
+            let _ = buf.write_char('.');
+            for pos in self.exp..self.exp + frac_digits {
+                let _ = buf.write_char(self.digit(pos));
+            }
+        }
+    }
+
+    /// Writes the number with an exponent, as `printf` does (with at least 2 digits) or as
+    /// JavaScript does, with `int_digits` digits before the point and `frac_digits` digits after
+    /// it, or only the digits it has
+    fn write_exp(
+        &self,
+        buf: &mut WriteBuf,
+        int_digits: i32,
+        frac_digits: Option<i32>,
+        printf: bool,
+    ) {
+        for pos in 0..int_digits {
+            let _ = buf.write_char(self.digit(pos));
+        }
+        let frac_digits = frac_digits.unwrap_or(self.len as i32 - int_digits);
+        if frac_digits > 0 {
+            let _ = buf.write_char('.');
+            for pos in int_digits..int_digits + frac_digits {
+                let _ = buf.write_char(self.digit(pos));
+            }
+        }
+        let exp = if self.len == 0 {
+            0
+        } else {
+            self.exp - int_digits
+        };
+        let sign = if exp < 0 { '-' } else { '+' };
+        if printf {
+            let _ = write!(buf, "e{}{:02}", sign, exp.abs());
+        } else {
+            let _ = write!(buf, "e{}{}", sign, exp.abs());
+        }
+    }
+}
+
+/// Writes the sign, and `inf` or `nan` when `a` is not finite, returns whether it was finite
+fn write_sign(buf: &mut WriteBuf, a: f64) -> bool {
+    if a.is_sign_negative() {
+        let _ = buf.write_char('-');
+    }
+    if a.is_nan() {
+        let _ = buf.write_str("nan");
+    } else if a.is_infinite() {
+        let _ = buf.write_str("inf");
+    }
+    a.is_finite()
+}
+
+/// The shortest digits that read back as `a` (finite, not negative), as a `Decimal`
+fn shortest(a: f64) -> Decimal {
+    // Formatted by `core` as `d.dddde-n` and parsed back
+    let mut text = [0u8; 32];
+    let mut text_buf = WriteBuf::new(&mut text);
+    let _ = write!(&mut text_buf, "{:e}", a);
+    let text = text_buf.as_str().as_bytes();
+
+    let mut dec = Decimal {
+        digits: [0; MAX_DIGITS],
+        len: 0,
+        exp: 0,
+    };
+    let mut pos = 0;
+    while pos < text.len() && text[pos] != b'e' {
+        if text[pos] != b'.' {
+            dec.push(text[pos] - b'0');
+        }
+        pos += 1;
+    }
+    let (negative, exp) = match text.get(pos + 1) {
+        Some(b'-') => (true, &text[pos + 2..]),
+        _ => (false, &text[pos + 1..]),
     };
+    let exp = exp
+        .iter()
+        .fold(0i32, |exp, digit| exp * 10 + i32::from(digit - b'0'));
+    dec.exp = if negative { -exp } else { exp } + 1;
+    dec.trim();
+    if dec.len == 0 {
+        dec.exp = 0;
+    }
+    dec
+}
 
-    let n_written = libc::snprintf(
-        buf.as_ptr() as *mut _,
-        120,
-        fmt.as_ptr() as *const _,
-        prec,
-        a as libc::c_double,
-    );
+fn write_shortest(buf: &mut WriteBuf, a: f64) {
+    if !write_sign(buf, a) {
+        return;
+    }
+    let dec = shortest(f64::from_bits(a.to_bits() & !(1 << 63)));
+    if dec.exp > -6 && dec.exp <= 21 {
+        dec.write_fixed(buf, None);
+    } else {
+        dec.write_exp(buf, 1, None, false);
+    }
+}
+
+/// Writes `a` in the `mode` of the module documentation, with the precision `prec`
+fn write_mode(buf: &mut WriteBuf, a: f64, prec: i32, mode: u32) {
+    if !write_sign(buf, a) {
+        return;
+    }
+    match mode {
+        0 => {
+            let mut dec = Decimal::exact(a);
+            dec.round(dec.exp + prec);
+            dec.write_fixed(buf, Some(prec));
+        }
+        1 => {
+            let mut dec = Decimal::exact(a);
+            dec.round(prec + 1);
+            dec.write_exp(buf, 1, Some(prec), true);
+        }
+        2 => {
+            // `prec` significant digits, without an exponent when it's from -4 to below `prec`,
+            // and without trailing zeros
+            let prec = core::cmp::max(prec, 1);
+            let mut dec = Decimal::exact(a);
+            dec.round(prec);
+            let exp = if dec.len == 0 { 0 } else { dec.exp - 1 };
+            if exp >= -4 && exp < prec {
+                dec.write_fixed(buf, None);
+            } else {
+                dec.write_exp(buf, 1, None, true);
+            }
+        }
+        3 => write_hex(buf, a, Some(prec)),
+        4 => {
+            // The digits before the point of the exponent, which may change by rounding up to a
+            // power of ten
+            let int_digits = |dec: &Decimal| {
+                if dec.len == 0 {
+                    1
+                } else {
+                    (dec.exp - 1).rem_euclid(3) + 1
+                }
+            };
+            let mut dec = Decimal::exact(a);
+            dec.round(int_digits(&dec) + prec);
+            dec.write_exp(buf, int_digits(&dec), Some(prec), true);
+        }
+        5 => write_hex(buf, a, None),
+        _ => unsafe {
+            crate::rts_trap_with(ErrorCode::InvalidArgument, "float_fmt: unrecognized mode")
+        },
+    }
+}
 
-    assert!(n_written > 0);
+/// `%a`: the normalized binary significand in hexadecimal with `prec` digits after the point
+/// (rounded half to even, so the first digit may become 2), or the digits it needs, and the
+/// binary exponent
+fn write_hex(buf: &mut WriteBuf, a: f64, prec: Option<i32>) {
+    let bits = a.to_bits();
+    let biased_exp = ((bits >> 52) & 0x7ff) as i32;
+    let mut frac = bits & ((1 << 52) - 1);
+    let (lead, exp) = if biased_exp == 0 && frac == 0 {
+        (0, 0)
+    } else if biased_exp == 0 {
+        let shift = frac.leading_zeros() - 11;
+        frac = (frac << shift) & ((1 << 52) - 1);
+        (1, -1022 - shift as i32)
+    } else {
+        (1, biased_exp - 1023)
+    };
+
+    let prec = match prec {
+        Some(prec) => prec as u32,
+        None if frac == 0 => 0,
+        None => 13 - frac.trailing_zeros() / 4,
+    };
+    let (lead, frac) = if prec < 13 {
+        let dropped = 52 - 4 * prec;
+        let full = lead << 52 | frac;
+        let mut kept = full >> dropped;
+        let rest = full & ((1 << dropped) - 1);
+        let half = 1 << (dropped - 1);
+        if rest > half || (rest == half && kept & 1 == 1) {
+            kept += 1;
+        }
+        (kept >> (4 * prec), kept & ((1 << (4 * prec)) - 1))
+    } else {
+        (lead, frac)
+    };
+
+    let _ = write!(buf, "0x{}", lead);
+    if prec > 0 {
+        let _ = buf.write_char('.');
+        let digits = core::cmp::min(prec, 13);
+        for i in (0..digits).rev() {
+            let digit = (frac >> (4 * i)) & 0xf;
+            let _ = buf.write_char(core::char::from_digit(digit as u32, 16).unwrap());
+        }
+        for _ in digits..prec {
+            let _ = buf.write_char('0');
+        }
+    }
+    let sign = if exp < 0 { '-' } else { '+' };
+    let _ = write!(buf, "p{}{}", sign, exp.abs());
+}
+
+/// The shortest text that reads back as `a`, see the module documentation
+#[no_mangle]
+pub unsafe extern "C" fn float_to_text(a: f64) -> SkewedPtr {
+    let mut bytes = [0u8; BUF_SIZE];
+    let mut buf = WriteBuf::new(&mut bytes);
+    write_shortest(&mut buf, a);
+    let text = buf.as_str();
+    text_of_ptr_size(text.as_ptr(), Bytes(text.len()))
+}
+
+/// `a` in the `mode` of the module documentation, traps on other modes
+#[no_mangle]
+pub unsafe extern "C" fn float_fmt(a: f64, prec: u32, mode: u32) -> SkewedPtr {
+    // prec and mode are Nat8, in the highest byte of the word
+    let mode = mode >> 24;
+    let prec = core::cmp::min(prec >> 24, 100) as i32;
 
-    text_of_ptr_size(buf.as_ptr(), Bytes(n_written as u32))
+    let mut bytes = [0u8; BUF_SIZE];
+    let mut buf = WriteBuf::new(&mut bytes);
+    write_mode(&mut buf, a, prec, mode);
+    let text = buf.as_str();
+    text_of_ptr_size(text.as_ptr(), Bytes(text.len()))
 }
@@ -0,0 +1,521 @@
+//! Parsing of floats from text, without libc, for `Float.fromText`: the float nearest to the
+//! decimal number of the text (ties to even). Parsing returns 0 instead of trapping when the text
+//! is not a float, so that the generated code can return `null`.
+//!
+//! A float is an optional sign, then `inf`, `infinity` or `nan` (in any case), or digits with an
+//! optional point and fraction, and an optional exponent (`e` or `E`, an optional sign and
+//! digits). There must be a digit before or after the point. Whitespace is not accepted.
+//!
+//! As in the `dec2flt` of recent Rust versions (and the `fast_float` library), the first 19
+//! significant digits are read into a `u64`, and converted by the first of these methods that
+//! decides:
+//!
+//! 1. Clinger's fast path: when the digits and the power of ten are exact floats, one
+//!    multiplication or division is correctly rounded.
+//! 2. The algorithm of Eisel and Lemire: the digits times the 128 most significant bits of the
+//!    power of ten (of `float_tables.rs`), which decides unless the product is too close to a
+//!    halfway point. With more than 19 digits, the truncated digits and the next larger ones must
+//!    round to the same float.
+//! 3. The exact decimal expansion, of up to 768 significant digits (beyond them, only whether a
+//!    digit is not 0 matters), shifted by powers of two into the range of the mantissa, as in
+//!    Nigel Tao's "simple decimal conversion".
+
+use crate::alloc::alloc_words;
+use crate::float_tables::{LARGEST_POWER, POWERS_OF_FIVE, SMALLEST_POWER};
+use crate::text::text_str;
+use crate::types::{size_of, Bits64, SkewedPtr, TAG_BITS64};
+
+/// Bits of the mantissa, without the implicit bit
+const MANTISSA_BITS: i32 = 52;
+
+/// Biased exponent of the infinities
+const INFINITE_POWER: i32 = 0x7FF;
+
+/// Exponent of the smallest normal floats, minus 1
+const MINIMUM_EXPONENT: i32 = -1023;
+
+/// Significant digits read into a `u64`
+const MAX_MANTISSA_DIGITS: usize = 19;
+
+/// Powers of ten that are exact floats
+const EXACT_POWERS: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+    1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+/// The digits of a decimal number before and after the point, and its exponent
+struct Number<'a> {
+    int: &'a [u8],
+    frac: &'a [u8],
+    exponent: i64,
+}
+
+impl<'a> Number<'a> {
+    fn digits(&self) -> impl Iterator<Item = u8> + 'a {
+        self.int.iter().chain(self.frac.iter()).map(|&c| c - b'0')
+    }
+
+    /// Returns `(w, q, truncated)` for the number `w * 10^q`, of the first 19 significant digits
+    /// of the number when `truncated`
+    fn mantissa(&self) -> (u64, i64, bool) {
+        let significant = self.digits().skip_while(|&d| d == 0).count();
+        let mut w = 0u64;
+        for d in self
+            .digits()
+            .skip_while(|&d| d == 0)
+            .take(MAX_MANTISSA_DIGITS)
+        {
+            w = w * 10 + u64::from(d);
+        }
+        let q = self.exponent - self.frac.len() as i64;
+        if significant <= MAX_MANTISSA_DIGITS {
+            (w, q, false)
+        } else {
+            (w, q + (significant - MAX_MANTISSA_DIGITS) as i64, true)
+        }
+    }
+}
+
+fn digits_len(s: &[u8]) -> usize {
+    s.iter().take_while(|c| c.is_ascii_digit()).count()
+}
+
+/// Parses a decimal number, without sign
+fn parse_number(s: &[u8]) -> Option<Number<'_>> {
+    let int = &s[..digits_len(s)];
+    let mut pos = int.len();
+
+    let mut frac: &[u8] = &[];
+    if s.get(pos) == Some(&b'.') {
+        pos += 1;
+        frac = &s[pos..pos + digits_len(&s[pos..])];
+        pos += frac.len();
+    }
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+
+    let mut exponent = 0i64;
+    if let Some(b'e') | Some(b'E') = s.get(pos) {
+        pos += 1;
+        let negative = s.get(pos) == Some(&b'-');
+        if let Some(b'-') | Some(b'+') = s.get(pos) {
+            pos += 1;
+        }
+        let digits = &s[pos..pos + digits_len(&s[pos..])];
+        if digits.is_empty() {
+            return None;
+        }
+        pos += digits.len();
+        for &c in digits {
+            // Far beyond the range of floats, the exponent does not matter
+            if exponent < 0x1_0000 {
+                exponent = exponent * 10 + i64::from(c - b'0');
+            }
+        }
+        if negative {
+            exponent = -exponent;
+        }
+    }
+
+    if pos != s.len() {
+        return None;
+    }
+    Some(Number {
+        int,
+        frac,
+        exponent,
+    })
+}
+
+/// Parses the bytes as a float, returns `None` when they are not a float
+pub fn parse_float(s: &[u8]) -> Option<f64> {
+    let (negative, s) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let magnitude = if s.eq_ignore_ascii_case(b"inf") || s.eq_ignore_ascii_case(b"infinity") {
+        f64::INFINITY
+    } else if s.eq_ignore_ascii_case(b"nan") {
+        f64::NAN
+    } else {
+        convert(&parse_number(s)?)
+    };
+
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+fn convert(number: &Number) -> f64 {
+    let (w, q, truncated) = number.mantissa();
+    if !truncated {
+        if let Some(f) = clinger(w, q) {
+            return f;
+        }
+    }
+
+    let mut bits = eisel_lemire(w, q);
+    if truncated && bits.is_some() && bits != eisel_lemire(w + 1, q) {
+        bits = None;
+    }
+    let bits = bits.unwrap_or_else(|| Decimal::of_number(number).round_to_float_bits());
+    f64::from_bits(bits)
+}
+
+/// The float `w * 10^q`, when `w` and `10^q` are exact floats
+fn clinger(w: u64, q: i64) -> Option<f64> {
+    if w >> (MANTISSA_BITS + 1) != 0 || !(-22..=22).contains(&q) {
+        return None;
+    }
+    let f = w as f64;
+    Some(if q < 0 {
+        f / EXACT_POWERS[-q as usize]
+    } else {
+        f * EXACT_POWERS[q as usize]
+    })
+}
+
+/// The bits of the float `w * 10^q`, `None` when the product with the 128-bit power of ten does
+/// not decide
+fn eisel_lemire(mut w: u64, q: i64) -> Option<u64> {
+    if w == 0 || q < i64::from(SMALLEST_POWER) {
+        return Some(0);
+    }
+    if q > i64::from(LARGEST_POWER) {
+        return Some(float_bits(0, INFINITE_POWER));
+    }
+
+    let lz = w.leading_zeros() as i32;
+    w <<= lz;
+    let (lo, hi) = product_approx(w, q as i32);
+    // The lower bits of the product are all ones, it may be above them when the power of ten
+    // is not exact
+    if lo == u64::MAX && !(-27..=55).contains(&q) {
+        return None;
+    }
+
+    let upper_bit = (hi >> 63) as i32;
+    let shift = upper_bit + 64 - MANTISSA_BITS - 3;
+    let mut mantissa = hi >> shift;
+    let mut power2 = binary_exponent(q as i32) + upper_bit - lz - MINIMUM_EXPONENT;
+
+    if power2 <= 0 {
+        // A subnormal float
+        if -power2 + 1 >= 64 {
+            return Some(0);
+        }
+        mantissa >>= -power2 + 1;
+        mantissa += mantissa & 1;
+        mantissa >>= 1;
+        // Rounding up may give the smallest normal float
+        let power2 = (mantissa >= 1 << MANTISSA_BITS) as i32;
+        return Some(float_bits(mantissa, power2));
+    }
+
+    // Exactly halfway between two floats, round to even. The product can only be exact for
+    // small powers of ten.
+    if lo <= 1 && (-4..=23).contains(&q) && mantissa & 3 == 1 && mantissa << shift == hi {
+        mantissa &= !1;
+    }
+
+    mantissa += mantissa & 1;
+    mantissa >>= 1;
+    if mantissa >= 2 << MANTISSA_BITS {
+        mantissa = 1 << MANTISSA_BITS;
+        power2 += 1;
+    }
+    if power2 >= INFINITE_POWER {
+        return Some(float_bits(0, INFINITE_POWER));
+    }
+    Some(float_bits(mantissa, power2))
+}
+
+/// `floor(q * log2(10)) + 63`
+fn binary_exponent(q: i32) -> i32 {
+    (q.wrapping_mul(152_170 + 65536) >> 16) + 63
+}
+
+/// The 128 most significant bits `(low, high)` of `w` times the power of ten `10^q`, exact in
+/// the bits of the mantissa and the rounding bits
+fn product_approx(w: u64, q: i32) -> (u64, u64) {
+    // One more bit than the mantissa and the rounding bit
+    let mask = u64::MAX >> (MANTISSA_BITS + 3);
+    let (hi5, lo5) = POWERS_OF_FIVE[(q - SMALLEST_POWER) as usize];
+    let (mut lo, mut hi) = full_multiplication(w, hi5);
+    if hi & mask == mask {
+        let (_, second_hi) = full_multiplication(w, lo5);
+        lo = lo.wrapping_add(second_hi);
+        if second_hi > lo {
+            hi += 1;
+        }
+    }
+    (lo, hi)
+}
+
+fn full_multiplication(a: u64, b: u64) -> (u64, u64) {
+    let r = u128::from(a) * u128::from(b);
+    (r as u64, (r >> 64) as u64)
+}
+
+/// The bits of a non-negative float, of the mantissa (without the implicit bit, which is
+/// masked) and the biased exponent
+fn float_bits(mantissa: u64, power2: i32) -> u64 {
+    mantissa & ((1 << MANTISSA_BITS) - 1) | (power2 as u64) << MANTISSA_BITS
+}
+
+/// Most significant digits of a `Decimal`
+const MAX_DIGITS: usize = 768;
+
+/// Beyond this decimal point, a `Decimal` is 0 or infinite as a float
+const DECIMAL_POINT_RANGE: i32 = 2047;
+
+/// Most bits of a shift of a `Decimal`, so a digit shifted fits in a `u64`
+const MAX_SHIFT: u32 = 60;
+
+/// A decimal number `0.d1 d2 ... dn * 10^decimal_point`, without leading or trailing zeros, and
+/// with more non-zero digits after the last when `truncated`. 0 has no digits.
+struct Decimal {
+    digits: [u8; MAX_DIGITS],
+    len: usize,
+    decimal_point: i32,
+    truncated: bool,
+}
+
+impl Decimal {
+    fn of_number(number: &Number) -> Self {
+        let mut d = Decimal {
+            digits: [0; MAX_DIGITS],
+            len: 0,
+            decimal_point: 0,
+            truncated: false,
+        };
+        let leading_zeros = number.digits().take_while(|&d| d == 0).count();
+        for digit in number.digits().skip(leading_zeros) {
+            if d.len < MAX_DIGITS {
+                d.digits[d.len] = digit;
+                d.len += 1;
+            } else if digit != 0 {
+                d.truncated = true;
+            }
+        }
+        d.trim();
+        let decimal_point = number.int.len() as i64 - leading_zeros as i64 + number.exponent;
+        let range = i64::from(DECIMAL_POINT_RANGE) * 2;
+        d.decimal_point = core::cmp::max(-range, core::cmp::min(decimal_point, range)) as i32;
+        d
+    }
+
+    fn trim(&mut self) {
+        while self.len != 0 && self.digits[self.len - 1] == 0 {
+            self.len -= 1;
+        }
+    }
+
+    /// Multiplies by `2^shift`
+    fn left_shift(&mut self, shift: u32) {
+        if self.len == 0 {
+            return;
+        }
+        // The digits of the product, least significant first
+        let mut product = [0u8; MAX_DIGITS + 20];
+        let mut len = 0;
+        let mut n = 0u64;
+        for i in (0..self.len).rev() {
+            n += u64::from(self.digits[i]) << shift;
+            product[len] = (n % 10) as u8;
+            len += 1;
+            n /= 10;
+        }
+        while n > 0 {
+            product[len] = (n % 10) as u8;
+            len += 1;
+            n /= 10;
+        }
+
+        let kept = core::cmp::min(len, MAX_DIGITS);
+        for i in 0..kept {
+            self.digits[i] = product[len - 1 - i];
+        }
+        if product[..len - kept].iter().any(|&d| d != 0) {
+            self.truncated = true;
+        }
+        self.decimal_point += (len - self.len) as i32;
+        self.len = kept;
+        self.trim();
+    }
+
+    /// Divides by `2^shift`
+    fn right_shift(&mut self, shift: u32) {
+        let mut read = 0;
+        let mut n = 0u64;
+        while n >> shift == 0 {
+            if read < self.len {
+                n = 10 * n + u64::from(self.digits[read]);
+                read += 1;
+            } else if n == 0 {
+                return;
+            } else {
+                while n >> shift == 0 {
+                    n *= 10;
+                    read += 1;
+                }
+                break;
+            }
+        }
+
+        self.decimal_point -= read as i32 - 1;
+        if self.decimal_point < -DECIMAL_POINT_RANGE {
+            self.len = 0;
+            self.decimal_point = 0;
+            self.truncated = false;
+            return;
+        }
+
+        let mask = (1 << shift) - 1;
+        let mut write = 0;
+        while read < self.len {
+            let digit = (n >> shift) as u8;
+            n = 10 * (n & mask) + u64::from(self.digits[read]);
+            read += 1;
+            self.digits[write] = digit;
+            write += 1;
+        }
+        while n > 0 {
+            let digit = (n >> shift) as u8;
+            n = 10 * (n & mask);
+            if write < MAX_DIGITS {
+                self.digits[write] = digit;
+                write += 1;
+            } else if digit > 0 {
+                self.truncated = true;
+            }
+        }
+        self.len = write;
+        self.trim();
+    }
+
+    /// The integer part, rounded half to even, saturated when it does not fit in a `u64`
+    fn round(&self) -> u64 {
+        if self.len == 0 || self.decimal_point < 0 {
+            return 0;
+        }
+        if self.decimal_point > 18 {
+            return u64::MAX;
+        }
+        let point = self.decimal_point as usize;
+        let mut n = 0u64;
+        for i in 0..point {
+            n = 10 * n + u64::from(self.digit(i));
+        }
+        let round_up = if point < self.len {
+            if self.digits[point] == 5 && point + 1 == self.len {
+                self.truncated || (point != 0 && self.digits[point - 1] & 1 != 0)
+            } else {
+                self.digits[point] >= 5
+            }
+        } else {
+            false
+        };
+        n + round_up as u64
+    }
+
+    fn digit(&self, i: usize) -> u8 {
+        if i < self.len {
+            self.digits[i]
+        } else {
+            0
+        }
+    }
+
+    fn round_to_float_bits(&mut self) -> u64 {
+        // Shifts by at most `MAX_SHIFT` bits, as much as gets closer to 1 by the decimal point
+        fn shift_of(decimal_point: i32) -> u32 {
+            const SHIFTS: [u32; 19] = [
+                0, 3, 6, 9, 13, 16, 19, 23, 26, 29, 33, 36, 39, 43, 46, 49, 53, 56, 59,
+            ];
+            SHIFTS
+                .get(decimal_point as usize)
+                .copied()
+                .unwrap_or(MAX_SHIFT)
+        }
+
+        let infinity = float_bits(0, INFINITE_POWER);
+        if self.len == 0 || self.decimal_point < -324 {
+            return 0;
+        }
+        if self.decimal_point >= 310 {
+            return infinity;
+        }
+
+        // Into [1/2, 1), with the binary exponent in `exp2`
+        let mut exp2 = 0;
+        while self.decimal_point > 0 {
+            let shift = shift_of(self.decimal_point);
+            self.right_shift(shift);
+            if self.decimal_point < -DECIMAL_POINT_RANGE {
+                return 0;
+            }
+            exp2 += shift as i32;
+        }
+        while self.decimal_point <= 0 {
+            let shift = if self.decimal_point == 0 {
+                match self.digits[0] {
+                    5..=9 => break,
+                    0 | 1 => 2,
+                    _ => 1,
+                }
+            } else {
+                shift_of(-self.decimal_point)
+            };
+            self.left_shift(shift);
+            if self.decimal_point > DECIMAL_POINT_RANGE {
+                return infinity;
+            }
+            exp2 -= shift as i32;
+        }
+
+        // Into [1, 2), and into the range of subnormals
+        exp2 -= 1;
+        while MINIMUM_EXPONENT + 1 > exp2 {
+            let shift = core::cmp::min((MINIMUM_EXPONENT + 1 - exp2) as u32, MAX_SHIFT);
+            self.right_shift(shift);
+            exp2 += shift as i32;
+        }
+        if exp2 - MINIMUM_EXPONENT >= INFINITE_POWER {
+            return infinity;
+        }
+
+        self.left_shift(MANTISSA_BITS as u32 + 1);
+        let mut mantissa = self.round();
+        if mantissa >= 1 << (MANTISSA_BITS + 1) {
+            // Rounded up to the next power of two
+            self.right_shift(1);
+            exp2 += 1;
+            mantissa = self.round();
+            if exp2 - MINIMUM_EXPONENT >= INFINITE_POWER {
+                return infinity;
+            }
+        }
+        let mut power2 = exp2 - MINIMUM_EXPONENT;
+        if mantissa < 1 << MANTISSA_BITS {
+            power2 -= 1;
+        }
+        float_bits(mantissa, power2)
+    }
+}
+
+/// Parses the text as a float, returns a boxed float, or 0 when the text is not a float
+#[no_mangle]
+pub unsafe extern "C" fn float_of_text(t: SkewedPtr) -> SkewedPtr {
+    let f = match parse_float(text_str(t).as_bytes()) {
+        Some(f) => f,
+        None => return SkewedPtr(0),
+    };
+
+    let r = alloc_words(size_of::<Bits64>());
+    let boxed = r.unskew() as *mut Bits64;
+    (*boxed).header.tag = TAG_BITS64;
+    (*boxed).bits = f.to_bits();
+    r
+}
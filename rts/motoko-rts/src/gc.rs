@@ -1,20 +1,73 @@
+mod card_table;
+pub(crate) mod generational;
+#[cfg(feature = "heap_sanity")]
+mod heap_sanity;
+pub(crate) mod incremental;
+pub(crate) mod large_objects;
+pub(crate) mod mark_bitmap;
+pub(crate) mod mark_sweep;
+mod schedule;
+pub(crate) mod weak_refs;
+pub(crate) mod word_stack;
+
 use crate::alloc;
+use crate::alloc_profile::{alloc_profile_table_loc, update_alloc_profile};
 use crate::closure_table::closure_table_loc;
+use crate::error_code::ErrorCode;
+use crate::finalizers::{finalizer_table_loc, queue_finalizers};
+use crate::idl;
+use crate::idl_plan_cache::idl_plan_cache_loc;
 use crate::mem::{memcpy_bytes, memcpy_words};
+use crate::perf::{perf_instructions, Subsystem};
+use crate::pin::pin_table_loc;
 use crate::rts_trap_with;
+use crate::scratch;
+use crate::text_intern::text_intern_table_loc;
+use crate::timer::timer_table_loc;
+use crate::to_space_arena;
 use crate::types::*;
+use crate::visitor::visit_pointer_fields;
+
+use large_objects::LARGE_OBJECT_THRESHOLD;
+use word_stack::WordStack;
 
 extern "C" {
     /// Get __heap_base. Provided by the code generator (src/codegen/compile.ml).
-    pub(crate) fn get_heap_base() -> u32;
+    pub(crate) fn get_heap_base() -> usize;
 
     /// Get pointer to the static memory with an array to the static roots. Provided by the
     /// generated code.
     pub(crate) fn get_static_roots() -> SkewedPtr;
 }
 
+/// Visits the locations of the roots of the collectors and heap checks: the fields of the static
+/// root objects (which are not in the dynamic heap), and the pointers to the finalizer, pin,
+/// allocation profile, text intern, and timer tables, the Candid field plan cache, and the closure
+/// table, which comes last (see `visit_roots_but_closure_table`).
+pub(crate) unsafe fn visit_roots<F: FnMut(*mut SkewedPtr)>(mut visit: F) {
+    visit_roots_but_closure_table(&mut visit);
+    visit(closure_table_loc());
+}
+
+/// Visits the roots other than the closure table, see `visit_roots`. The objects only reachable
+/// from the closure table are the ones it retains, see `CLOSURE_TABLE_RETAINED`.
+pub(crate) unsafe fn visit_roots_but_closure_table<F: FnMut(*mut SkewedPtr)>(mut visit: F) {
+    let static_roots = get_static_roots().as_array();
+    for i in 0..static_roots.len() {
+        let obj = static_roots.get(i).unskew() as *mut Obj;
+        visit_pointer_fields(obj, &mut visit);
+    }
+
+    visit(finalizer_table_loc());
+    visit(pin_table_loc());
+    visit(alloc_profile_table_loc());
+    visit(text_intern_table_loc());
+    visit(idl_plan_cache_loc());
+    visit(timer_table_loc());
+}
+
 /// Maximum live data retained in a GC.
-static mut MAX_LIVE: Bytes<u32> = Bytes(0);
+static mut MAX_LIVE: Bytes<usize> = Bytes(0);
 
 /// Amount of garbage collected so far.
 static mut RECLAIMED: Bytes<u64> = Bytes(0);
@@ -22,24 +75,78 @@ static mut RECLAIMED: Bytes<u64> = Bytes(0);
 /// Counter for total allocations
 pub(crate) static mut ALLOCATED: Bytes<u64> = Bytes(0);
 
+/// Counter for the objects allocated by `alloc_words` and the allocation fast path. Wraps around.
+pub(crate) static mut ALLOCATED_OBJECTS: usize = 0;
+
 /// Heap pointer
-pub(crate) static mut HP: u32 = 0;
+pub(crate) static mut HP: usize = 0;
+
+/// End of the memory used so far, by the heap or by the collectors (e.g. to-space of the copying
+/// collectors). The memory up to here is allocated, see `grow_memory`.
+pub(crate) static mut MEMORY_END: usize = 0;
+
+/// Number of completed collections. A cycle of the incremental GC counts as one collection.
+static mut COLLECTIONS: u64 = 0;
+
+/// Heap memory not used by live objects after the last collection: gaps between large and pinned
+/// objects, free chunks of the mark-sweep GC
+static mut FRAGMENTATION: Bytes<usize> = Bytes(0);
+
+/// Heap memory only retained by the closure table in the last collection of the whole heap (not
+/// counting minor collections of the generational GC). Set by the collectors while marking or
+/// copying, they do the closure table last.
+static mut CLOSURE_TABLE_RETAINED: Bytes<usize> = Bytes(0);
+
+/// GC statistics, returned by `get_gc_stats`. The generated code reads the fields by offset, see
+/// `Heap.get_gc_stats` in `compile.ml`.
+#[repr(C)]
+pub struct RtsGcStats {
+    pub collections: u64,
+    pub reclaimed: Bytes<u64>,
+    /// Instructions of the collections, see `perf.rs`. Only counted with `--perf-counters`.
+    pub gc_instructions: u64,
+    pub max_live_size: Bytes<usize>,
+    pub heap_size: Bytes<usize>,
+    pub fragmentation: Bytes<usize>,
+    /// Heap memory only retained by the closure table (the callbacks of outstanding calls), as of
+    /// the last collection of the whole heap
+    pub callback_table_retained: Bytes<usize>,
+}
+
+/// Soft limit on the size of the dynamic heap, see `set_heap_limit`. 0 means no limit.
+static mut HEAP_LIMIT: Bytes<usize> = Bytes(0);
+
+/// Set by `gc_force`, cleared by the next collection
+static mut FORCED: bool = false;
+
+/// Nesting depth of the regions entered with `gc_defer`
+static mut DEFER_DEPTH: u32 = 0;
+
+static mut GC_STATS: RtsGcStats = RtsGcStats {
+    collections: 0,
+    reclaimed: Bytes(0),
+    gc_instructions: 0,
+    max_live_size: Bytes(0),
+    heap_size: Bytes(0),
+    fragmentation: Bytes(0),
+    callback_table_retained: Bytes(0),
+};
 
 #[no_mangle]
 unsafe extern "C" fn init() {
-    HP = get_heap_base() as u32;
+    HP = get_heap_base();
 }
 
-unsafe fn note_live_size(live: Bytes<u32>) {
+unsafe fn note_live_size(live: Bytes<usize>) {
     MAX_LIVE = ::core::cmp::max(MAX_LIVE, live);
 }
 
 #[no_mangle]
-unsafe extern "C" fn get_max_live_size() -> Bytes<u32> {
+unsafe extern "C" fn get_max_live_size() -> Bytes<usize> {
     MAX_LIVE
 }
 
-unsafe fn note_reclaimed(reclaimed: Bytes<u32>) {
+unsafe fn note_reclaimed(reclaimed: Bytes<usize>) {
     RECLAIMED += Bytes(reclaimed.0 as u64);
 }
 
@@ -48,32 +155,231 @@ unsafe extern "C" fn get_reclaimed() -> Bytes<u64> {
     RECLAIMED
 }
 
+/// Called at the end of each collection, after the heap pointer is reset. `live` is the size of
+/// the live objects in the dynamic heap.
+unsafe fn note_collection(live: Bytes<usize>) {
+    COLLECTIONS += 1;
+
+    let heap_size = Bytes(HP - get_heap_base());
+    FRAGMENTATION = if heap_size > live {
+        heap_size - live
+    } else {
+        Bytes(0)
+    };
+}
+
+/// Size of the closure table object, 0 when there is none. The table is only referenced by its
+/// root, so the collectors reach it after the other roots, but it is not counted as retained. It's
+/// also 0 when the table was `reached` from the other roots, e.g. when it's pinned (see `pin.rs`).
+unsafe fn closure_table_size<F: FnOnce(usize) -> bool>(reached: F) -> Bytes<usize> {
+    let table = *closure_table_loc();
+    if table.is_tagged_scalar() || reached(table.unskew()) {
+        Bytes(0)
+    } else {
+        object_size(table.unskew()).to_bytes()
+    }
+}
+
+/// Called by the mark-sweep and incremental GC when marking is done, with the size of the objects
+/// marked after the closure table
+unsafe fn note_closure_table_retained(retained: Bytes<usize>) {
+    CLOSURE_TABLE_RETAINED = retained;
+}
+
+/// Returns a pointer to the GC statistics. The record is updated on every call.
 #[no_mangle]
-unsafe extern "C" fn get_total_allocations() -> Bytes<u64> {
-    ALLOCATED
+unsafe extern "C" fn get_gc_stats() -> *const RtsGcStats {
+    GC_STATS = RtsGcStats {
+        collections: COLLECTIONS,
+        reclaimed: RECLAIMED,
+        gc_instructions: perf_instructions(Subsystem::Gc),
+        max_live_size: MAX_LIVE,
+        heap_size: get_heap_size(),
+        fragmentation: FRAGMENTATION,
+        callback_table_retained: CLOSURE_TABLE_RETAINED,
+    };
+    &GC_STATS
 }
 
+/// Requests a collection at the end of the current message, regardless of the scheduling
+/// heuristics of the collector and of `gc_defer`. The GC can't run in the middle of a message as
+/// the stack is not scanned.
+///
+/// The incremental GC does the whole cycle in one go, the generational GC does a major collection.
 #[no_mangle]
-unsafe extern "C" fn get_heap_size() -> Bytes<u32> {
+unsafe extern "C" fn gc_force() {
+    FORCED = true;
+}
+
+/// Enters (`defer` is true) or leaves (`defer` is false) a region, possibly spanning several
+/// messages, in which the collections scheduled at the end of messages are skipped. Regions nest.
+/// Forced collections are not deferred.
+///
+/// Allocation is not affected, the heap just grows until the region is left.
+#[no_mangle]
+unsafe extern "C" fn gc_defer(defer: bool) {
+    if defer {
+        DEFER_DEPTH += 1;
+    } else if DEFER_DEPTH == 0 {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "gc_defer: not in a deferred region",
+        );
+    } else {
+        DEFER_DEPTH -= 1;
+    }
+}
+
+/// Whether the collection scheduled by the generated code should be skipped, see `gc_defer`
+unsafe fn deferred() -> bool {
+    DEFER_DEPTH != 0 && !FORCED
+}
+
+/// Whether a collection was requested with `gc_force`. Clears the request.
+unsafe fn take_forced() -> bool {
+    let forced = FORCED;
+    FORCED = false;
+    forced
+}
+
+/// Sets a soft limit on the size of the dynamic heap, 0 removes the limit. An allocation that would
+/// grow the heap beyond the limit traps before changing the heap, so the IC rolls back the message
+/// and rejects the call. Unlike running out of memory, the caller gets an error it can catch, and
+/// the canister keeps working when the limit leaves enough memory for the GC.
+///
+/// The heap size includes the garbage allocated since the last collection. Memory allocated by the
+/// collectors is not limited.
+#[no_mangle]
+unsafe extern "C" fn set_heap_limit(limit: Bytes<usize>) {
+    HEAP_LIMIT = limit;
+    alloc::update_alloc_limit();
+}
+
+#[no_mangle]
+unsafe extern "C" fn get_heap_limit() -> Bytes<usize> {
+    HEAP_LIMIT
+}
+
+/// The highest heap pointer allowed by the heap limit, for the allocation fast path
+pub(crate) unsafe fn heap_limit_end() -> usize {
+    if HEAP_LIMIT.0 == 0 {
+        usize::MAX
+    } else {
+        get_heap_base().saturating_add(HEAP_LIMIT.0)
+    }
+}
+
+/// Called by `alloc_words` before bumping the heap pointer to `new_hp`
+pub(crate) unsafe fn check_heap_limit(new_hp: usize) {
+    if HEAP_LIMIT.0 != 0 && new_hp - get_heap_base() > HEAP_LIMIT.0 {
+        rts_trap_with(ErrorCode::OutOfMemory, "heap limit exceeded");
+    }
+}
+
+/// Called by the collectors when the heap is consistent again. Queues finalizers and updates the
+/// allocation profile, which may allocate. The heap limit is lifted meanwhile, so that a
+/// collection can't fail.
+unsafe fn after_collection() {
+    scratch::forget();
+    alloc::flush_fast_allocated();
+
+    let limit = HEAP_LIMIT;
+    HEAP_LIMIT = Bytes(0);
+
+    queue_finalizers();
+    update_alloc_profile();
+
+    HEAP_LIMIT = limit;
+    alloc::update_alloc_limit();
+}
+
+#[no_mangle]
+pub(crate) unsafe extern "C" fn get_total_allocations() -> Bytes<u64> {
+    ALLOCATED + Bytes(alloc::FAST_ALLOCATED as u64)
+}
+
+/// Totals at the start of the current message, see `message_alloc_reset`
+static mut MESSAGE_START_ALLOCATED: Bytes<u64> = Bytes(0);
+static mut MESSAGE_START_OBJECTS: usize = 0;
+
+/// Allocations since the start of the current message, returned by `get_message_allocation`. The
+/// generated code reads the fields by offset, see `Heap.get_message_allocation` in `compile.ml`.
+#[repr(C)]
+pub struct MessageAllocation {
+    pub bytes: Bytes<u64>,
+    pub objects: usize,
+}
+
+static mut MESSAGE_ALLOCATION: MessageAllocation = MessageAllocation {
+    bytes: Bytes(0),
+    objects: 0,
+};
+
+/// Starts counting the allocations of a message. Called by the generated code at the start of
+/// each message (including callbacks), and of init and upgrades.
+#[no_mangle]
+unsafe extern "C" fn message_alloc_reset() {
+    MESSAGE_START_ALLOCATED = get_total_allocations();
+    MESSAGE_START_OBJECTS = ALLOCATED_OBJECTS;
+    idl::idl_stats_reset();
+}
+
+/// Returns a pointer to the allocations since the last `message_alloc_reset`. The record is
+/// updated on every call. Allocations of the collectors are not counted.
+#[no_mangle]
+unsafe extern "C" fn get_message_allocation() -> *const MessageAllocation {
+    MESSAGE_ALLOCATION = MessageAllocation {
+        bytes: get_total_allocations() - MESSAGE_START_ALLOCATED,
+        objects: ALLOCATED_OBJECTS.wrapping_sub(MESSAGE_START_OBJECTS),
+    };
+    &MESSAGE_ALLOCATION
+}
+
+#[no_mangle]
+unsafe extern "C" fn get_heap_size() -> Bytes<usize> {
     Bytes(HP - get_heap_base())
 }
 
+/// Allocate a blob for GC-internal data. Unlike `alloc_blob` this does not count as an allocation
+/// in the stats.
+pub(crate) unsafe fn alloc_gc_blob(size: Bytes<usize>) -> *mut Blob {
+    let blob_size = (size_of::<Blob>() + size.to_words()).to_bytes();
+    to_space_arena::make_room(blob_size);
+
+    let blob = HP as *mut Blob;
+    let new_hp = HP + blob_size.0;
+    alloc::grow_memory(new_hp);
+    HP = new_hp;
+
+    (*blob).header.tag = TAG_BLOB;
+    (*blob).len = size;
+    blob
+}
+
 /// Evacuate (copy) an object in from-space to to-space, update end_to_space. If the object was
 /// already evacuated end_to_space is not changed.
 ///
 /// Arguments:
 ///
-/// - begin_from_space: Where the dynamic heap starts. Used for two things:
+/// - begin_from_space: Where the dynamic heap starts. An object is static if its address is below
+///   this value. These objects either don't point to dynamic heap, or listed in static_roots
+///   array. The fields of the objects in static_roots are evacuated as roots, see
+///   `visit_roots`. So we skip these objects here.
+///
+///   In a minor collection of the generational GC this is the beginning of the young generation,
+///   and old objects are skipped the same way. Old objects that point to young objects are found
+///   via the remembered set.
 ///
-///   - An object is static if its address is below this value. These objects either don't point to
-///     dynamic heap, or listed in static_roots array. Objects in static_roots are scavenged
-///     separately in `evac_static_roots` below. So we skip these objects here.
+/// - begin_to_space: Where to-space starts.
 ///
-///   - After all objects are evacuated we move to-space to from-space, to be able to do that the
-///     pointers need to point to their (eventual) locations in from-space, which is calculated with
-///     `end_to_space - begin_to_space + begin_from_space`.
+/// - final_begin: After all objects are evacuated we move to-space to from-space, to be able to do
+///   that the pointers need to point to their (eventual) locations in from-space, which is
+///   calculated with `end_to_space - begin_to_space + final_begin`. This is usually
+///   `begin_from_space`.
 ///
-/// - begin_to_space: Where to-space starts. See above for how this is used.
+///   When there are large objects in from-space, which are not moved, to-space cannot be copied
+///   back in one piece. In that case this is `begin_to_space`, so pointers are set to the
+///   to-space locations, and relocated after all objects are evacuated. See `large_objects`.
 ///
 /// - end_to_space: Where the object in `ptr_loc` will be copied.
 ///
@@ -82,6 +388,7 @@ unsafe extern "C" fn get_heap_size() -> Bytes<u32> {
 unsafe fn evac(
     begin_from_space: usize,
     begin_to_space: usize,
+    final_begin: usize,
     end_to_space: &mut usize,
     ptr_loc: usize,
 ) {
@@ -109,14 +416,22 @@ unsafe fn evac(
     let obj_size = object_size(obj as usize);
     let obj_size_bytes = obj_size.to_bytes();
 
+    // Large and pinned objects are not moved, only marked. They are scavenged in
+    // `copy_live_objects`.
+    if (obj_size_bytes >= LARGE_OBJECT_THRESHOLD || large_objects::has_small_objects())
+        && large_objects::mark(obj as usize)
+    {
+        return;
+    }
+
     // Grow memory if needed
-    alloc::grow_memory(*end_to_space + obj_size_bytes.0 as usize);
+    alloc::grow_memory(*end_to_space + obj_size_bytes.0);
 
     // Copy object to to-space
     memcpy_words(*end_to_space, obj as usize, obj_size);
 
     // Final location of the object after copying to-space back to from-space
-    let obj_loc = (*end_to_space - begin_to_space) + begin_from_space;
+    let obj_loc = (*end_to_space - begin_to_space) + final_begin;
 
     // Set forwarding pointer
     let fwd = obj as *mut FwdPtr;
@@ -127,12 +442,13 @@ unsafe fn evac(
     *ptr_loc = skew(obj_loc);
 
     // Update end of to-space
-    *end_to_space += obj_size_bytes.0 as usize
+    *end_to_space += obj_size_bytes.0
 }
 
 unsafe fn scav(
     begin_from_space: usize,
     begin_to_space: usize,
+    final_begin: usize,
     end_to_space: &mut usize,
     obj: usize,
 ) {
@@ -146,6 +462,7 @@ unsafe fn scav(
                 evac(
                     begin_from_space,
                     begin_to_space,
+                    final_begin,
                     end_to_space,
                     obj_payload.offset(i) as usize,
                 );
@@ -159,6 +476,7 @@ unsafe fn scav(
                 evac(
                     begin_from_space,
                     begin_to_space,
+                    final_begin,
                     end_to_space,
                     array_payload.offset(i) as usize,
                 );
@@ -168,7 +486,13 @@ unsafe fn scav(
         TAG_MUTBOX => {
             let mutbox = obj as *mut MutBox;
             let field_addr = ((&mut (*mutbox).field) as *mut _) as usize;
-            evac(begin_from_space, begin_to_space, end_to_space, field_addr);
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field_addr,
+            );
         }
 
         TAG_CLOSURE => {
@@ -178,6 +502,7 @@ unsafe fn scav(
                 evac(
                     begin_from_space,
                     begin_to_space,
+                    final_begin,
                     end_to_space,
                     closure_payload.offset(i) as usize,
                 );
@@ -187,108 +512,284 @@ unsafe fn scav(
         TAG_SOME => {
             let some = obj as *mut Some;
             let field_addr = ((&mut (*some).field) as *mut _) as usize;
-            evac(begin_from_space, begin_to_space, end_to_space, field_addr);
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field_addr,
+            );
         }
 
         TAG_VARIANT => {
             let variant = obj as *mut Variant;
             let field_addr = ((&mut (*variant).field) as *mut _) as usize;
-            evac(begin_from_space, begin_to_space, end_to_space, field_addr);
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field_addr,
+            );
         }
 
         TAG_CONCAT => {
             let concat = obj as *mut Concat;
             let field1_addr = ((&mut (*concat).text1) as *mut _) as usize;
-            evac(begin_from_space, begin_to_space, end_to_space, field1_addr);
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field1_addr,
+            );
             let field2_addr = ((&mut (*concat).text2) as *mut _) as usize;
-            evac(begin_from_space, begin_to_space, end_to_space, field2_addr);
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field2_addr,
+            );
+        }
+
+        TAG_SLICE => {
+            let slice = obj as *mut Slice;
+            let field_addr = ((&mut (*slice).blob) as *mut _) as usize;
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field_addr,
+            );
         }
 
         TAG_OBJ_IND => {
             let obj_ind = obj as *mut ObjInd;
             let field_addr = ((&mut (*obj_ind).field) as *mut _) as usize;
-            evac(begin_from_space, begin_to_space, end_to_space, field_addr);
+            evac(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                field_addr,
+            );
+        }
+
+        TAG_WEAK_REF => {
+            // Weak, updated after evacuation
+            weak_refs::note_copied();
         }
 
-        TAG_BITS64 | TAG_BITS32 | TAG_BLOB | TAG_BIGINT => {
+        TAG_BITS64 | TAG_BITS32 | TAG_BLOB | TAG_BIGINT | TAG_SMALL_TEXT => {
             // These don't include pointers, skip
         }
 
         TAG_NULL => {
-            rts_trap_with("encountered NULL object tag in dynamic object in scav");
+            rts_trap_with(
+                ErrorCode::Internal,
+                "encountered NULL object tag in dynamic object in scav",
+            );
         }
 
         TAG_FWD_PTR | _ => {
             // Any other tag is a bug
-            rts_trap_with("invalid object tag in scav");
+            rts_trap_with(ErrorCode::Internal, "invalid object tag in scav");
         }
     }
 }
 
-// We have a special evacuation routine for "static roots" array: we don't evacuate elements of
-// "static roots", we just scavenge them.
-unsafe fn evac_static_roots(
+/// The entry point. Called by the generated code.
+#[no_mangle]
+unsafe extern "C" fn collect() {
+    scratch::release();
+    to_space_arena::release();
+
+    if deferred() {
+        return;
+    }
+    take_forced();
+    alloc::free_list::reset();
+    generational::reset();
+
+    #[cfg(feature = "redzones")]
+    crate::alloc::redzones::check_heap();
+
+    #[cfg(feature = "heap_sanity")]
+    let old_end = HP;
+
+    copy_live_objects(get_heap_base(), &WordStack::new());
+    after_collection();
+
+    #[cfg(feature = "heap_sanity")]
+    heap_sanity::check_heap(old_end);
+}
+
+/// Scavenges the objects in to-space from `p`, and the marked large objects, until no more objects
+/// are evacuated
+unsafe fn scav_all(
     begin_from_space: usize,
     begin_to_space: usize,
+    final_begin: usize,
     end_to_space: &mut usize,
-    roots: *mut Array,
+    p: &mut usize,
 ) {
-    // The array and the objects pointed by the array are all static so we don't evacuate them. We
-    // only evacuate fields of objects in the array.
-    for i in 0..(*roots).len {
-        let obj = roots.get(i);
-        scav(begin_from_space, begin_to_space, end_to_space, obj.unskew());
+    loop {
+        while *p < *end_to_space {
+            // NB: end_to_space keeps changing within this loop
+            scav(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                *p,
+            );
+            *p += object_size(*p).to_bytes().0;
+        }
+
+        let scavenged = large_objects::scav_marked(|obj| {
+            scav(
+                begin_from_space,
+                begin_to_space,
+                final_begin,
+                end_to_space,
+                obj,
+            )
+        });
+
+        if !scavenged {
+            break;
+        }
     }
 }
 
-/// The entry point. Called by the generated code.
-#[no_mangle]
-unsafe extern "C" fn collect() {
-    let begin_from_space = get_heap_base() as usize;
-    let end_from_space = HP as usize;
+/// Copies live objects in `[begin_from_space, HP)` to to-space, and then back to
+/// `begin_from_space`. Objects below `begin_from_space` are not collected.
+///
+/// `remembered` holds (sorted, unique) locations in the uncollected part of the heap that may
+/// point to objects being collected. These are evacuated as roots.
+unsafe fn copy_live_objects(begin_from_space: usize, remembered: &WordStack) {
+    // Pinned objects are handled as large objects. This may grow the table, so needs to be done
+    // before to-space is allocated.
+    large_objects::add_pinned(begin_from_space);
+
+    let end_from_space = HP;
     let begin_to_space = end_from_space;
     let mut end_to_space = begin_to_space;
 
-    let static_roots = get_static_roots().as_array();
+    // Large objects in from-space are not moved, so we need to relocate pointers after evacuation
+    let relocate = large_objects::any_above(begin_from_space);
+    let final_begin = if relocate {
+        begin_to_space
+    } else {
+        begin_from_space
+    };
+
+    // Evacuate the roots other than the closure table, and everything reachable from them
+    visit_roots_but_closure_table(|loc| {
+        evac(
+            begin_from_space,
+            begin_to_space,
+            final_begin,
+            &mut end_to_space,
+            loc as usize,
+        )
+    });
+
+    for i in 0..remembered.len() {
+        evac(
+            begin_from_space,
+            begin_to_space,
+            final_begin,
+            &mut end_to_space,
+            remembered.get(i),
+        );
+    }
 
-    // Evacuate roots
-    evac_static_roots(
+    let mut p = begin_to_space;
+    scav_all(
         begin_from_space,
         begin_to_space,
+        final_begin,
         &mut end_to_space,
-        static_roots,
+        &mut p,
     );
 
+    // The objects evacuated from here on are only retained by the closure table
+    let table_size = closure_table_size(|table| {
+        (table as *mut Obj).tag() == TAG_FWD_PTR || large_objects::is_marked(table)
+    });
+    let copied_before = end_to_space;
+    let marked_before = large_objects::marked_size(begin_from_space);
+
     evac(
         begin_from_space,
         begin_to_space,
+        final_begin,
         &mut end_to_space,
         closure_table_loc() as usize,
     );
+    scav_all(
+        begin_from_space,
+        begin_to_space,
+        final_begin,
+        &mut end_to_space,
+        &mut p,
+    );
 
-    // Scavenge to-space
-    let mut p = begin_to_space;
-    while p < end_to_space {
-        // NB: end_to_space keeps changing within this loop
-        scav(begin_from_space, begin_to_space, &mut end_to_space, p);
-        p += object_size(p).to_bytes().0 as usize;
+    // Minor collections of the generational GC only see the young objects
+    if begin_from_space == get_heap_base() {
+        let retained = Bytes(end_to_space - copied_before)
+            + (large_objects::marked_size(begin_from_space) - marked_before);
+        CLOSURE_TABLE_RETAINED = retained - table_size;
     }
 
-    // Note the stats
-    let new_live_size = end_to_space - begin_to_space;
-    note_live_size(Bytes(new_live_size as u32));
+    // Clear weak references to dead objects. Needs the large object marks, so do this before
+    // sweeping large objects.
+    weak_refs::update_copied(begin_from_space, begin_to_space, end_to_space);
 
-    let reclaimed = (end_from_space - begin_from_space) - (end_to_space - begin_to_space);
-    note_reclaimed(Bytes(reclaimed as u32));
+    // Save the live large objects before from-space is overwritten. Leave space for a blob header
+    // between the new heap pointer and the entries, see `large_objects::restore`.
+    let mut large_entries = end_to_space + size_of::<Blob>().to_bytes().0;
+    let n_large = large_objects::sweep(begin_from_space, large_entries);
+    let live_large = large_objects::live_size(large_entries, n_large, begin_from_space);
 
-    // Copy to-space to the beginning of from-space
-    memcpy_bytes(
-        begin_from_space,
-        begin_to_space,
-        Bytes((end_to_space - begin_to_space) as u32),
-    );
+    // Note the stats
+    let new_live_size =
+        (begin_from_space - get_heap_base()) + (end_to_space - begin_to_space) + live_large.0;
+    note_live_size(Bytes(new_live_size));
+
+    let reclaimed =
+        (end_from_space - begin_from_space) - (end_to_space - begin_to_space) - live_large.0;
+    note_reclaimed(Bytes(reclaimed));
+
+    let new_hp = if relocate {
+        let (new_hp, entries) = large_objects::place_and_relocate(
+            begin_from_space,
+            begin_to_space,
+            end_to_space,
+            large_entries,
+            n_large,
+            remembered,
+        );
+        large_entries = entries;
+        new_hp
+    } else {
+        // Copy to-space to the beginning of from-space
+        memcpy_bytes(
+            begin_from_space,
+            begin_to_space,
+            Bytes(end_to_space - begin_to_space),
+        );
+
+        begin_from_space + (end_to_space - begin_to_space)
+    };
 
     // Reset the heap pointer
-    let new_hp = begin_from_space + (end_to_space - begin_to_space);
-    HP = new_hp as u32;
+    HP = new_hp;
+
+    note_collection(Bytes(new_live_size));
+
+    large_objects::restore(large_entries, n_large);
 }
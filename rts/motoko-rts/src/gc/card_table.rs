@@ -0,0 +1,94 @@
+//! Card tables of large arrays, for the generational GC.
+//!
+//! The write barrier adds every location written in the old generation to the remembered set, so
+//! a loop writing to a large array in the old generation makes the remembered set as large as the
+//! number of writes, and a minor collection scans all of them. Instead, the generated code calls
+//! `post_write_barrier` after storing an element of an array. For arrays of at least `MIN_LEN`
+//! elements this marks the card of the element (`CARD_SIZE` elements) dirty in the card table of
+//! the array, and a minor collection scans only the elements in dirty cards.
+//!
+//! The card table of an array is a bitmap in a blob, allocated by the first write to the array
+//! after a collection. The tables are in the young generation, so they are reclaimed by the next
+//! collection, after which all cards are clean.
+//!
+//! `TABLES` holds (array, card table) pairs. Few large arrays are written between collections, so
+//! it's searched linearly, starting with the last array written.
+
+use super::alloc_gc_blob;
+use super::word_stack::WordStack;
+use crate::types::*;
+
+/// Elements per card
+const CARD_SIZE: usize = 128;
+
+/// Arrays shorter than this don't have a card table, the barrier adds the location written to
+/// the remembered set
+pub(crate) const MIN_LEN: usize = 8 * CARD_SIZE;
+
+/// (array, card table) pairs of the arrays written since the last collection
+static mut TABLES: WordStack = WordStack::new();
+
+/// Index of the last array written in `TABLES`
+static mut LAST: usize = 0;
+
+fn n_cards(array_len: usize) -> usize {
+    (array_len + CARD_SIZE - 1) / CARD_SIZE
+}
+
+/// Marks the card of element `idx` of the array dirty
+pub(crate) unsafe fn mark_card(array: *mut Array, idx: usize) {
+    let card = idx / CARD_SIZE;
+    let table = card_table(array);
+    *table.payload_addr().add(card / 8) |= 1 << (card % 8);
+}
+
+unsafe fn card_table(array: *mut Array) -> *mut Blob {
+    if LAST < TABLES.len() && TABLES.get(LAST) == array as usize {
+        return TABLES.get(LAST + 1) as *mut Blob;
+    }
+
+    for i in (0..TABLES.len()).step_by(2) {
+        if TABLES.get(i) == array as usize {
+            LAST = i;
+            return TABLES.get(i + 1) as *mut Blob;
+        }
+    }
+
+    // First write to the array since the last collection
+    let size = Bytes((n_cards(array.len()) + 7) / 8);
+    let table = alloc_gc_blob(size);
+    libc::memset(table.payload_addr() as *mut _, 0, size.0);
+
+    LAST = TABLES.len();
+    TABLES.push(array as usize);
+    TABLES.push(table as usize);
+
+    table
+}
+
+/// Adds the locations of the elements in dirty cards to `remembered`. Called by a minor collection
+/// before collecting.
+pub(crate) unsafe fn remember_dirty_cards(remembered: &mut WordStack) {
+    for i in (0..TABLES.len()).step_by(2) {
+        let array = TABLES.get(i) as *mut Array;
+        let table = TABLES.get(i + 1) as *mut Blob;
+        let len = array.len();
+
+        for card in 0..n_cards(len) {
+            if table.get(card / 8) & (1 << (card % 8)) == 0 {
+                continue;
+            }
+
+            let end = core::cmp::min((card + 1) * CARD_SIZE, len);
+            for idx in card * CARD_SIZE..end {
+                remembered.push(array.payload_addr().add(idx) as usize);
+            }
+        }
+    }
+}
+
+/// Forgets the card tables. Called after each collection, the tables were reclaimed.
+pub(crate) unsafe fn reset() {
+    TABLES = WordStack::new();
+    LAST = 0;
+}
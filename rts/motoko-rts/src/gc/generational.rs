@@ -0,0 +1,132 @@
+//! Generational GC.
+//!
+//! The dynamic heap is split into the old generation, from the heap base to `YOUNG_BEGIN`, and the
+//! young generation (the nursery), from `YOUNG_BEGIN` to the heap pointer. A minor collection only
+//! copies the live objects in the young generation, using the copying GC with `YOUNG_BEGIN` as the
+//! beginning of from-space. Survivors are promoted to the old generation immediately.
+//!
+//! Old objects can only point to young objects after a mutation, as old objects are created by
+//! promotion and all young objects are promoted together. The write barrier records locations in
+//! the old generation that are written to in the remembered set, which are used as roots in a
+//! minor collection. Writes to large arrays mark cards instead, see `card_table.rs`.
+//!
+//! When the old generation has grown by the budget of the schedule (see `schedule.rs`), or when
+//! requested with `gc_force`, we do a major collection, which is the copying GC on the whole heap.
+
+use super::card_table;
+use super::schedule::Schedule;
+use super::word_stack::WordStack;
+use super::{after_collection, copy_live_objects, deferred, get_heap_base, take_forced, HP};
+use crate::alloc::free_list;
+use crate::scratch;
+use crate::to_space_arena;
+use crate::types::{Bytes, SkewedPtr};
+
+/// Minimum growth of the old generation since the last major collection for the next collection
+/// to be a major one, see `schedule.rs`
+const MIN_OLD_GROWTH: Bytes<usize> = Bytes(32 << 20);
+
+/// Beginning of the young generation. 0 until the first generational GC and after collections of
+/// the other collectors (see `reset`), which means the barrier does not record anything when the
+/// generational GC is not in use.
+static mut YOUNG_BEGIN: usize = 0;
+
+/// Size of the old generation after the last major collection
+static mut OLD_SIZE_AFTER_MAJOR: Bytes<usize> = Bytes(0);
+
+/// Decides when to do a major collection
+static mut SCHEDULE: Schedule = Schedule::new(MIN_OLD_GROWTH);
+
+/// Locations in the old generation written since the last collection
+static mut REMEMBERED_SET: WordStack = WordStack::new();
+
+/// The entry point. Called by the generated code.
+#[no_mangle]
+pub unsafe extern "C" fn generational_gc() {
+    scratch::release();
+    to_space_arena::release();
+
+    if deferred() {
+        return;
+    }
+    free_list::reset();
+
+    #[cfg(feature = "redzones")]
+    crate::alloc::redzones::check_heap();
+
+    let heap_base = get_heap_base();
+
+    #[cfg(feature = "heap_sanity")]
+    let old_end = HP;
+
+    if YOUNG_BEGIN == 0 {
+        YOUNG_BEGIN = heap_base;
+    }
+
+    let old_size = Bytes(YOUNG_BEGIN - heap_base);
+    let old_growth = old_size - OLD_SIZE_AFTER_MAJOR;
+
+    if take_forced() || SCHEDULE.due(old_growth, 1) {
+        // Major collection. The whole heap is collected so the remembered set is not needed.
+        let heap_size = Bytes(HP - heap_base);
+        copy_live_objects(heap_base, &WordStack::new());
+        let live = Bytes(HP - heap_base);
+        // The heap can grow when objects don't fit in the gaps before pinned objects
+        let reclaimed = Bytes(heap_size.0.saturating_sub(live.0));
+        SCHEDULE.note_collection(heap_size - OLD_SIZE_AFTER_MAJOR, reclaimed, live);
+        OLD_SIZE_AFTER_MAJOR = live;
+    } else {
+        card_table::remember_dirty_cards(&mut REMEMBERED_SET);
+        REMEMBERED_SET.sort_dedup();
+        copy_live_objects(YOUNG_BEGIN, &REMEMBERED_SET);
+    }
+
+    // Promote survivors. The remembered set blob and the card tables were in the young generation
+    // so they're gone now.
+    YOUNG_BEGIN = HP;
+    REMEMBERED_SET = WordStack::new();
+    card_table::reset();
+
+    // This may write to the old generation, so needs to be done after resetting the remembered set
+    after_collection();
+
+    #[cfg(feature = "heap_sanity")]
+    super::heap_sanity::check_heap(old_end);
+}
+
+/// Forgets the generations, the remembered set and the card tables. Called by the other collectors
+/// before collecting, as they don't keep these alive.
+pub(crate) unsafe fn reset() {
+    YOUNG_BEGIN = 0;
+    OLD_SIZE_AFTER_MAJOR = Bytes(0);
+    REMEMBERED_SET = WordStack::new();
+    card_table::reset();
+}
+
+/// Called by the generated code and the RTS before storing a new value at `location`
+#[no_mangle]
+pub unsafe extern "C" fn generational_write_barrier(location: *mut SkewedPtr) {
+    let loc = location as usize;
+    // Static objects are always scanned, young objects are always collected
+    if loc < YOUNG_BEGIN && loc >= get_heap_base() {
+        REMEMBERED_SET.push(loc);
+    }
+}
+
+/// Called by the generated code after storing element `idx` of `array`. Marks the card of the
+/// element in large arrays, see `card_table.rs`.
+#[no_mangle]
+pub unsafe extern "C" fn post_write_barrier(array: SkewedPtr, idx: usize) {
+    let array = array.as_array();
+    let addr = array as usize;
+    // Static objects are always scanned, young objects are always collected
+    if addr >= YOUNG_BEGIN || addr < get_heap_base() {
+        return;
+    }
+
+    if array.len() < card_table::MIN_LEN {
+        REMEMBERED_SET.push(array.payload_addr().add(idx) as usize);
+    } else {
+        card_table::mark_card(array, idx);
+    }
+}
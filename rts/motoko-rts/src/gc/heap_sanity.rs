@@ -0,0 +1,132 @@
+//! Heap sanity checks, enabled with the `heap_sanity` feature. For debugging the collectors.
+//!
+//! `check_heap` is called by the collectors after each collection. It walks the whole dynamic heap
+//! and traps when
+//!
+//! - an object has an invalid tag, or a tag that should not survive a collection (forwarding
+//!   pointers, `NULL`, which is only used in static memory),
+//! - object sizes don't add up to the heap pointer,
+//! - a pointer (in the heap or in the roots) is not skewed or not word aligned,
+//! - a pointer into the dynamic heap does not point to an object header,
+//! - a pointer points to the part of the heap that was collected and is now free (from-space).
+//!
+//! Pointers to static objects are not checked.
+//!
+//! Object headers are recorded in a mark bitmap allocated after the heap, which is discarded after
+//! checking.
+
+use super::mark_bitmap::MarkBitmap;
+use super::{get_heap_base, visit_roots, HP};
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::*;
+use crate::visitor::visit_all_pointer_fields;
+
+/// Checks the heap after a collection. `old_end` is the end of the heap before the collection,
+/// pointers to `[HP, old_end)` are pointers to from-space.
+pub(crate) unsafe fn check_heap(old_end: usize) {
+    let heap_base = get_heap_base();
+    let heap_end = HP;
+
+    let mut headers = MarkBitmap::new(heap_base, heap_end);
+
+    let mut p = heap_base;
+    while p < heap_end {
+        if p % WORD_SIZE as usize != 0 {
+            rts_trap_with(ErrorCode::Internal, "heap_sanity: unaligned object");
+        }
+
+        let obj = p as *mut Obj;
+        match obj.tag() {
+            TAG_OBJECT | TAG_OBJ_IND | TAG_ARRAY | TAG_BITS64 | TAG_MUTBOX | TAG_CLOSURE
+            | TAG_SOME | TAG_VARIANT | TAG_BLOB | TAG_BITS32 | TAG_BIGINT | TAG_CONCAT
+            | TAG_WEAK_REF | TAG_SLICE | TAG_SMALL_TEXT => headers.mark_range(p, Words(1)),
+            0 => {
+                // Slop after a shrunk blob, see `object_size`
+            }
+            TAG_REDZONE => {
+                // Around objects allocated during a cycle of the incremental GC, see
+                // `alloc/redzones.rs`
+            }
+            TAG_FREE_CHUNK => {
+                // Memory freed by the mark-sweep GC or in place, see `alloc/free_list.rs`
+            }
+            TAG_FWD_PTR => rts_trap_with(
+                ErrorCode::Internal,
+                "heap_sanity: forwarding pointer after GC",
+            ),
+            TAG_NULL => rts_trap_with(
+                ErrorCode::Internal,
+                "heap_sanity: NULL object in dynamic heap",
+            ),
+            _ => rts_trap_with(ErrorCode::Internal, "heap_sanity: invalid object tag"),
+        }
+
+        p += object_size(p).to_bytes().0 as usize;
+    }
+
+    if p != heap_end {
+        rts_trap_with(
+            ErrorCode::Internal,
+            "heap_sanity: last object extends past the heap pointer",
+        );
+    }
+
+    let check_ptr = |ptr: SkewedPtr| check_pointer(&headers, heap_base, heap_end, old_end, ptr);
+
+    // Roots
+    visit_roots(|loc| check_ptr(*loc));
+
+    // Fields of all objects in the heap. Objects that are not reachable are checked too, dangling
+    // pointers in these are harmless but still a sign of a bug.
+    let mut p = heap_base;
+    while p < heap_end {
+        let obj = p as *mut Obj;
+        visit_all_pointer_fields(obj, |field| check_ptr(*field));
+        p += object_size(p).to_bytes().0 as usize;
+    }
+
+    // Discard the bitmap
+    HP = heap_end;
+}
+
+unsafe fn check_pointer(
+    headers: &MarkBitmap,
+    heap_base: usize,
+    heap_end: usize,
+    old_end: usize,
+    ptr: SkewedPtr,
+) {
+    if ptr.is_tagged_scalar() {
+        return;
+    }
+
+    let addr = ptr.unskew();
+
+    if addr % WORD_SIZE as usize != 0 {
+        rts_trap_with(ErrorCode::Internal, "heap_sanity: unaligned pointer");
+    }
+
+    if addr < heap_base {
+        // Static object
+        return;
+    }
+
+    if addr >= heap_end {
+        if addr < old_end {
+            rts_trap_with(ErrorCode::Internal, "heap_sanity: pointer to from-space");
+        } else {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "heap_sanity: pointer past the end of the heap",
+            );
+        }
+    }
+
+    if !headers.is_marked(addr) {
+        rts_trap_with(
+            ErrorCode::Internal,
+            "heap_sanity: pointer does not point to an object header",
+        );
+    }
+}
@@ -0,0 +1,888 @@
+//! Incremental mark-compact GC.
+//!
+//! The collector does a bounded amount of work (about `INCREMENT` words) at the end of each
+//! message, so the GC cost of a message is capped no matter how large the heap is. A GC cycle has
+//! two phases, marking and then sweeping or compacting the old objects.
+//!
+//! Mark: we use snapshot-at-the-beginning tri-color marking. When a cycle starts we shade the
+//! objects referenced by the roots (see `visit_roots`). Grey objects are on the mark stack, black
+//! objects are marked and not on the stack. The write barrier shades the value being overwritten,
+//! so everything that was reachable when the cycle started gets marked. Objects allocated during
+//! the cycle are above the marked area and are implicitly black, or are marked when they're
+//! allocated in the free memory of the old objects, see `mark_reused`.
+//!
+//! The closure table is shaded last, when the objects reachable from the other roots are marked,
+//! so that the objects marked after it are the ones only retained by the table (see
+//! `shade_closure_table`). Until then `recall_closure` shades the closures it takes out of the
+//! table with the write barrier, when it frees their slots.
+//!
+//! Weak references found while marking are recorded, and the ones to unmarked objects are cleared
+//! when marking is done. `weak_ref_get` shades the referent during marking, see
+//! `weak_ref_read_barrier`.
+//!
+//! When marking is done the old objects are compacted or swept. Compacting moves objects, and the
+//! mutator only knows the objects at their old locations (there is no read barrier), so all
+//! pointers to the moved objects have to be updated before the mutator runs again. The heap is
+//! only compacted when that fits in the increment, when the heap is not larger than the rest of
+//! the budget, and in collections forced with `gc_force`. Otherwise the old objects are swept.
+//!
+//! Sweep: nothing is moved, so the mutator runs between the increments. The runs of unmarked old
+//! objects are added to the free lists (see `alloc/free_list.rs`), in address order. The free
+//! chunks in a run are taken out of the lists and merged with it. An increment ends before an
+//! object that is not free memory, as `free_object` merges the memory it frees with the free
+//! memory after it, which must not reach into the old objects that are not swept yet.
+//!
+//! Compact: marked objects are copied, in address order, to a to-space blob at the end of the
+//! heap. Their final location (after copying to-space back to the beginning of the heap) is found
+//! with the mark bitmap, see `MarkBitmap::translate`, so no forwarding pointers are needed. Then
+//! we "flip": objects allocated during the cycle and the roots are updated, to-space is copied
+//! back to the beginning of the heap, and the objects allocated during the cycle are moved after
+//! it.
+//!
+//! Partial compaction: compacting the whole heap copies all live data, which is too expensive for
+//! large heaps that are mostly live. With a compaction threshold (see `set_compaction_threshold`)
+//! the old objects are divided into regions of `REGION_SIZE`, and the fragmentation of each region
+//! is computed from the mark bitmap when marking is done. Only the regions with more fragmentation
+//! than the threshold are evacuated, the others are left in place. A run of evacuated regions, a
+//! span, is compacted to its beginning, with `MarkBitmap::translate` relative to the beginning of
+//! the span. The dead objects in the regions left in place, and the space freed at the end of each
+//! span, are filled with blobs, so the heap stays walkable. Fields of objects left in place are
+//! updated in place when the objects are visited.
+//!
+//! Region boundaries are aligned to objects: an object that crosses the nominal beginning of a
+//! region belongs to the region before, this is recorded when marking.
+//!
+//! A collection requested with `gc_force` is done in one go, without bounding the work.
+//!
+//! Pinned objects (see `pin.rs`) are left in place. Sweeping doesn't move objects. When a cycle
+//! starts with pinned objects the region table is allocated even without a compaction threshold,
+//! and the regions with pinned objects are left in place like the regions with little
+//! fragmentation. When objects allocated during the cycle are pinned, the objects allocated during
+//! the cycle are not moved, and the space freed at the end of the old objects is filled. When an
+//! old object that would be moved was pinned during the cycle, the cycle sweeps, and the next
+//! cycle, which starts with the pin, can compact the heap.
+//!
+//! Heap layout during a cycle that compacts:
+//!
+//! ```text
+//! HEAP_BEGIN      OLD_END                 NEW_BEGIN     EVAC_BEGIN      HP
+//! | old objects   | mark bitmap blob      | new objects | to-space blob |
+//!                   (and region table blob)
+//! ```
+//!
+//! With partial compaction to-space only holds the evacuated objects.
+
+use super::generational;
+use super::large_objects;
+use super::mark_bitmap::MarkBitmap;
+use super::schedule::Schedule;
+use super::weak_refs;
+use super::word_stack::WordStack;
+use super::{
+    after_collection, alloc_gc_blob, deferred, get_heap_base, note_closure_table_retained,
+    note_collection, note_live_size, note_reclaimed, take_forced, visit_roots,
+    visit_roots_but_closure_table, HP,
+};
+use crate::alloc::free_list::{self, add_free_chunk};
+use crate::closure_table::closure_table_loc;
+use crate::error_code::ErrorCode;
+use crate::mem::memcpy_bytes;
+use crate::pin::{for_each_pinned, pinned_count};
+use crate::rts_trap_with;
+use crate::scratch;
+use crate::to_space_arena;
+use crate::types::*;
+use crate::visitor::{visit_all_pointer_fields, visit_pointer_fields};
+
+/// Amount of work (in words marked, swept, or compacted) done in one increment
+const INCREMENT: Words<usize> = Words(1 << 20);
+
+/// Minimum heap growth since the last cycle for a new cycle to start, see `schedule.rs`
+const MIN_HEAP_GROWTH: Bytes<usize> = Bytes(1 << 20);
+
+/// Size of the regions in partial compaction
+const REGION_SIZE: usize = 1 << 20;
+
+/// Regions with at most this fragmentation (in percent) are left in place. 0 compacts the whole
+/// heap.
+static mut COMPACTION_THRESHOLD: u32 = 0;
+
+/// Enables partial compaction: regions of the heap where at most `threshold` percent of the memory
+/// is garbage are not compacted. 0, the default, compacts the whole heap. Takes effect in the next
+/// cycle.
+#[no_mangle]
+unsafe extern "C" fn set_compaction_threshold(threshold: u32) {
+    if threshold >= 100 {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "set_compaction_threshold: threshold must be below 100",
+        );
+    }
+
+    COMPACTION_THRESHOLD = threshold;
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Phase {
+    Idle,
+    Mark,
+    Sweep,
+}
+
+static mut PHASE: Phase = Phase::Idle;
+
+/// Beginning of the dynamic heap
+static mut HEAP_BEGIN: usize = 0;
+
+/// End of the objects that are collected in the current cycle
+static mut OLD_END: usize = 0;
+
+/// Beginning of the objects allocated during the cycle
+static mut NEW_BEGIN: usize = 0;
+
+/// Beginning of the to-space blob (the header) = end of objects allocated during the cycle
+static mut EVAC_BEGIN: usize = 0;
+
+/// Beginning of the to-space blob payload
+static mut TO_SPACE: usize = 0;
+
+/// The old objects below this address have been swept
+static mut SWEEP_PTR: usize = 0;
+
+/// Marked old objects swept in the current cycle
+static mut SWEPT_LIVE: Bytes<usize> = Bytes(0);
+
+/// Unmarked old objects swept in the current cycle, not counting the free memory among them
+static mut SWEPT_RECLAIMED: Bytes<usize> = Bytes(0);
+
+/// Live data in the old objects
+static mut LIVE_OLD: Bytes<usize> = Bytes(0);
+
+/// Size of the to-space blob payload
+static mut TO_SPACE_SIZE: Bytes<usize> = Bytes(0);
+
+/// End of the old objects after the flip, the objects allocated during the cycle are moved here
+static mut FINAL_OLD_END: usize = 0;
+
+/// Region table for partial compaction, null when the whole heap is compacted in the current cycle
+static mut REGIONS: *mut Region = core::ptr::null_mut();
+
+static mut N_REGIONS: usize = 0;
+
+/// Whether the last object visited when compacting is left in place. True before the first one.
+static mut PREV_KEPT: bool = true;
+
+/// The regions before this one have been visited when compacting
+static mut NEXT_REGION: usize = 0;
+
+/// Whether the objects allocated during the current cycle are left in place in the flip, as some
+/// of them were pinned when marking was done
+static mut KEEP_NEW: bool = false;
+
+/// Whether the closure table was shaded in the current cycle
+static mut CLOSURE_TABLE_SHADED: bool = false;
+
+/// Size of the objects marked after the closure table was shaded, not counting the table
+static mut CLOSURE_TABLE_RETAINED: Bytes<usize> = Bytes(0);
+
+/// Live data retained in the last cycle, used for scheduling
+static mut LAST_LIVE: Bytes<usize> = Bytes(0);
+
+/// Growth of the heap since the last cycle when the current cycle started, used for scheduling
+static mut GROWTH: Bytes<usize> = Bytes(0);
+
+/// Work done by the current (or last) call of `incremental_gc`, see `INCREMENT`
+static mut WORK: Words<usize> = Words(0);
+
+/// Decides when to start a cycle
+static mut SCHEDULE: Schedule = Schedule::new(MIN_HEAP_GROWTH);
+
+static mut BITMAP: MarkBitmap = MarkBitmap::empty();
+
+static mut MARK_STACK: WordStack = WordStack::new();
+
+/// Marked weak references in the old objects
+static mut WEAK_REFS: WordStack = WordStack::new();
+
+/// A region of the old objects in partial compaction
+#[repr(C)]
+struct Region {
+    /// Whether the region has pinned objects when marking is done, then it's left in place
+    pinned: bool,
+
+    /// Beginning of the region. While marking this is the end of the marked object that crosses
+    /// the nominal beginning of the region, if there's one, otherwise 0.
+    begin: usize,
+
+    /// 0 if the region is left in place, otherwise the index of the first region of its span + 1
+    span: usize,
+
+    /// In the first region of a span: offset of the span in to-space
+    to_space_offset: usize,
+
+    /// In the first region of a span: end of the last object left in place before the span
+    gap_begin: usize,
+
+    /// In the first region of a span: the first object left in place after the span
+    gap_end: usize,
+}
+
+/// The entry point. Called by the generated code at the end of each message.
+#[no_mangle]
+pub unsafe extern "C" fn incremental_gc() {
+    scratch::release();
+    to_space_arena::release();
+
+    if deferred() {
+        return;
+    }
+    generational::reset();
+    WORK = Words(0);
+
+    // Forced collection: finish the current cycle, and collect the garbage created since it was
+    // started with a new one
+    if take_forced() {
+        if PHASE != Phase::Idle {
+            finish_cycle();
+        }
+        start_cycle();
+        finish_cycle();
+        return;
+    }
+
+    if PHASE == Phase::Idle {
+        if !SCHEDULE.due(growth(), cycle_length()) {
+            return;
+        }
+        start_cycle();
+    }
+
+    increment(INCREMENT);
+}
+
+/// Growth of the heap since the last cycle, not counting the free memory
+unsafe fn growth() -> Bytes<usize> {
+    let used = LAST_LIVE + free_list::free_size();
+    Bytes((HP - get_heap_base()).saturating_sub(used.0))
+}
+
+/// Expected number of increments in a cycle: the live data is marked, and the heap is swept
+unsafe fn cycle_length() -> usize {
+    (LAST_LIVE.to_words().0 + Bytes(HP - get_heap_base()).to_words().0) / INCREMENT.0 + 1
+}
+
+/// Does the cycle in one go
+unsafe fn finish_cycle() {
+    increment(Words(usize::MAX));
+}
+
+/// Returns the work done by the last call of `incremental_gc`, in words marked, swept, or
+/// compacted. Without `gc_force` it's at most `INCREMENT` plus the size of the last object visited.
+/// Used by the tests.
+#[no_mangle]
+unsafe extern "C" fn get_incremental_gc_work() -> Words<usize> {
+    WORK
+}
+
+/// Called by the generated code and the RTS before storing a new value at `location`
+#[no_mangle]
+pub unsafe extern "C" fn write_barrier(location: *mut SkewedPtr) {
+    match PHASE {
+        Phase::Idle | Phase::Sweep => {}
+        Phase::Mark => shade(*location),
+    }
+}
+
+/// Called by `alloc_words` when it allocates `size` words at `addr` from the free lists. During a
+/// cycle the objects allocated in the old objects are marked, the ones allocated after them are
+/// implicitly marked.
+pub(crate) unsafe fn mark_reused(addr: usize, size: Words<usize>) {
+    if PHASE != Phase::Idle && addr < OLD_END {
+        BITMAP.mark_range(addr, size);
+    }
+}
+
+/// Called by `weak_ref_get`. A referent that is not marked yet could be stored in an object that
+/// is already scanned, so it needs to be shaded.
+pub(crate) unsafe fn weak_ref_read_barrier(referent: SkewedPtr) {
+    if PHASE == Phase::Mark {
+        shade(referent);
+    }
+}
+
+unsafe fn start_cycle() {
+    // The scratch chunk may be in a gap that is filled during the cycle
+    scratch::forget();
+
+    #[cfg(feature = "redzones")]
+    crate::alloc::redzones::check_heap();
+
+    HEAP_BEGIN = get_heap_base();
+    OLD_END = HP;
+    GROWTH = growth();
+
+    MARK_STACK = WordStack::new();
+    WEAK_REFS = WordStack::new();
+    BITMAP = MarkBitmap::new(HEAP_BEGIN, OLD_END);
+
+    N_REGIONS = (OLD_END - HEAP_BEGIN + REGION_SIZE - 1) / REGION_SIZE;
+    if (COMPACTION_THRESHOLD != 0 || pinned_count() != 0) && N_REGIONS != 0 {
+        let size = Bytes(N_REGIONS * core::mem::size_of::<Region>());
+        REGIONS = alloc_gc_blob(size).payload_addr() as *mut Region;
+        libc::memset(REGIONS as *mut _, 0, size.0);
+    }
+
+    NEW_BEGIN = HP;
+
+    visit_roots_but_closure_table(|loc| shade(*loc));
+    CLOSURE_TABLE_SHADED = false;
+    CLOSURE_TABLE_RETAINED = Bytes(0);
+
+    PHASE = Phase::Mark;
+}
+
+/// Does `limit` words of work, or the rest of the cycle
+unsafe fn increment(limit: Words<usize>) {
+    if PHASE == Phase::Mark {
+        if !mark(limit) {
+            return;
+        }
+
+        note_closure_table_retained(CLOSURE_TABLE_RETAINED);
+        weak_refs::clear_unmarked(&WEAK_REFS, &BITMAP, HEAP_BEGIN, OLD_END);
+
+        // The flip touches each word of the heap a few times
+        let heap_size = Bytes(HP - HEAP_BEGIN).to_words();
+        if heap_size.0 <= limit.0.saturating_sub(WORK.0) && plan_compaction() {
+            WORK += heap_size;
+            compact();
+            return;
+        }
+
+        start_sweep();
+    }
+
+    sweep(limit);
+}
+
+/// Marks until the objects reachable from the roots and the closure table are marked, or until
+/// `limit`. Returns whether marking is done.
+unsafe fn mark(limit: Words<usize>) -> bool {
+    loop {
+        while let Option::Some(obj) = MARK_STACK.pop() {
+            let obj = obj as *mut Obj;
+            if obj.tag() == TAG_WEAK_REF {
+                WEAK_REFS.push(obj as usize);
+            }
+            visit_pointer_fields(obj, |field| shade(*field));
+            let size = object_size(obj as usize);
+            if CLOSURE_TABLE_SHADED {
+                CLOSURE_TABLE_RETAINED += size.to_bytes();
+            }
+            WORK += size;
+            if WORK.0 >= limit.0 {
+                return false;
+            }
+        }
+
+        if CLOSURE_TABLE_SHADED {
+            return true;
+        }
+        shade_closure_table();
+    }
+}
+
+/// Whether an object that would be moved when compacting is pinned
+unsafe fn moves_pinned() -> bool {
+    let mut moves = false;
+    for_each_pinned(|obj| {
+        moves |= if obj < OLD_END {
+            obj >= HEAP_BEGIN && is_evacuated(obj)
+        } else {
+            !KEEP_NEW
+        };
+    });
+    moves
+}
+
+/// Shades the closure table and the closures in it, when the mark stack is empty. The table
+/// itself is not counted as retained, it's taken off the mark stack and scanned here. A table
+/// allocated during the cycle is not shaded, but it's scanned the same way.
+unsafe fn shade_closure_table() {
+    CLOSURE_TABLE_SHADED = true;
+
+    let table = *closure_table_loc();
+    if table.is_tagged_scalar() {
+        return;
+    }
+
+    shade(table);
+    MARK_STACK.pop();
+    visit_pointer_fields(table.as_obj(), |field| shade(*field));
+}
+
+/// Shade an object: mark it and push it to the mark stack, unless it's already marked or not
+/// collected in the current cycle
+unsafe fn shade(ptr: SkewedPtr) {
+    if ptr.is_tagged_scalar() {
+        return;
+    }
+
+    let addr = ptr.unskew();
+
+    // Static objects are not collected, new objects are implicitly marked
+    if addr < HEAP_BEGIN || addr >= OLD_END {
+        return;
+    }
+
+    if BITMAP.is_marked(addr) {
+        return;
+    }
+
+    let size = object_size(addr);
+    BITMAP.mark_range(addr, size);
+    MARK_STACK.push(addr);
+
+    if !REGIONS.is_null() {
+        note_crossing(addr, size);
+    }
+}
+
+unsafe fn region_index(addr: usize) -> usize {
+    (addr - HEAP_BEGIN) / REGION_SIZE
+}
+
+/// Records the end of a marked object in the regions whose nominal beginning it crosses
+unsafe fn note_crossing(addr: usize, size: Words<usize>) {
+    let end = addr + size.to_bytes().0;
+    for i in region_index(addr) + 1..=region_index(end - 1) {
+        (*REGIONS.add(i)).begin = end;
+    }
+}
+
+/// Region of the object at or containing `addr`
+unsafe fn region_of(addr: usize) -> *mut Region {
+    let mut i = region_index(addr);
+    while addr < (*REGIONS.add(i)).begin {
+        i -= 1;
+    }
+    REGIONS.add(i)
+}
+
+/// First region of the span of an evacuated region
+unsafe fn span_of(region: *mut Region) -> *mut Region {
+    REGIONS.add((*region).span - 1)
+}
+
+/// End of the region with the given index
+unsafe fn region_end(i: usize) -> usize {
+    if i + 1 == N_REGIONS {
+        OLD_END
+    } else {
+        (*REGIONS.add(i + 1)).begin
+    }
+}
+
+/// Marked bytes in the old objects below `addr`
+unsafe fn live_before(addr: usize) -> usize {
+    if addr >= OLD_END {
+        LIVE_OLD.0
+    } else {
+        BITMAP.translate(addr) - HEAP_BEGIN
+    }
+}
+
+/// Decides where the marked old objects are moved when compacting. Returns `false` when a pinned
+/// object would be moved, the cycle sweeps then.
+unsafe fn plan_compaction() -> bool {
+    LIVE_OLD = BITMAP.compute_live_before();
+
+    // Pinned objects in the heap above the old objects were allocated during the cycle
+    for_each_pinned(|obj| KEEP_NEW |= obj >= OLD_END);
+
+    if REGIONS.is_null() {
+        TO_SPACE_SIZE = LIVE_OLD;
+        FINAL_OLD_END = HEAP_BEGIN + LIVE_OLD.0;
+    } else {
+        plan_regions();
+    }
+
+    !moves_pinned()
+}
+
+/// Copies the marked old objects to to-space, or visits them when they're left in place, and
+/// flips
+unsafe fn compact() {
+    // Objects are moved over the free chunks, the chunks in the new objects become blobs
+    free_list::reset();
+
+    EVAC_BEGIN = HP;
+    let to_space = alloc_gc_blob(TO_SPACE_SIZE);
+    TO_SPACE = to_space.payload_addr() as usize;
+
+    PREV_KEPT = true;
+    NEXT_REGION = 0;
+
+    let mut p = HEAP_BEGIN;
+    while let Option::Some(obj) = BITMAP.next_marked(p, OLD_END) {
+        let size = if REGIONS.is_null() {
+            evacuate(obj)
+        } else {
+            let region = region_index(obj);
+            let kept = (*REGIONS.add(region)).span == 0;
+            note_gap(p, obj, region, kept);
+            if kept {
+                keep(obj)
+            } else {
+                evacuate(obj)
+            }
+        };
+        p = obj + size.to_bytes().0;
+    }
+
+    if !REGIONS.is_null() {
+        // The space after the last marked object
+        let last = N_REGIONS - 1;
+        note_gap(p, OLD_END, last, (*REGIONS.add(last)).span == 0);
+    }
+
+    flip();
+}
+
+unsafe fn start_sweep() {
+    SWEEP_PTR = HEAP_BEGIN;
+    SWEPT_LIVE = Bytes(0);
+    SWEPT_RECLAIMED = Bytes(0);
+
+    PHASE = Phase::Sweep;
+}
+
+/// Adds the runs of unmarked old objects, and the free memory among them, to the free lists, until
+/// `limit`. Stops before an object that is not free memory, and adds the run before it, so that
+/// the free lists have all free memory below `SWEEP_PTR` between increments.
+unsafe fn sweep(limit: Words<usize>) {
+    // Beginning of the current run of free memory
+    let mut free_begin = SWEEP_PTR;
+
+    let mut p = SWEEP_PTR;
+    while p < OLD_END {
+        let tag = (p as *mut Obj).tag();
+        let free = tag == TAG_FREE_CHUNK || tag == 0;
+        if !free && WORK.0 >= limit.0 {
+            break;
+        }
+
+        let size = object_size(p);
+        if tag == TAG_FREE_CHUNK {
+            free_list::take_chunk(p);
+        } else if free {
+            // Slop after a shrunk blob, see `object_size`
+        } else if BITMAP.is_marked(p) {
+            if free_begin < p {
+                add_free_chunk(free_begin, Bytes(p - free_begin).to_words());
+            }
+            SWEPT_LIVE += size.to_bytes();
+            free_begin = p + size.to_bytes().0;
+        } else {
+            SWEPT_RECLAIMED += size.to_bytes();
+        }
+
+        p += size.to_bytes().0;
+        WORK += size;
+    }
+
+    if free_begin < p {
+        add_free_chunk(free_begin, Bytes(p - free_begin).to_words());
+    }
+    SWEEP_PTR = p;
+
+    if p == OLD_END {
+        end_sweep();
+    }
+}
+
+/// Ends the cycle when the old objects are swept
+unsafe fn end_sweep() {
+    let heap_size = Bytes(HP - HEAP_BEGIN);
+    let live = SWEPT_LIVE + Bytes(HP - OLD_END);
+    note_live_size(live);
+    note_reclaimed(SWEPT_RECLAIMED);
+    let used = heap_size - free_list::free_size();
+    SCHEDULE.note_collection(GROWTH, SWEPT_RECLAIMED, used);
+    LAST_LIVE = used;
+
+    note_collection(live);
+
+    #[cfg(feature = "heap_sanity")]
+    let hp = HP;
+    end_cycle();
+
+    #[cfg(feature = "heap_sanity")]
+    super::heap_sanity::check_heap(hp);
+}
+
+/// Decides which regions are evacuated, and lays out the spans in to-space
+unsafe fn plan_regions() {
+    for i in 0..N_REGIONS {
+        let region = REGIONS.add(i);
+        (*region).begin = ::core::cmp::max((*region).begin, HEAP_BEGIN + i * REGION_SIZE);
+    }
+
+    for_each_pinned(|obj| {
+        if obj >= HEAP_BEGIN && obj < OLD_END {
+            (*region_of(obj)).pinned = true;
+        }
+    });
+
+    let mut to_space_size = 0;
+    let mut span = 0;
+
+    for i in 0..N_REGIONS {
+        let region = REGIONS.add(i);
+        let begin = (*region).begin;
+        let size = (region_end(i) - begin) as u64;
+        let live = (live_before(region_end(i)) - live_before(begin)) as u64;
+
+        // Regions can be empty when an object covers them
+        if (*region).pinned
+            || (COMPACTION_THRESHOLD != 0
+                && size != 0
+                && (size - live) * 100 <= COMPACTION_THRESHOLD as u64 * size)
+        {
+            (*region).span = 0;
+            continue;
+        }
+
+        if i == 0 || (*REGIONS.add(i - 1)).span == 0 {
+            span = i + 1;
+            (*region).to_space_offset = to_space_size;
+            (*region).gap_begin = begin;
+            (*region).gap_end = begin;
+        }
+
+        (*region).span = span;
+        to_space_size += live as usize;
+    }
+
+    TO_SPACE_SIZE = Bytes(to_space_size);
+
+    let last = REGIONS.add(N_REGIONS - 1);
+    FINAL_OLD_END = if (*last).span == 0 {
+        OLD_END
+    } else {
+        let begin = (*span_of(last)).begin;
+        begin + (LIVE_OLD.0 - live_before(begin))
+    };
+}
+
+/// Location of the object at or containing `addr` in to-space
+unsafe fn to_space_loc(addr: usize) -> usize {
+    if REGIONS.is_null() {
+        TO_SPACE + (BITMAP.translate(addr) - HEAP_BEGIN)
+    } else {
+        let span = span_of(region_of(addr));
+        TO_SPACE + (*span).to_space_offset + (live_before(addr) - live_before((*span).begin))
+    }
+}
+
+/// Whether the object at or containing `addr`, an old object, is moved in the current cycle
+unsafe fn is_evacuated(addr: usize) -> bool {
+    REGIONS.is_null() || (*region_of(addr)).span != 0
+}
+
+/// Copies a marked object to to-space and updates its fields in the copy. Returns size of the
+/// object.
+unsafe fn evacuate(obj: usize) -> Words<usize> {
+    let size = object_size(obj);
+    let copy = to_space_loc(obj);
+    memcpy_bytes(copy, obj, size.to_bytes());
+    visit_all_pointer_fields(copy as *mut Obj, |field| *field = forward(*field));
+    size
+}
+
+/// Updates the fields of a marked object that is left in place. Returns size of the object.
+unsafe fn keep(obj: usize) -> Words<usize> {
+    visit_all_pointer_fields(obj as *mut Obj, |field| *field = forward(*field));
+    object_size(obj)
+}
+
+/// Called with the space between the marked objects visited when compacting, `[begin,
+/// end)`. `end` is the next object, in region `region`, or the end of the old objects. The space
+/// in regions that are left in place is filled now, the space around a span is filled when the
+/// span is moved in the flip.
+unsafe fn note_gap(begin: usize, end: usize, region: usize, kept: bool) {
+    if PREV_KEPT {
+        // Regions between the objects are evacuated, as the others have marked objects
+        let span = (NEXT_REGION..=region)
+            .find(|i| (*REGIONS.add(*i)).span != 0)
+            .map(|i| span_of(REGIONS.add(i)));
+
+        match span {
+            None => {
+                if end > begin {
+                    large_objects::fill_gap(begin, Bytes(end - begin));
+                }
+            }
+            Option::Some(span) => {
+                (*span).gap_begin = begin;
+                if kept {
+                    (*span).gap_end = end;
+                }
+            }
+        }
+    } else if kept {
+        (*span_of(REGIONS.add(NEXT_REGION - 1))).gap_end = end;
+    }
+
+    PREV_KEPT = kept;
+    NEXT_REGION = region + 1;
+}
+
+/// Returns the location of an object after the flip
+unsafe fn forward(ptr: SkewedPtr) -> SkewedPtr {
+    if ptr.is_tagged_scalar() {
+        return ptr;
+    }
+
+    let addr = ptr.unskew();
+
+    if addr < HEAP_BEGIN {
+        // Static object
+        ptr
+    } else if addr < OLD_END {
+        skew(final_loc(addr))
+    } else if KEEP_NEW && addr >= NEW_BEGIN && addr < EVAC_BEGIN {
+        // Allocated during the cycle, left in place
+        ptr
+    } else if addr >= NEW_BEGIN && addr < EVAC_BEGIN {
+        // Allocated during the cycle, moved after the old live objects
+        skew(FINAL_OLD_END + (addr - NEW_BEGIN))
+    } else {
+        rts_trap_with(ErrorCode::Internal, "incremental_gc: invalid pointer")
+    }
+}
+
+/// Location of a marked old object after the flip
+unsafe fn final_loc(addr: usize) -> usize {
+    if REGIONS.is_null() {
+        return BITMAP.translate(addr);
+    }
+
+    let region = region_of(addr);
+    if (*region).span == 0 {
+        addr
+    } else {
+        let begin = (*span_of(region)).begin;
+        begin + (live_before(addr) - live_before(begin))
+    }
+}
+
+/// Updates fields of objects in `[begin, end)` in place
+unsafe fn forward_objects(begin: usize, end: usize) {
+    let mut p = begin;
+    while p < end {
+        visit_all_pointer_fields(p as *mut Obj, |field| *field = forward(*field));
+        p += object_size(p).to_bytes().0;
+    }
+}
+
+/// Word-by-word copy towards lower addresses. Source and destination may overlap.
+unsafe fn move_down(to: usize, from: usize, n: Bytes<usize>) {
+    debug_assert!(to <= from);
+    let to = to as *mut usize;
+    let from = from as *const usize;
+    for i in 0..n.to_words().0 {
+        *to.add(i) = *from.add(i);
+    }
+}
+
+/// Copies the spans from to-space into place, and fills the space around them
+unsafe fn move_spans() {
+    let mut i = 0;
+    while i < N_REGIONS {
+        let span = REGIONS.add(i);
+        if (*span).span == 0 {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j < N_REGIONS && (*REGIONS.add(j)).span != 0 {
+            j += 1;
+        }
+
+        let begin = (*span).begin;
+        let size = live_before(region_end(j - 1)) - live_before(begin);
+
+        large_objects::fill_gap((*span).gap_begin, Bytes(begin - (*span).gap_begin));
+        memcpy_bytes(begin, TO_SPACE + (*span).to_space_offset, Bytes(size));
+        if j != N_REGIONS {
+            let end = begin + size;
+            large_objects::fill_gap(end, Bytes((*span).gap_end - end));
+        }
+
+        i = j;
+    }
+}
+
+unsafe fn flip() {
+    // Objects allocated during the cycle
+    forward_objects(NEW_BEGIN, EVAC_BEGIN);
+
+    // Roots
+    visit_roots(|loc| *loc = forward(*loc));
+
+    // Move everything into place. The destinations are always below the sources.
+    let new_size = Bytes(EVAC_BEGIN - NEW_BEGIN);
+
+    if REGIONS.is_null() {
+        memcpy_bytes(HEAP_BEGIN, TO_SPACE, LIVE_OLD);
+    } else {
+        move_spans();
+    }
+
+    let hp = HP;
+    let new_hp = if KEEP_NEW {
+        // The mark bitmap and to-space blobs stay, as garbage
+        if FINAL_OLD_END < OLD_END {
+            large_objects::fill_gap(FINAL_OLD_END, Bytes(OLD_END - FINAL_OLD_END));
+        }
+        hp
+    } else {
+        move_down(FINAL_OLD_END, NEW_BEGIN, new_size);
+        FINAL_OLD_END + new_size.0
+    };
+    HP = new_hp;
+
+    // Note the stats. With partial compaction the heap has garbage left in place.
+    let live = LIVE_OLD + new_size;
+    let heap_size = Bytes(new_hp - HEAP_BEGIN);
+    note_live_size(live);
+    let reclaimed = Bytes(OLD_END - FINAL_OLD_END);
+    note_reclaimed(reclaimed);
+    SCHEDULE.note_collection(GROWTH, reclaimed, heap_size);
+    LAST_LIVE = heap_size;
+
+    note_collection(live);
+
+    end_cycle();
+
+    #[cfg(feature = "heap_sanity")]
+    super::heap_sanity::check_heap(hp);
+}
+
+unsafe fn end_cycle() {
+    // Large objects are moved, the table is not used
+    large_objects::forget_all();
+
+    // Mark stack, weak reference, and region table blobs are now garbage
+    MARK_STACK = WordStack::new();
+    WEAK_REFS = WordStack::new();
+    BITMAP = MarkBitmap::empty();
+    REGIONS = core::ptr::null_mut();
+    KEEP_NEW = false;
+
+    PHASE = Phase::Idle;
+
+    after_collection();
+}
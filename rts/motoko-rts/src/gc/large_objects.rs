@@ -0,0 +1,438 @@
+//! Large object space.
+//!
+//! Objects larger than `LARGE_OBJECT_THRESHOLD` are recorded in a table when allocated. The
+//! copying GC does not move these objects: they are marked when reached, scavenged in place, and
+//! dropped from the table when not marked. The space of a dead large object is reused for small
+//! objects.
+//!
+//! When there are large objects in from-space, live small objects are placed in the gaps between
+//! the live large objects (`place_and_relocate`). For this the copying GC sets pointers to
+//! to-space locations, and these are relocated after evacuation, using a list of segments that
+//! map to-space ranges to their final locations.
+//!
+//! The table is sorted by address. Large objects are mostly allocated by bumping the heap pointer,
+//! and after a GC the heap pointer is above all live large objects, but objects allocated from
+//! the free lists (see `alloc/free_list.rs`) may be below others. Bit 0 of an entry is the mark
+//! bit, bit 1 is set when the object is scavenged.
+//!
+//! Pinned objects (see `pin.rs`) are not moved either. These are added to the table at the
+//! beginning of each collection, marked, and handled the same way as large objects. Small objects
+//! that are no longer pinned are removed from the table before that.
+//!
+//! The other collectors do not use the table and reset it in every collection.
+
+use super::visit_roots;
+use super::word_stack::WordStack;
+use crate::alloc;
+use crate::mem::memcpy_bytes;
+use crate::pin;
+use crate::types::*;
+use crate::visitor::visit_all_pointer_fields;
+
+pub(crate) const LARGE_OBJECT_THRESHOLD: Bytes<usize> = Bytes(64 * 1024);
+
+const MARKED: usize = 0b01;
+const SCANNED: usize = 0b10;
+
+static mut LARGE_OBJECTS: WordStack = WordStack::new();
+
+/// Whether the table has pinned objects smaller than the threshold
+static mut HAS_SMALL_OBJECTS: bool = false;
+
+/// Records a newly allocated large object
+pub(crate) unsafe fn register(addr: usize) {
+    LARGE_OBJECTS.push(addr);
+
+    // Objects allocated from the free lists may be below others
+    let mut idx = LARGE_OBJECTS.len() - 1;
+    while idx > 0 && (LARGE_OBJECTS.get(idx - 1) & !(MARKED | SCANNED)) > addr {
+        LARGE_OBJECTS.set(idx, LARGE_OBJECTS.get(idx - 1));
+        idx -= 1;
+    }
+    LARGE_OBJECTS.set(idx, addr);
+}
+
+/// Removes an object freed in place from the table, see `free_object`
+pub(crate) unsafe fn forget(addr: usize) {
+    if let Option::Some(idx) = find(addr) {
+        let len = LARGE_OBJECTS.len();
+        for i in idx..len - 1 {
+            LARGE_OBJECTS.set(i, LARGE_OBJECTS.get(i + 1));
+        }
+        LARGE_OBJECTS.truncate(len - 1);
+    }
+}
+
+/// Forgets about all large objects. Used by the collectors that do not need the table.
+pub(crate) unsafe fn forget_all() {
+    LARGE_OBJECTS = WordStack::new();
+    HAS_SMALL_OBJECTS = false;
+}
+
+/// Adds the pinned objects at or above `begin_from_space` to the table, and marks them. Small
+/// objects that are no longer pinned are removed. Should be called at the beginning of a copying
+/// collection.
+pub(crate) unsafe fn add_pinned(begin_from_space: usize) {
+    if HAS_SMALL_OBJECTS {
+        let mut n = 0;
+        for i in 0..LARGE_OBJECTS.len() {
+            let entry = LARGE_OBJECTS.get(i);
+            if object_size(entry).to_bytes() >= LARGE_OBJECT_THRESHOLD {
+                LARGE_OBJECTS.set(n, entry);
+                n += 1;
+            }
+        }
+        LARGE_OBJECTS.truncate(n);
+        HAS_SMALL_OBJECTS = false;
+    }
+
+    let mut added = false;
+    pin::for_each_pinned(|obj| {
+        if obj >= begin_from_space {
+            LARGE_OBJECTS.push(obj);
+            added = true;
+            if object_size(obj).to_bytes() < LARGE_OBJECT_THRESHOLD {
+                HAS_SMALL_OBJECTS = true;
+            }
+        }
+    });
+
+    if added {
+        LARGE_OBJECTS.sort_dedup();
+        pin::for_each_pinned(|obj| {
+            if obj >= begin_from_space {
+                mark(obj);
+            }
+        });
+    }
+}
+
+/// Does the table have objects smaller than the threshold? If so, objects of any size need to be
+/// looked up when evacuating.
+pub(crate) unsafe fn has_small_objects() -> bool {
+    HAS_SMALL_OBJECTS
+}
+
+/// Are there large objects at or above `addr`?
+pub(crate) unsafe fn any_above(addr: usize) -> bool {
+    let len = LARGE_OBJECTS.len();
+    len != 0 && (LARGE_OBJECTS.get(len - 1) & !(MARKED | SCANNED)) >= addr
+}
+
+/// Marks a large object. Returns `false` if the object is not in the table.
+pub(crate) unsafe fn mark(addr: usize) -> bool {
+    match find(addr) {
+        Option::Some(idx) => {
+            LARGE_OBJECTS.set(idx, LARGE_OBJECTS.get(idx) | MARKED);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Is the object a marked large object?
+pub(crate) unsafe fn is_marked(addr: usize) -> bool {
+    match find(addr) {
+        Option::Some(idx) => LARGE_OBJECTS.get(idx) & MARKED != 0,
+        None => false,
+    }
+}
+
+/// Index of the object in the table
+unsafe fn find(addr: usize) -> Option<usize> {
+    let mut lo = 0;
+    let mut hi = LARGE_OBJECTS.len();
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let entry_addr = LARGE_OBJECTS.get(mid) & !(MARKED | SCANNED);
+        if entry_addr == addr {
+            return Option::Some(mid);
+        } else if entry_addr < addr {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    None
+}
+
+/// Calls `scav` on marked large objects that were not scavenged yet. Returns whether any object
+/// was scavenged. Scavenging may mark more objects, so this needs to be called until it returns
+/// `false`.
+pub(crate) unsafe fn scav_marked<F: FnMut(usize)>(mut scav: F) -> bool {
+    let mut scavenged = false;
+    for i in 0..LARGE_OBJECTS.len() {
+        let entry = LARGE_OBJECTS.get(i);
+        if entry & (MARKED | SCANNED) == MARKED {
+            LARGE_OBJECTS.set(i, entry | SCANNED);
+            scav(entry & !(MARKED | SCANNED));
+            scavenged = true;
+        }
+    }
+    scavenged
+}
+
+/// Calls `f` with the marked objects
+pub(crate) unsafe fn for_each_marked<F: FnMut(usize)>(mut f: F) {
+    for i in 0..LARGE_OBJECTS.len() {
+        let entry = LARGE_OBJECTS.get(i);
+        if entry & MARKED != 0 {
+            f(entry & !(MARKED | SCANNED));
+        }
+    }
+}
+
+/// Total size of the marked objects at or above `begin`
+pub(crate) unsafe fn marked_size(begin: usize) -> Bytes<usize> {
+    let mut size = Bytes(0);
+    for_each_marked(|obj| {
+        if obj >= begin {
+            size += object_size(obj).to_bytes();
+        }
+    });
+    size
+}
+
+/// Copies the entries of the live large objects (all objects below `begin_from_space`, and the
+/// marked ones above it) to `scratch`, with mark bits cleared. Returns number of entries.
+///
+/// The table itself is in from-space, so it needs to be copied before to-space is copied back.
+pub(crate) unsafe fn sweep(begin_from_space: usize, scratch: usize) -> usize {
+    let scratch = scratch as *mut usize;
+    let mut n = 0;
+    for i in 0..LARGE_OBJECTS.len() {
+        let entry = LARGE_OBJECTS.get(i);
+        let addr = entry & !(MARKED | SCANNED);
+        if addr < begin_from_space || entry & MARKED != 0 {
+            alloc::grow_memory(scratch.add(n + 1) as usize);
+            *scratch.add(n) = addr;
+            n += 1;
+        }
+    }
+    n
+}
+
+/// Rebuilds the table from the entries in `scratch`, copied with `sweep`. Should be called after
+/// the heap pointer is reset. `scratch` should be at least a blob header above the heap pointer.
+pub(crate) unsafe fn restore(scratch: usize, n: usize) {
+    let scratch = scratch as *const usize;
+    LARGE_OBJECTS = WordStack::with_capacity(n);
+    for i in 0..n {
+        LARGE_OBJECTS.push(*scratch.add(i));
+    }
+}
+
+/// Total size of the large objects at or above `begin` in `entries`
+pub(crate) unsafe fn live_size(entries: usize, n: usize, begin: usize) -> Bytes<usize> {
+    let entries = entries as *const usize;
+    let mut size = Bytes(0);
+    for i in 0..n {
+        let obj = *entries.add(i);
+        if obj >= begin {
+            size += object_size(obj).to_bytes();
+        }
+    }
+    size
+}
+
+/// A range of to-space copied to `final_begin`, followed by a gap up to `gap_end`, which may be
+/// empty
+#[repr(C)]
+struct Segment {
+    to_begin: usize,
+    to_end: usize,
+    final_begin: usize,
+    gap_end: usize,
+}
+
+/// Copies the objects in to-space to `begin_from_space` and above, skipping live large objects
+/// (the `n` entries at `entries`, see `sweep`), and relocates pointers to to-space. `entries`
+/// should be right after to-space (plus a blob header, see `restore`), the segment list is built
+/// after it.
+///
+/// Returns the new heap pointer, and the new location of the entries, which are moved if they
+/// would be overwritten.
+pub(crate) unsafe fn place_and_relocate(
+    begin_from_space: usize,
+    begin_to_space: usize,
+    end_to_space: usize,
+    mut entries: usize,
+    n: usize,
+    remembered: &WordStack,
+) -> (usize, usize) {
+    let mut segments = entries + Words(n).to_bytes().0;
+    let mut n_segments = 0;
+
+    let mut push_segment = |to_begin, to_end, final_begin, gap_end| {
+        let segment = (segments as *mut Segment).add(n_segments);
+        alloc::grow_memory(segment.add(1) as usize);
+        *segment = Segment {
+            to_begin,
+            to_end,
+            final_begin,
+            gap_end,
+        };
+        n_segments += 1;
+    };
+
+    // Skip large objects below from-space
+    let mut large_idx = 0;
+    while large_idx < n && entry(entries, large_idx) < begin_from_space {
+        large_idx += 1;
+    }
+
+    let mut cursor = begin_from_space;
+    let mut segment_to_begin = begin_to_space;
+    let mut segment_final_begin = cursor;
+
+    let mut p = begin_to_space;
+    while p < end_to_space {
+        let size = object_size(p).to_bytes().0;
+
+        // Jump over large objects that we would overlap
+        while large_idx < n && cursor + size > entry(entries, large_idx) {
+            let obj = entry(entries, large_idx);
+            push_segment(segment_to_begin, p, segment_final_begin, obj);
+            cursor = obj + object_size(obj).to_bytes().0;
+            segment_to_begin = p;
+            segment_final_begin = cursor;
+            large_idx += 1;
+        }
+
+        cursor += size;
+        p += size;
+    }
+
+    // Gaps between the remaining large objects
+    while large_idx < n {
+        let obj = entry(entries, large_idx);
+        push_segment(segment_to_begin, end_to_space, segment_final_begin, obj);
+        cursor = obj + object_size(obj).to_bytes().0;
+        segment_to_begin = end_to_space;
+        segment_final_begin = cursor;
+        large_idx += 1;
+    }
+
+    push_segment(segment_to_begin, end_to_space, segment_final_begin, cursor);
+
+    // Placing objects in the gaps may need more space than the original layout. Make sure entries
+    // and segments are not overwritten.
+    let scratch_size = segments + n_segments * core::mem::size_of::<Segment>() - entries;
+    if cursor + size_of::<Blob>().to_bytes().0 > entries {
+        let new_entries = ::core::cmp::max(
+            cursor + size_of::<Blob>().to_bytes().0,
+            entries + scratch_size,
+        );
+        alloc::grow_memory(new_entries + scratch_size);
+        memcpy_bytes(new_entries, entries, Bytes(scratch_size));
+        segments = new_entries + (segments - entries);
+        entries = new_entries;
+    }
+
+    let segments = core::slice::from_raw_parts(segments as *const Segment, n_segments);
+
+    let relocate = |field: *mut SkewedPtr| {
+        if (*field).is_tagged_scalar() {
+            return;
+        }
+        let addr = (*field).unskew();
+        if addr < begin_to_space || addr >= end_to_space {
+            return;
+        }
+        // Last segment starting at or before `addr`. Empty segments starting at the same
+        // location come before the segment that has the object.
+        let idx = match segments.binary_search_by(|segment| segment.to_begin.cmp(&addr)) {
+            Ok(mut idx) => {
+                while idx + 1 < segments.len() && segments[idx + 1].to_begin == addr {
+                    idx += 1;
+                }
+                idx
+            }
+            Err(idx) => idx - 1,
+        };
+        let segment = &segments[idx];
+        *field = skew(addr - segment.to_begin + segment.final_begin);
+    };
+
+    // Relocate fields of to-space objects, including weak references
+    let mut p = begin_to_space;
+    while p < end_to_space {
+        visit_all_pointer_fields(p as *mut Obj, relocate);
+        p += object_size(p).to_bytes().0;
+    }
+
+    // Relocate fields of large objects, including (pinned) weak references
+    for i in 0..n {
+        let obj = entry(entries, i);
+        if obj >= begin_from_space {
+            visit_all_pointer_fields(obj as *mut Obj, relocate);
+        }
+    }
+
+    // Relocate roots
+    visit_roots(relocate);
+
+    for i in 0..remembered.len() {
+        relocate(remembered.get(i) as *mut SkewedPtr);
+    }
+
+    // Copy the segments. Distance between the source and destination of a segment grows with each
+    // segment, so segments copied upwards come last. These are copied in reverse order, the ones
+    // copied downwards in order. This way we never overwrite a segment that is not copied yet.
+    let n_down = segments
+        .iter()
+        .take_while(|segment| segment.final_begin <= segment.to_begin)
+        .count();
+
+    for segment in segments[n_down..].iter().rev() {
+        let len = Bytes(segment.to_end - segment.to_begin);
+        copy_words_up(segment.final_begin, segment.to_begin, len.to_words());
+    }
+
+    for segment in &segments[..n_down] {
+        let len = Bytes(segment.to_end - segment.to_begin);
+        copy_words_down(segment.final_begin, segment.to_begin, len.to_words());
+    }
+
+    // Fill the gaps, so that the heap can still be walked
+    for segment in segments {
+        let end = segment.final_begin + (segment.to_end - segment.to_begin);
+        fill_gap(end, Bytes(segment.gap_end - end));
+    }
+
+    (cursor, entries)
+}
+
+unsafe fn entry(entries: usize, idx: usize) -> usize {
+    *(entries as *const usize).add(idx)
+}
+
+/// Copies words to a lower address. Source and destination may overlap.
+unsafe fn copy_words_down(to: usize, from: usize, n: Words<usize>) {
+    let to = to as *mut usize;
+    let from = from as *const usize;
+    for i in 0..n.0 {
+        *to.add(i) = *from.add(i);
+    }
+}
+
+/// Copies words to a higher address. Source and destination may overlap.
+unsafe fn copy_words_up(to: usize, from: usize, n: Words<usize>) {
+    let to = to as *mut usize;
+    let from = from as *const usize;
+    for i in (0..n.0).rev() {
+        *to.add(i) = *from.add(i);
+    }
+}
+
+/// Fills a gap with a blob, or with zero words when too small for a blob
+pub(crate) unsafe fn fill_gap(addr: usize, size: Bytes<usize>) {
+    let size = size.to_words();
+    if size.0 < size_of::<Blob>().0 {
+        libc::memset(addr as *mut _, 0, size.to_bytes().0);
+    } else {
+        let blob = addr as *mut Blob;
+        (*blob).header.tag = TAG_BLOB;
+        (*blob).len = (size - size_of::<Blob>()).to_bytes();
+    }
+}
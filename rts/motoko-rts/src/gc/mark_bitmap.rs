@@ -0,0 +1,130 @@
+//! Mark bitmap with one bit per heap word.
+//!
+//! All words of a marked object are marked, not just the header. This way the number of live
+//! words below an address can be counted with `popcount`, which gives us the address of an object
+//! after compaction (see `translate`) without having to store forwarding pointers in the objects.
+
+use super::alloc_gc_blob;
+use crate::types::{Bytes, Words, WORD_SIZE};
+
+/// Bits in a word of the bitmap
+const BITS: usize = WORD_SIZE * 8;
+
+pub(crate) struct MarkBitmap {
+    /// Address of the first word covered by the bitmap
+    base: usize,
+    /// Number of words in `bits` (and `live_before`)
+    n_words: usize,
+    /// The bits
+    bits: *mut usize,
+    /// For each word of `bits`, number of marked heap words in the words of `bits` before it.
+    /// Only valid after `compute_live_before`.
+    live_before: *mut usize,
+}
+
+impl MarkBitmap {
+    pub(crate) const fn empty() -> Self {
+        MarkBitmap {
+            base: 0,
+            n_words: 0,
+            bits: core::ptr::null_mut(),
+            live_before: core::ptr::null_mut(),
+        }
+    }
+
+    /// Allocates a bitmap for the heap words in `[base, end)`, with no words marked
+    pub(crate) unsafe fn new(base: usize, end: usize) -> Self {
+        let n_heap_words = (end - base) / WORD_SIZE;
+        let n_words = (n_heap_words + BITS - 1) / BITS;
+
+        let blob = alloc_gc_blob(Words(n_words * 2).to_bytes());
+        let bits = blob.payload_addr() as *mut usize;
+        let live_before = bits.add(n_words);
+
+        libc::memset(bits as *mut _, 0, Words(n_words).to_bytes().0);
+
+        MarkBitmap {
+            base,
+            n_words,
+            bits,
+            live_before,
+        }
+    }
+
+    fn bit_index(&self, addr: usize) -> usize {
+        (addr - self.base) / WORD_SIZE
+    }
+
+    pub(crate) unsafe fn is_marked(&self, addr: usize) -> bool {
+        let idx = self.bit_index(addr);
+        (*self.bits.add(idx / BITS) >> (idx % BITS)) & 1 == 1
+    }
+
+    /// Marks `n` words starting at `addr`
+    pub(crate) unsafe fn mark_range(&mut self, addr: usize, n: Words<usize>) {
+        let mut idx = self.bit_index(addr);
+        let end = idx + n.0;
+        while idx < end {
+            let bit = idx % BITS;
+            let n_bits = core::cmp::min(BITS - bit, end - idx);
+            let mask = if n_bits == BITS {
+                !0
+            } else {
+                ((1 << n_bits) - 1) << bit
+            };
+            *self.bits.add(idx / BITS) |= mask;
+            idx += n_bits;
+        }
+    }
+
+    /// Fills in `live_before` and returns the total amount of marked memory
+    pub(crate) unsafe fn compute_live_before(&mut self) -> Bytes<usize> {
+        let mut live = 0;
+        for i in 0..self.n_words {
+            *self.live_before.add(i) = live;
+            live += (*self.bits.add(i)).count_ones() as usize;
+        }
+        Words(live).to_bytes()
+    }
+
+    /// Address of the (marked) word at `addr` when all marked words are slid down to `base`.
+    /// `compute_live_before` must have been called after marking.
+    pub(crate) unsafe fn translate(&self, addr: usize) -> usize {
+        let idx = self.bit_index(addr);
+        let word = idx / BITS;
+        let below_mask = (1usize << (idx % BITS)) - 1;
+        let live =
+            *self.live_before.add(word) + (*self.bits.add(word) & below_mask).count_ones() as usize;
+        self.base + Words(live).to_bytes().0
+    }
+
+    /// Returns the address of the first marked word in `[from, end)`
+    pub(crate) unsafe fn next_marked(&self, from: usize, end: usize) -> Option<usize> {
+        if from >= end {
+            return None;
+        }
+
+        let mut idx = self.bit_index(from);
+        let end_idx = self.bit_index(end);
+
+        // Bits below `idx` in the first word are ignored
+        let mut bits = *self.bits.add(idx / BITS) & !((1usize << (idx % BITS)) - 1);
+        idx &= !(BITS - 1);
+
+        loop {
+            if bits != 0 {
+                let found = idx + bits.trailing_zeros() as usize;
+                if found >= end_idx {
+                    return None;
+                }
+                return Some(self.base + Words(found).to_bytes().0);
+            }
+
+            idx += BITS;
+            if idx >= end_idx {
+                return None;
+            }
+            bits = *self.bits.add(idx / BITS);
+        }
+    }
+}
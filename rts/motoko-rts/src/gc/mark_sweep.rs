@@ -0,0 +1,197 @@
+//! Non-moving mark-sweep GC.
+//!
+//! Objects are marked using the mark bitmap, starting from the roots (see `visit_roots`). The heap
+//! is then swept, runs of unmarked objects are merged into free chunks and added to the free lists
+//! (see `alloc/free_list.rs`). `alloc_words` allocates from the free lists before bumping the heap
+//! pointer.
+//!
+//! Objects are never moved, so pointers to heap objects stay valid as long as the objects are
+//! reachable, and pinned objects (see `pin.rs`) need no special handling.
+//!
+//! Weak references to unmarked objects are cleared before sweeping, see `weak_refs`.
+
+use super::generational;
+use super::large_objects;
+use super::mark_bitmap::MarkBitmap;
+use super::weak_refs;
+use super::word_stack::WordStack;
+use super::{
+    after_collection, closure_table_size, deferred, get_heap_base, note_closure_table_retained,
+    note_collection, note_live_size, note_reclaimed, take_forced, visit_roots,
+    visit_roots_but_closure_table, HP,
+};
+use crate::alloc::free_list::{self, add_free_chunk};
+use crate::closure_table::closure_table_loc;
+use crate::scratch;
+use crate::to_space_arena;
+use crate::types::*;
+use crate::visitor::visit_pointer_fields;
+
+/// The entry point. Called by the generated code.
+#[no_mangle]
+pub unsafe extern "C" fn mark_sweep_gc() {
+    scratch::release();
+    to_space_arena::release();
+
+    if deferred() {
+        return;
+    }
+    take_forced();
+    generational::reset();
+
+    #[cfg(feature = "redzones")]
+    crate::alloc::redzones::check_heap();
+
+    let heap_base = get_heap_base();
+    let heap_end = HP;
+
+    // Bitmap, mark stack and the list of weak references are allocated after the heap, and
+    // discarded after sweeping
+    let mut bitmap = MarkBitmap::new(heap_base, heap_end);
+    let mut stack = WordStack::new();
+    let mut weak_ref_list = WordStack::new();
+
+    // The closure table is marked last, the objects marked after it are only retained by the table
+    mark_roots_but_closure_table(&mut bitmap, &mut stack, heap_base, heap_end);
+    mark_stack(
+        &mut bitmap,
+        &mut stack,
+        &mut weak_ref_list,
+        heap_base,
+        heap_end,
+    );
+
+    let table_size = closure_table_size(|table| bitmap.is_marked(table));
+    mark(
+        &mut bitmap,
+        &mut stack,
+        heap_base,
+        heap_end,
+        *closure_table_loc(),
+    );
+    let retained = mark_stack(
+        &mut bitmap,
+        &mut stack,
+        &mut weak_ref_list,
+        heap_base,
+        heap_end,
+    );
+    note_closure_table_retained(retained - table_size);
+
+    weak_refs::clear_unmarked(&weak_ref_list, &bitmap, heap_base, heap_end);
+
+    // Large objects are not moved anyway
+    large_objects::forget_all();
+
+    let free_before = free_list::free_size();
+    let live = sweep(&bitmap, heap_base, heap_end);
+
+    note_live_size(live);
+    note_reclaimed(Bytes(heap_end - heap_base) - live - free_before);
+
+    note_collection(live);
+
+    after_collection();
+
+    #[cfg(feature = "heap_sanity")]
+    super::heap_sanity::check_heap(heap_end);
+}
+
+/// Marks the objects reachable from the objects on the mark stack, and records the marked weak
+/// references. Returns the size of the objects taken from the stack.
+unsafe fn mark_stack(
+    bitmap: &mut MarkBitmap,
+    stack: &mut WordStack,
+    weak_ref_list: &mut WordStack,
+    heap_base: usize,
+    heap_end: usize,
+) -> Bytes<usize> {
+    let mut size = Bytes(0);
+    while let Option::Some(obj) = stack.pop() {
+        let obj = obj as *mut Obj;
+        if obj.tag() == TAG_WEAK_REF {
+            weak_ref_list.push(obj as usize);
+        }
+        size += object_size(obj as usize).to_bytes();
+        visit_pointer_fields(obj, |field| {
+            mark(bitmap, stack, heap_base, heap_end, *field)
+        });
+    }
+    size
+}
+
+/// Marks the objects referenced by the roots (see `visit_roots`) and pushes them to the mark stack.
+/// Also used by the live census, see `heap_census.rs`.
+pub(crate) unsafe fn mark_roots(
+    bitmap: &mut MarkBitmap,
+    stack: &mut WordStack,
+    heap_base: usize,
+    heap_end: usize,
+) {
+    visit_roots(|loc| mark(bitmap, stack, heap_base, heap_end, *loc));
+}
+
+/// Marks the objects referenced by the roots other than the closure table, see `mark_roots`
+unsafe fn mark_roots_but_closure_table(
+    bitmap: &mut MarkBitmap,
+    stack: &mut WordStack,
+    heap_base: usize,
+    heap_end: usize,
+) {
+    visit_roots_but_closure_table(|loc| mark(bitmap, stack, heap_base, heap_end, *loc));
+}
+
+pub(crate) unsafe fn mark(
+    bitmap: &mut MarkBitmap,
+    stack: &mut WordStack,
+    heap_base: usize,
+    heap_end: usize,
+    ptr: SkewedPtr,
+) {
+    if ptr.is_tagged_scalar() {
+        return;
+    }
+
+    let addr = ptr.unskew();
+
+    // Static objects are not collected
+    if addr < heap_base || addr >= heap_end {
+        return;
+    }
+
+    if bitmap.is_marked(addr) {
+        return;
+    }
+
+    bitmap.mark_range(addr, Words(1));
+    stack.push(addr);
+}
+
+/// Rebuilds the free lists from unmarked objects, resets the heap pointer to the end of the last
+/// marked object. Returns the amount of live data.
+unsafe fn sweep(bitmap: &MarkBitmap, heap_base: usize, heap_end: usize) -> Bytes<usize> {
+    free_list::clear();
+
+    let mut live = Bytes(0);
+
+    // Beginning of the current run of unmarked objects
+    let mut free_begin = heap_base;
+
+    let mut p = heap_base;
+    while p < heap_end {
+        let size = object_size(p).to_bytes();
+        if bitmap.is_marked(p) {
+            if free_begin < p {
+                add_free_chunk(free_begin, Bytes(p - free_begin).to_words());
+            }
+            live += size;
+            free_begin = p + size.0;
+        }
+        p += size.0;
+    }
+
+    // Free memory at the end of the heap is given back to the bump allocator
+    HP = free_begin;
+
+    live
+}
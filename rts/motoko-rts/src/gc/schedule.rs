@@ -0,0 +1,164 @@
+//! Adaptive GC scheduling. Decides when the incremental GC starts a cycle and when the
+//! generational GC does a major collection. (The copying and mark-sweep GCs collect at the end of
+//! every message.)
+//!
+//! The collectors call `Schedule::due` at the end of each message, a scheduling point, with the
+//! growth of the collected area since the last collection, and `Schedule::note_collection` after
+//! each collection. From these we track, as moving averages over the collections:
+//!
+//! - the allocation rate: bytes the collected area grows by per scheduling point, and
+//! - the reclamation ratio: the fraction of the growth that is garbage by the next collection.
+//!
+//! With `L` live bytes after the last collection, reclamation ratio `r` and target utilization `U`,
+//! the heap has live data `L + g * (1 - r)` after growing by `g`. The next collection is due when
+//! the utilization drops to `U`, which is after growing by the budget
+//!
+//! ```text
+//! g = L * (1 - U) / (r - (1 - U))
+//! ```
+//!
+//! When `r <= 1 - U` most of the new data stays live and the utilization does not drop to `U`; the
+//! budget is then unlimited. The budget is at least the minimum growth given by the collector, so
+//! small heaps are not collected all the time.
+//!
+//! A collection that takes several scheduling points (a cycle of the incremental GC) is started
+//! early, so that it's done when the heap has grown by the budget.
+//!
+//! Collections are at least `min_interval` and at most `max_interval` scheduling points apart,
+//! unless forced with `gc_force`. The target utilization and the intervals are configured with
+//! `set_gc_schedule`.
+
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::Bytes;
+
+/// Target utilization (live data / heap size) in percent
+static mut TARGET_UTILIZATION: u32 = 67;
+
+/// Minimum number of scheduling points between collections
+static mut MIN_INTERVAL: u32 = 1;
+
+/// Maximum number of scheduling points between collections
+static mut MAX_INTERVAL: u32 = u32::MAX;
+
+/// Configures the scheduling of the incremental and generational GCs. `target_utilization` is in
+/// percent, the intervals are in scheduling points (messages).
+#[no_mangle]
+unsafe extern "C" fn set_gc_schedule(
+    target_utilization: u32,
+    min_interval: u32,
+    max_interval: u32,
+) {
+    if target_utilization == 0 || target_utilization >= 100 {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "set_gc_schedule: target utilization must be between 1 and 99",
+        );
+    }
+
+    if min_interval == 0 || min_interval > max_interval {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "set_gc_schedule: invalid interval",
+        );
+    }
+
+    TARGET_UTILIZATION = target_utilization;
+    MIN_INTERVAL = min_interval;
+    MAX_INTERVAL = max_interval;
+}
+
+pub(crate) struct Schedule {
+    /// Minimum budget, given by the collector
+    min_growth: Bytes<usize>,
+
+    /// Scheduling points since the last collection
+    points: u32,
+
+    /// Live data in the collected area after the last collection
+    live: Bytes<usize>,
+
+    /// Allocation rate, bytes per scheduling point. `None` until the first collection.
+    alloc_rate: Option<u64>,
+
+    /// Reclamation ratio in percent. `None` until the first collection.
+    reclaim_ratio: Option<u64>,
+}
+
+impl Schedule {
+    pub(crate) const fn new(min_growth: Bytes<usize>) -> Self {
+        Schedule {
+            min_growth,
+            points: 0,
+            live: Bytes(0),
+            alloc_rate: None,
+            reclaim_ratio: None,
+        }
+    }
+
+    /// Called at each scheduling point, returns whether the next collection should start now.
+    /// `growth` is the growth of the collected area since the last collection, `length` is the
+    /// expected number of scheduling points the collection takes.
+    pub(crate) unsafe fn due(&mut self, growth: Bytes<usize>, length: usize) -> bool {
+        self.points = self.points.saturating_add(1);
+
+        if self.points < MIN_INTERVAL {
+            return false;
+        }
+
+        if self.points >= MAX_INTERVAL {
+            return true;
+        }
+
+        // Growth until the collection is done
+        let lead = self.alloc_rate.unwrap_or(0) * length.saturating_sub(1) as u64;
+
+        growth.0 as u64 + lead >= self.budget()
+    }
+
+    /// Growth of the collected area after which the next collection is due
+    unsafe fn budget(&self) -> u64 {
+        let min_growth = self.min_growth.0 as u64;
+
+        // Until we know better assume all new data is garbage
+        let r = self.reclaim_ratio.unwrap_or(100);
+        let free = 100 - TARGET_UTILIZATION as u64;
+
+        if r <= free {
+            return u64::MAX;
+        }
+
+        let budget = self.live.0 as u64 * free / (r - free);
+        ::core::cmp::max(budget, min_growth)
+    }
+
+    /// Called when a collection is done. `growth` is the growth of the collected area since the
+    /// last collection, `reclaimed` is the memory reclaimed, and `live` is the size of the
+    /// collected area after the collection.
+    pub(crate) fn note_collection(
+        &mut self,
+        growth: Bytes<usize>,
+        reclaimed: Bytes<usize>,
+        live: Bytes<usize>,
+    ) {
+        let points = ::core::cmp::max(self.points, 1);
+        self.alloc_rate = Some(average(self.alloc_rate, growth.0 as u64 / points as u64));
+
+        if growth.0 != 0 {
+            // More than the growth is reclaimed when older data dies
+            let ratio = ::core::cmp::min(reclaimed.0 as u64 * 100 / growth.0 as u64, 100);
+            self.reclaim_ratio = Some(average(self.reclaim_ratio, ratio));
+        }
+
+        self.points = 0;
+        self.live = live;
+    }
+}
+
+/// Exponential moving average, the new sample has weight 1/4
+fn average(avg: Option<u64>, sample: u64) -> u64 {
+    match avg {
+        None => sample,
+        Some(avg) => (avg * 3 + sample) / 4,
+    }
+}
@@ -0,0 +1,88 @@
+//! GC support for weak references (see `weak_ref.rs`).
+//!
+//! Fields of weak references are not followed when marking or evacuating. When all live objects
+//! are found, weak references to objects that were not found are cleared, the rest are updated
+//! like any other pointer field when objects are moved.
+//!
+//! The copying GC (and the generational GC, which uses it) does not keep a list of weak
+//! references. When a weak reference is copied to to-space we only note that to-space has weak
+//! references, and `update_copied` walks to-space after evacuation. This way collections without
+//! weak references do not pay for the extra walk.
+//!
+//! The mark-sweep and the incremental GCs collect the weak references they mark in a `WordStack`,
+//! and clear the dead ones with `clear_unmarked` before sweeping or evacuating. The incremental GC
+//! marks while the mutator runs, so it also needs a read barrier, otherwise the mutator could
+//! store a referent that is not marked yet in an object that is already scanned.
+
+use super::large_objects;
+use super::mark_bitmap::MarkBitmap;
+use super::word_stack::WordStack;
+use crate::types::*;
+
+/// Set when a weak reference is copied to to-space
+static mut COPIED: bool = false;
+
+pub(crate) unsafe fn note_copied() {
+    COPIED = true;
+}
+
+/// Updates fields of the weak references in `[begin_to_space, end_to_space)`, and of the pinned
+/// weak references (which are not moved, see `pin.rs`), after evacuation. Referents in from-space
+/// that were evacuated are forwarded to their new locations, marked large and pinned objects stay
+/// where they are, other referents in from-space are dead.
+pub(crate) unsafe fn update_copied(
+    begin_from_space: usize,
+    begin_to_space: usize,
+    end_to_space: usize,
+) {
+    if !COPIED {
+        return;
+    }
+    COPIED = false;
+
+    let mut p = begin_to_space;
+    while p < end_to_space {
+        update_copied_weak_ref(p, begin_from_space, begin_to_space);
+        p += object_size(p).to_bytes().0;
+    }
+
+    large_objects::for_each_marked(|obj| {
+        update_copied_weak_ref(obj, begin_from_space, begin_to_space)
+    });
+}
+
+unsafe fn update_copied_weak_ref(obj: usize, begin_from_space: usize, begin_to_space: usize) {
+    if (obj as *mut Obj).tag() != TAG_WEAK_REF {
+        return;
+    }
+
+    let field = &mut (*(obj as *mut WeakRef)).field;
+    let referent = field.unskew();
+    if referent >= begin_from_space && referent < begin_to_space {
+        if (referent as *mut Obj).tag() == TAG_FWD_PTR {
+            *field = (*(referent as *const FwdPtr)).fwd;
+        } else if !large_objects::is_marked(referent) {
+            *field = WEAK_REF_CLEARED;
+        }
+    }
+}
+
+/// Clears weak references in `weak_refs` to objects in `[begin, end)` that are not marked
+pub(crate) unsafe fn clear_unmarked(
+    weak_refs: &WordStack,
+    bitmap: &MarkBitmap,
+    begin: usize,
+    end: usize,
+) {
+    for i in 0..weak_refs.len() {
+        let weak_ref = weak_refs.get(i) as *mut WeakRef;
+        let referent = (*weak_ref).field;
+        if referent == WEAK_REF_CLEARED {
+            continue;
+        }
+        let addr = referent.unskew();
+        if addr >= begin && addr < end && !bitmap.is_marked(addr) {
+            (*weak_ref).field = WEAK_REF_CLEARED;
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! A growable stack of words, stored in a blob in the dynamic heap. Used by the collectors, e.g.
+//! for the mark stacks.
+
+use super::alloc_gc_blob;
+use crate::mem::memcpy_words;
+use crate::types::Words;
+
+const INIT_CAPACITY: usize = 1024;
+
+pub(crate) struct WordStack {
+    ptr: *mut usize,
+    len: usize,
+    cap: usize,
+}
+
+impl WordStack {
+    pub(crate) const fn new() -> Self {
+        WordStack {
+            ptr: core::ptr::null_mut(),
+            len: 0,
+            cap: 0,
+        }
+    }
+
+    /// Allocates a stack that can hold `cap` words without growing
+    pub(crate) unsafe fn with_capacity(cap: usize) -> Self {
+        if cap == 0 {
+            return WordStack::new();
+        }
+
+        WordStack {
+            ptr: alloc_gc_blob(Words(cap).to_bytes()).payload_addr() as *mut usize,
+            len: 0,
+            cap,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) unsafe fn push(&mut self, word: usize) {
+        if self.len == self.cap {
+            self.grow();
+        }
+        *self.ptr.add(self.len) = word;
+        self.len += 1;
+    }
+
+    pub(crate) unsafe fn pop(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            Some(*self.ptr.add(self.len))
+        }
+    }
+
+    pub(crate) unsafe fn get(&self, idx: usize) -> usize {
+        debug_assert!(idx < self.len);
+        *self.ptr.add(idx)
+    }
+
+    pub(crate) unsafe fn set(&mut self, idx: usize, word: usize) {
+        debug_assert!(idx < self.len);
+        *self.ptr.add(idx) = word;
+    }
+
+    /// Removes the words after the first `len` words
+    pub(crate) fn truncate(&mut self, len: usize) {
+        debug_assert!(len <= self.len);
+        self.len = len;
+    }
+
+    /// Sorts the words and removes duplicates
+    pub(crate) unsafe fn sort_dedup(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        let words = core::slice::from_raw_parts_mut(self.ptr, self.len);
+        words.sort_unstable();
+
+        let mut n_unique = 1;
+        for i in 1..words.len() {
+            if words[i] != words[n_unique - 1] {
+                words[n_unique] = words[i];
+                n_unique += 1;
+            }
+        }
+
+        self.len = n_unique;
+    }
+
+    /// Allocates a new blob with double the capacity. The old blob becomes garbage and is
+    /// reclaimed in the next GC cycle.
+    unsafe fn grow(&mut self) {
+        let new_cap = if self.cap == 0 {
+            INIT_CAPACITY
+        } else {
+            self.cap * 2
+        };
+
+        let new_ptr = alloc_gc_blob(Words(new_cap).to_bytes()).payload_addr() as *mut usize;
+
+        if self.len != 0 {
+            memcpy_words(new_ptr as usize, self.ptr as usize, Words(self.len));
+        }
+
+        self.ptr = new_ptr;
+        self.cap = new_cap;
+    }
+}
@@ -0,0 +1,28 @@
+//! Hooks for the GC stress mode of the tests (`gc_stress` feature, see `gc_stress.rs` in
+//! `motoko-rts-tests`), which collects in every allocation of the RTS.
+//!
+//! The RTS does not know the pointers in the locals of the running RTS functions, the tests find
+//! them on the native stack. For this they need the objects of the heap, `for_each_object`.
+
+use crate::gc::{get_heap_base, HP};
+use crate::types::*;
+
+/// Called by `alloc_words` before allocating, when set
+pub static mut ALLOC_HOOK: Option<unsafe fn()> = None;
+
+/// Calls `f` with the address and the size of each object in the dynamic heap, in address order.
+/// Free chunks, redzones, and the slop after shrunk blobs are not objects.
+pub unsafe fn for_each_object<F: FnMut(usize, Bytes<usize>)>(mut f: F) {
+    let heap_end = HP;
+    let mut p = get_heap_base();
+    while p < heap_end {
+        let tag = (p as *mut Obj).tag();
+        let size = object_size(p).to_bytes();
+
+        if tag != 0 && tag != TAG_REDZONE && tag != TAG_FREE_CHUNK {
+            f(p, size);
+        }
+
+        p += size.0;
+    }
+}
@@ -0,0 +1,464 @@
+//! Graph copy of the stable variables, an alternative to their Candid encoding for upgrades (with
+//! `--graph-copy-stabilization`).
+//!
+//! Encoding the stable variables in Candid before an upgrade, and decoding them after it, takes
+//! instructions in proportion to the size of the stable state, so a canister with enough stable
+//! state cannot be upgraded within the instruction limit. The graph copy writes the heap objects
+//! reachable from the stable variables to stable memory as they are, with pointers replaced by
+//! object numbers, and the new version allocates them again and turns the numbers back into
+//! pointers. There is no encoding of values and no traversal driven by their types.
+//!
+//! The copy is in region 0, after the size word, where the Candid encoding would be, and
+//! `FLAG_GRAPH_COPY` is set in the header of stable memory (see `region.rs`):
+//!
+//! ```text
+//! magic          "MOGC"
+//! version        1
+//! type           length and bytes of the fingerprint of the stable type (see `typ_hash.ml`)
+//! type table     length and bytes of the Candid type table of the stable type
+//! descriptor     number of words and the 32-bit words of the value descriptor (see `idl_encode.rs`)
+//! hashes         number of words and the words of the hash lists of the stable type
+//! n_objects
+//! objects        n_objects records: the size of the object in words, then its words
+//! ```
+//!
+//! Numbers are little-endian machine words (32 bits on wasm32). Object 0 holds the stable
+//! variables. Pointer fields hold `(n << 1) | 1` for object `n`, so they are still told apart from
+//! scalars, and the hash pointer of an `Object` holds the number of its hash list.
+//!
+//! The hash lists are the field hashes of the object types in the stable type, as provided by the
+//! compiler: the number of lists, then the length and the hashes of each list. An object with
+//! other fields (an object of a subtype, or with private fields) or an object that cannot be
+//! copied (a closure, found in a field of type `Any`) makes us fall back to the Candid encoding.
+//!
+//! Objects are numbered in breadth-first order. While copying, the number of an object is in its
+//! tag, as `MARK_BASE + n`, the tags are restored when done.
+//!
+//! After the upgrade, the graph is restored as it is when the stable type has the same
+//! fingerprint. Otherwise the type may have changed in a compatible way, which Candid subtyping
+//! decides: the objects are restored with the old hash lists, encoded in Candid with the old type
+//! table and descriptor, replacing the copy in region 0, and decoded by the generated code as
+//! usual. This only costs the Candid encoding and decoding on upgrades that change the stable type.
+
+use crate::alloc::alloc_words;
+use crate::buf::StableBuf;
+use crate::error_code::ErrorCode;
+use crate::idl_encode::idl_serialize_stable;
+use crate::region::{stable_vars_size, stable_vars_stream, STABLE_VARS};
+use crate::rts_trap_with;
+use crate::scratch::scratch_alloc;
+use crate::stream::Stream;
+use crate::types::*;
+use crate::visitor::visit_pointer_fields;
+
+use core::mem::size_of;
+use core::slice;
+
+const MAGIC: usize = 0x4347_4F4D;
+
+const VERSION: usize = 1;
+
+/// Tags of the objects being copied are `MARK_BASE` plus their number
+const MARK_BASE: Tag = !(usize::MAX >> 1);
+
+/// Number of entries of the cache of the hash list numbers of hash pointers
+const HASH_CACHE_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    obj: *mut Obj,
+    /// The tag of the object, replaced by its number while copying
+    tag: Tag,
+}
+
+/// The objects reachable from the stable variables, in the scratch space
+struct Objects {
+    entries: *mut Entry,
+    len: usize,
+    capacity: usize,
+}
+
+impl Objects {
+    fn new() -> Self {
+        Objects {
+            entries: core::ptr::null_mut(),
+            len: 0,
+            capacity: 0,
+        }
+    }
+
+    unsafe fn get(&self, n: usize) -> Entry {
+        *self.entries.add(n)
+    }
+
+    /// Adds the object, replacing its tag with its number
+    unsafe fn push(&mut self, obj: *mut Obj) {
+        if self.len == self.capacity {
+            let capacity = core::cmp::max(2 * self.capacity, 64);
+            let entries = scratch_alloc(Bytes(capacity * size_of::<Entry>())) as *mut Entry;
+            core::ptr::copy_nonoverlapping(self.entries, entries, self.len);
+            self.entries = entries;
+            self.capacity = capacity;
+        }
+        *self.entries.add(self.len) = Entry {
+            obj,
+            tag: obj.tag(),
+        };
+        (*obj).tag = MARK_BASE + self.len;
+        self.len += 1;
+    }
+
+    unsafe fn restore_tags(&self) {
+        for n in 0..self.len {
+            let entry = self.get(n);
+            (*entry.obj).tag = entry.tag;
+        }
+    }
+}
+
+/// Whether objects with the tag are copied. Other objects are not in stable values.
+fn copied(tag: Tag) -> bool {
+    matches!(
+        tag,
+        TAG_OBJECT
+            | TAG_OBJ_IND
+            | TAG_ARRAY
+            | TAG_BITS64
+            | TAG_MUTBOX
+            | TAG_SOME
+            | TAG_VARIANT
+            | TAG_BLOB
+            | TAG_BITS32
+            | TAG_BIGINT
+            | TAG_CONCAT
+            | TAG_NULL
+            | TAG_SLICE
+            | TAG_SMALL_TEXT
+    )
+}
+
+/// Calls `f` with the number, the address of the hashes and the length of every hash list of the
+/// hash lists at `lists`, returns the number of words of the hash lists
+unsafe fn for_hash_lists<F: FnMut(usize, *const usize, usize)>(
+    lists: *const usize,
+    mut f: F,
+) -> usize {
+    let n_lists = *lists;
+    let mut pos = 1;
+    for n in 0..n_lists {
+        let len = *lists.add(pos);
+        f(n, lists.add(pos + 1), len);
+        pos += 1 + len;
+    }
+    pos
+}
+
+/// Number of the hash list with the `len` hashes at `hashes`
+unsafe fn find_hash_list(lists: *const usize, hashes: *const usize, len: usize) -> Option<usize> {
+    let mut found = None;
+    for_hash_lists(lists, |n, list, list_len| {
+        if found.is_none()
+            && list_len == len
+            && slice::from_raw_parts(list, len) == slice::from_raw_parts(hashes, len)
+        {
+            found = Some(n);
+        }
+    });
+    found
+}
+
+/// Numbers of the hash lists of hash pointers, as objects of the same type share their hash
+/// pointer
+struct HashCache {
+    hash_ptrs: [usize; HASH_CACHE_SIZE],
+    numbers: [usize; HASH_CACHE_SIZE],
+}
+
+impl HashCache {
+    fn new() -> Self {
+        HashCache {
+            hash_ptrs: [0; HASH_CACHE_SIZE],
+            numbers: [0; HASH_CACHE_SIZE],
+        }
+    }
+
+    /// Number of the hash list of the object in `lists`
+    unsafe fn find(&mut self, lists: *const usize, obj: *mut Object) -> Option<usize> {
+        let hash_ptr = (*obj).hash_ptr;
+        let slot = (hash_ptr / WORD_SIZE) % HASH_CACHE_SIZE;
+        if self.hash_ptrs[slot] == hash_ptr {
+            return Some(self.numbers[slot]);
+        }
+        let hashes = SkewedPtr(hash_ptr).unskew() as *const usize;
+        let number = find_hash_list(lists, hashes, obj.size())?;
+        self.hash_ptrs[slot] = hash_ptr;
+        self.numbers[slot] = number;
+        Some(number)
+    }
+}
+
+fn write_word<S: Stream>(stream: &mut S, word: usize) {
+    stream.write(&word.to_le_bytes());
+}
+
+unsafe fn write_words<S: Stream>(stream: &mut S, words: *const usize, n: usize) {
+    stream.write(slice::from_raw_parts(words as *const u8, n * WORD_SIZE));
+}
+
+/// Writes the stable variables `x` to stable memory, as a graph copy, or in Candid (with the
+/// descriptor and the type table, see `idl_serialize_stable`) when they cannot be copied. `typ` is
+/// the fingerprint of the stable type and `hash_lists` its hash lists, see the module
+/// documentation.
+#[no_mangle]
+pub unsafe extern "C" fn graph_copy_stabilize(
+    x: SkewedPtr,
+    desc: *const u8,
+    n_words: u32,
+    tydesc: *const u8,
+    tydesc_len: u32,
+    typ: *const u8,
+    typ_len: u32,
+    hash_lists: *const usize,
+) {
+    let mut objects = Objects::new();
+    let mut hash_cache = HashCache::new();
+
+    // Number the objects, and find the size of the copy
+    objects.push(x.as_obj());
+    let mut objects_size = 0;
+    let mut copyable = true;
+    let mut n = 0;
+    while n < objects.len {
+        let entry = objects.get(n);
+        let obj = entry.obj;
+        (*obj).tag = entry.tag;
+        if !copied(entry.tag)
+            || (entry.tag == TAG_OBJECT
+                && hash_cache.find(hash_lists, obj as *mut Object).is_none())
+        {
+            copyable = false;
+            break;
+        }
+        objects_size += (1 + object_size(obj as usize).0) * WORD_SIZE;
+        visit_pointer_fields(obj, |field| {
+            let field = *field;
+            if !field.is_tagged_scalar() && field.as_obj() != obj && field.tag() < MARK_BASE {
+                objects.push(field.as_obj());
+            }
+        });
+        (*obj).tag = MARK_BASE + n;
+        n += 1;
+    }
+
+    if !copyable {
+        objects.restore_tags();
+        idl_serialize_stable(desc, n_words, x, tydesc, tydesc_len);
+        return;
+    }
+
+    let hash_words = for_hash_lists(hash_lists, |_, _, _| ());
+    let len = 7 * WORD_SIZE
+        + typ_len as usize
+        + tydesc_len as usize
+        + n_words as usize * size_of::<u32>()
+        + hash_words * WORD_SIZE
+        + objects_size;
+
+    let mut stream = stable_vars_stream(len as u32, true);
+    write_word(&mut stream, MAGIC);
+    write_word(&mut stream, VERSION);
+    write_word(&mut stream, typ_len as usize);
+    stream.write(slice::from_raw_parts(typ, typ_len as usize));
+    write_word(&mut stream, tydesc_len as usize);
+    stream.write(slice::from_raw_parts(tydesc, tydesc_len as usize));
+    write_word(&mut stream, n_words as usize);
+    stream.write(slice::from_raw_parts(
+        desc,
+        n_words as usize * size_of::<u32>(),
+    ));
+    write_word(&mut stream, hash_words);
+    write_words(&mut stream, hash_lists, hash_words);
+    write_word(&mut stream, objects.len);
+
+    // Copy the objects, with object numbers for pointers
+    for n in 0..objects.len {
+        let entry = objects.get(n);
+        let obj = entry.obj;
+        (*obj).tag = entry.tag;
+        let size = object_size(obj as usize).0;
+        write_word(&mut stream, size);
+
+        let words = obj as *const usize;
+        let mut pos = 0;
+        if entry.tag == TAG_OBJECT {
+            let hash_list = hash_cache.find(hash_lists, obj as *mut Object).unwrap();
+            write_words(&mut stream, words, 2);
+            write_word(&mut stream, hash_list);
+            pos = 3;
+        }
+        visit_pointer_fields(obj, |field| {
+            let field_pos = (field as usize - words as usize) / WORD_SIZE;
+            write_words(&mut stream, words.add(pos), field_pos - pos);
+            let field = *field;
+            if field.is_tagged_scalar() {
+                write_word(&mut stream, field.0);
+            } else {
+                let number = if field.as_obj() == obj {
+                    n
+                } else {
+                    field.tag() - MARK_BASE
+                };
+                write_word(&mut stream, (number << 1) | 1);
+            }
+            pos = field_pos + 1;
+        });
+        write_words(&mut stream, words.add(pos), size - pos);
+
+        (*obj).tag = MARK_BASE + n;
+    }
+
+    stream.flush();
+    objects.restore_tags();
+    if stream.written() != len {
+        rts_trap_with(
+            ErrorCode::Internal,
+            "graph_copy_stabilize: stable memory not filled",
+        );
+    }
+}
+
+/// Reads `len` bytes of the buffer into the scratch space
+unsafe fn read_scratch(buf: &mut StableBuf, len: usize) -> *mut u8 {
+    let dst = scratch_alloc(Bytes(len));
+    buf.read(dst, len);
+    dst
+}
+
+/// Restores the stable variables from their graph copy, when stable memory holds one, see the
+/// module documentation. `typ` is the fingerprint of the new stable type, `hash_lists` its hash
+/// lists, and `null` the null value of the new version. Returns the stable variables, or 0 when
+/// they are to be decoded from their Candid encoding.
+#[no_mangle]
+pub unsafe extern "C" fn graph_copy_restore(
+    typ: *const u8,
+    typ_len: u32,
+    hash_lists: *const usize,
+    null: SkewedPtr,
+) -> SkewedPtr {
+    let size = stable_vars_size();
+    if (size as usize) < 2 * WORD_SIZE {
+        return SkewedPtr(0);
+    }
+    let mut reader = StableBuf::new(STABLE_VARS, 0, u64::from(size));
+    if reader.read_word() != MAGIC {
+        // Candid
+        return SkewedPtr(0);
+    }
+    if reader.read_word() != VERSION {
+        rts_trap_with(
+            ErrorCode::StableMemory,
+            "graph_copy_restore: unsupported version",
+        );
+    }
+
+    let old_typ_len = reader.read_word();
+    let old_typ = read_scratch(&mut reader, old_typ_len);
+    let mut same_type =
+        slice::from_raw_parts(old_typ, old_typ_len) == slice::from_raw_parts(typ, typ_len as usize);
+    let tydesc_len = reader.read_word();
+    let tydesc = read_scratch(&mut reader, tydesc_len);
+    let n_words = reader.read_word();
+    let desc = read_scratch(&mut reader, n_words * size_of::<u32>());
+    let hash_words = reader.read_word();
+    let old_hash_lists = read_scratch(&mut reader, hash_words * WORD_SIZE) as *const usize;
+
+    // Hash pointers of the hash lists, of the new stable type if it's the same
+    let n_lists = *old_hash_lists;
+    let old_hash_ptrs = scratch_alloc(Bytes(n_lists * WORD_SIZE)) as *mut usize;
+    let new_hash_ptrs = scratch_alloc(Bytes(n_lists * WORD_SIZE)) as *mut usize;
+    for_hash_lists(old_hash_lists, |n, list, len| {
+        *old_hash_ptrs.add(n) = skew(list as usize).0;
+        match find_hash_list(hash_lists, list, len) {
+            Some(new_n) => {
+                for_hash_lists(hash_lists, |m, new_list, _| {
+                    if m == new_n {
+                        *new_hash_ptrs.add(n) = skew(new_list as usize).0;
+                    }
+                });
+            }
+            None => same_type = false,
+        }
+    });
+    let hash_ptrs = if same_type {
+        new_hash_ptrs
+    } else {
+        old_hash_ptrs
+    };
+
+    // Allocate the objects
+    let n_objects = reader.read_word();
+    if n_objects == 0 {
+        rts_trap_with(ErrorCode::StableMemory, "graph_copy_restore: no objects");
+    }
+    let addrs = scratch_alloc(Bytes(n_objects * WORD_SIZE)) as *mut usize;
+    for n in 0..n_objects {
+        let size = reader.read_word();
+        let tag = reader.read_word();
+        if size == 0 || !copied(tag) {
+            rts_trap_with(
+                ErrorCode::StableMemory,
+                "graph_copy_restore: invalid object",
+            );
+        }
+        if tag == TAG_NULL {
+            // Null is compared by address with the null of the new version
+            read_scratch(&mut reader, (size - 1) * WORD_SIZE);
+            *addrs.add(n) = null.unskew();
+            continue;
+        }
+        let obj = alloc_words(Words(size)).unskew() as *mut usize;
+        *obj = tag;
+        reader.read(obj.add(1) as *mut u8, (size - 1) * WORD_SIZE);
+        if object_size(obj as usize).0 != size {
+            rts_trap_with(
+                ErrorCode::StableMemory,
+                "graph_copy_restore: invalid object size",
+            );
+        }
+        *addrs.add(n) = obj as usize;
+    }
+
+    // Turn object numbers into pointers
+    for n in 0..n_objects {
+        let obj = *addrs.add(n) as *mut Obj;
+        if obj.tag() == TAG_OBJECT {
+            let object = obj as *mut Object;
+            if (*object).hash_ptr >= n_lists {
+                rts_trap_with(
+                    ErrorCode::StableMemory,
+                    "graph_copy_restore: invalid hash list",
+                );
+            }
+            (*object).hash_ptr = *hash_ptrs.add((*object).hash_ptr);
+        }
+        visit_pointer_fields(obj, |field| {
+            if !(*field).is_tagged_scalar() {
+                let number = (*field).0 >> 1;
+                if number >= n_objects {
+                    rts_trap_with(
+                        ErrorCode::StableMemory,
+                        "graph_copy_restore: invalid pointer",
+                    );
+                }
+                *field = skew(*addrs.add(number));
+            }
+        });
+    }
+
+    let x = skew(*addrs);
+    if same_type {
+        return x;
+    }
+    idl_serialize_stable(desc, n_words as u32, x, tydesc, tydesc_len as u32);
+    SkewedPtr(0)
+}
@@ -0,0 +1,288 @@
+//! Segmentation of texts into grapheme clusters (user-perceived characters, see Unicode Standard
+//! Annex #29), e.g. a letter with its combining marks, a Hangul syllable, a flag, or an emoji
+//! sequence joined with ZERO WIDTH JOINER.
+//!
+//! The Grapheme_Cluster_Break, Extended_Pictographic, and Indic_Conjunct_Break properties of the
+//! characters are in `grapheme_tables.rs`, generated by `rts/gen-grapheme-tables.py`. Hangul
+//! syllables are LV or LVT, which is computed.
+//!
+//! Grapheme iterators yield the clusters of a text as slices of the text (see `text_slice`). The
+//! iterator is a quadruple (array):
+//!
+//! 1. A pointer to the text
+//! 2. A character iterator of the text (see `text_iter.rs`), at the character after the first
+//!    character of the next cluster
+//! 3. Position of the next cluster in the text (shifted by two for GC's sake)
+//! 4. The first character of the next cluster (shifted by two), `NO_CHAR` when the iterator is
+//!    done
+
+use crate::alloc::alloc_array;
+use crate::error_code::ErrorCode;
+use crate::grapheme_tables::GRAPHEME_PROPERTIES;
+use crate::rts_trap_with;
+use crate::text::text_slice;
+use crate::text_iter::{set_field, text_iter, text_iter_done, text_iter_next};
+use crate::types::{Bytes, SkewedPtr};
+
+use core::cmp::Ordering;
+
+// Grapheme_Cluster_Break values, in the low four bits of the properties
+const OTHER: u8 = 0;
+const CR: u8 = 1;
+const LF: u8 = 2;
+const CONTROL: u8 = 3;
+const EXTEND: u8 = 4;
+const ZWJ: u8 = 5;
+const REGIONAL_INDICATOR: u8 = 6;
+const PREPEND: u8 = 7;
+const SPACING_MARK: u8 = 8;
+const L: u8 = 9;
+const V: u8 = 10;
+const T: u8 = 11;
+const LV: u8 = 12;
+const LVT: u8 = 13;
+
+const BREAK_MASK: u8 = 0x0F;
+
+// Extended_Pictographic flag
+const EXTENDED_PICTOGRAPHIC: u8 = 0x10;
+
+// Indic_Conjunct_Break values
+const INCB_MASK: u8 = 0x60;
+const INCB_CONSONANT: u8 = 0x20;
+const INCB_EXTEND: u8 = 0x40;
+const INCB_LINKER: u8 = 0x60;
+
+// Hangul syllables, see section 3.12 of the Unicode Standard
+const S_BASE: u32 = 0xAC00;
+const S_COUNT: u32 = 11172;
+const T_COUNT: u32 = 28;
+
+/// Properties of the character, encoded as in `GRAPHEME_PROPERTIES`
+fn properties(c: u32) -> u8 {
+    if (S_BASE..S_BASE + S_COUNT).contains(&c) {
+        return if (c - S_BASE) % T_COUNT == 0 { LV } else { LVT };
+    }
+
+    match GRAPHEME_PROPERTIES.binary_search_by(|&(first, last, _)| {
+        if last < c {
+            Ordering::Less
+        } else if first > c {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }) {
+        Ok(idx) => GRAPHEME_PROPERTIES[idx].2,
+        Err(_) => OTHER,
+    }
+}
+
+/// State for rule GB11, emoji sequences
+#[derive(Clone, Copy, PartialEq)]
+enum EmojiState {
+    None,
+    /// After an Extended_Pictographic character and Extend characters
+    Pictographic,
+    /// After an Extended_Pictographic character, Extend characters, and a ZWJ
+    PictographicZwj,
+}
+
+/// State for rule GB9c, Indic conjuncts
+#[derive(Clone, Copy, PartialEq)]
+enum ConjunctState {
+    None,
+    /// After a consonant and InCB=Extend characters
+    Consonant,
+    /// After a consonant and InCB=Extend and InCB=Linker characters, with at least one linker
+    Linked,
+}
+
+/// Finds the grapheme cluster boundaries in a sequence of characters
+pub(crate) struct Segmenter {
+    /// Properties of the previous character
+    prev: u8,
+    /// Number of regional indicators right before the current position
+    n_regional_indicators: usize,
+    emoji: EmojiState,
+    conjunct: ConjunctState,
+}
+
+impl Segmenter {
+    /// A segmenter after the first character of a text
+    pub(crate) fn new(first: u32) -> Self {
+        let mut segmenter = Segmenter {
+            prev: OTHER,
+            n_regional_indicators: 0,
+            emoji: EmojiState::None,
+            conjunct: ConjunctState::None,
+        };
+        segmenter.advance(properties(first));
+        segmenter
+    }
+
+    /// Returns whether there's a cluster boundary before the character, and advances past the
+    /// character
+    pub(crate) fn is_break(&mut self, next: u32) -> bool {
+        let next = properties(next);
+        let is_break = self.is_break_before(next);
+        self.advance(next);
+        is_break
+    }
+
+    fn is_break_before(&self, next: u8) -> bool {
+        let prev_break = self.prev & BREAK_MASK;
+        let next_break = next & BREAK_MASK;
+
+        if prev_break == CR && next_break == LF {
+            // GB3
+            false
+        } else if matches!(prev_break, CR | LF | CONTROL) || matches!(next_break, CR | LF | CONTROL)
+        {
+            // GB4, GB5
+            true
+        } else if prev_break == L && matches!(next_break, L | V | LV | LVT) {
+            // GB6
+            false
+        } else if matches!(prev_break, LV | V) && matches!(next_break, V | T) {
+            // GB7
+            false
+        } else if matches!(prev_break, LVT | T) && next_break == T {
+            // GB8
+            false
+        } else if matches!(next_break, EXTEND | ZWJ | SPACING_MARK) {
+            // GB9, GB9a
+            false
+        } else if prev_break == PREPEND {
+            // GB9b
+            false
+        } else if self.conjunct == ConjunctState::Linked && next & INCB_MASK == INCB_CONSONANT {
+            // GB9c
+            false
+        } else if self.emoji == EmojiState::PictographicZwj && next & EXTENDED_PICTOGRAPHIC != 0 {
+            // GB11
+            false
+        } else if prev_break == REGIONAL_INDICATOR && next_break == REGIONAL_INDICATOR {
+            // GB12, GB13: break between pairs of regional indicators
+            self.n_regional_indicators % 2 == 0
+        } else {
+            // GB999
+            true
+        }
+    }
+
+    fn advance(&mut self, next: u8) {
+        let next_break = next & BREAK_MASK;
+
+        self.n_regional_indicators = if next_break == REGIONAL_INDICATOR {
+            self.n_regional_indicators + 1
+        } else {
+            0
+        };
+
+        self.emoji = if next & EXTENDED_PICTOGRAPHIC != 0
+            || (self.emoji == EmojiState::Pictographic && next_break == EXTEND)
+        {
+            EmojiState::Pictographic
+        } else if self.emoji == EmojiState::Pictographic && next_break == ZWJ {
+            EmojiState::PictographicZwj
+        } else {
+            EmojiState::None
+        };
+
+        self.conjunct = match next & INCB_MASK {
+            INCB_CONSONANT => ConjunctState::Consonant,
+            INCB_LINKER if self.conjunct != ConjunctState::None => ConjunctState::Linked,
+            INCB_EXTEND => self.conjunct,
+            _ => ConjunctState::None,
+        };
+
+        self.prev = next;
+    }
+}
+
+const GRAPHEMES_TEXT_IDX: usize = 0;
+const GRAPHEMES_CHARS_IDX: usize = 1;
+const GRAPHEMES_POS_IDX: usize = 2;
+const GRAPHEMES_NEXT_IDX: usize = 3;
+
+/// Marks a done iterator, not a character
+const NO_CHAR: SkewedPtr = SkewedPtr(0x11_0000 << 2);
+
+/// The next character of the character iterator (shifted by two), `NO_CHAR` at the end
+unsafe fn next_char(chars: SkewedPtr) -> SkewedPtr {
+    if text_iter_done(chars) != 0 {
+        NO_CHAR
+    } else {
+        SkewedPtr((text_iter_next(chars) as usize) << 2)
+    }
+}
+
+/// Size of the UTF-8 encoding of the character
+unsafe fn char_size(c: u32) -> usize {
+    char::from_u32_unchecked(c).len_utf8()
+}
+
+/// Returns a new grapheme cluster iterator for the text
+#[no_mangle]
+pub unsafe extern "C" fn text_iter_graphemes(text: SkewedPtr) -> SkewedPtr {
+    let chars = text_iter(text);
+    let first = next_char(chars);
+
+    let iter = alloc_array(4);
+    let array = iter.as_array();
+    array.set(GRAPHEMES_TEXT_IDX, text);
+    array.set(GRAPHEMES_CHARS_IDX, chars);
+    array.set(GRAPHEMES_POS_IDX, SkewedPtr(0));
+    array.set(GRAPHEMES_NEXT_IDX, first);
+    iter
+}
+
+/// Returns whether the grapheme cluster iterator is finished
+#[no_mangle]
+pub unsafe extern "C" fn text_iter_graphemes_done(iter: SkewedPtr) -> u32 {
+    if iter.as_array().get(GRAPHEMES_NEXT_IDX) == NO_CHAR {
+        1
+    } else {
+        0
+    }
+}
+
+/// Returns the next grapheme cluster of the text, advances the iterator
+#[no_mangle]
+pub unsafe extern "C" fn text_iter_graphemes_next(iter: SkewedPtr) -> SkewedPtr {
+    let array = iter.as_array();
+    let first = array.get(GRAPHEMES_NEXT_IDX);
+
+    if first == NO_CHAR {
+        // Caller should check with text_iter_graphemes_done
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "text_iter_graphemes_next: Iter already done",
+        );
+    }
+
+    let chars = array.get(GRAPHEMES_CHARS_IDX);
+    let pos = array.get(GRAPHEMES_POS_IDX).0 >> 2;
+
+    let first = (first.0 >> 2) as u32;
+    let mut segmenter = Segmenter::new(first);
+    let mut len = char_size(first);
+
+    let next = loop {
+        let next = next_char(chars);
+        if next == NO_CHAR {
+            break next;
+        }
+        let c = (next.0 >> 2) as u32;
+        if segmenter.is_break(c) {
+            break next;
+        }
+        len += char_size(c);
+    };
+
+    set_field(array, GRAPHEMES_POS_IDX, SkewedPtr((pos + len) << 2));
+    set_field(array, GRAPHEMES_NEXT_IDX, next);
+
+    text_slice(array.get(GRAPHEMES_TEXT_IDX), Bytes(pos), Bytes(len))
+}
@@ -0,0 +1,203 @@
+//! Heap census, for finding out which kind of object is leaking.
+//!
+//! A census counts the objects in the dynamic heap, and their total size, per tag (see
+//! `types.rs`). `heap_checkpoint` takes a census and records it, `heap_diff` takes a new one and
+//! returns the difference to the recorded one.
+//!
+//! The census counts all objects in the heap, including garbage that was not collected yet, the
+//! GC-internal objects (mark bitmap etc.) as blobs, and the free chunks (see `alloc/free_list.rs`).
+//! To compare live data, force a collection (`gc_force`) in the messages before taking the census.
+//! The zero-filled slop after a shrunk blob and redzones (see `alloc/redzones.rs`) are not counted.
+//!
+//! `heap_live_census` only counts the objects reachable from the roots of the collectors (see
+//! `visit_roots` in `gc.rs`), marking them like the mark-sweep GC. It can be taken in the middle of a message, but as the
+//! stack is not scanned, objects only referenced by local variables of the running message are not
+//! counted. The mark bitmap and stack are blobs at the end of the heap, garbage after the census.
+
+use crate::alloc::alloc_array;
+use crate::bigint::{bigint_neg, bigint_of_word64};
+use crate::gc::mark_bitmap::MarkBitmap;
+use crate::gc::mark_sweep::{mark, mark_roots};
+use crate::gc::word_stack::WordStack;
+use crate::gc::{get_heap_base, HP};
+use crate::text::text_of_str;
+use crate::types::*;
+use crate::visitor::visit_pointer_fields;
+
+/// Tags are below this
+const N_TAGS: usize = TAG_SMALL_TEXT + 1;
+
+/// Number of objects and their total size in bytes, per tag
+struct Census {
+    objects: [usize; N_TAGS],
+    bytes: [usize; N_TAGS],
+}
+
+impl Census {
+    const fn new() -> Self {
+        Census {
+            objects: [0; N_TAGS],
+            bytes: [0; N_TAGS],
+        }
+    }
+}
+
+/// The census taken by the last `heap_checkpoint`, empty before the first one
+static mut CHECKPOINT: Census = Census::new();
+
+unsafe fn take_census() -> Census {
+    let mut census = Census::new();
+
+    let heap_end = HP;
+    let mut p = get_heap_base();
+    while p < heap_end {
+        let tag = (p as *mut Obj).tag();
+        let size = object_size(p).to_bytes().0;
+        p += size;
+
+        if tag == 0 || tag == TAG_REDZONE {
+            continue;
+        }
+
+        census.objects[tag] += 1;
+        census.bytes[tag] += size;
+    }
+
+    census
+}
+
+unsafe fn take_live_census() -> Census {
+    let mut census = Census::new();
+
+    let heap_base = get_heap_base();
+    let heap_end = HP;
+
+    let mut bitmap = MarkBitmap::new(heap_base, heap_end);
+    let mut stack = WordStack::new();
+
+    mark_roots(&mut bitmap, &mut stack, heap_base, heap_end);
+
+    while let Option::Some(obj) = stack.pop() {
+        let tag = (obj as *mut Obj).tag();
+        census.objects[tag] += 1;
+        census.bytes[tag] += object_size(obj).to_bytes().0;
+
+        visit_pointer_fields(obj as *mut Obj, |field| {
+            mark(&mut bitmap, &mut stack, heap_base, heap_end, *field)
+        });
+    }
+
+    census
+}
+
+fn tag_name(tag: Tag) -> &'static str {
+    match tag {
+        TAG_OBJECT => "Object",
+        TAG_OBJ_IND => "ObjInd",
+        TAG_ARRAY => "Array",
+        TAG_BITS64 => "Bits64",
+        TAG_MUTBOX => "MutBox",
+        TAG_CLOSURE => "Closure",
+        TAG_SOME => "Some",
+        TAG_VARIANT => "Variant",
+        TAG_BLOB => "Blob",
+        TAG_FWD_PTR => "FwdPtr",
+        TAG_BITS32 => "Bits32",
+        TAG_BIGINT => "BigInt",
+        TAG_CONCAT => "Concat",
+        TAG_NULL => "Null",
+        TAG_WEAK_REF => "WeakRef",
+        TAG_FREE_CHUNK => "FreeChunk",
+        TAG_SLICE => "Slice",
+        TAG_SMALL_TEXT => "SmallText",
+        _ => "Unknown",
+    }
+}
+
+/// An `Int` in the compact representation of the generated code: tagged scalar when it fits in
+/// 31 bits (signed), boxed otherwise
+unsafe fn int_of_diff(new: usize, old: usize) -> SkewedPtr {
+    if new >= old {
+        let n = new - old;
+        if n < 1 << 30 {
+            SkewedPtr(n << 1)
+        } else {
+            bigint_of_word64(n as u64)
+        }
+    } else {
+        let n = old - new;
+        if n <= 1 << 30 {
+            SkewedPtr((n << 1).wrapping_neg())
+        } else {
+            bigint_neg(bigint_of_word64(n as u64))
+        }
+    }
+}
+
+/// Returns an array of `(tag name, objects, bytes)` tuples for the tags for which `include`
+/// returns true, with the given numbers of objects and bytes
+unsafe fn census_array<I, O, B>(include: I, objects: O, bytes: B) -> SkewedPtr
+where
+    I: Fn(Tag) -> bool,
+    O: Fn(Tag) -> SkewedPtr,
+    B: Fn(Tag) -> SkewedPtr,
+{
+    let n_tags = (0..N_TAGS).filter(|tag| include(*tag)).count();
+    let result = alloc_array(n_tags);
+
+    let mut idx = 0;
+    for tag in 0..N_TAGS {
+        if !include(tag) {
+            continue;
+        }
+
+        let tuple = alloc_array(3);
+        let tuple_array = tuple.as_array();
+        tuple_array.set(0, text_of_str(tag_name(tag)));
+        tuple_array.set(1, objects(tag));
+        tuple_array.set(2, bytes(tag));
+
+        result.as_array().set(idx, tuple);
+        idx += 1;
+    }
+
+    result
+}
+
+/// Records a census of the heap, for `heap_diff`
+#[no_mangle]
+unsafe extern "C" fn heap_checkpoint() {
+    CHECKPOINT = take_census();
+}
+
+/// Returns an array of `(tag name, objects, bytes)` tuples, with the change in the number of
+/// objects and their total size per tag since the last `heap_checkpoint`. Tags without changes are
+/// not included.
+#[no_mangle]
+unsafe extern "C" fn heap_diff() -> SkewedPtr {
+    // Before allocating the result, so it's not counted
+    let census = take_census();
+
+    census_array(
+        |tag| {
+            census.objects[tag] != CHECKPOINT.objects[tag]
+                || census.bytes[tag] != CHECKPOINT.bytes[tag]
+        },
+        |tag| int_of_diff(census.objects[tag], CHECKPOINT.objects[tag]),
+        |tag| int_of_diff(census.bytes[tag], CHECKPOINT.bytes[tag]),
+    )
+}
+
+/// Returns an array of `(tag name, objects, bytes)` tuples, with the number of live objects and
+/// their total size per tag. Tags without live objects are not included.
+#[no_mangle]
+unsafe extern "C" fn heap_live_census() -> SkewedPtr {
+    // Before allocating the result, so it's not counted
+    let census = take_live_census();
+
+    census_array(
+        |tag| census.objects[tag] != 0,
+        |tag| int_of_diff(census.objects[tag], 0),
+        |tag| int_of_diff(census.bytes[tag], 0),
+    )
+}
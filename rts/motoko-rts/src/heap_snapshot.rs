@@ -0,0 +1,127 @@
+//! Heap snapshots, for offline analysis of the object graph (e.g. to find out what keeps a
+//! canister's heap growing).
+//!
+//! `heap_snapshot` walks the dynamic heap and returns a blob with the objects, their sizes, and
+//! their references. The blob is a sequence of little-endian machine words (32 bits on wasm32, 64
+//! bits on wasm64):
+//!
+//! ```text
+//! magic          0x53484F4D ("MOHS")
+//! version        1
+//! heap_base      beginning of the dynamic heap
+//! heap_end       end of the dynamic heap (the heap pointer before the blob was allocated)
+//! n_roots
+//! n_objects
+//! roots          n_roots words: addresses of the objects referenced by the roots of the
+//!                collectors (see `visit_roots` in `gc.rs`), in the order they are visited
+//! objects        n_objects records, in address order
+//! ```
+//!
+//! An object record:
+//!
+//! ```text
+//! address        address of the object header (not skewed)
+//! tag            object tag, see `types.rs`
+//! size           size of the object in words, including the header
+//! n_refs
+//! refs           n_refs words: addresses of the objects referenced by the object's fields
+//! ```
+//!
+//! Addresses below `heap_base` are static objects, which are not in the snapshot. Fields holding
+//! scalars are not references. The referent of a weak reference is listed as a reference of the
+//! `TAG_WEAK_REF` object, a cleared weak reference has no references.
+//!
+//! The snapshot includes unreachable objects, and the GC-internal objects (mark bitmap etc.) as
+//! blobs. The zero-filled slop after a shrunk blob (tag 0), redzones (see `alloc/redzones.rs`), and
+//! free chunks (see `alloc/free_list.rs`) are not included.
+
+use crate::gc::{alloc_gc_blob, get_heap_base, visit_roots, ALLOCATED, HP};
+use crate::types::*;
+use crate::visitor::visit_all_pointer_fields;
+
+const MAGIC: usize = 0x5348_4F4D;
+
+const VERSION: usize = 1;
+
+/// Returns a blob with a snapshot of the dynamic heap, in the format described above
+#[no_mangle]
+unsafe extern "C" fn heap_snapshot() -> SkewedPtr {
+    let heap_base = get_heap_base();
+    let heap_end = HP;
+
+    // Compute the size first. The blob is allocated at the heap pointer, so it's not in the walked
+    // part of the heap (`alloc_blob` could allocate in a free chunk of the mark-sweep GC).
+    let mut n_words = 0;
+    let (n_roots, n_objects) = write_snapshot(heap_base, heap_end, 0, 0, |_| n_words += 1);
+
+    let blob = alloc_gc_blob(Words(n_words).to_bytes());
+    ALLOCATED += Bytes(object_size(blob as usize).to_bytes().0 as u64);
+    let mut out = blob.payload_addr() as *mut usize;
+    write_snapshot(heap_base, heap_end, n_roots, n_objects, |word| {
+        *out = word;
+        out = out.add(1);
+    });
+
+    skew(blob as usize)
+}
+
+/// Emits the words of the snapshot. `n_roots` and `n_objects` are emitted as given, returns the
+/// actual numbers.
+unsafe fn write_snapshot<F: FnMut(usize)>(
+    heap_base: usize,
+    heap_end: usize,
+    n_roots: usize,
+    n_objects: usize,
+    mut emit: F,
+) -> (usize, usize) {
+    emit(MAGIC);
+    emit(VERSION);
+    emit(heap_base);
+    emit(heap_end);
+    emit(n_roots);
+    emit(n_objects);
+
+    let mut n_roots = 0;
+    let mut emit_root = |ptr: SkewedPtr| {
+        if !ptr.is_tagged_scalar() {
+            emit(ptr.unskew());
+            n_roots += 1;
+        }
+    };
+
+    visit_roots(|loc| emit_root(*loc));
+
+    let mut n_objects = 0;
+    let mut p = heap_base;
+    while p < heap_end {
+        let obj = p as *mut Obj;
+        let size = object_size(p);
+        p += size.to_bytes().0;
+
+        if obj.tag() == 0 || obj.tag() == TAG_REDZONE || obj.tag() == TAG_FREE_CHUNK {
+            continue;
+        }
+
+        n_objects += 1;
+
+        let mut n_refs = 0;
+        visit_all_pointer_fields(obj, |field| {
+            if !(*field).is_tagged_scalar() {
+                n_refs += 1;
+            }
+        });
+
+        emit(obj as usize);
+        emit(obj.tag());
+        emit(size.0);
+        emit(n_refs);
+
+        visit_all_pointer_fields(obj, |field| {
+            if !(*field).is_tagged_scalar() {
+                emit((*field).unskew());
+            }
+        });
+    }
+
+    (n_roots, n_objects)
+}
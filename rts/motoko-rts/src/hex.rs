@@ -0,0 +1,62 @@
+//! Hexadecimal encoding and decoding of blobs.
+//!
+//! `blob_to_hex` encodes with lowercase digits, `blob_of_hex` accepts both cases, and an optional
+//! `0x` prefix (as in Ethereum addresses). Decoding returns 0 instead of trapping when the text is
+//! not valid, so that the generated code can return `null`.
+
+use crate::alloc::alloc_blob;
+use crate::text::{alloc_text, text_str};
+use crate::types::{Bytes, SkewedPtr};
+
+use core::slice;
+
+static HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Encodes the blob, returns a text with two digits per byte
+#[no_mangle]
+pub unsafe extern "C" fn blob_to_hex(b: SkewedPtr) -> SkewedPtr {
+    let blob = b.as_blob();
+    let bytes = slice::from_raw_parts(blob.payload_addr(), blob.len().0);
+
+    let (r, payload) = alloc_text(Bytes(bytes.len() * 2));
+    let digits = slice::from_raw_parts_mut(payload, bytes.len() * 2);
+
+    for (byte, pair) in bytes.iter().zip(digits.chunks_exact_mut(2)) {
+        pair[0] = HEX_DIGITS[usize::from(byte >> 4)];
+        pair[1] = HEX_DIGITS[usize::from(byte & 0xF)];
+    }
+
+    r
+}
+
+fn digit_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes the text, returns a blob, or 0 when the text has an odd number of digits or other
+/// characters
+#[no_mangle]
+pub unsafe extern "C" fn blob_of_hex(t: SkewedPtr) -> SkewedPtr {
+    let mut digits = text_str(t).as_bytes();
+    if digits.starts_with(b"0x") || digits.starts_with(b"0X") {
+        digits = &digits[2..];
+    }
+
+    if digits.len() % 2 != 0 || !digits.iter().all(|&c| digit_value(c).is_some()) {
+        return SkewedPtr(0);
+    }
+
+    let r = alloc_blob(Bytes(digits.len() / 2));
+    let bytes = slice::from_raw_parts_mut(r.as_blob().payload_addr(), digits.len() / 2);
+
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = digit_value(pair[0]).unwrap() << 4 | digit_value(pair[1]).unwrap();
+    }
+
+    r
+}
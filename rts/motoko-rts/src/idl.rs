@@ -1,12 +1,18 @@
 #![allow(non_upper_case_globals)]
 
-use crate::alloc::alloc_blob;
+use crate::alloc::alloc_array;
 use crate::buf::{read_byte, read_word, skip_leb128, Buf};
+use crate::error_code::ErrorCode;
+#[cfg(feature = "gc")]
+use crate::gc::get_total_allocations;
+use crate::idl_plan_cache;
 use crate::leb128::{leb128_decode, sleb128_decode};
+use crate::scratch::scratch_alloc;
 use crate::trap_with_prefix;
-use crate::types::Words;
+use crate::types::{Bytes, SkewedPtr, Words, WORD_SIZE};
 use crate::utf8::utf8_validate;
 use core::cmp::min;
+use core::mem::size_of;
 
 //
 // IDL constants
@@ -43,14 +49,158 @@ const IDL_CON_alias: i32 = 1;
 
 const IDL_PRIM_lowest: i32 = -17;
 
+/// Type opcodes below this are future types, added to Candid after this decoder. Their type
+/// description and values are prefixed by their length, so they can be skipped.
+const IDL_FUTURE_highest: i32 = -25;
+
 pub(crate) unsafe fn idl_trap_with(msg: &str) -> ! {
-    trap_with_prefix("IDL error: ", msg);
+    trap_with_prefix(ErrorCode::IdlDecode, "IDL error: ", msg);
+}
+
+//
+// Limits
+//
+// Decoding traps with a distinct code when the input exceeds a limit, so callers can tell
+// resource exhaustion apart from malformed input (which traps with `idl_trap_with`). A limit of 0
+// is no limit.
+//
+
+/// Nesting depth of records in skipped values
+const IDL_LIMIT_DEPTH: u32 = 1;
+/// Number of vector elements in one message
+const IDL_LIMIT_VALUES: u32 = 2;
+/// Number of elements of one vector of zero-sized values (`null`, `reserved`, records without
+/// fields), which take no space in the input
+const IDL_LIMIT_ZERO_SIZED: u32 = 3;
+
+static mut MAX_DEPTH: u32 = 100;
+static mut MAX_VALUES: u32 = 0;
+static mut MAX_ZERO_SIZED: u32 = 0;
+
+/// Number of vector elements decoded or skipped since the last `parse_idl_header`
+static mut N_VALUES: u32 = 0;
+
+unsafe fn idl_limit_trap(code: u32) -> ! {
+    let msg = match code {
+        IDL_LIMIT_DEPTH => "1: too deeply nested",
+        IDL_LIMIT_VALUES => "2: too many values",
+        IDL_LIMIT_ZERO_SIZED => "3: too many zero-sized vector elements",
+        _ => "unknown limit",
+    };
+    trap_with_prefix(ErrorCode::IdlLimit, "IDL limit error ", msg);
+}
+
+fn exceeds(n: u32, limit: u32) -> bool {
+    limit != 0 && n > limit
+}
+
+#[no_mangle]
+unsafe extern "C" fn idl_set_limits(max_depth: u32, max_values: u32, max_zero_sized: u32) {
+    MAX_DEPTH = max_depth;
+    MAX_VALUES = max_values;
+    MAX_ZERO_SIZED = max_zero_sized;
+}
+
+/// Whether values of type `t` take no space in the input
+unsafe fn is_zero_sized(buf: *mut Buf, typtbl: *mut *mut u8, t: i32) -> bool {
+    if t < 0 {
+        return t == IDL_PRIM_null || t == IDL_PRIM_reserved;
+    }
+    let mut tb = Buf {
+        ptr: *typtbl.add(t as usize),
+        end: (*buf).end,
+    };
+    sleb128_decode(&mut tb) == IDL_CON_record && leb128_decode(&mut tb) == 0
+}
+
+/// Checks the length of a vector with elements of type `t` against the limits, before its
+/// elements are decoded or skipped
+#[no_mangle]
+unsafe extern "C" fn idl_check_vec(buf: *mut Buf, typtbl: *mut *mut u8, t: i32, len: u32) {
+    N_VALUES = N_VALUES.saturating_add(len);
+    if exceeds(N_VALUES, MAX_VALUES) {
+        idl_limit_trap(IDL_LIMIT_VALUES);
+    }
+    if exceeds(len, MAX_ZERO_SIZED) && is_zero_sized(buf, typtbl, t) {
+        idl_limit_trap(IDL_LIMIT_ZERO_SIZED);
+    }
+}
+
+//
+// Statistics
+//
+// With `moc --candid-stats` the generated code counts the values it decodes, and the bytes and
+// heap allocation of each decoding, so canisters can see what decoding the arguments of a method
+// costs. The counts are of the decoding in the current message (including the decoding of
+// replies in callbacks), they stay 0 without the flag.
+//
+
+/// Decoding in the current message, returned by `idl_stats`. The generated code reads the fields
+/// by offset, see `rts_candid_stats` in `compile.ml`.
+#[cfg(feature = "gc")]
+#[repr(C)]
+pub struct IdlStats {
+    pub values: u64,
+    pub bytes: u64,
+    pub allocated: Bytes<u64>,
+}
+
+#[cfg(feature = "gc")]
+static mut STATS: IdlStats = IdlStats {
+    values: 0,
+    bytes: 0,
+    allocated: Bytes(0),
+};
+
+/// Total allocation at the start of the decoding
+#[cfg(feature = "gc")]
+static mut DECODE_START_ALLOCATED: Bytes<u64> = Bytes(0);
+
+/// Called at the start of each message, see `message_alloc_reset`
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn idl_stats_reset() {
+    STATS = IdlStats {
+        values: 0,
+        bytes: 0,
+        allocated: Bytes(0),
+    };
+}
+
+/// Called for every value that's decoded (not skipped)
+#[cfg(feature = "gc")]
+#[no_mangle]
+unsafe extern "C" fn idl_stats_value() {
+    STATS.values += 1;
+}
+
+#[cfg(feature = "gc")]
+#[no_mangle]
+unsafe extern "C" fn idl_stats_begin() {
+    DECODE_START_ALLOCATED = get_total_allocations();
+}
+
+/// Called when `n_bytes` of input are decoded
+#[cfg(feature = "gc")]
+#[no_mangle]
+unsafe extern "C" fn idl_stats_end(n_bytes: u32) {
+    STATS.bytes += u64::from(n_bytes);
+    STATS.allocated += get_total_allocations() - DECODE_START_ALLOCATED;
+}
+
+#[cfg(feature = "gc")]
+#[no_mangle]
+unsafe extern "C" fn idl_stats() -> *const IdlStats {
+    &STATS
 }
 
 unsafe fn is_primitive_type(ty: i32) -> bool {
     ty < 0 && (ty >= IDL_PRIM_lowest || ty == IDL_REF_principal)
 }
 
+fn is_future_type(ty: i32) -> bool {
+    ty <= IDL_FUTURE_highest
+}
+
 unsafe fn check_typearg(ty: i32, n_types: u32) {
     // Arguments to type constructors can be primitive types or type indices
     if !(is_primitive_type(ty) || (ty >= 0 && (ty as u32) < n_types)) {
@@ -71,9 +221,9 @@ unsafe fn parse_fields(buf: *mut Buf, n_types: u32) {
     }
 }
 
-// NB. This function assumes the allocation does not need to survive GC
-unsafe fn alloc(size: Words<u32>) -> *mut u8 {
-    alloc_blob(size.to_bytes()).as_blob().payload_addr()
+// The type table is only used while deserializing, so it's allocated in the scratch space
+unsafe fn alloc(size: Words<usize>) -> *mut u8 {
+    scratch_alloc(size.to_bytes())
 }
 
 /// This function parses the IDL magic header and type description. It
@@ -109,6 +259,7 @@ unsafe extern "C" fn parse_idl_header(
     }
 
     // Create a table for the type description
+    let table_start = (*buf).ptr;
     let n_types = leb128_decode(buf);
 
     // Early sanity check
@@ -119,8 +270,12 @@ unsafe extern "C" fn parse_idl_header(
     // Let the caller know about the table size
     *typtbl_size_out = n_types;
 
+    // The limits and the field plans are per message
+    N_VALUES = 0;
+    PLANS = core::ptr::null_mut();
+
     // Allocate the type table to be passed out
-    let typtbl: *mut *mut u8 = alloc(Words(n_types)) as *mut _;
+    let typtbl: *mut *mut u8 = alloc(Words(n_types as usize)) as *mut _;
 
     // Go through the table
     for i in 0..n_types {
@@ -175,7 +330,7 @@ unsafe extern "C" fn parse_idl_header(
                 let p = (*buf).ptr;
                 buf.advance(len);
                 // Method names must be valid unicode
-                utf8_validate(p as *const _, len);
+                utf8_validate(p as *const _, len as usize);
                 // Method names must be in order
                 if last_p != core::ptr::null_mut() {
                     let cmp = libc::memcmp(
@@ -194,10 +349,12 @@ unsafe extern "C" fn parse_idl_header(
                 let t = sleb128_decode(buf);
                 check_typearg(t, n_types);
             }
-        } else {
-            // Future type
+        } else if is_future_type(ty) {
+            // The type description, which we can't read
             let n = leb128_decode(buf);
             buf.advance(n);
+        } else {
+            idl_trap_with("illegal type table");
         }
     }
 
@@ -235,6 +392,9 @@ unsafe extern "C" fn parse_idl_header(
         }
     }
 
+    // Plans cached for the same type table can be used again
+    idl_plan_cache::set_table(table_start, (*buf).ptr);
+
     // Now read the main types
     *main_types_out = (*buf).ptr;
     for _ in 0..leb128_decode(buf) {
@@ -245,6 +405,27 @@ unsafe extern "C" fn parse_idl_header(
     *typtbl_out = typtbl;
 }
 
+/// Decodes the `len` bytes of a `vec nat8` into a `[Nat8]` array, allocated once the bytes are
+/// known to be in the buffer. Faster than decoding the elements one by one, which dominates the
+/// cost of large byte arrays.
+#[no_mangle]
+unsafe extern "C" fn idl_read_nat8_array(buf: *mut Buf, len: u32) -> SkewedPtr {
+    if ((*buf).end as usize - (*buf).ptr as usize) < len as usize {
+        idl_trap_with("nat8 vector read out of buffer");
+    }
+
+    let bytes = core::slice::from_raw_parts((*buf).ptr, len as usize);
+    let r = alloc_array(len as usize);
+    let elems = core::slice::from_raw_parts_mut(r.as_array().payload_addr(), len as usize);
+    for (elem, &byte) in elems.iter_mut().zip(bytes.iter()) {
+        // The representation of a `Nat8`: in the most significant byte of the word
+        *elem = SkewedPtr((byte as usize) << ((WORD_SIZE - 1) * 8));
+    }
+
+    buf.advance(len);
+    r
+}
+
 // used for opt, bool, references...
 unsafe fn read_byte_tag(buf: *mut Buf) -> u8 {
     let b = read_byte(buf);
@@ -263,7 +444,7 @@ unsafe fn skip_text(buf: *mut Buf) {
     let len = leb128_decode(buf);
     let p = (*buf).ptr;
     buf.advance(len); // advance first; does the bounds check
-    utf8_validate(p as *const _, len);
+    utf8_validate(p as *const _, len as usize);
 }
 
 // Assumes buf is the encoding of type t, and fast-forwards past that
@@ -273,8 +454,8 @@ unsafe fn skip_text(buf: *mut Buf) {
 // do this in a loop (by maintaing a stack of the t arguments)
 #[no_mangle]
 unsafe extern "C" fn skip_any(buf: *mut Buf, typtbl: *mut *mut u8, t: i32, depth: i32) {
-    if depth > 100 {
-        idl_trap_with("skip_any: too deeply nested record");
+    if exceeds(depth as u32, MAX_DEPTH) {
+        idl_limit_trap(IDL_LIMIT_DEPTH);
     }
 
     if t < 0 {
@@ -328,7 +509,9 @@ unsafe extern "C" fn skip_any(buf: *mut Buf, typtbl: *mut *mut u8, t: i32, depth
             }
             IDL_CON_vec => {
                 let it = sleb128_decode(&mut tb);
-                for _ in 0..leb128_decode(buf) {
+                let len = leb128_decode(buf);
+                idl_check_vec(buf, typtbl, it, len);
+                for _ in 0..len {
                     skip_any(buf, typtbl, it, 0);
                 }
             }
@@ -389,80 +572,223 @@ unsafe extern "C" fn skip_any(buf: *mut Buf, typtbl: *mut *mut u8, t: i32, depth
                     buf.advance(4);
                 }
             }
-            _ => {
-                // Future type
+            _ if is_future_type(tc) => {
+                // The length of the data and the number of references, the references are not in
+                // the data so they can't be skipped with it
                 let n_data = leb128_decode(buf);
                 let n_ref = leb128_decode(buf);
-                buf.advance(n_data);
                 if n_ref > 0 {
                     idl_trap_with("skip_any: skipping references");
                 }
+                buf.advance(n_data);
+            }
+            _ => {
+                idl_trap_with("skip_any: unknown type constructor");
             }
         }
     }
 }
 
-/*
-This finds a field in a record.
-
-Preconditions:
-  tb:     points into the type table,
-          into the sequence of tags/types that are the argument of IDL_CON_record,
-          at the tag
-  b:      points into the data buffer, at value corresponding to the field
-          pointed to by tb
-  typtbl: the type table
-  tag:    the desired tag
-  n:      the number of fields left in the data
-
-If the tag exists:
-  return value: 1
-  tb:    points at the type corresponding to the found field
-  b:     points at the value corresponding to the found field
-  n:     the number of fields left after the found field
-
-If the tag does not exist:
-  return value: 0
-  tb:    points at the tag of the first field with a higher tag
-         or at the end of the buffer
-  b:     points at the value corresponding to that field
-         or at the value past the record
-  n:     the number of fields left, including the field pointed to by tb
-*/
-#[no_mangle]
-unsafe extern "C" fn find_field(
-    tb: *mut Buf,
+//
+// Field plans
+//
+// Decoding a record or a variant matches the fields of its type in the input against the fields
+// of the expected type. The match only depends on the two types, so it's computed once per pair of
+// types in a message and used for all values of the pair (like the elements of a
+// `vec record { … }`), instead of reading and comparing the field hashes of the type table for
+// every value. The plans are allocated in the scratch space, and kept for later messages with the
+// same type table in `idl_plan_cache.rs`.
+//
+
+/// An expected field that is not in the input, or an input field that is not expected
+const NO_FIELD: u32 = u32::MAX;
+
+/// Returned by `idl_plan_record_field` for an expected field that is not in the input
+const IDL_FIELD_MISSING: i32 = i32::MAX;
+
+#[repr(C)]
+struct Plan {
+    n_input: u32,
+    n_expected: u32,
+    // Followed by the `n_input` input fields, then the `n_expected` indices of the input fields of
+    // the expected fields (or `NO_FIELD`)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InputField {
+    /// Index of the expected field, or `NO_FIELD`
+    expected: u32,
+    typ: i32,
+}
+
+impl Plan {
+    unsafe fn input(self: *mut Self) -> *mut InputField {
+        self.add(1) as *mut InputField
+    }
+
+    unsafe fn expected(self: *mut Self) -> *mut u32 {
+        self.input().add((*self).n_input as usize) as *mut u32
+    }
+
+    unsafe fn size(self: *mut Self) -> usize {
+        size_of::<Plan>()
+            + size_of::<InputField>() * (*self).n_input as usize
+            + size_of::<u32>() * (*self).n_expected as usize
+    }
+}
+
+struct CachedPlan {
+    t: i32,
+    hashes: *const u32,
+    n_hashes: u32,
+    plan: *mut Plan,
+    next: *mut CachedPlan,
+}
+
+/// The plans computed since the last `parse_idl_header`, as the type table indices are only valid
+/// for one type table
+static mut PLANS: *mut CachedPlan = core::ptr::null_mut();
+
+unsafe fn compute_plan(
     buf: *mut Buf,
     typtbl: *mut *mut u8,
-    tag: u32,
-    n: *mut u8,
-) -> u32 {
-    while *n > 0 {
-        let last_p = (*tb).ptr;
-        let this_tag = leb128_decode(tb);
-        if this_tag < tag {
-            let it = sleb128_decode(tb);
-            skip_any(buf, typtbl, it, 0);
-            *n -= 1;
-        } else if tag == this_tag {
-            *n -= 1;
-            return 1;
+    t: i32,
+    hashes: *const u32,
+    n_hashes: u32,
+) -> *mut Plan {
+    let mut tb = Buf {
+        ptr: *typtbl.add(t as usize),
+        end: (*buf).end,
+    };
+    sleb128_decode(&mut tb); // record or variant, checked by the caller
+    let n_input = leb128_decode(&mut tb);
+
+    let size = size_of::<Plan>()
+        + size_of::<InputField>() * n_input as usize
+        + size_of::<u32>() * n_hashes as usize;
+    let plan = scratch_alloc(Bytes(size)) as *mut Plan;
+    (*plan).n_input = n_input;
+    (*plan).n_expected = n_hashes;
+    for i in 0..n_hashes as usize {
+        *plan.expected().add(i) = NO_FIELD;
+    }
+
+    // Both the input fields and the expected fields are sorted by hash
+    let mut i = 0;
+    for j in 0..n_input {
+        let tag = leb128_decode(&mut tb);
+        let typ = sleb128_decode(&mut tb);
+        while i < n_hashes && *hashes.add(i as usize) < tag {
+            i += 1;
+        }
+        let expected = if i < n_hashes && *hashes.add(i as usize) == tag {
+            *plan.expected().add(i as usize) = j;
+            i
         } else {
-            // Rewind reading tag
-            (*tb).ptr = last_p;
-            return 0;
+            NO_FIELD
+        };
+        *plan.input().add(j as usize) = InputField { expected, typ };
+    }
+
+    plan
+}
+
+/// Returns the plan to decode values of the record or variant type `t` of the type table into the
+/// expected type with the given (sorted) field hashes, which must be static
+#[no_mangle]
+unsafe extern "C" fn idl_field_plan(
+    buf: *mut Buf,
+    typtbl: *mut *mut u8,
+    t: i32,
+    hashes: *const u32,
+    n_hashes: u32,
+) -> *mut Plan {
+    let mut cached = PLANS;
+    while !cached.is_null() {
+        if (*cached).t == t && (*cached).hashes == hashes && (*cached).n_hashes == n_hashes {
+            return (*cached).plan;
         }
+        cached = (*cached).next;
     }
 
-    0
+    let mut plan = idl_plan_cache::find_plan(t, hashes, n_hashes) as *mut Plan;
+    if plan.is_null() {
+        plan = compute_plan(buf, typtbl, t, hashes, n_hashes);
+        idl_plan_cache::add_plan(t, hashes, n_hashes, plan as *const u8, plan.size());
+    }
+    let cached = scratch_alloc(Bytes(size_of::<CachedPlan>())) as *mut CachedPlan;
+    *cached = CachedPlan {
+        t,
+        hashes,
+        n_hashes,
+        plan,
+        next: PLANS,
+    };
+    PLANS = cached;
+    plan
 }
 
+/// Skips the input fields from `*pos` up to `end`
+unsafe fn skip_input_fields(
+    plan: *mut Plan,
+    buf: *mut Buf,
+    typtbl: *mut *mut u8,
+    pos: *mut u32,
+    end: u32,
+) {
+    while *pos < end {
+        skip_any(buf, typtbl, (*plan.input().add(*pos as usize)).typ, 0);
+        *pos += 1;
+    }
+}
+
+/// Returns the type of the expected field `i` of a record in the input, after skipping the input
+/// fields before it (from the input field `*pos`, which is advanced past the field), or
+/// `IDL_FIELD_MISSING`
 #[no_mangle]
-unsafe extern "C" fn skip_fields(tb: *mut Buf, buf: *mut Buf, typtbl: *mut *mut u8, n: *mut u8) {
-    while *n > 0 {
-        skip_leb128(tb);
-        let it = sleb128_decode(tb);
-        skip_any(buf, typtbl, it, 0);
-        *n -= 1;
+unsafe extern "C" fn idl_plan_record_field(
+    plan: *mut Plan,
+    buf: *mut Buf,
+    typtbl: *mut *mut u8,
+    pos: *mut u32,
+    i: u32,
+) -> i32 {
+    let j = *plan.expected().add(i as usize);
+    if j == NO_FIELD {
+        return IDL_FIELD_MISSING;
     }
+    skip_input_fields(plan, buf, typtbl, pos, j);
+    *pos = j + 1;
+    (*plan.input().add(j as usize)).typ
+}
+
+/// Skips the remaining input fields of a record, from the input field `*pos`
+#[no_mangle]
+unsafe extern "C" fn idl_plan_skip_fields(
+    plan: *mut Plan,
+    buf: *mut Buf,
+    typtbl: *mut *mut u8,
+    pos: *mut u32,
+) {
+    skip_input_fields(plan, buf, typtbl, pos, (*plan).n_input);
+}
+
+unsafe fn plan_case(plan: *mut Plan, idx: u32) -> InputField {
+    if idx >= (*plan).n_input {
+        idl_trap_with("variant index out of bounds");
+    }
+    *plan.input().add(idx as usize)
+}
+
+/// Returns the index of the expected case of the input case `idx` of a variant, or `NO_FIELD`
+#[no_mangle]
+unsafe extern "C" fn idl_plan_variant_case(plan: *mut Plan, idx: u32) -> u32 {
+    plan_case(plan, idx).expected
+}
+
+/// Returns the type of the input case `idx` of a variant
+#[no_mangle]
+unsafe extern "C" fn idl_plan_case_type(plan: *mut Plan, idx: u32) -> i32 {
+    plan_case(plan, idx).typ
 }
@@ -0,0 +1,366 @@
+//! Candid encoding of values, for the serialization code generated by the compiler (see
+//! `Serialization` in `compile.ml`), which writes the type table.
+//!
+//! The encoding is driven by a descriptor of the type of the value, so the compiler does not
+//! generate encoding code for every type. A descriptor is a sequence of 32-bit little-endian
+//! words, made of entries: an opcode followed by its operands. Entries refer to other entries by
+//! their word offset in the descriptor, the value is described by the entry at offset 0:
+//!
+//! - `NULL`, `BOOL`, `NAT`, `INT`, `NAT8`, `NAT16`, `NAT32`, `CHAR`, `NAT64`, `FLOAT`, `TEXT`,
+//!   `BLOB`, `PRINCIPAL`, `FUNC`, `NONE`: no operands (signed and unsigned words of the same size
+//!   have the same encoding)
+//! - `OPT t`, `VEC t`: the entry of the element type
+//! - `TUPLE n t1 … tn`: the entries of the components
+//! - `RECORD n (h1 t1) … (hn tn)`, `VARIANT n (h1 t1) … (hn tn)`: the fields, in the order of the
+//!   Candid encoding (by Candid field hash), each with the hash of its label in the heap
+//!   representation and its entry
+//!
+//! - `MUT t`: a mutable field (a `MutBox` or `ObjInd`) with contents of entry `t`
+//! - `MUT_ARRAY t`: a mutable array, with elements of entry `t`
+//!
+//! Mutable values are in stable variables only. They are encoded as aliases, see Note [mutable
+//! stable values] in `compile.ml`: the size traversal marks every mutable value as seen in its
+//! heap tag, the first occurrence of the value in the encoding replaces the mark with its
+//! position, the other occurrences refer to it. The heap is not used after encoding stable
+//! variables, on upgrades, so the tags are not restored.
+
+use crate::bigint::{bigint_leb128_write, bigint_sleb128_write};
+use crate::error_code::ErrorCode;
+use crate::region::stable_vars_stream;
+use crate::rts_trap_with;
+use crate::stream::{CountStream, SliceStream, Stream};
+use crate::text::{text_size, Leaves};
+use crate::types::*;
+
+use core::slice;
+
+// Opcodes of the descriptor, also see `value_desc` in `compile.ml`
+const OP_NULL: u32 = 0;
+const OP_BOOL: u32 = 1;
+const OP_NAT: u32 = 2;
+const OP_INT: u32 = 3;
+const OP_NAT8: u32 = 4;
+const OP_NAT16: u32 = 5;
+const OP_NAT32: u32 = 6;
+const OP_CHAR: u32 = 7;
+const OP_NAT64: u32 = 8;
+const OP_FLOAT: u32 = 9;
+const OP_TEXT: u32 = 10;
+const OP_BLOB: u32 = 11;
+const OP_PRINCIPAL: u32 = 12;
+const OP_OPT: u32 = 13;
+const OP_VEC: u32 = 14;
+const OP_TUPLE: u32 = 15;
+const OP_RECORD: u32 = 16;
+const OP_VARIANT: u32 = 17;
+const OP_FUNC: u32 = 18;
+const OP_NONE: u32 = 19;
+const OP_MUT: u32 = 20;
+const OP_MUT_ARRAY: u32 = 21;
+
+/// Heap tag of mutable values seen by the size traversal
+const TAG_STABLE_SEEN: Tag = 0xffff_ffff;
+
+/// The heap tag of mutable values written to the encoding is their position in the encoding plus
+/// `SEEN_POSITION`, which is above all other tags
+const SEEN_POSITION: Tag = 0x100;
+
+/// A descriptor, see the module documentation
+#[derive(Clone, Copy)]
+struct Desc {
+    words: *const u8,
+    n_words: u32,
+    /// Whether this is the size traversal, which marks the mutable values
+    sizing: bool,
+}
+
+impl Desc {
+    unsafe fn word(self, offset: u32) -> u32 {
+        if offset >= self.n_words {
+            rts_trap_with(ErrorCode::Internal, "idl_serialize: invalid descriptor");
+        }
+        let word = self.words.add(offset as usize * 4) as *const [u8; 4];
+        u32::from_le_bytes(*word)
+    }
+}
+
+fn write_leb128<S: Stream>(stream: &mut S, mut val: u64) {
+    loop {
+        let byte = (val & 0b0111_1111) as u8;
+        val >>= 7;
+        if val == 0 {
+            stream.write(&[byte]);
+            break;
+        }
+        stream.write(&[byte | 0b1000_0000]);
+    }
+}
+
+fn write_sleb128<S: Stream>(stream: &mut S, mut val: i64) {
+    loop {
+        let byte = (val & 0b0111_1111) as u8;
+        val >>= 7;
+        if (val == 0 && byte & 0b0100_0000 == 0) || (val == -1 && byte & 0b0100_0000 != 0) {
+            stream.write(&[byte]);
+            break;
+        }
+        stream.write(&[byte | 0b1000_0000]);
+    }
+}
+
+/// The value of a tagged scalar
+fn untag(x: SkewedPtr) -> isize {
+    (x.0 as isize) >> 1
+}
+
+unsafe fn write_blob<S: Stream>(stream: &mut S, blob: SkewedPtr) {
+    let blob = blob.as_blob();
+    let len = blob.len().0;
+    write_leb128(stream, len as u64);
+    stream.write(slice::from_raw_parts(blob.payload_addr(), len));
+}
+
+unsafe fn write_text<S: Stream>(stream: &mut S, text: SkewedPtr) {
+    write_leb128(stream, text_size(text).0 as u64);
+    let mut leaves = Leaves::new(text);
+    while let Some(bytes) = leaves.next() {
+        stream.write(bytes);
+    }
+}
+
+/// The field of an object with the given label hash
+unsafe fn object_field(obj: SkewedPtr, hash: u32) -> SkewedPtr {
+    let obj = obj.unskew() as *const Object;
+    let hashes = SkewedPtr((*obj).hash_ptr).unskew() as *const usize;
+    for i in 0..(*obj).size {
+        if *hashes.add(i) == hash as usize {
+            return *obj.payload_addr().add(i);
+        }
+    }
+    rts_trap_with(ErrorCode::Internal, "idl_serialize: object field not found")
+}
+
+/// Writes the mutable value `x`, the `MutBox` or array with the given `tag`, with `write_data`
+/// writing its data on the first occurrence
+unsafe fn write_alias<S: Stream, F: FnOnce(&mut S)>(
+    stream: &mut S,
+    desc: Desc,
+    x: SkewedPtr,
+    tag: Tag,
+    write_data: F,
+) {
+    let obj = x.unskew() as *mut Obj;
+    let seen = (*obj).tag;
+    if desc.sizing {
+        if seen == TAG_STABLE_SEEN {
+            stream.write(&[1, 0, 0, 0, 0]);
+            return;
+        }
+        if seen != tag && !(tag == TAG_MUTBOX && seen == TAG_OBJ_IND) {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "idl_serialize: unexpected tag of mutable value",
+            );
+        }
+        stream.write(&[0]);
+        (*obj).tag = TAG_STABLE_SEEN;
+    } else if seen == TAG_STABLE_SEEN {
+        stream.write(&[0]);
+        (*obj).tag = SEEN_POSITION + stream.written() as Tag;
+    } else {
+        stream.write(&[1]);
+        let offset = (seen - SEEN_POSITION) as i32 - stream.written() as i32;
+        stream.write(&offset.to_le_bytes());
+        return;
+    }
+    // Space for the bookkeeping of the decoder
+    stream.write(&[0; 8]);
+    write_data(stream)
+}
+
+/// Writes the value described by the entry at `ty` of the descriptor
+unsafe fn write_value<S: Stream>(stream: &mut S, desc: Desc, ty: u32, x: SkewedPtr) {
+    match desc.word(ty) {
+        OP_NULL => {}
+        OP_BOOL => stream.write(&[untag(x) as u8]),
+        OP_NAT => {
+            if x.is_tagged_scalar() {
+                write_leb128(stream, untag(x) as u64)
+            } else {
+                bigint_leb128_write(x, stream)
+            }
+        }
+        OP_INT => {
+            if x.is_tagged_scalar() {
+                write_sleb128(stream, untag(x) as i64)
+            } else {
+                bigint_sleb128_write(x, stream)
+            }
+        }
+        OP_NAT8 => stream.write(&[(x.0 >> 24) as u8]),
+        OP_NAT16 => stream.write(&((x.0 >> 16) as u16).to_le_bytes()),
+        OP_NAT32 => {
+            let n = if x.is_tagged_scalar() {
+                untag(x) as u32
+            } else {
+                (*(x.unskew() as *const Bits32)).bits
+            };
+            stream.write(&n.to_le_bytes())
+        }
+        OP_CHAR => stream.write(&((x.0 >> 8) as u32).to_le_bytes()),
+        OP_NAT64 => {
+            let n = if x.is_tagged_scalar() {
+                untag(x) as i64 as u64
+            } else {
+                (*(x.unskew() as *const Bits64)).bits
+            };
+            stream.write(&n.to_le_bytes())
+        }
+        OP_FLOAT => stream.write(&(*(x.unskew() as *const Bits64)).bits.to_le_bytes()),
+        OP_TEXT => write_text(stream, x),
+        OP_BLOB => write_blob(stream, x),
+        OP_PRINCIPAL => {
+            stream.write(&[1]);
+            write_blob(stream, x)
+        }
+        OP_OPT => {
+            if !x.is_tagged_scalar() && x.tag() == TAG_NULL {
+                stream.write(&[0])
+            } else {
+                stream.write(&[1]);
+                let payload = if !x.is_tagged_scalar() && x.tag() == TAG_SOME {
+                    (*(x.unskew() as *const Some)).field
+                } else {
+                    x
+                };
+                write_value(stream, desc, desc.word(ty + 1), payload)
+            }
+        }
+        OP_VEC => {
+            let array = x.as_array();
+            let elem_ty = desc.word(ty + 1);
+            write_leb128(stream, array.len() as u64);
+            for i in 0..array.len() {
+                write_value(stream, desc, elem_ty, array.get(i))
+            }
+        }
+        OP_TUPLE => {
+            let array = x.as_array();
+            for i in 0..desc.word(ty + 1) {
+                write_value(stream, desc, desc.word(ty + 2 + i), array.get(i as usize))
+            }
+        }
+        OP_RECORD => {
+            for i in 0..desc.word(ty + 1) {
+                let field = object_field(x, desc.word(ty + 2 + 2 * i));
+                write_value(stream, desc, desc.word(ty + 3 + 2 * i), field)
+            }
+        }
+        OP_VARIANT => {
+            let variant = x.unskew() as *const Variant;
+            let tag = (*variant).tag;
+            for i in 0..desc.word(ty + 1) {
+                if desc.word(ty + 2 + 2 * i) as usize == tag {
+                    write_leb128(stream, u64::from(i));
+                    return write_value(stream, desc, desc.word(ty + 3 + 2 * i), (*variant).field);
+                }
+            }
+            rts_trap_with(ErrorCode::Internal, "idl_serialize: unexpected variant")
+        }
+        OP_FUNC => {
+            let array = x.as_array();
+            // The actor (as a principal) and the method name
+            stream.write(&[1, 1]);
+            write_blob(stream, array.get(0));
+            write_text(stream, array.get(1))
+        }
+        OP_NONE => rts_trap_with(ErrorCode::Internal, "idl_serialize: value of type None"),
+        OP_MUT => write_alias(stream, desc, x, TAG_MUTBOX, |stream| {
+            let field = (*(x.unskew() as *const MutBox)).field;
+            write_value(stream, desc, desc.word(ty + 1), field)
+        }),
+        OP_MUT_ARRAY => write_alias(stream, desc, x, TAG_ARRAY, |stream| {
+            // Not `as_array`, the tag is the mark
+            let array = x.unskew() as *mut Array;
+            let elem_ty = desc.word(ty + 1);
+            write_leb128(stream, array.len() as u64);
+            for i in 0..array.len() {
+                write_value(stream, desc, elem_ty, array.get(i))
+            }
+        }),
+        _ => rts_trap_with(ErrorCode::Internal, "idl_serialize: invalid descriptor"),
+    }
+}
+
+/// Size of the Candid encoding of the value, without the type table
+#[no_mangle]
+pub unsafe extern "C" fn idl_serialize_size(desc: *const u8, n_words: u32, x: SkewedPtr) -> u32 {
+    let mut stream = CountStream::new();
+    write_value(
+        &mut stream,
+        Desc {
+            words: desc,
+            n_words,
+            sizing: true,
+        },
+        0,
+        x,
+    );
+    stream.written() as u32
+}
+
+/// Writes the Candid encoding of the value, without the type table, to the buffer, which must be
+/// of the size returned by `idl_serialize_size`
+#[no_mangle]
+pub unsafe extern "C" fn idl_serialize(
+    desc: *const u8,
+    n_words: u32,
+    x: SkewedPtr,
+    buf: *mut u8,
+    len: u32,
+) {
+    let mut stream = SliceStream::new(slice::from_raw_parts_mut(buf, len as usize));
+    write_value(
+        &mut stream,
+        Desc {
+            words: desc,
+            n_words,
+            sizing: false,
+        },
+        0,
+        x,
+    );
+    if stream.written() != len as usize {
+        rts_trap_with(ErrorCode::Internal, "idl_serialize: data buffer not filled");
+    }
+}
+
+/// Writes the Candid encoding of the stable variables `x`, after the type table of `tydesc_len`
+/// bytes at `tydesc`, to stable memory (see `region.rs`). The encoding is streamed to stable
+/// memory in chunks, so it does not take space in the heap or the scratch space.
+#[no_mangle]
+pub unsafe extern "C" fn idl_serialize_stable(
+    desc: *const u8,
+    n_words: u32,
+    x: SkewedPtr,
+    tydesc: *const u8,
+    tydesc_len: u32,
+) {
+    let mut desc = Desc {
+        words: desc,
+        n_words,
+        sizing: true,
+    };
+    let mut count = CountStream::new();
+    write_value(&mut count, desc, 0, x);
+    let len = tydesc_len as usize + count.written();
+
+    let mut stream = stable_vars_stream(len as u32, false);
+    stream.write(slice::from_raw_parts(tydesc, tydesc_len as usize));
+    desc.sizing = false;
+    write_value(&mut stream, desc, 0, x);
+    stream.flush();
+    if stream.written() != len {
+        rts_trap_with(ErrorCode::Internal, "idl_serialize: data buffer not filled");
+    }
+}
@@ -0,0 +1,172 @@
+//! Field plans (see `idl.rs`) kept across messages.
+//!
+//! Decoding does not check the input type against the expected type separately, the values are
+//! coerced while decoding, but matching the fields of the records and variants of the type table
+//! against the expected fields is the part that only depends on the types. Callers usually send
+//! the same type table in every message, so the plans are kept for the type tables of the last
+//! `N_TABLES` messages, and a message with one of these tables reuses the plans.
+//!
+//! The cache is a heap-allocated Motoko array, a GC root like the closure table, allocated when the
+//! first plan is cached. Slot `2 * i` is a blob with the bytes of a type table (or `FREE`), slot
+//! `2 * i + 1` a blob with its plans, each after an `Entry`. The tables are compared by their
+//! bytes, the CRC-32C of the bytes is kept in the blob to compare most tables without reading them.
+//! The blobs are not changed after they are allocated: a plan is added by copying the plans to a
+//! new blob, as the GC copies objects in the evacuation phase. The GC only runs between messages,
+//! so the plans in the heap can be used by address in a message.
+//!
+//! The plans refer to the field hashes in the static data of the expected types, so they are only
+//! valid for one canister module. The heap is not kept by upgrades, which drop the cache with it.
+
+use crate::alloc::{alloc_array, alloc_blob, write_barrier};
+use crate::crc::{Crc, CRC32C};
+use crate::types::{Bytes, SkewedPtr};
+
+use core::mem::size_of;
+
+/// Number of type tables with cached plans. A table that is not cached replaces the one cached
+/// first.
+const N_TABLES: usize = 16;
+
+const FREE: SkewedPtr = SkewedPtr(0);
+
+const NO_TABLE: usize = usize::MAX;
+
+// Skewed pointer to the `Array` object, a GC root like the closure table
+static mut CACHE: SkewedPtr = SkewedPtr(0);
+
+/// Slot of the table to be replaced next
+static mut NEXT_SLOT: usize = 0;
+
+/// The type table of the current message, set by `set_table`
+static mut TABLE_START: *const u8 = core::ptr::null();
+static mut TABLE_LEN: usize = 0;
+static mut TABLE_CRC: u32 = 0;
+
+/// Slot of the current type table, or `NO_TABLE` when it's not cached (yet)
+static mut TABLE_SLOT: usize = NO_TABLE;
+
+/// Header of a cached plan, followed by the plan
+#[repr(C)]
+struct Entry {
+    t: i32,
+    n_hashes: u32,
+    hashes: *const u32,
+    /// Size of the plan, rounded up to a word
+    size: usize,
+}
+
+unsafe fn get_slot(idx: usize) -> SkewedPtr {
+    CACHE.as_array().get(idx)
+}
+
+unsafe fn set_slot(idx: usize, value: SkewedPtr) {
+    let array = CACHE.as_array();
+    write_barrier(array.payload_addr().add(idx));
+    array.set(idx, value);
+}
+
+unsafe fn blob_bytes(blob: SkewedPtr) -> (*mut u8, usize) {
+    let blob = blob.as_blob();
+    (blob.payload_addr(), blob.len().0)
+}
+
+/// Sets the type table of the message, the bytes from `start` to `end`, which are read until the
+/// next call
+pub(crate) unsafe fn set_table(start: *const u8, end: *const u8) {
+    TABLE_START = start;
+    TABLE_LEN = end as usize - start as usize;
+    let mut crc = Crc::new(&CRC32C);
+    crc.update(core::slice::from_raw_parts(start, TABLE_LEN));
+    TABLE_CRC = crc.finish();
+
+    TABLE_SLOT = NO_TABLE;
+    if CACHE.0 == 0 {
+        return;
+    }
+    for i in 0..N_TABLES {
+        let table = get_slot(2 * i);
+        if table == FREE {
+            continue;
+        }
+        let (bytes, len) = blob_bytes(table);
+        if len == size_of::<u32>() + TABLE_LEN
+            && *(bytes as *const u32) == TABLE_CRC
+            && libc::memcmp(
+                bytes.add(size_of::<u32>()) as *const libc::c_void,
+                TABLE_START as *const libc::c_void,
+                TABLE_LEN,
+            ) == 0
+        {
+            TABLE_SLOT = i;
+            return;
+        }
+    }
+}
+
+/// The cached plan of the record or variant type `t` of the current type table for the expected
+/// field hashes, or null
+pub(crate) unsafe fn find_plan(t: i32, hashes: *const u32, n_hashes: u32) -> *mut u8 {
+    if TABLE_SLOT == NO_TABLE {
+        return core::ptr::null_mut();
+    }
+    let (mut entry, len) = blob_bytes(get_slot(2 * TABLE_SLOT + 1));
+    let end = entry.add(len);
+    while entry < end {
+        let header = entry as *const Entry;
+        let plan = entry.add(size_of::<Entry>());
+        if (*header).t == t && (*header).hashes == hashes && (*header).n_hashes == n_hashes {
+            return plan;
+        }
+        entry = plan.add((*header).size);
+    }
+    core::ptr::null_mut()
+}
+
+/// Caches the plan, of `size` bytes, of the record or variant type `t` of the current type table
+/// for the expected field hashes
+pub(crate) unsafe fn add_plan(
+    t: i32,
+    hashes: *const u32,
+    n_hashes: u32,
+    plan: *const u8,
+    size: usize,
+) {
+    if CACHE.0 == 0 {
+        CACHE = alloc_array(2 * N_TABLES);
+        for i in 0..2 * N_TABLES {
+            CACHE.as_array().set(i, FREE);
+        }
+    }
+
+    if TABLE_SLOT == NO_TABLE {
+        TABLE_SLOT = NEXT_SLOT;
+        NEXT_SLOT = (NEXT_SLOT + 1) % N_TABLES;
+
+        let table = alloc_blob(Bytes(size_of::<u32>() + TABLE_LEN));
+        let (bytes, _) = blob_bytes(table);
+        *(bytes as *mut u32) = TABLE_CRC;
+        core::ptr::copy_nonoverlapping(TABLE_START, bytes.add(size_of::<u32>()), TABLE_LEN);
+        set_slot(2 * TABLE_SLOT, table);
+        set_slot(2 * TABLE_SLOT + 1, alloc_blob(Bytes(0)));
+    }
+
+    let rounded = (size + size_of::<usize>() - 1) / size_of::<usize>() * size_of::<usize>();
+    let (old, old_len) = blob_bytes(get_slot(2 * TABLE_SLOT + 1));
+    let plans = alloc_blob(Bytes(old_len + size_of::<Entry>() + rounded));
+    let (bytes, _) = blob_bytes(plans);
+    core::ptr::copy_nonoverlapping(old, bytes, old_len);
+    let entry = bytes.add(old_len);
+    *(entry as *mut Entry) = Entry {
+        t,
+        n_hashes,
+        hashes,
+        size: rounded,
+    };
+    core::ptr::copy_nonoverlapping(plan, entry.add(size_of::<Entry>()), size);
+    set_slot(2 * TABLE_SLOT + 1, plans);
+}
+
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn idl_plan_cache_loc() -> *mut SkewedPtr {
+    &mut CACHE
+}
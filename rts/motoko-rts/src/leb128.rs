@@ -1,6 +1,12 @@
 //! LEB1128 encoding. Reference: https://en.wikipedia.org/wiki/LEB128
+//!
+//! Numbers of up to 128 bits (like cycles) are encoded and decoded without big integers, Candid
+//! `nat` and `int` values that don't fit are decoded to big integers.
 
+use crate::bigint::{bigint_leb128_decode, bigint_of_mag128, bigint_sleb128_decode};
 use crate::buf::{read_byte, Buf};
+use crate::idl::idl_trap_with;
+use crate::types::SkewedPtr;
 
 #[no_mangle]
 pub unsafe extern "C" fn leb128_encode(mut val: u32, mut buf: *mut u8) {
@@ -33,8 +39,57 @@ pub unsafe extern "C" fn sleb128_encode(mut val: i32, mut buf: *mut u8) {
     }
 }
 
+// Decoding reads the next 8 bytes as one word when the buffer has them, and finds the end of the
+// number and its bits in the word without a loop. Numbers near the end of the buffer are decoded
+// byte by byte.
+
+/// The next 8 bytes as a little-endian word, if the buffer has them
+unsafe fn peek_word(buf: *const Buf) -> Option<u64> {
+    if (*buf).end as usize - (*buf).ptr as usize >= 8 {
+        Some(u64::from_le_bytes(*((*buf).ptr as *const [u8; 8])))
+    } else {
+        None
+    }
+}
+
+/// The number of bytes of the (s)leb128 number at the beginning of the word, or 0 when it's longer
+/// than the word
+fn word_encoded_len(word: u64) -> u32 {
+    let last_bytes = !word & 0x8080_8080_8080_8080;
+    if last_bytes == 0 {
+        0
+    } else {
+        last_bytes.trailing_zeros() / 8 + 1
+    }
+}
+
+/// The 7-bit groups of the first `len` (1 to 8) bytes of the word, i.e. the bits of the number
+fn word_groups(word: u64, len: u32) -> u64 {
+    let mut x = word & (!0 >> (64 - 8 * len)) & 0x7f7f_7f7f_7f7f_7f7f;
+    x = (x & 0x007f_007f_007f_007f) | (x & 0x7f00_7f00_7f00_7f00) >> 1;
+    x = (x & 0x0000_3fff_0000_3fff) | (x & 0x3fff_0000_3fff_0000) >> 2;
+    (x & 0x0000_0000_0fff_ffff) | (x & 0x0fff_ffff_0000_0000) >> 4
+}
+
+/// The groups of the first `len` bytes of the word, sign-extended from their highest bit
+fn word_groups_signed(word: u64, len: u32) -> i64 {
+    let unused = 64 - 7 * len;
+    ((word_groups(word, len) << unused) as i64) >> unused
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn leb128_decode(buf: *mut Buf) -> u32 {
+    if let Some(word) = peek_word(buf) {
+        let len = word_encoded_len(word);
+        if len != 0 && len <= 5 {
+            let result = word_groups(word, len);
+            if result >> 32 == 0 {
+                (*buf).ptr = (*buf).ptr.add(len as usize);
+                return result as u32;
+            }
+        }
+    }
+
     let mut result = 0;
     let mut shift = 0;
 
@@ -46,7 +101,7 @@ pub unsafe extern "C" fn leb128_decode(buf: *mut Buf) -> u32 {
         // The 5th byte needs to be the last, and it must contribute at most 4 bits, otherwise we
         // have an overflow
         if shift == 28 && (byte & 0b1111_0000) != 0 {
-            panic!("leb128_decode: overflow");
+            idl_trap_with("leb128 overflow");
         }
 
         if byte & 0b1000_0000 == 0 {
@@ -61,21 +116,35 @@ pub unsafe extern "C" fn leb128_decode(buf: *mut Buf) -> u32 {
 
 #[no_mangle]
 pub unsafe extern "C" fn sleb128_decode(buf: *mut Buf) -> i32 {
+    if let Some(word) = peek_word(buf) {
+        let len = word_encoded_len(word);
+        if len != 0 && len <= 5 {
+            let result = word_groups_signed(word, len);
+            if result == i64::from(result as i32) {
+                (*buf).ptr = (*buf).ptr.add(len as usize);
+                return result as i32;
+            }
+        }
+    }
+
     let mut result = 0;
     let mut shift = 0;
 
     let last_byte = loop {
         let byte = read_byte(buf);
 
+        // The 5th byte needs to be the last, and the bits it contributes above the 32nd must be
+        // copies of the sign bit, otherwise we have an overflow
+        if shift == 28 {
+            let high = byte & 0b0111_1000;
+            if byte & 0b1000_0000 != 0 || (high != 0 && high != 0b0111_1000) {
+                idl_trap_with("sleb128 overflow");
+            }
+        }
+
         result |= ((byte & 0b0111_1111) as i32) << shift;
         shift += 7;
 
-        // The 5th byte needs to be the last, and it must contribute at most 4 bits, otherwise we
-        // have an overflow
-        if shift == 28 && (byte & 0b1111_0000 != 0 || byte & 0b1110_0000 != 0) {
-            panic!("sleb128_decode: overflow");
-        }
-
         if byte & 0b1000_0000 == 0 {
             break byte;
         }
@@ -88,3 +157,150 @@ pub unsafe extern "C" fn sleb128_decode(buf: *mut Buf) -> i32 {
 
     result
 }
+
+/// Writes the leb128 encoding of the number, returns the number of bytes written
+pub unsafe fn leb128_encode_u128(mut val: u128, buf: *mut u8) -> usize {
+    let mut n = 0;
+    loop {
+        let byte = (val & 0b0111_1111) as u8;
+        val >>= 7;
+        if val == 0 {
+            *buf.add(n) = byte;
+            return n + 1;
+        }
+        *buf.add(n) = byte | 0b1000_0000;
+        n += 1;
+    }
+}
+
+/// Writes the sleb128 encoding of the number, returns the number of bytes written
+pub unsafe fn sleb128_encode_i128(mut val: i128, buf: *mut u8) -> usize {
+    let mut n = 0;
+    loop {
+        let byte = (val & 0b0111_1111) as u8;
+        val >>= 7;
+        if (val == 0 && byte & 0b0100_0000 == 0) || (val == -1 && byte & 0b0100_0000 != 0) {
+            *buf.add(n) = byte;
+            return n + 1;
+        }
+        *buf.add(n) = byte | 0b1000_0000;
+        n += 1;
+    }
+}
+
+/// Decodes a leb128-encoded number of up to 128 bits. Returns `None` if the number is larger, then
+/// the buffer is not advanced.
+pub unsafe fn leb128_decode_u128(buf: *mut Buf) -> Option<u128> {
+    if let Some(word) = peek_word(buf) {
+        let len = word_encoded_len(word);
+        if len != 0 {
+            (*buf).ptr = (*buf).ptr.add(len as usize);
+            return Some(u128::from(word_groups(word, len)));
+        }
+    }
+
+    let mut tmp = Buf {
+        ptr: (*buf).ptr,
+        end: (*buf).end,
+    };
+    let mut result: u128 = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = read_byte(&mut tmp);
+
+        // The 19th byte needs to be the last, and it must contribute at most 2 bits
+        if shift == 126 && byte & 0b1111_1100 != 0 {
+            return None;
+        }
+
+        result |= u128::from(byte & 0b0111_1111) << shift;
+
+        if byte & 0b1000_0000 == 0 {
+            break;
+        }
+
+        shift += 7;
+    }
+
+    (*buf).ptr = tmp.ptr;
+    Some(result)
+}
+
+/// Decodes a sleb128-encoded number of up to 128 bits. Returns `None` if the number is larger,
+/// then the buffer is not advanced.
+pub unsafe fn sleb128_decode_i128(buf: *mut Buf) -> Option<i128> {
+    if let Some(word) = peek_word(buf) {
+        let len = word_encoded_len(word);
+        if len != 0 {
+            (*buf).ptr = (*buf).ptr.add(len as usize);
+            return Some(i128::from(word_groups_signed(word, len)));
+        }
+    }
+
+    let mut tmp = Buf {
+        ptr: (*buf).ptr,
+        end: (*buf).end,
+    };
+    let mut result: i128 = 0;
+    let mut shift = 0;
+
+    let last_byte = loop {
+        let byte = read_byte(&mut tmp);
+
+        // The 19th byte needs to be the last, and the bits above the 128th must be copies of the
+        // sign bit
+        if shift == 126 {
+            let high = byte & 0b0111_1110;
+            if byte & 0b1000_0000 != 0 || (high != 0 && high != 0b0111_1110) {
+                return None;
+            }
+        }
+
+        result |= i128::from(byte & 0b0111_1111) << shift;
+        shift += 7;
+
+        if byte & 0b1000_0000 == 0 {
+            break byte;
+        }
+    };
+
+    // Sign extend
+    if shift < 128 && last_byte & 0b0100_0000 != 0 {
+        result |= !0 << shift;
+    }
+
+    (*buf).ptr = tmp.ptr;
+    Some(result)
+}
+
+/// The number in the compact representation of `Nat` and `Int`: a tagged scalar if it fits in 31
+/// bits (see `BitTagged` in `compile.ml`), a big integer otherwise
+unsafe fn compact_of_i128(n: i128) -> SkewedPtr {
+    if (-(1 << 30)..1 << 30).contains(&n) {
+        SkewedPtr(((n as isize) << 1) as usize)
+    } else {
+        bigint_of_mag128(n.wrapping_abs() as u128, n < 0)
+    }
+}
+
+/// Decodes a Candid `nat` to a `Nat`. Only numbers of more than 128 bits are decoded to a big
+/// integer from the digits of the encoding.
+#[no_mangle]
+pub unsafe extern "C" fn leb128_decode_nat(buf: *mut Buf) -> SkewedPtr {
+    match leb128_decode_u128(buf) {
+        Some(n) if n < 1 << 30 => compact_of_i128(n as i128),
+        Some(n) => bigint_of_mag128(n, false),
+        None => bigint_leb128_decode(buf),
+    }
+}
+
+/// Decodes a Candid `int` to an `Int`. Only numbers of more than 128 bits are decoded to a big
+/// integer from the digits of the encoding.
+#[no_mangle]
+pub unsafe extern "C" fn sleb128_decode_int(buf: *mut Buf) -> SkewedPtr {
+    match sleb128_decode_i128(buf) {
+        Some(n) => compact_of_i128(n),
+        None => bigint_sleb128_decode(buf),
+    }
+}
@@ -20,35 +20,82 @@ mod gc;
 pub mod debug;
 
 mod alloc;
+pub mod alloc_profile;
+pub mod array;
+pub mod backtrace;
+pub mod base64;
 pub mod bigint;
 mod blob_iter;
 pub mod buf;
-mod char;
+pub mod char;
+mod char_tables;
 pub mod closure_table;
-mod float;
+pub mod compress;
+pub mod crc;
+pub mod error_code;
+pub mod features;
+pub mod finalizers;
+pub mod float;
+pub mod float_parse;
+mod float_tables;
+#[cfg(feature = "gc_stress")]
+pub mod gc_stress;
+pub mod graph_copy;
+pub mod grapheme;
+mod grapheme_tables;
+#[cfg(feature = "gc")]
+mod heap_census;
+#[cfg(feature = "gc")]
+mod heap_snapshot;
+pub mod hex;
 mod idl;
+pub mod idl_encode;
+mod idl_plan_cache;
 pub mod leb128;
 mod mem;
+pub mod nat128;
+pub mod normalize;
+pub mod perf;
+pub mod pin;
 pub mod principal_id;
+pub mod random;
+pub mod region;
+pub mod scratch;
+pub mod sha256;
+pub mod stream;
 pub mod text;
+pub mod text_intern;
 pub mod text_iter;
-#[allow(non_camel_case_types)]
-mod tommath_bindings;
+pub mod text_split;
+pub mod timer;
+#[cfg(feature = "gc")]
+pub mod to_space_arena;
 pub mod types;
+mod unicode_tables;
 pub mod utf8;
+mod visitor;
+pub mod weak_ref;
 
+use error_code::ErrorCode;
 use types::{Bytes, SkewedPtr};
 
 #[no_mangle]
 unsafe extern "C" fn version() -> SkewedPtr {
-    text::text_of_str("0.1")
+    text::text_of_str(features::VERSION)
 }
 
 extern "C" {
-    fn rts_trap(msg: *const u8, len: Bytes<u32>) -> !;
+    fn rts_trap(msg: *const u8, len: Bytes<usize>) -> !;
 }
 
-pub(crate) unsafe fn trap_with_prefix(prefix: &str, msg: &str) -> ! {
+/// Traps with the message, after the tag of the error code (see `error_code.rs`) and the prefix
+pub(crate) unsafe fn trap_with_prefix(code: ErrorCode, prefix: &str, msg: &str) -> ! {
+    let tag = code.tag();
+    trap_with_parts(&[&tag, b" ", prefix.as_bytes(), msg.as_bytes()])
+}
+
+/// Traps with the concatenation of the parts, and the backtrace (see `backtrace.rs`)
+pub(crate) unsafe fn trap_with_parts(parts: &[&[u8]]) -> ! {
     // Rust currently doesn't support stack-allocated dynamically-sized arrays or alloca, so we
     // have a max bound to the message size here.
     //
@@ -60,7 +107,7 @@ pub(crate) unsafe fn trap_with_prefix(prefix: &str, msg: &str) -> ! {
     let mut c_str = [0u8; BUF_LEN];
     let mut b_idx = 0;
 
-    for b in prefix.as_bytes() {
+    for b in parts.iter().flat_map(|part| part.iter()) {
         if b_idx == BUF_LEN {
             break;
         }
@@ -68,19 +115,13 @@ pub(crate) unsafe fn trap_with_prefix(prefix: &str, msg: &str) -> ! {
         b_idx += 1;
     }
 
-    for b in msg.as_bytes() {
-        if b_idx == BUF_LEN {
-            break;
-        }
-        c_str[b_idx] = *b;
-        b_idx += 1;
-    }
+    b_idx += backtrace::write_backtrace(&mut c_str[b_idx..]);
 
-    rts_trap(c_str.as_ptr(), Bytes(b_idx as u32));
+    rts_trap(c_str.as_ptr(), Bytes(b_idx));
 }
 
-pub(crate) unsafe fn rts_trap_with(msg: &str) -> ! {
-    trap_with_prefix("RTS error: ", msg)
+pub(crate) unsafe fn rts_trap_with(code: ErrorCode, msg: &str) -> ! {
+    trap_with_prefix(code, "RTS error: ", msg)
 }
 
 #[cfg(feature = "panic_handler")]
@@ -97,6 +138,6 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
         } else {
             println!(1000, "RTS panic: weird payload");
         }
-        rts_trap_with("RTS panicked");
+        rts_trap_with(ErrorCode::Internal, "RTS panicked");
     }
 }
@@ -1,9 +1,9 @@
 use crate::types::{Bytes, Words};
 
-pub(crate) unsafe fn memcpy_words(to: usize, from: usize, n: Words<u32>) {
-    libc::memcpy(to as *mut _, from as *const _, n.to_bytes().0 as usize);
+pub(crate) unsafe fn memcpy_words(to: usize, from: usize, n: Words<usize>) {
+    libc::memcpy(to as *mut _, from as *const _, n.to_bytes().0);
 }
 
-pub(crate) unsafe fn memcpy_bytes(to: usize, from: usize, n: Bytes<u32>) {
-    libc::memcpy(to as *mut _, from as *const _, n.0 as usize);
+pub(crate) unsafe fn memcpy_bytes(to: usize, from: usize, n: Bytes<usize>) {
+    libc::memcpy(to as *mut _, from as *const _, n.0);
 }
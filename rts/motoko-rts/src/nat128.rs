@@ -0,0 +1,176 @@
+//! Arithmetic on 128-bit naturals (e.g. amounts of cycles), without allocating big integers.
+//!
+//! The numbers are passed as two 64-bit words, the low and the high word, and results are written
+//! to a `Nat128` provided by the caller. The checked operations return 1 when the result is
+//! written, and 0 (leaving the result unchanged) on overflow, underflow, or division by zero, so
+//! the caller decides how to trap.
+//!
+//! The generated code doesn't call these yet: cycles are still `Nat64` there, passed to the 64-bit
+//! system calls (`call_cycles_add` etc.).
+
+use crate::bigint::{bigint_of_mag128, bigint_to_nat128};
+use crate::text::{alloc_text, text_str};
+use crate::types::{Bytes, SkewedPtr};
+
+/// A 128-bit natural as two words, the low word first
+#[repr(C)]
+pub struct Nat128 {
+    pub lo: u64,
+    pub hi: u64,
+}
+
+impl Nat128 {
+    pub fn new(n: u128) -> Nat128 {
+        Nat128 {
+            lo: n as u64,
+            hi: (n >> 64) as u64,
+        }
+    }
+
+    pub fn get(&self) -> u128 {
+        u128::from(self.hi) << 64 | u128::from(self.lo)
+    }
+}
+
+fn nat128(lo: u64, hi: u64) -> u128 {
+    Nat128 { lo, hi }.get()
+}
+
+unsafe fn store(r: *mut Nat128, n: Option<u128>) -> u32 {
+    match n {
+        Some(n) => {
+            *r = Nat128::new(n);
+            1
+        }
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nat128_add(
+    a_lo: u64,
+    a_hi: u64,
+    b_lo: u64,
+    b_hi: u64,
+    r: *mut Nat128,
+) -> u32 {
+    store(r, nat128(a_lo, a_hi).checked_add(nat128(b_lo, b_hi)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nat128_sub(
+    a_lo: u64,
+    a_hi: u64,
+    b_lo: u64,
+    b_hi: u64,
+    r: *mut Nat128,
+) -> u32 {
+    store(r, nat128(a_lo, a_hi).checked_sub(nat128(b_lo, b_hi)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nat128_mul(
+    a_lo: u64,
+    a_hi: u64,
+    b_lo: u64,
+    b_hi: u64,
+    r: *mut Nat128,
+) -> u32 {
+    store(r, nat128(a_lo, a_hi).checked_mul(nat128(b_lo, b_hi)))
+}
+
+/// The quotient, rounded down
+#[no_mangle]
+pub unsafe extern "C" fn nat128_div(
+    a_lo: u64,
+    a_hi: u64,
+    b_lo: u64,
+    b_hi: u64,
+    r: *mut Nat128,
+) -> u32 {
+    store(r, nat128(a_lo, a_hi).checked_div(nat128(b_lo, b_hi)))
+}
+
+/// -1, 0, or 1 when `a` is less than, equal to, or greater than `b`
+#[no_mangle]
+pub extern "C" fn nat128_compare(a_lo: u64, a_hi: u64, b_lo: u64, b_hi: u64) -> i32 {
+    nat128(a_lo, a_hi).cmp(&nat128(b_lo, b_hi)) as i32
+}
+
+/// The number as a (boxed) big integer
+#[no_mangle]
+pub unsafe extern "C" fn nat128_to_bigint(lo: u64, hi: u64) -> SkewedPtr {
+    bigint_of_mag128(nat128(lo, hi), false)
+}
+
+/// Writes the big integer, returns 0 when it is negative or doesn't fit in 128 bits
+#[no_mangle]
+pub unsafe extern "C" fn nat128_of_bigint(n: SkewedPtr, r: *mut Nat128) -> u32 {
+    store(r, bigint_to_nat128(n))
+}
+
+/// The decimal text of the number, with `separators` with `_` between groups of three digits (as
+/// `bigint_to_text`)
+#[no_mangle]
+pub unsafe extern "C" fn nat128_to_text(lo: u64, hi: u64, separators: bool) -> SkewedPtr {
+    // `u128::MAX` has 39 digits
+    let mut buf = [0u8; 39];
+    let mut n = nat128(lo, hi);
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    let digits = &buf[start..];
+
+    let n_separators = if separators {
+        (digits.len() - 1) / 3
+    } else {
+        0
+    };
+    let len = digits.len() + n_separators;
+    let (r, payload) = alloc_text(Bytes(len));
+    let out = core::slice::from_raw_parts_mut(payload, len);
+
+    let mut i = 0;
+    for (j, &c) in digits.iter().enumerate() {
+        if j > 0 && n_separators > 0 && (digits.len() - j) % 3 == 0 {
+            out[i] = b'_';
+            i += 1;
+        }
+        out[i] = c;
+        i += 1;
+    }
+    r
+}
+
+/// Parses the decimal digits of the text, with single `_` between them (as in literals). Returns 0
+/// when the text is not of this form or the number doesn't fit in 128 bits.
+#[no_mangle]
+pub unsafe extern "C" fn nat128_of_text(text: SkewedPtr, r: *mut Nat128) -> u32 {
+    let s = text_str(text).as_bytes();
+
+    if s.is_empty() || s[0] == b'_' || s[s.len() - 1] == b'_' || s.windows(2).any(|w| w == b"__") {
+        return 0;
+    }
+
+    let mut n: u128 = 0;
+    for &c in s {
+        match c {
+            b'_' => {}
+            b'0'..=b'9' => match n
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(u128::from(c - b'0')))
+            {
+                Some(m) => n = m,
+                None => return 0,
+            },
+            _ => return 0,
+        }
+    }
+    store(r, Some(n))
+}
@@ -0,0 +1,184 @@
+//! Unicode normalization of texts to NFC (Normalization Form C, see Unicode Standard Annex #15).
+//!
+//! A text is normalized by decomposing each character (recursively, with the canonical
+//! decompositions), sorting the combining marks after each starter by their canonical combining
+//! class, and composing the characters again (with the primary composites). The tables are in
+//! `unicode_tables.rs`, generated by `rts/gen-unicode-tables.py`. Hangul syllables are decomposed
+//! and composed algorithmically.
+//!
+//! The decomposed characters are stored in a scratch buffer (see `scratch.rs`).
+
+use crate::scratch::scratch_alloc;
+use crate::text::{text_of_chars, text_str};
+use crate::types::{Bytes, SkewedPtr};
+use crate::unicode_tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+
+use core::cmp::Ordering;
+use core::slice;
+
+// Hangul syllables, see section 3.12 of the Unicode Standard
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// Canonical combining class of the character
+fn combining_class(c: u32) -> u8 {
+    match COMBINING_CLASSES.binary_search_by(|&(first, last, _)| {
+        if last < c {
+            Ordering::Less
+        } else if first > c {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }) {
+        Ok(idx) => COMBINING_CLASSES[idx].2,
+        Err(_) => 0,
+    }
+}
+
+/// Calls `f` with the characters of the full canonical decomposition of the character
+fn decompose<F: FnMut(u32)>(c: u32, f: &mut F) {
+    if (S_BASE..S_BASE + S_COUNT).contains(&c) {
+        let s_index = c - S_BASE;
+        f(L_BASE + s_index / N_COUNT);
+        f(V_BASE + (s_index % N_COUNT) / T_COUNT);
+        let t_index = s_index % T_COUNT;
+        if t_index != 0 {
+            f(T_BASE + t_index);
+        }
+        return;
+    }
+
+    match DECOMPOSITIONS.binary_search_by_key(&c, |&(c, _, _)| c) {
+        Ok(idx) => {
+            let (_, first, second) = DECOMPOSITIONS[idx];
+            decompose(first, f);
+            if second != 0 {
+                decompose(second, f);
+            }
+        }
+        Err(_) => f(c),
+    }
+}
+
+/// The primary composite of the two characters, if any
+fn compose(first: u32, second: u32) -> Option<u32> {
+    if (L_BASE..L_BASE + L_COUNT).contains(&first) && (V_BASE..V_BASE + V_COUNT).contains(&second) {
+        let l_index = first - L_BASE;
+        let v_index = second - V_BASE;
+        return Some(S_BASE + (l_index * V_COUNT + v_index) * T_COUNT);
+    }
+
+    if (S_BASE..S_BASE + S_COUNT).contains(&first)
+        && (first - S_BASE) % T_COUNT == 0
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&second)
+    {
+        return Some(first + (second - T_BASE));
+    }
+
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(first, second, _)| (first, second))
+        .ok()
+        .map(|idx| COMPOSITIONS[idx].2)
+}
+
+/// Sorts the runs of combining marks by their combining class, keeping the order of marks with
+/// the same class (canonical ordering)
+fn reorder(chars: &mut [u32]) {
+    for i in 1..chars.len() {
+        let class = combining_class(chars[i]);
+        if class == 0 {
+            continue;
+        }
+        let mut j = i;
+        while j > 0 && combining_class(chars[j - 1]) > class {
+            chars.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// Composes the characters in place (canonical composition), returns the number of characters
+/// after composition
+fn recompose(chars: &mut [u32]) -> usize {
+    // Index of the last starter in the result, and the class of the last character after it
+    let mut starter: Option<usize> = None;
+    let mut last_class: u8 = 0;
+
+    let mut len = 0;
+    for i in 0..chars.len() {
+        let c = chars[i];
+        let class = combining_class(c);
+
+        if let Some(starter_idx) = starter {
+            // Blocked when there's a character with the same or a higher class (or a starter) in
+            // between. The characters in between are combining marks in canonical order.
+            let blocked = len > starter_idx + 1 && last_class >= class;
+            if !blocked {
+                if let Some(composite) = compose(chars[starter_idx], c) {
+                    chars[starter_idx] = composite;
+                    continue;
+                }
+            }
+        }
+
+        if class == 0 {
+            starter = Some(len);
+        }
+        last_class = class;
+
+        chars[len] = c;
+        len += 1;
+    }
+
+    len
+}
+
+/// Normalizes the text to NFC. Returns the text itself when it's already normalized.
+#[no_mangle]
+pub unsafe extern "C" fn text_normalize_nfc(s: SkewedPtr) -> SkewedPtr {
+    let text = text_str(s);
+
+    // Characters below U+0300 (encoded with bytes below 0xCC) are not changed by normalization,
+    // and there are no combining marks among them
+    if text.bytes().all(|b| b < 0xCC) {
+        return s;
+    }
+
+    let mut n_chars = 0;
+    for c in text.chars() {
+        decompose(c as u32, &mut |_| n_chars += 1);
+    }
+
+    let chars = slice::from_raw_parts_mut(
+        scratch_alloc(Bytes(n_chars * core::mem::size_of::<u32>())) as *mut u32,
+        n_chars,
+    );
+
+    let mut idx = 0;
+    for c in text.chars() {
+        decompose(c as u32, &mut |c| {
+            chars[idx] = c;
+            idx += 1;
+        });
+    }
+
+    reorder(chars);
+    let len = recompose(chars);
+    let chars = &chars[..len];
+
+    let chars = chars.iter().map(|&c| char::from_u32_unchecked(c));
+
+    if chars.clone().eq(text.chars()) {
+        return s;
+    }
+
+    text_of_chars(chars)
+}
@@ -0,0 +1,101 @@
+//! Instructions spent in the subsystems of the RTS, with `--perf-counters`.
+//!
+//! The generated code reads the instruction counter of the message (`ic0.performance_counter`)
+//! when it enters and leaves the code of a subsystem (a collection, Candid decoding or encoding,
+//! stabilization or destabilization), and passes it to `perf_enter` and `perf_leave`. The
+//! instructions in between are added to the subsystem, so the instructions of a message can be
+//! broken down by subsystem (`Prim.rts_perf_counters`). The instructions of the collections are
+//! also in the GC statistics (`Prim.rts_gc_stats`). Without the flag nothing is counted.
+//!
+//! The sections nest, e.g. the Candid decoding of stable variables in stabilization. The
+//! instructions of the inner section are only added to the inner subsystem. At most `MAX_DEPTH`
+//! sections are kept, instructions in deeper sections are added to the innermost kept one.
+//!
+//! The counter restarts with every message. As a trap rolls back the message, the sections are
+//! balanced at the end of every message that doesn't trap.
+
+/// Subsystems counted, the argument of `perf_enter`
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Subsystem {
+    Gc = 0,
+    CandidDecode = 1,
+    CandidEncode = 2,
+    Stabilization = 3,
+}
+
+const N_SUBSYSTEMS: usize = 4;
+
+const MAX_DEPTH: usize = 8;
+
+/// Instructions of the subsystems, returned by `get_perf_counters` and read by the generated code
+/// by offset.
+#[repr(C)]
+pub struct PerfCounters {
+    pub gc: u64,
+    pub candid_decode: u64,
+    pub candid_encode: u64,
+    pub stabilization: u64,
+}
+
+/// Instructions of the subsystems, indexed by `Subsystem`
+static mut INSTRUCTIONS: [u64; N_SUBSYSTEMS] = [0; N_SUBSYSTEMS];
+
+/// The subsystems of the open sections, outermost first
+static mut SECTIONS: [u32; MAX_DEPTH] = [0; MAX_DEPTH];
+
+/// Number of open sections, including those not in `SECTIONS`
+static mut DEPTH: usize = 0;
+
+/// Counter when the innermost section was entered, or when its inner section was left
+static mut LAST: u64 = 0;
+
+static mut PERF_COUNTERS: PerfCounters = PerfCounters {
+    gc: 0,
+    candid_decode: 0,
+    candid_encode: 0,
+    stabilization: 0,
+};
+
+/// Adds the instructions since `LAST` to the innermost section
+unsafe fn charge(now: u64) {
+    if DEPTH != 0 {
+        let subsystem = SECTIONS[core::cmp::min(DEPTH, MAX_DEPTH) - 1] as usize;
+        INSTRUCTIONS[subsystem] += now.saturating_sub(LAST);
+    }
+    LAST = now;
+}
+
+/// Enters a section of `subsystem` (a `Subsystem`) at the instruction counter `now`
+#[no_mangle]
+pub unsafe extern "C" fn perf_enter(subsystem: u32, now: u64) {
+    charge(now);
+    if DEPTH < MAX_DEPTH {
+        SECTIONS[DEPTH] = core::cmp::min(subsystem as usize, N_SUBSYSTEMS - 1) as u32;
+    }
+    DEPTH += 1;
+}
+
+/// Leaves the innermost section at the instruction counter `now`
+#[no_mangle]
+pub unsafe extern "C" fn perf_leave(now: u64) {
+    charge(now);
+    DEPTH = DEPTH.saturating_sub(1);
+}
+
+/// Instructions of `subsystem` so far
+pub unsafe fn perf_instructions(subsystem: Subsystem) -> u64 {
+    INSTRUCTIONS[subsystem as usize]
+}
+
+/// Returns a pointer to the instructions of the subsystems, updated on every call
+#[no_mangle]
+pub unsafe extern "C" fn get_perf_counters() -> *const PerfCounters {
+    PERF_COUNTERS = PerfCounters {
+        gc: INSTRUCTIONS[Subsystem::Gc as usize],
+        candid_decode: INSTRUCTIONS[Subsystem::CandidDecode as usize],
+        candid_encode: INSTRUCTIONS[Subsystem::CandidEncode as usize],
+        stabilization: INSTRUCTIONS[Subsystem::Stabilization as usize],
+    };
+    &PERF_COUNTERS
+}
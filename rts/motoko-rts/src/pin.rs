@@ -0,0 +1,126 @@
+//! Pinning heap objects.
+//!
+//! The moving collectors invalidate pointers to heap objects. Code that needs a pointer to stay
+//! valid across collections (e.g. a buffer that is read asynchronously) pins the object with
+//! `pin_object`, and unpins it with `unpin_object` when done. Pinned objects are also kept alive.
+//! Pins nest: an object pinned twice needs to be unpinned twice.
+//!
+//! How the collectors handle pinned objects:
+//!
+//! - The copying GC (and the generational GC, which uses it) does not move pinned objects, same as
+//!   large objects. Pinned objects are added to the large object table in each collection, see
+//!   `large_objects::add_pinned`.
+//!
+//! - The incremental GC leaves the regions with pinned objects in place, see `gc/incremental.rs`.
+//!
+//! - The mark-sweep GC does not move objects.
+//!
+//! Pinned objects are stored in a heap-allocated Motoko array, which is a GC root like the closure
+//! table. The first `N_PINNED` slots are used, the rest hold `FREE`.
+
+use crate::alloc::{alloc_array, write_barrier};
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::SkewedPtr;
+
+const INITIAL_SIZE: usize = 16;
+
+const FREE: SkewedPtr = SkewedPtr(0);
+
+// Skewed pointer to the `Array` object
+static mut TABLE: SkewedPtr = SkewedPtr(0);
+
+// Number of pins
+static mut N_PINNED: usize = 0;
+
+unsafe fn table_size() -> usize {
+    if TABLE.0 == 0 {
+        0
+    } else {
+        TABLE.as_array().len()
+    }
+}
+
+unsafe fn set_slot(idx: usize, value: SkewedPtr) {
+    let array = TABLE.as_array();
+    write_barrier(array.payload_addr().add(idx));
+    array.set(idx, value);
+}
+
+unsafe fn grow_table() {
+    let old_size = table_size();
+    let new_size = if old_size == 0 {
+        INITIAL_SIZE
+    } else {
+        old_size * 2
+    };
+
+    let new_table = alloc_array(new_size);
+    let new_array = new_table.as_array();
+
+    for i in 0..N_PINNED {
+        new_array.set(i, TABLE.as_array().get(i));
+    }
+
+    for i in N_PINNED..new_size {
+        new_array.set(i, FREE);
+    }
+
+    TABLE = new_table;
+}
+
+/// Pins an object: the GC does not move it, and keeps it alive, until it's unpinned
+#[no_mangle]
+pub unsafe extern "C" fn pin_object(obj: SkewedPtr) {
+    if obj.is_tagged_scalar() {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "pin_object: Argument is not a skewed pointer",
+        );
+    }
+
+    if N_PINNED == table_size() {
+        grow_table();
+    }
+
+    set_slot(N_PINNED, obj);
+    N_PINNED += 1;
+}
+
+/// Removes a pin added with `pin_object`
+#[no_mangle]
+pub unsafe extern "C" fn unpin_object(obj: SkewedPtr) {
+    for i in (0..N_PINNED).rev() {
+        if TABLE.as_array().get(i) == obj {
+            N_PINNED -= 1;
+            let last = TABLE.as_array().get(N_PINNED);
+            set_slot(i, last);
+            set_slot(N_PINNED, FREE);
+            return;
+        }
+    }
+
+    rts_trap_with(
+        ErrorCode::InvalidArgument,
+        "unpin_object: Object is not pinned",
+    );
+}
+
+/// Number of pins. An object pinned multiple times is counted multiple times.
+#[no_mangle]
+pub unsafe extern "C" fn pinned_count() -> usize {
+    N_PINNED
+}
+
+/// Calls `f` with the address of each pinned object
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn for_each_pinned<F: FnMut(usize)>(mut f: F) {
+    for i in 0..N_PINNED {
+        f(TABLE.as_array().get(i).unskew());
+    }
+}
+
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn pin_table_loc() -> *mut SkewedPtr {
+    &mut TABLE
+}
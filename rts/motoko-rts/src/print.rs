@@ -8,7 +8,7 @@ use core::fmt;
 extern "C" {
     // `print_ptr` is implemented by the code generator and has different implementations depending
     // on the target platform (WASI or IC).
-    fn print_ptr(ptr: usize, len: u32);
+    fn print_ptr(ptr: usize, len: usize);
 }
 
 /*
@@ -78,8 +78,14 @@ impl<'a> WriteBuf<'a> {
         self.offset = 0;
     }
 
+    /// The bytes written, which are cut off in the middle of a character when the buffer is
+    /// filled
+    pub(crate) fn as_str(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.offset]) }
+    }
+
     pub(crate) unsafe fn print(&self) {
-        print_ptr(self.buf.as_ptr() as usize, self.offset as u32)
+        print_ptr(self.buf.as_ptr() as usize, self.offset)
     }
 }
 
@@ -104,5 +110,5 @@ pub(crate) unsafe fn print(buf: &WriteBuf) {
 }
 
 // pub(crate) unsafe fn print_str(str: &str) {
-//     print_ptr(str.as_ptr() as usize, str.len() as u32)
+//     print_ptr(str.as_ptr() as usize, str.len())
 // }
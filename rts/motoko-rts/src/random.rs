@@ -0,0 +1,105 @@
+//! A deterministic pseudo-random number generator, xoshiro256++ (see
+//! <https://prng.di.unimi.it/>), for property tests, simulations, and randomized data structures
+//! that don't need a `raw_rand` call per value. It is not suitable for cryptography.
+//!
+//! The generator is seeded explicitly, with a number (`random_seed`, expanded to the state with
+//! SplitMix64 as recommended by the authors) or with the 32 bytes of the state
+//! (`random_seed_blob`, e.g. with the blob of `raw_rand`). The same seed gives the same sequence,
+//! before seeding the sequence is that of `random_seed(0)`.
+//!
+//! The state is not stable, it is reset on upgrades.
+
+use crate::alloc::alloc_blob;
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::{Bytes, SkewedPtr};
+
+/// Bytes of the state, the size of the seed of `random_seed_blob`
+pub const STATE_SIZE: usize = 32;
+
+/// `None` before seeding
+static mut STATE: Option<[u64; 4]> = None;
+
+fn splitmix64(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn state_of_seed(mut seed: u64) -> [u64; 4] {
+    [
+        splitmix64(&mut seed),
+        splitmix64(&mut seed),
+        splitmix64(&mut seed),
+        splitmix64(&mut seed),
+    ]
+}
+
+/// Seeds the generator with a number
+#[no_mangle]
+pub unsafe extern "C" fn random_seed(seed: u64) {
+    STATE = Some(state_of_seed(seed));
+}
+
+/// Seeds the generator with the state, the 32 bytes of the blob as four little-endian numbers.
+/// Traps on other sizes. All zeros, the only invalid state, is replaced by the state of
+/// `random_seed(0)`.
+#[no_mangle]
+pub unsafe extern "C" fn random_seed_blob(seed: SkewedPtr) {
+    let blob = seed.as_blob();
+    if blob.len().0 != STATE_SIZE {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "random_seed_blob: Seed is not 32 bytes",
+        );
+    }
+
+    let mut state = [0u64; 4];
+    for (i, word) in state.iter_mut().enumerate() {
+        let mut bytes = [0u8; 8];
+        for (j, byte) in bytes.iter_mut().enumerate() {
+            *byte = blob.get(i * 8 + j);
+        }
+        *word = u64::from_le_bytes(bytes);
+    }
+
+    STATE = Some(if state == [0; 4] {
+        state_of_seed(0)
+    } else {
+        state
+    });
+}
+
+/// The next number of the sequence
+#[no_mangle]
+pub unsafe extern "C" fn random_next() -> u64 {
+    let s = STATE.get_or_insert_with(|| state_of_seed(0));
+
+    let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+
+    let t = s[1] << 17;
+    s[2] ^= s[0];
+    s[3] ^= s[1];
+    s[1] ^= s[2];
+    s[0] ^= s[3];
+    s[2] ^= t;
+    s[3] = s[3].rotate_left(45);
+
+    result
+}
+
+/// Allocates a blob of `n` bytes of the sequence, the bytes of each number in little-endian order.
+/// A number is used for every 8 bytes, so the sequence continues the same way as with
+/// `ceil(n / 8)` calls of `random_next`.
+#[no_mangle]
+pub unsafe extern "C" fn blob_random(n: Bytes<usize>) -> SkewedPtr {
+    let ptr = alloc_blob(n);
+    let payload = core::slice::from_raw_parts_mut(ptr.as_blob().payload_addr(), n.0);
+    for chunk in payload.chunks_mut(8) {
+        let bytes = random_next().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+    ptr
+}
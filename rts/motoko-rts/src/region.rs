@@ -0,0 +1,591 @@
+//! Regions: independent, growable partitions of stable memory.
+//!
+//! Stable memory is split into a header page followed by blocks of `BLOCK_PAGES` pages. Every
+//! region owns a sequence of blocks, allocated when the region grows. The `k`-th block owned by a
+//! region holds its pages from `k * BLOCK_PAGES`, and as blocks are allocated in order, the
+//! blocks of a region are found by scanning the owners of the blocks. Regions never shrink and
+//! are never freed.
+//!
+//! The header page:
+//!
+//! - at 0: a zero word. In the layout before regions stable memory starts with the size of the
+//!   stable variables, which is never zero, so old stable memory is told apart by this word and
+//!   the magic number.
+//! - at 4: `MAGIC`, at 8: the version of the layout, `VERSION`
+//! - at 12: number of regions, at 16: number of blocks
+//! - at 20: flags, `FLAG_*`
+//! - at `REGIONS_OFFSET`: the size of every region in pages, 64-bit
+//! - at `BLOCKS_OFFSET`: the region owning every block, 16-bit
+//!
+//! The header is mirrored in the static data of the RTS, read from stable memory when a region
+//! is used first after an upgrade, and written through when it changes.
+//!
+//! A header of an older version is migrated to `VERSION` when stable memory is changed first,
+//! by the migrations in `migrate`. A header of a newer version cannot be read, and traps. The
+//! header as found after the upgrade (before migrating it) is returned by `stable_memory_header`,
+//! for the generated code to tell which layout the previous version of the canister used.
+//!
+//! Versions:
+//!
+//! 1. Regions.
+//! 2. Flags.
+//!
+//! Region 0 holds the stable variables, as their size (a 32-bit word) followed by their Candid
+//! encoding or their graph copy (see `graph_copy.rs`), which may be compressed (see
+//! `compress.rs`), and then by the CRC-32 of the stable variables. The stable variables of the
+//! layout before regions are at offset 0 of the stable memory. They are read when upgrading,
+//! before anything else uses stable memory, and the stable memory is formatted for regions when
+//! it's changed first.
+//!
+//! Offsets and sizes are 64-bit, but stable memory is accessed with the 32-bit system API
+//! provided by the generated code, so it is limited to 4 GiB.
+
+use crate::alloc::alloc_blob;
+use crate::compress::{CompressStream, Decompressor};
+use crate::crc::{Crc, CRC32};
+use crate::error_code::ErrorCode;
+use crate::print::WriteBuf;
+use crate::rts_trap_with;
+use crate::stream::{RegionStream, Stream, CHUNK_SIZE};
+use crate::types::{Bytes, SkewedPtr};
+
+use core::fmt::Write;
+use core::mem::size_of;
+
+extern "C" {
+    // The stable memory system API (`ic0.stable_*`), provided by the generated code (see
+    // `RTS_Exports` in `compile.ml`)
+    fn stable_mem_size() -> u32;
+    fn stable_mem_grow(pages: u32) -> i32;
+    fn stable_mem_read(dst: *mut u8, offset: u32, len: u32);
+    fn stable_mem_write(offset: u32, src: *const u8, len: u32);
+}
+
+const PAGE_SIZE: u64 = 65536;
+
+/// Pages of a block, the unit in which regions grow
+pub const BLOCK_PAGES: u64 = 16;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"MOrg");
+pub const VERSION: u32 = 2;
+
+const VERSION_OFFSET: u32 = 8;
+const N_REGIONS_OFFSET: u32 = 12;
+const N_BLOCKS_OFFSET: u32 = 16;
+const FLAGS_OFFSET: u32 = 20;
+const REGIONS_OFFSET: u32 = 32;
+const BLOCKS_OFFSET: u32 = REGIONS_OFFSET + (MAX_REGIONS * size_of::<u64>()) as u32;
+
+pub const MAX_REGIONS: usize = 1024;
+pub const MAX_BLOCKS: usize = (PAGE_SIZE as usize - BLOCKS_OFFSET as usize) / size_of::<u16>();
+
+/// The region of the stable variables
+pub const STABLE_VARS: u32 = 0;
+
+/// The stable variables are a graph copy (see `graph_copy.rs`), not their Candid encoding
+pub const FLAG_GRAPH_COPY: u32 = 1;
+
+/// The stable variables are compressed (see `compress.rs`)
+pub const FLAG_COMPRESSED: u32 = 2;
+
+/// The stable variables are followed by the CRC-32 of their (uncompressed) bytes, see
+/// `stable_vars_verify`
+pub const FLAG_CHECKSUM: u32 = 4;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Layout {
+    /// The header has not been read yet
+    Unknown,
+    /// Stable memory is empty or holds the stable variables at offset 0
+    Legacy,
+    Regions,
+}
+
+static mut LAYOUT: Layout = Layout::Unknown;
+
+/// The header, see the module documentation
+static mut N_REGIONS: u32 = 0;
+static mut N_BLOCKS: u32 = 0;
+static mut REGION_PAGES: [u64; MAX_REGIONS] = [0; MAX_REGIONS];
+static mut BLOCK_OWNERS: [u16; MAX_BLOCKS] = [0; MAX_BLOCKS];
+static mut FLAGS: u32 = 0;
+
+/// The version of the header in stable memory, 0 when not read yet or in the layout before
+/// regions
+static mut STORED_VERSION: u32 = 0;
+
+/// The header as found after the upgrade, returned by `stable_memory_header`. The generated code
+/// reads the fields by offset, see `rts_stable_memory_header` in `compile.ml`.
+#[repr(C)]
+pub struct StableMemoryHeader {
+    /// 0 when stable memory was empty or in the layout before regions
+    pub version: u32,
+    pub flags: u32,
+    pub regions: u32,
+}
+
+/// Whether the stable variables written next are compressed, see `stable_vars_compress`
+static mut COMPRESS: bool = false;
+
+/// Reading the compressed stable variables
+static mut DECOMPRESSOR: Decompressor = Decompressor::new();
+
+static mut FOUND_HEADER: StableMemoryHeader = StableMemoryHeader {
+    version: 0,
+    flags: 0,
+    regions: 0,
+};
+
+unsafe fn read_word(offset: u32) -> u32 {
+    let mut word = 0u32;
+    stable_mem_read(&mut word as *mut u32 as *mut u8, offset, 4);
+    word
+}
+
+unsafe fn write_word(offset: u32, word: u32) {
+    stable_mem_write(offset, &word as *const u32 as *const u8, 4);
+}
+
+/// Reads the header when it's not read yet
+unsafe fn load() {
+    if LAYOUT != Layout::Unknown {
+        return;
+    }
+
+    if stable_mem_size() == 0 || read_word(0) != 0 || read_word(4) != MAGIC {
+        LAYOUT = Layout::Legacy;
+        return;
+    }
+    STORED_VERSION = read_word(VERSION_OFFSET);
+    if STORED_VERSION == 0 || STORED_VERSION > VERSION {
+        rts_trap_with(
+            ErrorCode::StableMemory,
+            "unsupported version of the stable memory regions",
+        );
+    }
+
+    N_REGIONS = read_word(N_REGIONS_OFFSET);
+    N_BLOCKS = read_word(N_BLOCKS_OFFSET);
+    if N_REGIONS as usize > MAX_REGIONS || N_BLOCKS as usize > MAX_BLOCKS {
+        rts_trap_with(ErrorCode::StableMemory, "invalid stable memory regions");
+    }
+    stable_mem_read(
+        REGION_PAGES.as_mut_ptr() as *mut u8,
+        REGIONS_OFFSET,
+        N_REGIONS * size_of::<u64>() as u32,
+    );
+    stable_mem_read(
+        BLOCK_OWNERS.as_mut_ptr() as *mut u8,
+        BLOCKS_OFFSET,
+        N_BLOCKS * size_of::<u16>() as u32,
+    );
+    FLAGS = if STORED_VERSION >= 2 {
+        read_word(FLAGS_OFFSET)
+    } else {
+        0
+    };
+    FOUND_HEADER = StableMemoryHeader {
+        version: STORED_VERSION,
+        flags: FLAGS,
+        regions: N_REGIONS,
+    };
+    LAYOUT = Layout::Regions;
+}
+
+/// Migrates the header in stable memory to `VERSION`
+unsafe fn migrate() {
+    while STORED_VERSION < VERSION {
+        match STORED_VERSION {
+            1 => write_word(FLAGS_OFFSET, FLAGS),
+            _ => rts_trap_with(
+                ErrorCode::StableMemory,
+                "unsupported version of the stable memory regions",
+            ),
+        }
+        STORED_VERSION += 1;
+        write_word(VERSION_OFFSET, STORED_VERSION);
+    }
+}
+
+/// Formats stable memory for regions, unless it already is
+unsafe fn format() {
+    load();
+    if LAYOUT == Layout::Regions {
+        migrate();
+        return;
+    }
+
+    // Stable variables of the layout before regions at offset 0 have been read already, the
+    // pages are reused for blocks
+    if stable_mem_size() == 0 && stable_mem_grow(1) < 0 {
+        rts_trap_with(ErrorCode::OutOfMemory, "Cannot grow stable memory.");
+    }
+    N_REGIONS = 1;
+    N_BLOCKS = 0;
+    REGION_PAGES[STABLE_VARS as usize] = 0;
+    FLAGS = 0;
+    STORED_VERSION = VERSION;
+
+    write_word(0, 0);
+    write_word(4, MAGIC);
+    write_word(VERSION_OFFSET, VERSION);
+    write_word(N_REGIONS_OFFSET, N_REGIONS);
+    write_word(N_BLOCKS_OFFSET, N_BLOCKS);
+    write_word(FLAGS_OFFSET, FLAGS);
+    write_region_pages(STABLE_VARS);
+    LAYOUT = Layout::Regions;
+}
+
+unsafe fn write_region_pages(id: u32) {
+    stable_mem_write(
+        REGIONS_OFFSET + id * size_of::<u64>() as u32,
+        &REGION_PAGES[id as usize] as *const u64 as *const u8,
+        size_of::<u64>() as u32,
+    );
+}
+
+unsafe fn check_region(id: u32) {
+    format();
+    if id >= N_REGIONS {
+        rts_trap_with(ErrorCode::InvalidArgument, "invalid region");
+    }
+}
+
+fn blocks_of_pages(pages: u64) -> u64 {
+    (pages + BLOCK_PAGES - 1) / BLOCK_PAGES
+}
+
+/// The `k`-th block of the region
+unsafe fn region_block(id: u32, mut k: u64) -> u32 {
+    for (block, owner) in BLOCK_OWNERS[..N_BLOCKS as usize].iter().enumerate() {
+        if u32::from(*owner) == id {
+            if k == 0 {
+                return block as u32;
+            }
+            k -= 1;
+        }
+    }
+    rts_trap_with(ErrorCode::StableMemory, "region block not found")
+}
+
+/// Calls `f` with the stable memory offset, the offset in the buffer and the length of the
+/// chunks of the `len` bytes at `offset` in the region, which are in different blocks
+unsafe fn for_chunks<F: FnMut(u32, usize, u32)>(id: u32, offset: u64, len: u64, mut f: F) {
+    check_region(id);
+    let size = REGION_PAGES[id as usize] * PAGE_SIZE;
+    if offset > size || len > size - offset {
+        rts_trap_with(ErrorCode::OutOfBounds, "region access out of bounds");
+    }
+
+    let block_size = BLOCK_PAGES * PAGE_SIZE;
+    let mut done = 0;
+    while done < len {
+        let pos = offset + done;
+        let block = u64::from(region_block(id, pos / block_size));
+        let chunk = core::cmp::min(len - done, block_size - pos % block_size);
+        let stable_offset = (1 + block * BLOCK_PAGES) * PAGE_SIZE + pos % block_size;
+        f(stable_offset as u32, done as usize, chunk as u32);
+        done += chunk;
+    }
+}
+
+/// Creates an empty region, returns its id
+#[no_mangle]
+pub unsafe extern "C" fn region_new() -> u32 {
+    format();
+    if N_REGIONS as usize == MAX_REGIONS {
+        rts_trap_with(ErrorCode::OutOfMemory, "region_new: too many regions");
+    }
+    let id = N_REGIONS;
+    N_REGIONS += 1;
+    REGION_PAGES[id as usize] = 0;
+    write_region_pages(id);
+    write_word(N_REGIONS_OFFSET, N_REGIONS);
+    id
+}
+
+/// Size of the region in pages
+#[no_mangle]
+pub unsafe extern "C" fn region_size(id: u32) -> u64 {
+    check_region(id);
+    REGION_PAGES[id as usize]
+}
+
+/// Grows the region by `pages` pages, returns its previous size, or `u64::MAX` when stable memory
+/// cannot grow (as `ic0.stable_grow`)
+#[no_mangle]
+pub unsafe extern "C" fn region_grow(id: u32, pages: u64) -> u64 {
+    check_region(id);
+    let old_pages = REGION_PAGES[id as usize];
+    let new_pages = match old_pages.checked_add(pages) {
+        Some(new_pages) => new_pages,
+        None => return u64::MAX,
+    };
+
+    let new_blocks = blocks_of_pages(new_pages) - blocks_of_pages(old_pages);
+    if new_blocks > (MAX_BLOCKS - N_BLOCKS as usize) as u64 {
+        return u64::MAX;
+    }
+    let needed = 1 + (u64::from(N_BLOCKS) + new_blocks) * BLOCK_PAGES;
+    let available = u64::from(stable_mem_size());
+    if needed > available
+        && (needed - available > u64::from(u32::MAX)
+            || stable_mem_grow((needed - available) as u32) < 0)
+    {
+        return u64::MAX;
+    }
+
+    for _ in 0..new_blocks {
+        BLOCK_OWNERS[N_BLOCKS as usize] = id as u16;
+        stable_mem_write(
+            BLOCKS_OFFSET + N_BLOCKS * size_of::<u16>() as u32,
+            &BLOCK_OWNERS[N_BLOCKS as usize] as *const u16 as *const u8,
+            size_of::<u16>() as u32,
+        );
+        N_BLOCKS += 1;
+    }
+    write_word(N_BLOCKS_OFFSET, N_BLOCKS);
+
+    REGION_PAGES[id as usize] = new_pages;
+    write_region_pages(id);
+    old_pages
+}
+
+/// Copies `len` bytes at `offset` in the region to `dst`
+#[no_mangle]
+pub unsafe extern "C" fn region_read(id: u32, offset: u64, dst: *mut u8, len: u32) {
+    for_chunks(id, offset, u64::from(len), |stable_offset, pos, chunk| {
+        stable_mem_read(dst.add(pos), stable_offset, chunk)
+    })
+}
+
+/// Copies `len` bytes from `src` to `offset` in the region
+#[no_mangle]
+pub unsafe extern "C" fn region_write(id: u32, offset: u64, src: *const u8, len: u32) {
+    for_chunks(id, offset, u64::from(len), |stable_offset, pos, chunk| {
+        stable_mem_write(stable_offset, src.add(pos), chunk)
+    })
+}
+
+/// Grows the region to at least `size` bytes, traps when stable memory cannot grow
+pub(crate) unsafe fn region_reserve(id: u32, size: u64) {
+    let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    let old_pages = region_size(id);
+    if pages > old_pages && region_grow(id, pages - old_pages) == u64::MAX {
+        rts_trap_with(ErrorCode::OutOfMemory, "Cannot grow stable memory.");
+    }
+}
+
+/// A blob with `len` bytes at `offset` in the region
+#[no_mangle]
+pub unsafe extern "C" fn region_load_blob(id: u32, offset: u64, len: u32) -> SkewedPtr {
+    let blob = alloc_blob(Bytes(len as usize));
+    region_read(id, offset, blob.as_blob().payload_addr(), len);
+    blob
+}
+
+/// Size of the encoding of the stable variables, 0 when there are none
+#[no_mangle]
+pub unsafe extern "C" fn stable_vars_size() -> u32 {
+    load();
+    match LAYOUT {
+        Layout::Regions => {
+            if REGION_PAGES[STABLE_VARS as usize] == 0 {
+                return 0;
+            }
+            let mut size = 0u32;
+            // The uncompressed length follows the size of the compressed stable variables
+            let offset = if FLAGS & FLAG_COMPRESSED != 0 { 4 } else { 0 };
+            region_read(STABLE_VARS, offset, &mut size as *mut u32 as *mut u8, 4);
+            size
+        }
+        _ => {
+            if stable_mem_size() == 0 {
+                0
+            } else {
+                read_word(0)
+            }
+        }
+    }
+}
+
+/// Copies `len` bytes at `offset` of the encoding of the stable variables to `dst`
+#[no_mangle]
+pub unsafe extern "C" fn stable_vars_read(dst: *mut u8, offset: u32, len: u32) {
+    load();
+    match LAYOUT {
+        Layout::Regions if FLAGS & FLAG_COMPRESSED != 0 => DECOMPRESSOR.read(
+            |dst, offset, len| region_read(STABLE_VARS, offset + 8, dst, len),
+            stable_vars_size() as usize,
+            dst,
+            offset as usize,
+            len as usize,
+        ),
+        Layout::Regions => region_read(STABLE_VARS, u64::from(offset) + 4, dst, len),
+        _ => stable_mem_read(dst, offset + 4, len),
+    }
+}
+
+/// Compresses the stable variables written next (with `--compress-stabilization`)
+#[no_mangle]
+pub unsafe extern "C" fn stable_vars_compress() {
+    COMPRESS = true;
+}
+
+/// A stream of the stable variables, see `stable_vars_stream`
+pub struct StableVarsStream {
+    stream: Compression,
+    crc: Crc,
+    /// Number of bytes written so far, uncompressed
+    written: usize,
+}
+
+/// It's on the stack, so the sizes of the variants do not matter
+#[allow(clippy::large_enum_variant)]
+enum Compression {
+    Plain(RegionStream),
+    Compressed(CompressStream<RegionStream>),
+}
+
+impl StableVarsStream {
+    /// Writes the buffered bytes and the checksum, to be called when done
+    pub fn flush(&mut self) {
+        let crc = self.crc.finish().to_le_bytes();
+        match &mut self.stream {
+            Compression::Plain(stream) => {
+                stream.write(&crc);
+                stream.flush();
+            }
+            Compression::Compressed(stream) => {
+                let stream = stream.finish();
+                let size = stream.written() as u32;
+                stream.write(&crc);
+                stream.flush();
+                unsafe { region_write(STABLE_VARS, 0, &size as *const u32 as *const u8, 4) };
+            }
+        }
+    }
+}
+
+impl Stream for StableVarsStream {
+    fn write(&mut self, bytes: &[u8]) {
+        self.crc.update(bytes);
+        self.written += bytes.len();
+        match &mut self.stream {
+            Compression::Plain(stream) => stream.write(bytes),
+            Compression::Compressed(stream) => stream.write(bytes),
+        }
+    }
+
+    fn written(&self) -> usize {
+        self.written
+    }
+}
+
+/// A stream replacing the encoding of the stable variables with the `len` bytes written to it,
+/// a graph copy or not (see `FLAG_GRAPH_COPY`), compressed after `stable_vars_compress`. The size
+/// of compressed stable variables is only known when they are written, so region 0 grows with
+/// them, and the size is written by `flush`.
+pub unsafe fn stable_vars_stream(len: u32, graph_copy: bool) -> StableVarsStream {
+    format();
+    FLAGS &= !(FLAG_GRAPH_COPY | FLAG_COMPRESSED);
+    FLAGS |= FLAG_CHECKSUM;
+    if graph_copy {
+        FLAGS |= FLAG_GRAPH_COPY;
+    }
+    if COMPRESS {
+        FLAGS |= FLAG_COMPRESSED;
+    }
+    write_word(FLAGS_OFFSET, FLAGS);
+    DECOMPRESSOR.reset();
+
+    let stream = if COMPRESS {
+        COMPRESS = false;
+        let mut stream = RegionStream::growing(STABLE_VARS, 4);
+        stream.write(&len.to_le_bytes());
+        Compression::Compressed(CompressStream::new(stream))
+    } else {
+        region_reserve(STABLE_VARS, u64::from(len) + 8);
+        region_write(STABLE_VARS, 0, &len as *const u32 as *const u8, 4);
+        Compression::Plain(RegionStream::new(STABLE_VARS, 4))
+    };
+    StableVarsStream {
+        stream,
+        crc: Crc::new(&CRC32),
+        written: 0,
+    }
+}
+
+/// The checksum of the stable variables does not match, see `stable_vars_check`
+pub struct ChecksumMismatch {
+    /// Number of bytes of the stable variables, uncompressed
+    pub size: u32,
+    /// The CRC-32 written with the stable variables
+    pub expected: u32,
+    /// The CRC-32 of the stable variables as read
+    pub found: u32,
+}
+
+/// Checks the stable variables against their checksum, when they have one (see `FLAG_CHECKSUM`)
+pub unsafe fn stable_vars_check() -> Result<(), ChecksumMismatch> {
+    load();
+    if LAYOUT != Layout::Regions || FLAGS & FLAG_CHECKSUM == 0 {
+        return Ok(());
+    }
+
+    let size = stable_vars_size();
+    let mut crc = Crc::new(&CRC32);
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut offset = 0;
+    while offset < size {
+        let len = core::cmp::min(CHUNK_SIZE as u32, size - offset);
+        stable_vars_read(chunk.as_mut_ptr(), offset, len);
+        crc.update(&chunk[..len as usize]);
+        offset += len;
+    }
+
+    // The checksum follows what the size word counts, the compressed bytes when compressed
+    let mut stored_size = 0u32;
+    region_read(STABLE_VARS, 0, &mut stored_size as *mut u32 as *mut u8, 4);
+    let mut expected = 0u32;
+    region_read(
+        STABLE_VARS,
+        u64::from(stored_size) + 4,
+        &mut expected as *mut u32 as *mut u8,
+        4,
+    );
+
+    let found = crc.finish();
+    if found == expected {
+        Ok(())
+    } else {
+        Err(ChecksumMismatch {
+            size,
+            expected,
+            found,
+        })
+    }
+}
+
+/// Checks the stable variables against their checksum before they are restored, traps when they
+/// are corrupted rather than failing to decode them
+#[no_mangle]
+pub unsafe extern "C" fn stable_vars_verify() {
+    if let Err(mismatch) = stable_vars_check() {
+        let mut buf = [0u8; 120];
+        let mut msg = WriteBuf::new(&mut buf);
+        let _ = write!(
+            &mut msg,
+            "stable variables corrupted: CRC-32 {:#010x}, expected {:#010x}, of {} bytes",
+            mismatch.found, mismatch.expected, mismatch.size
+        );
+        rts_trap_with(ErrorCode::StableMemory, msg.as_str());
+    }
+}
+
+/// The header of stable memory as found after the upgrade, see the module documentation
+#[no_mangle]
+pub unsafe extern "C" fn stable_memory_header() -> *const StableMemoryHeader {
+    load();
+    &FOUND_HEADER
+}
@@ -0,0 +1,97 @@
+//! Scratch space for transient buffers: the serialization buffers of the generated code and the
+//! type table of the IDL decoder. These are only used during a message, so instead of allocating
+//! them as objects in the heap we allocate them in an arena that is released at the end of the
+//! message.
+//!
+//! The arena is a chunk, a blob allocated with `alloc_gc_blob`, so it's not counted as an
+//! allocation, and `scratch_alloc` bumps a pointer in it. When a buffer doesn't fit, a new chunk
+//! twice as big (or as big as the buffer) is allocated, the old chunk becomes garbage. The
+//! collectors call `release` at the end of each message, which rewinds the pointer, so the next
+//! message reuses the chunk.
+//!
+//! Nothing points to the chunk, so it's freed (or moved) by the next collection. The collectors
+//! call `forget` when that may have happened, and the next message allocates a new chunk, as big
+//! as the most scratch space a message used since the last collection. (The incremental GC also
+//! calls it when it starts a cycle, as it fills the gaps between kept objects during the cycle.)
+//!
+//! Without the `gc` feature the buffers are allocated as blobs in the heap.
+
+use crate::types::Bytes;
+
+#[cfg(feature = "gc")]
+use crate::gc::alloc_gc_blob;
+
+/// Size of the first chunk
+#[cfg(feature = "gc")]
+const MIN_CHUNK_SIZE: Bytes<usize> = Bytes(4096);
+
+/// Next free byte in the current chunk, 0 when there's no chunk
+#[cfg(feature = "gc")]
+static mut NEXT: usize = 0;
+
+/// End of the current chunk
+#[cfg(feature = "gc")]
+static mut END: usize = 0;
+
+/// Beginning of the payload of the current chunk
+#[cfg(feature = "gc")]
+static mut BEGIN: usize = 0;
+
+/// Most scratch space used in a message since the last collection
+#[cfg(feature = "gc")]
+static mut USED: Bytes<usize> = Bytes(0);
+
+/// Size of the chunk allocated after the last collection
+#[cfg(feature = "gc")]
+static mut NEXT_CHUNK_SIZE: Bytes<usize> = Bytes(0);
+
+/// Allocates a word-aligned buffer of `size` bytes, which is valid until the end of the message.
+/// Called by the generated code.
+#[cfg(feature = "gc")]
+#[no_mangle]
+pub unsafe extern "C" fn scratch_alloc(size: Bytes<usize>) -> *mut u8 {
+    let size = size.to_words().to_bytes();
+
+    if NEXT == 0 || END - NEXT < size.0 {
+        let chunk_size = ::core::cmp::max(
+            ::core::cmp::max(size, MIN_CHUNK_SIZE),
+            ::core::cmp::max(Bytes(2 * (END - BEGIN)), NEXT_CHUNK_SIZE),
+        );
+        let chunk = alloc_gc_blob(chunk_size);
+        BEGIN = chunk.payload_addr() as usize;
+        NEXT = BEGIN;
+        END = BEGIN + chunk_size.0;
+    }
+
+    let buf = NEXT;
+    NEXT += size.0;
+
+    if NEXT - BEGIN > USED.0 {
+        USED = Bytes(NEXT - BEGIN);
+    }
+
+    buf as *mut u8
+}
+
+#[cfg(not(feature = "gc"))]
+#[no_mangle]
+pub unsafe extern "C" fn scratch_alloc(size: Bytes<usize>) -> *mut u8 {
+    crate::alloc::alloc_blob(size).as_blob().payload_addr()
+}
+
+/// Releases the scratch space allocated in the message. Called by the collectors at the end of
+/// each message, before collecting.
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn release() {
+    NEXT = BEGIN;
+}
+
+/// Drops the current chunk. Called by the collectors when the chunk may have been reclaimed.
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn forget() {
+    NEXT = 0;
+    BEGIN = 0;
+    END = 0;
+    NEXT_CHUNK_SIZE = USED;
+    USED = Bytes(0);
+}
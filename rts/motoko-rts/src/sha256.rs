@@ -0,0 +1,175 @@
+//! SHA-256 and SHA-224 (FIPS 180-4) of blobs and texts.
+//!
+//! The leaves of a text are hashed one after the other (see `Leaves` in `text.rs`), so texts are
+//! not copied to a blob first. SHA-224 is SHA-256 with a different initial state, truncated to
+//! 28 bytes.
+
+use crate::alloc::alloc_blob;
+use crate::text::Leaves;
+use crate::types::{Bytes, SkewedPtr};
+
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA224_INIT: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4,
+];
+
+static K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const BLOCK_SIZE: usize = 64;
+
+/// Incremental SHA-256 (or SHA-224) hashing
+pub(crate) struct Sha256 {
+    state: [u32; 8],
+    /// Bytes of the current block
+    block: [u8; BLOCK_SIZE],
+    /// Number of bytes in `block`
+    block_len: usize,
+    /// Number of bytes hashed so far
+    len: u64,
+}
+
+impl Sha256 {
+    pub(crate) fn new() -> Self {
+        Self::with_state(SHA256_INIT)
+    }
+
+    pub(crate) fn new_224() -> Self {
+        Self::with_state(SHA224_INIT)
+    }
+
+    fn with_state(state: [u32; 8]) -> Self {
+        Sha256 {
+            state,
+            block: [0; BLOCK_SIZE],
+            block_len: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+
+        // Fill the current block first
+        if self.block_len > 0 {
+            let n = core::cmp::min(BLOCK_SIZE - self.block_len, bytes.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&bytes[..n]);
+            self.block_len += n;
+            bytes = &bytes[n..];
+
+            if self.block_len < BLOCK_SIZE {
+                return;
+            }
+
+            let block = self.block;
+            self.compress(&block);
+            self.block_len = 0;
+        }
+
+        // Then hash the full blocks in place
+        let mut blocks = bytes.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+
+        let rest = blocks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.block_len = rest.len();
+    }
+
+    /// Pads the message and writes the hash (the first `out.len()` bytes of it) to `out`
+    pub(crate) fn finish(mut self, out: &mut [u8]) {
+        let bit_len = self.len.wrapping_mul(8);
+
+        // A single 1 bit, zeros up to 8 bytes before the end of a block, then the length
+        let zeros = (BLOCK_SIZE * 2 - 9 - self.block_len) % BLOCK_SIZE;
+        let mut padding = [0u8; BLOCK_SIZE + 8];
+        padding[0] = 0x80;
+        padding[1 + zeros..9 + zeros].copy_from_slice(&bit_len.to_be_bytes());
+        self.update(&padding[..9 + zeros]);
+        debug_assert_eq!(self.block_len, 0);
+
+        for (chunk, word) in out.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, x) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+            *s = s.wrapping_add(*x);
+        }
+    }
+}
+
+/// Hashes the blob or text, returns a blob with the first `size` bytes of the hash
+unsafe fn hash(mut hasher: Sha256, s: SkewedPtr, size: usize) -> SkewedPtr {
+    let mut leaves = Leaves::new(s);
+    while let Some(bytes) = leaves.next() {
+        hasher.update(bytes);
+    }
+
+    let r = alloc_blob(Bytes(size));
+    let blob = r.as_blob();
+    hasher.finish(core::slice::from_raw_parts_mut(blob.payload_addr(), size));
+    r
+}
+
+/// SHA-256 of a blob or a text, a blob of 32 bytes
+#[no_mangle]
+pub unsafe extern "C" fn blob_sha256(s: SkewedPtr) -> SkewedPtr {
+    hash(Sha256::new(), s, 32)
+}
+
+/// SHA-224 of a blob or a text, a blob of 28 bytes
+#[no_mangle]
+pub unsafe extern "C" fn blob_sha224(s: SkewedPtr) -> SkewedPtr {
+    hash(Sha256::new_224(), s, 28)
+}
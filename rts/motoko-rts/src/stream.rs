@@ -0,0 +1,177 @@
+//! Output streams, for writing a large result in chunks instead of building it in one allocation
+//! and copying it
+
+use crate::alloc::alloc_blob;
+use crate::region::{region_reserve, region_write};
+use crate::types::{Bytes, SkewedPtr};
+
+use core::mem::size_of;
+
+/// A sink for bytes, written in order
+pub trait Stream {
+    fn write(&mut self, bytes: &[u8]);
+
+    /// Number of bytes written so far
+    fn written(&self) -> usize;
+}
+
+/// A stream into a slice, which must have room for all bytes written to it
+pub struct SliceStream<'a> {
+    slice: &'a mut [u8],
+    /// Number of bytes written so far
+    len: usize,
+}
+
+impl<'a> SliceStream<'a> {
+    pub fn new(slice: &'a mut [u8]) -> Self {
+        SliceStream { slice, len: 0 }
+    }
+}
+
+impl<'a> Stream for SliceStream<'a> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.slice[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn written(&self) -> usize {
+        self.len
+    }
+}
+
+/// A stream that only counts the bytes written to it, for the size of an output before it is
+/// allocated
+#[derive(Default)]
+pub struct CountStream {
+    len: usize,
+}
+
+impl CountStream {
+    pub fn new() -> Self {
+        CountStream { len: 0 }
+    }
+}
+
+impl Stream for CountStream {
+    fn write(&mut self, bytes: &[u8]) {
+        self.len += bytes.len();
+    }
+
+    fn written(&self) -> usize {
+        self.len
+    }
+}
+
+/// Size of the chunks written to stable memory by `RegionStream`
+pub const CHUNK_SIZE: usize = 4096;
+
+/// A stream into a region of stable memory (see `region.rs`), which must be large enough for all
+/// bytes written to it, or grows with them when `growing`. Bytes are buffered and written in
+/// chunks of `CHUNK_SIZE`, as every write to stable memory is a system call. `flush` writes the
+/// buffered bytes.
+///
+/// Packed, as it's also a heap object, for the generated code (see `region_stream_new`).
+#[repr(packed)]
+pub struct RegionStream {
+    region: u32,
+    /// Offset in the region of the buffered bytes
+    offset: u64,
+    buf: [u8; CHUNK_SIZE],
+    /// Number of buffered bytes
+    len: usize,
+    /// Number of bytes written so far
+    written: usize,
+    /// Whether the region grows with the bytes written
+    grow: bool,
+}
+
+impl RegionStream {
+    /// A stream writing from `offset` of the region
+    pub fn new(region: u32, offset: u64) -> Self {
+        RegionStream {
+            region,
+            offset,
+            buf: [0; CHUNK_SIZE],
+            len: 0,
+            written: 0,
+            grow: false,
+        }
+    }
+
+    /// A stream writing from `offset` of the region, growing the region when it's not large
+    /// enough
+    pub fn growing(region: u32, offset: u64) -> Self {
+        RegionStream {
+            grow: true,
+            ..RegionStream::new(region, offset)
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.write_through(self.buf.as_ptr(), self.len);
+        self.len = 0;
+    }
+
+    fn write_through(&mut self, bytes: *const u8, len: usize) {
+        if self.grow {
+            unsafe { region_reserve(self.region, self.offset + len as u64) };
+        }
+        unsafe { region_write(self.region, self.offset, bytes, len as u32) };
+        self.offset += len as u64;
+    }
+}
+
+impl Stream for RegionStream {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.len + bytes.len() > CHUNK_SIZE {
+            self.flush();
+        }
+        if bytes.len() >= CHUNK_SIZE {
+            // Large writes (of blobs and texts) are not copied to the buffer
+            self.write_through(bytes.as_ptr(), bytes.len());
+        } else {
+            self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        }
+        self.written += bytes.len();
+    }
+
+    fn written(&self) -> usize {
+        self.written
+    }
+}
+
+// Streams for the generated code, for logs or journals written to a region by the program (see
+// `regionStream` in `prim.mo`). The stream is the payload of a blob, it has no pointers, so the GC
+// can move it. The buffered bytes are lost when the stream is dropped (or on an upgrade) before it
+// is flushed.
+
+unsafe fn stream_of_blob(blob: SkewedPtr) -> *mut RegionStream {
+    blob.as_blob().payload_addr() as *mut RegionStream
+}
+
+/// A stream writing from `offset` of the region, in a blob
+#[no_mangle]
+pub unsafe extern "C" fn region_stream_new(region: u32, offset: u64) -> SkewedPtr {
+    let blob = alloc_blob(Bytes(size_of::<RegionStream>()));
+    core::ptr::write(stream_of_blob(blob), RegionStream::new(region, offset));
+    blob
+}
+
+/// Writes the bytes of the blob `bytes` to the stream
+#[no_mangle]
+pub unsafe extern "C" fn region_stream_write(stream: SkewedPtr, bytes: SkewedPtr) {
+    let bytes = bytes.as_blob();
+    (*stream_of_blob(stream)).write(core::slice::from_raw_parts(
+        bytes.payload_addr(),
+        bytes.len().0,
+    ));
+}
+
+/// Flushes the stream, returns the offset in the region after the bytes written
+#[no_mangle]
+pub unsafe extern "C" fn region_stream_flush(stream: SkewedPtr) -> u64 {
+    let stream = stream_of_blob(stream);
+    (*stream).flush();
+    (*stream).offset
+}
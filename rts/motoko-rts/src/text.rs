@@ -12,54 +12,126 @@
 //!
 //! In a subsequent step, the actual concatenation node has been introduced.
 //!
+//! Concat nodes record the depth of the tree below them. When a concatenation would create a node
+//! deeper than `MAX_DEPTH`, the tree is rebalanced (see `rebalance`), so the depth of a text stays
+//! logarithmic in its size, and the recursive functions (`text_compare` etc.) use bounded stack.
+//!
+//! Slice nodes (`text_slice`) refer to a part of a blob, so that taking a substring doesn't copy
+//! the bytes. A slice keeps the whole blob alive.
+//!
+//! Texts shorter than `MIN_CONCAT_SIZE` created by the RTS are small texts (see `SmallText` in
+//! `types.rs`), with the bytes in the object and without a length field, which saves a word over
+//! a blob. Text literals of the generated code are blobs, so short texts can be either.
+//!
 //! From here on, there are stretch goals like:
 //!  - restructure recursive code to not use unbounded Rust stack
-//!  - maybe rebalancing
 
 // Layout of a concat node:
 //
-//      ┌──────────────┬─────────┬───────┬───────┐
-//      │ tag (concat) │ n_bytes │ text1 │ text2 │
-//      └──────────────┴─────────┴───────┴───────┘
+//      ┌──────────────┬─────────┬───────┬───────┬───────┐
+//      │ tag (concat) │ n_bytes │ text1 │ text2 │ depth │
+//      └──────────────┴─────────┴───────┴───────┴───────┘
+//
+// Layout of a slice node:
+//
+//      ┌─────────────┬─────────┬──────┬────────┐
+//      │ tag (slice) │ n_bytes │ blob │ offset │
+//      └─────────────┴─────────┴──────┴────────┘
+//
+// Layout of a small text, with one or two payload words:
+//
+//      ┌──────────────────┬────────────────────────────┐
+//      │ tag (small text) │ bytes, padded with 0xFF    │
+//      └──────────────────┴────────────────────────────┘
+//
+// Note that `CONCAT_LEN`, `SLICE_LEN` and `BLOB_LEN` are identical, so only small texts need a
+// check of the tag to know the size of the text.
 //
-// Note that `CONCAT_LEN` and `BLOB_LEN` are identical, so no need to check the tag to know the
-// size of the text.
+// Blobs, slices and small texts are the leaves of the tree, a slice never refers to another slice
+// or a concat, but always to a blob.
 
 use crate::alloc::{alloc_blob, alloc_words};
+use crate::char::{
+    char_is_case_ignorable, char_is_cased, to_lower_full, to_upper_full, CaseMapping,
+};
+use crate::error_code::ErrorCode;
 use crate::mem::memcpy_bytes;
 use crate::rts_trap_with;
-use crate::types::{size_of, Blob, Bytes, Concat, SkewedPtr, TAG_BLOB, TAG_CONCAT};
+use crate::types::{
+    size_of, Blob, Bytes, Concat, SkewedPtr, Slice, SmallText, Words, SMALL_TEXT_MAX_SIZE,
+    TAG_BLOB, TAG_CONCAT, TAG_SLICE, TAG_SMALL_TEXT, WORD_SIZE,
+};
+use crate::utf8::{utf16_units, utf16_valid, utf8_lossy_parts, utf8_valid};
 
-use core::cmp::{min, Ordering};
+use core::cmp::{max, min, Ordering};
 use core::{slice, str};
 
-const MAX_STR_SIZE: Bytes<u32> = Bytes((1 << 30) - 1);
+const MAX_STR_SIZE: Bytes<usize> = Bytes((1 << 30) - 1);
 
-// Strings smaller than this must be blobs
+// Strings smaller than this must be blobs (not concats or slices)
 // Make this MAX_STR_SIZE to disable the use of ropes completely, e.g. for debugging
-const MIN_CONCAT_SIZE: Bytes<u32> = Bytes(9);
+const MIN_CONCAT_SIZE: Bytes<usize> = Bytes(9);
 
-unsafe fn alloc_text_blob(size: Bytes<u32>) -> SkewedPtr {
+// Concatenations that would create a deeper node rebalance the tree. Balanced texts of size up to
+// `MAX_STR_SIZE` are less deep than this.
+const MAX_DEPTH: usize = 48;
+
+unsafe fn alloc_text_blob(size: Bytes<usize>) -> SkewedPtr {
     if size > MAX_STR_SIZE {
-        rts_trap_with("alloc_text_bloc: Text too large");
+        rts_trap_with(ErrorCode::OutOfMemory, "alloc_text_bloc: Text too large");
     }
     alloc_blob(size)
 }
 
+/// Allocates a small text with the given size, with all bytes padding
+unsafe fn alloc_small_text(size: Bytes<usize>) -> SkewedPtr {
+    debug_assert!(size <= SMALL_TEXT_MAX_SIZE);
+
+    let payload_words = if size.0 < WORD_SIZE { 1 } else { 2 };
+    let r = alloc_words(size_of::<SmallText>() + Words(payload_words));
+    let small_text = r.unskew() as *mut SmallText;
+    (*small_text).header.tag = TAG_SMALL_TEXT;
+    small_text
+        .payload_addr()
+        .write_bytes(0xFF, payload_words * WORD_SIZE);
+    r
+}
+
+/// Allocates a text with the given size: a small text when it's short, a blob otherwise. Returns
+/// the text and its payload, which the caller fills with exactly `size` bytes.
+pub(crate) unsafe fn alloc_text(size: Bytes<usize>) -> (SkewedPtr, *mut u8) {
+    if size < MIN_CONCAT_SIZE && size <= SMALL_TEXT_MAX_SIZE {
+        let r = alloc_small_text(size);
+        (r, r.as_small_text().payload_addr())
+    } else {
+        let r = alloc_text_blob(size);
+        (r, r.as_blob().payload_addr())
+    }
+}
+
 #[no_mangle]
-pub unsafe extern "C" fn text_of_ptr_size(buf: *const u8, n: Bytes<u32>) -> SkewedPtr {
-    let blob = alloc_text_blob(n);
-    let payload_addr = blob.as_blob().payload_addr();
+pub unsafe extern "C" fn text_of_ptr_size(buf: *const u8, n: Bytes<usize>) -> SkewedPtr {
+    let (text, payload_addr) = alloc_text(n);
     memcpy_bytes(payload_addr as usize, buf as usize, n);
-    blob
+    text
 }
 
 pub unsafe fn text_of_str(s: &str) -> SkewedPtr {
-    text_of_ptr_size(s.as_ptr(), Bytes(s.len() as u32))
+    text_of_ptr_size(s.as_ptr(), Bytes(s.len()))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn text_concat(s1: SkewedPtr, s2: SkewedPtr) -> SkewedPtr {
+    let r = concat(s1, s2);
+    if text_depth(r) > MAX_DEPTH {
+        rebalance(r)
+    } else {
+        r
+    }
+}
+
+/// Concatenation without rebalancing
+unsafe fn concat(s1: SkewedPtr, s2: SkewedPtr) -> SkewedPtr {
     let blob1_len = text_size(s1);
     let blob2_len = text_size(s2);
 
@@ -73,19 +145,18 @@ pub unsafe extern "C" fn text_concat(s1: SkewedPtr, s2: SkewedPtr) -> SkewedPtr
 
     let new_len = blob1_len + blob2_len;
 
-    // Short texts are copied into a single blob
+    // Short texts are copied into a single leaf
     if new_len < MIN_CONCAT_SIZE {
-        // Because lengths of texts are smaller than MIN_CONCAT_SIZE we know both are blobs so the
-        // casts are safe
-        let blob1 = s1.as_blob();
-        let blob2 = s2.as_blob();
-
-        let r = alloc_text_blob(new_len);
-        let r_payload: *const u8 = r.as_blob().payload_addr();
-        memcpy_bytes(r_payload as usize, blob1.payload_addr() as usize, blob1_len);
+        // Because lengths of texts are smaller than MIN_CONCAT_SIZE we know both are blobs or
+        // small texts
+        let (payload1, _) = leaf_bytes(s1);
+        let (payload2, _) = leaf_bytes(s2);
+
+        let (r, r_payload) = alloc_text(new_len);
+        memcpy_bytes(r_payload as usize, payload1 as usize, blob1_len);
         memcpy_bytes(
-            r_payload.add(blob1_len.0 as usize) as usize,
-            blob2.payload_addr() as usize,
+            r_payload.add(blob1_len.0) as usize,
+            payload2 as usize,
             blob2_len,
         );
 
@@ -94,7 +165,7 @@ pub unsafe extern "C" fn text_concat(s1: SkewedPtr, s2: SkewedPtr) -> SkewedPtr
 
     // Check max size
     if new_len > MAX_STR_SIZE {
-        rts_trap_with("text_concat: Text too large");
+        rts_trap_with(ErrorCode::OutOfMemory, "text_concat: Text too large");
     }
 
     // Create concat node
@@ -104,9 +175,203 @@ pub unsafe extern "C" fn text_concat(s1: SkewedPtr, s2: SkewedPtr) -> SkewedPtr
     (*r_concat).n_bytes = new_len;
     (*r_concat).text1 = s1;
     (*r_concat).text2 = s2;
+    (*r_concat).depth = max(text_depth(s1), text_depth(s2)) + 1;
     r
 }
 
+/// Depth of the concat tree of a text, 0 for blobs and slices
+unsafe fn text_depth(s: SkewedPtr) -> usize {
+    if s.tag() == TAG_CONCAT {
+        (*s.as_concat()).depth
+    } else {
+        0
+    }
+}
+
+/// Minimum size of a balanced text of the given depth: the (depth + 2)th Fibonacci number
+fn min_balanced_size(depth: usize) -> Bytes<usize> {
+    let (mut a, mut b) = (1usize, 2usize);
+    for _ in 0..depth {
+        let c = a.saturating_add(b);
+        a = b;
+        b = c;
+    }
+    Bytes(a)
+}
+
+/// Builds a balanced text with the same contents as `s`, as described in "Ropes: an Alternative to
+/// Strings" (Boehm, Atkinson, Plass, 1995).
+///
+/// The leaves of the tree, and the subtrees that are balanced already, are added from left to
+/// right to a forest of balanced texts: slot `i` holds a text of size in
+/// `[min_balanced_size(i), min_balanced_size(i + 1))`, and the texts in higher slots come first.
+/// Adding a text concatenates it with the smaller texts in the forest, and then with the texts
+/// in the slots it grows into. The result is the concatenation of the forest.
+///
+/// A text built by appending leaf by leaf is rebalanced every `MAX_DEPTH` or so concatenations,
+/// but the balanced subtree of the previous rebalancing is kept as it is.
+unsafe fn rebalance(s: SkewedPtr) -> SkewedPtr {
+    let mut forest = [SkewedPtr(0); MAX_DEPTH];
+    add_to_forest(&mut forest, s);
+
+    let mut sum: Option<SkewedPtr> = None;
+    for &slot in forest.iter() {
+        if slot.0 != 0 {
+            sum = Some(concat_opt(Some(slot), sum));
+        }
+    }
+
+    sum.unwrap()
+}
+
+/// Adds the leaves and the balanced subtrees of `s` to the forest, see `rebalance`
+unsafe fn add_to_forest(forest: &mut [SkewedPtr; MAX_DEPTH], s: SkewedPtr) {
+    if s.tag() == TAG_CONCAT {
+        let concat = s.as_concat();
+        let depth = (*concat).depth;
+        if depth >= MAX_DEPTH || (*concat).n_bytes < min_balanced_size(depth) {
+            add_to_forest(forest, (*concat).text1);
+            add_to_forest(forest, (*concat).text2);
+            return;
+        }
+    }
+
+    let size = text_size(s);
+
+    // Concatenate the smaller texts, and `s`
+    let mut i = 0;
+    let mut sum: Option<SkewedPtr> = None;
+    while size >= min_balanced_size(i + 1) {
+        if forest[i].0 != 0 {
+            sum = Some(concat_opt(Some(forest[i]), sum));
+            forest[i] = SkewedPtr(0);
+        }
+        i += 1;
+    }
+    let mut sum = concat_opt(sum, Some(s));
+
+    // Concatenate the texts in the slots the sum grows into
+    while text_size(sum) >= min_balanced_size(i) {
+        if forest[i].0 != 0 {
+            sum = concat_opt(Some(forest[i]), Some(sum));
+            forest[i] = SkewedPtr(0);
+        }
+        i += 1;
+    }
+
+    forest[i - 1] = sum;
+}
+
+/// Concatenation of optional texts for `rebalance`, at least one is given
+unsafe fn concat_opt(s1: Option<SkewedPtr>, s2: Option<SkewedPtr>) -> SkewedPtr {
+    match (s1, s2) {
+        (Some(s1), Some(s2)) => concat(s1, s2),
+        (Some(s), None) | (None, Some(s)) => s,
+        (None, None) => unreachable!(),
+    }
+}
+
+/// Returns a text with the `len` bytes of `s` from `start` on. Traps when the range is out of
+/// bounds, or doesn't begin or end at a character boundary.
+///
+/// The bytes are not copied (unless there are less than `MIN_CONCAT_SIZE`): the result refers to
+/// the blobs of `s`, with slice nodes for partial blobs and concat nodes when the range spans
+/// several leaves. This allocates at most two nodes per level of the concat tree of `s`.
+#[no_mangle]
+pub unsafe extern "C" fn text_slice(
+    s: SkewedPtr,
+    start: Bytes<usize>,
+    len: Bytes<usize>,
+) -> SkewedPtr {
+    let size = text_size(s);
+    if start > size || len > size - start {
+        rts_trap_with(ErrorCode::OutOfBounds, "text_slice: out of bounds");
+    }
+
+    if !is_char_boundary(s, start) || !is_char_boundary(s, start + len) {
+        rts_trap_with(
+            ErrorCode::OutOfBounds,
+            "text_slice: not at a character boundary",
+        );
+    }
+
+    sub_text(s, start, len)
+}
+
+/// `text_slice`, with the range checked
+unsafe fn sub_text(s: SkewedPtr, start: Bytes<usize>, len: Bytes<usize>) -> SkewedPtr {
+    if start == Bytes(0) && len == text_size(s) {
+        return s;
+    }
+
+    if s.tag() == TAG_CONCAT {
+        let s_concat = s.as_concat();
+        let left = (*s_concat).text1;
+        let right = (*s_concat).text2;
+        let left_size = text_size(left);
+
+        return if start + len <= left_size {
+            sub_text(left, start, len)
+        } else if start >= left_size {
+            sub_text(right, start - left_size, len)
+        } else {
+            // Not deeper than `s`, no need to rebalance
+            concat(
+                sub_text(left, start, left_size - start),
+                sub_text(right, Bytes(0), start + len - left_size),
+            )
+        };
+    }
+
+    // Short texts are copied. Small texts are short.
+    if len < MIN_CONCAT_SIZE {
+        let (payload, _) = leaf_bytes(s);
+        return text_of_ptr_size(payload.add(start.0), len);
+    }
+
+    let (blob, offset) = if s.tag() == TAG_SLICE {
+        let s_slice = s.as_slice();
+        ((*s_slice).blob, (*s_slice).offset)
+    } else {
+        (s, Bytes(0))
+    };
+
+    let r = alloc_words(size_of::<Slice>());
+    let r_slice = r.unskew() as *mut Slice;
+    (*r_slice).header.tag = TAG_SLICE;
+    (*r_slice).n_bytes = len;
+    (*r_slice).blob = blob;
+    (*r_slice).offset = offset + start;
+    r
+}
+
+/// Whether the byte at `offset` begins a character, or is the end of the text
+unsafe fn is_char_boundary(s: SkewedPtr, offset: Bytes<usize>) -> bool {
+    if offset == text_size(s) {
+        return true;
+    }
+
+    let (leaf, offset) = text_get_range(s, offset, Bytes(1));
+    let (payload, _) = leaf_bytes(leaf);
+    (*payload.add(offset.0) & 0b1100_0000) != 0b1000_0000
+}
+
+/// Returns the payload and the size of a leaf: a blob, a slice, or a small text
+pub(crate) unsafe fn leaf_bytes(s: SkewedPtr) -> (*const u8, Bytes<usize>) {
+    let tag = s.tag();
+    if tag == TAG_SLICE {
+        let s_slice = s.as_slice();
+        let payload = (*s_slice).blob.as_blob().payload_addr();
+        (payload.add((*s_slice).offset.0), (*s_slice).n_bytes)
+    } else if tag == TAG_SMALL_TEXT {
+        let small_text = s.as_small_text();
+        (small_text.payload_addr(), small_text.len())
+    } else {
+        let blob = s.as_blob();
+        (blob.payload_addr(), blob.len())
+    }
+}
+
 // Leaving breadcrumbs in the destination buffer for which concat node/blob to continue
 // serializing
 #[repr(packed)]
@@ -117,15 +382,18 @@ struct Crumb {
     next: *const Crumb,
 }
 
+/// Writes the bytes of the text to `buf`, which must have room for `text_size(s)` bytes, e.g. a
+/// serialization buffer, without allocating. The parts of the buffer not written yet are used for
+/// breadcrumbs to the right subtrees of concatenations.
 #[no_mangle]
-unsafe extern "C" fn text_to_buf(mut s: SkewedPtr, mut buf: *mut u8) {
+pub unsafe extern "C" fn text_to_buf(mut s: SkewedPtr, mut buf: *mut u8) {
     let mut next_crumb: *const Crumb = core::ptr::null();
 
     loop {
         let s_ptr = s.as_obj();
-        if s_ptr.tag() == TAG_BLOB {
-            let blob = s_ptr.as_blob();
-            memcpy_bytes(buf as usize, blob.payload_addr() as usize, blob.len());
+        if s_ptr.tag() != TAG_CONCAT {
+            let (payload, len) = leaf_bytes(s);
+            memcpy_bytes(buf as usize, payload as usize, len);
 
             if next_crumb.is_null() {
                 return;
@@ -135,7 +403,6 @@ unsafe extern "C" fn text_to_buf(mut s: SkewedPtr, mut buf: *mut u8) {
             s = (*next_crumb).t;
             next_crumb = (*next_crumb).next;
         } else {
-            debug_assert_eq!(s_ptr.tag(), TAG_CONCAT);
             let concat = s_ptr as *const Concat;
             let s1 = (*concat).text1;
             let s2 = (*concat).text2;
@@ -143,13 +410,13 @@ unsafe extern "C" fn text_to_buf(mut s: SkewedPtr, mut buf: *mut u8) {
             let s1_len = text_size(s1);
             let s2_len = text_size(s2);
 
-            if s2_len < Bytes(core::mem::size_of::<Crumb>() as u32) {
+            if s2_len < Bytes(core::mem::size_of::<Crumb>()) {
                 // If second string is smaller than size of a crumb just do it directly
-                text_to_buf(s2, buf.add(s1_len.0 as usize));
+                text_to_buf(s2, buf.add(s1_len.0));
                 s = s1;
             } else {
                 // Otherwise leave a breadcrumb to the location of the second string
-                let new_crumb: *mut Crumb = buf.add(s1_len.0 as usize) as *mut Crumb;
+                let new_crumb: *mut Crumb = buf.add(s1_len.0) as *mut Crumb;
                 (*new_crumb).t = s2;
                 (*new_crumb).next = next_crumb;
                 next_crumb = new_crumb;
@@ -166,97 +433,194 @@ pub unsafe extern "C" fn blob_of_text(s: SkewedPtr) -> SkewedPtr {
     if obj.tag() == TAG_BLOB {
         s
     } else {
-        let concat = obj.as_concat();
-        let r = alloc_text_blob((*concat).n_bytes);
+        let r = alloc_text_blob(text_size(s));
         text_to_buf(s, r.as_blob().payload_addr());
         r
     }
 }
 
-/// Size of the text, in bytes
+/// The text as a `str`. Texts that aren't leaves are copied to a blob.
+pub(crate) unsafe fn text_str(s: SkewedPtr) -> &'static str {
+    let leaf = if s.tag() == TAG_CONCAT {
+        blob_of_text(s)
+    } else {
+        s
+    };
+    let (payload, len) = leaf_bytes(leaf);
+    str::from_utf8_unchecked(slice::from_raw_parts(payload, len.0))
+}
+
+/// Encodes the text in UTF-16 (little-endian, without byte order mark), returns a blob
 #[no_mangle]
-pub unsafe extern "C" fn text_size(s: SkewedPtr) -> Bytes<u32> {
-    // We don't know whether the string is a blob or concat, but both types have the length in same
-    // location so using any of the types to get the length is fine
-    // NB. We can't use `s.as_blob()` here as that method checks the tag in debug mode
-    (s.unskew() as *mut Blob).len()
+pub unsafe extern "C" fn text_to_utf16_blob(s: SkewedPtr) -> SkewedPtr {
+    let chars = text_str(s);
+
+    let n_units = chars.encode_utf16().count();
+    let r = alloc_blob(Bytes(n_units * 2));
+    let buf = slice::from_raw_parts_mut(r.as_blob().payload_addr(), n_units * 2);
+    for (unit, bytes) in chars.encode_utf16().zip(buf.chunks_exact_mut(2)) {
+        bytes.copy_from_slice(&unit.to_le_bytes());
+    }
+
+    r
 }
 
-/// Compares texts from given offset on for the given number of bytes. All assumed to be in range.
-unsafe fn text_compare_range(
-    s1: SkewedPtr,
-    offset1: Bytes<u32>,
-    s2: SkewedPtr,
-    offset2: Bytes<u32>,
-    n: Bytes<u32>,
-) -> Ordering {
-    // Follow the left/right strings of concat nodes until we reach to blobs or concats that cannot
-    // be split further (the range spans left and right strings)
-    let (s1, offset1) = text_get_range(s1, offset1, n);
-    let (s2, offset2) = text_get_range(s2, offset2, n);
-
-    let s1_obj = s1.as_obj();
-    let s2_obj = s2.as_obj();
-
-    // Decompose concats
-    if s1_obj.tag() == TAG_CONCAT {
-        let s1_concat = s1_obj as *const Concat;
-        let n_compared = text_size((*s1_concat).text1) - offset1;
-        let cmp = text_compare_range((*s1_concat).text1, offset1, s2, offset2, n_compared);
-        match cmp {
-            Ordering::Less | Ordering::Greater => cmp,
-            Ordering::Equal => text_compare_range(
-                (*s1_concat).text2,
-                Bytes(0),
-                s2,
-                offset2 + n_compared,
-                n - n_compared,
-            ),
-        }
-    } else if s2_obj.tag() == TAG_CONCAT {
-        let s2_concat = s2_obj as *const Concat;
-        let n_compared = text_size((*s2_concat).text1) - offset2;
-        let cmp = text_compare_range(s1, offset1, (*s2_concat).text1, offset2, n_compared);
-        match cmp {
-            Ordering::Less | Ordering::Greater => cmp,
-            Ordering::Equal => text_compare_range(
-                s1,
-                offset1 + n_compared,
-                (*s2_concat).text2,
-                Bytes(0),
-                n - n_compared,
-            ),
-        }
-    } else {
-        debug_assert_eq!(s1_obj.tag(), TAG_BLOB);
-        debug_assert_eq!(s2_obj.tag(), TAG_BLOB);
+/// Decodes a UTF-16 (little-endian, without byte order mark) blob. Traps when it's not UTF-16,
+/// see `utf16_valid`.
+#[no_mangle]
+pub unsafe extern "C" fn text_of_utf16_blob(b: SkewedPtr) -> SkewedPtr {
+    let blob = b.as_blob();
+    let (payload, len) = (blob.payload_addr(), blob.len().0);
+
+    if !utf16_valid(payload, len) {
+        rts_trap_with(ErrorCode::Utf8, "text_of_utf16_blob: blob is not UTF-16");
+    }
+
+    text_of_chars(char::decode_utf16(utf16_units(payload, len)).map(|c| c.unwrap()))
+}
+
+/// Decodes the blob as UTF-8, replacing invalid sequences with U+FFFD. Returns the blob itself when
+/// it's valid UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn text_of_blob_lossy(b: SkewedPtr) -> SkewedPtr {
+    let blob = b.as_blob();
+    let (payload, len) = (blob.payload_addr(), blob.len().0);
+
+    if utf8_valid(payload as *const _, len) {
+        return b;
+    }
+
+    let mut size = 0;
+    utf8_lossy_parts(payload, len, |part| size += part.len());
+
+    let (r, mut dest) = alloc_text(Bytes(size));
+    utf8_lossy_parts(payload, len, |part| {
+        memcpy_bytes(dest as usize, part.as_ptr() as usize, Bytes(part.len()));
+        dest = dest.add(part.len());
+    });
+    r
+}
+
+/// Returns the text repeated `n` times, as a single leaf: `s` is copied once, then the filled
+/// prefix of the result is doubled until it's full
+#[no_mangle]
+pub unsafe extern "C" fn text_repeat(s: SkewedPtr, n: usize) -> SkewedPtr {
+    if n == 1 {
+        return s;
+    }
 
-        let s1_blob = s1_obj.as_blob();
-        let s2_blob = s2_obj.as_blob();
+    let size = text_size(s);
+    let total = match size.0.checked_mul(n) {
+        Some(total) if Bytes(total) <= MAX_STR_SIZE => Bytes(total),
+        _ => rts_trap_with(ErrorCode::OutOfMemory, "text_repeat: Text too large"),
+    };
 
-        let cmp = libc::memcmp(
-            s1_blob.payload_addr().add(offset1.0 as usize) as *const _,
-            s2_blob.payload_addr().add(offset2.0 as usize) as *const _,
-            n.0 as usize,
+    let (r, payload) = alloc_text(total);
+    if total == Bytes(0) {
+        return r;
+    }
+
+    text_to_buf(s, payload);
+
+    let mut filled = size;
+    while filled < total {
+        let n = min(filled, total - filled);
+        memcpy_bytes(payload.add(filled.0) as usize, payload as usize, n);
+        filled += n;
+    }
+
+    r
+}
+
+/// Returns a blob of `n` bytes with the value `byte`
+#[no_mangle]
+pub unsafe extern "C" fn blob_fill(byte: u32, n: Bytes<usize>) -> SkewedPtr {
+    let r = alloc_blob(n);
+    r.as_blob().payload_addr().write_bytes(byte as u8, n.0);
+    r
+}
+
+/// Returns the concatenation of an array of blobs, allocated at once (unlike repeated
+/// `b1 # b2`, which copies the prefix for every piece)
+#[no_mangle]
+pub unsafe extern "C" fn blob_concat_many(blobs: SkewedPtr) -> SkewedPtr {
+    let blobs = blobs.as_array();
+
+    let mut total = Bytes(0usize);
+    for i in 0..blobs.len() {
+        total = match total.0.checked_add(blobs.get(i).as_blob().len().0) {
+            Some(total) => Bytes(total),
+            None => rts_trap_with(ErrorCode::OutOfMemory, "blob_concat_many: Blob too large"),
+        };
+    }
+
+    let r = alloc_blob(total);
+    let mut dest = r.as_blob().payload_addr() as usize;
+    for i in 0..blobs.len() {
+        let blob = blobs.get(i).as_blob();
+        memcpy_bytes(dest, blob.payload_addr() as usize, blob.len());
+        dest += blob.len().0;
+    }
+
+    r
+}
+
+/// Returns a copy of the blob `dst` with the `len` bytes from `dst_off` replaced by the `len`
+/// bytes of `src` from `src_off` (blobs are immutable, so `dst` itself is not changed)
+#[no_mangle]
+pub unsafe extern "C" fn blob_copy_range(
+    dst: SkewedPtr,
+    dst_off: usize,
+    src: SkewedPtr,
+    src_off: usize,
+    len: usize,
+) -> SkewedPtr {
+    let dst = dst.as_blob();
+    let src = src.as_blob();
+
+    let in_bounds = |off: usize, size: Bytes<usize>| match off.checked_add(len) {
+        Some(end) => end <= size.0,
+        None => false,
+    };
+    if !in_bounds(dst_off, dst.len()) || !in_bounds(src_off, src.len()) {
+        rts_trap_with(
+            ErrorCode::OutOfBounds,
+            "blob_copy_range: Range out of bounds",
         );
+    }
 
-        if cmp < 0 {
-            Ordering::Less
-        } else if cmp == 0 {
-            Ordering::Equal
-        } else {
-            Ordering::Greater
-        }
+    let r = alloc_blob(dst.len());
+    let payload = r.as_blob().payload_addr();
+    memcpy_bytes(payload as usize, dst.payload_addr() as usize, dst.len());
+    memcpy_bytes(
+        payload.add(dst_off) as usize,
+        src.payload_addr().add(src_off) as usize,
+        Bytes(len),
+    );
+
+    r
+}
+
+/// Size of the text, in bytes
+#[no_mangle]
+pub unsafe extern "C" fn text_size(s: SkewedPtr) -> Bytes<usize> {
+    if s.tag() == TAG_SMALL_TEXT {
+        return s.as_small_text().len();
     }
+
+    // Otherwise we don't know whether the string is a blob, concat, or slice, but all have the
+    // length in the same location so using any of the types to get the length is fine
+    // NB. We can't use `s.as_blob()` here as that method checks the tag in debug mode
+    (s.unskew() as *mut Blob).len()
 }
 
-/// Follow left/right strings of concat nodes until we reach to a BLOB or a CONCAT that can't be
-/// split further (i.e. range spans left and right nodes). Returns a BLOB or CONCAT.
+/// Follow left/right strings of concat nodes until we reach to a leaf or a CONCAT that can't be
+/// split further (i.e. range spans left and right nodes). Returns a BLOB, SLICE, or CONCAT.
 unsafe fn text_get_range(
     mut s: SkewedPtr,
-    mut offset: Bytes<u32>,
-    n: Bytes<u32>,
-) -> (SkewedPtr, Bytes<u32>) {
+    mut offset: Bytes<usize>,
+    n: Bytes<usize>,
+) -> (SkewedPtr, Bytes<usize>) {
     loop {
         let s_obj = s.as_obj();
 
@@ -278,8 +642,6 @@ unsafe fn text_get_range(
                 offset -= left_size;
                 continue;
             }
-        } else {
-            debug_assert_eq!(s_obj.tag(), TAG_BLOB);
         }
 
         break;
@@ -288,27 +650,196 @@ unsafe fn text_get_range(
     (s, offset)
 }
 
+/// Iterates over the bytes of the leaves of a text, left to right, without allocating. The
+/// concats whose right texts are still to do are kept on a stack, which is at most as deep as the
+/// text.
+pub(crate) struct Leaves {
+    todo: [SkewedPtr; MAX_DEPTH],
+    n_todo: usize,
+    next: Option<SkewedPtr>,
+}
+
+impl Leaves {
+    pub(crate) fn new(text: SkewedPtr) -> Self {
+        Leaves {
+            todo: [SkewedPtr(0); MAX_DEPTH],
+            n_todo: 0,
+            next: Some(text),
+        }
+    }
+
+    /// The bytes of the next non-empty leaf
+    pub(crate) unsafe fn next(&mut self) -> Option<&'static [u8]> {
+        loop {
+            let mut text = match self.next.take() {
+                Some(text) => text,
+                None if self.n_todo == 0 => return None,
+                None => {
+                    self.n_todo -= 1;
+                    self.todo[self.n_todo]
+                }
+            };
+
+            while text.tag() == TAG_CONCAT {
+                let concat = text.as_concat();
+                if self.n_todo == MAX_DEPTH {
+                    rts_trap_with(ErrorCode::Internal, "text_compare: text too deep");
+                }
+                self.todo[self.n_todo] = concat.text2();
+                self.n_todo += 1;
+                text = concat.text1();
+            }
+
+            let (payload, len) = leaf_bytes(text);
+            if len.0 != 0 {
+                return Some(slice::from_raw_parts(payload, len.0));
+            }
+        }
+    }
+}
+
+/// Hash of the bytes of the text (FNV-1a), independent of how the text is split into leaves.
+/// Doesn't allocate.
+pub(crate) unsafe fn text_hash(s: SkewedPtr) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut leaves = Leaves::new(s);
+    while let Some(bytes) = leaves.next() {
+        for byte in bytes {
+            hash = (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193);
+        }
+    }
+    hash
+}
+
+/// Compares the texts leaf by leaf, stopping at the first difference. Doesn't allocate.
 #[no_mangle]
 pub unsafe extern "C" fn text_compare(s1: SkewedPtr, s2: SkewedPtr) -> i32 {
-    let n1 = text_size(s1);
-    let n2 = text_size(s2);
-    let n = min(n1, n2);
+    if s1 == s2 {
+        return 0;
+    }
 
-    match text_compare_range(s1, Bytes(0), s2, Bytes(0), n) {
-        Ordering::Less => -1,
-        Ordering::Greater => 1,
-        Ordering::Equal => {
-            if n1 > n {
-                1
-            } else if n2 > n {
-                -1
-            } else {
-                0
+    let mut leaves1 = Leaves::new(s1);
+    let mut leaves2 = Leaves::new(s2);
+    let mut bytes1: &[u8] = &[];
+    let mut bytes2: &[u8] = &[];
+
+    loop {
+        if bytes1.is_empty() {
+            bytes1 = leaves1.next().unwrap_or(&[]);
+        }
+        if bytes2.is_empty() {
+            bytes2 = leaves2.next().unwrap_or(&[]);
+        }
+
+        match (bytes1.is_empty(), bytes2.is_empty()) {
+            (true, true) => return 0,
+            (true, false) => return -1,
+            (false, true) => return 1,
+            (false, false) => {}
+        }
+
+        let n = min(bytes1.len(), bytes2.len());
+        match bytes1[..n].cmp(&bytes2[..n]) {
+            Ordering::Less => return -1,
+            Ordering::Greater => return 1,
+            Ordering::Equal => {
+                bytes1 = &bytes1[n..];
+                bytes2 = &bytes2[n..];
             }
         }
     }
 }
 
+/// Returns the byte offset of the first occurrence of `needle` in `haystack`, or -1 when there is
+/// none. An empty needle is found at offset 0.
+///
+/// Uses the Boyer-Moore-Horspool algorithm. The bytes of the haystack are read through the leaves
+/// of its concat tree (see `Cursor`), the haystack is not copied. A needle that isn't a leaf is
+/// copied to a blob.
+///
+/// Texts are valid UTF-8, so matches are at character boundaries.
+#[no_mangle]
+pub unsafe extern "C" fn text_find(haystack: SkewedPtr, needle: SkewedPtr) -> isize {
+    text_find_from(haystack, needle, 0)
+}
+
+/// Like `text_find`, but only finds occurrences from byte offset `start` on (which must be in
+/// range). An empty needle is found at `start`.
+pub(crate) unsafe fn text_find_from(haystack: SkewedPtr, needle: SkewedPtr, start: usize) -> isize {
+    let n = text_size(haystack).0;
+    let m = text_size(needle).0;
+
+    if m == 0 {
+        return start as isize;
+    }
+
+    if m > n - start {
+        return -1;
+    }
+
+    let needle = if needle.tag() == TAG_CONCAT {
+        blob_of_text(needle)
+    } else {
+        needle
+    };
+    let (needle, _) = leaf_bytes(needle);
+    let needle = slice::from_raw_parts(needle, m);
+
+    // How far the window can move when its last byte is the given byte
+    let mut shifts = [m; 256];
+    for (i, byte) in needle[..m - 1].iter().enumerate() {
+        shifts[*byte as usize] = m - 1 - i;
+    }
+
+    let mut haystack = Cursor::new(haystack);
+    let mut pos = start;
+    while pos <= n - m {
+        let last = haystack.byte_at(pos + m - 1);
+        if last == needle[m - 1]
+            && (0..m - 1)
+                .rev()
+                .all(|i| haystack.byte_at(pos + i) == needle[i])
+        {
+            return pos as isize;
+        }
+        pos += shifts[last as usize];
+    }
+
+    -1
+}
+
+/// Reads bytes of a text by offset. The leaf of the last read is cached, so that reads near each
+/// other don't traverse the concat tree.
+struct Cursor {
+    text: SkewedPtr,
+    // Offset of the leaf in the text, and its bytes
+    leaf_offset: usize,
+    leaf_payload: *const u8,
+    leaf_len: usize,
+}
+
+impl Cursor {
+    fn new(text: SkewedPtr) -> Self {
+        Cursor {
+            text,
+            leaf_offset: 0,
+            leaf_payload: core::ptr::null(),
+            leaf_len: 0,
+        }
+    }
+
+    unsafe fn byte_at(&mut self, offset: usize) -> u8 {
+        if offset < self.leaf_offset || offset >= self.leaf_offset + self.leaf_len {
+            let (leaf, offset_in_leaf) = text_get_range(self.text, Bytes(offset), Bytes(1));
+            let (payload, len) = leaf_bytes(leaf);
+            self.leaf_offset = offset - offset_in_leaf.0;
+            self.leaf_payload = payload;
+            self.leaf_len = len.0;
+        }
+        *self.leaf_payload.add(offset - self.leaf_offset)
+    }
+}
+
 pub(crate) unsafe fn blob_compare(s1: SkewedPtr, s2: SkewedPtr) -> i32 {
     let n1 = text_size(s1);
     let n2 = text_size(s2);
@@ -331,23 +862,44 @@ pub(crate) unsafe fn blob_compare(s1: SkewedPtr, s2: SkewedPtr) -> i32 {
     }
 }
 
+/// Returns 1 when the blobs are equal, 0 otherwise. Unlike the other comparisons this doesn't stop
+/// at the first difference, the time it takes depends on the sizes of the blobs but not on their
+/// contents, so it can be used for secrets such as API keys or MACs.
+#[no_mangle]
+pub unsafe extern "C" fn blob_compare_const_time(b1: SkewedPtr, b2: SkewedPtr) -> u32 {
+    let blob1 = b1.as_blob();
+    let blob2 = b2.as_blob();
+
+    let len = blob1.len();
+    if blob2.len() != len {
+        return 0;
+    }
+
+    let bytes1 = slice::from_raw_parts(blob1.payload_addr(), len.0);
+    let bytes2 = slice::from_raw_parts(blob2.payload_addr(), len.0);
+
+    // Volatile accesses, so that the compiler keeps the loop as it is instead of returning early
+    let mut diff: u8 = 0;
+    for (byte1, byte2) in bytes1.iter().zip(bytes2.iter()) {
+        let d = core::ptr::read_volatile(&diff) | (byte1 ^ byte2);
+        core::ptr::write_volatile(&mut diff, d);
+    }
+
+    (diff == 0) as u32
+}
+
 /// Length in characters
 #[no_mangle]
-pub unsafe extern "C" fn text_len(text: SkewedPtr) -> u32 {
-    if text.tag() == TAG_BLOB {
-        let blob = text.as_blob();
-        let payload_addr = blob.payload_addr();
-        let len = blob.len();
-
-        str::from_utf8_unchecked(slice::from_raw_parts(
-            payload_addr as *const u8,
-            len.0 as usize,
-        ))
-        .chars()
-        .count() as u32
-    } else {
+pub unsafe extern "C" fn text_len(text: SkewedPtr) -> usize {
+    if text.tag() == TAG_CONCAT {
         let concat = text.as_concat();
         text_len(concat.text1()) + text_len(concat.text2())
+    } else {
+        let (payload_addr, len) = leaf_bytes(text);
+
+        str::from_utf8_unchecked(slice::from_raw_parts(payload_addr, len.0))
+            .chars()
+            .count()
     }
 }
 
@@ -381,15 +933,73 @@ pub unsafe fn decode_code_point(s: *const u8, size: *mut u32) -> u32 {
 #[no_mangle]
 pub unsafe extern "C" fn text_singleton(char: u32) -> SkewedPtr {
     let mut buf = [0u8; 4];
-    let str_len = char::from_u32_unchecked(char).encode_utf8(&mut buf).len() as u32;
+    let str_len = char::from_u32_unchecked(char).encode_utf8(&mut buf).len();
+
+    text_of_ptr_size(buf.as_ptr(), Bytes(str_len))
+}
 
-    let blob_ptr = alloc_text_blob(Bytes(str_len));
+/// Maps each character of the text with `f`, which returns the characters it maps to, given the
+/// text and the byte offset of the character in it. Returns the text itself when no character
+/// changes.
+unsafe fn text_map_chars<F, I>(s: SkewedPtr, f: F) -> SkewedPtr
+where
+    F: Fn(&str, usize, char) -> I,
+    I: Iterator<Item = char> + Clone,
+{
+    let chars = text_str(s);
+    let mapped = || chars.char_indices().flat_map(|(i, c)| f(chars, i, c));
+
+    if mapped().eq(chars.chars()) {
+        return s;
+    }
 
-    let blob = blob_ptr.as_blob();
+    text_of_chars(mapped())
+}
 
-    for i in 0..str_len {
-        blob.set(i, buf[i as usize]);
+/// Allocates a text with the characters. The iterator is traversed twice, first for the size.
+pub(crate) unsafe fn text_of_chars<I: Iterator<Item = char> + Clone>(chars: I) -> SkewedPtr {
+    let size = chars.clone().map(char::len_utf8).sum::<usize>();
+    let (r, payload) = alloc_text(Bytes(size));
+    let buf = slice::from_raw_parts_mut(payload, size);
+    let mut offset = 0;
+    for c in chars {
+        offset += c.encode_utf8(&mut buf[offset..]).len();
     }
+    r
+}
+
+/// Converts the text to uppercase. Uses the full case mappings of Unicode (the generated tables of
+/// `UnicodeData.txt` and `SpecialCasing.txt` in `unicode_tables.rs`), so a character can map to
+/// multiple characters, e.g. 'ß' to "SS". The mappings of languages are not applied.
+#[no_mangle]
+pub unsafe extern "C" fn text_to_upper(s: SkewedPtr) -> SkewedPtr {
+    text_map_chars(s, |_, _, c| to_upper_full(c))
+}
+
+/// Converts the text to lowercase, see `text_to_upper`. 'Σ' maps to the final sigma 'ς' at the end
+/// of a word, after a cased character and not before one (ignoring e.g. apostrophes in between),
+/// and to 'σ' otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn text_to_lower(s: SkewedPtr) -> SkewedPtr {
+    text_map_chars(s, |chars, i, c| {
+        if c == 'Σ' && is_final_sigma(chars, i) {
+            CaseMapping::single('ς' as u32)
+        } else {
+            to_lower_full(c)
+        }
+    })
+}
+
+/// The `Final_Sigma` condition of the sigma at byte offset `i`: the first character before it that
+/// isn't case-ignorable is cased, and the first one after it isn't cased
+fn is_final_sigma(chars: &str, i: usize) -> bool {
+    let (before, after) = (&chars[..i], &chars[i + 'Σ'.len_utf8()..]);
+    cased_next(before.chars().rev()) && !cased_next(after.chars())
+}
 
-    blob_ptr
+/// Whether the first character that isn't case-ignorable is cased
+fn cased_next<I: Iterator<Item = char>>(mut chars: I) -> bool {
+    chars
+        .find(|&c| !char_is_case_ignorable(c))
+        .map_or(false, char_is_cased)
 }
@@ -0,0 +1,172 @@
+//! Interning of texts.
+//!
+//! `text_intern` returns the same heap object for all texts with the same contents, so that
+//! repeated texts (e.g. the keys of a large map) are stored once, and interned texts can be
+//! compared by pointer, which `text_compare` does first.
+//!
+//! The intern table is a heap-allocated Motoko array, a GC root like the closure table, allocated
+//! by the first `text_intern`. It's a hash table with open addressing (linear probing), a slot is
+//! `FREE` or a weak reference (see `weak_ref.rs`) to an interned text, so interning does not keep
+//! texts alive. When the GC reclaims an interned text it clears the weak reference. Slots with
+//! cleared references are reused for new texts, and dropped when the table is resized.
+//!
+//! Concatenations and slices are copied to a blob when interned, so that interned texts are
+//! leaves, and don't keep the whole blob of a slice alive.
+
+use crate::alloc::{alloc_array, write_barrier};
+use crate::text::{blob_of_text, text_compare, text_hash, text_size};
+use crate::types::{SkewedPtr, TAG_CONCAT, TAG_SLICE};
+use crate::weak_ref::{weak_ref_alive, weak_ref_get, weak_ref_new};
+
+/// Initial number of slots, a power of two
+const INITIAL_SIZE: usize = 64;
+
+const FREE: SkewedPtr = SkewedPtr(0);
+
+// Skewed pointer to the `Array` object, a GC root like the closure table
+static mut TABLE: SkewedPtr = SkewedPtr(0);
+
+// Number of slots that are not free, including the ones with cleared references
+static mut N_USED: usize = 0;
+
+unsafe fn capacity() -> usize {
+    if TABLE.0 == 0 {
+        0
+    } else {
+        TABLE.as_array().len()
+    }
+}
+
+unsafe fn get_slot(idx: usize) -> SkewedPtr {
+    TABLE.as_array().get(idx)
+}
+
+unsafe fn set_slot(idx: usize, value: SkewedPtr) {
+    let array = TABLE.as_array();
+    write_barrier(array.payload_addr().add(idx));
+    array.set(idx, value);
+}
+
+/// The interned text of a weak reference that is not cleared. Unlike `weak_ref_get` this doesn't
+/// make the text reachable for the incremental GC, so it's only used for comparing and hashing.
+unsafe fn peek(weak_ref: SkewedPtr) -> SkewedPtr {
+    (*weak_ref.as_weak_ref()).field
+}
+
+/// Returns the index of the slot with the text and `true` when the text is interned. Otherwise
+/// returns the index of the slot for inserting the text (the first slot with a cleared reference
+/// on the way, or the free slot that ends the search) and `false`.
+unsafe fn find_slot(s: SkewedPtr, hash: u32) -> (usize, bool) {
+    let mask = capacity() - 1;
+    let size = text_size(s);
+    let mut insert_idx: Option<usize> = None;
+
+    let mut idx = hash as usize & mask;
+    loop {
+        let weak_ref = get_slot(idx);
+
+        if weak_ref == FREE {
+            return (insert_idx.unwrap_or(idx), false);
+        }
+
+        if weak_ref_alive(weak_ref) == 0 {
+            if insert_idx.is_none() {
+                insert_idx = Some(idx);
+            }
+        } else {
+            let text = peek(weak_ref);
+            if text_size(text) == size && text_compare(text, s) == 0 {
+                return (idx, true);
+            }
+        }
+
+        idx = (idx + 1) & mask;
+    }
+}
+
+/// Moves the live entries to a new table of at least four times their number, so at most a
+/// quarter of the new table is used
+unsafe fn resize() {
+    let old_size = capacity();
+
+    let mut n_alive = 0;
+    for i in 0..old_size {
+        let weak_ref = get_slot(i);
+        if weak_ref != FREE && weak_ref_alive(weak_ref) != 0 {
+            n_alive += 1;
+        }
+    }
+
+    let mut new_size = INITIAL_SIZE;
+    while new_size < (n_alive + 1) * 4 {
+        new_size *= 2;
+    }
+
+    let new_table = alloc_array(new_size);
+    let new_array = new_table.as_array();
+
+    for i in 0..new_size {
+        new_array.set(i, FREE);
+    }
+
+    for i in 0..old_size {
+        let weak_ref = get_slot(i);
+        if weak_ref == FREE || weak_ref_alive(weak_ref) == 0 {
+            continue;
+        }
+
+        let mut idx = text_hash(peek(weak_ref)) as usize & (new_size - 1);
+        while new_array.get(idx) != FREE {
+            idx = (idx + 1) & (new_size - 1);
+        }
+        new_array.set(idx, weak_ref);
+    }
+
+    TABLE = new_table;
+    N_USED = n_alive;
+}
+
+/// Returns the interned text with the contents of `s`: the text that was interned before, or
+/// `s` (as a leaf, see above), which is interned from now on
+#[no_mangle]
+pub unsafe extern "C" fn text_intern(s: SkewedPtr) -> SkewedPtr {
+    // At most half of the table is used, so there's always a free slot to end the search
+    if (N_USED + 1) * 2 > capacity() {
+        resize();
+    }
+
+    let (idx, found) = find_slot(s, text_hash(s));
+
+    if found {
+        return weak_ref_get(get_slot(idx));
+    }
+
+    let tag = s.tag();
+    let text = if tag == TAG_CONCAT || tag == TAG_SLICE {
+        blob_of_text(s)
+    } else {
+        s
+    };
+
+    if get_slot(idx) == FREE {
+        N_USED += 1;
+    }
+    set_slot(idx, weak_ref_new(text));
+
+    text
+}
+
+/// Number of interned texts that are alive
+pub unsafe fn text_intern_count() -> usize {
+    (0..capacity())
+        .filter(|i| {
+            let weak_ref = get_slot(*i);
+            weak_ref != FREE && weak_ref_alive(weak_ref) != 0
+        })
+        .count()
+}
+
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn text_intern_table_loc() -> *mut SkewedPtr {
+    &mut TABLE
+}
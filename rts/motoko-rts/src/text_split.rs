@@ -0,0 +1,80 @@
+//! Text split iterators yield the parts of a text between the occurrences of a delimiter, as
+//! slices of the text (see `text_slice`), finding the next occurrence on demand.
+//!
+//! The iterator is a triple (array):
+//!
+//! 1. A pointer to the text, 0 when the iterator is done
+//! 2. Position of the next part in the text (shifted by two for GC's sake)
+//! 3. A pointer to the delimiter
+//!
+//! A text with `n` occurrences of the delimiter has `n + 1` parts, which may be empty. With an
+//! empty delimiter the only part is the text itself.
+
+use crate::alloc::alloc_array;
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::text::{text_find_from, text_size, text_slice};
+use crate::text_iter::set_field;
+use crate::types::{Bytes, SkewedPtr};
+
+const SPLIT_TEXT_IDX: usize = 0;
+const SPLIT_POS_IDX: usize = 1;
+const SPLIT_DELIMITER_IDX: usize = 2;
+
+/// Returns a new split iterator for the text
+#[no_mangle]
+pub unsafe extern "C" fn text_split(text: SkewedPtr, delimiter: SkewedPtr) -> SkewedPtr {
+    let iter = alloc_array(3);
+    let array = iter.as_array();
+    array.set(SPLIT_TEXT_IDX, text);
+    array.set(SPLIT_POS_IDX, SkewedPtr(0));
+    array.set(SPLIT_DELIMITER_IDX, delimiter);
+    iter
+}
+
+/// Returns whether the split iterator is finished
+#[no_mangle]
+pub unsafe extern "C" fn text_split_done(iter: SkewedPtr) -> u32 {
+    if iter.as_array().get(SPLIT_TEXT_IDX) == SkewedPtr(0) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Returns the next part of the text, advances the iterator
+#[no_mangle]
+pub unsafe extern "C" fn text_split_next(iter: SkewedPtr) -> SkewedPtr {
+    let array = iter.as_array();
+    let text = array.get(SPLIT_TEXT_IDX);
+
+    if text == SkewedPtr(0) {
+        // Caller should check with text_split_done
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "text_split_next: Iter already done",
+        );
+    }
+
+    let delimiter = array.get(SPLIT_DELIMITER_IDX);
+    let pos = array.get(SPLIT_POS_IDX).0 >> 2;
+
+    let delimiter_size = text_size(delimiter).0;
+    let found = if delimiter_size == 0 {
+        -1
+    } else {
+        text_find_from(text, delimiter, pos)
+    };
+
+    let end = if found < 0 {
+        // The last part
+        set_field(array, SPLIT_TEXT_IDX, SkewedPtr(0));
+        text_size(text).0
+    } else {
+        let end = found as usize;
+        set_field(array, SPLIT_POS_IDX, SkewedPtr((end + delimiter_size) << 2));
+        end
+    };
+
+    text_slice(text, Bytes(pos), Bytes(end - pos))
+}
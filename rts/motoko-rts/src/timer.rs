@@ -0,0 +1,274 @@
+//! Canister timers.
+//!
+//! A timer runs a closure once the system time reaches its expiration. The IC has one global timer
+//! per canister (`ic0.global_timer_set`), it calls the `canister_global_timer` export once after
+//! the time it was set to. The timers are kept here, the global timer is set to the earliest
+//! expiration:
+//!
+//!  1. `timer_set` adds a timer and returns its id
+//!  2. `timer_cancel` removes a timer by id
+//!  3. `canister_global_timer` in the generated code, while `timer_expired`, sends the closure of
+//!     the earliest timer (`timer_next`) to the canister itself, and then removes the timer with
+//!     `timer_dequeue`. At the end it sets the global timer with `timer_rearm`.
+//!
+//! Each job runs in a message of its own: a trapping job only rolls back itself, and the other
+//! timers stay removed, as `canister_global_timer` commits once the jobs are sent. A job that
+//! couldn't be sent (e.g. the output queue is full) stays, and the global timer is set to its
+//! expiration, so it runs in the next `canister_global_timer`. A timer can't be cancelled once its
+//! message is sent.
+//!
+//! The closures are kept in the closure table, so they count as outstanding closures (e.g. in
+//! `closure_count`), until the message of the job recalls them. The timers are a binary min-heap,
+//! ordered by expiration and then by id (so timers with the same expiration fire in the order they
+//! were set), in a heap-allocated blob, which is a GC root like the closure table. The blob only
+//! holds scalars.
+//!
+//! The global timer is set via `timer_global_set`, provided by the generated code (see
+//! `RTS_Exports` in `compile.ml`), whenever the timers change, even when the earliest expiration
+//! stays the same: the IC deactivates the global timer when it calls `canister_global_timer`, and
+//! when that message traps the changes of the timers are rolled back, but the global timer stays
+//! deactivated. The global timer is deactivated with 0, so expirations are at least 1.
+//!
+//! Ids are not reused until 2^32 timers were set.
+
+use crate::alloc::alloc_blob;
+use crate::closure_table::{recall_closure, remember_closure};
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::{Bytes, SkewedPtr};
+
+use core::mem::size_of;
+
+extern "C" {
+    // Sets the global timer (`ic0.global_timer_set`), 0 deactivates it
+    fn timer_global_set(timestamp: u64);
+}
+
+const INITIAL_CAPACITY: usize = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Timer {
+    expiration: u64,
+    id: u32,
+    /// Handle of the closure in the closure table
+    handle: u32,
+}
+
+impl Timer {
+    fn before(&self, other: &Timer) -> bool {
+        (self.expiration, self.id) < (other.expiration, other.id)
+    }
+}
+
+// Skewed pointer to the `Blob` object, a GC root like the closure table
+static mut TABLE: SkewedPtr = SkewedPtr(0);
+
+// Number of timers
+static mut N_TIMERS: usize = 0;
+
+// Id of the next timer
+static mut NEXT_ID: u32 = 1;
+
+// Value the global timer was last set to
+static mut DEADLINE: u64 = 0;
+
+unsafe fn capacity() -> usize {
+    if TABLE.0 == 0 {
+        0
+    } else {
+        TABLE.as_blob().len().0 / size_of::<Timer>()
+    }
+}
+
+// The blob payload is only word-aligned, so the timers are read and written unaligned
+unsafe fn get(idx: usize) -> Timer {
+    let timers = TABLE.as_blob().payload_addr() as *const Timer;
+    timers.add(idx).read_unaligned()
+}
+
+unsafe fn set(idx: usize, timer: Timer) {
+    let timers = TABLE.as_blob().payload_addr() as *mut Timer;
+    timers.add(idx).write_unaligned(timer)
+}
+
+unsafe fn grow() {
+    let old_capacity = capacity();
+    let new_capacity = if old_capacity == 0 {
+        INITIAL_CAPACITY
+    } else {
+        old_capacity * 2
+    };
+    let new_table = alloc_blob(Bytes(new_capacity * size_of::<Timer>()));
+    if old_capacity != 0 {
+        core::ptr::copy_nonoverlapping(
+            TABLE.as_blob().payload_addr(),
+            new_table.as_blob().payload_addr(),
+            N_TIMERS * size_of::<Timer>(),
+        );
+    }
+    TABLE = new_table;
+}
+
+unsafe fn sift_up(mut idx: usize) {
+    let timer = get(idx);
+    while idx > 0 {
+        let parent = (idx - 1) / 2;
+        let parent_timer = get(parent);
+        if !timer.before(&parent_timer) {
+            break;
+        }
+        set(idx, parent_timer);
+        idx = parent;
+    }
+    set(idx, timer);
+}
+
+unsafe fn sift_down(mut idx: usize) {
+    let timer = get(idx);
+    loop {
+        let left = 2 * idx + 1;
+        if left >= N_TIMERS {
+            break;
+        }
+        let right = left + 1;
+        let child = if right < N_TIMERS && get(right).before(&get(left)) {
+            right
+        } else {
+            left
+        };
+        let child_timer = get(child);
+        if !child_timer.before(&timer) {
+            break;
+        }
+        set(idx, child_timer);
+        idx = child;
+    }
+    set(idx, timer);
+}
+
+/// Removes the timer at `idx` of the heap
+unsafe fn remove(idx: usize) -> Timer {
+    let timer = get(idx);
+    N_TIMERS -= 1;
+    if idx < N_TIMERS {
+        set(idx, get(N_TIMERS));
+        sift_down(idx);
+        sift_up(idx);
+    }
+    timer
+}
+
+/// Earliest expiration, 0 when there are no timers
+unsafe fn next_deadline() -> u64 {
+    if N_TIMERS == 0 {
+        0
+    } else {
+        get(0).expiration
+    }
+}
+
+/// Sets the global timer to the earliest expiration
+unsafe fn update_deadline() {
+    DEADLINE = next_deadline();
+    timer_global_set(DEADLINE);
+}
+
+/// Adds a timer running `closure` at `expiration` (in nanoseconds since the epoch, as
+/// `ic0.time`), returns its id
+#[no_mangle]
+pub unsafe extern "C" fn timer_set(expiration: u64, closure: SkewedPtr) -> u32 {
+    let handle = remember_closure(closure) as u32;
+
+    if N_TIMERS == capacity() {
+        grow();
+    }
+
+    let id = NEXT_ID;
+    NEXT_ID = NEXT_ID.wrapping_add(1).max(1);
+
+    set(
+        N_TIMERS,
+        Timer {
+            expiration: expiration.max(1),
+            id,
+            handle,
+        },
+    );
+    N_TIMERS += 1;
+    sift_up(N_TIMERS - 1);
+
+    update_deadline();
+
+    id
+}
+
+/// Removes the timer `id`. Returns 1 when the timer was removed, 0 when there is no timer `id`
+/// (e.g. it already fired).
+#[no_mangle]
+pub unsafe extern "C" fn timer_cancel(id: u32) -> u32 {
+    match (0..N_TIMERS).find(|&idx| get(idx).id == id) {
+        None => 0,
+        Some(idx) => {
+            let timer = remove(idx);
+            recall_closure(timer.handle as usize);
+            update_deadline();
+            1
+        }
+    }
+}
+
+/// Whether the earliest timer expired at `now`
+#[no_mangle]
+pub unsafe extern "C" fn timer_expired(now: u64) -> u32 {
+    (N_TIMERS != 0 && get(0).expiration <= now) as u32
+}
+
+/// Closure table index of the closure of the earliest timer, which stays
+#[no_mangle]
+pub unsafe extern "C" fn timer_next() -> u32 {
+    if N_TIMERS == 0 {
+        rts_trap_with(ErrorCode::Internal, "timer_next: No timers");
+    }
+    get(0).handle
+}
+
+/// Removes the earliest timer. Its closure stays in the closure table, for the message that runs
+/// it.
+#[no_mangle]
+pub unsafe extern "C" fn timer_dequeue() {
+    if N_TIMERS == 0 {
+        rts_trap_with(ErrorCode::Internal, "timer_dequeue: No timers");
+    }
+    remove(0);
+}
+
+/// Sets the global timer to the earliest expiration, at the end of `canister_global_timer`
+#[no_mangle]
+pub unsafe extern "C" fn timer_rearm() {
+    update_deadline();
+}
+
+/// Number of timers
+#[no_mangle]
+pub unsafe extern "C" fn timer_count() -> usize {
+    N_TIMERS
+}
+
+/// Expiration of the timer `id`, `None` when there is no timer `id`
+pub unsafe fn timer_expiration(id: u32) -> Option<u64> {
+    (0..N_TIMERS)
+        .map(|idx| get(idx))
+        .find(|timer| timer.id == id)
+        .map(|timer| timer.expiration)
+}
+
+/// Value the global timer was last set to, 0 when it is deactivated
+pub unsafe fn timer_deadline() -> u64 {
+    DEADLINE
+}
+
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn timer_table_loc() -> *mut SkewedPtr {
+    &mut TABLE
+}
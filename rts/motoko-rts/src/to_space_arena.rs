@@ -0,0 +1,96 @@
+//! Arena in the memory above the heap, for transient buffers.
+//!
+//! The collectors use the memory after the heap pointer: the copying GCs copy the live objects
+//! there (to-space) before moving them back, the others allocate their bitmaps and stacks there.
+//! This memory stays allocated after the collection, but is not used until the heap grows into it
+//! again. `to_space_alloc` allocates buffers in it, from its end downwards, so that transient data
+//! (serialization buffers, see `scratch.rs`) doesn't grow the heap.
+//!
+//! The buffers are valid until the end of the message: the collectors call `release` at the end
+//! of each message, before collecting. When the heap would grow into the arena during a message,
+//! the arena becomes a blob in the heap (see `make_room`): the heap pointer is moved after it, and
+//! the buffers stay valid. The blob is garbage, reclaimed by the next collection.
+//!
+//! The arena takes at most half of the idle memory, when a buffer doesn't fit `to_space_alloc`
+//! returns null and the caller allocates the buffer elsewhere.
+
+use crate::gc::large_objects::fill_gap;
+use crate::gc::{HP, MEMORY_END};
+use crate::types::*;
+
+/// End of the arena, 0 when there are no buffers
+static mut END: usize = 0;
+
+/// Beginning of the arena, the last allocated buffer. There is space for a blob header before it.
+static mut BEGIN: usize = 0;
+
+/// Allocates a word-aligned buffer of `size` bytes in the arena, which is valid until the end of
+/// the message. Returns null when it doesn't fit.
+#[no_mangle]
+pub unsafe extern "C" fn to_space_alloc(size: Bytes<usize>) -> *mut u8 {
+    let size = size.to_words().to_bytes();
+
+    let (end, begin) = if END == 0 {
+        let end = MEMORY_END / WORD_SIZE * WORD_SIZE;
+        (end, end)
+    } else {
+        (END, BEGIN)
+    };
+
+    let heap_end = HP + size_of::<Blob>().to_bytes().0;
+    if end < heap_end || begin - heap_end < size.0 {
+        return core::ptr::null_mut();
+    }
+
+    let new_begin = begin - size.0;
+    if end - new_begin > (end - HP) / 2 {
+        return core::ptr::null_mut();
+    }
+
+    END = end;
+    BEGIN = new_begin;
+    crate::alloc::update_alloc_limit();
+
+    new_begin as *mut u8
+}
+
+/// Makes room for growing the heap by `size` bytes. Called before bumping the heap pointer. When
+/// the heap would grow into the arena, the memory up to the arena is filled, and the arena is
+/// made a blob in the heap.
+pub(crate) unsafe fn make_room(size: Bytes<usize>) {
+    if END == 0 {
+        return;
+    }
+
+    let header = BEGIN - size_of::<Blob>().to_bytes().0;
+    if HP + size.0 <= header {
+        return;
+    }
+
+    fill_gap(HP, Bytes(header - HP));
+
+    let blob = header as *mut Blob;
+    (*blob).header.tag = TAG_BLOB;
+    (*blob).len = Bytes(END - BEGIN);
+
+    HP = END;
+
+    END = 0;
+    BEGIN = 0;
+}
+
+/// The highest heap pointer that doesn't need `make_room`, for the allocation fast path
+pub(crate) unsafe fn heap_end_limit() -> usize {
+    if END == 0 {
+        usize::MAX
+    } else {
+        BEGIN - size_of::<Blob>().to_bytes().0
+    }
+}
+
+/// Releases the buffers allocated in the message. Called by the collectors at the end of each
+/// message, before collecting.
+pub(crate) unsafe fn release() {
+    END = 0;
+    BEGIN = 0;
+}
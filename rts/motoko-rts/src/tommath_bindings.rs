@@ -1 +0,0 @@
-include!("../../_build/tommath_bindings.rs");
@@ -1,21 +1,24 @@
-use crate::tommath_bindings::{mp_digit, mp_int};
 use core::ops::{Add, AddAssign, Sub, SubAssign};
 
+use crate::error_code::ErrorCode;
 use crate::rts_trap_with;
 
-pub fn size_of<T>() -> Words<u32> {
-    Bytes(::core::mem::size_of::<T>() as u32).to_words()
+pub fn size_of<T>() -> Words<usize> {
+    Bytes(::core::mem::size_of::<T>()).to_words()
 }
 
-pub const WORD_SIZE: u32 = 4;
+/// Size of a heap word, the size of a pointer: 4 bytes on wasm32, 8 bytes on 64-bit hosts, where
+/// the tests also run (there is no wasm64 build yet, see `test64` in `rts/Makefile`). Object fields
+/// are words, except for the payload of `Bits64`, `Bits32`, blobs, and big integers.
+pub const WORD_SIZE: usize = ::core::mem::size_of::<usize>();
 
-/// The unit "words": `Words(123u32)` means 123 words.
+/// The unit "words": `Words(123usize)` means 123 words.
 #[repr(transparent)]
 #[derive(PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
 pub struct Words<A>(pub A);
 
-impl Words<u32> {
-    pub fn to_bytes(self) -> Bytes<u32> {
+impl Words<usize> {
+    pub fn to_bytes(self) -> Bytes<usize> {
         Bytes(self.0 * WORD_SIZE)
     }
 }
@@ -28,26 +31,34 @@ impl<A: Add<Output = A>> Add for Words<A> {
     }
 }
 
+impl<A: Sub<Output = A>> Sub for Words<A> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Words(self.0 - rhs.0)
+    }
+}
+
 impl<A: AddAssign> AddAssign for Words<A> {
     fn add_assign(&mut self, rhs: Self) {
         self.0 += rhs.0;
     }
 }
 
-impl From<Bytes<u32>> for Words<u32> {
-    fn from(bytes: Bytes<u32>) -> Words<u32> {
+impl From<Bytes<usize>> for Words<usize> {
+    fn from(bytes: Bytes<usize>) -> Words<usize> {
         bytes.to_words()
     }
 }
 
-/// The unit "bytes": `Bytes(123u32)` means 123 bytes.
+/// The unit "bytes": `Bytes(123usize)` means 123 bytes.
 #[repr(transparent)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
 pub struct Bytes<A>(pub A);
 
-impl Bytes<u32> {
+impl Bytes<usize> {
     // Rounds up
-    pub fn to_words(self) -> Words<u32> {
+    pub fn to_words(self) -> Words<usize> {
         // Rust issue for adding ceiling_div: https://github.com/rust-lang/rfcs/issues/2844
         Words((self.0 + WORD_SIZE - 1) / WORD_SIZE)
     }
@@ -81,8 +92,8 @@ impl<A: SubAssign> SubAssign for Bytes<A> {
     }
 }
 
-impl From<Words<u32>> for Bytes<u32> {
-    fn from(words: Words<u32>) -> Bytes<u32> {
+impl From<Words<usize>> for Bytes<usize> {
+    fn from(words: Words<usize>) -> Bytes<usize> {
         words.to_bytes()
     }
 }
@@ -119,6 +130,16 @@ impl SkewedPtr {
         self.unskew() as *mut Concat
     }
 
+    pub unsafe fn as_slice(self) -> *mut Slice {
+        debug_assert_eq!(self.tag(), TAG_SLICE);
+        self.unskew() as *mut Slice
+    }
+
+    pub unsafe fn as_small_text(self) -> *mut SmallText {
+        debug_assert_eq!(self.tag(), TAG_SMALL_TEXT);
+        self.unskew() as *mut SmallText
+    }
+
     pub unsafe fn as_blob(self) -> *mut Blob {
         debug_assert_eq!(self.tag(), TAG_BLOB);
         self.unskew() as *mut Blob
@@ -128,6 +149,11 @@ impl SkewedPtr {
         debug_assert_eq!(self.tag(), TAG_BIGINT);
         self.unskew() as *mut BigInt
     }
+
+    pub unsafe fn as_weak_ref(self) -> *mut WeakRef {
+        debug_assert_eq!(self.tag(), TAG_WEAK_REF);
+        self.unskew() as *mut WeakRef
+    }
 }
 
 pub fn skew(ptr: usize) -> SkewedPtr {
@@ -137,7 +163,7 @@ pub fn skew(ptr: usize) -> SkewedPtr {
 // NOTE: We don't create an enum for tags as we can never assume to do exhaustive pattern match on
 // tags, because of heap corruptions and other bugs (in the code generator or RTS, or maybe because
 // of an unsafe API usage).
-pub type Tag = u32;
+pub type Tag = usize;
 
 pub const TAG_OBJECT: Tag = 1;
 pub const TAG_OBJ_IND: Tag = 2;
@@ -153,6 +179,11 @@ pub const TAG_BITS32: Tag = 12;
 pub const TAG_BIGINT: Tag = 13;
 pub const TAG_CONCAT: Tag = 14;
 pub const TAG_NULL: Tag = 15;
+pub const TAG_WEAK_REF: Tag = 16;
+pub const TAG_REDZONE: Tag = 17;
+pub const TAG_FREE_CHUNK: Tag = 18;
+pub const TAG_SLICE: Tag = 19;
+pub const TAG_SMALL_TEXT: Tag = 20;
 
 // Common parts of any object. Other object pointers can be coerced into a pointer to this.
 #[repr(packed)]
@@ -180,9 +211,9 @@ impl Obj {
 #[rustfmt::skip]
 pub struct Array {
     pub header: Obj,
-    pub len: u32, // number of elements
+    pub len: usize, // number of elements
 
-    // Array elements follow, each u32 sized. We can't have variable-sized structs in Rust so we
+    // Array elements follow, each word sized. We can't have variable-sized structs in Rust so we
     // can't add a field here for the elements.
     // https://doc.rust-lang.org/nomicon/exotic-sizes.html
 }
@@ -192,17 +223,17 @@ impl Array {
         self.offset(1) as *mut SkewedPtr // skip array header
     }
 
-    pub unsafe fn get(self: *mut Self, idx: u32) -> SkewedPtr {
-        let slot_addr = self.payload_addr() as usize + (idx * WORD_SIZE) as usize;
+    pub unsafe fn get(self: *mut Self, idx: usize) -> SkewedPtr {
+        let slot_addr = self.payload_addr() as usize + idx * WORD_SIZE;
         *(slot_addr as *const SkewedPtr)
     }
 
-    pub unsafe fn set(self: *mut Self, idx: u32, ptr: SkewedPtr) {
-        let slot_addr = self.payload_addr() as usize + (idx * WORD_SIZE) as usize;
+    pub unsafe fn set(self: *mut Self, idx: usize, ptr: SkewedPtr) {
+        let slot_addr = self.payload_addr() as usize + idx * WORD_SIZE;
         *(slot_addr as *mut SkewedPtr) = ptr;
     }
 
-    pub unsafe fn len(self: *mut Self) -> u32 {
+    pub unsafe fn len(self: *mut Self) -> usize {
         (*self).len
     }
 }
@@ -210,8 +241,8 @@ impl Array {
 #[repr(packed)]
 pub struct Object {
     pub header: Obj,
-    pub size: u32,     // Number of elements
-    pub hash_ptr: u32, // Pointer to static information about object field labels. Not important for GC (does not contain pointers).
+    pub size: usize,     // Number of elements
+    pub hash_ptr: usize, // Pointer to static information about object field labels. Not important for GC (does not contain pointers).
 }
 
 impl Object {
@@ -219,7 +250,7 @@ impl Object {
         self.offset(1) as *const SkewedPtr // skip object header
     }
 
-    pub unsafe fn size(self: *mut Self) -> u32 {
+    pub unsafe fn size(self: *mut Self) -> usize {
         (*self).size
     }
 }
@@ -233,9 +264,9 @@ pub struct ObjInd {
 #[repr(packed)]
 pub struct Closure {
     pub header: Obj,
-    pub funid: u32,
-    pub size: u32, // number of elements
-                   // other stuff follows ...
+    pub funid: usize,
+    pub size: usize, // number of elements
+                     // other stuff follows ...
 }
 
 impl Closure {
@@ -243,7 +274,7 @@ impl Closure {
         self.offset(1) as *const SkewedPtr // skip closure header
     }
 
-    pub unsafe fn size(self: *mut Self) -> u32 {
+    pub unsafe fn size(self: *mut Self) -> usize {
         (*self).size
     }
 }
@@ -251,7 +282,7 @@ impl Closure {
 #[repr(packed)]
 pub struct Blob {
     pub header: Obj,
-    pub len: Bytes<u32>,
+    pub len: Bytes<usize>,
     // data follows ..
 }
 
@@ -260,16 +291,16 @@ impl Blob {
         self.add(1) as *mut u8 // skip closure header
     }
 
-    pub unsafe fn len(self: *mut Self) -> Bytes<u32> {
+    pub unsafe fn len(self: *mut Self) -> Bytes<usize> {
         (*self).len
     }
 
-    pub unsafe fn get(self: *mut Self, idx: u32) -> u8 {
-        *self.payload_addr().add(idx as usize)
+    pub unsafe fn get(self: *mut Self, idx: usize) -> u8 {
+        *self.payload_addr().add(idx)
     }
 
-    pub unsafe fn set(self: *mut Self, idx: u32, byte: u8) {
-        *self.payload_addr().add(idx as usize) = byte;
+    pub unsafe fn set(self: *mut Self, idx: usize, byte: u8) {
+        *self.payload_addr().add(idx) = byte;
     }
 }
 
@@ -280,42 +311,27 @@ pub struct FwdPtr {
     pub fwd: SkewedPtr,
 }
 
+/// An arbitrary precision integer, see `bigint.rs`. The magnitude is in 32-bit digits after the
+/// fields, least significant first.
 #[repr(packed)]
 pub struct BigInt {
     pub header: Obj,
-    /// The data following now must describe is the `mp_int` struct.
-    /// The data pointer (mp_int.dp) is irrelevant, and will be changed to point to
-    /// the data within this object before it is used.
-    /// (NB: If we have a non-moving GC, we can make this an invaiant)
-    pub mp_int: mp_int,
-    // data follows ..
+    /// Number of digits of the magnitude, without leading zeros
+    pub used: usize,
+    /// Number of digits allocated, at least `used`
+    pub alloc: usize,
+    /// 1 for negative numbers, 0 for zero and positive numbers
+    pub sign: usize,
+    // digits follow ..
 }
 
 impl BigInt {
-    pub unsafe fn len(self: *mut Self) -> Bytes<u32> {
-        Bytes(((*self).mp_int.alloc as usize * core::mem::size_of::<mp_digit>()) as u32)
+    pub unsafe fn len(self: *mut Self) -> Bytes<usize> {
+        Bytes((*self).alloc * core::mem::size_of::<u32>())
     }
 
-    pub unsafe fn payload_addr(self: *mut Self) -> *mut mp_digit {
-        self.add(1) as *mut mp_digit // skip closure header
-    }
-
-    pub unsafe fn from_payload(ptr: *mut mp_digit) -> *mut Self {
-        (ptr as *mut u32).sub(size_of::<BigInt>().0 as usize) as *mut BigInt
-    }
-
-    /// Returns pointer to the `mp_int` struct
-    ///
-    /// It fixes up the dp pointer. Instead of doing it here
-    /// this could be done on allocation and every object move.
-    ///
-    /// Note that this returns a `const` pointer. This is very nice, as together with the const
-    /// annotation on the libtommath API, this should prevent us from passing this pointer to a
-    /// libtommath function that tries to change it. For example, we cannot confuse input and
-    /// output parameters of mp_add() this way.
-    pub unsafe fn mp_int_ptr(self: *mut BigInt) -> *const mp_int {
-        (*self).mp_int.dp = self.payload_addr();
-        &(*self).mp_int
+    pub unsafe fn payload_addr(self: *mut Self) -> *mut u32 {
+        self.add(1) as *mut u32 // skip closure header
     }
 }
 
@@ -334,16 +350,63 @@ pub struct Some {
 #[repr(packed)]
 pub struct Variant {
     pub header: Obj,
-    pub tag: u32,
+    pub tag: usize,
     pub field: SkewedPtr,
 }
 
 #[repr(packed)]
 pub struct Concat {
     pub header: Obj,
-    pub n_bytes: Bytes<u32>,
+    pub n_bytes: Bytes<usize>,
     pub text1: SkewedPtr,
     pub text2: SkewedPtr,
+    pub depth: usize, // depth of the tree, see `text.rs`
+}
+
+/// A part of a text blob, see `text_slice`
+#[repr(packed)]
+pub struct Slice {
+    pub header: Obj,
+    pub n_bytes: Bytes<usize>,
+    pub blob: SkewedPtr,
+    pub offset: Bytes<usize>,
+}
+
+/// A short text with the bytes in the object, see `text.rs`. The bytes are padded with 0xFF, which
+/// doesn't occur in UTF-8. There is one payload word when its last byte is padding, two otherwise,
+/// so the object holds up to `SMALL_TEXT_MAX_SIZE` bytes.
+#[repr(packed)]
+pub struct SmallText {
+    pub header: Obj,
+    // payload follows
+}
+
+pub const SMALL_TEXT_MAX_SIZE: Bytes<usize> = Bytes(2 * WORD_SIZE);
+
+impl SmallText {
+    pub unsafe fn payload_addr(self: *mut Self) -> *mut u8 {
+        self.add(1) as *mut u8
+    }
+
+    /// Size of the payload, in words
+    pub unsafe fn payload_words(self: *mut Self) -> Words<usize> {
+        if *self.payload_addr().add(WORD_SIZE - 1) == 0xFF {
+            Words(1)
+        } else {
+            Words(2)
+        }
+    }
+
+    /// Size of the text, in bytes
+    pub unsafe fn len(self: *mut Self) -> Bytes<usize> {
+        let payload = self.payload_addr();
+        let capacity = self.payload_words().to_bytes().0;
+        Bytes(
+            (0..capacity)
+                .find(|i| *payload.add(*i) == 0xFF)
+                .unwrap_or(capacity),
+        )
+    }
 }
 
 impl Concat {
@@ -361,6 +424,28 @@ pub struct Null {
     pub header: Obj,
 }
 
+/// A weak reference. The GC does not keep `field` alive, and sets it to `WEAK_REF_CLEARED` when
+/// the referent is reclaimed. See `weak_ref.rs`.
+#[repr(packed)]
+pub struct WeakRef {
+    pub header: Obj,
+    pub field: SkewedPtr,
+}
+
+/// Free memory in the free lists, see `alloc/free_list.rs`. `next` and `prev` are the neighbours in
+/// the list, null at the ends.
+#[repr(packed)]
+pub struct FreeChunk {
+    pub header: Obj,
+    pub size: Words<usize>,
+    pub next: *mut FreeChunk,
+    pub prev: *mut FreeChunk,
+}
+
+/// Value of the field of a weak reference after the referent is reclaimed. A tagged scalar, so
+/// the GC does not follow it.
+pub const WEAK_REF_CLEARED: SkewedPtr = SkewedPtr(0);
+
 #[repr(packed)]
 pub struct Bits64 {
     pub header: Obj,
@@ -374,7 +459,7 @@ pub struct Bits32 {
 }
 
 /// Returns object size in words
-pub(crate) unsafe fn object_size(obj: usize) -> Words<u32> {
+pub(crate) unsafe fn object_size(obj: usize) -> Words<usize> {
     let obj = obj as *mut Obj;
     match obj.tag() {
         TAG_OBJECT => {
@@ -411,7 +496,7 @@ pub(crate) unsafe fn object_size(obj: usize) -> Words<u32> {
         }
 
         TAG_FWD_PTR => {
-            rts_trap_with("object_size: forwarding pointer");
+            rts_trap_with(ErrorCode::Internal, "object_size: forwarding pointer");
         }
 
         TAG_BITS32 => size_of::<Bits32>(),
@@ -423,8 +508,22 @@ pub(crate) unsafe fn object_size(obj: usize) -> Words<u32> {
 
         TAG_CONCAT => size_of::<Concat>(),
 
+        TAG_SLICE => size_of::<Slice>(),
+
+        TAG_SMALL_TEXT => {
+            let small_text = obj as *mut SmallText;
+            size_of::<SmallText>() + small_text.payload_words()
+        }
+
         TAG_NULL => size_of::<Null>(),
 
+        TAG_WEAK_REF => size_of::<WeakRef>(),
+
+        // See `alloc/redzones.rs`
+        TAG_REDZONE => Words(2),
+
+        TAG_FREE_CHUNK => (*(obj as *mut FreeChunk)).size,
+
         0 => {
             // This can happens when we shrink a blob in principal id functions. The slop between
             // new size and old size is filled with zeros.
@@ -432,7 +531,7 @@ pub(crate) unsafe fn object_size(obj: usize) -> Words<u32> {
         }
 
         _ => {
-            rts_trap_with("object_size: invalid object tag");
+            rts_trap_with(ErrorCode::Internal, "object_size: invalid object tag");
         }
     }
 }
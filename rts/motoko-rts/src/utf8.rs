@@ -1,15 +1,183 @@
+//! UTF-8 and UTF-16 validation.
+//!
+//! UTF-8 is validated with a DFA over byte classes (the well-formed byte sequences are in table 3-7
+//! of the Unicode Standard). Between characters, runs of ASCII are skipped a word at a time.
+
+use crate::error_code::ErrorCode;
 use crate::rts_trap_with;
+use crate::types::WORD_SIZE;
+
+// Byte classes
+const ASCII: u8 = 0; // 00..7F
+const CONT_LOW: u8 = 1; // 80..8F
+const CONT_MID: u8 = 2; // 90..9F
+const CONT_HIGH: u8 = 3; // A0..BF
+const INVALID: u8 = 4; // C0, C1, F5..FF
+const LEAD_2: u8 = 5; // C2..DF
+const LEAD_E0: u8 = 6;
+const LEAD_3: u8 = 7; // E1..EC, EE, EF
+const LEAD_ED: u8 = 8;
+const LEAD_F0: u8 = 9;
+const LEAD_4: u8 = 10; // F1..F3
+const LEAD_F4: u8 = 11;
+
+const N_CLASSES: usize = 12;
+
+// States: the continuation bytes still expected, and the range of the next one
+const ACCEPT: u8 = 0;
+const REJECT: u8 = 1;
+const NEED_1: u8 = 2; // 80..BF
+const NEED_2: u8 = 3; // 80..BF, then one more
+const NEED_3: u8 = 4; // 80..BF, then two more
+const AFTER_E0: u8 = 5; // A0..BF, then one more
+const AFTER_ED: u8 = 6; // 80..9F, then one more
+const AFTER_F0: u8 = 7; // 90..BF, then two more
+const AFTER_F4: u8 = 8; // 80..8F, then two more
+
+const N_STATES: usize = 9;
+
+const fn byte_class(byte: u8) -> u8 {
+    match byte {
+        0x00..=0x7F => ASCII,
+        0x80..=0x8F => CONT_LOW,
+        0x90..=0x9F => CONT_MID,
+        0xA0..=0xBF => CONT_HIGH,
+        0xC2..=0xDF => LEAD_2,
+        0xE0 => LEAD_E0,
+        0xED => LEAD_ED,
+        0xE1..=0xEF => LEAD_3,
+        0xF0 => LEAD_F0,
+        0xF1..=0xF3 => LEAD_4,
+        0xF4 => LEAD_F4,
+        _ => INVALID,
+    }
+}
+
+const fn transition(state: u8, class: u8) -> u8 {
+    let is_cont = class == CONT_LOW || class == CONT_MID || class == CONT_HIGH;
+    match state {
+        ACCEPT => match class {
+            ASCII => ACCEPT,
+            LEAD_2 => NEED_1,
+            LEAD_E0 => AFTER_E0,
+            LEAD_3 => NEED_2,
+            LEAD_ED => AFTER_ED,
+            LEAD_F0 => AFTER_F0,
+            LEAD_4 => NEED_3,
+            LEAD_F4 => AFTER_F4,
+            _ => REJECT,
+        },
+        NEED_1 if is_cont => ACCEPT,
+        NEED_2 if is_cont => NEED_1,
+        NEED_3 if is_cont => NEED_2,
+        AFTER_E0 if class == CONT_HIGH => NEED_1,
+        AFTER_ED if class == CONT_LOW || class == CONT_MID => NEED_1,
+        AFTER_F0 if class == CONT_MID || class == CONT_HIGH => NEED_2,
+        AFTER_F4 if class == CONT_LOW => NEED_2,
+        _ => REJECT,
+    }
+}
+
+static CLASSES: [u8; 256] = {
+    let mut classes = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        classes[byte] = byte_class(byte as u8);
+        byte += 1;
+    }
+    classes
+};
+
+static TRANSITIONS: [u8; N_STATES * N_CLASSES] = {
+    let mut transitions = [0; N_STATES * N_CLASSES];
+    let mut state = 0;
+    while state < N_STATES {
+        let mut class = 0;
+        while class < N_CLASSES {
+            transitions[state * N_CLASSES + class] = transition(state as u8, class as u8);
+            class += 1;
+        }
+        state += 1;
+    }
+    transitions
+};
+
+/// The high bit of each byte of a word, set in a word with a non-ASCII byte
+const NON_ASCII_MASK: usize = usize::MAX / 0xFF * 0x80;
 
 /// Panics if the string is not valid UTF-8
 #[no_mangle]
-pub(crate) unsafe extern "C" fn utf8_validate(str: *const libc::c_char, len: u32) {
+pub(crate) unsafe extern "C" fn utf8_validate(str: *const libc::c_char, len: usize) {
     if !utf8_valid(str, len) {
-        rts_trap_with("utf8_validate: string is not UTF-8");
+        rts_trap_with(ErrorCode::Utf8, "utf8_validate: string is not UTF-8");
     }
 }
 
 /// Returns whether the string is valid UTF-8
 #[no_mangle]
-pub unsafe fn utf8_valid(str: *const libc::c_char, len: u32) -> bool {
-    core::str::from_utf8(core::slice::from_raw_parts(str as *const _, len as usize)).is_ok()
+pub unsafe fn utf8_valid(str: *const libc::c_char, len: usize) -> bool {
+    let str = str as *const u8;
+    let mut state = ACCEPT;
+    let mut i = 0;
+
+    while i < len {
+        if state == ACCEPT {
+            while i + WORD_SIZE <= len
+                && (str.add(i) as *const usize).read_unaligned() & NON_ASCII_MASK == 0
+            {
+                i += WORD_SIZE;
+            }
+            if i == len {
+                break;
+            }
+        }
+
+        let class = CLASSES[*str.add(i) as usize];
+        state = TRANSITIONS[state as usize * N_CLASSES + class as usize];
+        if state == REJECT {
+            return false;
+        }
+        i += 1;
+    }
+
+    state == ACCEPT
+}
+
+/// Calls `f` with the parts of the string: the valid UTF-8 parts, and "\u{FFFD}" for each invalid
+/// sequence, which is a prefix of a valid sequence or a single byte (as `String::from_utf8_lossy`)
+pub(crate) unsafe fn utf8_lossy_parts<F: FnMut(&str)>(str: *const u8, len: usize, mut f: F) {
+    let mut bytes = core::slice::from_raw_parts(str, len);
+    loop {
+        match core::str::from_utf8(bytes) {
+            Ok(valid) => {
+                f(valid);
+                return;
+            }
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                f(core::str::from_utf8_unchecked(valid));
+                f("\u{FFFD}");
+                match err.error_len() {
+                    Some(n) => bytes = &rest[n..],
+                    // Truncated sequence at the end
+                    None => return,
+                }
+            }
+        }
+    }
+}
+
+/// The code units of a UTF-16 (little-endian) string of `len` bytes. A trailing odd byte is
+/// ignored.
+pub(crate) unsafe fn utf16_units(str: *const u8, len: usize) -> impl Iterator<Item = u16> + Clone {
+    core::slice::from_raw_parts(str, len)
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+}
+
+/// Returns whether the string is valid UTF-16 (little-endian, without byte order mark): an even
+/// number of bytes, and surrogates only in pairs
+#[no_mangle]
+pub unsafe extern "C" fn utf16_valid(str: *const u8, len: usize) -> bool {
+    len % 2 == 0 && core::char::decode_utf16(utf16_units(str, len)).all(|c| c.is_ok())
 }
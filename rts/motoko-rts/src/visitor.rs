@@ -0,0 +1,107 @@
+//! Visiting pointer fields of heap objects. Shared by the garbage collectors and the graph copy of
+//! the stable variables (`graph_copy.rs`), which is also compiled without the `gc` feature.
+
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::*;
+
+/// Calls `visit_field` with the location of every field of the object that may hold a pointer.
+/// Fields may also hold tagged scalars, it is up to the caller to check.
+///
+/// Fields of weak references are not visited, as these should not keep the referent alive. Use
+/// `visit_all_pointer_fields` when updating pointers to moved objects.
+pub(crate) unsafe fn visit_pointer_fields<F>(obj: *mut Obj, mut visit_field: F)
+where
+    F: FnMut(*mut SkewedPtr),
+{
+    match obj.tag() {
+        TAG_OBJECT => {
+            let object = obj as *const Object;
+            let object_payload = object.payload_addr() as *mut SkewedPtr;
+            for i in 0..(*object).size {
+                visit_field(object_payload.add(i));
+            }
+        }
+
+        TAG_ARRAY => {
+            let array = obj as *mut Array;
+            let array_payload = array.payload_addr();
+            for i in 0..array.len() {
+                visit_field(array_payload.add(i));
+            }
+        }
+
+        TAG_MUTBOX => {
+            let mutbox = obj as *mut MutBox;
+            visit_field(&mut (*mutbox).field);
+        }
+
+        TAG_CLOSURE => {
+            let closure = obj as *const Closure;
+            let closure_payload = closure.payload_addr() as *mut SkewedPtr;
+            for i in 0..(*closure).size {
+                visit_field(closure_payload.add(i));
+            }
+        }
+
+        TAG_SOME => {
+            let some = obj as *mut Some;
+            visit_field(&mut (*some).field);
+        }
+
+        TAG_VARIANT => {
+            let variant = obj as *mut Variant;
+            visit_field(&mut (*variant).field);
+        }
+
+        TAG_CONCAT => {
+            let concat = obj as *mut Concat;
+            visit_field(&mut (*concat).text1);
+            visit_field(&mut (*concat).text2);
+        }
+
+        TAG_SLICE => {
+            let slice = obj as *mut Slice;
+            visit_field(&mut (*slice).blob);
+        }
+
+        TAG_OBJ_IND => {
+            let obj_ind = obj as *mut ObjInd;
+            visit_field(&mut (*obj_ind).field);
+        }
+
+        TAG_BITS64 | TAG_BITS32 | TAG_BLOB | TAG_BIGINT | TAG_NULL | TAG_REDZONE
+        | TAG_FREE_CHUNK | TAG_SMALL_TEXT => {
+            // These don't include pointers, skip
+        }
+
+        TAG_WEAK_REF => {
+            // Weak, see `visit_all_pointer_fields`
+        }
+
+        0 => {
+            // Slop after a shrunk blob, see `object_size`
+        }
+
+        TAG_FWD_PTR | _ => {
+            rts_trap_with(
+                ErrorCode::Internal,
+                "invalid object tag in visit_pointer_fields",
+            );
+        }
+    }
+}
+
+/// Like `visit_pointer_fields`, but also visits fields of weak references
+#[cfg(feature = "gc")]
+pub(crate) unsafe fn visit_all_pointer_fields<F>(obj: *mut Obj, mut visit_field: F)
+where
+    F: FnMut(*mut SkewedPtr),
+{
+    if obj.tag() == TAG_WEAK_REF {
+        let weak_ref = obj as *mut WeakRef;
+        visit_field(&mut (*weak_ref).field);
+    } else {
+        visit_pointer_fields(obj, visit_field);
+    }
+}
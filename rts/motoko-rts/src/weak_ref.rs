@@ -0,0 +1,54 @@
+//! Weak references.
+//!
+//! A weak reference points to a heap object without keeping it alive. When the GC reclaims the
+//! referent it clears the reference, after that `weak_ref_alive` returns `false`. This can be used
+//! to implement caches that do not leak.
+//!
+//! Static objects are never reclaimed, so weak references to these are never cleared. See
+//! `gc/weak_refs.rs` for how the collectors handle weak references.
+
+use crate::alloc::{alloc_words, weak_ref_read_barrier};
+use crate::error_code::ErrorCode;
+use crate::rts_trap_with;
+use crate::types::*;
+
+/// Allocates a weak reference to `referent`, which should be a heap object
+#[no_mangle]
+pub unsafe extern "C" fn weak_ref_new(referent: SkewedPtr) -> SkewedPtr {
+    if referent.is_tagged_scalar() {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "weak_ref_new: referent is not a heap object",
+        );
+    }
+
+    let ptr = alloc_words(size_of::<WeakRef>());
+    let weak_ref = ptr.unskew() as *mut WeakRef;
+    (*weak_ref).header.tag = TAG_WEAK_REF;
+    (*weak_ref).field = referent;
+    ptr
+}
+
+/// Returns whether the referent is still alive, i.e. `weak_ref_get` can be called
+#[no_mangle]
+pub unsafe extern "C" fn weak_ref_alive(weak_ref: SkewedPtr) -> u32 {
+    ((*weak_ref.as_weak_ref()).field != WEAK_REF_CLEARED) as u32
+}
+
+/// Returns the referent. Traps if the referent was reclaimed.
+#[no_mangle]
+pub unsafe extern "C" fn weak_ref_get(weak_ref: SkewedPtr) -> SkewedPtr {
+    let referent = (*weak_ref.as_weak_ref()).field;
+
+    if referent == WEAK_REF_CLEARED {
+        rts_trap_with(
+            ErrorCode::InvalidArgument,
+            "weak_ref_get: referent was reclaimed",
+        );
+    }
+
+    // The referent may become strongly reachable again, let the GC know
+    weak_ref_read_barrier(referent);
+
+    referent
+}
@@ -257,6 +257,8 @@ module E = struct
       (* GC roots in static memory. (Everything that may be mutable.) *)
     labs : LabSet.t ref; (* Used labels (fields and variants),
                             collected for Motoko custom section 0 *)
+    alloc_sites : Source.region list ref; (* Allocation sites, see AllocProfile *)
+    alloc_site : int32 ref; (* Site of the expression being compiled *)
 
     (* Local fields (only valid/used inside a function) *)
     (* Static *)
@@ -291,6 +293,8 @@ module E = struct
     static_memory_frozen = ref false;
     static_roots = ref [];
     labs = ref LabSet.empty;
+    alloc_sites = ref [];
+    alloc_site = ref 0l;
     (* Actually unused outside mk_fun_env: *)
     n_param = 0l;
     return_arity = 0;
@@ -308,6 +312,15 @@ module E = struct
 
   let get_labs env = LabSet.elements (!(env.labs))
 
+  (* Sites are numbered from 1, 0 is no site *)
+  let add_alloc_site (env : t) at =
+    Int32.add (reg env.alloc_sites at) 1l
+
+  let get_alloc_sites (env : t) = !(env.alloc_sites)
+
+  let get_alloc_site (env : t) = !(env.alloc_site)
+  let set_alloc_site (env : t) site = env.alloc_site := site
+
   let mk_fun_env env n_param return_arity =
     { env with
       n_param;
@@ -593,6 +606,10 @@ let new_float_local env name =
 
 (* Some common code macros *)
 
+(* Trap message of malformed Candid, with the error code of the RTS
+   (see rts/motoko-rts/src/error_code.rs) *)
+let idl_error msg = "[E0002] IDL error: " ^ msg
+
 (* Iterates while cond is true. *)
 let compile_while cond body =
     G.loop_ [] (
@@ -766,8 +783,26 @@ module RTS = struct
     E.add_func_import env "rts" "bigint_rem" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_div" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_pow" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_pow_mod" [I32Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_isqrt" [I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_gcd" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_lcm" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_to_le_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_of_le_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_to_signed_le_blob" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_of_signed_le_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_to_text" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_of_text_radix" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_to_float64" [I32Type] [F64Type];
+    E.add_func_import env "rts" "bigint_of_float64" [F64Type] [I32Type];
     E.add_func_import env "rts" "bigint_neg" [I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_lsh" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_and" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_or" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_xor" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_shl" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_shr" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "bigint_bit_test" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_abs" [I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_leb128_size" [I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_leb128_encode" [I32Type; I32Type] [];
@@ -775,32 +810,116 @@ module RTS = struct
     E.add_func_import env "rts" "bigint_sleb128_size" [I32Type] [I32Type];
     E.add_func_import env "rts" "bigint_sleb128_encode" [I32Type; I32Type] [];
     E.add_func_import env "rts" "bigint_sleb128_decode" [I32Type] [I32Type];
+    E.add_func_import env "rts" "leb128_decode_nat" [I32Type] [I32Type];
+    E.add_func_import env "rts" "sleb128_decode_int" [I32Type] [I32Type];
     E.add_func_import env "rts" "leb128_encode" [I32Type; I32Type] [];
     E.add_func_import env "rts" "sleb128_encode" [I32Type; I32Type] [];
     E.add_func_import env "rts" "utf8_valid" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "utf8_validate" [I32Type; I32Type] [];
+    E.add_func_import env "rts" "utf16_valid" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "skip_leb128" [I32Type] [];
     E.add_func_import env "rts" "skip_any" [I32Type; I32Type; I32Type; I32Type] [];
-    E.add_func_import env "rts" "find_field" [I32Type; I32Type; I32Type; I32Type; I32Type] [I32Type];
-    E.add_func_import env "rts" "skip_fields" [I32Type; I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "idl_field_plan" [I32Type; I32Type; I32Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "idl_plan_record_field" [I32Type; I32Type; I32Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "idl_plan_skip_fields" [I32Type; I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "idl_plan_variant_case" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "idl_plan_case_type" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "idl_read_nat8_array" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "idl_check_vec" [I32Type; I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "idl_set_limits" [I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "idl_stats_value" [] [];
+    E.add_func_import env "rts" "idl_stats_begin" [] [];
+    E.add_func_import env "rts" "idl_stats_end" [I32Type] [];
+    E.add_func_import env "rts" "idl_stats" [] [I32Type];
+    E.add_func_import env "rts" "stable_vars_size" [] [I32Type];
+    E.add_func_import env "rts" "stable_vars_read" [I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "stable_vars_compress" [] [];
+    E.add_func_import env "rts" "stable_vars_verify" [] [];
+    E.add_func_import env "rts" "stable_memory_header" [] [I32Type];
+    E.add_func_import env "rts" "region_new" [] [I32Type];
+    E.add_func_import env "rts" "region_size" [I32Type] [I64Type];
+    E.add_func_import env "rts" "region_grow" [I32Type; I64Type] [I64Type];
+    E.add_func_import env "rts" "region_load_blob" [I32Type; I64Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "region_stream_new" [I32Type; I64Type] [I32Type];
+    E.add_func_import env "rts" "region_stream_write" [I32Type; I32Type] [];
+    E.add_func_import env "rts" "region_stream_flush" [I32Type] [I64Type];
+    E.add_func_import env "rts" "region_buf_new" [I32Type; I64Type; I64Type] [I32Type];
+    E.add_func_import env "rts" "region_buf_read" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "region_buf_remaining" [I32Type] [I64Type];
+    E.add_func_import env "rts" "idl_serialize_size" [I32Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "idl_serialize" [I32Type; I32Type; I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "idl_serialize_stable" [I32Type; I32Type; I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "graph_copy_stabilize" [I32Type; I32Type; I32Type; I32Type; I32Type; I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "graph_copy_restore" [I32Type; I32Type; I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "remember_closure" [I32Type] [I32Type];
     E.add_func_import env "rts" "recall_closure" [I32Type] [I32Type];
     E.add_func_import env "rts" "closure_count" [] [I32Type];
     E.add_func_import env "rts" "closure_table_size" [] [I32Type];
+    E.add_func_import env "rts" "timer_set" [I64Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "timer_cancel" [I32Type] [I32Type];
+    E.add_func_import env "rts" "timer_expired" [I64Type] [I32Type];
+    E.add_func_import env "rts" "timer_next" [] [I32Type];
+    E.add_func_import env "rts" "timer_dequeue" [] [];
+    E.add_func_import env "rts" "timer_rearm" [] [];
+    E.add_func_import env "rts" "register_finalizer" [I32Type; I32Type] [];
+    E.add_func_import env "rts" "pending_finalizers" [] [I32Type];
+    E.add_func_import env "rts" "next_finalizer" [] [I32Type];
+    E.add_func_import env "rts" "dequeue_finalizer" [] [];
+    E.add_func_import env "rts" "weak_ref_new" [I32Type] [I32Type];
+    E.add_func_import env "rts" "weak_ref_alive" [I32Type] [I32Type];
+    E.add_func_import env "rts" "weak_ref_get" [I32Type] [I32Type];
+    E.add_func_import env "rts" "pin_object" [I32Type] [];
+    E.add_func_import env "rts" "unpin_object" [I32Type] [];
+    E.add_func_import env "rts" "backtrace_push" [I32Type] [];
+    E.add_func_import env "rts" "backtrace_pop" [] [];
+    E.add_func_import env "rts" "trap_with_backtrace" [I32Type; I32Type] [];
     E.add_func_import env "rts" "blob_of_text" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_compare" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "text_concat" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "text_iter_done" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_iter" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_iter_next" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_split" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "text_split_done" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_split_next" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_iter_graphemes" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_iter_graphemes_done" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_iter_graphemes_next" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_len" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_of_ptr_size" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "text_singleton" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_size" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_slice" [I32Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "text_find" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "text_to_upper" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_to_lower" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_normalize_nfc" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_to_utf16_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_of_utf16_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_of_blob_lossy" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_intern" [I32Type] [I32Type];
+    E.add_func_import env "rts" "text_repeat" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_fill" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_concat_many" [I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_copy_range" [I32Type; I32Type; I32Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_compare_const_time" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_sha256" [I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_sha224" [I32Type] [I32Type];
+    E.add_func_import env "rts" "base64_encode" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "base64_decode" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_to_hex" [I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_of_hex" [I32Type] [I32Type];
     E.add_func_import env "rts" "text_to_buf" [I32Type; I32Type] [];
     E.add_func_import env "rts" "blob_of_principal" [I32Type] [I32Type];
     E.add_func_import env "rts" "principal_of_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "principal_of_text_checked" [I32Type] [I32Type];
+    E.add_func_import env "rts" "principal_of_public_key" [I32Type] [I32Type];
+    E.add_func_import env "rts" "account_identifier" [I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "principal_compare" [I32Type; I32Type] [I32Type];
     E.add_func_import env "rts" "compute_crc32" [I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_crc32c" [I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_crc16" [I32Type] [I32Type];
+    E.add_func_import env "rts" "blob_crc8" [I32Type] [I32Type];
     E.add_func_import env "rts" "blob_iter_done" [I32Type] [I32Type];
     E.add_func_import env "rts" "blob_iter" [I32Type] [I32Type];
     E.add_func_import env "rts" "blob_iter_next" [I32Type] [I32Type];
@@ -816,25 +935,97 @@ module RTS = struct
     E.add_func_import env "rts" "log" [F64Type] [F64Type]; (* musl *)
     E.add_func_import env "rts" "fmod" [F64Type; F64Type] [F64Type]; (* remainder, musl *)
     E.add_func_import env "rts" "float_fmt" [F64Type; I32Type; I32Type] [I32Type];
+    E.add_func_import env "rts" "float_to_text" [F64Type] [I32Type];
+    E.add_func_import env "rts" "float_of_text" [I32Type] [I32Type];
     E.add_func_import env "rts" "char_to_upper" [I32Type] [I32Type];
     E.add_func_import env "rts" "char_to_lower" [I32Type] [I32Type];
+    E.add_func_import env "rts" "char_fold_case" [I32Type] [I32Type];
     E.add_func_import env "rts" "char_is_whitespace" [I32Type] [I32Type];
     E.add_func_import env "rts" "char_is_lowercase" [I32Type] [I32Type];
     E.add_func_import env "rts" "char_is_uppercase" [I32Type] [I32Type];
     E.add_func_import env "rts" "char_is_alphabetic" [I32Type] [I32Type];
+    E.add_func_import env "rts" "char_is_numeric" [I32Type] [I32Type];
+    E.add_func_import env "rts" "char_general_category" [I32Type] [I32Type];
     E.add_func_import env "rts" "get_max_live_size" [] [I32Type];
     E.add_func_import env "rts" "get_reclaimed" [] [I64Type];
     E.add_func_import env "rts" "collect" [] [];
+    E.add_func_import env "rts" "incremental_gc" [] [];
+    E.add_func_import env "rts" "write_barrier" [I32Type] [];
+    E.add_func_import env "rts" "generational_gc" [] [];
+    E.add_func_import env "rts" "generational_write_barrier" [I32Type] [];
+    E.add_func_import env "rts" "post_write_barrier" [I32Type; I32Type] [];
+    E.add_func_import env "rts" "mark_sweep_gc" [] [];
     E.add_func_import env "rts" "alloc_words" [I32Type] [I32Type];
+    E.add_func_import env "rts" "get_alloc_fast_path" [] [I32Type];
     E.add_func_import env "rts" "get_total_allocations" [] [I64Type];
+    E.add_func_import env "rts" "message_alloc_reset" [] [];
+    E.add_func_import env "rts" "get_message_allocation" [] [I32Type];
     E.add_func_import env "rts" "get_heap_size" [] [I32Type];
+    E.add_func_import env "rts" "get_gc_stats" [] [I32Type];
+    E.add_func_import env "rts" "set_compiler_features" [I32Type; I32Type] [];
+    E.add_func_import env "rts" "get_features" [] [I32Type];
+    E.add_func_import env "rts" "perf_enter" [I32Type; I64Type] [];
+    E.add_func_import env "rts" "perf_leave" [I64Type] [];
+    E.add_func_import env "rts" "get_perf_counters" [] [I32Type];
+    E.add_func_import env "rts" "random_seed" [I64Type] [];
+    E.add_func_import env "rts" "random_seed_blob" [I32Type] [];
+    E.add_func_import env "rts" "random_next" [] [I64Type];
+    E.add_func_import env "rts" "blob_random" [I32Type] [I32Type];
+    E.add_func_import env "rts" "gc_force" [] [];
+    E.add_func_import env "rts" "gc_defer" [I32Type] [];
+    E.add_func_import env "rts" "set_heap_limit" [I32Type] [];
+    E.add_func_import env "rts" "set_gc_schedule" [I32Type; I32Type; I32Type] [];
+    E.add_func_import env "rts" "set_compaction_threshold" [I32Type] [];
+    E.add_func_import env "rts" "get_heap_limit" [] [I32Type];
+    E.add_func_import env "rts" "heap_snapshot" [] [I32Type];
+    E.add_func_import env "rts" "heap_checkpoint" [] [];
+    E.add_func_import env "rts" "heap_diff" [] [I32Type];
+    E.add_func_import env "rts" "heap_live_census" [] [I32Type];
+    E.add_func_import env "rts" "alloc_profile_enable" [] [];
+    E.add_func_import env "rts" "set_alloc_site" [I32Type] [];
+    E.add_func_import env "rts" "alloc_profile" [I32Type] [I32Type];
     E.add_func_import env "rts" "init" [] [];
     E.add_func_import env "rts" "alloc_blob" [I32Type] [I32Type];
+    E.add_func_import env "rts" "scratch_alloc" [I32Type] [I32Type];
+    E.add_func_import env "rts" "to_space_alloc" [I32Type] [I32Type];
     E.add_func_import env "rts" "alloc_array" [I32Type] [I32Type];
+    E.add_func_import env "rts" "array_blit" [I32Type; I32Type; I32Type; I32Type; I32Type] [];
     ()
 
 end (* RTS *)
 
+module PerfCounters = struct
+  (* See rts/motoko-rts/src/perf.rs. With --perf-counters the code of a
+     subsystem is wrapped in perf_enter and perf_leave, with the instruction
+     counter of the message. Locally there is no counter, nothing is counted *)
+
+  (* See Subsystem *)
+  type subsystem = Gc | CandidDecode | CandidEncode | Stabilization
+
+  let subsystem_code = function
+    | Gc -> 0l
+    | CandidDecode -> 1l
+    | CandidEncode -> 2l
+    | Stabilization -> 3l
+
+  let now env =
+    match E.mode env with
+    | Flags.ICMode | Flags.RefMode ->
+      compile_unboxed_const 0l ^^ (* instruction counter *)
+      E.call_import env "ic0" "performance_counter"
+    | _ ->
+      compile_const_64 0L
+
+  let section env subsystem code =
+    if not !Flags.perf_counters then code else
+    compile_unboxed_const (subsystem_code subsystem) ^^ now env ^^
+    E.call_import env "rts" "perf_enter" ^^
+    code ^^
+    now env ^^
+    E.call_import env "rts" "perf_leave"
+
+end (* PerfCounters *)
+
 module Heap = struct
   (* General heap object functionality (allocation, setting fields, reading fields) *)
 
@@ -848,11 +1039,27 @@ module Heap = struct
 
   let register_globals env =
     (* end-of-heap pointer, we set this to __heap_base upon start *)
-    E.add_global32 env "end_of_heap" Mutable 0xDEADBEEFl
+    E.add_global32 env "end_of_heap" Mutable 0xDEADBEEFl;
+    (* locations of the RTS variables used by the allocation fast path,
+       set by init_alloc_fast_path *)
+    E.add_global32 env "alloc_hp_loc" Mutable 0l;
+    E.add_global32 env "alloc_limit_loc" Mutable 0l;
+    E.add_global32 env "alloc_allocated_loc" Mutable 0l;
+    E.add_global32 env "alloc_objects_loc" Mutable 0l
 
   let get_total_allocation env =
     E.call_import env "rts" "get_total_allocations"
 
+  (* Called at the start of each message, see message_alloc_reset in the RTS *)
+  let message_alloc_reset env =
+    E.call_import env "rts" "message_alloc_reset"
+
+  (* Returns a record with the allocations since the start of the message,
+     bytes (0) and objects (8), see MessageAllocation in
+     rts/motoko-rts/src/gc.rs *)
+  let get_message_allocation env =
+    E.call_import env "rts" "get_message_allocation"
+
   let get_reclaimed env =
     E.call_import env "rts" "get_reclaimed"
 
@@ -863,14 +1070,120 @@ module Heap = struct
   let get_max_live_size env =
     E.call_import env "rts" "get_max_live_size"
 
+  (* Returns a record with the GC statistics. The fields of the RTS record
+     (RtsGcStats in rts/motoko-rts/src/gc.rs) are at these offsets:
+     collections (0), reclaimed (8), gc_instructions (16), max_live_size (24),
+     heap_size (28), fragmentation (32), callback_table_retained (36). *)
+  let get_gc_stats env =
+    E.call_import env "rts" "get_gc_stats"
+
+  (* Collection at the end of the current message, see gc_force in the RTS *)
+  let force_gc env =
+    E.call_import env "rts" "gc_force"
+
+  (* Expects a Bool: enter (true) or leave (false) a region without collections *)
+  let defer_gc env =
+    E.call_import env "rts" "gc_defer"
+
+  (* Expects the target utilization (percent), and the minimum and maximum
+     number of messages between collections, see schedule.rs in the RTS *)
+  let set_gc_schedule env =
+    E.call_import env "rts" "set_gc_schedule"
+
+  (* Fragmentation (percent) up to which the incremental GC leaves a region of
+     the heap in place when compacting, 0 to compact the whole heap, see
+     incremental.rs *)
+  let set_compaction_threshold env =
+    E.call_import env "rts" "set_compaction_threshold"
+
+  (* Soft limit on the heap size in bytes, 0 for no limit. Allocations that
+     would exceed it trap, see set_heap_limit in the RTS. *)
+  let set_heap_limit env =
+    E.call_import env "rts" "set_heap_limit"
+
+  let get_heap_limit env =
+    E.call_import env "rts" "get_heap_limit"
+
+  (* Returns a blob, see rts/motoko-rts/src/heap_snapshot.rs for the format *)
+  let heap_snapshot env =
+    E.call_import env "rts" "heap_snapshot"
+
+  (* Records the number of objects and bytes per tag. heap_diff returns an
+     array of (tag name, objects, bytes) tuples with the changes since then,
+     see rts/motoko-rts/src/heap_census.rs *)
+  let heap_checkpoint env =
+    E.call_import env "rts" "heap_checkpoint"
+
+  let heap_diff env =
+    E.call_import env "rts" "heap_diff"
+
+  (* Returns an array of (tag name, objects, bytes) tuples with the objects
+     reachable from the static roots and the RTS tables *)
+  let heap_live_census env =
+    E.call_import env "rts" "heap_live_census"
+
+  (* Called at the end of update messages, and after init and upgrade *)
+  let collect_garbage env =
+    PerfCounters.section env PerfCounters.Gc
+    (match !Flags.gc_strategy with
+    | Flags.Copying -> E.call_import env "rts" "collect"
+    | Flags.Incremental -> E.call_import env "rts" "incremental_gc"
+    | Flags.Generational -> E.call_import env "rts" "generational_gc"
+    | Flags.MarkSweep -> E.call_import env "rts" "mark_sweep_gc")
+
   let dyn_alloc_words env =
     E.call_import env "rts" "alloc_words"
 
+  let get_loc env name = G.i (GlobalGet (nr (E.get_global env name)))
+  let set_loc env name = G.i (GlobalSet (nr (E.get_global env name)))
+
+  (* Called on start up, after the RTS is initialized. get_alloc_fast_path
+     returns a record with the locations of the heap pointer (0), the limit
+     of the fast path (4), the counter of its allocations (8), and the
+     counter of allocated objects (12), see AllocFastPath in
+     rts/motoko-rts/src/alloc/gc.rs *)
+  let init_alloc_fast_path env =
+    let (set_locs, get_locs) = new_local env "locs" in
+    let load_loc offset = G.i (Load {ty = I32Type; align = 2; offset; sz = None}) in
+    E.call_import env "rts" "get_alloc_fast_path" ^^ set_locs ^^
+    get_locs ^^ load_loc 0l ^^ set_loc env "alloc_hp_loc" ^^
+    get_locs ^^ load_loc 4l ^^ set_loc env "alloc_limit_loc" ^^
+    get_locs ^^ load_loc 8l ^^ set_loc env "alloc_allocated_loc" ^^
+    get_locs ^^ load_loc 12l ^^ set_loc env "alloc_objects_loc"
+
+  (* Objects at least this large are registered by alloc_words, see
+     LARGE_OBJECT_THRESHOLD in rts/motoko-rts/src/gc/large_objects.rs *)
+  let large_object_threshold = page_size
+
   (* Static allocation (always words)
-     (uses dynamic allocation for smaller and more readable code) *)
+     Bumps the heap pointer when the object fits below the limit set by the
+     RTS, and calls alloc_words otherwise. The limit is 0 when allocations
+     need more than that (redzones, allocation profile, free chunks of the
+     mark-sweep GC), see ALLOC_LIMIT in rts/motoko-rts/src/alloc/gc.rs. *)
   let alloc env (n : int32) : G.t =
-    compile_unboxed_const n  ^^
-    dyn_alloc_words env
+    let bytes = Int32.mul n word_size in
+    let slow_path = compile_unboxed_const n ^^ dyn_alloc_words env in
+    if Int32.compare bytes large_object_threshold >= 0 then slow_path else
+    let (set_hp, get_hp) = new_local env "hp" in
+    let (set_new_hp, get_new_hp) = new_local env "new_hp" in
+    get_loc env "alloc_hp_loc" ^^ load_unskewed_ptr ^^ set_hp ^^
+    get_hp ^^ compile_add_const bytes ^^ set_new_hp ^^
+    get_new_hp ^^ get_loc env "alloc_limit_loc" ^^ load_unskewed_ptr ^^
+    G.i (Compare (Wasm.Values.I32 I32Op.LeU)) ^^
+    G.if_ [I32Type]
+      begin
+        get_loc env "alloc_hp_loc" ^^ get_new_hp ^^ store_unskewed_ptr ^^
+        get_loc env "alloc_allocated_loc" ^^
+        get_loc env "alloc_allocated_loc" ^^ load_unskewed_ptr ^^
+        compile_add_const bytes ^^
+        store_unskewed_ptr ^^
+        get_loc env "alloc_objects_loc" ^^
+        get_loc env "alloc_objects_loc" ^^ load_unskewed_ptr ^^
+        compile_add_const 1l ^^
+        store_unskewed_ptr ^^
+        get_hp ^^ compile_add_const ptr_skew
+      end
+      slow_path
 
   (* Heap objects *)
 
@@ -884,6 +1197,26 @@ module Heap = struct
     let offset = Int32.(add (mul word_size i) ptr_unskew) in
     G.i (Store {ty = I32Type; align = 2; offset; sz = None})
 
+  (* Like store_field, but for fields of objects that may have survived a
+     message. The incremental and generational GCs need to know about these
+     writes. *)
+  let store_field_with_barrier env (i : int32) : G.t =
+    let barrier name =
+      let (set_val, get_val) = new_local env "val" in
+      let (set_ptr, get_ptr) = new_local env "ptr" in
+      set_val ^^ set_ptr ^^
+      get_ptr ^^ compile_add_const Int32.(add (mul word_size i) ptr_unskew) ^^
+      E.call_import env "rts" name ^^
+      get_ptr ^^ get_val ^^ store_field i in
+    match !Flags.gc_strategy with
+    | Flags.Copying | Flags.MarkSweep -> store_field i
+    | Flags.Incremental -> barrier "write_barrier"
+    | Flags.Generational -> barrier "generational_write_barrier"
+
+  (* Same as store_ptr, with the barrier *)
+  let store_ptr_with_barrier env : G.t =
+    store_field_with_barrier env 0l
+
   (* Although we occasionally want to treat two 32 bit fields as one 64 bit number *)
 
   let load_field64 (i : int32) : G.t =
@@ -993,6 +1326,37 @@ module ClosureTable = struct
   let size env : G.t = E.call_import env "rts" "closure_table_size"
 end (* ClosureTable *)
 
+module Timer = struct
+  (* See rts/motoko-rts/src/timer.rs *)
+  let set env : G.t = E.call_import env "rts" "timer_set"
+  let cancel env : G.t = E.call_import env "rts" "timer_cancel"
+  let expired env : G.t = E.call_import env "rts" "timer_expired"
+  let next env : G.t = E.call_import env "rts" "timer_next"
+  let dequeue env : G.t = E.call_import env "rts" "timer_dequeue"
+  let rearm env : G.t = E.call_import env "rts" "timer_rearm"
+end (* Timer *)
+
+module Finalizer = struct
+  (* See rts/motoko-rts/src/finalizers.rs *)
+  let register env : G.t = E.call_import env "rts" "register_finalizer"
+  let pending env : G.t = E.call_import env "rts" "pending_finalizers"
+  let next env : G.t = E.call_import env "rts" "next_finalizer"
+  let dequeue env : G.t = E.call_import env "rts" "dequeue_finalizer"
+end (* Finalizer *)
+
+module WeakRef = struct
+  (* See rts/motoko-rts/src/weak_ref.rs *)
+  let alloc env : G.t = E.call_import env "rts" "weak_ref_new"
+  let alive env : G.t = E.call_import env "rts" "weak_ref_alive"
+  let get env : G.t = E.call_import env "rts" "weak_ref_get"
+end (* WeakRef *)
+
+module Pin = struct
+  (* See rts/motoko-rts/src/pin.rs *)
+  let pin env : G.t = E.call_import env "rts" "pin_object"
+  let unpin env : G.t = E.call_import env "rts" "unpin_object"
+end (* Pin *)
+
 module Bool = struct
   (* Boolean literals are either 0 or 1,
      at StackRep UnboxedWord32
@@ -1151,7 +1515,6 @@ module Tagged = struct
     | BigInt
     | Concat (* String concatenation, used by rts/text.c *)
     | Null (* For opt. Static singleton! *)
-    | StableSeen (* Marker that we have seen this thing before *)
     | CoercionFailure (* Used in the Candid decoder. Static singleton! *)
 
   (* Let's leave out tag 0 to trap earlier on invalid memory *)
@@ -1171,7 +1534,8 @@ module Tagged = struct
     | Concat -> 14l
     | Null -> 15l
     | CoercionFailure -> 0xfffffffel
-    | StableSeen -> 0xffffffffl
+    (* 0xffffffff marks mutable values seen when serializing stable variables,
+       see Note [mutable stable values] *)
 
   (* The tag *)
   let header_size = 1l
@@ -1832,6 +2196,14 @@ module Float = struct
 
   let unbox env = Heap.load_field_float64 payload_field
 
+  (* Parsing, see rts/motoko-rts/src/float_parse.rs. The RTS returns a boxed
+     float, or 0 when the text is not a float *)
+  let of_text env =
+    let (set_r, get_r) = new_local env "r" in
+    E.call_import env "rts" "float_of_text" ^^ set_r ^^
+    get_r ^^ G.i (Test (Wasm.Values.I32 I32Op.Eqz)) ^^
+    G.if_ [I32Type] (Opt.null_lit env) (Opt.inject_noop env get_r)
+
 end (* Float *)
 
 
@@ -1882,7 +2254,7 @@ module ReadBuf = struct
     get_delta ^^
     get_end get_buf ^^ get_ptr get_buf ^^ G.i (Binary (Wasm.Values.I32 I32Op.Sub)) ^^
     G.i (Compare (Wasm.Values.I32 I64Op.LeU)) ^^
-    E.else_trap_with env "IDL error: out of bounds read"
+    E.else_trap_with env (idl_error "out of bounds read")
 
   let is_empty env get_buf =
     get_end get_buf ^^ get_ptr get_buf ^^
@@ -1971,6 +2343,29 @@ sig
    *)
   val compile_load_from_data_buf : E.t -> bool -> G.t
 
+  (* little-endian blobs *)
+  (* numeric object (vanilla) to the blob of its magnitude *)
+  val compile_to_le_blob : E.t -> G.t
+  (* blob to the natural number *)
+  val compile_of_le_blob : E.t -> G.t
+  (* numeric object (vanilla) and width (i32, TOS) to the blob of the two's
+     complement in that many bytes *)
+  val compile_to_signed_le_blob : E.t -> G.t
+  (* blob of the two's complement to the number *)
+  val compile_of_signed_le_blob : E.t -> G.t
+
+  (* numeric object (vanilla) to its decimal text, with `_` separators as in
+     `debug_show` *)
+  val compile_to_text : E.t -> G.t
+  (* text and radix (i32, TOS) to the number as an option, null when the text
+     is malformed *)
+  val compile_of_text_radix : E.t -> G.t
+
+  (* numeric object (vanilla) to the nearest f64, ties to even *)
+  val compile_to_float64 : E.t -> G.t
+  (* f64 to the number, truncated towards zero, traps on NaN and infinities *)
+  val compile_of_float64 : E.t -> G.t
+
   (* literals *)
   val vanilla_lit : E.t -> Big_int.big_int -> int32
 
@@ -1986,12 +2381,29 @@ sig
   val compile_unsigned_div : E.t -> G.t
   val compile_unsigned_rem : E.t -> G.t
   val compile_unsigned_pow : E.t -> G.t
+  (* base, exponent and modulus on the stack *)
+  val compile_pow_mod : E.t -> G.t
+  (* integer square root, of a non-negative number *)
+  val compile_isqrt : E.t -> G.t
+  (* greatest common divisor and least common multiple, of the magnitudes *)
+  val compile_gcd : E.t -> G.t
+  val compile_lcm : E.t -> G.t
 
   (* comparisons *)
   val compile_eq : E.t -> G.t
   val compile_is_negative : E.t -> G.t
   val compile_relop : E.t -> comparator -> G.t
 
+  (* bitwise operations, on the two's complement with infinitely many sign bits *)
+  val compile_and : E.t -> G.t
+  val compile_or : E.t -> G.t
+  val compile_xor : E.t -> G.t
+  (* shifts, the amount (unboxed i32) on TOS *)
+  val compile_shl : E.t -> G.t
+  val compile_shr : E.t -> G.t
+  (* bit test, the index (unboxed i32) on TOS, leaves boolean result on the stack *)
+  val compile_bit_test : E.t -> G.t
+
   (* representation checks *)
   (* given a numeric object on the stack as skewed pointer, check whether
      it can be faithfully stored in N bits, including a leading sign bit
@@ -2157,6 +2569,139 @@ module MakeCompact (Num : BigNumType) : BigNumType = struct
   let compile_unsigned_rem = try_unbox2 "B_rem" Word64.compile_unsigned_rem Num.compile_unsigned_rem
   let compile_unsigned_sub = try_unbox2 "B_sub" Word64.compile_unsigned_sub Num.compile_unsigned_sub
 
+  (* The bitwise operations work on the right-0-padded representation directly *)
+  let compile_and = try_unbox2 "B_and" (fun _ -> G.i (Binary (Wasm.Values.I64 I64Op.And))) Num.compile_and
+  let compile_or = try_unbox2 "B_or" (fun _ -> G.i (Binary (Wasm.Values.I64 I64Op.Or))) Num.compile_or
+  let compile_xor = try_unbox2 "B_xor" (fun _ -> G.i (Binary (Wasm.Values.I64 I64Op.Xor))) Num.compile_xor
+
+  (* The second argument of shifts and bit tests is an unboxed i32, only the
+     first argument needs boxing. These always take the slow path. *)
+  let box_arg1 env get_a =
+    get_a ^^ BitTagged.if_tagged_scalar env [I32Type]
+      (get_a ^^ extend_and_box64 env)
+      get_a
+
+  let try_shift name slow env =
+    Func.share_code2 env name (("a", I32Type), ("b", I32Type)) [I32Type]
+      (fun env get_a get_b ->
+        let set_res, get_res = new_local env "res" in
+        box_arg1 env get_a ^^
+        get_b ^^
+        slow env ^^ set_res ^^ get_res ^^
+        fits_in_vanilla env ^^
+        G.if_ [I32Type]
+          (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+          get_res)
+
+  let compile_shl = try_shift "B_shl" Num.compile_shl
+  let compile_shr = try_shift "B_shr" Num.compile_shr
+
+  let compile_pow_mod env =
+    Func.share_code3 env "B_pow_mod" (("base", I32Type), ("exp", I32Type), ("modulus", I32Type)) [I32Type]
+      (fun env get_base get_exp get_modulus ->
+        let set_res, get_res = new_local env "res" in
+        box_arg1 env get_base ^^
+        box_arg1 env get_exp ^^
+        box_arg1 env get_modulus ^^
+        Num.compile_pow_mod env ^^ set_res ^^ get_res ^^
+        fits_in_vanilla env ^^
+        G.if_ [I32Type]
+          (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+          get_res)
+
+  let compile_isqrt env =
+    Func.share_code1 env "B_isqrt" ("n", I32Type) [I32Type] (fun env get_n ->
+      let set_res, get_res = new_local env "res" in
+      box_arg1 env get_n ^^
+      Num.compile_isqrt env ^^ set_res ^^ get_res ^^
+      fits_in_vanilla env ^^
+      G.if_ [I32Type]
+        (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+        get_res)
+
+  let box2 name num env =
+    Func.share_code2 env name (("a", I32Type), ("b", I32Type)) [I32Type]
+      (fun env get_a get_b ->
+        let set_res, get_res = new_local env "res" in
+        box_arg1 env get_a ^^
+        box_arg1 env get_b ^^
+        num env ^^ set_res ^^ get_res ^^
+        fits_in_vanilla env ^^
+        G.if_ [I32Type]
+          (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+          get_res)
+
+  let compile_gcd = box2 "B_gcd" Num.compile_gcd
+  let compile_lcm = box2 "B_lcm" Num.compile_lcm
+
+  let compile_to_le_blob env =
+    Func.share_code1 env "B_to_le_blob" ("n", I32Type) [I32Type] (fun env get_n ->
+      box_arg1 env get_n ^^
+      Num.compile_to_le_blob env)
+
+  let compile_to_signed_le_blob env =
+    Func.share_code2 env "B_to_signed_le_blob" (("n", I32Type), ("width", I32Type)) [I32Type]
+      (fun env get_n get_width ->
+        box_arg1 env get_n ^^
+        get_width ^^
+        Num.compile_to_signed_le_blob env)
+
+  let from_blob name num env =
+    Func.share_code1 env name ("b", I32Type) [I32Type] (fun env get_b ->
+      let set_res, get_res = new_local env "res" in
+      get_b ^^
+      num env ^^ set_res ^^ get_res ^^
+      fits_in_vanilla env ^^
+      G.if_ [I32Type]
+        (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+        get_res)
+
+  let compile_of_le_blob = from_blob "B_of_le_blob" Num.compile_of_le_blob
+  let compile_of_signed_le_blob = from_blob "B_of_signed_le_blob" Num.compile_of_signed_le_blob
+
+  let compile_to_text env =
+    Func.share_code1 env "B_to_text" ("n", I32Type) [I32Type] (fun env get_n ->
+      box_arg1 env get_n ^^
+      Num.compile_to_text env)
+
+  let compile_of_text_radix env =
+    Func.share_code2 env "B_of_text_radix" (("t", I32Type), ("radix", I32Type)) [I32Type]
+      (fun env get_t get_radix ->
+        let set_res, get_res = new_local env "res" in
+        get_t ^^ get_radix ^^
+        Num.compile_of_text_radix env ^^ set_res ^^
+        get_res ^^ Opt.is_some env ^^
+        G.if_ [I32Type]
+          (get_res ^^ fits_in_vanilla env ^^
+           G.if_ [I32Type]
+             (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+             get_res)
+          get_res)
+
+  let compile_to_float64 env =
+    Func.share_code1 env "B_to_float64" ("n", I32Type) [F64Type] (fun env get_n ->
+      get_n ^^
+      BitTagged.if_tagged_scalar env [F64Type]
+        (get_n ^^ BitTagged.untag_i32 ^^ G.i (Convert (Wasm.Values.F64 F64Op.ConvertSI32)))
+        (get_n ^^ Num.compile_to_float64 env))
+
+  let compile_of_float64 env =
+    Func.share_code1 env "B_of_float64" ("f", F64Type) [I32Type] (fun env get_f ->
+      let set_res, get_res = new_local env "res" in
+      get_f ^^
+      Num.compile_of_float64 env ^^ set_res ^^ get_res ^^
+      fits_in_vanilla env ^^
+      G.if_ [I32Type]
+        (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
+        get_res)
+
+  let compile_bit_test env =
+    Func.share_code2 env "B_bit_test" (("a", I32Type), ("b", I32Type)) [I32Type]
+      (fun env get_a get_b ->
+        box_arg1 env get_a ^^
+        get_b ^^
+        Num.compile_bit_test env)
+
   let compile_unsigned_pow env =
     Func.share_code2 env "B_pow" (("a", I32Type), ("b", I32Type)) [I32Type]
     (fun env get_a get_b ->
@@ -2323,14 +2868,11 @@ module MakeCompact (Num : BigNumType) : BigNumType = struct
       Num.compile_abs
       env
 
-  let compile_load_from_data_buf env signed =
-    let set_res, get_res = new_local env "res" in
-    Num.compile_load_from_data_buf env signed ^^
-    set_res ^^
-    get_res ^^ fits_in_vanilla env ^^
-    G.if_ [I32Type]
-      (get_res ^^ Num.truncate_to_word32 env ^^ BitTagged.tag_i32)
-      get_res
+  (* The RTS decodes numbers of up to 128 bits without a big integer, and
+     returns the compact representation directly *)
+  let compile_load_from_data_buf env = function
+    | false -> E.call_import env "rts" "leb128_decode_nat"
+    | true -> E.call_import env "rts" "sleb128_decode_int"
 
   let compile_store_to_data_buf_unsigned env =
     let set_x, get_x = new_local env "x" in
@@ -2448,7 +2990,7 @@ module MakeCompact (Num : BigNumType) : BigNumType = struct
       (get_a ^^ get_err_msg ^^ Num.to_word32_with env)
 end
 
-module BigNumLibtommath : BigNumType = struct
+module BigNumRts : BigNumType = struct
 
   let to_word32 env = E.call_import env "rts" "bigint_to_word32_trap"
   let to_word64 env = E.call_import env "rts" "bigint_to_word64_trap"
@@ -2483,32 +3025,31 @@ module BigNumLibtommath : BigNumType = struct
     | true -> E.call_import env "rts" "bigint_sleb128_decode"
 
   let vanilla_lit env n =
-    (* See enum mp_sign *)
+    (* See BigInt::sign in the RTS *)
     let sign = if Big_int.sign_big_int n >= 0 then 0l else 1l in
 
     let n = Big_int.abs_big_int n in
 
     let limbs =
-      (* see MP_DIGIT_BIT *)
-      let twoto28 = Big_int.power_int_positive_int 2 28 in
+      (* see DIGIT_BITS in the RTS *)
+      let twoto32 = Big_int.power_int_positive_int 2 32 in
       let rec go n =
         if Big_int.sign_big_int n = 0
         then []
         else
-          let (a, b) = Big_int.quomod_big_int n twoto28 in
-          [ Int32.of_int (Big_int.int_of_big_int b) ] @ go a
+          let (a, b) = Big_int.quomod_big_int n twoto32 in
+          [ Int64.to_int32 (Big_int.int64_of_big_int b) ] @ go a
       in go n
     in
     (* how many 32 bit digits *)
     let size = Int32.of_int (List.length limbs) in
 
-    (* cf. mp_int in tommath.h *)
+    (* cf. BigInt in the RTS *)
     let ptr = E.add_static env StaticBytes.[
       I32 Tagged.(int_of_tag BigInt);
       I32 size; (* used *)
-      I32 size; (* size; relying on Heap.word_size == size_of(mp_digit) *)
+      I32 size; (* alloc *)
       I32 sign;
-      I32 0l; (* dp; this will be patched in BigInt::mp_int_ptr in the RTS when used *)
       i32s limbs
 
     ] in
@@ -2533,6 +3074,27 @@ module BigNumLibtommath : BigNumType = struct
   let compile_unsigned_rem env = E.call_import env "rts" "bigint_rem"
   let compile_unsigned_div env = E.call_import env "rts" "bigint_div"
   let compile_unsigned_pow env = E.call_import env "rts" "bigint_pow"
+  let compile_pow_mod env = E.call_import env "rts" "bigint_pow_mod"
+  let compile_isqrt env = E.call_import env "rts" "bigint_isqrt"
+  let compile_gcd env = E.call_import env "rts" "bigint_gcd"
+  let compile_lcm env = E.call_import env "rts" "bigint_lcm"
+
+  let compile_to_le_blob env = E.call_import env "rts" "bigint_to_le_blob"
+  let compile_of_le_blob env = E.call_import env "rts" "bigint_of_le_blob"
+  let compile_to_signed_le_blob env = E.call_import env "rts" "bigint_to_signed_le_blob"
+  let compile_of_signed_le_blob env = E.call_import env "rts" "bigint_of_signed_le_blob"
+
+  let compile_to_text env =
+    compile_unboxed_const 1l ^^
+    E.call_import env "rts" "bigint_to_text"
+  let compile_of_text_radix env =
+    let (set_r, get_r) = new_local env "r" in
+    E.call_import env "rts" "bigint_of_text_radix" ^^ set_r ^^
+    get_r ^^ G.i (Test (Wasm.Values.I32 I32Op.Eqz)) ^^
+    G.if_ [I32Type] (Opt.null_lit env) (Opt.inject_noop env get_r)
+
+  let compile_to_float64 env = E.call_import env "rts" "bigint_to_float64"
+  let compile_of_float64 env = E.call_import env "rts" "bigint_of_float64"
 
   let compile_eq env = E.call_import env "rts" "bigint_eq"
   let compile_is_negative env = E.call_import env "rts" "bigint_isneg"
@@ -2542,6 +3104,13 @@ module BigNumLibtommath : BigNumType = struct
       | Ge -> E.call_import env "rts" "bigint_ge"
       | Gt -> E.call_import env "rts" "bigint_gt"
 
+  let compile_and env = E.call_import env "rts" "bigint_and"
+  let compile_or env = E.call_import env "rts" "bigint_or"
+  let compile_xor env = E.call_import env "rts" "bigint_xor"
+  let compile_shl env = E.call_import env "rts" "bigint_shl"
+  let compile_shr env = E.call_import env "rts" "bigint_shr"
+  let compile_bit_test env = E.call_import env "rts" "bigint_bit_test"
+
   let fits_signed_bits env bits =
     E.call_import env "rts" "bigint_2complement_bits" ^^
     compile_unboxed_const (Int32.of_int bits) ^^
@@ -2551,9 +3120,9 @@ module BigNumLibtommath : BigNumType = struct
     compile_unboxed_const (Int32.of_int bits) ^^
     G.i (Compare (Wasm.Values.I32 I32Op.LeU))
 
-end (* BigNumLibtommath *)
+end (* BigNumRts *)
 
-module BigNum = MakeCompact(BigNumLibtommath)
+module BigNum = MakeCompact(BigNumRts)
 
 (* Primitive functions *)
 module Prim = struct
@@ -2739,21 +3308,11 @@ module Object = struct
     let _, fields = Type.as_obj_sub [s] obj_type in
     Type.is_mut (Type.lookup_val_field s fields)
 
-  (* Returns a pointer to the object field (without following the indirection) *)
-  let idx_raw env f =
-    compile_unboxed_const (E.hash env f) ^^
-    idx_hash_raw env
-
   (* Returns a pointer to the object field (possibly following the indirection) *)
   let idx env obj_type f =
     compile_unboxed_const (E.hash env f) ^^
     idx_hash env (is_mut_field env obj_type f)
 
-  (* load the value (or the mutbox) *)
-  let load_idx_raw env f =
-    idx_raw env f ^^
-    load_ptr
-
 
   (* load the actual value (dereferencing the mutbox) *)
   let load_idx env obj_type f =
@@ -2918,10 +3477,84 @@ module Blob = struct
     E.call_import env "rts" "blob_iter_next" ^^
     TaggedSmallWord.msb_adjust Type.Nat8
 
-  let dyn_alloc_scratch env = alloc env ^^ payload_ptr_unskewed
+  (* Blob of the given size with all bytes set to the given (unboxed) byte *)
+  let fill env =
+    E.call_import env "rts" "blob_fill"
+
+  (* Concatenation of an array of blobs, allocated at once *)
+  let concat_many env =
+    E.call_import env "rts" "blob_concat_many"
+
+  (* Expects dst, dst offset, src, src offset and length (unboxed). Copy of dst
+     with the range replaced, traps when a range is out of bounds *)
+  let copy_range env =
+    E.call_import env "rts" "blob_copy_range"
+
+  (* Equality that takes the same time for all blobs of the same size, for secrets *)
+  let equal_const_time env =
+    E.call_import env "rts" "blob_compare_const_time"
+
+  (* SHA-256 and SHA-224 of a blob, or of a text without flattening it, see
+     rts/motoko-rts/src/sha256.rs *)
+  let sha256 env =
+    E.call_import env "rts" "blob_sha256"
+  let sha224 env =
+    E.call_import env "rts" "blob_sha224"
+
+  (* Base64 (RFC 4648), see rts/motoko-rts/src/base64.rs. The flags select the
+     URL-safe alphabet (1) and padding (2, only for encoding) *)
+  let base64_encode env flags =
+    compile_unboxed_const flags ^^
+    E.call_import env "rts" "base64_encode"
+  let base64_decode env flags =
+    let (set_r, get_r) = new_local env "r" in
+    compile_unboxed_const flags ^^
+    E.call_import env "rts" "base64_decode" ^^ set_r ^^
+    get_r ^^ G.i (Test (Wasm.Values.I32 I32Op.Eqz)) ^^
+    G.if_ [I32Type] (Opt.null_lit env) (Opt.inject_noop env get_r)
+
+  (* Hexadecimal, see rts/motoko-rts/src/hex.rs. Encoding uses lowercase
+     digits, decoding accepts both cases and a "0x" prefix *)
+  let to_hex env =
+    E.call_import env "rts" "blob_to_hex"
+  let of_hex env =
+    let (set_r, get_r) = new_local env "r" in
+    E.call_import env "rts" "blob_of_hex" ^^ set_r ^^
+    get_r ^^ G.i (Test (Wasm.Values.I32 I32Op.Eqz)) ^^
+    G.if_ [I32Type] (Opt.null_lit env) (Opt.inject_noop env get_r)
+
+  (* Transient buffer of the given size, valid until the end of the message.
+     Not a heap object, returns the (unskewed) address of the buffer. Allocated
+     in the idle memory above the heap (see to_space_arena.rs in the RTS) when
+     it fits there, in the scratch space otherwise. *)
+  let dyn_alloc_scratch env =
+    Func.share_code1 env "dyn_alloc_scratch" ("size", I32Type) [I32Type] (fun env get_size ->
+      let (set_buf, get_buf) = new_local env "buf" in
+      get_size ^^ E.call_import env "rts" "to_space_alloc" ^^ set_buf ^^
+      get_buf ^^
+      G.if_ [I32Type]
+        get_buf
+        (get_size ^^ E.call_import env "rts" "scratch_alloc")
+    )
 
 end (* Blob *)
 
+module Backtrace = struct
+  (* See rts/motoko-rts/src/backtrace.rs. The frames are only pushed with
+     --trap-backtrace. *)
+  let push env name at =
+    if !Flags.trap_backtrace
+    then
+      Blob.lit env (Printf.sprintf "%s (%s)" name (Source.string_of_region at)) ^^
+      E.call_import env "rts" "backtrace_push"
+    else G.nop
+
+  let pop env =
+    if !Flags.trap_backtrace
+    then E.call_import env "rts" "backtrace_pop"
+    else G.nop
+end (* Backtrace *)
+
 module Text = struct
   (*
   Most of the heavy lifting around text values is in rts/motoko-rts/src/text.rs
@@ -2929,11 +3562,12 @@ module Text = struct
 
   (* The layout of a concatenation node is
 
-     ┌─────┬─────────┬───────┬───────┐
-     │ tag │ n_bytes │ text1 │ text2 │
-     └─────┴─────────┴───────┴───────┘
+     ┌─────┬─────────┬───────┬───────┬───────┐
+     │ tag │ n_bytes │ text1 │ text2 │ depth │
+     └─────┴─────────┴───────┴───────┴───────┘
 
     This is internal to rts/text.c, with the exception of GC-related code.
+    The same holds for slice nodes, see text_slice in rts/motoko-rts/src/text.rs.
   *)
 
   let of_ptr_size env =
@@ -2951,6 +3585,48 @@ module Text = struct
     E.call_import env "rts" "text_singleton"
   let to_blob env = E.call_import env "rts" "blob_of_text"
 
+  (* Pointer to and size of the bytes of the text, e.g. for system calls. A blob is used as it is,
+     other texts are written to a transient buffer (see Blob.dyn_alloc_scratch) with text_to_buf,
+     instead of being copied to a new blob in the heap. *)
+  let as_ptr_len env = Func.share_code1 env "text_as_ptr_len" ("text", I32Type) [I32Type; I32Type] (
+    fun env get_text ->
+      let (set_size, get_size) = new_local env "size" in
+      let (set_buf, get_buf) = new_local env "buf" in
+      get_text ^^ size env ^^ set_size ^^
+      get_text ^^ Tagged.load ^^
+      compile_eq_const Tagged.(int_of_tag Blob) ^^
+      G.if_ [I32Type]
+        (get_text ^^ Blob.payload_ptr_unskewed)
+        ( get_size ^^ Blob.dyn_alloc_scratch env ^^ set_buf ^^
+          get_text ^^ get_buf ^^ to_buf env ^^
+          get_buf ) ^^
+      get_size
+    )
+
+  (* Expects the text, and the start and the length of the slice in bytes *)
+  let slice env =
+    E.call_import env "rts" "text_slice"
+
+  (* Expects the haystack and the needle, returns the byte offset of the first
+     occurrence as ?Nat *)
+  let find env =
+    let (set_offset, get_offset) = new_local env "offset" in
+    E.call_import env "rts" "text_find" ^^ set_offset ^^
+    get_offset ^^ compile_eq_const (-1l) ^^
+    G.if_ [I32Type]
+      (Opt.null_lit env)
+      (Opt.inject_noop env (get_offset ^^ Prim.prim_word32toNat env))
+
+  (* Full Unicode case mappings, a character can map to multiple characters *)
+  let to_upper env = E.call_import env "rts" "text_to_upper"
+  let to_lower env = E.call_import env "rts" "text_to_lower"
+
+  (* Unicode normalization form C, see rts/motoko-rts/src/normalize.rs *)
+  let normalize_nfc env = E.call_import env "rts" "text_normalize_nfc"
+
+  (* Texts with the same contents share one object, see rts/motoko-rts/src/text_intern.rs *)
+  let intern env = E.call_import env "rts" "text_intern"
+
   let of_blob env =
     let (set_blob, get_blob) = new_local env "blob" in
     set_blob ^^
@@ -2958,6 +3634,22 @@ module Text = struct
     E.call_import env "rts" "utf8_valid" ^^
     G.if_ [I32Type] (Opt.inject_noop env get_blob) (Opt.null_lit env)
 
+  (* Invalid UTF-8 sequences are replaced with U+FFFD *)
+  let of_blob_lossy env = E.call_import env "rts" "text_of_blob_lossy"
+
+  (* The text repeated the given number of times, copied into a single leaf *)
+  let repeat env = E.call_import env "rts" "text_repeat"
+
+  (* UTF-16, little-endian and without byte order mark *)
+  let to_utf16_blob env = E.call_import env "rts" "text_to_utf16_blob"
+  let of_utf16_blob env =
+    let (set_blob, get_blob) = new_local env "blob" in
+    set_blob ^^
+    get_blob ^^ Blob.as_ptr_len env ^^
+    E.call_import env "rts" "utf16_valid" ^^
+    G.if_ [I32Type]
+      (Opt.inject_noop env (get_blob ^^ E.call_import env "rts" "text_of_utf16_blob"))
+      (Opt.null_lit env)
 
   let iter env =
     E.call_import env "rts" "text_iter"
@@ -2967,6 +3659,23 @@ module Text = struct
     E.call_import env "rts" "text_iter_next" ^^
     TaggedSmallWord.tag_codepoint
 
+  (* Iterator over the parts between the occurrences of a delimiter, see
+     rts/motoko-rts/src/text_split.rs *)
+  let split env =
+    E.call_import env "rts" "text_split"
+  let split_done env =
+    E.call_import env "rts" "text_split_done"
+  let split_next env =
+    E.call_import env "rts" "text_split_next"
+
+  (* Iterator over the grapheme clusters, see rts/motoko-rts/src/grapheme.rs *)
+  let graphemes env =
+    E.call_import env "rts" "text_iter_graphemes"
+  let graphemes_done env =
+    E.call_import env "rts" "text_iter_graphemes_done"
+  let graphemes_next env =
+    E.call_import env "rts" "text_iter_graphemes_next"
+
   let compare env op =
     let open Operator in
     let name = match op with
@@ -3036,6 +3745,21 @@ module Arr = struct
       idx env
   )
 
+  (* Expects the address returned by idx and the value. Stores the value, and
+     then calls the post-write barrier of the generational GC with the array
+     and the index, which marks a card in the card table of large arrays, see
+     rts/motoko-rts/src/gc/card_table.rs *)
+  let store_with_post_barrier env get_array =
+    let (set_val, get_val) = new_local env "val" in
+    let (set_loc, get_loc) = new_local env "loc" in
+    set_val ^^ set_loc ^^
+    get_loc ^^ get_val ^^ store_ptr ^^
+    get_array ^^
+    get_loc ^^ get_array ^^ G.i (Binary (Wasm.Values.I32 I32Op.Sub)) ^^
+    compile_divU_const element_size ^^
+    compile_sub_const header_size ^^
+    E.call_import env "rts" "post_write_barrier"
+
 
   let vanilla_lit env ptrs =
     E.add_static env StaticBytes.[
@@ -3053,6 +3777,11 @@ module Arr = struct
   (* Does not initialize the fields! *)
   let alloc env = E.call_import env "rts" "alloc_array"
 
+  (* Expects dst, dst offset, src, src offset and length (unboxed). Copies the
+     range of src to dst in place (with the write barriers), traps when a range
+     is out of bounds *)
+  let blit env = E.call_import env "rts" "array_blit"
+
   (* The primitive operations *)
   (* No need to wrap them in RTS functions: They occur only once, in the prelude. *)
   let init env =
@@ -3327,6 +4056,10 @@ module IC = struct
       E.add_func_import env "ic0" "stable_size" [] [I32Type];
       E.add_func_import env "ic0" "stable_grow" [I32Type] [I32Type];
       E.add_func_import env "ic0" "time" [] [I64Type];
+      if !Flags.global_timer then
+        E.add_func_import env "ic0" "global_timer_set" [I64Type] [I64Type];
+      if !Flags.perf_counters then
+        E.add_func_import env "ic0" "performance_counter" [I32Type] [I64Type];
       ()
 
   let system_imports env =
@@ -3404,10 +4137,7 @@ module IC = struct
 
   let print_text env =
     Func.share_code1 env "print_text" ("str", I32Type) [] (fun env get_str ->
-      let (set_blob, get_blob) = new_local env "blob" in
-      get_str ^^ Text.to_blob env ^^ set_blob ^^
-      get_blob ^^ Blob.payload_ptr_unskewed ^^
-      get_blob ^^ Blob.len env ^^
+      get_str ^^ Text.as_ptr_len env ^^
       print_ptr_len env
     )
 
@@ -3424,10 +4154,16 @@ module IC = struct
     | Flags.ICMode | Flags.RefMode -> ic_trap env ^^ G.i Unreachable
 
   let trap_with env s =
-    Blob.lit_ptr_len env s ^^ trap_ptr_len env
+    if !Flags.trap_backtrace
+    then
+      Blob.lit_ptr_len env s ^^
+      E.call_import env "rts" "trap_with_backtrace" ^^
+      G.i Unreachable
+    else
+      Blob.lit_ptr_len env s ^^ trap_ptr_len env
 
   let _trap_text env  =
-    Text.to_blob env ^^ Blob.as_ptr_len env ^^ trap_ptr_len env
+    Text.as_ptr_len env ^^ trap_ptr_len env
 
   let default_exports env =
     (* these exports seem to be wanted by the hypervisor/v8 *)
@@ -3448,10 +4184,11 @@ module IC = struct
     assert (E.mode env = Flags.ICMode || E.mode env = Flags.RefMode);
     let empty_f = Func.of_body env [] [] (fun env ->
       Lifecycle.trans env Lifecycle.InInit ^^
+      Heap.message_alloc_reset env ^^
 
       G.i (Call (nr (E.built_in env "init"))) ^^
       (* Collect garbage *)
-      E.call_import env "rts" "collect" ^^
+      Heap.collect_garbage env ^^
 
       Lifecycle.trans env Lifecycle.Idle
     ) in
@@ -3478,17 +4215,19 @@ module IC = struct
 
     let pre_upgrade_fi = E.add_fun env "pre_upgrade" (Func.of_body env [] [] (fun env ->
       Lifecycle.trans env Lifecycle.InPreUpgrade ^^
+      Heap.message_alloc_reset env ^^
       G.i (Call (nr (E.built_in env "pre_exp"))) ^^
       Lifecycle.trans env Lifecycle.PostPreUpgrade
     )) in
 
     let post_upgrade_fi = E.add_fun env "post_upgrade" (Func.of_body env [] [] (fun env ->
       Lifecycle.trans env Lifecycle.InInit ^^
+      Heap.message_alloc_reset env ^^
       G.i (Call (nr (E.built_in env "init"))) ^^
       Lifecycle.trans env Lifecycle.InPostUpgrade ^^
       G.i (Call (nr (E.built_in env "post_exp"))) ^^
       Lifecycle.trans env Lifecycle.Idle ^^
-      E.call_import env "rts" "collect"
+      Heap.collect_garbage env
     )) in
 
     E.add_export env (nr {
@@ -3534,8 +4273,7 @@ module IC = struct
     match E.mode env with
     | Flags.ICMode | Flags.RefMode ->
       arg_instrs ^^
-      Text.to_blob env ^^
-      Blob.as_ptr_len env ^^
+      Text.as_ptr_len env ^^
       system_call env "ic0" "msg_reject"
     | _ ->
       E.trap_with env (Printf.sprintf "cannot reject when running locally")
@@ -3703,6 +4441,46 @@ module RTS_Exports = struct
     E.add_export env (nr {
       name = Wasm.Utf8.decode "rts_trap";
       edesc = nr (FuncExport (nr rts_trap_fi))
+    });
+
+    (* The stable memory system calls, for the regions of the RTS (see region.rs) *)
+    let stable_mem_export name system_call params results =
+      let fi = E.add_fun env name (
+        Func.of_body env (List.map (fun p -> (p, I32Type)) params) results (fun env ->
+          match E.mode env with
+          | Flags.ICMode | Flags.RefMode ->
+            G.concat_mapi (fun i _ -> G.i (LocalGet (nr (Int32.of_int i)))) params ^^
+            IC.system_call env "ic0" system_call
+          | _ ->
+            E.trap_with env "no stable memory when running locally"
+        )
+      ) in
+      E.add_export env (nr {
+        name = Wasm.Utf8.decode name;
+        edesc = nr (FuncExport (nr fi))
+      }) in
+    stable_mem_export "stable_mem_size" "stable_size" [] [I32Type];
+    stable_mem_export "stable_mem_grow" "stable_grow" ["pages"] [I32Type];
+    stable_mem_export "stable_mem_read" "stable_read" ["dst"; "offset"; "len"] [];
+    stable_mem_export "stable_mem_write" "stable_write" ["offset"; "src"; "len"] [];
+
+    (* The global timer, for the timers of the RTS (see timer.rs) *)
+    let timer_global_set_fi = E.add_fun env "timer_global_set" (
+      Func.of_body env ["timestamp", I64Type] [] (fun env ->
+        match E.mode env with
+        | (Flags.ICMode | Flags.RefMode) when !Flags.global_timer ->
+          G.i (LocalGet (nr 0l)) ^^
+          IC.system_call env "ic0" "global_timer_set" ^^
+          G.i Drop
+        | Flags.ICMode | Flags.RefMode ->
+          E.trap_with env "timers need --global-timer"
+        | _ ->
+          E.trap_with env "no timers when running locally"
+      )
+    ) in
+    E.add_export env (nr {
+      name = Wasm.Utf8.decode "timer_global_set";
+      edesc = nr (FuncExport (nr timer_global_set_fi))
     })
 
 end (* RTS_Exports *)
@@ -3714,11 +4492,14 @@ module Serialization = struct
     * We traverse the data to calculate the size needed for the data buffer and the
       reference buffer.
     * We allocate memory for the data buffer and the reference buffer
-      (this memory area is not referenced, so will be dead with the next GC)
+      (transient buffers, see Blob.dyn_alloc_scratch, so they don't grow the
+      heap when there is idle memory above it)
     * We copy the IDL type header to the data buffer.
     * We traverse the data and serialize it into the data buffer.
       This is type driven, and we use the `share_code` machinery and names that
       properly encode the type to resolve loops in a convenient way.
+      Unless the data is mutable, the traversals are done by the RTS instead,
+      driven by a static descriptor of the type (see value_desc).
     * We externalize all that new data space into a databuf
     * We externalize the reference space into a elembuf
     * We pass both databuf and elembuf to shared functions
@@ -3728,8 +4509,8 @@ module Serialization = struct
     * We allocate some scratch space, and internalize the databuf and elembuf into it.
     * We parse the data, in a type-driven way, using normal construction and
       allocation, while keeping tabs on the type description header for subtyping.
-    * At the end, the scratch space is a hole in the heap, and will be reclaimed
-      by the next GC.
+    * The scratch space is allocated in the arena of the RTS (see scratch.rs),
+      which is released at the end of the message.
   *)
 
   open Typ_hash
@@ -3921,347 +4702,86 @@ module Serialization = struct
     List.iter add_idx ts;
     Buffer.contents buf
 
-  (* Returns data (in bytes) and reference buffer size (in entries) needed *)
-  let rec buffer_size env t =
+  (* The descriptor of a type for the table-driven encoder of the RTS (see
+     rts/motoko-rts/src/idl_encode.rs): little-endian words, made of an opcode
+     and its operands per type, referring to other types by their word offset.
+  *)
+  let value_desc env t : string =
     let open Type in
-    let t = Type.normalize t in
-    let name = "@buffer_size<" ^ typ_hash t ^ ">" in
-    Func.share_code1 env name ("x", I32Type) [I32Type; I32Type]
-    (fun env get_x ->
-
-      (* Some combinators for writing values *)
-      let (set_data_size, get_data_size) = new_local env "data_size" in
-      let (set_ref_size, get_ref_size) = new_local env "ref_size" in
-      compile_unboxed_const 0l ^^ set_data_size ^^
-      compile_unboxed_const 0l ^^ set_ref_size ^^
-
-      let inc_data_size code =
-        get_data_size ^^ code ^^
-        G.i (Binary (Wasm.Values.I32 I32Op.Add)) ^^
-        set_data_size
-      in
 
-      let size_word env code =
-        let (set_word, get_word) = new_local env "word" in
-        code ^^ set_word ^^
-        inc_data_size (I32Leb.compile_leb128_size get_word)
-      in
-
-      let size env t =
-        buffer_size env t ^^
-        get_ref_size ^^ G.i (Binary (Wasm.Values.I32 I32Op.Add)) ^^ set_ref_size ^^
-        get_data_size ^^ G.i (Binary (Wasm.Values.I32 I32Op.Add)) ^^ set_data_size
-      in
+    let entry_size = function
+      | Opt _ | Array _ | Mut _ -> 2
+      | Tup (_ :: _ as ts) -> 2 + List.length ts
+      | Obj ((Object | Memory), fs) | Variant fs -> 2 + 2 * List.length fs
+      | _ -> 1 in
 
-      let size_alias size_thing =
-        (* see Note [mutable stable values] *)
-        let (set_tag, get_tag) = new_local env "tag" in
-        get_x ^^ Tagged.load ^^ set_tag ^^
-        (* Sanity check *)
-        get_tag ^^ compile_eq_const Tagged.(int_of_tag StableSeen) ^^
-        get_tag ^^ compile_eq_const Tagged.(int_of_tag MutBox) ^^
-        G.i (Binary (Wasm.Values.I32 I32Op.Or)) ^^
-        get_tag ^^ compile_eq_const Tagged.(int_of_tag ObjInd) ^^
-        G.i (Binary (Wasm.Values.I32 I32Op.Or)) ^^
-        get_tag ^^ compile_eq_const Tagged.(int_of_tag Array) ^^
-        G.i (Binary (Wasm.Values.I32 I32Op.Or)) ^^
-        E.else_trap_with env "object_size/Mut: Unexpected tag " ^^
-        (* Check if we have seen this before *)
-        get_tag ^^ compile_eq_const Tagged.(int_of_tag StableSeen) ^^
-        G.if_ [] begin
-          (* Seen before *)
-          (* One byte marker, one word offset *)
-          inc_data_size (compile_unboxed_const 5l)
-        end begin
-          (* Not yet seen *)
-          (* One byte marker, two words scratch space *)
-          inc_data_size (compile_unboxed_const 9l) ^^
-          (* Mark it as seen *)
-          get_x ^^ Tagged.(store StableSeen) ^^
-          (* and descend *)
-          size_thing ()
+    (* We do a first traversal to find out the offsets of all types *)
+    let (typs, idx) =
+      let typs = ref [] in
+      let idx = ref TM.empty in
+      let size = ref 0 in
+      let rec go t =
+        let t = Type.normalize t in
+        if TM.mem t !idx then () else begin
+          idx := TM.add t (Int32.of_int !size) !idx;
+          size := !size + entry_size t;
+          typs := !typs @ [ t ];
+          match t with
+          | Tup ts -> List.iter go ts
+          | Obj ((Object | Memory), fs) | Variant fs ->
+            List.iter (fun f -> go f.typ) fs
+          | Array (Mut t) | Array t | Opt t | Mut t -> go t
+          | _ -> ()
         end
       in
+      go t;
+      (!typs, !idx)
+    in
 
-      (* Now the actual type-dependent code *)
-      begin match t with
-      | Prim Nat -> inc_data_size (get_x ^^ BigNum.compile_data_size_unsigned env)
-      | Prim Int -> inc_data_size (get_x ^^ BigNum.compile_data_size_signed env)
-      | Prim (Int8|Nat8) -> inc_data_size (compile_unboxed_const 1l)
-      | Prim (Int16|Nat16) -> inc_data_size (compile_unboxed_const 2l)
-      | Prim (Int32|Nat32|Char) -> inc_data_size (compile_unboxed_const 4l)
-      | Prim (Int64|Nat64|Float) -> inc_data_size (compile_unboxed_const 8l)
-      | Prim Bool -> inc_data_size (compile_unboxed_const 1l)
-      | Prim Null -> G.nop
-      | Any -> G.nop
-      | Tup [] -> G.nop (* e(()) = null *)
-      | Tup ts ->
-        G.concat_mapi (fun i t ->
-          get_x ^^ Tuple.load_n (Int32.of_int i) ^^
-          size env t
-          ) ts
-      | Obj ((Object | Memory), fs) ->
-        G.concat_map (fun (_h, f) ->
-          get_x ^^ Object.load_idx_raw env f.Type.lab ^^
-          size env f.typ
-          ) (sort_by_hash fs)
-      | Array (Mut t) ->
-        size_alias (fun () -> get_x ^^ size env (Array t))
-      | Array t ->
-        size_word env (get_x ^^ Heap.load_field Arr.len_field) ^^
-        get_x ^^ Heap.load_field Arr.len_field ^^
-        from_0_to_n env (fun get_i ->
-          get_x ^^ get_i ^^ Arr.idx env ^^ load_ptr ^^
-          size env t
-        )
-      | Prim Blob ->
-        let (set_len, get_len) = new_local env "len" in
-        get_x ^^ Blob.len env ^^ set_len ^^
-        size_word env get_len ^^
-        inc_data_size get_len
-      | Prim Text ->
-        let (set_len, get_len) = new_local env "len" in
-        get_x ^^ Text.size env ^^ set_len ^^
-        size_word env get_len ^^
-        inc_data_size get_len
-      | Opt t ->
-        inc_data_size (compile_unboxed_const 1l) ^^ (* one byte tag *)
-        get_x ^^ Opt.is_some env ^^
-        G.if_ [] (get_x ^^ Opt.project env ^^ size env t) G.nop
-      | Variant vs ->
-        List.fold_right (fun (i, {lab = l; typ = t; _}) continue ->
-            get_x ^^
-            Variant.test_is env l ^^
-            G.if_ []
-              ( size_word env (compile_unboxed_const (Int32.of_int i)) ^^
-                get_x ^^ Variant.project ^^ size env t
-              ) continue
-          )
-          ( List.mapi (fun i (_h, f) -> (i,f)) (sort_by_hash vs) )
-          ( E.trap_with env "buffer_size: unexpected variant" )
-      | Func _ ->
-        inc_data_size (compile_unboxed_const 1l) ^^ (* one byte tag *)
-        get_x ^^ Arr.load_field 0l ^^ size env (Obj (Actor, [])) ^^
-        get_x ^^ Arr.load_field 1l ^^ size env (Prim Text)
-      | Obj (Actor, _) | Prim Principal ->
-        inc_data_size (compile_unboxed_const 1l) ^^ (* one byte tag *)
-        get_x ^^ size env (Prim Blob)
-      | Non ->
-        E.trap_with env "buffer_size called on value of type None"
-      | Mut t ->
-        size_alias (fun () -> get_x ^^ Heap.load_field MutBox.field ^^ size env t)
-      | _ -> todo "buffer_size" (Arrange_ir.typ t) G.nop
-      end ^^
-      get_data_size ^^
-      get_ref_size
-    )
-
-  (* Copies x to the data_buffer, storing references after ref_count entries in ref_base *)
-  let rec serialize_go env t =
-    let open Type in
-    let t = Type.normalize t in
-    let name = "@serialize_go<" ^ typ_hash t ^ ">" in
-    Func.share_code3 env name (("x", I32Type), ("data_buffer", I32Type), ("ref_buffer", I32Type)) [I32Type; I32Type]
-    (fun env get_x get_data_buf get_ref_buf ->
-      let set_data_buf = G.i (LocalSet (nr 1l)) in
-      let set_ref_buf = G.i (LocalSet (nr 2l)) in
-
-      (* Some combinators for writing values *)
-
-      let advance_data_buf =
-        get_data_buf ^^ G.i (Binary (Wasm.Values.I32 I32Op.Add)) ^^ set_data_buf in
-
-      let write_word code =
-        let (set_word, get_word) = new_local env "word" in
-        code ^^ set_word ^^
-        I32Leb.compile_store_to_data_buf_unsigned env get_word get_data_buf ^^
-        advance_data_buf
-      in
-
-      let write_word32 code =
-        get_data_buf ^^ code ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = None}) ^^
-        compile_unboxed_const Heap.word_size ^^ advance_data_buf
-      in
-
-      let write_byte code =
-        get_data_buf ^^ code ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = Some Wasm.Types.Pack8}) ^^
-        compile_unboxed_const 1l ^^ advance_data_buf
-      in
-
-      let write env t =
-        get_data_buf ^^
-        get_ref_buf ^^
-        serialize_go env t ^^
-        set_ref_buf ^^
-        set_data_buf
-      in
-
-      let write_alias write_thing =
-        (* see Note [mutable stable values] *)
-        (* Check heap tag *)
-        let (set_tag, get_tag) = new_local env "tag" in
-        get_x ^^ Tagged.load ^^ set_tag ^^
-        get_tag ^^ compile_eq_const Tagged.(int_of_tag StableSeen) ^^
-        G.if_ []
-        begin
-          (* This is the real data *)
-          write_byte (compile_unboxed_const 0l) ^^
-          (* Remember the current offset in the tag word *)
-          get_x ^^ get_data_buf ^^ Heap.store_field Tagged.tag_field ^^
-          (* Leave space in the output buffer for the decoder's bookkeeping *)
-          write_word32 (compile_unboxed_const 0l) ^^
-          write_word32 (compile_unboxed_const 0l) ^^
-          (* Now the data, following the object field mutbox indirection *)
-          write_thing ()
-        end
-        begin
-          (* This is a reference *)
-          write_byte (compile_unboxed_const 1l) ^^
-          (* Sanity Checks *)
-          get_tag ^^ compile_eq_const Tagged.(int_of_tag MutBox) ^^
-          E.then_trap_with env "unvisited mutable data in serialize_go (MutBox)" ^^
-          get_tag ^^ compile_eq_const Tagged.(int_of_tag ObjInd) ^^
-          E.then_trap_with env "unvisited mutable data in serialize_go (ObjInd)" ^^
-          get_tag ^^ compile_eq_const Tagged.(int_of_tag Array) ^^
-          E.then_trap_with env "unvisited mutable data in serialize_go (Array)" ^^
-          (* Second time we see this *)
-          (* Calculate relative offset *)
-          let (set_offset, get_offset) = new_local env "offset" in
-          get_tag ^^ get_data_buf ^^ G.i (Binary (Wasm.Values.I32 I32Op.Sub)) ^^
-          set_offset ^^
-          (* A sanity check *)
-          get_offset ^^ compile_unboxed_const 0l ^^
-          G.i (Compare (Wasm.Values.I32 I32Op.LtS)) ^^
-          E.else_trap_with env "Odd offset" ^^
-          (* Write the office to the output buffer *)
-          write_word32 get_offset
-        end
-      in
+    let buf = Buffer.create 16 in
+    let add_word i = Buffer.add_int32_le buf i in
+    let add_idx t = add_word (TM.find (Type.normalize t) idx) in
 
-      (* Now the actual serialization *)
+    let add_fields fs =
+      add_word (Lib.List32.length fs);
+      List.iter (fun (_h, f) ->
+        add_word (E.hash env f.lab);
+        add_idx f.typ
+      ) (sort_by_hash fs) in
 
-      begin match t with
-      | Prim Nat ->
-        get_data_buf ^^
-        get_x ^^
-        BigNum.compile_store_to_data_buf_unsigned env ^^
-        advance_data_buf
-      | Prim Int ->
-        get_data_buf ^^
-        get_x ^^
-        BigNum.compile_store_to_data_buf_signed env ^^
-        advance_data_buf
-      | Prim Float ->
-        get_data_buf ^^
-        get_x ^^ Float.unbox env ^^
-        G.i (Store {ty = F64Type; align = 0; offset = 0l; sz = None}) ^^
-        compile_unboxed_const 8l ^^ advance_data_buf
-      | Prim (Int64|Nat64) ->
-        get_data_buf ^^
-        get_x ^^ BoxedWord64.unbox env ^^
-        G.i (Store {ty = I64Type; align = 0; offset = 0l; sz = None}) ^^
-        compile_unboxed_const 8l ^^ advance_data_buf
-      | Prim (Int32|Nat32) ->
-        get_data_buf ^^
-        get_x ^^ BoxedSmallWord.unbox env ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = None}) ^^
-        compile_unboxed_const 4l ^^ advance_data_buf
-      | Prim Char ->
-        get_data_buf ^^
-        get_x ^^ TaggedSmallWord.untag_codepoint ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = None}) ^^
-        compile_unboxed_const 4l ^^ advance_data_buf
-      | Prim (Int16|Nat16) ->
-        get_data_buf ^^
-        get_x ^^ TaggedSmallWord.lsb_adjust Nat16 ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = Some Wasm.Types.Pack16}) ^^
-        compile_unboxed_const 2l ^^ advance_data_buf
-      | Prim (Int8|Nat8) ->
-        get_data_buf ^^
-        get_x ^^ TaggedSmallWord.lsb_adjust Nat8 ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = Some Wasm.Types.Pack8}) ^^
-        compile_unboxed_const 1l ^^ advance_data_buf
-      | Prim Bool ->
-        get_data_buf ^^
-        get_x ^^
-        BoxedSmallWord.unbox env (* essentially SR.adjust SR.Vanilla SR.bool *) ^^
-        G.i (Store {ty = I32Type; align = 0; offset = 0l; sz = Some Wasm.Types.Pack8}) ^^
-        compile_unboxed_const 1l ^^ advance_data_buf
-      | Tup [] -> (* e(()) = null *)
-        G.nop
+    let add_typ t =
+      match t with
+      | Prim Null | Any | Tup [] -> add_word 0l
+      | Prim Bool -> add_word 1l
+      | Prim Nat -> add_word 2l
+      | Prim Int -> add_word 3l
+      | Prim (Nat8|Int8) -> add_word 4l
+      | Prim (Nat16|Int16) -> add_word 5l
+      | Prim (Nat32|Int32) -> add_word 6l
+      | Prim Char -> add_word 7l
+      | Prim (Nat64|Int64) -> add_word 8l
+      | Prim Float -> add_word 9l
+      | Prim Text -> add_word 10l
+      | Prim Blob -> add_word 11l
+      | Prim Principal | Obj (Actor, _) -> add_word 12l
+      | Opt t -> add_word 13l; add_idx t
+      | Array (Mut t) -> add_word 21l; add_idx t
+      | Array t -> add_word 14l; add_idx t
       | Tup ts ->
-        G.concat_mapi (fun i t ->
-          get_x ^^ Tuple.load_n (Int32.of_int i) ^^
-          write env t
-        ) ts
-      | Obj ((Object | Memory), fs) ->
-        G.concat_map (fun (_h, f) ->
-          get_x ^^ Object.load_idx_raw env f.Type.lab ^^
-          write env f.typ
-        ) (sort_by_hash fs)
-      | Array (Mut t) ->
-        write_alias (fun () -> get_x ^^ write env (Array t))
-      | Array t ->
-        write_word (get_x ^^ Heap.load_field Arr.len_field) ^^
-        get_x ^^ Heap.load_field Arr.len_field ^^
-        from_0_to_n env (fun get_i ->
-          get_x ^^ get_i ^^ Arr.idx env ^^ load_ptr ^^
-          write env t
-        )
-      | Prim Null -> G.nop
-      | Any -> G.nop
-      | Opt t ->
-        get_x ^^
-        Opt.is_some env ^^
-        G.if_ []
-          ( write_byte (compile_unboxed_const 1l) ^^ get_x ^^ Opt.project env ^^ write env t )
-          ( write_byte (compile_unboxed_const 0l) )
-      | Variant vs ->
-        List.fold_right (fun (i, {lab = l; typ = t; _}) continue ->
-            get_x ^^
-            Variant.test_is env l ^^
-            G.if_ []
-              ( write_word (compile_unboxed_const (Int32.of_int i)) ^^
-                get_x ^^ Variant.project ^^ write env t)
-              continue
-          )
-          ( List.mapi (fun i (_h, f) -> (i,f)) (sort_by_hash vs) )
-          ( E.trap_with env "serialize_go: unexpected variant" )
-      | Prim Blob ->
-        let (set_len, get_len) = new_local env "len" in
-        get_x ^^ Blob.len env ^^ set_len ^^
-        write_word get_len ^^
-        get_data_buf ^^
-        get_x ^^ Blob.payload_ptr_unskewed ^^
-        get_len ^^
-        Heap.memcpy env ^^
-        get_len ^^ advance_data_buf
-      | Prim Text ->
-        let (set_len, get_len) = new_local env "len" in
-        get_x ^^ Text.size env ^^ set_len ^^
-        write_word get_len ^^
-        get_x ^^ get_data_buf ^^ Text.to_buf env ^^
-        get_len ^^ advance_data_buf
-      | Func _ ->
-        write_byte (compile_unboxed_const 1l) ^^
-        get_x ^^ Arr.load_field 0l ^^ write env (Obj (Actor, [])) ^^
-        get_x ^^ Arr.load_field 1l ^^ write env (Prim Text)
-      | Obj (Actor, _) | Prim Principal ->
-        write_byte (compile_unboxed_const 1l) ^^
-        get_x ^^ write env (Prim Blob)
-      | Non ->
-        E.trap_with env "serializing value of type None"
-      | Mut t ->
-        write_alias (fun () ->
-          get_x ^^ Heap.load_field MutBox.field ^^ write env t
-        )
-      | _ -> todo "serialize" (Arrange_ir.typ t) G.nop
-      end ^^
-      get_data_buf ^^
-      get_ref_buf
-    )
+        add_word 15l;
+        add_word (Lib.List32.length ts);
+        List.iter add_idx ts
+      | Obj ((Object | Memory), fs) -> add_word 16l; add_fields fs
+      | Variant vs -> add_word 17l; add_fields vs
+      | Func _ -> add_word 18l
+      | Non -> add_word 19l
+      | Mut t -> add_word 20l; add_idx t
+      | _ ->
+        Printf.eprintf "value_desc: unexpected type %s\n" (string_of_typ t);
+        assert false in
+
+    List.iter add_typ typs;
+    Buffer.contents buf
 
   (* This value is returned by deserialize_go if deserialization fails in a way
      that should be recovereable by opt parsing.
@@ -4286,6 +4806,11 @@ module Serialization = struct
      It returns the value of type t (vanilla representation) or coercion_error_value,
      It advances the data_buffer past the decoded value (even if it returns coercion_error_value!)
    *)
+  (* With --candid-stats, counts the decoding in the RTS, see Statistics in
+     rts/motoko-rts/src/idl.rs *)
+  let count_stats code =
+    if !Flags.candid_stats then code else G.nop
+
   let rec deserialize_go env t =
     let open Type in
     let t = Type.normalize t in
@@ -4301,6 +4826,8 @@ module Serialization = struct
       ) [I32Type]
     (fun env get_data_buf get_ref_buf get_typtbl get_idltyp get_typtbl_size get_depth get_can_recover ->
 
+      count_stats (E.call_import env "rts" "idl_stats_value") ^^
+
       (* Check recursion depth (protects against empty record etc.) *)
       (* Factor 2 because at each step, the expected type could go through one
          level of opt that is not present in the value type
@@ -4308,7 +4835,7 @@ module Serialization = struct
       get_depth ^^
       get_typtbl_size ^^ compile_add_const 1l ^^ compile_mul_const 2l ^^
       G.i (Compare (Wasm.Values.I32 I32Op.LeU)) ^^
-      E.else_trap_with env ("IDL error: circular record read") ^^
+      E.else_trap_with env (idl_error "circular record read") ^^
 
       (* Remember data buffer position, to detect progress *)
       let (set_old_pos, get_old_pos) = new_local env "old_pos" in
@@ -4343,6 +4870,12 @@ module Serialization = struct
         E.call_import env "rts" "skip_any"
       in
 
+      (* Checks the length of a vector against the decoding limits of the RTS *)
+      let check_vec get_arg_typ get_len =
+        get_data_buf ^^ get_typtbl ^^ get_arg_typ ^^ get_len ^^
+        E.call_import env "rts" "idl_check_vec"
+      in
+
       (* This flag is set to return a coercion error at the very end
          We cannot use (G.i Return) for early exit, or we’d leak stack space,
          as Stack.with_words is used to allocate scratch space.
@@ -4377,7 +4910,7 @@ module Serialization = struct
         check_prim_typ t ^^
         G.if_ [I32Type] f
           ( skip get_idltyp ^^
-            coercion_failed ("IDL error: unexpected IDL type when parsing " ^ string_of_typ t)
+            coercion_failed (idl_error ("unexpected IDL type when parsing " ^ string_of_typ t))
           )
       in
 
@@ -4392,7 +4925,7 @@ module Serialization = struct
           begin code0
           end begin
             get_b ^^ compile_eq_const 1l ^^
-            E.else_trap_with env "IDL error: byte tag not 0 or 1" ^^
+            E.else_trap_with env (idl_error "byte tag not 0 or 1") ^^
             code1
           end
         | _ -> assert false; (* can be generalized later as needed *)
@@ -4423,7 +4956,7 @@ module Serialization = struct
 
       let read_actor_data () =
         read_byte_tagged
-          [ E.trap_with env "IDL error: unexpected actor reference"
+          [ E.trap_with env (idl_error "unexpected actor reference")
           ; read_blob ()
           ]
       in
@@ -4482,13 +5015,13 @@ module Serialization = struct
             end
             begin
               skip get_arg_typ ^^
-              coercion_failed ("IDL error: unexpected IDL type when parsing " ^ string_of_typ t)
+              coercion_failed (idl_error ("unexpected IDL type when parsing " ^ string_of_typ t))
             end
           )
         end
         begin
           skip get_arg_typ ^^
-          coercion_failed ("IDL error: unexpected IDL type when parsing " ^ string_of_typ t)
+          coercion_failed (idl_error ("unexpected IDL type when parsing " ^ string_of_typ t))
         end
       in
 
@@ -4496,24 +5029,56 @@ module Serialization = struct
         with_composite_arg_typ get_idltyp idl_tycon_id f
       in
 
-      let with_record_typ f = with_composite_typ idl_record (fun get_typ_buf ->
-        Stack.with_words env "get_n_ptr" 1l (fun get_n_ptr ->
-          get_n_ptr ^^
-          ReadBuf.read_leb128 env get_typ_buf ^^
-          store_unskewed_ptr ^^
-          f get_typ_buf get_n_ptr
-        )
-      ) in
+      (* The field plan of the RTS for the IDL type and the expected fields,
+         see idl_field_plan *)
+      let field_plan hashes =
+        get_data_buf ^^ get_typtbl ^^ get_idltyp ^^
+        compile_unboxed_const (E.add_static env StaticBytes.[ i32s hashes ]) ^^
+        compile_unboxed_const (Lib.List32.length hashes) ^^
+        E.call_import env "rts" "idl_field_plan"
+      in
 
-      let with_blob_typ env f =
-        with_composite_typ idl_vec (fun get_typ_buf ->
+      (* Passes to f a function that reads the expected field i (of type t),
+         with these (sorted) hashes, or returns the code if_missing *)
+      let with_record_typ hashes f = with_composite_typ idl_record (fun _get_typ_buf ->
+        let (set_plan, get_plan) = new_local env "plan" in
+        field_plan hashes ^^ set_plan ^^
+        Stack.with_words env "get_pos_ptr" 1l (fun get_pos_ptr ->
+          get_pos_ptr ^^ compile_unboxed_const 0l ^^ store_unskewed_ptr ^^
+
+          let read_field i t if_missing =
+            let (set_arg_typ, get_arg_typ) = new_local env "arg_typ" in
+            let (set_val, get_val) = new_local env "val" in
+            (* skip all possible intermediate extra fields *)
+            get_plan ^^ get_data_buf ^^ get_typtbl ^^ get_pos_ptr ^^
+            compile_unboxed_const (Int32.of_int i) ^^
+            E.call_import env "rts" "idl_plan_record_field" ^^ set_arg_typ ^^
+            get_arg_typ ^^ compile_eq_const Int32.max_int (* IDL_FIELD_MISSING *) ^^
+            G.if_ [I32Type]
+              if_missing
+              begin
+                get_arg_typ ^^ go env t ^^ set_val ^^
+                remember_failure get_val ^^
+                get_val
+              end
+          in
+          f read_field ^^
+
+          (* skip all possible trailing extra fields *)
+          get_plan ^^ get_data_buf ^^ get_typtbl ^^ get_pos_ptr ^^
+          E.call_import env "rts" "idl_plan_skip_fields"
+        )
+      ) in
+
+      let with_blob_typ env f =
+        with_composite_typ idl_vec (fun get_typ_buf ->
           ReadBuf.read_sleb128 env get_typ_buf ^^
           compile_eq_const (-5l) (* Nat8 *) ^^
           G.if_ [I32Type]
             f
             begin
               skip get_idltyp ^^
-              coercion_failed "IDL error: blob not a vector of nat8"
+              coercion_failed (idl_error "blob not a vector of nat8")
             end
         )
       in
@@ -4661,7 +5226,7 @@ module Serialization = struct
         with_prim_typ t
         begin
           read_byte_tagged
-            [ E.trap_with env "IDL error: unexpected principal reference"
+            [ E.trap_with env (idl_error "unexpected principal reference")
             ; read_blob ()
             ]
         end
@@ -4671,60 +5236,30 @@ module Serialization = struct
         with_prim_typ t (Tuple.from_stack env 0)
       (* Composite types *)
       | Tup ts ->
-        with_record_typ (fun get_typ_buf get_n_ptr ->
-          let (set_val, get_val) = new_local env "val" in
-
+        with_record_typ (List.mapi (fun i _ -> Int32.of_int i) ts) (fun read_field ->
           G.concat_mapi (fun i t ->
-            (* skip all possible intermediate extra fields *)
-            get_typ_buf ^^ get_data_buf ^^ get_typtbl ^^ compile_unboxed_const (Int32.of_int i) ^^ get_n_ptr ^^
-            E.call_import env "rts" "find_field" ^^
-            G.if_ [I32Type]
-              begin
-                ReadBuf.read_sleb128 env get_typ_buf ^^
-                go env t ^^ set_val ^^
-                remember_failure get_val ^^
-                get_val
-              end
+            read_field i t
               begin
                 match normalize t with
                 | Opt _ | Any -> Opt.null_lit env
-                | _ -> coercion_failed "IDL error: did not find tuple field in record"
+                | _ -> coercion_failed (idl_error "did not find tuple field in record")
               end
           ) ts ^^
-
-          (* skip all possible trailing extra fields *)
-          get_typ_buf ^^ get_data_buf ^^ get_typtbl ^^ get_n_ptr ^^
-          E.call_import env "rts" "skip_fields" ^^
-
           Tuple.from_stack env (List.length ts)
         )
       | Obj ((Object | Memory), fs) ->
-        with_record_typ (fun get_typ_buf get_n_ptr ->
-          let (set_val, get_val) = new_local env "val" in
-
-          Object.lit_raw env (List.map (fun (h,f) ->
+        let fs = sort_by_hash fs in
+        with_record_typ (List.map (fun (h, _) -> Lib.Uint32.to_int32 h) fs) (fun read_field ->
+          Object.lit_raw env (List.mapi (fun i (_h, f) ->
             f.Type.lab, fun () ->
-              (* skip all possible intermediate extra fields *)
-              get_typ_buf ^^ get_data_buf ^^ get_typtbl ^^ compile_unboxed_const (Lib.Uint32.to_int32 h) ^^ get_n_ptr ^^
-              E.call_import env "rts" "find_field" ^^
-              G.if_ [I32Type]
-                begin
-                  ReadBuf.read_sleb128 env get_typ_buf ^^
-                  go env f.typ ^^ set_val ^^
-                  remember_failure get_val ^^
-                  get_val
-                  end
+              read_field i f.typ
                 begin
                   match normalize f.typ with
                   | Opt _ | Any -> Opt.null_lit env
-                  | _ -> coercion_failed (Printf.sprintf "IDL error: did not find field %s in record" f.lab)
+                  | _ -> coercion_failed (idl_error (Printf.sprintf "did not find field %s in record" f.lab))
                 end
-          ) (sort_by_hash fs)) ^^
-
-          (* skip all possible trailing extra fields *)
-          get_typ_buf ^^ get_data_buf ^^ get_typtbl ^^ get_n_ptr ^^
-          E.call_import env "rts" "skip_fields"
-          )
+          ) fs)
+        )
       | Array (Mut t) ->
         read_alias env (Array (Mut t)) (fun get_array_typ on_alloc ->
           let (set_len, get_len) = new_local env "len" in
@@ -4733,6 +5268,7 @@ module Serialization = struct
           let (set_arg_typ, get_arg_typ) = new_local env "arg_typ" in
           with_composite_arg_typ get_array_typ idl_vec (ReadBuf.read_sleb128 env) ^^ set_arg_typ ^^
           ReadBuf.read_leb128 env get_data_buf ^^ set_len ^^
+          check_vec get_arg_typ get_len ^^
           get_len ^^ Arr.alloc env ^^ set_x ^^
           on_alloc get_x ^^
           get_len ^^ from_0_to_n env (fun get_i ->
@@ -4747,16 +5283,27 @@ module Serialization = struct
         let (set_x, get_x) = new_local env "x" in
         let (set_val, get_val) = new_local env "val" in
         let (set_arg_typ, get_arg_typ) = new_local env "arg_typ" in
+        let read_elems =
+          get_len ^^ Arr.alloc env ^^ set_x ^^
+          get_len ^^ from_0_to_n env (fun get_i ->
+            get_x ^^ get_i ^^ Arr.idx env ^^
+            get_arg_typ ^^ go env t ^^ set_val ^^
+            remember_failure get_val ^^
+            get_val ^^ store_ptr
+          ) ^^
+          get_x in
         with_composite_typ idl_vec (ReadBuf.read_sleb128 env) ^^ set_arg_typ ^^
         ReadBuf.read_leb128 env get_data_buf ^^ set_len ^^
-        get_len ^^ Arr.alloc env ^^ set_x ^^
-        get_len ^^ from_0_to_n env (fun get_i ->
-          get_x ^^ get_i ^^ Arr.idx env ^^
-          get_arg_typ ^^ go env t ^^ set_val ^^
-          remember_failure get_val ^^
-          get_val ^^ store_ptr
-        ) ^^
-        get_x
+        check_vec get_arg_typ get_len ^^
+        begin match normalize t with
+        | Prim Nat8 ->
+          (* Fast path for `vec nat8`, decoding the bytes in one go *)
+          get_arg_typ ^^ compile_eq_const (-5l) (* Nat8 *) ^^
+          G.if_ [I32Type]
+            (get_data_buf ^^ get_len ^^ E.call_import env "rts" "idl_read_nat8_array")
+            read_elems
+        | _ -> read_elems
+        end
       | Opt t ->
         check_prim_typ (Prim Null) ^^
         G.if_ [I32Type] (Opt.null_lit env)
@@ -4803,32 +5350,21 @@ module Serialization = struct
         end
       | Variant vs ->
         let (set_val, get_val) = new_local env "val" in
-        with_composite_typ idl_variant (fun get_typ_buf ->
-          (* Find the tag *)
-          let (set_n, get_n) = new_local env "len" in
-          ReadBuf.read_leb128 env get_typ_buf ^^ set_n ^^
+        let vs = sort_by_hash vs in
+        with_composite_typ idl_variant (fun _get_typ_buf ->
+          let (set_plan, get_plan) = new_local env "plan" in
+          field_plan (List.map (fun (h, _) -> Lib.Uint32.to_int32 h) vs) ^^ set_plan ^^
 
+          (* Find the case, the RTS checks the index *)
           let (set_tagidx, get_tagidx) = new_local env "tagidx" in
           ReadBuf.read_leb128 env get_data_buf ^^ set_tagidx ^^
-
-          get_tagidx ^^ get_n ^^
-          G.i (Compare (Wasm.Values.I32 I32Op.LtU)) ^^
-          E.else_trap_with env "IDL error: variant index out of bounds" ^^
-
-          (* Zoom past the previous entries *)
-          get_tagidx ^^ from_0_to_n env (fun _ ->
-            get_typ_buf ^^ E.call_import env "rts" "skip_leb128" ^^
-            get_typ_buf ^^ E.call_import env "rts" "skip_leb128"
-          ) ^^
-
-          (* Now read the tag *)
-          let (set_tag, get_tag) = new_local env "tag" in
-          ReadBuf.read_leb128 env get_typ_buf ^^ set_tag ^^
+          let (set_case, get_case) = new_local env "case" in
+          get_plan ^^ get_tagidx ^^ E.call_import env "rts" "idl_plan_variant_case" ^^ set_case ^^
           let (set_arg_typ, get_arg_typ) = new_local env "arg_typ" in
-          ReadBuf.read_sleb128 env get_typ_buf ^^ set_arg_typ ^^
+          get_plan ^^ get_tagidx ^^ E.call_import env "rts" "idl_plan_case_type" ^^ set_arg_typ ^^
 
-          List.fold_right (fun (h, {lab = l; typ = t; _}) continue ->
-              get_tag ^^ compile_eq_const (Lib.Uint32.to_int32 h) ^^
+          List.fold_right (fun (i, (_h, {lab = l; typ = t; _})) continue ->
+              get_case ^^ compile_eq_const (Int32.of_int i) ^^
               G.if_ [I32Type]
                 ( Variant.inject env l (
                   get_arg_typ ^^ go env t ^^ set_val ^^
@@ -4837,13 +5373,13 @@ module Serialization = struct
                 ))
                 continue
             )
-            ( sort_by_hash vs )
-            ( coercion_failed "IDL error: unexpected variant tag" )
+            ( List.mapi (fun i f -> (i, f)) vs )
+            ( coercion_failed (idl_error "unexpected variant tag") )
         )
       | Func _ ->
         with_composite_typ idl_func (fun _get_typ_buf ->
           read_byte_tagged
-            [ E.trap_with env "IDL error: unexpected function reference"
+            [ E.trap_with env (idl_error "unexpected function reference")
             ; read_actor_data () ^^
               read_text () ^^
               Tuple.from_stack env 2
@@ -4861,37 +5397,38 @@ module Serialization = struct
           Heap.store_field MutBox.field
         )
       | Non ->
-        E.trap_with env "IDL error: deserializing value of type None"
+        E.trap_with env (idl_error "deserializing value of type None")
       | _ -> todo_trap env "deserialize" (Arrange_ir.typ t)
       end ^^
       (* Parsed value on the stack, return that, unless the failure flag is set *)
       when_failed (compile_unboxed_const (coercion_error_value env) ^^ G.i Return)
     )
 
+  (* The value descriptor of the type, for the RTS, as a pointer and its length in words *)
+  let value_desc_ptr env t =
+    let valdesc = value_desc env t in
+    Blob.lit env valdesc ^^ Blob.payload_ptr_unskewed ^^
+    compile_unboxed_const (Int32.of_int (String.length valdesc / 4))
+
   let serialize env ts : G.t =
     let ts_name = typ_seq_hash ts in
     let name = "@serialize<" ^ ts_name ^ ">" in
     (* returns data/length pointers (will be GC’ed next time!) *)
     Func.share_code1 env name ("x", I32Type) [I32Type; I32Type] (fun env get_x ->
       let (set_data_size, get_data_size) = new_local env "data_size" in
-      let (set_refs_size, get_refs_size) = new_local env "refs_size" in
+      let (set_data_start, get_data_start) = new_local env "data_start" in
 
       let tydesc = type_desc env ts in
       let tydesc_len = Int32.of_int (String.length tydesc) in
+      let get_valdesc = value_desc_ptr env (Type.seq ts) in
 
-      (* Get object sizes *)
-      get_x ^^
-      buffer_size env (Type.seq ts) ^^
-      set_refs_size ^^
-
-      compile_add_const tydesc_len  ^^
+      PerfCounters.section env PerfCounters.CandidEncode (
+      (* Get data size *)
+      get_valdesc ^^ get_x ^^ E.call_import env "rts" "idl_serialize_size" ^^
+      compile_add_const tydesc_len ^^
       set_data_size ^^
 
-      let (set_data_start, get_data_start) = new_local env "data_start" in
-      let (set_refs_start, get_refs_start) = new_local env "refs_start" in
-
       get_data_size ^^ Blob.dyn_alloc_scratch env ^^ set_data_start ^^
-      get_refs_size ^^ compile_mul_const Heap.word_size ^^ Blob.dyn_alloc_scratch env ^^ set_refs_start ^^
 
       (* Write ty desc *)
       get_data_start ^^
@@ -4899,25 +5436,12 @@ module Serialization = struct
       compile_unboxed_const tydesc_len ^^
       Heap.memcpy env ^^
 
-      (* Serialize x into the buffer *)
-      get_x ^^
+      (* Serialize x into the buffer, the RTS checks that it is filled *)
+      get_valdesc ^^ get_x ^^
       get_data_start ^^ compile_add_const tydesc_len ^^
-      get_refs_start ^^
-      serialize_go env (Type.seq ts) ^^
-
-      (* Sanity check: Did we fill exactly the buffer *)
-      get_refs_start ^^ get_refs_size ^^ compile_mul_const Heap.word_size ^^ G.i (Binary (Wasm.Values.I32 I32Op.Add)) ^^
-      G.i (Compare (Wasm.Values.I32 I32Op.Eq)) ^^
-      E.else_trap_with env "reference buffer not filled " ^^
-
-      get_data_start ^^ get_data_size ^^ G.i (Binary (Wasm.Values.I32 I32Op.Add)) ^^
-      G.i (Compare (Wasm.Values.I32 I32Op.Eq)) ^^
-      E.else_trap_with env "data buffer not filled " ^^
-
-      get_refs_size ^^
-      compile_unboxed_const 0l ^^
-      G.i (Compare (Wasm.Values.I32 I32Op.Eq)) ^^
-      E.else_trap_with env "cannot send references on IC System API" ^^
+      get_data_size ^^ compile_sub_const tydesc_len ^^
+      E.call_import env "rts" "idl_serialize"
+      ) ^^
 
       get_data_start ^^
       get_data_size
@@ -4936,9 +5460,14 @@ module Serialization = struct
       let (set_refs_start, get_refs_start) = new_local env "refs_start" in
       let (set_arg_count, get_arg_count) = new_local env "arg_count" in
       let (set_val, get_val) = new_local env "val" in
+      (* The decoding of the stable variables counts as stabilization *)
+      let subsystem =
+        if extended then PerfCounters.Stabilization else PerfCounters.CandidDecode in
 
+      PerfCounters.section env subsystem (
       get_blob ^^ Blob.len env ^^ set_data_size ^^
       get_blob ^^ Blob.payload_ptr_unskewed ^^ set_data_start ^^
+      count_stats (E.call_import env "rts" "idl_stats_begin") ^^
 
       (* Allocate space for the reference buffer and copy it *)
       compile_unboxed_const 0l ^^ set_refs_size (* none yet *) ^^
@@ -4968,7 +5497,7 @@ module Serialization = struct
 
         get_arg_count ^^
         compile_rel_const I32Op.GeU (Int32.of_int (List.length ts)) ^^
-        E.else_trap_with env ("IDL error: too few arguments " ^ ts_name) ^^
+        E.else_trap_with env (idl_error ("too few arguments " ^ ts_name)) ^^
 
         G.concat_map (fun t ->
           get_data_buf ^^ get_ref_buf ^^
@@ -4979,7 +5508,7 @@ module Serialization = struct
           compile_unboxed_const 0l ^^ (* initially, cannot recover *)
           deserialize_go env t ^^ set_val ^^
           get_val ^^ compile_eq_const (coercion_error_value env) ^^
-          E.then_trap_with env ("IDL error: coercion failure encountered") ^^
+          E.then_trap_with env (idl_error "coercion failure encountered") ^^
           get_val
         ) ts ^^
 
@@ -4994,10 +5523,12 @@ module Serialization = struct
         ) ^^
 
         ReadBuf.is_empty env get_data_buf ^^
-        E.else_trap_with env ("IDL error: left-over bytes " ^ ts_name) ^^
+        E.else_trap_with env (idl_error ("left-over bytes " ^ ts_name)) ^^
         ReadBuf.is_empty env get_ref_buf ^^
-        E.else_trap_with env ("IDL error: left-over references " ^ ts_name)
-      ))))))
+        E.else_trap_with env (idl_error ("left-over references " ^ ts_name)) ^^
+
+        count_stats (get_data_size ^^ E.call_import env "rts" "idl_stats_end")
+      )))))))
     )
 
   let deserialize env ts =
@@ -5050,16 +5581,18 @@ The type-driven code in this module treats `Type.Mut` to always refer to an
 
 To detect and preserve aliasing, these steps are taken:
 
- * In `buffer_size`, when we see a mutable thing (`Array` or `ObjInd`), the
-   first time, we mark it by setting the heap tag to `StableSeen`.
+ * Stable values are serialized by the RTS (see idl_encode.rs), where the
+   value descriptor has entries for mutable fields and mutable arrays.
+ * When computing the size, when we see a mutable thing (`Array` or `ObjInd`),
+   the first time, we mark it by setting the heap tag to 0xffffffff (“seen”).
    This way, when we see it a second time, we can skip the value in the size
    calculation.
- * In `serialize`, when we see it a first time (tag still `StableSeen`),
-   we serialize it (first form above), and remember the absolute position
-   in the output buffer, abusing the heap tag here.
-   (Invariant: This absolute position is never `StableSeen`)
-   Upon a second visit (tag not `StableSeen`), we can thus fetch that absolute
-   position and calculate the offset.
+ * When serializing, when we see it a first time (tag still “seen”),
+   we serialize it (first form above), and remember the position in the
+   output, abusing the heap tag here.
+   (Invariant: The tag holding the position is never “seen”, nor another tag)
+   Upon a second visit (tag not “seen”), we can thus fetch that position
+   and calculate the offset.
  * In `deserialize`, when we come across a `alias t`, we follow the offset (if
    needed) to find the content.
 
@@ -5082,75 +5615,157 @@ end (* Serialization *)
 
 module Stabilization = struct
 
-  let stabilize env t =
-    let (set_dst, get_dst) = new_local env "dst" in
-    let (set_len, get_len) = new_local env "len" in
-    Serialization.serialize env [t] ^^
-    set_len ^^
-    set_dst ^^
+  (* The stable variables are kept in a region of stable memory (see region.rs),
+     so that other regions can be allocated next to them *)
 
-    let (set_pages, get_pages) = new_local env "len" in
-    get_len ^^
-    compile_add_const 4l ^^  (* reserve one word for size *)
-    compile_divU_const page_size ^^
-    compile_add_const 1l ^^
-    set_pages ^^
-
-    (* grow stable memory if needed *)
-    let (set_pages_needed, get_pages_needed) = new_local env "pages_needed" in
-    get_pages ^^
-    E.call_import env "ic0" "stable_size" ^^
-    G.i (Binary (Wasm.Values.I32 I32Op.Sub)) ^^
-    set_pages_needed ^^
-
-    get_pages_needed ^^
-    compile_unboxed_zero ^^
-    G.i (Compare (Wasm.Values.I32 I32Op.GtS)) ^^
-    G.if_ []
-      ( get_pages_needed ^^
-        E.call_import env "ic0" "stable_grow" ^^
-        (* Check result *)
-        compile_unboxed_zero ^^
-        G.i (Compare (Wasm.Values.I32 I32Op.LtS)) ^^
-        E.then_trap_with env "Cannot grow stable memory."
-      ) G.nop
-    ^^
-
-    (* write len to initial word of stable memory*)
-    Stack.with_words env "get_size_ptr" 1l (fun get_size_ptr ->
-
-      get_size_ptr ^^ get_len ^^ store_unskewed_ptr ^^
+  (* With --graph-copy-stabilization the RTS copies the heap objects of the
+     stable variables to stable memory instead (see graph_copy.rs). It needs
+     the field hashes of the object types of the stable type, to tell whether
+     an object can be copied, and to find them in the static data of the new
+     version: the number of lists, then the length and the hashes of each list,
+     sorted as in Object.lit_raw *)
+  let hash_lists env t =
+    let open Type in
+    let lists = ref [] in
+    let seen = ref [] in
+    let rec go t =
+      let t = normalize t in
+      if not (List.mem t !seen) then begin
+        seen := t :: !seen;
+        match t with
+        | Obj ((Object | Memory), fs) ->
+          let hashes = List.sort compare (List.map (fun f -> E.hash env f.lab) fs) in
+          if not (List.mem hashes !lists) then lists := !lists @ [ hashes ];
+          List.iter (fun f -> go f.typ) fs
+        | Variant fs -> List.iter (fun f -> go f.typ) fs
+        | Tup ts -> List.iter go ts
+        | Array t | Opt t | Mut t -> go t
+        | _ -> ()
+      end
+    in
+    go t;
+    let buf = Buffer.create 16 in
+    let add_word i = Buffer.add_int32_le buf i in
+    add_word (Lib.List32.length !lists);
+    List.iter (fun hashes ->
+      add_word (Lib.List32.length hashes);
+      List.iter add_word hashes
+    ) !lists;
+    Buffer.contents buf
 
-      compile_unboxed_const 0l ^^
-      get_size_ptr ^^ compile_unboxed_const 4l ^^
-      IC.system_call env "ic0" "stable_write") ^^
+  let typ_hash_ptr env t =
+    let typ = Typ_hash.typ_hash t in
+    Blob.lit env typ ^^ Blob.payload_ptr_unskewed ^^
+    compile_unboxed_const (Int32.of_int (String.length typ))
 
-    (* copy data to following stable memory *)
-    compile_unboxed_const 4l ^^
-    get_dst ^^
-    get_len ^^
-    E.call_import env "ic0" "stable_write"
+  let hash_lists_ptr env t =
+    Blob.lit env (hash_lists env t) ^^ Blob.payload_ptr_unskewed
 
+  (* The encoding is streamed to stable memory by the RTS (see idl_encode.rs),
+     so it doesn't take space in memory as well *)
+  let stabilize env t =
+    let (set_x, get_x) = new_local env "x" in
+    let tydesc = Serialization.type_desc env [t] in
+    let get_tydesc =
+      Blob.lit env tydesc ^^ Blob.payload_ptr_unskewed ^^
+      compile_unboxed_const (Int32.of_int (String.length tydesc)) in
+    set_x ^^
+    PerfCounters.section env PerfCounters.Stabilization (
+    (if !Flags.compress_stabilization
+     then E.call_import env "rts" "stable_vars_compress"
+     else G.nop) ^^
+    (if !Flags.graph_copy_stabilization then
+      get_x ^^
+      Serialization.value_desc_ptr env t ^^
+      get_tydesc ^^
+      typ_hash_ptr env t ^^
+      hash_lists_ptr env t ^^
+      E.call_import env "rts" "graph_copy_stabilize"
+    else
+      Serialization.value_desc_ptr env t ^^
+      get_x ^^
+      get_tydesc ^^
+      E.call_import env "rts" "idl_serialize_stable"))
 
-  (* return the initial i32 in stable memory recording the size of the following stable data *)
+  (* return the size of the stable data, 0 when there is none *)
   let stable_data_size env =
-    match E.mode env with
-    | Flags.ICMode | Flags.RefMode ->
-      (* read size from initial word of (assumed non-empty) stable memory*)
-      Stack.with_words env "get_size_ptr" 1l (fun get_size_ptr ->
-        get_size_ptr ^^ compile_unboxed_const 0l ^^  compile_unboxed_const 4l ^^
-        IC.system_call env "ic0" "stable_read" ^^
-        get_size_ptr ^^ load_unskewed_ptr)
-    | _ -> assert false
+    E.call_import env "rts" "stable_vars_size"
 
-  let destabilize env t =
+  let destabilize_candid env t =
     Blob.of_size_copy env stable_data_size
-      (* copy the stable data from stable memory from offset 4 *)
-      (fun env -> IC.system_call env "ic0" "stable_read") 4l ^^
+      (fun env -> E.call_import env "rts" "stable_vars_read") 0l ^^
     Serialization.deserialize_from_blob true env [t]
 
+  (* The stable variables may be a graph copy, also without
+     --graph-copy-stabilization, when upgrading from a version with it.
+     When it cannot be restored as it is, the RTS replaces it with the Candid
+     encoding. Either is checked against its checksum first, when it has one. *)
+  let destabilize env t =
+    let (set_x, get_x) = new_local env "x" in
+    PerfCounters.section env PerfCounters.Stabilization (
+    E.call_import env "rts" "stable_vars_verify" ^^
+    typ_hash_ptr env t ^^
+    hash_lists_ptr env t ^^
+    Opt.null_lit env ^^
+    E.call_import env "rts" "graph_copy_restore" ^^
+    set_x ^^
+    get_x ^^
+    G.if_ [I32Type] get_x (destabilize_candid env t))
+
 end
 
+(* Regions of stable memory for the program (see rts/motoko-rts/src/region.rs),
+   identified by their number, and streams writing to them (see stream.rs).
+   Region 0 holds the stable variables, it's not accessible to the program. *)
+
+module Region = struct
+
+  (* Checks the region on the top of the stack, for the functions taking
+     another argument after it *)
+  let check env =
+    Func.share_code1 env "check_region" ("r", I32Type) [I32Type] (fun env get_r ->
+      get_r ^^
+      G.i (Test (Wasm.Values.I32 I32Op.Eqz)) ^^
+      E.then_trap_with env "region 0 is reserved for the stable variables" ^^
+      get_r
+    )
+
+  let new_ env = E.call_import env "rts" "region_new"
+
+  let size env = check env ^^ E.call_import env "rts" "region_size"
+
+  let grow env =
+    let (set_pages, get_pages) = new_local64 env "pages" in
+    set_pages ^^ check env ^^ get_pages ^^
+    E.call_import env "rts" "region_grow"
+
+  let load_blob env =
+    let (set_len, get_len) = new_local env "len" in
+    let (set_offset, get_offset) = new_local64 env "offset" in
+    set_len ^^ set_offset ^^ check env ^^ get_offset ^^ get_len ^^
+    E.call_import env "rts" "region_load_blob"
+
+  let stream env =
+    let (set_offset, get_offset) = new_local64 env "offset" in
+    set_offset ^^ check env ^^ get_offset ^^
+    E.call_import env "rts" "region_stream_new"
+
+  let stream_write env = E.call_import env "rts" "region_stream_write"
+
+  let stream_flush env = E.call_import env "rts" "region_stream_flush"
+
+  let buffer env =
+    let (set_len, get_len) = new_local64 env "len" in
+    let (set_offset, get_offset) = new_local64 env "offset" in
+    set_len ^^ set_offset ^^ check env ^^ get_offset ^^ get_len ^^
+    E.call_import env "rts" "region_buf_new"
+
+  let buffer_read env = E.call_import env "rts" "region_buf_read"
+
+  let buffer_remaining env = E.call_import env "rts" "region_buf_remaining"
+
+end (* Region *)
+
 module GC = struct
 
   let register env static_roots =
@@ -5169,6 +5784,117 @@ module GC = struct
 
 end (* GC *)
 
+module AllocProfile = struct
+  (* See rts/motoko-rts/src/alloc_profile.rs. With --alloc-profile every
+     (non-constant) expression is an allocation site. Its code sets the current
+     site in the RTS, and sets it back to the site of the enclosing expression
+     when done, so that objects allocated in helper functions and the RTS are
+     attributed to the innermost expression being evaluated. *)
+
+  let set_site env site =
+    compile_unboxed_const site ^^
+    E.call_import env "rts" "set_alloc_site"
+
+  let with_site env at (compile : unit -> 'a * G.t) : 'a * G.t =
+    if not !Flags.alloc_profile then compile () else begin
+      let outer = E.get_alloc_site env in
+      let site = E.add_alloc_site env at in
+      E.set_alloc_site env site;
+      let (sr, code) = compile () in
+      E.set_alloc_site env outer;
+      (sr, set_site env site ^^ code ^^ set_site env outer)
+    end
+
+  let enable env =
+    if !Flags.alloc_profile
+    then E.call_import env "rts" "alloc_profile_enable"
+    else G.nop
+
+  (* Returns an array of (name, objects, bytes) tuples. The site names are only
+     known when the whole program is compiled, see register *)
+  let get_profile env =
+    G.i (Call (nr (E.built_in env "alloc_site_names"))) ^^
+    E.call_import env "rts" "alloc_profile"
+
+  let register env =
+    Func.define_built_in env "alloc_site_names" [] [I32Type] (fun env ->
+      let names = List.map (fun at ->
+        Blob.vanilla_lit env (Source.string_of_region at)) (E.get_alloc_sites env) in
+      compile_unboxed_const (Arr.vanilla_lit env names)
+    )
+
+end (* AllocProfile *)
+
+module Features = struct
+  (* See rts/motoko-rts/src/features.rs. The RTS knows its own build, the
+     collector and the other compiler flags are passed to it on start up *)
+
+  (* See GcAlgorithm *)
+  let gc_algorithm () =
+    match !Flags.gc_strategy with
+    | Flags.Copying -> 1l
+    | Flags.Incremental -> 2l
+    | Flags.Generational -> 3l
+    | Flags.MarkSweep -> 4l
+
+  (* See the FEATURE_* bits of the compiler flags *)
+  let compiler_flags () =
+    let bit flag n = if !flag then Int32.shift_left 1l n else 0l in
+    List.fold_left Int32.logor 0l [
+      bit Flags.graph_copy_stabilization 8;
+      bit Flags.compress_stabilization 9;
+      bit Flags.global_timer 10;
+      bit Flags.trap_backtrace 11;
+      bit Flags.perf_counters 12;
+    ]
+
+  let init env =
+    compile_unboxed_const (gc_algorithm ()) ^^
+    compile_unboxed_const (compiler_flags ()) ^^
+    E.call_import env "rts" "set_compiler_features"
+
+  let flag_bit = function
+    | "heap_sanity" -> 0
+    | "redzones" -> 1
+    | "free_list" -> 2
+    | "graph_copy_stabilization" -> 8
+    | "compress_stabilization" -> 9
+    | "global_timer" -> 10
+    | "trap_backtrace" -> 11
+    | "perf_counters" -> 12
+    | _ -> assert false
+
+  (* Returns the features as a Motoko record, see RtsFeatures in prim.mo *)
+  let get env =
+    let (set_features, get_features) = new_local env "features" in
+    let load32 offset =
+      get_features ^^ G.i (Load {ty = I32Type; align = 2; offset; sz = None}) in
+    let text offset =
+      load32 offset ^^ load32 (Int32.add offset 4l) ^^ Text.of_ptr_size env in
+    let flag name =
+      name, (fun () ->
+        load32 28l ^^
+        compile_shrU_const (Int32.of_int (flag_bit name)) ^^
+        compile_bitand_const 1l ^^
+        BitTagged.tag_i32) in
+    E.call_import env "rts" "get_features" ^^ set_features ^^
+    Object.lit_raw env [
+      "version", (fun () -> text 0l);
+      "git_revision", (fun () -> text 8l);
+      "gc", (fun () -> text 16l);
+      "word_size", (fun () -> load32 24l ^^ BigNum.from_word32 env);
+      flag "heap_sanity";
+      flag "redzones";
+      flag "free_list";
+      flag "graph_copy_stabilization";
+      flag "compress_stabilization";
+      flag "global_timer";
+      flag "trap_backtrace";
+      flag "perf_counters";
+    ]
+
+end (* Features *)
+
 module StackRep = struct
   open SR
 
@@ -5472,13 +6198,13 @@ module Var = struct
       set_new_val ^^
       G.i (LocalGet (nr i)) ^^
       get_new_val ^^
-      Heap.store_field MutBox.field
+      Heap.store_field_with_barrier env MutBox.field
     | Some (HeapStatic ptr) ->
       let (set_new_val, get_new_val) = new_local env "new_val" in
       set_new_val ^^
       compile_unboxed_const ptr ^^
       get_new_val ^^
-      Heap.store_field MutBox.field
+      Heap.store_field_with_barrier env MutBox.field
     | Some (Const _) -> fatal "set_val: %s is const" var
     | Some (PublicMethod _) -> fatal "set_val: %s is PublicMethod" var
     | None   -> fatal "set_val: %s missing" var
@@ -5567,7 +6293,7 @@ module FuncDec = struct
   (* Create a WebAssembly func from a pattern (for the argument) and the body.
    Parameter `captured` should contain the, well, captured local variables that
    the function will find in the closure. *)
-  let compile_local_function outer_env outer_ae restore_env args mk_body ret_tys at =
+  let compile_local_function outer_env outer_ae restore_env name args mk_body ret_tys at =
     let arg_names = List.map (fun a -> a.it, I32Type) args in
     let return_arity = List.length ret_tys in
     let retty = Lib.List.make return_arity I32Type in
@@ -5580,28 +6306,82 @@ module FuncDec = struct
       (* Add arguments to the environment (shifted by 1) *)
       let ae2 = bind_args env ae1 1 args in
 
-      closure_codeW (mk_body env ae2)
+      Backtrace.push env name at ^^
+      closure_codeW (mk_body env ae2) ^^
+      Backtrace.pop env
     ))
 
-  let message_start env sort = match sort with
+  let ignoring_callback env =
+    let name = "@ignore_callback" in
+    Func.define_built_in env name ["env", I32Type] [] (fun env -> G.nop);
+    compile_unboxed_const (E.add_fun_ptr env (E.built_in env name))
+
+  (* Sends the closure table index on the stack to ourselves, the async helper
+     (see export_async_method) recalls and runs the closure in that message.
+     Leaves the error code of call_perform on the stack, 0 when the message
+     was sent. *)
+  let self_call_closure env =
+    let (set_idx, get_idx) = new_local env "closure_idx" in
+    let (set_meth_pair, get_meth_pair) = new_local env "meth_pair" in
+    set_idx ^^
+    IC.get_self_reference env ^^
+    IC.actor_public_field env IC.async_method_name ^^
+    set_meth_pair ^^
+    (* The callee *)
+    get_meth_pair ^^ Arr.load_field 0l ^^ Blob.as_ptr_len env ^^
+    (* The method name *)
+    get_meth_pair ^^ Arr.load_field 1l ^^ Blob.as_ptr_len env ^^
+    (* The reply and reject callback *)
+    ignoring_callback env ^^
+    compile_unboxed_zero ^^
+    ignoring_callback env ^^
+    compile_unboxed_zero ^^
+    IC.system_call env "ic0" "call_new" ^^
+    (* the data *)
+    get_idx ^^ BoxedSmallWord.box env ^^
+    Serialization.serialize env Type.[Prim Nat32] ^^
+    IC.system_call env "ic0" "call_data_append" ^^
+    IC.system_call env "ic0" "call_perform"
+
+  (* Runs each queued finalizer in its own message (see finalizers.rs). When a
+     message can't be sent, the remaining finalizers stay queued until the end
+     of the next update message. Not in init and post_upgrade, where we can't
+     send messages. *)
+  let dispatch_finalizers env =
+    match E.mode env with
+    | Flags.ICMode | Flags.RefMode ->
+      compile_while
+        (Finalizer.pending env ^^
+         G.if_ [I32Type]
+           (Finalizer.next env ^^
+            self_call_closure env ^^
+            G.i (Test (Wasm.Values.I32 I32Op.Eqz)))
+           (Bool.lit false))
+        (Finalizer.dequeue env)
+    | _ -> G.nop
+
+  let message_start env sort = (match sort with
       | Type.Shared Type.Write ->
         Lifecycle.trans env Lifecycle.InUpdate
       | Type.Shared Type.Query ->
         Lifecycle.trans env Lifecycle.InQuery
-      | _ -> assert false
+      | _ -> assert false) ^^
+    Heap.message_alloc_reset env
 
   let message_cleanup env sort = match sort with
       | Type.Shared Type.Write ->
-        E.call_import env "rts" "collect" ^^
+        Heap.collect_garbage env ^^
+        dispatch_finalizers env ^^
         Lifecycle.trans env Lifecycle.Idle
       | Type.Shared Type.Query ->
         Lifecycle.trans env Lifecycle.PostQuery
       | _ -> assert false
 
-  let compile_const_message outer_env outer_ae sort control args mk_body ret_tys at : E.func_with_names =
+  let compile_const_message outer_env outer_ae sort control name args mk_body ret_tys at : E.func_with_names =
     let ae0 = VarEnv.mk_fun_ae outer_ae in
     Func.of_body outer_env [] [] (fun env -> G.with_region at (
       message_start env sort ^^
+      Backtrace.push env name at ^^
       (* cycles *)
       Internals.reset_cycles env outer_ae ^^
       Internals.reset_refund env outer_ae ^^
@@ -5619,6 +6399,7 @@ module FuncDec = struct
       Serialization.deserialize env arg_tys ^^
       G.concat_map (Var.set_val env ae1) (List.rev arg_names) ^^
       mk_body env ae1 ^^
+      Backtrace.pop env ^^
       message_cleanup env sort
     ))
 
@@ -5628,14 +6409,14 @@ module FuncDec = struct
     then begin
       let (fi, fill) = E.reserve_fun pre_env name in
       ( Const.t_of_v (Const.Message fi), fun env ae ->
-        fill (compile_const_message env ae sort control args mk_body ret_tys at)
+        fill (compile_const_message env ae sort control name args mk_body ret_tys at)
       )
     end else begin
       assert (control = Type.Returns);
       let lf = E.make_lazy_function pre_env name in
       ( Const.t_of_v (Const.Fun (fun () -> Lib.AllocOnUse.use lf)), fun env ae ->
         let restore_no_env _env ae _ = ae, unmodified in
-        Lib.AllocOnUse.def lf (lazy (compile_local_function env ae restore_no_env args mk_body ret_tys at))
+        Lib.AllocOnUse.def lf (lazy (compile_local_function env ae restore_no_env name args mk_body ret_tys at))
       )
     end
 
@@ -5671,7 +6452,7 @@ module FuncDec = struct
 
       let f =
         if is_local
-        then compile_local_function env ae restore_env args mk_body ret_tys at
+        then compile_local_function env ae restore_env name args mk_body ret_tys at
         else assert false (* no first class shared functions yet *) in
 
       let fi = E.add_fun env name f in
@@ -5718,7 +6499,7 @@ module FuncDec = struct
       (SR.Const ct, G.nop)
     else closure env ae sort control name captured args mk_body ret_tys at
 
-  (* Returns the index of a saved closure *)
+  (* Returns the handle of a saved closure *)
   let async_body env ae ts free_vars mk_body at =
     (* We compile this as a local, returning function, so set return type to [] *)
     let sr, code = lit env ae "anon_async" Type.Local Type.Returns free_vars [] mk_body [] at in
@@ -5795,11 +6576,6 @@ module FuncDec = struct
       compile_unboxed_const (E.add_fun_ptr env (E.built_in env reject_name)) ^^
       get_cb_index
 
-  let ignoring_callback env =
-    let name = "@ignore_callback" in
-    Func.define_built_in env name ["env", I32Type] [] (fun env -> G.nop);
-    compile_unboxed_const (E.add_fun_ptr env (E.built_in env name))
-
   let ic_call env ts1 ts2 get_meth_pair get_arg get_k get_r add_cycles =
     match E.mode env with
     | Flags.ICMode
@@ -5895,6 +6671,32 @@ module FuncDec = struct
     | _ -> ()
     end
 
+  (* Sends the jobs of the expired timers to ourselves (see timer.rs), each
+     runs in its own message. This message only removes the timers and sets
+     the global timer again, so a trapping job doesn't roll back the others.
+     When a job can't be sent, it runs at the next global timer. *)
+  let export_global_timer env =
+    match E.mode env with
+    | (Flags.ICMode | Flags.RefMode) when !Flags.global_timer ->
+      let fi = E.add_fun env "canister_global_timer" (Func.of_body env [] [] (fun env ->
+        message_start env (Type.Shared Type.Write) ^^
+        compile_while
+          (IC.get_system_time env ^^ Timer.expired env ^^
+           G.if_ [I32Type]
+             (Timer.next env ^^
+              self_call_closure env ^^
+              G.i (Test (Wasm.Values.I32 I32Op.Eqz)))
+             (Bool.lit false))
+          (Timer.dequeue env) ^^
+        Timer.rearm env ^^
+        message_cleanup env (Type.Shared Type.Write)
+      )) in
+      E.add_export env (nr {
+        name = Wasm.Utf8.decode "canister_global_timer";
+        edesc = nr (FuncExport (nr fi))
+      })
+    | _ -> ()
+
 end (* FuncDec *)
 
 
@@ -6766,22 +7568,29 @@ let rec compile_lexp (env : E.t) ae lexp =
   | VarLE var ->
      G.nop,
      Var.set_val env ae var
+  | IdxLE (e1, e2) when !Flags.gc_strategy = Flags.Generational ->
+     let (set_array, get_array) = new_local env "array" in
+     compile_exp_vanilla env ae e1 ^^ set_array ^^
+     get_array ^^ (* offset to array *)
+     compile_exp_vanilla env ae e2 ^^ (* idx *)
+     Arr.idx_bigint env,
+     Arr.store_with_post_barrier env get_array
   | IdxLE (e1, e2) ->
      compile_exp_vanilla env ae e1 ^^ (* offset to array *)
      compile_exp_vanilla env ae e2 ^^ (* idx *)
      Arr.idx_bigint env,
-     store_ptr
+     Heap.store_ptr_with_barrier env
   | DotLE (e, n) ->
      compile_exp_vanilla env ae e ^^
      (* Only real objects have mutable fields, no need to branch on the tag *)
      Object.idx env e.note.Note.typ n,
-     store_ptr
+     Heap.store_ptr_with_barrier env
 
 and compile_exp (env : E.t) ae exp =
   (fun (sr,code) -> (sr, G.with_region exp.at code)) @@
   if exp.note.Note.const
   then let (c, fill) = compile_const_exp env ae exp in fill env ae; (SR.Const c, G.nop)
-  else match exp.it with
+  else AllocProfile.with_site env exp.at @@ fun () -> match exp.it with
   | PrimE (p, es) when List.exists (fun e -> Type.is_non e.note.Note.typ) es ->
     (* Handle dead code separately, so that we can rely on useful type
        annotations below *)
@@ -6915,6 +7724,7 @@ and compile_exp (env : E.t) ae exp =
       SR.Unreachable,
       compile_exp_as env ae (StackRep.of_arity (E.get_return_arity env)) e ^^
       FakeMultiVal.store env (Lib.List.make (E.get_return_arity env) I32Type) ^^
+      Backtrace.pop env ^^
       G.i Return
 
     (* Numeric conversions *)
@@ -7041,6 +7851,16 @@ and compile_exp (env : E.t) ae exp =
         compile_exp_as env ae SR.UnboxedWord64 e ^^
         G.i (Convert (Wasm.Values.F64 F64Op.ConvertSI64))
 
+      | Float, Int ->
+        SR.Vanilla,
+        compile_exp_as env ae SR.UnboxedFloat64 e ^^
+        BigNum.compile_of_float64 env
+
+      | Int, Float ->
+        SR.UnboxedFloat64,
+        compile_exp_vanilla env ae e ^^
+        BigNum.compile_to_float64 env
+
       | _ -> SR.Unreachable, todo_trap env "compile_exp" (Arrange_ir.exp exp)
       end
 
@@ -7062,6 +7882,15 @@ and compile_exp (env : E.t) ae exp =
       Heap.load_field Arr.len_field ^^
       BigNum.from_word32 env
 
+    | OtherPrim "array_blit", [e1; e2; e3; e4; e5] ->
+      SR.unit,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e3 ^^
+      compile_exp_vanilla env ae e4 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e5 ^^ BigNum.to_word32 env ^^
+      Arr.blit env
+
     | OtherPrim "text_len", [e] ->
       SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.len env
     | OtherPrim "text_iter", [e] ->
@@ -7070,9 +7899,90 @@ and compile_exp (env : E.t) ae exp =
       SR.bool, compile_exp_vanilla env ae e ^^ Text.iter_done env
     | OtherPrim "text_iter_next", [e] ->
       SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.iter_next env
+    | OtherPrim "text_split", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      Text.split env
+    | OtherPrim "text_split_done", [e] ->
+      SR.bool, compile_exp_vanilla env ae e ^^ Text.split_done env
+    | OtherPrim "text_split_next", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.split_next env
+    | OtherPrim "text_iter_graphemes", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.graphemes env
+    | OtherPrim "text_iter_graphemes_done", [e] ->
+      SR.bool, compile_exp_vanilla env ae e ^^ Text.graphemes_done env
+    | OtherPrim "text_iter_graphemes_next", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.graphemes_next env
+    | OtherPrim "text_slice", [e1; e2; e3] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e3 ^^ BigNum.to_word32 env ^^
+      Text.slice env
+    | OtherPrim "text_find", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      Text.find env
+    | OtherPrim "text_to_upper", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.to_upper env
+    | OtherPrim "text_to_lower", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.to_lower env
+    | OtherPrim "text_normalize_nfc", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.normalize_nfc env
+    | OtherPrim "text_intern", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.intern env
+    | OtherPrim "text_of_blob_lossy", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.of_blob_lossy env
+    | OtherPrim "text_to_utf16_blob", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.to_utf16_blob env
+    | OtherPrim "text_of_utf16_blob", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Text.of_utf16_blob env
+    | OtherPrim "text_repeat", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      Text.repeat env
 
     | OtherPrim "blob_size", [e] ->
       SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.len env ^^ BigNum.from_word32 env
+    | OtherPrim "blob_fill", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^ TaggedSmallWord.lsb_adjust Type.Nat8 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      Blob.fill env
+    | OtherPrim "blob_concat_many", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.concat_many env
+    | OtherPrim "blob_copy_range", [e1; e2; e3; e4; e5] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e3 ^^
+      compile_exp_vanilla env ae e4 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e5 ^^ BigNum.to_word32 env ^^
+      Blob.copy_range env
+    | OtherPrim "blob_compare_const_time", [e1; e2] ->
+      SR.bool,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      Blob.equal_const_time env
+    | OtherPrim "blob_sha256", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.sha256 env
+    | OtherPrim "blob_sha224", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.sha224 env
+    | OtherPrim "base64_encode", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.base64_encode env 2l
+    | OtherPrim "base64_encode_url", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.base64_encode env 1l
+    | OtherPrim "base64_decode", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.base64_decode env 0l
+    | OtherPrim "base64_decode_url", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.base64_decode env 1l
+    | OtherPrim "blob_to_hex", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.to_hex env
+    | OtherPrim "blob_of_hex", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.of_hex env
     | OtherPrim "blob_vals_iter", [e] ->
       SR.Vanilla, compile_exp_vanilla env ae e ^^ Blob.iter env
     | OtherPrim "blob_iter_done", [e] ->
@@ -7085,6 +7995,98 @@ and compile_exp (env : E.t) ae exp =
       compile_exp_vanilla env ae e ^^
       BigNum.compile_abs env
 
+    | OtherPrim "int_pow_mod", [e1; e2; e3] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      compile_exp_vanilla env ae e3 ^^
+      BigNum.compile_pow_mod env
+
+    | OtherPrim "int_gcd", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      BigNum.compile_gcd env
+
+    | OtherPrim "int_lcm", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      BigNum.compile_lcm env
+
+    | OtherPrim "nat_isqrt", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      BigNum.compile_isqrt env
+
+    | OtherPrim "nat_to_le_blob", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      BigNum.compile_to_le_blob env
+
+    | OtherPrim "nat_of_le_blob", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      BigNum.compile_of_le_blob env
+
+    | OtherPrim "int_to_signed_le_blob", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      BigNum.compile_to_signed_le_blob env
+
+    | OtherPrim "int_of_signed_le_blob", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      BigNum.compile_of_signed_le_blob env
+
+    | OtherPrim "nat_to_text", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      BigNum.compile_to_text env
+
+    | OtherPrim "int_of_text_radix", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      BigNum.compile_of_text_radix env
+
+    | OtherPrim "int_and", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      BigNum.compile_and env
+
+    | OtherPrim "int_or", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      BigNum.compile_or env
+
+    | OtherPrim "int_xor", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      BigNum.compile_xor env
+
+    | OtherPrim "int_shl", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      BigNum.compile_shl env
+
+    | OtherPrim "int_shr", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      BigNum.compile_shr env
+
+    | OtherPrim "int_btst", [e1; e2] ->
+      SR.bool,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      BigNum.compile_bit_test env
+
     | OtherPrim "fabs", [e] ->
       SR.UnboxedFloat64,
       compile_exp_as env ae SR.UnboxedFloat64 e ^^
@@ -7136,9 +8138,10 @@ and compile_exp (env : E.t) ae exp =
     | OtherPrim "Float->Text", [e] ->
       SR.Vanilla,
       compile_exp_as env ae SR.UnboxedFloat64 e ^^
-      compile_unboxed_const (TaggedSmallWord.vanilla_lit Type.Nat8 6) ^^
-      compile_unboxed_const (TaggedSmallWord.vanilla_lit Type.Nat8 0) ^^
-      E.call_import env "rts" "float_fmt"
+      E.call_import env "rts" "float_to_text"
+
+    | OtherPrim "float_of_text", [e] ->
+      SR.Vanilla, compile_exp_vanilla env ae e ^^ Float.of_text env
 
     | OtherPrim "fmtFloat->Text", [f; prec; mode] ->
       SR.Vanilla,
@@ -7223,6 +8226,202 @@ and compile_exp (env : E.t) ae exp =
       SR.Vanilla,
       Heap.get_max_live_size env ^^ BigNum.from_word32 env
 
+    | OtherPrim "rts_gc_stats", [] ->
+      SR.Vanilla,
+      let (set_stats, get_stats) = new_local env "gc_stats" in
+      let load64 offset =
+        get_stats ^^ G.i (Load {ty = I64Type; align = 2; offset; sz = None}) ^^
+        BigNum.from_word64 env in
+      let load32 offset =
+        get_stats ^^ G.i (Load {ty = I32Type; align = 2; offset; sz = None}) ^^
+        BigNum.from_word32 env in
+      Heap.get_gc_stats env ^^ set_stats ^^
+      Object.lit_raw env [
+        "collections", (fun () -> load64 0l);
+        "reclaimed", (fun () -> load64 8l);
+        "gc_instructions", (fun () -> load64 16l);
+        "max_live_size", (fun () -> load32 24l);
+        "heap_size", (fun () -> load32 28l);
+        "fragmentation", (fun () -> load32 32l);
+        "callback_table_retained", (fun () -> load32 36l);
+      ]
+
+    | OtherPrim "rts_features", [] ->
+      SR.Vanilla,
+      Features.get env
+
+    | OtherPrim "rts_perf_counters", [] ->
+      SR.Vanilla,
+      let (set_counters, get_counters) = new_local env "perf_counters" in
+      let load64 offset =
+        get_counters ^^ G.i (Load {ty = I64Type; align = 2; offset; sz = None}) ^^
+        BigNum.from_word64 env in
+      (* See PerfCounters in rts/motoko-rts/src/perf.rs *)
+      E.call_import env "rts" "get_perf_counters" ^^ set_counters ^^
+      Object.lit_raw env [
+        "gc", (fun () -> load64 0l);
+        "candid_decode", (fun () -> load64 8l);
+        "candid_encode", (fun () -> load64 16l);
+        "stabilization", (fun () -> load64 24l);
+      ]
+
+    | OtherPrim "rts_message_allocation", [] ->
+      SR.Vanilla,
+      let (set_alloc, get_alloc) = new_local env "message_allocation" in
+      Heap.get_message_allocation env ^^ set_alloc ^^
+      Object.lit_raw env [
+        "bytes", (fun () ->
+          get_alloc ^^ G.i (Load {ty = I64Type; align = 2; offset = 0l; sz = None}) ^^
+          BigNum.from_word64 env);
+        "objects", (fun () ->
+          get_alloc ^^ G.i (Load {ty = I32Type; align = 2; offset = 8l; sz = None}) ^^
+          BigNum.from_word32 env);
+      ]
+
+    | OtherPrim "rts_candid_stats", [] ->
+      SR.Vanilla,
+      let (set_stats, get_stats) = new_local env "candid_stats" in
+      let load64 offset =
+        get_stats ^^ G.i (Load {ty = I64Type; align = 2; offset; sz = None}) ^^
+        BigNum.from_word64 env in
+      (* See IdlStats in rts/motoko-rts/src/idl.rs *)
+      E.call_import env "rts" "idl_stats" ^^ set_stats ^^
+      Object.lit_raw env [
+        "values", (fun () -> load64 0l);
+        "bytes", (fun () -> load64 8l);
+        "allocated", (fun () -> load64 16l);
+      ]
+
+    | OtherPrim "rts_stable_memory_header", [] ->
+      SR.Vanilla,
+      let (set_header, get_header) = new_local env "stable_memory_header" in
+      let load32 offset =
+        get_header ^^ G.i (Load {ty = I32Type; align = 2; offset; sz = None}) ^^
+        BigNum.from_word32 env in
+      (* See StableMemoryHeader in rts/motoko-rts/src/region.rs *)
+      E.call_import env "rts" "stable_memory_header" ^^ set_header ^^
+      Object.lit_raw env [
+        "version", (fun () -> load32 0l);
+        "flags", (fun () -> load32 4l);
+        "regions", (fun () -> load32 8l);
+      ]
+
+    | OtherPrim "regionNew", [] ->
+      SR.UnboxedWord32,
+      Region.new_ env
+
+    | OtherPrim "regionSize", [e] ->
+      SR.UnboxedWord64,
+      compile_exp_as env ae SR.UnboxedWord32 e ^^
+      Region.size env
+
+    | OtherPrim "regionGrow", [e1; e2] ->
+      SR.UnboxedWord64,
+      compile_exp_as env ae SR.UnboxedWord32 e1 ^^
+      compile_exp_as env ae SR.UnboxedWord64 e2 ^^
+      Region.grow env
+
+    | OtherPrim "regionLoadBlob", [e1; e2; e3] ->
+      SR.Vanilla,
+      compile_exp_as env ae SR.UnboxedWord32 e1 ^^
+      compile_exp_as env ae SR.UnboxedWord64 e2 ^^
+      compile_exp_vanilla env ae e3 ^^ BigNum.to_word32 env ^^
+      Region.load_blob env
+
+    | OtherPrim "regionStream", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_as env ae SR.UnboxedWord32 e1 ^^
+      compile_exp_as env ae SR.UnboxedWord64 e2 ^^
+      Region.stream env
+
+    | OtherPrim "regionStreamWrite", [e1; e2] ->
+      SR.unit,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      Region.stream_write env
+
+    | OtherPrim "regionStreamFlush", [e] ->
+      SR.UnboxedWord64,
+      compile_exp_vanilla env ae e ^^
+      Region.stream_flush env
+
+    | OtherPrim "regionBuffer", [e1; e2; e3] ->
+      SR.Vanilla,
+      compile_exp_as env ae SR.UnboxedWord32 e1 ^^
+      compile_exp_as env ae SR.UnboxedWord64 e2 ^^
+      compile_exp_as env ae SR.UnboxedWord64 e3 ^^
+      Region.buffer env
+
+    | OtherPrim "regionBufferRead", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      Region.buffer_read env
+
+    | OtherPrim "regionBufferRemaining", [e] ->
+      SR.UnboxedWord64,
+      compile_exp_vanilla env ae e ^^
+      Region.buffer_remaining env
+
+    | OtherPrim "rts_gc_force", [] ->
+      SR.unit,
+      Heap.force_gc env
+
+    | OtherPrim "rts_gc_defer", [e] ->
+      SR.unit,
+      compile_exp_as env ae SR.bool e ^^
+      Heap.defer_gc env
+
+    | OtherPrim "rts_set_gc_schedule", [e1; e2; e3] ->
+      SR.unit,
+      compile_exp_vanilla env ae e1 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e3 ^^ BigNum.to_word32 env ^^
+      Heap.set_gc_schedule env
+
+    | OtherPrim "rts_set_candid_limits", [e1; e2; e3] ->
+      SR.unit,
+      compile_exp_vanilla env ae e1 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e2 ^^ BigNum.to_word32 env ^^
+      compile_exp_vanilla env ae e3 ^^ BigNum.to_word32 env ^^
+      E.call_import env "rts" "idl_set_limits"
+
+    | OtherPrim "rts_set_compaction_threshold", [e] ->
+      SR.unit,
+      compile_exp_vanilla env ae e ^^
+      BigNum.to_word32 env ^^
+      Heap.set_compaction_threshold env
+
+    | OtherPrim "rts_set_heap_limit", [e] ->
+      SR.unit,
+      compile_exp_vanilla env ae e ^^
+      BigNum.to_word32 env ^^
+      Heap.set_heap_limit env
+
+    | OtherPrim "rts_heap_limit", [] ->
+      SR.Vanilla,
+      Heap.get_heap_limit env ^^ Prim.prim_word32toNat env
+
+    | OtherPrim "rts_heap_snapshot", [] ->
+      SR.Vanilla,
+      Heap.heap_snapshot env
+
+    | OtherPrim "rts_heap_checkpoint", [] ->
+      SR.unit,
+      Heap.heap_checkpoint env
+
+    | OtherPrim "rts_heap_diff", [] ->
+      SR.Vanilla,
+      Heap.heap_diff env
+
+    | OtherPrim "rts_heap_census", [] ->
+      SR.Vanilla,
+      Heap.heap_live_census env
+
+    | OtherPrim "rts_alloc_profile", [] ->
+      SR.Vanilla,
+      AllocProfile.get_profile env
+
     | OtherPrim "rts_callback_table_count", [] ->
       SR.Vanilla,
       ClosureTable.count env ^^ Prim.prim_word32toNat env
@@ -7231,11 +8430,88 @@ and compile_exp (env : E.t) ae exp =
       SR.Vanilla,
       ClosureTable.size env ^^ Prim.prim_word32toNat env
 
+    | OtherPrim "random_seed", [e] ->
+      SR.unit,
+      compile_exp_as env ae SR.UnboxedWord64 e ^^
+      E.call_import env "rts" "random_seed"
+
+    | OtherPrim "random_seed_blob", [e] ->
+      SR.unit,
+      compile_exp_vanilla env ae e ^^
+      E.call_import env "rts" "random_seed_blob"
+
+    | OtherPrim "random_next", [] ->
+      SR.UnboxedWord64,
+      E.call_import env "rts" "random_next"
+
+    | OtherPrim "blob_random", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^ BigNum.to_word32 env ^^
+      E.call_import env "rts" "blob_random"
+
+    | OtherPrim "timer_set", [e1; e2] ->
+      SR.UnboxedWord32,
+      compile_exp_as env ae SR.UnboxedWord64 e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      Timer.set env
+
+    | OtherPrim "timer_cancel", [e] ->
+      SR.bool,
+      compile_exp_as env ae SR.UnboxedWord32 e ^^
+      Timer.cancel env
+
+    | OtherPrim "register_finalizer", [e1; e2] ->
+      SR.unit,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      Finalizer.register env
+
+    | OtherPrim "weak_ref_new", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      WeakRef.alloc env
+
+    | OtherPrim "weak_ref_alive", [e] ->
+      SR.bool,
+      compile_exp_vanilla env ae e ^^
+      WeakRef.alive env
+
+    | OtherPrim "weak_ref_get", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      WeakRef.get env
+
+    | OtherPrim "pin_object", [e] ->
+      SR.unit,
+      compile_exp_vanilla env ae e ^^
+      Pin.pin env
+
+    | OtherPrim "unpin_object", [e] ->
+      SR.unit,
+      compile_exp_vanilla env ae e ^^
+      Pin.unpin env
+
     | OtherPrim "crc32Hash", [e] ->
       SR.UnboxedWord32,
       compile_exp_vanilla env ae e ^^
       E.call_import env "rts" "compute_crc32"
 
+    (* More CRCs, of blobs or texts, see rts/motoko-rts/src/crc.rs *)
+    | OtherPrim "blob_crc32c", [e] ->
+      SR.UnboxedWord32,
+      compile_exp_vanilla env ae e ^^
+      E.call_import env "rts" "blob_crc32c"
+    | OtherPrim "blob_crc16", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      E.call_import env "rts" "blob_crc16" ^^
+      TaggedSmallWord.msb_adjust Type.Nat16
+    | OtherPrim "blob_crc8", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      E.call_import env "rts" "blob_crc8" ^^
+      TaggedSmallWord.msb_adjust Type.Nat8
+
     | OtherPrim "idlHash", [e] ->
       SR.Vanilla,
       E.trap_with env "idlHash only implemented in interpreter "
@@ -7279,6 +8555,9 @@ and compile_exp (env : E.t) ae exp =
     | OtherPrim "char_to_lower", [e] ->
       compile_char_to_char_rts env ae e "char_to_lower"
 
+    | OtherPrim "char_fold_case", [e] ->
+      compile_char_to_char_rts env ae e "char_fold_case"
+
     | OtherPrim "char_is_whitespace", [e] ->
       compile_char_to_bool_rts env ae e "char_is_whitespace"
 
@@ -7291,6 +8570,16 @@ and compile_exp (env : E.t) ae exp =
     | OtherPrim "char_is_alphabetic", [e] ->
       compile_char_to_bool_rts env ae e "char_is_alphabetic"
 
+    | OtherPrim "char_is_numeric", [e] ->
+      compile_char_to_bool_rts env ae e "char_is_numeric"
+
+    | OtherPrim "char_general_category", [e] ->
+      SR.Vanilla,
+      compile_exp_as env ae SR.Vanilla e ^^
+      TaggedSmallWord.untag_codepoint ^^
+      E.call_import env "rts" "char_general_category" ^^
+      TaggedSmallWord.msb_adjust Type.Nat8
+
     | OtherPrim "print", [e] ->
       SR.unit,
       compile_exp_vanilla env ae e ^^
@@ -7335,6 +8624,32 @@ and compile_exp (env : E.t) ae exp =
     (* The other direction *)
     | IcUrlOfBlob, [_] ->
       const_sr SR.Vanilla (E.call_import env "rts" "principal_of_blob")
+    (* Like BlobOfIcUrl, but null instead of trapping *)
+    | OtherPrim "principal_of_text_checked", [e] ->
+      SR.Vanilla,
+      let (set_r, get_r) = new_local env "r" in
+      compile_exp_vanilla env ae e ^^
+      E.call_import env "rts" "principal_of_text_checked" ^^ set_r ^^
+      get_r ^^ G.i (Test (Wasm.Values.I32 I32Op.Eqz)) ^^
+      G.if_ [I32Type] (Opt.null_lit env) (Opt.inject_noop env get_r)
+    (* Self-authenticating principal of a public key *)
+    | OtherPrim "principal_of_public_key", [e] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e ^^
+      E.call_import env "rts" "principal_of_public_key"
+    (* Ordering of principals, -1, 0 or 1 as an Int8 *)
+    | OtherPrim "principal_compare", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      E.call_import env "rts" "principal_compare" ^^
+      TaggedSmallWord.msb_adjust Type.Int8
+    (* Ledger account identifier of a principal and a subaccount *)
+    | OtherPrim "account_identifier", [e1; e2] ->
+      SR.Vanilla,
+      compile_exp_vanilla env ae e1 ^^
+      compile_exp_vanilla env ae e2 ^^
+      E.call_import env "rts" "account_identifier"
 
     (* Actor ids are blobs in the RTS *)
     | ActorOfIdBlob _, [e] ->
@@ -7389,7 +8704,7 @@ and compile_exp (env : E.t) ae exp =
            2. return v
 *)
       SR.Vanilla,
-      E.call_import env "ic0" "stable_size" ^^
+      Stabilization.stable_data_size env ^^
       G.if_ [I32Type]
         (Stabilization.destabilize env ty)
         (let (_, fs) = Type.as_obj ty in
@@ -8068,6 +9383,10 @@ and conclude_module env start_fi_o =
 
   FuncDec.export_async_method env;
 
+  FuncDec.export_global_timer env;
+
+  AllocProfile.register env;
+
   let static_roots = GC.store_static_roots env in
   (* declare before building GC *)
 
@@ -8085,6 +9404,9 @@ and conclude_module env start_fi_o =
   (* Wrap the start function with the RTS initialization *)
   let rts_start_fi = E.add_fun env "rts_start" (Func.of_body env [] [] (fun env1 ->
     E.call_import env "rts" "init" ^^
+    Features.init env ^^
+    Heap.init_alloc_fast_path env ^^
+    AllocProfile.enable env ^^
     match start_fi_o with
     | Some fi ->
       G.i (Call fi)
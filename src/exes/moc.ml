@@ -100,6 +100,39 @@ let argspec = Arg.align [
   Arg.Unit
     (fun () -> Flags.sanity := true),
   " enable sanity checking in the RTS and generated code";
+  "--incremental-gc",
+  Arg.Unit
+    (fun () -> Flags.gc_strategy := Flags.Incremental),
+  " use the incremental garbage collector, which does a bounded amount of work per message";
+  "--generational-gc",
+  Arg.Unit
+    (fun () -> Flags.gc_strategy := Flags.Generational),
+  " use the generational garbage collector";
+  "--mark-sweep-gc",
+  Arg.Unit
+    (fun () -> Flags.gc_strategy := Flags.MarkSweep),
+  " use the non-moving mark-sweep garbage collector";
+  "--alloc-profile",
+  Arg.Set Flags.alloc_profile,
+  " record the allocation site of heap objects, see Prim.rts_alloc_profile";
+  "--candid-stats",
+  Arg.Set Flags.candid_stats,
+  " count the values, bytes and allocation of Candid decoding, see Prim.rts_candid_stats";
+  "--graph-copy-stabilization",
+  Arg.Set Flags.graph_copy_stabilization,
+  " copy the stable variables to stable memory as heap objects on upgrades, instead of encoding them in Candid";
+  "--compress-stabilization",
+  Arg.Set Flags.compress_stabilization,
+  " compress the stable variables in stable memory on upgrades";
+  "--global-timer",
+  Arg.Set Flags.global_timer,
+  " export canister_global_timer to run the timers of Prim.setTimer";
+  "--trap-backtrace",
+  Arg.Set Flags.trap_backtrace,
+  " append the chain of Motoko function calls to trap messages";
+  "--perf-counters",
+  Arg.Set Flags.perf_counters,
+  " count the instructions of collections, Candid and stabilization, see Prim.rts_perf_counters";
     ]
   @  Args.inclusion_args
 
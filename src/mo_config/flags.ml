@@ -4,6 +4,8 @@ module M = Map.Make(String)
 
 type compile_mode = WasmMode | ICMode | RefMode | WASIMode
 
+type gc_strategy = Copying | Incremental | Generational | MarkSweep
+
 let trace = ref false
 let verbose = ref false
 let print_warnings = ref true
@@ -30,3 +32,11 @@ let profile_field_names : string list ref = ref []
 let compiled = ref false
 let error_detail = ref 2
 let sanity = ref false
+let gc_strategy = ref Copying
+let alloc_profile = ref false
+let candid_stats = ref false
+let graph_copy_stabilization = ref false
+let compress_stabilization = ref false
+let global_timer = ref false
+let trap_backtrace = ref false
+let perf_counters = ref false
@@ -49,6 +49,49 @@ let of_big_int_wrap = function
 Wrapping numeric conversions are all specified uniformly by going through bigint
 *)
 
+(* Big integers to the nearest float, ties to even, like the RTS: the top 63 bits, with the lowest
+   bit set when any of the dropped bits is set, round like the number *)
+let big_int_to_float n =
+  let open Big_int in
+  let m = abs_big_int n in
+  let shift = max 0 (num_bits_big_int m - 63) in
+  let top = shift_right_big_int m shift in
+  let sticky = not (eq_big_int (shift_left_big_int top shift) m) in
+  let top = Int64.logor (int64_of_big_int top) (if sticky then 1L else 0L) in
+  let f = ldexp (Int64.to_float top) shift in
+  if sign_big_int n < 0 then -. f else f
+
+(* Floats to big integers, truncated towards zero *)
+let big_int_of_float f =
+  let open Big_int in
+  if not (Stdlib.Float.is_finite f)
+  then raise (Invalid_argument "bigint_of_float64: Not a finite number");
+  let fr, exp = frexp (Stdlib.Float.trunc f) in
+  let m = big_int_of_int64 (Int64.of_float (ldexp fr 53)) in
+  if exp >= 53 then shift_left_big_int m (exp - 53)
+  else div_big_int m (power_int_positive_int 2 (53 - exp))
+
+(* CRCs (as in rts/motoko-rts/src/crc.rs), one bit at a time. When reflected,
+   `poly` is the reversed polynomial *)
+let crc ~width ~poly ~init ~reflected ~xor_out s =
+  let mask = (1 lsl width) - 1 in
+  let top = 1 lsl (width - 1) in
+  let crc = ref init in
+  String.iter (fun c ->
+    let byte = Char.code c in
+    if reflected then begin
+      crc := !crc lxor byte;
+      for _ = 1 to 8 do
+        crc := if !crc land 1 <> 0 then (!crc lsr 1) lxor poly else !crc lsr 1
+      done
+    end else begin
+      crc := !crc lxor (byte lsl (width - 8));
+      for _ = 1 to 8 do
+        crc := (if !crc land top <> 0 then (!crc lsl 1) lxor poly else !crc lsl 1) land mask
+      done
+    end) s;
+  !crc lxor xor_out
+
 (* Trapping conversions (the num_conv_t1_t2 prim used in prelude/prelude.ml) *)
 let num_conv_trap_prim t1 t2 =
   let module T = Type in
@@ -62,6 +105,8 @@ let num_conv_trap_prim t1 t2 =
 
   | T.Float, T.Int64 -> fun v -> Int64 (Int_64.of_big_int (Big_int.big_int_of_int64 (Wasm.I64_convert.trunc_f64_s (as_float v))))
   | T.Int64, T.Float -> fun v -> Float (Wasm.F64_convert.convert_i64_s (Big_int.int64_of_big_int (Int_64.to_big_int (as_int64 v))))
+  | T.Float, T.Int -> fun v -> Int (big_int_of_float (Float.to_float (as_float v)))
+  | T.Int, T.Float -> fun v -> Float (Float.of_float (big_int_to_float (as_int v)))
   | t1, t2 -> raise (Invalid_argument ("Value.num_conv_trap_prim: " ^ T.string_of_typ (T.Prim t1) ^ T.string_of_typ (T.Prim t2) ))
 
 (*
@@ -71,6 +116,254 @@ for suitable types t1 and t2
 let num_conv_wrap_prim t1 t2 =
   fun v -> of_big_int_wrap t2 (as_big_int t1 v)
 
+(* Decodes UTF-8, replacing each invalid sequence (a prefix of a valid sequence, or a single byte)
+   with U+FFFD, like the RTS *)
+let utf8_decode_lossy s =
+  let n = String.length s in
+  let byte i = Char.code s.[i] in
+  let cont i lo hi = i < n && byte i >= lo && byte i <= hi in
+  let payload i = byte i land 0x3f in
+  let rec go i acc =
+    if i >= n then List.rev acc else
+    let b = byte i in
+    if b < 0x80 then go (i + 1) (b :: acc) else
+    (* Length of the sequence, and the range of its second byte *)
+    let len, lo, hi =
+      if b >= 0xc2 && b <= 0xdf then 2, 0x80, 0xbf
+      else if b = 0xe0 then 3, 0xa0, 0xbf
+      else if b = 0xed then 3, 0x80, 0x9f
+      else if b >= 0xe1 && b <= 0xef then 3, 0x80, 0xbf
+      else if b = 0xf0 then 4, 0x90, 0xbf
+      else if b >= 0xf1 && b <= 0xf3 then 4, 0x80, 0xbf
+      else if b = 0xf4 then 4, 0x80, 0x8f
+      else 0, 0, 0 in
+    if len = 0 || not (cont (i + 1) lo hi) then go (i + 1) (0xfffd :: acc)
+    else if len = 2 then go (i + 2) (((b land 0x1f) lsl 6 lor payload (i + 1)) :: acc)
+    else if not (cont (i + 2) 0x80 0xbf) then go (i + 2) (0xfffd :: acc)
+    else if len = 3 then
+      go (i + 3) (((b land 0x0f) lsl 12 lor payload (i + 1) lsl 6 lor payload (i + 2)) :: acc)
+    else if not (cont (i + 3) 0x80 0xbf) then go (i + 3) (0xfffd :: acc)
+    else
+      go (i + 4) (((b land 0x07) lsl 18 lor payload (i + 1) lsl 12 lor payload (i + 2) lsl 6
+        lor payload (i + 3)) :: acc)
+  in go 0 []
+
+(* Base64 (RFC 4648) with the standard or the URL-safe alphabet, like the RTS: decoding accepts
+   texts with or without padding, but no other characters, and no bits after the last byte that
+   are not zero *)
+let base64_chars url_safe =
+  "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789" ^
+  (if url_safe then "-_" else "+/")
+
+let base64_encode url_safe pad s =
+  let chars = base64_chars url_safe in
+  let n = String.length s in
+  let byte i = if i < n then Char.code s.[i] else 0 in
+  let buf = Buffer.create ((n + 2) / 3 * 4) in
+  let rec go i =
+    if i < n then begin
+      let data = byte i lsl 16 lor byte (i + 1) lsl 8 lor byte (i + 2) in
+      (* A group of n bytes is encoded with n + 1 characters *)
+      for j = 0 to 3 do
+        if j <= n - i then Buffer.add_char buf chars.[(data lsr (18 - 6 * j)) land 0x3f]
+        else if pad then Buffer.add_char buf '='
+      done;
+      go (i + 3)
+    end in
+  go 0;
+  Buffer.contents buf
+
+let base64_decode url_safe t =
+  let chars = base64_chars url_safe in
+  let n = String.length t in
+  let n =
+    if n mod 4 = 0 && n >= 2 && String.sub t (n - 2) 2 = "==" then n - 2
+    else if n mod 4 = 0 && n >= 1 && t.[n - 1] = '=' then n - 1
+    else n in
+  let value c = match String.index_opt chars c with Some v -> v | None -> -1 in
+  let values = Array.init n (fun i -> value t.[i]) in
+  let buf = Buffer.create (n / 4 * 3) in
+  let rec go i =
+    if i >= n then Some (Buffer.contents buf) else
+    let len = min 4 (n - i) in
+    let data = ref 0 in
+    for j = 0 to len - 1 do data := !data lor values.(i + j) lsl (18 - 6 * j) done;
+    for j = 0 to len - 2 do
+      Buffer.add_char buf (Char.chr ((!data lsr (16 - 8 * j)) land 0xff))
+    done;
+    if !data land ((1 lsl (24 - 8 * (len - 1))) - 1) <> 0 then None else go (i + 4) in
+  if n mod 4 = 1 || Array.exists (fun v -> v < 0) values then None else go 0
+
+(* Hexadecimal, like the RTS: lowercase digits for encoding, decoding accepts both cases and a
+   "0x" prefix *)
+let blob_to_hex s =
+  String.concat "" (List.init (String.length s) (fun i -> Printf.sprintf "%02x" (Char.code s.[i])))
+
+let blob_of_hex t =
+  let n = String.length t in
+  let t = if n >= 2 && (String.sub t 0 2 = "0x" || String.sub t 0 2 = "0X") then String.sub t 2 (n - 2) else t in
+  let value c = match c with
+    | '0'..'9' -> Char.code c - Char.code '0'
+    | 'a'..'f' -> Char.code c - Char.code 'a' + 10
+    | 'A'..'F' -> Char.code c - Char.code 'A' + 10
+    | _ -> -1 in
+  let n = String.length t in
+  let digits = List.init n (fun i -> value t.[i]) in
+  if n mod 2 <> 0 || List.exists (fun v -> v < 0) digits then None
+  else Some (String.init (n / 2) (fun i -> Char.chr (value t.[2 * i] lsl 4 lor value t.[2 * i + 1])))
+
+(* Engineering notation, like the RTS: "%.*e" with an exponent that is a multiple of 3, and 1 to 3
+   digits before the point *)
+let float_engineering prec f =
+  let open Printf in
+  let exponent s =
+    let i = String.index s 'e' in
+    int_of_string (String.sub s (i + 1) (String.length s - i - 1)) in
+  let format e10 =
+    let int_digits = (e10 mod 3 + 3) mod 3 + 1 in
+    int_digits, sprintf "%.*e" (int_digits - 1 + prec) f in
+  if not (Stdlib.Float.is_finite f) then sprintf "%.*e" prec f else
+  let e10 = if f = 0.0 then 0 else exponent (sprintf "%.40e" f) in
+  let int_digits, s = format e10 in
+  (* Rounding may reach the next power of ten *)
+  let int_digits, s = if exponent s = e10 then int_digits, s else format (exponent s) in
+  let exp = exponent s - int_digits + 1 in
+  let negative = s.[0] = '-' in
+  let mantissa = List.hd (String.split_on_char 'e' s) in
+  let digits = String.concat "" (String.split_on_char '.' mantissa) in
+  let digits = if negative then String.sub digits 1 (String.length digits - 1) else digits in
+  let frac = String.sub digits int_digits (String.length digits - int_digits) in
+  sprintf "%s%s%s%se%c%02d" (if negative then "-" else "") (String.sub digits 0 int_digits)
+    (if frac = "" then "" else ".") frac (if exp < 0 then '-' else '+') (abs exp)
+
+(* Parsing of floats, like the RTS: an optional sign, then "inf", "infinity" or "nan" in any case,
+   or digits with an optional point and fraction (at least one digit) and an optional exponent *)
+let float_of_text t =
+  let n = String.length t in
+  let start = if n > 0 && (t.[0] = '+' || t.[0] = '-') then 1 else 0 in
+  let negative = start = 1 && t.[0] = '-' in
+  let rec digits i = if i < n && '0' <= t.[i] && t.[i] <= '9' then digits (i + 1) else i in
+  let sign f = if negative then -. f else f in
+  match String.lowercase_ascii (String.sub t start (n - start)) with
+  | "inf" | "infinity" -> Some (sign Stdlib.infinity)
+  | "nan" -> Some Stdlib.nan
+  | _ ->
+    let int_end = digits start in
+    let frac_end = if int_end < n && t.[int_end] = '.' then digits (int_end + 1) else int_end in
+    let mantissa_digits = int_end - start + max 0 (frac_end - int_end - 1) in
+    let exp_end =
+      if frac_end < n && (t.[frac_end] = 'e' || t.[frac_end] = 'E') then
+        let exp_start =
+          if frac_end + 1 < n && (t.[frac_end + 1] = '+' || t.[frac_end + 1] = '-')
+          then frac_end + 2 else frac_end + 1 in
+        let e = digits exp_start in
+        if e = exp_start then -1 else e
+      else frac_end in
+    if mantissa_digits = 0 || exp_end <> n then None else Some (float_of_string t)
+
+(* Bitwise operations on the two's complement, like the RTS. Big_int only supports non-negative
+   arguments, so a negative number is handled as the complement of a non-negative one *)
+let int_lnot a = Big_int.(sub_big_int (minus_big_int a) unit_big_int)
+
+let int_and a b =
+  let open Big_int in
+  match sign_big_int a < 0, sign_big_int b < 0 with
+  | false, false -> and_big_int a b
+  | true, false -> sub_big_int b (and_big_int b (int_lnot a))
+  | false, true -> sub_big_int a (and_big_int a (int_lnot b))
+  | true, true -> int_lnot (or_big_int (int_lnot a) (int_lnot b))
+
+let int_or a b = int_lnot (int_and (int_lnot a) (int_lnot b))
+
+let int_xor a b =
+  let open Big_int in
+  match sign_big_int a < 0, sign_big_int b < 0 with
+  | false, false -> xor_big_int a b
+  | true, false -> int_lnot (xor_big_int (int_lnot a) b)
+  | false, true -> int_lnot (xor_big_int a (int_lnot b))
+  | true, true -> xor_big_int (int_lnot a) (int_lnot b)
+
+(* Little-endian encodings, like the RTS *)
+let nat_to_le_blob n =
+  let open Big_int in
+  if sign_big_int n < 0 then raise (Invalid_argument "bigint_to_le_blob: Negative number");
+  let rec go n =
+    if sign_big_int n = 0 then [] else
+    String.make 1 (Char.chr (int_of_big_int (and_big_int n (big_int_of_int 255)))) ::
+    go (shift_right_big_int n 8)
+  in String.concat "" (go n)
+
+let nat_of_le_blob s =
+  let open Big_int in
+  let n = ref zero_big_int in
+  for i = String.length s - 1 downto 0 do
+    n := add_int_big_int (Char.code s.[i]) (shift_left_big_int !n 8)
+  done;
+  !n
+
+let int_to_signed_le_blob n width =
+  let open Big_int in
+  let fits =
+    if width = 0 then sign_big_int n = 0 else
+    let half = power_int_positive_int 2 (8 * width - 1) in
+    le_big_int (minus_big_int half) n && lt_big_int n half in
+  if not fits then raise (Invalid_argument "bigint_to_signed_le_blob: Number does not fit");
+  let m = mod_big_int n (power_int_positive_int 2 (8 * width)) in
+  String.init width (fun i ->
+    Char.chr (int_of_big_int (and_big_int (shift_right_big_int m (8 * i)) (big_int_of_int 255))))
+
+let int_of_signed_le_blob s =
+  let n = String.length s in
+  let m = nat_of_le_blob s in
+  if n > 0 && Char.code s.[n - 1] land 0x80 <> 0
+  then Big_int.(sub_big_int m (power_int_positive_int 2 (8 * n)))
+  else m
+
+(* Square and multiply, the result is in [0, m) *)
+let int_pow_mod b e m =
+  let open Big_int in
+  if sign_big_int m <= 0 then raise (Invalid_argument "bigint_pow_mod: Modulus is not positive");
+  let rec go r b e =
+    if sign_big_int e = 0 then r else
+    let r = if eq_big_int (and_big_int e unit_big_int) unit_big_int
+      then mod_big_int (mult_big_int r b) m else r in
+    go r (mod_big_int (mult_big_int b b) m) (shift_right_big_int e 1)
+  in go (mod_big_int unit_big_int m) (mod_big_int b m) e
+
+(* Integers in the syntax of literals, like the RTS: optional sign, optional prefix matching the
+   radix (any with radix 0, which is decimal otherwise), digits with single "_" between them *)
+let int_of_text_radix t radix =
+  if radix = 1 || radix > 36 then raise (Invalid_argument "bigint_of_text_radix: Invalid radix");
+  let n = String.length t in
+  let negative = n > 0 && t.[0] = '-' in
+  let i = if n > 0 && (t.[0] = '-' || t.[0] = '+') then 1 else 0 in
+  let prefix_radix =
+    if n - i < 2 || t.[i] <> '0' then 0 else
+    match t.[i + 1] with
+    | 'x' | 'X' -> 16
+    | 'o' | 'O' -> 8
+    | 'b' | 'B' -> 2
+    | _ -> 0 in
+  let i, radix =
+    if prefix_radix > 0 && (radix = 0 || radix = prefix_radix) then i + 2, prefix_radix
+    else i, (if radix = 0 then 10 else radix) in
+  let value c = match c with
+    | '0'..'9' -> Char.code c - Char.code '0'
+    | 'a'..'z' -> Char.code c - Char.code 'a' + 10
+    | 'A'..'Z' -> Char.code c - Char.code 'A' + 10
+    | _ -> 36 in
+  let rec go acc j last_sep =
+    if j = n then (if last_sep then None else Some acc)
+    else if t.[j] = '_' then (if last_sep then None else go acc (j + 1) true)
+    else
+      let v = value t.[j] in
+      if v >= radix then None
+      else go Big_int.(add_int_big_int v (mult_int_big_int radix acc)) (j + 1) false
+  in
+  match go Big_int.zero_big_int i true with
+  | Some m -> Some (if negative then Big_int.minus_big_int m else m)
+  | None -> None
+
 let prim =
   let via_float f v = Float.(Float (of_float (f (to_float (as_float v))))) in
   let via_float2 f v w = Float.(Float (of_float (f (to_float (as_float v)) (to_float (as_float w))))) in
@@ -82,9 +375,51 @@ let prim =
     | 1 -> sprintf "%.*e" prec
     | 2 -> sprintf "%.*g" prec
     | 3 -> sprintf "%.*h" prec
+    | 4 -> float_engineering prec
+    | 5 -> sprintf "%h"
     | _ -> fun _ -> raise (Invalid_argument "float_formatter: unrecognised mode") in
   function
   | "abs" -> fun _ v k -> k (Int (Nat.abs (as_int v)))
+  | "nat_to_le_blob" -> fun _ v k -> k (Blob (nat_to_le_blob (as_int v)))
+  | "nat_of_le_blob" -> fun _ v k -> k (Int (nat_of_le_blob (as_blob v)))
+  | "int_to_signed_le_blob" -> fun _ v k ->
+    let n, width = as_pair v in
+    k (Blob (int_to_signed_le_blob (as_int n) (Big_int.int_of_big_int (as_int width))))
+  | "int_of_signed_le_blob" -> fun _ v k -> k (Int (int_of_signed_le_blob (as_blob v)))
+  | "nat_isqrt" -> fun _ v k -> k (Int (Big_int.sqrt_big_int (as_int v)))
+  | "nat_to_text" -> fun _ v k -> k (Text (Nat.to_string (as_int v)))
+  | "int_of_text_radix" -> fun _ v k ->
+    let t, radix = as_pair v in
+    (match int_of_text_radix (as_text t) (Big_int.int_of_big_int (as_int radix)) with
+     | Some n -> k (Opt (Int n))
+     | None -> k Null)
+  | "int_pow_mod" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [b; e; m] -> k (Int (int_pow_mod (as_int b) (as_int e) (as_int m)))
+     | _ -> assert false)
+  | "int_gcd" -> fun _ v k ->
+    let a, b = as_pair v in
+    k (Int (Big_int.gcd_big_int (as_int a) (as_int b)))
+  | "int_lcm" -> fun _ v k ->
+    let a, b = as_pair v in
+    let a, b = Big_int.(abs_big_int (as_int a), abs_big_int (as_int b)) in
+    k (Int Big_int.(if sign_big_int a = 0 || sign_big_int b = 0 then zero_big_int
+      else mult_big_int (div_big_int a (gcd_big_int a b)) b))
+  | "int_and" | "int_or" | "int_xor" as p -> fun _ v k ->
+    let a, b = as_pair v in
+    let op = match p with "int_and" -> int_and | "int_or" -> int_or | _ -> int_xor in
+    k (Int (op (as_int a) (as_int b)))
+  | "int_shl" -> fun _ v k ->
+    let a, n = as_pair v in
+    k (Int (Big_int.shift_left_big_int (as_int a) (Big_int.int_of_big_int (as_int n))))
+  | "int_shr" -> fun _ v k ->
+    (* rounds towards negative infinity *)
+    let a, n = as_pair v in
+    k (Int (Big_int.shift_right_big_int (as_int a) (Big_int.int_of_big_int (as_int n))))
+  | "int_btst" -> fun _ v k ->
+    let a, n = as_pair v in
+    let shifted = Big_int.shift_right_big_int (as_int a) (Big_int.int_of_big_int (as_int n)) in
+    k (Bool (Big_int.(eq_big_int (mod_big_int shifted (big_int_of_int 2)) unit_big_int)))
   | "fabs" -> fun _ v k -> k (Float (Float.abs (as_float v)))
   | "fsqrt" -> fun _ v k -> k (Float (Float.sqrt (as_float v)))
   | "fceil" -> fun _ v k -> k (Float (Float.ceil (as_float v)))
@@ -104,6 +439,11 @@ let prim =
      | [a; b] -> k (Float (Float.copysign (as_float a) (as_float b)))
      | _ -> assert false)
   | "Float->Text" -> fun _ v k -> k (Text (Float.to_string (as_float v)))
+  | "float_of_text" -> fun _ v k ->
+    begin match float_of_text (as_text v) with
+      | Some f -> k (Opt (Float (Float.of_float f)))
+      | None -> k Null
+    end
   | "fmtFloat->Text" -> fun _ v k ->
     (match Value.as_tup v with
      | [f; prec; mode] ->
@@ -184,6 +524,34 @@ let prim =
   | "rts_heap_size" -> fun _ v k -> as_unit v; k (Int (Int.of_int 0))
   | "rts_total_allocation" -> fun _ v k -> as_unit v; k (Int (Int.of_int 0))
   | "rts_outstanding_callbacks" -> fun _ v k -> as_unit v; k (Int (Int.of_int 0))
+  | "rts_gc_force" -> fun _ v k -> as_unit v; k unit
+  | "rts_gc_defer" -> fun _ v k -> ignore (as_bool v); k unit
+  | "rts_set_gc_schedule" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [a; b; c] -> ignore (as_int a, as_int b, as_int c); k unit
+     | _ -> assert false)
+  | "rts_set_candid_limits" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [a; b; c] -> ignore (as_int a, as_int b, as_int c); k unit
+     | _ -> assert false)
+  | "rts_set_compaction_threshold" -> fun _ v k -> ignore (as_int v); k unit
+  | "rts_set_heap_limit" -> fun _ v k -> ignore (as_int v); k unit
+  | "rts_heap_limit" -> fun _ v k -> as_unit v; k (Int (Int.of_int 0))
+  | "rts_alloc_profile" -> fun _ v k -> as_unit v; k (Array [||])
+  | "rts_heap_checkpoint" -> fun _ v k -> as_unit v; k unit
+  | "rts_heap_diff" -> fun _ v k -> as_unit v; k (Array [||])
+  | "rts_heap_census" -> fun _ v k -> as_unit v; k (Array [||])
+  | "register_finalizer" -> fun _ v k ->
+    (* There is no GC, so finalizers never run *)
+    (match Value.as_tup v with
+     | [_; f] -> ignore (as_func f); k unit
+     | _ -> assert false)
+  (* There is no GC, so the referents stay alive. The weak reference is the referent itself. *)
+  | "weak_ref_new" -> fun _ v k -> k v
+  | "weak_ref_alive" -> fun _ v k -> k (Bool true)
+  | "weak_ref_get" -> fun _ v k -> k v
+  (* Nothing moves or is reclaimed *)
+  | "pin_object" | "unpin_object" -> fun _ v k -> k unit
   | "time" -> fun _ v k -> as_unit v; k (Value.Nat64 (Numerics.Nat64.of_int 42))
   | "idlHash" -> fun _ v k ->
     let s = as_text v in
@@ -191,18 +559,44 @@ let prim =
   | "crc32Hash" -> fun _ v k -> let s = as_blob v in
     let i = Optint.(to_int32 (Checkseum.Crc32.digest_string s 0 (String.length s) zero)) in
     k (Nat32 (Nat32.wrapping_of_big_int (Big_int.big_int_of_int32 i)))
+  | "blob_crc32c" | "blob_crc16" | "blob_crc8" as p -> fun _ v k ->
+    let s = match v with Blob s | Text s -> s | _ -> assert false in
+    let n = match p with
+      | "blob_crc32c" -> crc ~width:32 ~poly:0x82f6_3b78 ~init:0xffff_ffff ~reflected:true ~xor_out:0xffff_ffff s
+      | "blob_crc16" -> crc ~width:16 ~poly:0x1021 ~init:0xffff ~reflected:false ~xor_out:0 s
+      | _ -> crc ~width:8 ~poly:0x07 ~init:0 ~reflected:false ~xor_out:0 s in
+    k (match p with
+      | "blob_crc32c" -> Nat32 (Nat32.of_int n)
+      | "blob_crc16" -> Nat16 (Nat16.of_int n)
+      | _ -> Nat8 (Nat8.of_int n))
   | "array_len" -> fun _ v k ->
     k (Int (Int.of_int (Array.length (Value.as_array v))))
+  | "array_blit" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [dst; dst_off; src; src_off; len] ->
+       let dst = Value.as_array dst and src = Value.as_array src in
+       let dst_off = Int.to_int (as_int dst_off)
+       and src_off = Int.to_int (as_int src_off)
+       and len = Int.to_int (as_int len) in
+       if dst_off + len > Array.length dst || src_off + len > Array.length src
+       then raise (Invalid_argument "array_blit: Range out of bounds");
+       (* The ranges can overlap, so read all elements first *)
+       let values = Array.init len (fun i -> !(Value.as_mut src.(src_off + i))) in
+       Array.iteri (fun i x -> Value.as_mut dst.(dst_off + i) := x) values;
+       k unit
+     | _ -> assert false)
   | "blob_size" -> fun _ v k ->
     k (Int (Nat.of_int (String.length (Value.as_blob v))))
   | "blob_vals_iter" -> fun _ v k ->
     let s = String.to_seq (Value.as_blob v) in
     let valuation b = Nat8 (Nat8.of_int (Char.code b)) in
     k (Iter (ref (Seq.map valuation s)))
-  | "blob_iter_done" | "text_iter_done" -> fun _ v k ->
+  | "blob_iter_done" | "text_iter_done" | "text_split_done"
+  | "text_iter_graphemes_done" -> fun _ v k ->
     let i = Value.as_iter v in
     k (Bool (!i () = Seq.Nil))
-  | "blob_iter_next" | "text_iter_next" -> fun _ v k ->
+  | "blob_iter_next" | "text_iter_next" | "text_split_next"
+  | "text_iter_graphemes_next" -> fun _ v k ->
     let i = Value.as_iter v in
     begin match !i () with
     | Seq.Nil -> assert false
@@ -210,10 +604,82 @@ let prim =
     end
   | "text_len" -> fun _ v k ->
     k (Int (Nat.of_int (List.length (Wasm.Utf8.decode (Value.as_text v)))))
+  | "text_slice" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [t; start; len] ->
+       let s = as_text t in
+       let start = Int.to_int (as_int start) and len = Int.to_int (as_int len) in
+       let boundary i = i = String.length s || Char.code s.[i] land 0xc0 <> 0x80 in
+       if start > String.length s || len > String.length s - start
+       then raise (Invalid_argument "text_slice: out of bounds");
+       if not (boundary start && boundary (start + len))
+       then raise (Invalid_argument "text_slice: not at a character boundary");
+       k (Text (String.sub s start len))
+     | _ -> assert false)
+  | "text_find" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [h; n] ->
+       let h = as_text h and n = as_text n in
+       let rec find i =
+         if i + String.length n > String.length h then Null
+         else if String.sub h i (String.length n) = n then Opt (Int (Nat.of_int i))
+         else find (i + 1) in
+       k (find 0)
+     | _ -> assert false)
+  | "text_to_upper" | "text_to_lower" as name -> fun _ v k ->
+    let map = if name = "text_to_upper" then Uucp.Case.Map.to_upper else Uucp.Case.Map.to_lower in
+    let case c =
+      match map (Uchar.of_int c) with
+      | `Uchars cs -> List.map Uchar.to_int cs
+      | `Self -> [c] in
+    let s = Wasm.Utf8.decode (as_text v) in
+    k (Text (Wasm.Utf8.encode (List.concat (List.map case s))))
+  | "text_intern" -> fun _ v k -> k v
+  | "text_normalize_nfc" -> fun _ v k ->
+    k (Text (Uunf_string.normalize_utf_8 `NFC (as_text v)))
+  | "text_to_utf16_blob" -> fun _ v k ->
+    let b = Buffer.create 16 in
+    let unit u = Buffer.add_char b (Char.chr (u land 0xff)); Buffer.add_char b (Char.chr (u lsr 8)) in
+    List.iter (fun c ->
+      if c < 0x10000 then unit c
+      else (unit (0xd800 lor ((c - 0x10000) lsr 10)); unit (0xdc00 lor ((c - 0x10000) land 0x3ff)))
+    ) (Wasm.Utf8.decode (as_text v));
+    k (Blob (Buffer.contents b))
+  | "text_of_utf16_blob" -> fun _ v k ->
+    let s = as_blob v in
+    let unit i = Char.code s.[i] lor (Char.code s.[i + 1] lsl 8) in
+    let rec decode i acc =
+      if i = String.length s then Some (List.rev acc)
+      else if i + 2 > String.length s then None
+      else
+        let u = unit i in
+        if u >= 0xd800 && u < 0xdc00 && i + 4 <= String.length s
+           && unit (i + 2) >= 0xdc00 && unit (i + 2) < 0xe000
+        then decode (i + 4) (0x10000 + ((u - 0xd800) lsl 10) + (unit (i + 2) - 0xdc00) :: acc)
+        else if u >= 0xd800 && u < 0xe000 then None
+        else decode (i + 2) (u :: acc) in
+    k (match decode 0 [] with
+       | Some cs -> Opt (Text (Wasm.Utf8.encode cs))
+       | None -> Null)
   | "text_iter" -> fun _ v k ->
     let s = Wasm.Utf8.decode (Value.as_text v) in
     let i = Seq.map (fun c -> Char c) (List.to_seq s) in
     k (Iter (ref i))
+  | "text_split" -> fun _ v k ->
+    (match Value.as_tup v with
+     | [t; d] ->
+       let t = as_text t and d = as_text d in
+       let n = String.length t and m = String.length d in
+       let rec parts start i =
+         if m = 0 || i + m > n then [String.sub t start (n - start)]
+         else if String.sub t i m = d then String.sub t start (i - start) :: parts (i + m) (i + m)
+         else parts start (i + 1) in
+       k (Iter (ref (Seq.map (fun s -> Text s) (List.to_seq (parts 0 0)))))
+     | _ -> assert false)
+  | "text_iter_graphemes" -> fun _ v k ->
+    let add gs g = Text g :: gs in
+    let gs = Uuseg_string.fold_utf_8 `Grapheme_cluster add [] (as_text v) in
+    k (Iter (ref (List.to_seq (List.rev gs))))
   | "Array.init" -> fun _ v k ->
     (match Value.as_tup v with
     | [len; x] ->
@@ -269,24 +735,32 @@ let prim =
     | _ -> assert false
     end
 
+  (* The RTS maps characters with the simple case mappings, which Uucp does
+     not have. Where the full mapping has several characters, they are the
+     title case (for uppercase), the lowercase (for folding), or the
+     character itself, as in `rts/gen-unicode-tables.py` *)
   | "char_to_upper" ->
       fun _ v k ->
-        begin match Uucp.Case.Map.to_upper (Uchar.of_int (as_char v)) with
-        | `Uchars [c] -> k (Char (Uchar.to_int c))
-        | `Uchars _ ->
-            (* RTS implementation of to_upper returns the input for characters
-               that map to multiple characters in uppercase versions, so to be
-               in sync with that we do the same here *)
-            k v
-        | `Self -> k v
+        let u = Uchar.of_int (as_char v) in
+        begin match Uucp.Case.Map.to_upper u, Uucp.Case.Map.to_title u with
+        | `Uchars [c], _ | `Uchars _, `Uchars [c] -> k (Char (Uchar.to_int c))
+        | _ -> k v
         end
 
   | "char_to_lower" ->
       fun _ v k ->
+        if as_char v = 0x130 then k (Char 0x69) (* 'İ' to 'i' *) else
         begin match Uucp.Case.Map.to_lower (Uchar.of_int (as_char v)) with
         | `Uchars [c] -> k (Char (Uchar.to_int c))
-        | `Uchars _ -> k v (* same as above, in char_to_upper *)
-        | `Self -> k v
+        | _ -> k v
+        end
+
+  | "char_fold_case" ->
+      fun _ v k ->
+        let u = Uchar.of_int (as_char v) in
+        begin match Uucp.Case.Fold_map.fold u, Uucp.Case.Map.to_lower u with
+        | `Uchars [c], _ | `Uchars _, `Uchars [c] -> k (Char (Uchar.to_int c))
+        | _ -> k v
         end
 
   | "char_is_whitespace" ->
@@ -301,6 +775,25 @@ let prim =
   | "char_is_alphabetic" ->
       fun _ v k -> k (Bool (Uucp.Alpha.is_alphabetic (Uchar.of_int (as_char v))))
 
+  | "char_is_numeric" ->
+      fun _ v k ->
+        begin match Uucp.Gc.general_category (Uchar.of_int (as_char v)) with
+        | `Nd | `Nl | `No -> k (Bool true)
+        | _ -> k (Bool false)
+        end
+
+  | "char_general_category" ->
+      fun _ v k ->
+        (* Numbered as in the RTS, see rts/motoko-rts/src/char.rs *)
+        let categories = [
+          `Lu; `Ll; `Lt; `Lm; `Lo; `Mn; `Mc; `Me; `Nd; `Nl; `No; `Pc; `Pd; `Ps; `Pe;
+          `Pi; `Pf; `Po; `Sm; `Sc; `Sk; `So; `Zs; `Zl; `Zp; `Cc; `Cf; `Cs; `Co; `Cn] in
+        let gc = Uucp.Gc.general_category (Uchar.of_int (as_char v)) in
+        let rec index i = function
+          | c :: cs -> if c = gc then i else index (i + 1) cs
+          | [] -> assert false in
+        k (Nat8 (Nat8.of_int (index 0 categories)))
+
   | "decodeUtf8" ->
       fun _ v k ->
         let s = as_blob v in
@@ -309,7 +802,106 @@ let prim =
           | exception Wasm.Utf8.Utf8 -> k Null
         end
 
+  | "text_of_blob_lossy" ->
+      fun _ v k -> k (Text (Wasm.Utf8.encode (utf8_decode_lossy (as_blob v))))
+
   | "encodeUtf8" ->
       fun _ v k -> k (Blob (as_text v))
 
+  | "text_repeat" ->
+      fun _ v k ->
+        (match Value.as_tup v with
+         | [t; n] ->
+           let n = Int.to_int (as_int n) in
+           k (Text (String.concat "" (List.init n (fun _ -> as_text t))))
+         | _ -> assert false)
+
+  | "blob_fill" ->
+      fun _ v k ->
+        (match Value.as_tup v with
+         | [b; n] ->
+           let b = Char.chr (Nat8.to_int (as_nat8 b)) in
+           k (Blob (String.make (Int.to_int (as_int n)) b))
+         | _ -> assert false)
+
+  | "blob_concat_many" ->
+      fun _ v k -> k (Blob (String.concat "" (Array.to_list (Array.map as_blob (Value.as_array v)))))
+
+  | "blob_copy_range" ->
+      fun _ v k ->
+        (match Value.as_tup v with
+         | [dst; dst_off; src; src_off; len] ->
+           let dst = as_blob dst and src = as_blob src in
+           let dst_off = Int.to_int (as_int dst_off)
+           and src_off = Int.to_int (as_int src_off)
+           and len = Int.to_int (as_int len) in
+           if dst_off + len > String.length dst || src_off + len > String.length src
+           then raise (Invalid_argument "blob_copy_range: Range out of bounds")
+           else
+             let b = Bytes.of_string dst in
+             Bytes.blit_string src src_off b dst_off len;
+             k (Blob (Bytes.to_string b))
+         | _ -> assert false)
+
+  | "blob_compare_const_time" ->
+      fun _ v k ->
+        (match Value.as_tup v with
+         | [b1; b2] -> k (Bool (String.equal (as_blob b1) (as_blob b2)))
+         | _ -> assert false)
+
+  | "blob_sha256" ->
+      fun _ v k ->
+        let s = match v with Blob s | Text s -> s | _ -> assert false in
+        k (Blob Digestif.SHA256.(to_raw_string (digest_string s)))
+
+  | "blob_sha224" ->
+      fun _ v k -> k (Blob Digestif.SHA224.(to_raw_string (digest_string (as_blob v))))
+
+  | "base64_encode" -> fun _ v k -> k (Text (base64_encode false true (as_blob v)))
+  | "base64_encode_url" -> fun _ v k -> k (Text (base64_encode true false (as_blob v)))
+  | "base64_decode" | "base64_decode_url" as p ->
+      fun _ v k ->
+        begin match base64_decode (p = "base64_decode_url") (as_text v) with
+          | Some b -> k (Opt (Blob b))
+          | None -> k Null
+        end
+
+  | "blob_to_hex" -> fun _ v k -> k (Text (blob_to_hex (as_blob v)))
+  | "principal_of_text_checked" ->
+      fun _ v k ->
+        begin match Ic.Url.decode_principal (as_text v) with
+          | Ok bytes when String.length bytes <= 29 -> k (Opt (Blob bytes))
+          | _ -> k Null
+        end
+  | "principal_of_public_key" ->
+      fun _ v k ->
+        k (Blob (Digestif.SHA224.(to_raw_string (digest_string (as_blob v))) ^ "\x02"))
+  | "principal_compare" ->
+      fun _ v k ->
+        (match Value.as_tup v with
+         | [p1; p2] ->
+           let c = String.compare (as_blob p1) (as_blob p2) in
+           k (Int8 (Int_8.of_int (if c < 0 then -1 else if c > 0 then 1 else 0)))
+         | _ -> assert false)
+  | "account_identifier" ->
+      fun _ v k ->
+        (match Value.as_tup v with
+         | [p; s] ->
+           let s = as_blob s in
+           if String.length s <> 32
+           then raise (Invalid_argument "account_identifier: Subaccount must be 32 bytes");
+           let hash = Digestif.SHA224.(to_raw_string
+             (digest_string ("\x0Aaccount-id" ^ as_blob p ^ s))) in
+           let buf = Buffer.create 32 in
+           Buffer.add_int32_be buf (Lib.CRC.crc32 hash);
+           Buffer.add_string buf hash;
+           k (Blob (Buffer.contents buf))
+         | _ -> assert false)
+  | "blob_of_hex" ->
+      fun _ v k ->
+        begin match blob_of_hex (as_text v) with
+          | Some b -> k (Opt (Blob b))
+          | None -> k Null
+        end
+
   | s -> raise (Invalid_argument ("Value.prim: " ^ s))